@@ -0,0 +1,3 @@
+DROP TABLE "table_row_history";
+
+ALTER TABLE "table_schema" DROP COLUMN "audit_rows";
@@ -0,0 +1,16 @@
+-- Per-row change history for the tables with `auditRows` enabled
+
+ALTER TABLE "table_schema" ADD COLUMN "audit_rows" BOOLEAN NOT NULL DEFAULT FALSE;
+
+CREATE TABLE "table_row_history" (
+    "row_history_id"          BIGSERIAL PRIMARY KEY,
+    "entity_id"               BIGINT REFERENCES "entity" NOT NULL,
+    "row_key"                 JSONB NOT NULL,
+    "change"                  VARCHAR NOT NULL, -- insert, update or delete
+    "old_data"                JSON, -- NULL for inserts, or updates of rows changed before the auditing started
+    "new_data"                JSON, -- NULL for deletes
+    "made_at"                 TIMESTAMP NOT NULL DEFAULT NOW(),
+    "made_by"                 BIGINT REFERENCES "user"
+);
+
+CREATE INDEX "table_row_history_row_key_idx" ON "table_row_history" ("entity_id", "row_key");
@@ -0,0 +1,3 @@
+-- Views are created in the domain's database from their SELECT statement
+
+ALTER TABLE "view" ADD COLUMN "statement" VARCHAR NOT NULL DEFAULT '';
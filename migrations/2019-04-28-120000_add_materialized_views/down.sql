@@ -0,0 +1 @@
+ALTER TABLE "view" DROP COLUMN "materialized";
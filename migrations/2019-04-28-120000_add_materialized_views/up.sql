@@ -0,0 +1,3 @@
+-- Materialized views keep the result of their statement until they are refreshed
+
+ALTER TABLE "view" ADD COLUMN "materialized" BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,13 @@
+-- Runs of the queries with a schedule, see `ListScheduledRuns`
+
+CREATE TABLE "query_scheduled_run" (
+    "scheduled_run_id"        BIGSERIAL PRIMARY KEY,
+    "entity_id"               BIGINT REFERENCES "entity" NOT NULL,
+    "started_at"              TIMESTAMP NOT NULL,
+    "duration_ms"             BIGINT NOT NULL,
+    "succeeded"               BOOLEAN NOT NULL,
+    "row_count"               BIGINT, -- NULL if the run failed
+    "error"                   VARCHAR -- NULL if the run succeeded
+);
+
+CREATE INDEX "query_scheduled_run_started_at_idx" ON "query_scheduled_run" ("entity_id", "started_at");
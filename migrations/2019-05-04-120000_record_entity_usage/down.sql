@@ -0,0 +1,4 @@
+DROP INDEX "entity_usage_used_at_idx";
+
+DELETE FROM "entity_usage" WHERE "used_by" IS NULL;
+ALTER TABLE "entity_usage" ALTER COLUMN "used_by" SET NOT NULL;
@@ -0,0 +1,6 @@
+-- Every use of a table, query or script, see `GetEntityUsage`
+-- the jobs the server runs by itself don't have a user
+
+ALTER TABLE "entity_usage" ALTER COLUMN "used_by" DROP NOT NULL;
+
+CREATE INDEX "entity_usage_used_at_idx" ON "entity_usage" ("entity_id", "used_at");
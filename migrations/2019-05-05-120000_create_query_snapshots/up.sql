@@ -0,0 +1,14 @@
+-- Frozen copies of query results, see `SnapshotQueryResult`
+
+CREATE TABLE "query_snapshot" (
+    "query_snapshot_id"       BIGSERIAL PRIMARY KEY,
+    "entity_id"               BIGINT REFERENCES "entity" NOT NULL,
+    "name"                    VARCHAR NOT NULL,
+    "params"                  JSON NOT NULL,
+    "data"                    JSON NOT NULL,
+    "row_count"               BIGINT NOT NULL,
+    "truncated"               BOOLEAN NOT NULL,
+    "created_at"              TIMESTAMP NOT NULL DEFAULT NOW(),
+    "created_by"              BIGINT REFERENCES "user", -- NULL if taken by a scheduled job
+    UNIQUE ("entity_id", "name")
+);
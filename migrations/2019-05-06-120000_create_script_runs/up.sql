@@ -0,0 +1,15 @@
+-- Runs of the scripts with their output, see `GetScriptRuns`
+
+CREATE TABLE "script_run" (
+    "script_run_id"           BIGSERIAL PRIMARY KEY,
+    "entity_id"               BIGINT REFERENCES "entity" NOT NULL,
+    "started_at"              TIMESTAMP NOT NULL,
+    "duration_ms"             BIGINT NOT NULL,
+    "successful"              BOOLEAN NOT NULL,
+    "exit_code"               INTEGER, -- NULL if the script was killed by a signal
+    "stdout"                  VARCHAR NOT NULL,
+    "stderr"                  VARCHAR NOT NULL,
+    "run_by"                  BIGINT REFERENCES "user" -- NULL if run by a scheduled job
+);
+
+CREATE INDEX "script_run_started_at_idx" ON "script_run" ("entity_id", "started_at");
@@ -0,0 +1 @@
+ALTER TABLE "script_run" DROP COLUMN "scheduled";
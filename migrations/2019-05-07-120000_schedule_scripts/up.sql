@@ -0,0 +1,3 @@
+-- The runs started by the scheduler, see `RunScheduledScripts`
+
+ALTER TABLE "script_run" ADD COLUMN "scheduled" BOOLEAN NOT NULL DEFAULT FALSE;
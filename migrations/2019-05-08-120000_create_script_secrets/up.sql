@@ -0,0 +1,12 @@
+-- Secrets passed to the scripts as environment variables, encrypted with the secrets key of the server
+
+CREATE TABLE "script_secret" (
+    "script_secret_id"        BIGSERIAL PRIMARY KEY,
+    "entity_id"               BIGINT REFERENCES "entity" NOT NULL,
+    "name"                    VARCHAR NOT NULL,
+    "nonce"                   BYTEA NOT NULL,
+    "ciphertext"              BYTEA NOT NULL, -- with the authentication tag appended
+    "modified_at"             TIMESTAMP NOT NULL DEFAULT NOW(),
+    "modified_by"             BIGINT REFERENCES "user", -- NULL if set by the system
+    UNIQUE("entity_id", "name")
+);
@@ -0,0 +1,31 @@
+-- Outgoing webhooks, the messages published on the channels of a webhook are posted to its url
+
+CREATE TABLE "webhook" (
+    "webhook_id"              BIGSERIAL PRIMARY KEY,
+    "entity_id"               BIGINT REFERENCES "entity" NOT NULL,
+    "name"                    VARCHAR NOT NULL,
+    "description"             VARCHAR NOT NULL DEFAULT '',
+    "url"                     VARCHAR NOT NULL,
+    "webhook_info"            JSON NOT NULL DEFAULT '{}', -- the secret, the channels and the retry policy
+    "is_deleted"              BOOLEAN NOT NULL DEFAULT FALSE,
+    "modified_at"             TIMESTAMP NOT NULL DEFAULT NOW(),
+    "modified_by"             BIGINT REFERENCES "user" NOT NULL
+);
+
+CREATE TABLE "webhook_delivery" (
+    "webhook_delivery_id"     BIGSERIAL PRIMARY KEY,
+    "entity_id"               BIGINT REFERENCES "entity" NOT NULL,
+    "channel"                 JSONB NOT NULL,
+    "action"                  VARCHAR NOT NULL,
+    "payload"                 JSONB NOT NULL,
+    "status"                  VARCHAR NOT NULL DEFAULT 'pending', -- pending, delivered or failed
+    "attempts"                INTEGER NOT NULL DEFAULT 0,
+    "last_status_code"        INTEGER,
+    "last_error"              VARCHAR,
+    "created_at"              TIMESTAMP NOT NULL DEFAULT NOW(),
+    "next_attempt_at"         TIMESTAMP NOT NULL DEFAULT NOW(),
+    "delivered_at"            TIMESTAMP
+);
+
+CREATE INDEX "webhook_delivery_pending_idx" ON "webhook_delivery" ("next_attempt_at") WHERE "status" = 'pending';
+CREATE INDEX "webhook_delivery_entity_idx" ON "webhook_delivery" ("entity_id", "created_at");
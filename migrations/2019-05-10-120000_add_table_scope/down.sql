@@ -0,0 +1 @@
+ALTER TABLE "table_schema" DROP COLUMN "scope";
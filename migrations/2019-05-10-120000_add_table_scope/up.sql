@@ -0,0 +1,2 @@
+-- The tables of a scope are in the postgres schema with its name, NULL is the main scope
+ALTER TABLE "table_schema" ADD COLUMN "scope" VARCHAR;
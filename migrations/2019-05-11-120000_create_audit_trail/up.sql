@@ -0,0 +1,19 @@
+-- Every mutating procedure call, who made it, on what, and how it went, see `WithAudit`
+
+CREATE TABLE "audit_entry" (
+    "audit_entry_id"          BIGSERIAL PRIMARY KEY,
+    "username"                VARCHAR, -- NULL for the calls without a login
+    "procedure"               VARCHAR NOT NULL,
+    "entity_type"             VARCHAR,
+    "entity_name"             VARCHAR,
+    "domain"                  VARCHAR,
+    "request_id"              VARCHAR,
+    "before"                  JSONB,
+    "after"                   JSONB,
+    "outcome"                 VARCHAR NOT NULL, -- succeeded or failed
+    "error"                   VARCHAR,
+    "created_at"              TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX "audit_entry_created_at_idx" ON "audit_entry" ("created_at");
+CREATE INDEX "audit_entry_entity_idx" ON "audit_entry" ("entity_type", "entity_name", "created_at");
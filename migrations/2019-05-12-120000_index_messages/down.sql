@@ -0,0 +1,2 @@
+DROP INDEX "message_channel_sent_at_idx";
+DROP INDEX "message_sent_at_idx";
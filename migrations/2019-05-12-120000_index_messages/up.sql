@@ -0,0 +1,4 @@
+-- The messages past their retention are looked up by age, and by age within their channel, see `PurgeMessages`
+
+CREATE INDEX "message_sent_at_idx" ON "message" ("sent_at");
+CREATE INDEX "message_channel_sent_at_idx" ON "message" ("channel_id", "sent_at");
@@ -0,0 +1,3 @@
+DROP TABLE "pending_message";
+
+ALTER TABLE "user_channel" DROP COLUMN "durable";
@@ -0,0 +1,12 @@
+-- The durable subscriptions outlive the sessions, their messages are kept until the client acknowledges them
+
+ALTER TABLE "user_channel" ADD COLUMN "durable" BOOLEAN NOT NULL DEFAULT FALSE;
+
+CREATE TABLE "pending_message" (
+    "user_channel_id"         BIGINT NOT NULL REFERENCES "user_channel" ON DELETE CASCADE,
+    "message_id"              BIGINT NOT NULL REFERENCES "message" ON DELETE CASCADE,
+    "delivered_at"            TIMESTAMP, -- NULL until it's sent to a session, it's sent again on the next login
+    PRIMARY KEY ("user_channel_id", "message_id")
+);
+
+CREATE INDEX "pending_message_message_idx" ON "pending_message" ("message_id");
@@ -0,0 +1,7 @@
+DELETE FROM "webhook_delivery" WHERE "channel_webhook_id" IS NOT NULL;
+
+ALTER TABLE "webhook_delivery" DROP CONSTRAINT "webhook_delivery_target_check";
+ALTER TABLE "webhook_delivery" DROP COLUMN "channel_webhook_id";
+ALTER TABLE "webhook_delivery" ALTER COLUMN "entity_id" SET NOT NULL;
+
+DROP TABLE "channel_webhook";
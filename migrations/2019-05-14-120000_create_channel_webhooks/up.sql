@@ -0,0 +1,20 @@
+-- Webhooks attached to a channel by an admin, every message published on it is posted to the url.
+-- Their deliveries are queued with the ones of the webhook entities, without an entity
+
+CREATE TABLE "channel_webhook" (
+    "channel_webhook_id"      BIGSERIAL PRIMARY KEY,
+    "channel_id"              BIGINT NOT NULL REFERENCES "channel" ON DELETE CASCADE,
+    "url"                     VARCHAR NOT NULL,
+    "secret"                  VARCHAR NOT NULL,
+    "retry"                   JSONB NOT NULL DEFAULT '{}',
+    "created_at"              TIMESTAMP NOT NULL DEFAULT NOW(),
+    "created_by"              BIGINT REFERENCES "user",
+    UNIQUE ("channel_id", "url")
+);
+
+ALTER TABLE "webhook_delivery" ALTER COLUMN "entity_id" DROP NOT NULL;
+ALTER TABLE "webhook_delivery" ADD COLUMN "channel_webhook_id" BIGINT REFERENCES "channel_webhook" ON DELETE CASCADE;
+ALTER TABLE "webhook_delivery" ADD CONSTRAINT "webhook_delivery_target_check"
+    CHECK (("entity_id" IS NULL) <> ("channel_webhook_id" IS NULL));
+
+CREATE INDEX "webhook_delivery_channel_webhook_idx" ON "webhook_delivery" ("channel_webhook_id", "created_at");
@@ -0,0 +1,3 @@
+DROP TABLE "dead_letter";
+
+ALTER TABLE "pending_message" DROP COLUMN "attempts";
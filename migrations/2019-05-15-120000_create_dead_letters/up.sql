@@ -0,0 +1,16 @@
+-- The messages that couldn't be delivered, kept until an admin redelivers them
+
+ALTER TABLE "pending_message" ADD COLUMN "attempts" INTEGER NOT NULL DEFAULT 0;
+
+CREATE TABLE "dead_letter" (
+    "dead_letter_id"          BIGSERIAL PRIMARY KEY,
+    "channel"                 JSONB NOT NULL,
+    "payload"                 JSONB NOT NULL,
+    "attempts"                INTEGER NOT NULL,
+    "last_error"              VARCHAR,
+    "webhook_delivery_id"     BIGINT UNIQUE REFERENCES "webhook_delivery" ON DELETE CASCADE, -- a failed webhook delivery
+    "user_channel_id"         BIGINT REFERENCES "user_channel" ON DELETE CASCADE, -- or a message a durable subscriber never acknowledged
+    "message_id"              BIGINT REFERENCES "message" ON DELETE CASCADE,
+    "created_at"              TIMESTAMP NOT NULL DEFAULT NOW(),
+    CHECK (("webhook_delivery_id" IS NULL) = ("user_channel_id" IS NOT NULL AND "message_id" IS NOT NULL))
+);
@@ -0,0 +1,4 @@
+DROP INDEX "user_channel_expires_at_idx";
+
+ALTER TABLE "user_channel" DROP COLUMN "expires_at";
+ALTER TABLE "user" DROP COLUMN "last_seen_at";
@@ -0,0 +1,6 @@
+-- The subscriptions can expire, and the ones of the users that haven't logged in for a while are removed
+
+ALTER TABLE "user" ADD COLUMN "last_seen_at" TIMESTAMP; -- the last login or token refresh
+ALTER TABLE "user_channel" ADD COLUMN "expires_at" TIMESTAMP; -- NULL for the subscriptions that don't expire
+
+CREATE INDEX "user_channel_expires_at_idx" ON "user_channel" ("expires_at") WHERE "expires_at" IS NOT NULL;
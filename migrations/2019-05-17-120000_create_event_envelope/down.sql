@@ -0,0 +1,6 @@
+ALTER TABLE "message" DROP COLUMN "schema_version";
+ALTER TABLE "message" DROP COLUMN "actor";
+ALTER TABLE "message" DROP COLUMN "action";
+ALTER TABLE "message" DROP COLUMN "sequence";
+
+ALTER TABLE "channel" DROP COLUMN "last_sequence";
@@ -0,0 +1,24 @@
+-- The messages keep everything the event envelope needs, see `data::event::Event`
+
+ALTER TABLE "channel" ADD COLUMN "last_sequence" BIGINT NOT NULL DEFAULT 0;
+
+ALTER TABLE "message" ADD COLUMN "sequence" BIGINT NOT NULL DEFAULT 0; -- increases by one with each message of the channel
+ALTER TABLE "message" ADD COLUMN "action" VARCHAR NOT NULL DEFAULT '';
+ALTER TABLE "message" ADD COLUMN "actor" VARCHAR; -- the username of the caller, NULL for the messages published before this
+ALTER TABLE "message" ADD COLUMN "schema_version" INTEGER NOT NULL DEFAULT 1;
+
+-- the messages that are left are numbered in the order they were published
+UPDATE "message" SET "sequence" = "numbered"."sequence"
+FROM (
+    SELECT "message_id", ROW_NUMBER() OVER (PARTITION BY "channel_id" ORDER BY "message_id") AS "sequence"
+    FROM "message"
+) AS "numbered"
+WHERE "message"."message_id" = "numbered"."message_id";
+
+UPDATE "channel" SET "last_sequence" = "numbered"."last_sequence"
+FROM (
+    SELECT "channel_id", MAX("sequence") AS "last_sequence"
+    FROM "message"
+    GROUP BY "channel_id"
+) AS "numbered"
+WHERE "channel"."channel_id" = "numbered"."channel_id";
@@ -0,0 +1,7 @@
+-- the encrypted secrets can't be decrypted here, those webhooks have to be added again
+DELETE FROM "channel_webhook" WHERE "secret" IS NULL;
+
+ALTER TABLE "channel_webhook" DROP CONSTRAINT "channel_webhook_secret_check";
+ALTER TABLE "channel_webhook" DROP COLUMN "secret_ciphertext";
+ALTER TABLE "channel_webhook" DROP COLUMN "secret_nonce";
+ALTER TABLE "channel_webhook" ALTER COLUMN "secret" SET NOT NULL;
@@ -0,0 +1,8 @@
+-- The secrets of the channel webhooks are encrypted with the secrets key of the server, like the script secrets.
+-- The ones added before are still in "secret" until the webhook is added again
+
+ALTER TABLE "channel_webhook" ALTER COLUMN "secret" DROP NOT NULL;
+ALTER TABLE "channel_webhook" ADD COLUMN "secret_nonce" BYTEA;
+ALTER TABLE "channel_webhook" ADD COLUMN "secret_ciphertext" BYTEA; -- with the authentication tag appended
+ALTER TABLE "channel_webhook" ADD CONSTRAINT "channel_webhook_secret_check"
+    CHECK (("secret" IS NULL) <> ("secret_ciphertext" IS NULL));
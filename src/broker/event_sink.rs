@@ -0,0 +1,157 @@
+use std::sync::Mutex;
+use std::sync::mpsc;
+#[cfg(feature = "kafka-sink")]
+use std::thread;
+use std::time::Duration;
+
+use serde_json;
+
+use data::event::Event;
+
+#[cfg(feature = "kafka-sink")]
+use kafka::producer::Producer;
+#[cfg(feature = "kafka-sink")]
+use kafka::producer::Record;
+#[cfg(feature = "kafka-sink")]
+use kafka::producer::RequiredAcks;
+
+/// the events waiting for the producer, the newer ones are dropped when kafka can't keep up
+#[cfg(feature = "kafka-sink")]
+const MAX_PENDING_EVENTS: usize = 10_000;
+
+/// Where the published messages are mirrored, e.g. `KafkaSinkConfig::new(&["localhost:9092"], "kakapo-events")`
+#[derive(Debug, Clone)]
+pub struct KafkaSinkConfig {
+    pub brokers: Vec<String>,
+    pub topic: String,
+    /// how long the brokers have to acknowledge a message
+    pub ack_timeout: Duration,
+}
+
+impl KafkaSinkConfig {
+    pub fn new(brokers: &[&str], topic: &str) -> Self {
+        Self {
+            brokers: brokers.iter().map(|x| x.to_string()).collect(),
+            topic: topic.to_string(),
+            ack_timeout: Duration::from_secs(1),
+        }
+    }
+
+    pub fn ack_timeout(mut self, ack_timeout: u64) -> Self {
+        self.ack_timeout = Duration::from_millis(ack_timeout);
+        self
+    }
+}
+
+/// A published event as it is sent to kafka, keyed by its channel so that the events of an entity stay ordered.
+/// The value is the event with the domain it was published in
+#[derive(Debug, Clone, PartialEq)]
+pub struct SinkEvent {
+    pub key: String,
+    pub value: String,
+}
+
+impl SinkEvent {
+    pub fn new(event: &Event, domain_name: &Option<String>) -> Result<Self, serde_json::Error> {
+        let key = serde_json::to_string(&event.channel)?;
+        let mut value = serde_json::to_value(event)?;
+        value["domain"] = json!(domain_name);
+        let value = serde_json::to_string(&value)?;
+
+        Ok(Self { key, value })
+    }
+}
+
+/// Mirrors every published message into a kafka topic, for the pipelines that shouldn't poll the messages.
+/// The messages are produced by a thread of its own so that the actions don't wait on kafka, the ones
+/// that can't be sent are logged and dropped, the messages table stays the record of what was published
+#[derive(Debug)]
+pub struct KafkaSink {
+    sender: Mutex<mpsc::SyncSender<SinkEvent>>,
+}
+
+impl KafkaSink {
+    /// connects to the brokers, an unreachable cluster is reported when the server starts
+    #[cfg(feature = "kafka-sink")]
+    pub fn start(config: &KafkaSinkConfig) -> Result<Self, String> {
+        let mut producer = Producer::from_hosts(config.brokers.to_owned())
+            .with_ack_timeout(config.ack_timeout)
+            .with_required_acks(RequiredAcks::One)
+            .create()
+            .map_err(|err| format!("Could not connect to kafka at {:?}: {}", &config.brokers, err))?;
+
+        let topic = config.topic.to_owned();
+        let (sender, receiver) = mpsc::sync_channel::<SinkEvent>(MAX_PENDING_EVENTS);
+        thread::spawn(move || {
+            for event in receiver {
+                let record = Record::from_key_value(&topic, event.key.as_bytes(), event.value.as_bytes());
+                if let Err(err) = producer.send(&record) {
+                    error!("Could not send the event of {} to kafka: {}", &event.key, err);
+                }
+            }
+        });
+        info!("Mirroring the published messages into the kafka topic {}", &config.topic);
+
+        Ok(Self { sender: Mutex::new(sender) })
+    }
+
+    #[cfg(not(feature = "kafka-sink"))]
+    pub fn start(_config: &KafkaSinkConfig) -> Result<Self, String> {
+        Err("kakapo was built without the `kafka-sink` feature".to_string())
+    }
+
+    pub fn send(&self, event: &Event, domain_name: &Option<String>) {
+        let channel = &event.channel;
+        let event = match SinkEvent::new(event, domain_name) {
+            Ok(event) => event,
+            Err(err) => {
+                error!("Could not serialize the event of {:?}: {}", channel, err);
+                return;
+            },
+        };
+
+        let sent = self.sender
+            .lock()
+            .map_err(|_| "the sink is poisoned".to_string())
+            .and_then(|sender| sender.try_send(event).map_err(|err| err.to_string()));
+        if let Err(err) = sent {
+            warn!("Dropped the kafka event of {:?}: {}", channel, err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use serde_json::Value;
+
+    use data::channels::Channels;
+    use data::event::EVENT_SCHEMA_VERSION;
+
+    #[test]
+    fn test_sink_event() {
+        let channel = Channels::user("bob");
+        let event = Event {
+            id: 7,
+            sequence: 2,
+            channel: channel.to_owned(),
+            action: "updateUser".to_string(),
+            actor: Some("admin".to_string()),
+            timestamp: chrono::Utc::now().naive_utc(),
+            schema_version: EVENT_SCHEMA_VERSION,
+            data: json!({ "name": "bob" }),
+        };
+        let sink_event = SinkEvent::new(&event, &Some("app".to_string())).unwrap();
+        assert_eq!(sink_event.key, serde_json::to_string(&channel).unwrap());
+
+        let value: Value = serde_json::from_str(&sink_event.value).unwrap();
+        assert_eq!(value["domain"], json!("app"));
+        assert_eq!(value["action"], json!("updateUser"));
+        assert_eq!(value["actor"], json!("admin"));
+        assert_eq!(value["sequence"], json!(2));
+        assert_eq!(value["schemaVersion"], json!(EVENT_SCHEMA_VERSION));
+        assert_eq!(value["data"], json!({ "name": "bob" }));
+        assert_eq!(value["channel"], serde_json::to_value(&channel).unwrap());
+    }
+}
@@ -1,6 +1,7 @@
 
 use serde_json;
 use data::channels::Channels;
+use data::key_case::KeyCase;
 
 #[derive(Clone, Debug, Deserialize)]
 #[serde(tag = "action")]
@@ -16,5 +17,14 @@ pub enum WsInputData {
         params: serde_json::Value,
         data: serde_json::Value,
     },
+    #[serde(rename_all = "camelCase")]
+    SetKeyCase {
+        key_case: KeyCase,
+    },
+    /// the messages of the durable subscriptions that were received, they aren't sent again
+    #[serde(rename_all = "camelCase")]
+    Ack {
+        ids: Vec<i64>,
+    },
 
 }
\ No newline at end of file
@@ -0,0 +1,219 @@
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde_json;
+
+/// upper bounds of the histogram buckets in milliseconds, the last bucket catches everything else
+const BUCKET_BOUNDS: [u64; 9] = [1, 5, 10, 25, 50, 100, 250, 500, 1000];
+const MAX_SLOW_DELIVERIES: usize = 100;
+const DEFAULT_SLOW_THRESHOLD: u64 = 1000; // milliseconds
+
+/// Stages of a broadcast, from the action publishing to the websocket frame being queued
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Stage {
+    /// writing the message into the pub sub store
+    Publish,
+    /// time between the message being stored and a session picking it up
+    Fanout,
+    /// queueing the frame in the context of the session, the socket is written afterwards by actix
+    /// so the write itself isn't part of it
+    Enqueue,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Histogram {
+    pub bucket_bounds: Vec<u64>,
+    pub buckets: Vec<u64>,
+    pub count: u64,
+    pub sum: u64,
+    pub max: u64,
+}
+
+impl Histogram {
+    pub fn new() -> Self {
+        Self {
+            bucket_bounds: BUCKET_BOUNDS.to_vec(),
+            buckets: vec![0; BUCKET_BOUNDS.len() + 1],
+            count: 0,
+            sum: 0,
+            max: 0,
+        }
+    }
+
+    pub fn observe(&mut self, millis: u64) {
+        let bucket = BUCKET_BOUNDS
+            .iter()
+            .position(|bound| millis <= *bound)
+            .unwrap_or(BUCKET_BOUNDS.len());
+
+        self.buckets[bucket] += 1;
+        self.count += 1;
+        self.sum += millis;
+        self.max = self.max.max(millis);
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SlowDelivery {
+    pub stage: Stage,
+    pub name: String,
+    pub latency: u64,
+    pub recorded_at: chrono::NaiveDateTime,
+}
+
+#[derive(Debug)]
+struct MetricsInner {
+    publish: Histogram,
+    fanout: Histogram,
+    enqueue: Histogram,
+    slow_deliveries: VecDeque<SlowDelivery>,
+    purges: Purges,
+}
+
+/// The messages removed past their retention, see `PurgeMessages`
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Purges {
+    pub runs: u64,
+    pub purged_messages: u64,
+    pub last_purged_at: Option<chrono::NaiveDateTime>,
+}
+
+/// Latency metrics for the broadcast pipeline, shared by the executors and the websocket sessions
+#[derive(Debug)]
+pub struct BroadcastMetrics {
+    slow_threshold: u64,
+    inner: Mutex<MetricsInner>,
+}
+
+impl Default for BroadcastMetrics {
+    fn default() -> Self {
+        Self::new(DEFAULT_SLOW_THRESHOLD)
+    }
+}
+
+impl BroadcastMetrics {
+    pub fn new(slow_threshold: u64) -> Self {
+        Self {
+            slow_threshold,
+            inner: Mutex::new(MetricsInner {
+                publish: Histogram::new(),
+                fanout: Histogram::new(),
+                enqueue: Histogram::new(),
+                slow_deliveries: VecDeque::new(),
+                purges: Purges::default(),
+            }),
+        }
+    }
+
+    pub fn record(&self, stage: Stage, name: &str, latency: Duration) {
+        let millis = latency.as_secs() * 1000 + u64::from(latency.subsec_millis());
+        let mut inner = match self.inner.lock() {
+            Ok(inner) => inner,
+            Err(err) => {
+                warn!("broadcast metrics lock was poisoned: {:?}", &err);
+                return;
+            }
+        };
+
+        match stage {
+            Stage::Publish => inner.publish.observe(millis),
+            Stage::Fanout => inner.fanout.observe(millis),
+            Stage::Enqueue => inner.enqueue.observe(millis),
+        };
+
+        if millis >= self.slow_threshold {
+            debug!("slow broadcast at {:?} stage for {:?}: {}ms", &stage, name, millis);
+            if inner.slow_deliveries.len() >= MAX_SLOW_DELIVERIES {
+                inner.slow_deliveries.pop_front();
+            }
+            inner.slow_deliveries.push_back(SlowDelivery {
+                stage,
+                name: name.to_owned(),
+                latency: millis,
+                recorded_at: chrono::Utc::now().naive_utc(),
+            });
+        }
+    }
+
+    /// the fanout stage is measured from the timestamp stored with the message
+    pub fn record_since(&self, stage: Stage, name: &str, since: chrono::NaiveDateTime) {
+        let elapsed = chrono::Utc::now().naive_utc() - since;
+        let latency = elapsed.to_std().unwrap_or_else(|_| Duration::from_millis(0));
+        self.record(stage, name, latency)
+    }
+
+    pub fn record_purge(&self, purged_messages: usize) {
+        let mut inner = match self.inner.lock() {
+            Ok(inner) => inner,
+            Err(err) => {
+                warn!("broadcast metrics lock was poisoned: {:?}", &err);
+                return;
+            }
+        };
+
+        inner.purges.runs += 1;
+        inner.purges.purged_messages += purged_messages as u64;
+        inner.purges.last_purged_at = Some(chrono::Utc::now().naive_utc());
+    }
+
+    pub fn snapshot(&self) -> serde_json::Value {
+        match self.inner.lock() {
+            Ok(inner) => json!({
+                "slowThreshold": self.slow_threshold,
+                "histograms": {
+                    "publish": inner.publish,
+                    "fanout": inner.fanout,
+                    "enqueue": inner.enqueue,
+                },
+                "slowDeliveries": inner.slow_deliveries,
+                "purges": inner.purges,
+            }),
+            Err(err) => {
+                warn!("broadcast metrics lock was poisoned: {:?}", &err);
+                json!({})
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_record_latency() {
+        let metrics = BroadcastMetrics::new(100);
+        metrics.record(Stage::Publish, "insertTableData", Duration::from_millis(3));
+        metrics.record(Stage::Publish, "insertTableData", Duration::from_millis(30));
+        metrics.record(Stage::Enqueue, "insertTableData", Duration::from_millis(2000));
+
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot["histograms"]["publish"]["count"], json!(2));
+        assert_eq!(snapshot["histograms"]["publish"]["buckets"][1], json!(1));
+        assert_eq!(snapshot["histograms"]["publish"]["buckets"][4], json!(1));
+        assert_eq!(snapshot["histograms"]["enqueue"]["buckets"][9], json!(1));
+        assert_eq!(snapshot["histograms"]["fanout"]["count"], json!(0));
+
+        let slow = snapshot["slowDeliveries"].as_array().unwrap();
+        assert_eq!(slow.len(), 1);
+        assert_eq!(slow[0]["stage"], json!("enqueue"));
+    }
+
+    #[test]
+    fn test_record_purge() {
+        let metrics = BroadcastMetrics::default();
+        metrics.record_purge(40);
+        metrics.record_purge(2);
+
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot["purges"]["runs"], json!(2));
+        assert_eq!(snapshot["purges"]["purgedMessages"], json!(42));
+        assert!(snapshot["purges"]["lastPurgedAt"].is_string());
+    }
+}
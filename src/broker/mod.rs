@@ -1,6 +1,8 @@
 
 mod input;
 mod routes;
+pub mod metrics;
+pub mod event_sink;
 
 use std::marker::PhantomData;
 use std::collections::HashSet;
@@ -11,6 +13,7 @@ use std::iter;
 use uuid::Uuid;
 
 use futures::Future;
+use futures::sync::mpsc;
 
 use actix_web::ws;
 use actix_web::HttpResponse;
@@ -30,18 +33,27 @@ use chrono;
 
 use AppStateLike;
 use view::action_wrapper::ActionWrapper;
+use view::action_wrapper::send_action;
+use view::request_id::new_request_id;
 use view::procedure::ProcedureBuilder;
 use view::error::Error::TooManyConnections;
 use view::bearer_token::to_bearer_token;
+use view::shutdown;
 
 use model::actions::Action;
 
 use data::claims::AuthClaims;
 use data::channels::Channels;
+use data::key_case::KeyCase;
+
+use scripting::ScriptOutputLine;
+
+use view::routes::manage;
 
 use broker::input::WsInputData;
 use broker::routes::CallAction;
 use broker::routes::CallParams;
+use broker::metrics::Stage;
 use actix::System;
 
 
@@ -53,6 +65,9 @@ const MESSAGE_INTERVAL: Duration = Duration::from_millis(500); // 500 millisecon
 // How much time it should lag from now, This is so that if there is a time mismatch between the db and the server, it doesn't skip messages
 const MESSAGE_LAG: Duration = Duration::from_micros(50);
 
+/// number of output lines waiting to be sent before the script is held up
+const SCRIPT_OUTPUT_BUFFER: usize = 64;
+
 
 impl<S> Actor for WsClientSession<S>
     where
@@ -68,10 +83,10 @@ impl<S> Actor for WsClientSession<S>
 
     fn stopped(&mut self, ctx: &mut Self::Context) {
 
-        // unsubscribing from all
+        // unsubscribing from all, the durable subscriptions are kept for the next session
         // TODO: maybe this should be dependent on what has been subscribed during this session
         let data = json!({});
-        let params = json!({});
+        let params = json!({ "keepDurable": true });
 
         {
             let mut call_params = CallParams {
@@ -117,32 +132,47 @@ impl<S> WsClientSession<S>
         debug!("User unsubscribed from all channels {:?}", &res);
     }
 
-    fn do_nothing_for_unsubscribe_err(ctx: &mut ws::WebsocketContext<Self, S>, res: String) {
+    fn do_nothing_for_unsubscribe_err(ctx: &mut ws::WebsocketContext<Self, S>, res: serde_json::Value) {
         debug!("User wasn't able to unsubscribed from all channels {:?}", &res);
     }
 
+    /// every event is sent as it is, see `data::event::Event`, it has the `action` and the `data` of the other messages
     fn process_message_when_callback_is_ok(ctx: &mut ws::WebsocketContext<Self, S>, res: serde_json::Value) {
-        let action_name = &res["action"];
+        let metrics = ctx.state().broadcast_metrics().clone();
+        let metrics_name = res["action"].as_str().unwrap_or_default().to_owned();
         let messages = res["data"]
             .as_array() //Assumes that the getMessages returns an array
             .unwrap_or(&vec![])
             .into_iter()
-            .for_each(|message_res| {
-                let message = json!({
-                    "action": action_name.to_owned(),
-                    "data": message_res,
-                });
-                let message_text = serde_json::to_string(&message).unwrap_or_default();
+            .for_each(|event| {
+                let sent_at = serde_json::from_value::<chrono::NaiveDateTime>(event["timestamp"].to_owned());
+                if let Ok(sent_at) = sent_at {
+                    metrics.record_since(Stage::Fanout, &metrics_name, sent_at);
+                }
+
+                let message_text = serde_json::to_string(event).unwrap_or_default();
+
+                let enqueue_start = Instant::now();
                 ctx.text(message_text);
+                metrics.record(Stage::Enqueue, &metrics_name, enqueue_start.elapsed());
             });
     }
 
-    fn process_message_when_callback_is_not_ok(ctx: &mut ws::WebsocketContext<Self, S>, res: String) {
+    fn process_message_when_callback_is_not_ok(ctx: &mut ws::WebsocketContext<Self, S>, res: serde_json::Value) {
         warn!("Encountered an error when processing message: {:?}", &res);
         // Do nothing
     }
 
     fn message_process(&mut self, ctx: &mut ws::WebsocketContext<Self, S>) {
+        if self.draining {
+            info!("WsSession [{}] closing for the shutdown", &self.id.to_hyphenated_ref());
+            ctx.close(Some(ws::CloseCode::Away.into()));
+            ctx.stop();
+            return;
+        }
+        // the messages published before the shutdown are still sent, the next run closes the session
+        self.draining = shutdown::is_shutting_down();
+
         let lag = chrono::Duration::from_std(MESSAGE_LAG)
             .unwrap_or_else(|err| {
                 warn!("Could not understand MESSAGE_LAG, setting to 0: err: {:?}", &err);
@@ -170,8 +200,28 @@ impl<S> WsClientSession<S>
             routes::call_procedure("getMessages", self, &mut call_params);
         }
 
+        if self.auth_header.is_some() {
+            self.pending_message_process(ctx);
+        }
+
         ctx.run_later(MESSAGE_INTERVAL, Self::message_process);
     }
+
+    /// The messages of the durable subscriptions, they are sent until the client acks them. The first run after
+    /// authenticating sends all of them again, the ones sent before the client disconnected might not have arrived
+    fn pending_message_process(&mut self, ctx: &mut ws::WebsocketContext<Self, S>) {
+        let data = json!({});
+        let params = json!({ "redeliver": self.redeliver_pending });
+        self.redeliver_pending = false;
+
+        let mut call_params = CallParams {
+            data, params, ctx,
+            on_received: &Self::process_message_when_callback_is_ok,
+            on_received_error: &Self::process_message_when_callback_is_not_ok,
+        };
+
+        routes::call_procedure("getPendingMessages", self, &mut call_params);
+    }
 }
 
 
@@ -239,6 +289,11 @@ pub struct WsClientSession<S>
     last_beat: Instant,
     last_message: chrono::NaiveDateTime,
     auth_header: Option<Vec<u8>>,
+    key_case: KeyCase,
+    /// the unacknowledged messages that were already sent are sent again, set when the user authenticates
+    redeliver_pending: bool,
+    /// the messages were fetched once more after the shutdown started, the session closes on the next run
+    draining: bool,
 
     phantom_data: PhantomData<(S)>,
 }
@@ -255,6 +310,9 @@ impl<S> WsClientSession<S>
             last_beat: Instant::now(),
             last_message: chrono::Utc::now().naive_utc(),
             auth_header: None,
+            key_case: KeyCase::default(),
+            redeliver_pending: false,
+            draining: false,
             phantom_data: PhantomData,
         }
     }
@@ -267,8 +325,9 @@ impl<S> WsClientSession<S>
         ctx.text(message);
     }
 
-    fn callback_when_action_is_not_ok(ctx: &mut ws::WebsocketContext<Self, S>, res: String) {
-        let message = serde_json::to_string(&json!({"error": res})).unwrap_or_default();
+    /// `res` is the error envelope, `{"error": "...", "details": {...}}`
+    fn callback_when_action_is_not_ok(ctx: &mut ws::WebsocketContext<Self, S>, res: serde_json::Value) {
+        let message = serde_json::to_string(&res).unwrap_or_default();
         warn!("an error occurred in callback when action is not ok: {:?}", &message);
         ctx.text(message)
     }
@@ -281,6 +340,11 @@ impl<S> WsClientSession<S>
             },
             WsInputData::Call { procedure, params, data } => {
                 debug!("calling procedure: {:?}", &procedure);
+                if procedure == "streamScript" {
+                    self.stream_script(ctx, data, params);
+                    return;
+                }
+
                 let mut call_params = CallParams {
                     data, params, ctx,
                     on_received: &Self::callback_when_action_is_ok,
@@ -290,57 +354,144 @@ impl<S> WsClientSession<S>
                 let result = routes::call_procedure(&procedure, self, &mut call_params);
                 debug!("finished calling procedure {:?}", &result);
             },
+            WsInputData::SetKeyCase { key_case } => {
+                debug!("setting key case: {:?}", &key_case);
+                self.key_case = key_case;
+
+                let message = json!({
+                    "action": "keyCaseSet",
+                    "data": key_case,
+                });
+                let message = serde_json::to_string(&message).unwrap_or_default();
+                ctx.text(message);
+            },
+            WsInputData::Ack { ids } => {
+                debug!("acknowledging {} messages", ids.len());
+                let mut call_params = CallParams {
+                    data: json!({ "ids": ids }),
+                    params: json!({}),
+                    ctx,
+                    on_received: &Self::callback_when_action_is_ok,
+                    on_received_error: &Self::callback_when_action_is_not_ok,
+                };
+
+                routes::call_procedure("ackMessages", self, &mut call_params);
+            },
         };
     }
 }
 
+impl<S> WsClientSession<S>
+    where
+        S: AppStateLike + 'static,
+{
+    /// Runs the script like `runScript`, the lines the script prints are sent to this session as
+    /// `scriptOutput` messages while it runs, followed by the usual result
+    fn stream_script(&mut self, ctx: &mut ws::WebsocketContext<Self, S>, data: serde_json::Value, params: serde_json::Value) {
+        let (sender, receiver) = mpsc::channel(SCRIPT_OUTPUT_BUFFER);
+
+        let mut action_wrapper = ActionWrapper::new(manage::stream_script(data, params, sender))
+            .with_key_case(self.key_case);
+
+        if let Some(ref auth) = self.auth_header {
+            action_wrapper = action_wrapper.with_auth(&auth);
+        }
+
+        ctx.add_stream(receiver);
+
+        // spawned rather than waited on, so that the output lines are sent while the script is running
+        ctx
+            .state()
+            .connect()
+            .send(action_wrapper)
+            .into_actor(self)
+            .then(|res, _actor, ctx| {
+                match res {
+                    Ok(Ok(res)) => Self::callback_when_action_is_ok(ctx, res.get_tagged_data()),
+                    Ok(Err(err)) => Self::callback_when_action_is_not_ok(ctx, err.envelope()),
+                    Err(err) => {
+                        error!("websocket error occurred with error message: {:?}", &err);
+                        Self::callback_when_action_is_not_ok(ctx, json!({ "error": err.to_string() }));
+                    },
+                };
+
+                fut::ok(())
+            })
+            .spawn(ctx);
+    }
+}
+
+impl<S> StreamHandler<ScriptOutputLine, ()> for WsClientSession<S>
+    where
+        S: AppStateLike + 'static,
+{
+    fn handle(&mut self, output_line: ScriptOutputLine, ctx: &mut Self::Context) {
+        let message = json!({
+            "action": "scriptOutput",
+            "data": output_line,
+        });
+        let message = serde_json::to_string(&message).unwrap_or_default();
+        ctx.text(message);
+    }
+
+    /// the script is done, the session stays open
+    fn finished(&mut self, _ctx: &mut Self::Context) {
+        debug!("script output stream finished");
+    }
+}
+
 impl<S> CallAction<S> for WsClientSession<S>
     where S: AppStateLike
 {
     /// For use by the websockets
-    fn call<'a, PB, A, F, EF>(&mut self, procedure_builder: PB, call_params: &mut CallParams<'a, S, F, EF>)
+    fn call<'a, PB, A, F, EF>(&mut self, procedure: &str, procedure_builder: PB, call_params: &mut CallParams<'a, S, F, EF>)
         where
             PB: ProcedureBuilder<S, serde_json::Value, serde_json::Value, A> + Clone + 'static,
             S: AppStateLike + 'static,
             A: Action + 'static,
             for<'b> F: Fn(&'b mut ws::WebsocketContext<WsClientSession<S>, S>, serde_json::Value) -> () + 'static,
-            for<'b> EF: Fn(&'b mut ws::WebsocketContext<WsClientSession<S>, S>, String) -> () + 'static,
+            for<'b> EF: Fn(&'b mut ws::WebsocketContext<WsClientSession<S>, S>, serde_json::Value) -> () + 'static,
     {
 
         let action = procedure_builder
             .build(call_params.data.to_owned(), call_params.params.to_owned());
 
-        let mut action_wrapper = ActionWrapper::new(action);
+        // every call on the socket is a request of its own
+        let request_id = new_request_id();
+        let mut action_wrapper = ActionWrapper::new(action)
+            .with_key_case(self.key_case)
+            .with_request_id(&request_id);
 
         if let Some(ref auth) = self.auth_header {
             action_wrapper = action_wrapper.with_auth(&auth);
         }
+        if let Some(timeout) = call_params.ctx.state().action_timeout(procedure) {
+            action_wrapper = action_wrapper.with_timeout(procedure, timeout);
+        }
 
         let on_received = call_params.on_received;
         let on_received_error = call_params.on_received_error;
 
-        call_params
-            .ctx
-            .state()
-            .connect()
-            .send(action_wrapper)
+        send_action(call_params.ctx.state().connect_for::<A>(), action_wrapper)
             .into_actor(self)
             .then(move |res, actor, ctx| {
                 match res {
                     Ok(ok_res) => match ok_res {
                         Ok(res) => {
-                            info!("action message ok");
+                            info!("action message ok [{}]", &request_id);
                             let res_value = res.get_tagged_data();
                             (&on_received)(ctx, res_value);
                         },
                         Err(err) => {
-                            info!("action message error");
-                            (&on_received_error)(ctx, err.to_string());
+                            info!("action message error [{}]", &request_id);
+                            let mut envelope = err.envelope();
+                            envelope["requestId"] = json!(request_id);
+                            (&on_received_error)(ctx, envelope);
                         }
                     },
                     Err(err) => {
                         error!("websocket error occurred with error message: {:?}", &err);
-                        (&on_received_error)(ctx, err.to_string());
+                        (&on_received_error)(ctx, json!({ "error": err.to_string() }));
                     }
                 }
 
@@ -353,9 +504,9 @@ impl<S> CallAction<S> for WsClientSession<S>
         where
             S: AppStateLike + 'static,
             for<'b> F: Fn(&'b mut ws::WebsocketContext<WsClientSession<S>, S>, serde_json::Value) -> () + 'static,
-            for<'b> EF: Fn(&'b mut ws::WebsocketContext<WsClientSession<S>, S>, String) -> () + 'static,
+            for<'b> EF: Fn(&'b mut ws::WebsocketContext<WsClientSession<S>, S>, serde_json::Value) -> () + 'static,
     {
-        let message = "Did not understand procedure".to_string();
+        let message = json!({ "error": "Did not understand procedure" });
         let on_received_error = call_params.on_received_error;
         (&on_received_error)(call_params.ctx, message);
     }
@@ -377,6 +528,7 @@ impl<S> WsClientSession<S>
             Ok(x) => {
                 let bearer_token = to_bearer_token(token); //need it to be a bearer token for the action wrapper to handle it
                 self.auth_header = Some(bearer_token.as_bytes().to_vec());
+                self.redeliver_pending = true;
 
                 let message = json!({
                     "action": "authenticated",
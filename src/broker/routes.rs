@@ -1,10 +1,17 @@
+use serde::Serialize;
+
+use actix::prelude::*;
 use actix_web::ws;
 use broker::WsClientSession;
+use connection::executor::Executor;
+use view::action_wrapper::ActionWrapper;
 use view::procedure::ProcedureBuilder;
+use view::registry::Procedure;
+use view::registry::ProcedureVisitor;
+use view::registry::visit_procedures;
+use view::versioning::ApiVersion;
 use AppStateLike;
 use model::actions::Action;
-use view::routes::manage;
-use view::routes::pubsub;
 
 pub struct CallParams<'a, S, F, EF>
     where
@@ -12,7 +19,7 @@ pub struct CallParams<'a, S, F, EF>
         //TODO: this is really annoying. You can probably fuck around with the lifetimes and generics enough to get this working
         //more generally, but right now we have to pass in a static function, can't be a closure
         for<'b> F: Fn(&'b mut ws::WebsocketContext<WsClientSession<S>, S>, serde_json::Value) -> () + 'static,
-        for<'b> EF: Fn(&'b mut ws::WebsocketContext<WsClientSession<S>, S>, String) -> () + 'static,
+        for<'b> EF: Fn(&'b mut ws::WebsocketContext<WsClientSession<S>, S>, serde_json::Value) -> () + 'static,
 {
     pub data: serde_json::Value,
     pub params: serde_json::Value,
@@ -23,67 +30,76 @@ pub struct CallParams<'a, S, F, EF>
 
 
 pub trait CallAction<S> {
-    fn call<'a, PB, A, F, EF>(&mut self, procedure_builder: PB, call_params: &'a mut CallParams<'a, S, F, EF>)
+    fn call<'a, PB, A, F, EF>(&mut self, procedure: &str, procedure_builder: PB, call_params: &'a mut CallParams<'a, S, F, EF>)
         where
             PB: ProcedureBuilder<S, serde_json::Value, serde_json::Value, A> + Clone + 'static,
             S: AppStateLike + 'static,
             A: Action + 'static,
             for<'b> F: Fn(&'b mut ws::WebsocketContext<WsClientSession<S>, S>, serde_json::Value) -> () + 'static,
-            for<'b> EF: Fn(&'b mut ws::WebsocketContext<WsClientSession<S>, S>, String) -> () + 'static;
+            for<'b> EF: Fn(&'b mut ws::WebsocketContext<WsClientSession<S>, S>, serde_json::Value) -> () + 'static;
 
     fn error<'a, F, EF>(&mut self, call_params: &'a mut CallParams<'a, S, F, EF>)
         where
             S: AppStateLike + 'static,
             for<'b> F: Fn(&'b mut ws::WebsocketContext<WsClientSession<S>, S>, serde_json::Value) -> () + 'static,
-            for<'b> EF: Fn(&'b mut ws::WebsocketContext<WsClientSession<S>, S>, String) -> () + 'static;
+            for<'b> EF: Fn(&'b mut ws::WebsocketContext<WsClientSession<S>, S>, serde_json::Value) -> () + 'static;
 }
 
-pub fn call_procedure<'a, CB, S, F, EF>(procedure: &str, cb: &mut CB, call_params: &'a mut CallParams<'a, S, F, EF>)
+/// Calls the procedure of the registry with the name, if it's served on the socket
+struct SocketDispatch<'p, 'a, CB, S, F, EF>
     where
+        CB: 'p,
         S: AppStateLike + 'static,
-        CB: CallAction<S>,
         for<'b> F: Fn(&'b mut ws::WebsocketContext<WsClientSession<S>, S>, serde_json::Value) -> () + 'static,
-        for<'b> EF: Fn(&'b mut ws::WebsocketContext<WsClientSession<S>, S>, String) -> () + 'static,
+        for<'b> EF: Fn(&'b mut ws::WebsocketContext<WsClientSession<S>, S>, serde_json::Value) -> () + 'static,
 {
-    //TODO: put this in a macro, we are using this in the routes as well
-    match procedure {
-        "getAllDomains" => cb.call(manage::get_all_domains, call_params),
-
-        "getAllTables" => cb.call(manage::get_all_tables, call_params),
-        "getAllQueries" => cb.call(manage::get_all_queries, call_params),
-        "getAllScripts" => cb.call(manage::get_all_scripts, call_params),
-
-        "getTable" => cb.call(manage::get_table, call_params),
-        "getQuery" => cb.call(manage::get_query, call_params),
-        "getScript" => cb.call(manage::get_script, call_params),
-
-        "createTable" => cb.call(manage::create_table, call_params),
-        "createQuery" => cb.call(manage::create_query, call_params),
-        "createScript" => cb.call(manage::create_script, call_params),
-
-        "updateTable" => cb.call(manage::update_table, call_params),
-        "updateQuery" => cb.call(manage::update_query, call_params),
-        "updateScript" => cb.call(manage::update_script, call_params),
-
-        "deleteTable" => cb.call(manage::delete_table, call_params),
-        "deleteQuery" => cb.call(manage::delete_query, call_params),
-        "deleteScript" => cb.call(manage::delete_script, call_params),
+    procedure: &'p str,
+    cb: &'p mut CB,
+    /// taken by the procedure that is called, still there if none was
+    call_params: Option<&'a mut CallParams<'a, S, F, EF>>,
+}
 
-        "queryTableData" => cb.call(manage::query_table_data, call_params),
-        "insertTableData" => cb.call(manage::insert_table_data, call_params),
-        "modifyTableData" => cb.call(manage::modify_table_data, call_params),
-        "removeTableData" => cb.call(manage::remove_table_data, call_params),
+impl<'p, 'a, CB, S, F, EF> ProcedureVisitor<S> for SocketDispatch<'p, 'a, CB, S, F, EF>
+    where
+        S: AppStateLike + 'static,
+        CB: CallAction<S>,
+        for<'b> F: Fn(&'b mut ws::WebsocketContext<WsClientSession<S>, S>, serde_json::Value) -> () + 'static,
+        for<'b> EF: Fn(&'b mut ws::WebsocketContext<WsClientSession<S>, S>, serde_json::Value) -> () + 'static,
+{
+    fn visit<A, PB>(&mut self, procedure: Procedure, procedure_builder: PB) -> &mut Self
+        where
+            Executor: Handler<ActionWrapper<A>>,
+            A: Action + Send + 'static,
+            PB: ProcedureBuilder<S, serde_json::Value, serde_json::Value, A> + Clone + 'static,
+            <A as Action>::Ret: Send + Serialize,
+    {
+        if procedure.socket && procedure.name == self.procedure {
+            if let Some(call_params) = self.call_params.take() {
+                self.cb.call(procedure.name, procedure_builder, call_params);
+            }
+        }
+        self
+    }
+}
 
-        "runQuery" => cb.call(manage::run_query, call_params),
-        "runScript" => cb.call(manage::run_script, call_params),
+/// The procedures are the ones of the registry, the same as the http routes, see `visit_procedures`
+pub fn call_procedure<'a, CB, S, F, EF>(procedure: &str, cb: &mut CB, call_params: &'a mut CallParams<'a, S, F, EF>)
+    where
+        S: AppStateLike + 'static,
+        CB: CallAction<S>,
+        for<'b> F: Fn(&'b mut ws::WebsocketContext<WsClientSession<S>, S>, serde_json::Value) -> () + 'static,
+        for<'b> EF: Fn(&'b mut ws::WebsocketContext<WsClientSession<S>, S>, serde_json::Value) -> () + 'static,
+{
+    let mut dispatch = SocketDispatch {
+        procedure,
+        cb,
+        call_params: Some(call_params),
+    };
 
-        "subscribeTo" => cb.call(pubsub::subscribe_to, call_params),
-        "unsubscribeFrom" => cb.call(pubsub::unsubscribe_from, call_params),
-        "unsubscribeAll" => cb.call(pubsub::unsubscribe_all, call_params),
-        "getSubscribers" => cb.call(pubsub::get_subscribers, call_params),
-        "getMessages" => cb.call(pubsub::get_messages, call_params),
+    // the socket messages aren't versioned, they are the ones from before the versioning
+    visit_procedures(ApiVersion::LEGACY, &mut dispatch);
 
-        _ => cb.error(call_params),
+    if let Some(call_params) = dispatch.call_params.take() {
+        dispatch.cb.error(call_params);
     }
-
-}
\ No newline at end of file
+}
@@ -0,0 +1,48 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// How long the callers wait for an action before they get a timeout error, by procedure.
+/// The executor thread isn't interrupted, but an action that didn't start by then is skipped, and
+/// the statements of a query only get what is left of the timeout, so they are canceled by the database
+#[derive(Debug, Clone, Default)]
+pub struct ActionTimeouts {
+    /// none waits for as long as it takes
+    default: Option<Duration>,
+    /// none for the procedures that are never timed out, e.g. the long scripts
+    procedures: HashMap<String, Option<Duration>>,
+}
+
+impl ActionTimeouts {
+    pub fn set_default(&mut self, timeout: Option<Duration>) {
+        self.default = timeout;
+    }
+
+    pub fn set_procedure(&mut self, procedure: &str, timeout: Option<Duration>) {
+        self.procedures.insert(procedure.to_owned(), timeout);
+    }
+
+    pub fn for_procedure(&self, procedure: &str) -> Option<Duration> {
+        match self.procedures.get(procedure) {
+            Some(timeout) => *timeout,
+            None => self.default,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_for_procedure() {
+        let mut timeouts = ActionTimeouts::default();
+        assert_eq!(timeouts.for_procedure("getTable"), None);
+
+        timeouts.set_default(Some(Duration::from_secs(30)));
+        timeouts.set_procedure("runQuery", Some(Duration::from_secs(120)));
+        timeouts.set_procedure("runScript", None);
+        assert_eq!(timeouts.for_procedure("getTable"), Some(Duration::from_secs(30)));
+        assert_eq!(timeouts.for_procedure("runQuery"), Some(Duration::from_secs(120)));
+        assert_eq!(timeouts.for_procedure("runScript"), None);
+    }
+}
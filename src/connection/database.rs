@@ -0,0 +1,40 @@
+use diesel;
+use diesel::Connection;
+use diesel::pg::PgConnection;
+
+use connection::executor::Conn;
+
+/// The connection to the metastore, see `StateFunctions::Database`
+///
+/// The state, the entity controllers, the table controller and the entity updates are generic over it,
+/// so that an other backend only needs an implementation of it and of the metastore ops for its connection.
+/// `StateFunctions` is only implemented for `ActionState<Conn>` so far, the metastore ops are written for postgres
+pub trait DatabaseConnection {
+    type Connection: Connection;
+
+    fn get_connection(&self) -> &Self::Connection;
+
+    fn transaction<G, E, F>(&self, f: F) -> Result<G, E>
+        where F: FnOnce() -> Result<G, E>, E: From<diesel::result::Error>,
+    {
+        self.get_connection().transaction::<G, E, _>(f)
+    }
+}
+
+impl DatabaseConnection for Conn {
+    type Connection = PgConnection;
+
+    fn get_connection(&self) -> &PgConnection {
+        &**self
+    }
+}
+
+impl<'a, D> DatabaseConnection for &'a D
+    where D: DatabaseConnection,
+{
+    type Connection = D::Connection;
+
+    fn get_connection(&self) -> &Self::Connection {
+        (*self).get_connection()
+    }
+}
@@ -4,6 +4,8 @@ use std::fmt;
 use std::path::PathBuf;
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+use std::time::Instant;
 
 use diesel::pg::PgConnection;
 
@@ -13,10 +15,19 @@ use diesel::r2d2::Pool;
 
 use connection::AppStateBuilder;
 use connection::domain::DomainCollection;
+use broker::metrics::BroadcastMetrics;
+use broker::event_sink::KafkaSink;
+use connection::metrics::DatabaseMetrics;
+use connection::metrics::METASTORE_POOL;
+use model::running_queries::RunningQueries;
+use connection::query_limit::QueryLimit;
+use scripting::container::ContainerConfig;
+use scripting::jobs::ScriptJobs;
 
 use plugins::v1::Domain;
 use plugins::v1::Datastore;
 use plugins::v1::DataQuery;
+use plugins::v1::DatastoreError;
 
 #[derive(Debug, Fail, PartialEq, Eq)]
 pub enum DomainError {
@@ -26,21 +37,35 @@ pub enum DomainError {
     DatastoreNotAvailable,
     #[fail(display = "domain does not support query operations")]
     QueryNotAvailable,
+    #[fail(display = "no connection to the domain is available")]
+    Unavailable,
     #[fail(display = "An unknown error occurred")]
     Unknown,
 }
 
 pub type Conn = PooledConnection<ConnectionManager<PgConnection>>;
 
+/// The metastore connections of one executor
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PoolState {
+    pub connections: u32,
+    pub idle_connections: u32,
+    pub max_size: u32,
+}
+
 #[derive(Clone)]
 pub struct Secrets {
     pub token_secret: String,
     pub password_secret: String,
+    /// encrypts the script secrets, they can't be used if it's not set
+    pub secrets_key: Option<String>,
 }
 
 pub struct Executor {
     pool: Pool<ConnectionManager<PgConnection>>,
     script_path: PathBuf,
+    script_container: Option<ContainerConfig>,
     secrets: Secrets,
 
     domains: DomainCollection,
@@ -48,6 +73,15 @@ pub struct Executor {
     pub jwt_issuer: String,
     pub jwt_token_duration: i64,
     pub jwt_refresh_token_duration: i64,
+
+    read_only: Arc<AtomicBool>,
+    broadcast_metrics: Arc<BroadcastMetrics>,
+    database_metrics: Arc<DatabaseMetrics>,
+    running_queries: Arc<RunningQueries>,
+    script_jobs: Arc<ScriptJobs>,
+    query_limit: Arc<QueryLimit>,
+    retry_after: u64,
+    kafka_sink: Option<Arc<KafkaSink>>,
 }
 
 impl fmt::Debug for Executor {
@@ -58,49 +92,67 @@ impl fmt::Debug for Executor {
 
 impl Executor {
     pub fn get_connection(&self) -> Conn {
-        self.pool.get()
+        self.try_get_connection()
             .expect("Could not get connection")
     }
 
+    /// same as `get_connection`, but the error is returned instead of panicking, for the health checks
+    pub fn try_get_connection(&self) -> Result<Conn, String> {
+        let started = Instant::now();
+        let conn = self.pool.get();
+        self.database_metrics.record_checkout(METASTORE_POOL, started.elapsed(), conn.is_err());
+
+        conn.map_err(|err| err.to_string())
+    }
+
+    pub fn get_pool_state(&self) -> PoolState {
+        let state = self.pool.state();
+        PoolState {
+            connections: state.connections,
+            idle_connections: state.idle_connections,
+            max_size: self.pool.max_size(),
+        }
+    }
+
     pub fn get_datastore_conn(&self, domain_name: &str) -> Result<Box<Datastore>, DomainError> {
+        let started = Instant::now();
         let datastore = self.domains
             .get(domain_name)
             .ok_or_else(|| DomainError::DomainNotFound(domain_name.to_string()))?
             .connect_datastore()
-            .ok_or_else(|| DomainError::DatastoreNotAvailable)?;
+            .map_err(|err| match err {
+                DatastoreError::Unavailable => DomainError::Unavailable,
+                _ => DomainError::DatastoreNotAvailable,
+            })?;
+        self.database_metrics.record_checkout(domain_name, started.elapsed(), false);
 
         Ok(datastore)
     }
 
     pub fn get_query_conn(&self, domain_name: &str) -> Result<Box<DataQuery>, DomainError> {
+        let started = Instant::now();
         let dataquery = self.domains
             .get(domain_name)
             .ok_or_else(|| DomainError::DomainNotFound(domain_name.to_string()))?
             .connect_query()
-            .ok_or_else(|| DomainError::DatastoreNotAvailable)?;
+            .map_err(|err| match err {
+                DatastoreError::Unavailable => DomainError::Unavailable,
+                _ => DomainError::QueryNotAvailable,
+            })?;
+        self.database_metrics.record_checkout(domain_name, started.elapsed(), false);
 
         Ok(dataquery)
     }
 
     pub fn create(info: &AppStateBuilder) -> Self {
 
-        let database_url = format!(
-            "postgres://{}:{}@{}:{}/{}",
-            info.user.clone().unwrap_or_default(),
-            info.pass.clone().unwrap_or_default(),
-            info.host.clone().unwrap_or_default(),
-            info.port.clone().unwrap_or_default(),
-            info.db.clone().unwrap_or_default(),
-        );
-        let mut domains = DomainCollection::new();
-        for (key, value) in info.domain_builders.iter() {
-            domains.insert(key, value.build());
-        }
+        let database_url = info.database_url();
+        let domains = info.build_domains();
         let _ = domains.sync_with_database(&database_url)
             .expect("Could not setup the domains in the database");
 
         let manager = ConnectionManager::<PgConnection>::new(database_url);
-        let pool = Pool::builder().build(manager)
+        let pool = info.pool_config.build(manager)
             .expect("Could not start connection");
 
         let script_path = match info.script_path.clone() {
@@ -111,12 +163,14 @@ impl Executor {
         let secrets = Secrets {
             token_secret: info.token_secret.clone().unwrap_or_default(),
             password_secret: info.password_secret.clone().unwrap_or_default(),
+            secrets_key: info.secrets_key.clone(),
         };
 
 
         Self {
             pool,
             script_path,
+            script_container: info.script_container.clone(),
             secrets,
 
             domains,
@@ -124,6 +178,15 @@ impl Executor {
             jwt_issuer: info.jwt_issuer.clone().unwrap_or_default(), //TODO: what is the default here?
             jwt_token_duration: info.jwt_token_duration.clone(),
             jwt_refresh_token_duration: info.jwt_refresh_token_duration.clone(),
+
+            read_only: info.read_only.clone(),
+            broadcast_metrics: info.broadcast_metrics.clone(),
+            database_metrics: info.database_metrics.clone(),
+            running_queries: info.running_queries.clone(),
+            script_jobs: info.script_jobs.clone(),
+            query_limit: info.query_limit.clone(),
+            retry_after: info.retry_after,
+            kafka_sink: info.kafka_sink.clone(),
         }
     }
 
@@ -131,6 +194,10 @@ impl Executor {
         self.script_path.to_owned()
     }
 
+    pub fn get_script_container(&self) -> Option<ContainerConfig> {
+        self.script_container.to_owned()
+    }
+
     pub fn get_token_secret(&self) -> String {
         self.secrets.token_secret.to_owned()
     }
@@ -138,6 +205,39 @@ impl Executor {
     pub fn get_secrets(&self) -> Secrets {
         self.secrets.to_owned()
     }
+
+    pub fn get_read_only(&self) -> Arc<AtomicBool> {
+        self.read_only.clone()
+    }
+
+    pub fn get_broadcast_metrics(&self) -> Arc<BroadcastMetrics> {
+        self.broadcast_metrics.clone()
+    }
+
+    pub fn get_database_metrics(&self) -> Arc<DatabaseMetrics> {
+        self.database_metrics.clone()
+    }
+
+    pub fn get_running_queries(&self) -> Arc<RunningQueries> {
+        self.running_queries.clone()
+    }
+
+    pub fn get_script_jobs(&self) -> Arc<ScriptJobs> {
+        self.script_jobs.clone()
+    }
+
+    pub fn get_query_limit(&self) -> Arc<QueryLimit> {
+        self.query_limit.clone()
+    }
+
+    pub fn get_kafka_sink(&self) -> Option<Arc<KafkaSink>> {
+        self.kafka_sink.clone()
+    }
+
+    /// in seconds, sent to the clients when no connection could be checked out
+    pub fn get_retry_after(&self) -> u64 {
+        self.retry_after
+    }
 }
 
 impl Actor for Executor {
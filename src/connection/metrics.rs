@@ -0,0 +1,126 @@
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::sync::MutexGuard;
+use std::time::Duration;
+use std::time::Instant;
+
+use serde_json;
+
+use broker::metrics::Histogram;
+
+/// The pool of the metastore connections, the domain pools are named after their domain
+pub const METASTORE_POOL: &str = "metastore";
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct OperationMetrics {
+    latency: Histogram,
+    errors: u64,
+}
+
+impl OperationMetrics {
+    fn new() -> Self {
+        Self {
+            latency: Histogram::new(),
+            errors: 0,
+        }
+    }
+
+    fn observe(&mut self, latency: Duration, failed: bool) {
+        self.latency.observe(as_millis(latency));
+        if failed {
+            self.errors += 1;
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct MetricsInner {
+    /// by the kind of operation, e.g. `insert` or `query`
+    operations: BTreeMap<String, OperationMetrics>,
+    /// the time waited for a connection, by pool
+    checkouts: BTreeMap<String, OperationMetrics>,
+}
+
+/// Latency and error metrics for the database, shared by all the executors
+#[derive(Debug, Default)]
+pub struct DatabaseMetrics {
+    inner: Mutex<MetricsInner>,
+}
+
+impl DatabaseMetrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn lock(&self) -> MutexGuard<MetricsInner> {
+        // the metrics are only added to, so they are still usable after a panic
+        self.inner.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    pub fn record_operation(&self, kind: &str, latency: Duration, failed: bool) {
+        let millis = as_millis(latency);
+        if failed {
+            debug!("{} failed on the database after {}ms", kind, millis);
+        }
+
+        self.lock()
+            .operations
+            .entry(kind.to_owned())
+            .or_insert_with(OperationMetrics::new)
+            .observe(latency, failed);
+    }
+
+    pub fn record_checkout(&self, pool: &str, wait: Duration, failed: bool) {
+        self.lock()
+            .checkouts
+            .entry(pool.to_owned())
+            .or_insert_with(OperationMetrics::new)
+            .observe(wait, failed);
+    }
+
+    /// runs the operation and records how long it took and whether it failed
+    pub fn time_operation<T, E, F>(&self, kind: &str, f: F) -> Result<T, E>
+        where F: FnOnce() -> Result<T, E>
+    {
+        let started = Instant::now();
+        let res = f();
+        self.record_operation(kind, started.elapsed(), res.is_err());
+        res
+    }
+
+    pub fn snapshot(&self) -> serde_json::Value {
+        let inner = self.lock();
+        json!({
+            "operations": inner.operations,
+            "checkouts": inner.checkouts,
+        })
+    }
+}
+
+fn as_millis(duration: Duration) -> u64 {
+    duration.as_secs() * 1000 + u64::from(duration.subsec_millis())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_record_operations() {
+        let metrics = DatabaseMetrics::new();
+        metrics.record_operation("insert", Duration::from_millis(3), false);
+        metrics.record_operation("insert", Duration::from_millis(300), true);
+        let _ = metrics.time_operation("query", || -> Result<(), ()> { Err(()) });
+        metrics.record_checkout(METASTORE_POOL, Duration::from_millis(40), false);
+
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot["operations"]["insert"]["latency"]["count"], json!(2));
+        assert_eq!(snapshot["operations"]["insert"]["latency"]["buckets"][1], json!(1));
+        assert_eq!(snapshot["operations"]["insert"]["latency"]["buckets"][7], json!(1));
+        assert_eq!(snapshot["operations"]["insert"]["errors"], json!(1));
+        assert_eq!(snapshot["operations"]["query"]["errors"], json!(1));
+        assert_eq!(snapshot["checkouts"]["metastore"]["latency"]["buckets"][4], json!(1));
+        assert_eq!(snapshot["checkouts"]["metastore"]["errors"], json!(0));
+    }
+}
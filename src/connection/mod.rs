@@ -1,17 +1,48 @@
 
 pub mod executor;
 pub mod domain;
+pub mod database;
+pub mod query_limit;
+pub mod pool;
+pub mod tls;
+pub mod metrics;
+pub mod action_timeout;
 
 use num_cpus;
 
 use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
 use std::fmt::Debug;
 use std::collections::HashMap;
+use std::time::Duration;
 
+use actix::Actor;
 use actix::Addr;
 use actix::sync::SyncArbiter;
 
 use data::channels::Channels;
+use broker::metrics::BroadcastMetrics;
+use broker::event_sink::KafkaSink;
+use broker::event_sink::KafkaSinkConfig;
+use data::message_retention::MessageRetention;
+use model::running_queries::RunningQueries;
+use model::actions::Action;
+use connection::query_limit::QueryLimit;
+use connection::action_timeout::ActionTimeouts;
+use connection::domain::DomainCollection;
+use connection::metrics::DatabaseMetrics;
+use connection::pool::PoolConfig;
+use connection::tls::SslMode;
+use connection::tls::TlsConfig;
+use metastore::migrations;
+use jobs::message_cleanup::MessageCleanupJob;
+use jobs::subscription_cleanup::SubscriptionCleanupJob;
+use jobs::retention::RetentionJob;
+use jobs::scheduler::SchedulerJob;
+use jobs::webhooks::WebhookJob;
+use jobs::script_jobs;
+use scripting::container::ContainerConfig;
+use scripting::jobs::ScriptJobs;
 
 use plugins::v1::DomainBuilder;
 use plugins::v1::Domain;
@@ -23,13 +54,39 @@ pub trait GetSecrets {
 
 pub trait AppStateLike: GetSecrets {
     fn connect(&self) -> &Addr<executor::Executor>;
+
+    /// the executors running the actions with `Action::runs_domain_queries`
+    fn connect_queries(&self) -> &Addr<executor::Executor> {
+        self.connect()
+    }
+
+    /// the executors the action should be sent to
+    fn connect_for<A: Action>(&self) -> &Addr<executor::Executor>
+        where Self: Sized
+    {
+        if A::runs_domain_queries() {
+            self.connect_queries()
+        } else {
+            self.connect()
+        }
+    }
+
+    fn broadcast_metrics(&self) -> &Arc<BroadcastMetrics>;
+
+    /// how long the callers of the procedure wait for its action, see `ActionTimeouts`
+    fn action_timeout(&self, _procedure: &str) -> Option<Duration> {
+        None
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct AppState {
     connections: Addr<executor::Executor>,
+    query_connections: Addr<executor::Executor>,
     token_secret: String, //This is duplicated here as well as inside the executor , because we need it both in the view (websocket) and in the model
     password_secret: String, // TODO: find a better way
+    broadcast_metrics: Arc<BroadcastMetrics>,
+    action_timeouts: Arc<ActionTimeouts>,
 }
 
 /// Builder for the AppState
@@ -39,15 +96,43 @@ pub struct AppStateBuilder {
     user: Option<String>,
     pass: Option<String>,
     db: Option<String>,
+    metastore_url: Option<String>,
+    pool_config: PoolConfig,
+    tls_config: TlsConfig,
     script_path: Option<String>,
+    script_container: Option<ContainerConfig>,
     token_secret: Option<String>,
     password_secret: Option<String>,
+    secrets_key: Option<String>,
     jwt_issuer: Option<String>,
     jwt_token_duration: i64,
     jwt_refresh_token_duration: i64,
     num_threads: usize,
+    query_threads: usize,
+    query_limit: Arc<QueryLimit>,
+    retry_after: u64,
+    action_timeouts: ActionTimeouts,
+    read_only: Arc<AtomicBool>,
+    broadcast_metrics: Arc<BroadcastMetrics>,
+    database_metrics: Arc<DatabaseMetrics>,
+    running_queries: Arc<RunningQueries>,
+    script_jobs: Arc<ScriptJobs>,
+    script_workers: usize,
+    retention_interval: Option<u64>,
+    message_retention: MessageRetention,
+    message_cleanup_interval: u64,
+    subscription_inactivity: Option<u64>,
+    subscription_cleanup_interval: u64,
+    schedule_queries: bool,
+    schedule_scripts: bool,
+    deliver_webhooks: bool,
+    run_migrations: bool,
+    kafka_sink_config: Option<KafkaSinkConfig>,
+    kafka_sink: Option<Arc<KafkaSink>>,
 
     domain_builders: HashMap<String, Box<DomainBuilder>>,
+    /// registered more than once, `done` fails with them
+    duplicate_domains: Vec<String>,
 }
 
 /// Example Usage
@@ -60,15 +145,42 @@ impl AppStateBuilder {
             user: None,
             pass: None,
             db: None,
+            metastore_url: None,
+            pool_config: PoolConfig::default(),
+            tls_config: TlsConfig::default(),
             script_path: None,
+            script_container: None,
             token_secret: None,
             password_secret: None,
+            secrets_key: None,
             jwt_issuer: None,
             jwt_token_duration: 600,
             jwt_refresh_token_duration: 60 * 60 * 24,
             num_threads: num_cpus::get(),
+            query_threads: num_cpus::get(),
+            query_limit: Arc::new(QueryLimit::default()),
+            retry_after: 1,
+            action_timeouts: ActionTimeouts::default(),
+            read_only: Arc::new(AtomicBool::new(false)),
+            broadcast_metrics: Arc::new(BroadcastMetrics::default()),
+            database_metrics: Arc::new(DatabaseMetrics::default()),
+            running_queries: Arc::new(RunningQueries::default()),
+            script_jobs: Arc::new(ScriptJobs::default()),
+            script_workers: 2,
+            retention_interval: None,
+            message_retention: MessageRetention::default(),
+            message_cleanup_interval: 60 * 60,
+            subscription_inactivity: None,
+            subscription_cleanup_interval: 60 * 60,
+            schedule_queries: true,
+            schedule_scripts: true,
+            deliver_webhooks: true,
+            run_migrations: true,
+            kafka_sink_config: None,
+            kafka_sink: None,
 
             domain_builders: HashMap::new(),
+            duplicate_domains: vec![],
         }
     }
 
@@ -97,11 +209,94 @@ impl AppStateBuilder {
         self
     }
 
+    /// the connection url of the metastore, used instead of the host, port, user, pass and db.
+    /// The tables and queries are in the databases of the domains, see `add_plugin`
+    pub fn metastore_url(mut self, metastore_url: &str) -> Self {
+        self.metastore_url = Some(metastore_url.to_string());
+        self
+    }
+
+    pub fn ssl_mode(mut self, ssl_mode: SslMode) -> Self {
+        self.tls_config.ssl_mode = Some(ssl_mode);
+        self
+    }
+
+    /// the certificates the server certificate is checked against, for `SslMode::VerifyCa` and `SslMode::VerifyFull`
+    pub fn ssl_root_cert(mut self, root_cert: &str) -> Self {
+        self.tls_config.root_cert = Some(root_cert.to_string());
+        self
+    }
+
+    /// the certificate and its key, for the servers that authenticate the clients with it
+    pub fn ssl_client_cert(mut self, client_cert: &str, client_key: &str) -> Self {
+        self.tls_config.client_cert = Some(client_cert.to_string());
+        self.tls_config.client_key = Some(client_key.to_string());
+        self
+    }
+
+    /// the most connections to the metastore each executor thread keeps open
+    pub fn pool_size(mut self, pool_size: u32) -> Self {
+        self.pool_config.max_size = Some(pool_size);
+        self
+    }
+
+    /// the idle connections each executor thread keeps open, the pool is filled up to `pool_size` by default
+    pub fn pool_min_idle(mut self, min_idle: u32) -> Self {
+        self.pool_config.min_idle = Some(min_idle);
+        self
+    }
+
+    /// how long to wait for a metastore connection (in seconds), the requests that can't get one
+    /// in time fail with `ServiceUnavailable`
+    pub fn connection_timeout(mut self, connection_timeout: u64) -> Self {
+        self.pool_config.connection_timeout = Some(connection_timeout);
+        self
+    }
+
+    /// the `Retry-After` (in seconds) of the requests that failed to get a connection, 1 by default
+    pub fn retry_after(mut self, retry_after: u64) -> Self {
+        self.retry_after = retry_after;
+        self
+    }
+
+    /// the `statement_timeout` of the metastore connections (in milliseconds)
+    pub fn statement_timeout(mut self, statement_timeout: u64) -> Self {
+        self.pool_config.statement_timeout = Some(statement_timeout);
+        self
+    }
+
+    /// idle connections over `pool_min_idle` are closed after this long (in seconds)
+    pub fn idle_timeout(mut self, idle_timeout: u64) -> Self {
+        self.pool_config.idle_timeout = Some(idle_timeout);
+        self
+    }
+
+    /// connections are closed once they are this old (in seconds)
+    pub fn max_lifetime(mut self, max_lifetime: u64) -> Self {
+        self.pool_config.max_lifetime = Some(max_lifetime);
+        self
+    }
+
     pub fn script_path(mut self, script_path: &str) -> Self {
         self.script_path = Some(script_path.to_string());
         self
     }
 
+    /// run the scripts in containers with `engine` (`docker` or `podman`) instead of on the host,
+    /// the images need `python3` and `qjs` respectively
+    pub fn script_containers(mut self, engine: &str, python_image: &str, javascript_image: &str) -> Self {
+        self.script_container = Some(ContainerConfig::new(engine, python_image, javascript_image));
+        self
+    }
+
+    /// let the script containers reach the network, they have none by default
+    pub fn script_container_network(mut self, network: bool) -> Self {
+        if let Some(ref mut script_container) = self.script_container {
+            script_container.network = network;
+        }
+        self
+    }
+
     pub fn token_secret(mut self, token_secret: &str) -> Self {
         self.token_secret = Some(token_secret.to_string());
         self
@@ -112,6 +307,12 @@ impl AppStateBuilder {
         self
     }
 
+    /// the key the script secrets are encrypted with, changing it makes the stored secrets unreadable
+    pub fn secrets_key(mut self, secrets_key: &str) -> Self {
+        self.secrets_key = Some(secrets_key.to_string());
+        self
+    }
+
     pub fn issuer(mut self, issuer: &str) -> Self {
         self.jwt_issuer = Some(issuer.to_string());
         self
@@ -132,33 +333,260 @@ impl AppStateBuilder {
         self
     }
 
+    /// the number of threads running the table data actions and the queries, separate from the
+    /// `num_threads` running the rest so that slow queries don't hold them up.
+    /// Every thread opens its own connections, 0 runs them on the same threads as the rest
+    pub fn query_threads(mut self, query_threads: usize) -> Self {
+        self.query_threads = query_threads;
+        self
+    }
+
+    /// how many table data actions and queries can run on a domain at the same time, the ones
+    /// over it wait on their query thread until one is done. Keep it under the `query_threads`,
+    /// so that the other domains still get a thread, 0 doesn't limit them
+    pub fn max_concurrent_queries(mut self, max_concurrent_queries: usize) -> Self {
+        self.query_limit = Arc::new(QueryLimit::new(max_concurrent_queries));
+        self
+    }
+
+    /// how long (in milliseconds) the http and socket callers wait for an action before they get a timeout error,
+    /// 0 waits for as long as it takes, which is the default
+    pub fn action_timeout(mut self, action_timeout: u64) -> Self {
+        self.action_timeouts.set_default(millis(action_timeout));
+        self
+    }
+
+    /// the `action_timeout` of one procedure, e.g. longer for `runQuery`, 0 never times it out
+    pub fn procedure_timeout(mut self, procedure: &str, timeout: u64) -> Self {
+        self.action_timeouts.set_procedure(procedure, millis(timeout));
+        self
+    }
+
+    /// start the server in read-only mode, can be toggled later on with `setReadOnlyMode`
+    pub fn read_only(mut self, read_only: bool) -> Self {
+        self.read_only = Arc::new(AtomicBool::new(read_only));
+        self
+    }
+
+    /// deliveries slower than this (in milliseconds) are kept for `getBroadcastMetrics`
+    pub fn slow_broadcast_threshold(mut self, slow_threshold: u64) -> Self {
+        self.broadcast_metrics = Arc::new(BroadcastMetrics::new(slow_threshold));
+        self
+    }
+
+    /// apply the retention policies of the tables every `retention_interval` seconds, off by default
+    pub fn retention_interval(mut self, retention_interval: u64) -> Self {
+        self.retention_interval = Some(retention_interval);
+        self
+    }
+
+    /// purge the published messages older than `max_age` seconds, or past the newest `max_per_channel`
+    /// of their channel, none of them keeps the messages forever, which is the default
+    pub fn message_retention(mut self, max_age: Option<u64>, max_per_channel: Option<usize>) -> Self {
+        self.message_retention = MessageRetention { max_age, max_per_channel };
+        self
+    }
+
+    /// how often (in seconds) the messages past the `message_retention` are purged, every hour by default
+    pub fn message_cleanup_interval(mut self, message_cleanup_interval: u64) -> Self {
+        self.message_cleanup_interval = message_cleanup_interval;
+        self
+    }
+
+    /// remove the subscriptions of the users that haven't logged in for `days`, they are kept by default
+    pub fn subscription_inactivity(mut self, days: u64) -> Self {
+        self.subscription_inactivity = Some(days);
+        self
+    }
+
+    /// how often (in seconds) the subscriptions past their ttl or the `subscription_inactivity` are removed,
+    /// every hour by default, 0 keeps them
+    pub fn subscription_cleanup_interval(mut self, subscription_cleanup_interval: u64) -> Self {
+        self.subscription_cleanup_interval = subscription_cleanup_interval;
+        self
+    }
+
+    /// the number of threads running the scripts started with `runScriptAsync`, 0 disables them
+    pub fn script_workers(mut self, script_workers: usize) -> Self {
+        self.script_workers = script_workers;
+        self
+    }
+
+    /// run the queries with a schedule, on by default
+    pub fn schedule_queries(mut self, schedule_queries: bool) -> Self {
+        self.schedule_queries = schedule_queries;
+        self
+    }
+
+    /// run the scripts with an enabled schedule, on by default
+    pub fn schedule_scripts(mut self, schedule_scripts: bool) -> Self {
+        self.schedule_scripts = schedule_scripts;
+        self
+    }
+
+    /// post the messages to the webhooks, on by default
+    /// when it's off the deliveries are still queued, and posted once a server with it on runs
+    pub fn deliver_webhooks(mut self, deliver_webhooks: bool) -> Self {
+        self.deliver_webhooks = deliver_webhooks;
+        self
+    }
+
+    /// mirror every published message into a kafka topic, keyed by its channel, needs the `kafka-sink` feature
+    pub fn kafka_sink(mut self, kafka_sink_config: KafkaSinkConfig) -> Self {
+        self.kafka_sink_config = Some(kafka_sink_config);
+        self
+    }
+
+    /// apply the metastore migrations that aren't in the `version` table when starting, on by default
+    pub fn run_migrations(mut self, run_migrations: bool) -> Self {
+        self.run_migrations = run_migrations;
+        self
+    }
+
+    /// Registers a domain, e.g. an "analytics" and an "app" database behind the same server.
+    /// The entities belong to the domain they were created in, and the requests pick it with
+    /// their `domain` parameter, the table data and the queries are routed to its connections.
+    /// The names have to be unique, `done` fails otherwise
     pub fn add_plugin<HD>(mut self, name: &str, domain_builder: HD) -> Self
         where
             HD: DomainBuilder + 'static,
     {
-        self.domain_builders.insert(name.to_string(), Box::new(domain_builder));
+        if self.domain_builders.contains_key(name) {
+            self.duplicate_domains.push(name.to_string());
+        } else {
+            self.domain_builders.insert(name.to_string(), Box::new(domain_builder));
+        }
         self
     }
 
-    pub fn done(self) -> AppState {
+    /// every executor thread builds its own domains
+    fn build_domains(&self) -> DomainCollection {
+        let mut domains = DomainCollection::new();
+        for (name, domain_builder) in self.domain_builders.iter() {
+            domains.insert(name, domain_builder.build());
+        }
+        domains
+    }
+
+    /// the connection url of the metastore
+    pub fn database_url(&self) -> String {
+        if let Some(ref metastore_url) = self.metastore_url {
+            return self.tls_config.apply(metastore_url);
+        }
+
+        let database_url = format!(
+            "postgres://{}:{}@{}:{}/{}",
+            self.user.clone().unwrap_or_default(),
+            self.pass.clone().unwrap_or_default(),
+            self.host.clone().unwrap_or_default(),
+            self.port.clone().unwrap_or_default(),
+            self.db.clone().unwrap_or_default(),
+        );
+
+        self.tls_config.apply(&database_url)
+    }
+
+    /// applies the metastore migrations, returns the names of the ones that weren't applied yet
+    pub fn migrate(&self) -> Result<Vec<String>, String> {
+        let applied = migrations::run_migrations(&self.database_url())?;
+        for name in applied.iter() {
+            info!("applied migration {}", name);
+        }
+        Ok(applied)
+    }
+
+    pub fn done(mut self) -> Result<AppState, String> {
+        if !self.duplicate_domains.is_empty() {
+            return Err(format!("the domains {} are registered more than once", self.duplicate_domains.join(", ")));
+        }
+
         let token_secret = self.token_secret.clone()
             .expect("Must specify a token secret");
         let password_secret = self.password_secret.clone()
             .expect("Must specify a password secret");
         let threads = self.num_threads;
+        let query_threads = self.query_threads;
+        let broadcast_metrics = self.broadcast_metrics.clone();
+        let action_timeouts = Arc::new(self.action_timeouts.clone());
+        let retention_interval = self.retention_interval;
+        let message_retention = self.message_retention.to_owned();
+        let message_cleanup_interval = self.message_cleanup_interval;
+        let subscription_inactivity = self.subscription_inactivity.map(|days| days * 24 * 60 * 60);
+        let subscription_cleanup_interval = self.subscription_cleanup_interval;
+        let schedule_queries = self.schedule_queries;
+        let schedule_scripts = self.schedule_scripts;
+        let deliver_webhooks = self.deliver_webhooks;
+        let script_jobs = self.script_jobs.clone();
+        let script_workers = self.script_workers;
+        let domain_names: Vec<String> = self.domain_builders.keys().cloned().collect();
+
+        if self.run_migrations {
+            self.migrate()
+                .expect("Could not run the metastore migrations");
+        }
+
+        if let Some(kafka_sink_config) = self.kafka_sink_config.to_owned() {
+            let kafka_sink = KafkaSink::start(&kafka_sink_config)
+                .expect("Could not start the kafka sink");
+            self.kafka_sink = Some(Arc::new(kafka_sink));
+        }
 
         info!("Starting database connection");
+        let builder = Arc::new(self);
+        let executor_builder = builder.clone();
         let connections = SyncArbiter::start(
             threads,
-            move || executor::Executor::create(&self));
+            move || executor::Executor::create(&executor_builder));
+
+        let query_connections = if query_threads > 0 {
+            info!("Starting query executors");
+            SyncArbiter::start(
+                query_threads,
+                move || executor::Executor::create(&builder))
+        } else {
+            connections.clone()
+        };
+
+        if let Some(retention_interval) = retention_interval {
+            RetentionJob::new(connections.clone(), domain_names.to_owned(), Duration::from_secs(retention_interval)).start();
+        }
+
+        if !message_retention.is_unbounded() && message_cleanup_interval > 0 {
+            MessageCleanupJob::new(connections.clone(), message_retention, Duration::from_secs(message_cleanup_interval)).start();
+        }
+
+        if subscription_cleanup_interval > 0 {
+            SubscriptionCleanupJob::new(connections.clone(), subscription_inactivity, Duration::from_secs(subscription_cleanup_interval)).start();
+        }
+
+        if script_workers > 0 {
+            ScriptJobs::start_workers(&script_jobs, script_workers, script_jobs::finish_on_executor(connections.clone()));
+        }
 
+        if deliver_webhooks {
+            WebhookJob::new(connections.clone(), domain_names.to_owned()).start();
+        }
 
+        if schedule_queries || schedule_scripts {
+            SchedulerJob::new(connections.clone(), domain_names, schedule_queries, schedule_scripts).start();
+        }
 
-        AppState {
+        Ok(AppState {
             connections,
+            query_connections,
             token_secret,
             password_secret,
-        }
+            broadcast_metrics,
+            action_timeouts,
+        })
+    }
+}
+
+fn millis(timeout: u64) -> Option<Duration> {
+    if timeout > 0 {
+        Some(Duration::from_millis(timeout))
+    } else {
+        None
     }
 }
 
@@ -167,6 +595,18 @@ impl AppStateLike for AppState {
     fn connect(&self) -> &Addr<executor::Executor> {
         &self.connections
     }
+
+    fn connect_queries(&self) -> &Addr<executor::Executor> {
+        &self.query_connections
+    }
+
+    fn broadcast_metrics(&self) -> &Arc<BroadcastMetrics> {
+        &self.broadcast_metrics
+    }
+
+    fn action_timeout(&self, procedure: &str) -> Option<Duration> {
+        self.action_timeouts.for_procedure(procedure)
+    }
 }
 
 impl GetSecrets for AppState {
@@ -178,3 +618,54 @@ impl GetSecrets for AppState {
         self.password_secret.to_owned()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use kakapo_postgres::KakapoPostgres;
+    #[cfg(feature = "sqlite")]
+    use kakapo_sqlite::KakapoSqlite;
+    #[cfg(feature = "sqlite")]
+    use plugins::v1::DataStoreEntity;
+    #[cfg(feature = "sqlite")]
+    use serde_json::from_value;
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_route_to_domains() {
+        let builder = AppStateBuilder::new()
+            .add_plugin("analytics", KakapoSqlite::new())
+            .add_plugin("app", KakapoSqlite::new());
+        let domains = builder.build_domains();
+
+        let table: DataStoreEntity = from_value(json!({
+            "name": "orders",
+            "description": "",
+            "schema": {
+                "columns": [ { "name": "id", "dataType": "integer" } ],
+                "constraint": [ { "key": "id" } ]
+            }
+        })).unwrap();
+
+        let analytics = domains.get("analytics").unwrap().connect_datastore().unwrap();
+        analytics.on_datastore_created(&table).unwrap();
+        analytics.insert(&table, &json!([{ "id": 1 }])).unwrap();
+        assert!(analytics.retrieve(&table, &json!(null)).is_ok());
+
+        // the table is only in the database of its own domain
+        let app = domains.get("app").unwrap().connect_datastore().unwrap();
+        assert!(app.retrieve(&table, &json!(null)).is_err());
+        assert!(domains.get("reporting").is_none());
+    }
+
+    #[test]
+    fn test_duplicate_domain() {
+        let builder = AppStateBuilder::new()
+            .add_plugin("app", KakapoPostgres::new())
+            .add_plugin("app", KakapoPostgres::new());
+
+        let err = builder.done().err().unwrap();
+        assert_eq!(err, "the domains app are registered more than once");
+    }
+}
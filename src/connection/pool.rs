@@ -0,0 +1,63 @@
+use std::time::Duration;
+
+use diesel::connection::SimpleConnection;
+use diesel::pg::PgConnection;
+use diesel::r2d2;
+use diesel::r2d2::ConnectionManager;
+use diesel::r2d2::CustomizeConnection;
+use diesel::r2d2::Pool;
+use diesel::r2d2::PoolError;
+
+/// The settings of the metastore connection pool, the ones that aren't set keep the r2d2 defaults
+/// (10 connections, 30 seconds to get one, idle for 10 minutes and kept for 30 minutes)
+#[derive(Debug, Clone, Default)]
+pub struct PoolConfig {
+    pub max_size: Option<u32>,
+    pub min_idle: Option<u32>,
+    /// in seconds
+    pub connection_timeout: Option<u64>,
+    /// in milliseconds, set on every connection of the pool
+    pub statement_timeout: Option<u64>,
+    /// in seconds
+    pub idle_timeout: Option<u64>,
+    /// in seconds
+    pub max_lifetime: Option<u64>,
+}
+
+impl PoolConfig {
+    pub fn build(&self, manager: ConnectionManager<PgConnection>) -> Result<Pool<ConnectionManager<PgConnection>>, PoolError> {
+        let mut builder = Pool::builder();
+
+        if let Some(max_size) = self.max_size {
+            builder = builder.max_size(max_size);
+        }
+        if let Some(min_idle) = self.min_idle {
+            builder = builder.min_idle(Some(min_idle));
+        }
+        if let Some(connection_timeout) = self.connection_timeout {
+            builder = builder.connection_timeout(Duration::from_secs(connection_timeout));
+        }
+        if let Some(idle_timeout) = self.idle_timeout {
+            builder = builder.idle_timeout(Some(Duration::from_secs(idle_timeout)));
+        }
+        if let Some(max_lifetime) = self.max_lifetime {
+            builder = builder.max_lifetime(Some(Duration::from_secs(max_lifetime)));
+        }
+        if let Some(statement_timeout) = self.statement_timeout {
+            builder = builder.connection_customizer(Box::new(StatementTimeout(statement_timeout)));
+        }
+
+        builder.build(manager)
+    }
+}
+
+/// sets the timeout when the connection is opened, so that it isn't sent on every checkout
+#[derive(Debug)]
+struct StatementTimeout(u64);
+
+impl CustomizeConnection<PgConnection, r2d2::Error> for StatementTimeout {
+    fn on_acquire(&self, conn: &mut PgConnection) -> Result<(), r2d2::Error> {
+        conn.batch_execute(&format!("SET statement_timeout = {}", self.0))
+            .map_err(r2d2::Error::QueryError)
+    }
+}
@@ -0,0 +1,141 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::Condvar;
+use std::sync::Mutex;
+use std::sync::MutexGuard;
+
+/// Bounds the actions running the queries of a domain at the same time, shared by all the executors.
+/// Each executor has its own connection pool for a domain, so without it a burst of slow queries on
+/// one domain can take every connection of the database and leave none for the other domains.
+/// The actions over the limit wait for one of the running ones to finish
+#[derive(Debug, Default)]
+pub struct QueryLimit {
+    /// 0 doesn't limit them
+    max_per_domain: usize,
+    running: Mutex<HashMap<String, usize>>,
+    released: Condvar,
+}
+
+impl QueryLimit {
+    pub fn new(max_per_domain: usize) -> Self {
+        Self {
+            max_per_domain,
+            running: Mutex::new(HashMap::new()),
+            released: Condvar::new(),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<HashMap<String, usize>> {
+        // the counts are only incremented and decremented, so they are still usable after a panic
+        self.running.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    fn is_full(&self, running: &HashMap<String, usize>, domain_name: &str) -> bool {
+        let count = running.get(domain_name).cloned().unwrap_or(0);
+        self.max_per_domain > 0 && count >= self.max_per_domain
+    }
+
+    /// The query counts against the limit until the returned guard is dropped, if the domain
+    /// already runs as many queries as it is allowed to, this blocks until one of them is done
+    pub fn acquire(query_limit: &Arc<Self>, domain_name: &str) -> QueryLimitGuard {
+        let mut running = query_limit.lock();
+        if query_limit.is_full(&running, domain_name) {
+            debug!("domain {} already runs {} queries, waiting for one to finish", domain_name, query_limit.max_per_domain);
+        }
+        while query_limit.is_full(&running, domain_name) {
+            running = query_limit.released
+                .wait(running)
+                .unwrap_or_else(|err| err.into_inner());
+        }
+        *running.entry(domain_name.to_owned()).or_insert(0) += 1;
+
+        QueryLimitGuard {
+            domain_name: domain_name.to_owned(),
+            query_limit: query_limit.clone(),
+        }
+    }
+
+    pub fn running(&self, domain_name: &str) -> usize {
+        self.lock().get(domain_name).cloned().unwrap_or(0)
+    }
+
+    fn release(&self, domain_name: &str) {
+        let mut running = self.lock();
+        let remove = match running.get_mut(domain_name) {
+            Some(count) => {
+                *count = count.saturating_sub(1);
+                *count == 0
+            },
+            None => false,
+        };
+        if remove {
+            running.remove(domain_name);
+        }
+        // the waiting actions can be on any domain, they check their own count
+        self.released.notify_all();
+    }
+}
+
+pub struct QueryLimitGuard {
+    domain_name: String,
+    query_limit: Arc<QueryLimit>,
+}
+
+impl Drop for QueryLimitGuard {
+    fn drop(&mut self) {
+        self.query_limit.release(&self.domain_name);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::sync::mpsc;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn test_query_limit() {
+        let query_limit = Arc::new(QueryLimit::new(2));
+
+        let first = QueryLimit::acquire(&query_limit, "one");
+        let _second = QueryLimit::acquire(&query_limit, "one");
+        assert_eq!(query_limit.running("one"), 2);
+        // the other domains have their own limit
+        let _other = QueryLimit::acquire(&query_limit, "two");
+        assert_eq!(query_limit.running("two"), 1);
+
+        drop(first);
+        assert_eq!(query_limit.running("one"), 1);
+        let _third = QueryLimit::acquire(&query_limit, "one");
+        assert_eq!(query_limit.running("one"), 2);
+    }
+
+    #[test]
+    fn test_query_limit_waits() {
+        let query_limit = Arc::new(QueryLimit::new(1));
+        let first = QueryLimit::acquire(&query_limit, "one");
+
+        let (sender, receiver) = mpsc::channel();
+        let waiting_limit = query_limit.clone();
+        let waiting = thread::spawn(move || {
+            let _guard = QueryLimit::acquire(&waiting_limit, "one");
+            sender.send(()).unwrap();
+        });
+
+        // queued instead of rejected, until the running query is done
+        assert!(receiver.recv_timeout(Duration::from_millis(100)).is_err());
+        drop(first);
+        assert!(receiver.recv_timeout(Duration::from_secs(5)).is_ok());
+        waiting.join().unwrap();
+        assert_eq!(query_limit.running("one"), 0);
+    }
+
+    #[test]
+    fn test_query_limit_unlimited() {
+        let query_limit = Arc::new(QueryLimit::new(0));
+        let _guards: Vec<_> = (0..10).map(|_| QueryLimit::acquire(&query_limit, "one")).collect();
+        assert_eq!(query_limit.running("one"), 10);
+    }
+}
@@ -0,0 +1,139 @@
+use serde_json::Value;
+
+/// the values longer than this are replaced by their size in the audit trail, e.g. the rows of a bulk change
+const MAX_SUMMARY_LENGTH: usize = 2048;
+
+/// What a mutating call was made on, e.g. the table `users`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditTarget {
+    /// e.g. `table`, `user` or `role`
+    pub entity_type: Option<String>,
+    pub entity_name: Option<String>,
+}
+
+impl AuditTarget {
+    pub fn new(entity_type: &str, entity_name: &str) -> Self {
+        Self {
+            entity_type: Some(entity_type.to_owned()),
+            entity_name: Some(entity_name.to_owned()),
+        }
+    }
+
+    /// for the calls that aren't on one entity, e.g. `setReadOnlyMode`
+    pub fn none() -> Self {
+        Self::default()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum AuditOutcome {
+    Succeeded,
+    Failed,
+}
+
+impl AuditOutcome {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditOutcome::Succeeded => "succeeded",
+            AuditOutcome::Failed => "failed",
+        }
+    }
+
+    pub fn from_str(outcome: &str) -> Option<Self> {
+        match outcome {
+            "succeeded" => Some(AuditOutcome::Succeeded),
+            "failed" => Some(AuditOutcome::Failed),
+            _ => None,
+        }
+    }
+}
+
+/// A call to record, see `WithAudit`
+#[derive(Debug, Clone, PartialEq)]
+pub struct NewAuditEntry {
+    pub username: Option<String>,
+    pub procedure: String,
+    pub target: AuditTarget,
+    pub domain: Option<String>,
+    pub request_id: Option<String>,
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+    pub outcome: AuditOutcome,
+    pub error: Option<String>,
+}
+
+/// A recorded call, see `GetAuditTrail`
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditEntry {
+    pub id: i64,
+    pub username: Option<String>,
+    pub procedure: String,
+    #[serde(flatten)]
+    pub target: AuditTarget,
+    pub domain: Option<String>,
+    pub request_id: Option<String>,
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+    pub outcome: AuditOutcome,
+    pub error: Option<String>,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+/// Narrows down the audit trail, the unset fields match every entry
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AuditFilter {
+    pub username: Option<String>,
+    pub procedure: Option<String>,
+    pub domain: Option<String>,
+    pub entity_type: Option<String>,
+    pub entity_name: Option<String>,
+}
+
+/// The value as it is kept in the audit trail, the large ones are only counted
+pub fn summarize(value: &Value) -> Value {
+    let length = value.to_string().len();
+    if length <= MAX_SUMMARY_LENGTH {
+        return value.to_owned();
+    }
+
+    match value {
+        Value::Array(items) => json!({ "truncated": true, "count": items.len() }),
+        _ => json!({ "truncated": true, "size": length }),
+    }
+}
+
+/// The state before and after a call, from its result. The results of the entity changes have the
+/// `old` and the `new` entity, the other results are the state after the call
+pub fn before_and_after(result: &Value) -> (Option<Value>, Option<Value>) {
+    let before = result.get("old").map(summarize);
+    let after = match result.get("new") {
+        Some(new) => Some(summarize(new)),
+        None if before.is_some() => None,
+        None => Some(summarize(result)),
+    };
+
+    (before, after)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_before_and_after() {
+        let updated = json!({ "result": "updated", "old": { "name": "a" }, "new": { "name": "b" } });
+        assert_eq!(before_and_after(&updated), (Some(json!({ "name": "a" })), Some(json!({ "name": "b" }))));
+
+        let deleted = json!({ "result": "deleted", "id": "a", "old": { "name": "a" } });
+        assert_eq!(before_and_after(&deleted), (Some(json!({ "name": "a" })), None));
+
+        let inserted = json!([{ "id": 1 }]);
+        assert_eq!(before_and_after(&inserted), (None, Some(json!([{ "id": 1 }]))));
+
+        let rows: Vec<Value> = (0..1000).map(|x| json!({ "id": x })).collect();
+        assert_eq!(summarize(&json!(rows)), json!({ "truncated": true, "count": 1000 }));
+    }
+}
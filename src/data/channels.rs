@@ -9,7 +9,10 @@ pub enum Defaults {
     Query(String),
     Script(String),
     View(String),
+    Webhook(String),
     TableData(String), //TODO: this is tricky since the filter / query can go in as well
+    /// only for the user with this username, e.g. the progress of their imports
+    User(String),
 }
 
 //A little bit messy as there isn't currently a way in serde to organize this
@@ -32,6 +35,20 @@ pub enum Channels {
 pub struct Subscription {
     pub user: User,
     pub channel: Channels,
+    /// kept when the session closes, its messages are delivered until they are acknowledged
+    #[serde(default)]
+    pub durable: bool,
+    /// removed by the subscription cleanup after this, none for the subscriptions that don't expire
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<chrono::NaiveDateTime>,
+}
+
+/// A channel the user can subscribe to, see `ListChannels`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChannelInfo {
+    pub channel: Channels,
+    /// the users subscribed to it, a user with a few sessions is counted once
+    pub subscribers: i64,
 }
 
 pub trait GetEntityChannel {
@@ -51,6 +68,10 @@ impl Channels {
     pub fn table(table_name: &str) -> Self {
         Channels::Defaults(Defaults::TableData(table_name.to_string()))
     }
+
+    pub fn user(username: &str) -> Self {
+        Channels::Defaults(Defaults::User(username.to_string()))
+    }
 }
 
 
@@ -69,5 +90,8 @@ mod test {
 
         let repr = serde_json::to_value(&channel).unwrap();
         assert_eq!(repr, json!({"type": "subscribers", "table": "test"}));
+
+        let repr = serde_json::to_value(&Channels::user("bob")).unwrap();
+        assert_eq!(repr, json!({"user": "bob"}));
     }
 }
\ No newline at end of file
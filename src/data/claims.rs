@@ -10,7 +10,29 @@ pub struct AuthClaims {
     pub role: Option<String>, //the default role that the user is interacting with
 }
 
+/// username of the jobs the server runs by itself
+pub const SYSTEM_USERNAME: &str = "kakapo";
+
 impl AuthClaims {
+    /// Claims of the background jobs, they act as an admin and are never encoded into a token
+    pub fn system() -> Self {
+        let now = chrono::Utc::now().timestamp();
+        Self {
+            iss: SYSTEM_USERNAME.to_string(),
+            sub: 0,
+            iat: now,
+            exp: now,
+            username: SYSTEM_USERNAME.to_string(),
+            is_admin: true,
+            role: None,
+        }
+    }
+
+    /// the system claims don't belong to any user
+    pub fn is_system(&self) -> bool {
+        self.sub == 0
+    }
+
     pub fn get_user_id(&self) -> i64 {
         self.sub
     }
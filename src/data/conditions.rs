@@ -0,0 +1,449 @@
+
+use std::fmt;
+use std::hash::Hash;
+use std::hash::Hasher;
+
+use serde::de;
+use serde::Deserialize;
+use serde::Deserializer;
+use serde::Serialize;
+use serde::Serializer;
+use serde_json;
+
+use data::claims::AuthClaims;
+
+/// Small expression language for attribute based permissions
+/// e.g. `claims.role == entity.tag("team") && entity.type == "table"`
+#[derive(Debug, Clone, PartialEq)]
+pub enum Condition {
+    Literal(serde_json::Value),
+    Claim(String),
+    EntityName,
+    EntityType,
+    EntityTag(String),
+    Equals(Box<Condition>, Box<Condition>),
+    NotEqual(Box<Condition>, Box<Condition>),
+    And(Box<Condition>, Box<Condition>),
+    Or(Box<Condition>, Box<Condition>),
+    Not(Box<Condition>),
+}
+
+#[derive(Debug, Fail, PartialEq, Eq)]
+pub enum ConditionError {
+    #[fail(display = "Unexpected token {} at {}", 0, 1)]
+    UnexpectedToken(String, usize),
+    #[fail(display = "Unexpected end of condition")]
+    UnexpectedEnd,
+    #[fail(display = "Unknown identifier {}", 0)]
+    UnknownIdentifier(String),
+}
+
+/// The target of the permission, only entities can have tags
+#[derive(Debug, Clone, Default)]
+pub struct ConditionTarget {
+    pub type_name: Option<String>,
+    pub entity_name: Option<String>,
+    /// tags are stored as `key:value`, a tag without a value is treated as `key:`
+    pub tags: Vec<String>,
+}
+
+pub struct ConditionContext<'a> {
+    pub claims: &'a Option<AuthClaims>,
+    pub target: &'a ConditionTarget,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Str(String),
+    Number(i64),
+    Eq,
+    NotEq,
+    And,
+    Or,
+    Not,
+    LParen,
+    RParen,
+}
+
+fn tokenize(input: &str) -> Result<Vec<(Token, usize)>, ConditionError> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = vec![];
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let start = i;
+        match c {
+            ' ' | '\t' | '\n' | '\r' => { i += 1; continue; },
+            '(' => { tokens.push((Token::LParen, start)); i += 1; },
+            ')' => { tokens.push((Token::RParen, start)); i += 1; },
+            '=' | '!' | '&' | '|' => {
+                let next = chars.get(i + 1).cloned();
+                let token = match (c, next) {
+                    ('=', Some('=')) => { i += 2; Token::Eq },
+                    ('!', Some('=')) => { i += 2; Token::NotEq },
+                    ('&', Some('&')) => { i += 2; Token::And },
+                    ('|', Some('|')) => { i += 2; Token::Or },
+                    ('!', _) => { i += 1; Token::Not },
+                    _ => return Err(ConditionError::UnexpectedToken(c.to_string(), start)),
+                };
+                tokens.push((token, start));
+            },
+            '"' | '\'' => {
+                let quote = c;
+                i += 1;
+                let mut value = String::new();
+                loop {
+                    match chars.get(i) {
+                        None => return Err(ConditionError::UnexpectedEnd),
+                        Some('\\') => {
+                            if let Some(escaped) = chars.get(i + 1) {
+                                value.push(*escaped);
+                            }
+                            i += 2;
+                        },
+                        Some(x) if *x == quote => { i += 1; break; },
+                        Some(x) => { value.push(*x); i += 1; },
+                    }
+                }
+                tokens.push((Token::Str(value), start));
+            },
+            x if x.is_ascii_digit() || x == '-' => {
+                let mut value = x.to_string();
+                i += 1;
+                while let Some(d) = chars.get(i).filter(|d| d.is_ascii_digit()) {
+                    value.push(*d);
+                    i += 1;
+                }
+                let number = value.parse::<i64>()
+                    .map_err(|_| ConditionError::UnexpectedToken(value.to_owned(), start))?;
+                tokens.push((Token::Number(number), start));
+            },
+            x if x.is_alphabetic() || x == '_' => {
+                let mut value = String::new();
+                while let Some(d) = chars.get(i).filter(|d| d.is_alphanumeric() || **d == '_' || **d == '.') {
+                    value.push(*d);
+                    i += 1;
+                }
+                tokens.push((Token::Ident(value), start));
+            },
+            x => return Err(ConditionError::UnexpectedToken(x.to_string(), start)),
+        }
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(token, _)| token)
+    }
+
+    fn next(&mut self) -> Result<(Token, usize), ConditionError> {
+        let token = self.tokens.get(self.pos).cloned().ok_or(ConditionError::UnexpectedEnd)?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<(), ConditionError> {
+        let (token, at) = self.next()?;
+        if token == expected {
+            Ok(())
+        } else {
+            Err(ConditionError::UnexpectedToken(format!("{:?}", token), at))
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<Condition, ConditionError> {
+        let mut lhs = self.parse_and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.pos += 1;
+            let rhs = self.parse_and()?;
+            lhs = Condition::Or(Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn parse_and(&mut self) -> Result<Condition, ConditionError> {
+        let mut lhs = self.parse_unary()?;
+        while self.peek() == Some(&Token::And) {
+            self.pos += 1;
+            let rhs = self.parse_unary()?;
+            lhs = Condition::And(Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn parse_unary(&mut self) -> Result<Condition, ConditionError> {
+        if self.peek() == Some(&Token::Not) {
+            self.pos += 1;
+            let inner = self.parse_unary()?;
+            return Ok(Condition::Not(Box::new(inner)));
+        }
+        self.parse_comparison()
+    }
+
+    fn parse_comparison(&mut self) -> Result<Condition, ConditionError> {
+        let lhs = self.parse_atom()?;
+        match self.peek() {
+            Some(Token::Eq) => {
+                self.pos += 1;
+                let rhs = self.parse_atom()?;
+                Ok(Condition::Equals(Box::new(lhs), Box::new(rhs)))
+            },
+            Some(Token::NotEq) => {
+                self.pos += 1;
+                let rhs = self.parse_atom()?;
+                Ok(Condition::NotEqual(Box::new(lhs), Box::new(rhs)))
+            },
+            _ => Ok(lhs),
+        }
+    }
+
+    fn parse_atom(&mut self) -> Result<Condition, ConditionError> {
+        let (token, at) = self.next()?;
+        match token {
+            Token::LParen => {
+                let inner = self.parse_or()?;
+                self.expect(Token::RParen)?;
+                Ok(inner)
+            },
+            Token::Str(x) => Ok(Condition::Literal(json!(x))),
+            Token::Number(x) => Ok(Condition::Literal(json!(x))),
+            Token::Ident(ident) => self.parse_identifier(ident),
+            x => Err(ConditionError::UnexpectedToken(format!("{:?}", x), at)),
+        }
+    }
+
+    fn parse_identifier(&mut self, ident: String) -> Result<Condition, ConditionError> {
+        match ident.as_str() {
+            "true" => return Ok(Condition::Literal(json!(true))),
+            "false" => return Ok(Condition::Literal(json!(false))),
+            "null" => return Ok(Condition::Literal(serde_json::Value::Null)),
+            "entity.name" => return Ok(Condition::EntityName),
+            "entity.type" => return Ok(Condition::EntityType),
+            "entity.tag" => {
+                self.expect(Token::LParen)?;
+                let (token, at) = self.next()?;
+                let tag = match token {
+                    Token::Str(x) => x,
+                    x => return Err(ConditionError::UnexpectedToken(format!("{:?}", x), at)),
+                };
+                self.expect(Token::RParen)?;
+                return Ok(Condition::EntityTag(tag));
+            },
+            _ => {},
+        };
+
+        if ident.starts_with("claims.") {
+            let claim = ident["claims.".len()..].to_owned();
+            Ok(Condition::Claim(claim))
+        } else {
+            Err(ConditionError::UnknownIdentifier(ident))
+        }
+    }
+}
+
+impl Condition {
+    pub fn parse(input: &str) -> Result<Self, ConditionError> {
+        let tokens = tokenize(input)?;
+        let mut parser = Parser { tokens, pos: 0 };
+        let condition = parser.parse_or()?;
+
+        match parser.tokens.get(parser.pos) {
+            Some((token, at)) => Err(ConditionError::UnexpectedToken(format!("{:?}", token), *at)),
+            None => Ok(condition),
+        }
+    }
+
+    fn value(&self, ctx: &ConditionContext) -> serde_json::Value {
+        match self {
+            Condition::Literal(x) => x.to_owned(),
+            Condition::Claim(claim) => ctx.claims
+                .as_ref()
+                .and_then(|claims| serde_json::to_value(claims).ok())
+                .map(|claims| claims[claim.as_str()].to_owned())
+                .unwrap_or_default(),
+            Condition::EntityName => json!(ctx.target.entity_name),
+            Condition::EntityType => json!(ctx.target.type_name),
+            Condition::EntityTag(key) => {
+                let prefix = format!("{}:", key);
+                ctx.target.tags
+                    .iter()
+                    .find(|tag| tag.starts_with(&prefix) || *tag == key)
+                    .map(|tag| json!(tag.get(prefix.len()..).unwrap_or("")))
+                    .unwrap_or_default()
+            },
+            _ => json!(self.evaluate(ctx)),
+        }
+    }
+
+    pub fn evaluate(&self, ctx: &ConditionContext) -> bool {
+        match self {
+            Condition::Equals(lhs, rhs) => {
+                let lhs = lhs.value(ctx);
+                !lhs.is_null() && lhs == rhs.value(ctx)
+            },
+            Condition::NotEqual(lhs, rhs) => lhs.value(ctx) != rhs.value(ctx),
+            Condition::And(lhs, rhs) => lhs.evaluate(ctx) && rhs.evaluate(ctx),
+            Condition::Or(lhs, rhs) => lhs.evaluate(ctx) || rhs.evaluate(ctx),
+            Condition::Not(inner) => !inner.evaluate(ctx),
+            x => match x.value(ctx) {
+                serde_json::Value::Bool(x) => x,
+                serde_json::Value::Null => false,
+                _ => true,
+            },
+        }
+    }
+}
+
+/// The condition of a `Permission::Conditional`, it is parsed when the permission is deserialized so that
+/// an invalid condition is rejected when the permission is added, and isn't parsed again on every check.
+/// Compared and serialized as the text it was parsed from
+#[derive(Debug, Clone)]
+pub struct PermissionCondition {
+    text: String,
+    condition: Condition,
+}
+
+impl PermissionCondition {
+    pub fn parse(text: &str) -> Result<Self, ConditionError> {
+        Ok(Self {
+            text: text.to_owned(),
+            condition: Condition::parse(text)?,
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+
+    pub fn evaluate(&self, ctx: &ConditionContext) -> bool {
+        self.condition.evaluate(ctx)
+    }
+}
+
+impl PartialEq for PermissionCondition {
+    fn eq(&self, other: &Self) -> bool {
+        self.text == other.text
+    }
+}
+
+impl Eq for PermissionCondition {}
+
+impl Hash for PermissionCondition {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.text.hash(state)
+    }
+}
+
+impl Serialize for PermissionCondition {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where S: Serializer
+    {
+        serializer.serialize_str(&self.text)
+    }
+}
+
+impl<'de> Deserialize<'de> for PermissionCondition {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where D: Deserializer<'de>
+    {
+        let text = String::deserialize(deserializer)?;
+        PermissionCondition::parse(&text)
+            .map_err(|err| de::Error::custom(format!("invalid condition {:?}: {}", &text, err)))
+    }
+}
+
+impl fmt::Display for PermissionCondition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.text)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn claims(role: Option<&str>) -> Option<AuthClaims> {
+        Some(AuthClaims {
+            iss: "test".to_string(),
+            sub: 2,
+            iat: 0,
+            exp: 0,
+            username: "bob".to_string(),
+            is_admin: false,
+            role: role.map(|x| x.to_string()),
+        })
+    }
+
+    fn target() -> ConditionTarget {
+        ConditionTarget {
+            type_name: Some("table".to_string()),
+            entity_name: Some("sales".to_string()),
+            tags: vec!["team:alpha".to_string(), "archived".to_string()],
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        let condition = Condition::parse(r#"claims.role == entity.tag("team")"#).unwrap();
+        assert_eq!(condition, Condition::Equals(
+            Box::new(Condition::Claim("role".to_string())),
+            Box::new(Condition::EntityTag("team".to_string()))));
+
+        assert!(Condition::parse("claims.role ==").is_err());
+        assert!(Condition::parse("user.role == 'a'").is_err());
+        assert!(Condition::parse("(claims.role == 'a'").is_err());
+    }
+
+    #[test]
+    fn test_evaluate_tag() {
+        let condition = Condition::parse(r#"claims.role == entity.tag("team")"#).unwrap();
+        let target = target();
+
+        let claims_alpha = claims(Some("alpha"));
+        let ctx = ConditionContext { claims: &claims_alpha, target: &target };
+        assert!(condition.evaluate(&ctx));
+
+        let claims_beta = claims(Some("beta"));
+        let ctx = ConditionContext { claims: &claims_beta, target: &target };
+        assert!(!condition.evaluate(&ctx));
+
+        // missing values never compare equal
+        let claims_none = claims(None);
+        let ctx = ConditionContext { claims: &claims_none, target: &ConditionTarget::default() };
+        assert!(!condition.evaluate(&ctx));
+    }
+
+    #[test]
+    fn test_evaluate_compound() {
+        let condition = Condition::parse(
+            r#"(entity.type == "table" && !entity.tag("archived")) || claims.username == "admin""#).unwrap();
+        let claims = claims(None);
+        let target = target();
+        let ctx = ConditionContext { claims: &claims, target: &target };
+        assert!(!condition.evaluate(&ctx));
+
+        let condition = Condition::parse(r#"entity.name == "sales" && claims.sub == 2"#).unwrap();
+        assert!(condition.evaluate(&ctx));
+    }
+
+    #[test]
+    fn test_permission_condition() {
+        let condition: PermissionCondition = serde_json::from_value(json!(r#"claims.role == entity.tag("team")"#)).unwrap();
+        assert_eq!(serde_json::to_value(&condition).unwrap(), json!(r#"claims.role == entity.tag("team")"#));
+
+        let claims_alpha = claims(Some("alpha"));
+        let target = target();
+        assert!(condition.evaluate(&ConditionContext { claims: &claims_alpha, target: &target }));
+
+        assert!(serde_json::from_value::<PermissionCondition>(json!("claims.role ==")).is_err());
+    }
+}
@@ -0,0 +1,171 @@
+
+use chrono::Datelike;
+use chrono::Timelike;
+
+/// A five field cron expression, `minute hour day-of-month month day-of-week`
+///
+/// Every field is `*`, a number, a range `a-b`, or a list of those, each with an optional step
+/// like `*/15`, the nicknames `@hourly`, `@daily`, `@weekly`, `@monthly` and `@yearly` are accepted as well
+/// As in cron, a time matches if either of the days matches when both of them are restricted
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CronSchedule {
+    minutes: Vec<u32>,
+    hours: Vec<u32>,
+    days_of_month: Vec<u32>,
+    months: Vec<u32>,
+    /// sunday is 0
+    days_of_week: Vec<u32>,
+    any_day_of_month: bool,
+    any_day_of_week: bool,
+}
+
+fn parse_number(value: &str, min: u32, max: u32) -> Result<u32, String> {
+    value
+        .parse::<u32>()
+        .ok()
+        .filter(|x| *x >= min && *x <= max)
+        .ok_or_else(|| format!("`{}` is not a number between {} and {}", value, min, max))
+}
+
+/// All the values of a field, sorted
+fn parse_field(field: &str, min: u32, max: u32) -> Result<Vec<u32>, String> {
+    let mut values = vec![];
+
+    for part in field.split(',') {
+        let (range, step) = match part.find('/') {
+            Some(idx) => (&part[..idx], parse_number(&part[idx + 1..], 1, max)?),
+            None => (part, 1),
+        };
+
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some(idx) = range.find('-') {
+            let start = parse_number(&range[..idx], min, max)?;
+            let end = parse_number(&range[idx + 1..], min, max)?;
+            if start > end {
+                return Err(format!("`{}` is not a valid range", range));
+            }
+            (start, end)
+        } else {
+            let start = parse_number(range, min, max)?;
+            // `5/10` starts at 5 and goes on until the end
+            if part.contains('/') { (start, max) } else { (start, start) }
+        };
+
+        values.extend((start..end + 1).step_by(step as usize));
+    }
+
+    values.sort();
+    values.dedup();
+    Ok(values)
+}
+
+impl CronSchedule {
+    pub fn parse(expression: &str) -> Result<Self, String> {
+        let expression = match expression.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            x => x,
+        };
+
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        if fields.len() != 5 {
+            return Err(format!("expected 5 fields in the cron expression, got {}", fields.len()));
+        }
+
+        // 7 is sunday as well
+        let mut days_of_week: Vec<u32> = parse_field(fields[4], 0, 7)?
+            .into_iter()
+            .map(|x| x % 7)
+            .collect();
+        days_of_week.sort();
+        days_of_week.dedup();
+
+        Ok(Self {
+            minutes: parse_field(fields[0], 0, 59)?,
+            hours: parse_field(fields[1], 0, 23)?,
+            days_of_month: parse_field(fields[2], 1, 31)?,
+            months: parse_field(fields[3], 1, 12)?,
+            days_of_week,
+            any_day_of_month: fields[2].starts_with('*'),
+            any_day_of_week: fields[4].starts_with('*'),
+        })
+    }
+
+    /// Whether the schedule runs in the minute of the given time
+    pub fn matches(&self, time: &chrono::NaiveDateTime) -> bool {
+        let day_of_month = self.days_of_month.contains(&time.day());
+        let day_of_week = self.days_of_week.contains(&time.weekday().num_days_from_sunday());
+        let day = match (self.any_day_of_month, self.any_day_of_week) {
+            (false, false) => day_of_month || day_of_week,
+            _ => day_of_month && day_of_week,
+        };
+
+        day &&
+            self.minutes.contains(&time.minute()) &&
+            self.hours.contains(&time.hour()) &&
+            self.months.contains(&time.month())
+    }
+
+    /// The latest minute in `(after, until]` where the schedule runs, `until` is expected to be a whole minute
+    pub fn latest_match(&self, after: &chrono::NaiveDateTime, until: &chrono::NaiveDateTime) -> Option<chrono::NaiveDateTime> {
+        let mut time = *until;
+        while time > *after {
+            if self.matches(&time) {
+                return Some(time);
+            }
+            time = time - chrono::Duration::minutes(1);
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn at(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> chrono::NaiveDateTime {
+        chrono::NaiveDate::from_ymd(year, month, day).and_hms(hour, minute, 0)
+    }
+
+    #[test]
+    fn test_parse_field() {
+        assert_eq!(parse_field("*/15", 0, 59).unwrap(), vec![0, 15, 30, 45]);
+        assert_eq!(parse_field("1-3,10", 0, 59).unwrap(), vec![1, 2, 3, 10]);
+        assert_eq!(parse_field("50/5", 0, 59).unwrap(), vec![50, 55]);
+        assert_eq!(parse_field("3,1,3", 0, 59).unwrap(), vec![1, 3]);
+        assert!(parse_field("60", 0, 59).is_err());
+        assert!(parse_field("5-1", 0, 59).is_err());
+        assert!(parse_field("*/0", 0, 59).is_err());
+        assert!(parse_field("a", 0, 59).is_err());
+    }
+
+    #[test]
+    fn test_cron_schedule() {
+        let schedule = CronSchedule::parse("30 9 * * 1-5").unwrap();
+        assert!(schedule.matches(&at(2019, 4, 19, 9, 30))); // friday
+        assert!(!schedule.matches(&at(2019, 4, 20, 9, 30))); // saturday
+        assert!(!schedule.matches(&at(2019, 4, 19, 9, 31)));
+
+        // either of the days
+        let schedule = CronSchedule::parse("0 0 1 * 0").unwrap();
+        assert!(schedule.matches(&at(2019, 4, 1, 0, 0))); // monday
+        assert!(schedule.matches(&at(2019, 4, 21, 0, 0))); // sunday
+        assert!(!schedule.matches(&at(2019, 4, 22, 0, 0)));
+
+        let schedule = CronSchedule::parse("@weekly").unwrap();
+        assert_eq!(schedule, CronSchedule::parse("0 0 * * 7").unwrap());
+
+        let schedule = CronSchedule::parse("0 3 * * *").unwrap();
+        assert_eq!(schedule.latest_match(&at(2019, 4, 18, 12, 0), &at(2019, 4, 20, 2, 59)), Some(at(2019, 4, 19, 3, 0)));
+        assert_eq!(schedule.latest_match(&at(2019, 4, 19, 3, 0), &at(2019, 4, 20, 2, 59)), None);
+        assert_eq!(schedule.latest_match(&at(2019, 4, 19, 12, 0), &at(2019, 4, 20, 3, 0)), Some(at(2019, 4, 20, 3, 0)));
+
+        assert!(CronSchedule::parse("* * * *").is_err());
+        assert!(CronSchedule::parse("* * 0 * *").is_err());
+    }
+}
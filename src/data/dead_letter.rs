@@ -0,0 +1,48 @@
+use serde_json::Value;
+
+use data::channels::Channels;
+
+/// a message of a durable subscription is dead after it was sent this many times without being acknowledged
+pub const MAX_PENDING_ATTEMPTS: i32 = 10;
+/// a message sent to a durable subscriber is sent again if it isn't acknowledged within this, in seconds
+pub const ACK_TIMEOUT_SECS: u64 = 60;
+
+/// Who the message couldn't be delivered to
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+#[serde(tag = "type")]
+pub enum DeadLetterTarget {
+    /// every attempt of the delivery failed, see `GetWebhookDeliveries`
+    #[serde(rename_all = "camelCase")]
+    Webhook { delivery_id: i64 },
+    /// the durable subscriber never acknowledged it
+    #[serde(rename_all = "camelCase")]
+    Subscriber { username: String },
+}
+
+/// A message that couldn't be delivered, kept until an admin redelivers it, see `RedeliverDeadLetter`
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeadLetter {
+    pub id: i64,
+    pub channel: Channels,
+    pub data: Value,
+    pub target: DeadLetterTarget,
+    pub attempts: i32,
+    pub last_error: Option<String>,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_serialize_target() {
+        let target = DeadLetterTarget::Webhook { delivery_id: 3 };
+        assert_eq!(serde_json::to_value(&target).unwrap(), json!({ "type": "webhook", "deliveryId": 3 }));
+
+        let target = DeadLetterTarget::Subscriber { username: "bob".to_string() };
+        assert_eq!(serde_json::to_value(&target).unwrap(), json!({ "type": "subscriber", "username": "bob" }));
+    }
+}
@@ -0,0 +1,269 @@
+
+/// A lexical token of a query statement, the literals and comments are dropped
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Token {
+    /// lowercased, unless it was quoted
+    Identifier { name: String, quoted: bool },
+    Symbol(char),
+}
+
+/// the words that can't be an alias or a function name
+const KEYWORDS: [&str; 38] = [
+    "select", "from", "where", "join", "inner", "left", "right", "full", "outer", "cross", "natural",
+    "on", "using", "as", "in", "exists", "not", "and", "or", "any", "all", "some", "group", "order",
+    "having", "limit", "offset", "union", "intersect", "except", "with", "recursive", "lateral", "only",
+    "into", "update", "set", "values",
+];
+
+fn is_keyword(token: &Token) -> bool {
+    match token {
+        Token::Identifier { name, quoted: false } => KEYWORDS.contains(&name.as_str()),
+        _ => false,
+    }
+}
+
+fn is_word(token: Option<&Token>, word: &str) -> bool {
+    match token {
+        Some(Token::Identifier { name, quoted: false }) => name == word,
+        _ => false,
+    }
+}
+
+fn tokenize(statement: &str) -> Vec<Token> {
+    let chars: Vec<char> = statement.chars().collect();
+    let mut tokens = vec![];
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).cloned();
+
+        if c.is_whitespace() {
+            i += 1;
+        } else if c == '-' && next == Some('-') {
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+        } else if c == '/' && next == Some('*') {
+            i += 2;
+            while i < chars.len() && !(chars[i] == '*' && chars.get(i + 1) == Some(&'/')) {
+                i += 1;
+            }
+            i += 2;
+        } else if c == '\'' {
+            // a doubled quote is part of the string
+            i += 1;
+            while i < chars.len() {
+                if chars[i] == '\'' && chars.get(i + 1) == Some(&'\'') {
+                    i += 2;
+                } else if chars[i] == '\'' {
+                    break;
+                } else {
+                    i += 1;
+                }
+            }
+            i += 1;
+        } else if c == '"' {
+            let mut name = String::new();
+            i += 1;
+            while i < chars.len() {
+                if chars[i] == '"' && chars.get(i + 1) == Some(&'"') {
+                    name.push('"');
+                    i += 2;
+                } else if chars[i] == '"' {
+                    break;
+                } else {
+                    name.push(chars[i]);
+                    i += 1;
+                }
+            }
+            i += 1;
+            tokens.push(Token::Identifier { name, quoted: true });
+        } else if c == '$' && next.map(|x| x.is_ascii_digit()).unwrap_or(false) {
+            // a param
+            i += 1;
+            while i < chars.len() && chars[i].is_ascii_digit() {
+                i += 1;
+            }
+        } else if c == '$' {
+            // dollar quoted string, e.g. `$body$ ... $body$`
+            let tag_end = (i + 1..chars.len()).find(|&x| chars[x] == '$');
+            match tag_end {
+                Some(tag_end) => {
+                    let tag: String = chars[i..tag_end + 1].iter().collect();
+                    let rest: String = chars[tag_end + 1..].iter().collect();
+                    i = match rest.find(&tag) {
+                        Some(idx) => tag_end + 1 + rest[..idx].chars().count() + tag.chars().count(),
+                        None => chars.len(),
+                    };
+                },
+                None => i += 1,
+            }
+        } else if c.is_alphabetic() || c == '_' {
+            let mut name = String::new();
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_' || chars[i] == '$') {
+                name.push(chars[i]);
+                i += 1;
+            }
+            tokens.push(Token::Identifier { name: name.to_lowercase(), quoted: false });
+        } else if c.is_ascii_digit() {
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '.') {
+                i += 1;
+            }
+        } else {
+            tokens.push(Token::Symbol(c));
+            i += 1;
+        }
+    }
+
+    tokens
+}
+
+/// Reads a possibly qualified name starting at `i`, returns the last part and the index after it
+fn read_name(tokens: &[Token], mut i: usize) -> Option<(String, usize)> {
+    let mut name = match tokens.get(i) {
+        Some(Token::Identifier { name, .. }) if !is_keyword(&tokens[i]) => name.to_owned(),
+        _ => return None,
+    };
+    i += 1;
+
+    while tokens.get(i) == Some(&Token::Symbol('.')) {
+        match tokens.get(i + 1) {
+            Some(Token::Identifier { name: part, .. }) => {
+                name = part.to_owned();
+                i += 2;
+            },
+            _ => break,
+        }
+    }
+
+    Some((name, i))
+}
+
+/// The names of the tables (or views) a statement reads from or writes to, sorted
+///
+/// This is a heuristic over the tokens rather than a full sql parser, the names after `FROM`, `JOIN`,
+/// `INTO`, `UPDATE` and `USING` are collected, leaving out the function calls, the common table
+/// expressions and the `FROM` inside function arguments like `EXTRACT(year FROM x)`
+pub fn table_references(statement: &str) -> Vec<String> {
+    let tokens = tokenize(statement);
+
+    // `WITH name AS (` declares a name that isn't a table
+    let cte_names: Vec<String> = tokens
+        .windows(3)
+        .filter_map(|window| match window {
+            [Token::Identifier { name, .. }, as_word, Token::Symbol('(')] if is_word(Some(as_word), "as") => Some(name.to_owned()),
+            _ => None,
+        })
+        .collect();
+
+    let mut references = vec![];
+    // whether each open parenthesis is a function call
+    let mut parens: Vec<bool> = vec![];
+
+    let mut i = 0;
+    while i < tokens.len() {
+        let token = &tokens[i];
+        match token {
+            Token::Symbol('(') => {
+                let is_call = match i.checked_sub(1).map(|x| &tokens[x]) {
+                    Some(previous @ Token::Identifier { .. }) => !is_keyword(previous),
+                    _ => false,
+                };
+                parens.push(is_call);
+                i += 1;
+                continue;
+            },
+            Token::Symbol(')') => {
+                parens.pop();
+                i += 1;
+                continue;
+            },
+            _ => {},
+        }
+
+        let in_call = parens.last().cloned().unwrap_or(false);
+        let is_from = is_word(Some(token), "from") || is_word(Some(token), "join") || is_word(Some(token), "using");
+        let is_write = is_word(Some(token), "into") || is_word(Some(token), "update");
+        if in_call || !(is_from || is_write) {
+            i += 1;
+            continue;
+        }
+
+        // `FROM a, b AS x, c`
+        let mut j = i + 1;
+        loop {
+            while is_word(tokens.get(j), "only") || is_word(tokens.get(j), "lateral") {
+                j += 1;
+            }
+
+            let (name, after) = match read_name(&tokens, j) {
+                Some(x) => x,
+                None => break,
+            };
+
+            let is_function = is_from && tokens.get(after) == Some(&Token::Symbol('('));
+            if !is_function && !cte_names.contains(&name) {
+                references.push(name);
+            }
+
+            if !is_from {
+                break;
+            }
+
+            // skip the alias
+            j = after;
+            if is_word(tokens.get(j), "as") {
+                j += 1;
+            }
+            if tokens.get(j).map(|x| !is_keyword(x)).unwrap_or(false) {
+                if let Some(Token::Identifier { .. }) = tokens.get(j) {
+                    j += 1;
+                }
+            }
+
+            if tokens.get(j) == Some(&Token::Symbol(',')) {
+                j += 1;
+            } else {
+                break;
+            }
+        }
+
+        i += 1;
+    }
+
+    references.sort();
+    references.dedup();
+    references
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_table_references() {
+        let statement = r#"
+            SELECT u.name, COUNT(*) FROM public.users AS u
+            INNER JOIN "Orders" o ON o.user_id = u.id
+            WHERE u.name <> 'FROM nothing' -- JOIN comment
+            GROUP BY u.name;
+        "#;
+        assert_eq!(table_references(statement), vec!["Orders", "users"]);
+
+        let statement = "SELECT * FROM a, b x, c AS y WHERE EXTRACT(year FROM a.created) = $1";
+        assert_eq!(table_references(statement), vec!["a", "b", "c"]);
+
+        let statement = r#"
+            WITH recent AS (SELECT * FROM events WHERE created > now())
+            SELECT * FROM recent, generate_series(1, 10)
+            WHERE recent.id IN (SELECT event_id FROM flags)
+        "#;
+        assert_eq!(table_references(statement), vec!["events", "flags"]);
+
+        let statement = "INSERT INTO log (msg) SELECT $$ FROM x $$ FROM dual; UPDATE counters SET n = n + 1";
+        assert_eq!(table_references(statement), vec!["counters", "dual", "log"]);
+
+        assert!(table_references("SELECT 1").is_empty());
+    }
+}
@@ -21,6 +21,28 @@ pub enum DatastoreError {
     NoColumns,
     #[fail(display = "{}", 0)]
     DbError(String),
+    #[fail(display = "Key collision: {}", 0)]
+    KeyCollision(String),
+    #[fail(display = "Invalid query: {}", 0)]
+    InvalidQuery(String),
+    #[fail(display = "{}", 0)]
+    InvalidParam(String),
+    #[fail(display = "The statement took too long and was canceled")]
+    Timeout,
+    #[fail(display = "The query was canceled")]
+    Canceled,
+    #[fail(display = "{} of the rows were not found", 0)]
+    RowsNotFound(usize),
+    #[fail(display = "Invalid identifier {:?}, only letters, digits and underscores are allowed", 0)]
+    InvalidIdentifier(String),
+    #[fail(display = "The receiving end of the stream was closed")]
+    StreamClosed,
+    #[fail(display = "No secrets key is configured on the server")]
+    NoSecretsKey,
+    #[fail(display = "Could not decrypt the secret {:?}, was the secrets key changed?", 0)]
+    DecryptionError(String),
+    #[fail(display = "No connection to the database is available, try again later")]
+    Unavailable,
     #[fail(display = "An unknown error occurred")]
     Unknown,
 }
\ No newline at end of file
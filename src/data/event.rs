@@ -0,0 +1,58 @@
+use serde_json::Value;
+
+use data::channels::Channels;
+
+/// the version of the `Event` format, bumped when a field is removed or changes meaning
+pub const EVENT_SCHEMA_VERSION: i32 = 1;
+
+/// A published message, as every consumer gets it: from `getMessages`, `getPendingMessages`,
+/// the websocket and the kafka sink
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Event {
+    /// unique across the channels, the durable subscribers acknowledge it, see `AckMessages`
+    pub id: i64,
+    /// increases by one with each event of the channel, a gap means events were missed or purged
+    pub sequence: i64,
+    pub channel: Channels,
+    /// the action that published it, e.g. `updateTable`
+    pub action: String,
+    /// the username of the caller, none for the events published before it was recorded
+    pub actor: Option<String>,
+    pub timestamp: chrono::NaiveDateTime,
+    /// the `EVENT_SCHEMA_VERSION` the event was published with
+    pub schema_version: i32,
+    pub data: Value,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_serialize_event() {
+        let event = Event {
+            id: 1,
+            sequence: 4,
+            channel: Channels::table("users"),
+            action: "insertTableData".to_string(),
+            actor: Some("bob".to_string()),
+            timestamp: chrono::NaiveDateTime::from_timestamp(0, 0),
+            schema_version: EVENT_SCHEMA_VERSION,
+            data: json!([{ "id": 1 }]),
+        };
+
+        let repr = serde_json::to_value(&event).unwrap();
+        assert_eq!(repr, json!({
+            "id": 1,
+            "sequence": 4,
+            "channel": { "tableData": "users" },
+            "action": "insertTableData",
+            "actor": "bob",
+            "timestamp": "1970-01-01T00:00:00",
+            "schemaVersion": 1,
+            "data": [{ "id": 1 }],
+        }));
+        assert_eq!(serde_json::from_value::<Event>(repr).unwrap(), event);
+    }
+}
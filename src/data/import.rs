@@ -0,0 +1,269 @@
+use std::collections::HashMap;
+use std::io::BufRead;
+
+use serde_json;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportFormat {
+    /// the first line has the column names
+    Csv,
+    /// one json object per line, like the export
+    Ndjson,
+}
+
+impl ImportFormat {
+    pub fn from_param(param: &str) -> Result<Self, String> {
+        match param {
+            "csv" => Ok(ImportFormat::Csv),
+            "ndjson" | "jsonl" => Ok(ImportFormat::Ndjson),
+            other => Err(format!("unknown import format `{}`", other)),
+        }
+    }
+
+    /// guessed from the extension of the uploaded file
+    pub fn from_file_name(file_name: &str) -> Option<Self> {
+        let (_, extension) = file_name.split_at(file_name.rfind('.')? + 1);
+        Self::from_param(&extension.to_lowercase()).ok()
+    }
+
+    pub fn from_content_type(content_type: &str) -> Option<Self> {
+        match content_type.split(';').next()?.trim() {
+            "text/csv" => Some(ImportFormat::Csv),
+            "application/x-ndjson" | "application/jsonl" => Some(ImportFormat::Ndjson),
+            _ => None,
+        }
+    }
+}
+
+/// Splits a csv record, the quoted fields can have commas, escaped quotes and new lines.
+/// Returns None if the record isn't finished, i.e. a quoted field goes on on the next line
+fn split_csv_record(record: &str) -> Option<Vec<String>> {
+    let mut fields = vec![];
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = record.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quotes && chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            },
+            '"' => in_quotes = !in_quotes,
+            ',' if !in_quotes => fields.push(std::mem::replace(&mut field, String::new())),
+            c => field.push(c),
+        }
+    }
+
+    if in_quotes {
+        return None;
+    }
+
+    fields.push(field);
+    Some(fields)
+}
+
+/// The csv fields are text, they are converted to the type of their column. Empty fields are null
+fn csv_value(text: String, data_type: Option<&serde_json::Value>) -> Result<serde_json::Value, String> {
+    if text.is_empty() {
+        return Ok(serde_json::Value::Null);
+    }
+
+    let type_name = data_type.and_then(|x| x.as_str()).unwrap_or_default();
+    let value = match type_name {
+        "smallInteger" | "integer" | "bigInteger" => text
+            .parse::<i64>()
+            .map(|x| json!(x))
+            .map_err(|_| format!("{:?} is not an integer", text))?,
+        "float" | "doubleFloat" => text
+            .parse::<f64>()
+            .map(|x| json!(x))
+            .map_err(|_| format!("{:?} is not a number", text))?,
+        "boolean" => match text.to_lowercase().as_str() {
+            "true" | "t" | "1" => json!(true),
+            "false" | "f" | "0" => json!(false),
+            _ => return Err(format!("{:?} is not a boolean", text)),
+        },
+        "json" => serde_json::from_str(&text)
+            .map_err(|err| format!("{:?} is not json: {}", text, err))?,
+        _ => serde_json::Value::String(text),
+    };
+
+    Ok(value)
+}
+
+/// Reads the rows of an uploaded file one at a time, so that the file is never all in memory
+pub struct RowReader<R> {
+    reader: R,
+    format: ImportFormat,
+    /// the csv columns, read from the first line
+    column_names: Option<Vec<String>>,
+    column_types: HashMap<String, serde_json::Value>,
+    line: usize,
+}
+
+impl<R: BufRead> RowReader<R> {
+    /// `schema` is the one of the table the rows go in, the csv fields are converted with its column types
+    pub fn new(reader: R, format: ImportFormat, schema: &serde_json::Value) -> Self {
+        let column_types = schema["columns"]
+            .as_array()
+            .map(|columns| columns
+                .iter()
+                .filter_map(|column| {
+                    let name = column["name"].as_str()?;
+                    Some((name.to_owned(), column["dataType"].to_owned()))
+                })
+                .collect())
+            .unwrap_or_default();
+
+        Self {
+            reader,
+            format,
+            column_names: None,
+            column_types,
+            line: 0,
+        }
+    }
+
+    /// the next line that isn't blank, with the lines a quoted csv field spans
+    fn next_record(&mut self) -> Result<Option<String>, String> {
+        let mut record = String::new();
+        loop {
+            let mut line = String::new();
+            let read = self.reader
+                .read_line(&mut line)
+                .map_err(|err| format!("line {}: {}", self.line + 1, err))?;
+            if read == 0 {
+                return if record.is_empty() {
+                    Ok(None)
+                } else {
+                    Err(format!("line {}: the quoted field is not closed", self.line))
+                };
+            }
+            self.line += 1;
+
+            record.push_str(&line);
+            let is_complete = match self.format {
+                ImportFormat::Csv => split_csv_record(&record).is_some(),
+                ImportFormat::Ndjson => true,
+            };
+            if !is_complete {
+                continue;
+            }
+
+            let trimmed_len = record.trim_end_matches(|c| c == '\n' || c == '\r').len();
+            record.truncate(trimmed_len);
+            if record.trim().is_empty() {
+                record.clear();
+                continue;
+            }
+            return Ok(Some(record));
+        }
+    }
+
+    fn next_row(&mut self) -> Result<Option<serde_json::Value>, String> {
+        let record = match self.next_record()? {
+            Some(record) => record,
+            None => return Ok(None),
+        };
+
+        match self.format {
+            ImportFormat::Ndjson => {
+                let row: serde_json::Value = serde_json::from_str(&record)
+                    .map_err(|err| format!("line {}: {}", self.line, err))?;
+                if !row.is_object() {
+                    return Err(format!("line {}: expected an object", self.line));
+                }
+                Ok(Some(row))
+            },
+            ImportFormat::Csv => {
+                let fields = split_csv_record(&record).unwrap_or_default();
+                if self.column_names.is_none() {
+                    self.column_names = Some(fields.into_iter().map(|x| x.trim_matches(|c: char| c == '\u{feff}' || c.is_whitespace()).to_owned()).collect());
+                    return self.next_row();
+                }
+                let column_names = self.column_names.as_ref().map(|x| x.as_slice()).unwrap_or_default();
+
+                if fields.len() != column_names.len() {
+                    return Err(format!("line {}: expected {} fields, found {}", self.line, column_names.len(), fields.len()));
+                }
+
+                let mut row = serde_json::Map::new();
+                for (name, field) in column_names.iter().zip(fields) {
+                    let value = csv_value(field, self.column_types.get(name))
+                        .map_err(|err| format!("line {}, column {}: {}", self.line, name, err))?;
+                    row.insert(name.to_owned(), value);
+                }
+                Ok(Some(serde_json::Value::Object(row)))
+            },
+        }
+    }
+
+    /// The next `batch_size` rows, fewer at the end of the file and none once it's all read
+    pub fn next_batch(&mut self, batch_size: usize) -> Result<Vec<serde_json::Value>, String> {
+        let mut rows = vec![];
+        while rows.len() < batch_size {
+            match self.next_row()? {
+                Some(row) => rows.push(row),
+                None => break,
+            }
+        }
+        Ok(rows)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_split_csv_record() {
+        assert_eq!(split_csv_record("1,Bob,"), Some(vec!["1".to_string(), "Bob".to_string(), "".to_string()]));
+        assert_eq!(split_csv_record("\"hello, world\",\"say \"\"hi\"\"\""), Some(vec!["hello, world".to_string(), "say \"hi\"".to_string()]));
+        assert_eq!(split_csv_record("1,\"two\nlines"), None);
+    }
+
+    #[test]
+    fn test_read_csv() {
+        let schema = json!({
+            "columns": [
+                { "name": "id", "dataType": "integer" },
+                { "name": "name", "dataType": "string" },
+                { "name": "active", "dataType": "boolean" }
+            ]
+        });
+        let file = "id,name,active\r\n1,Bob,true\r\n\r\n2,\"two\nlines\",\r\n3,Alice,f\r\n";
+        let mut reader = RowReader::new(Cursor::new(file), ImportFormat::Csv, &schema);
+
+        let batch = reader.next_batch(2).unwrap();
+        assert_eq!(batch, vec![
+            json!({ "id": 1, "name": "Bob", "active": true }),
+            json!({ "id": 2, "name": "two\nlines", "active": null }),
+        ]);
+        let batch = reader.next_batch(2).unwrap();
+        assert_eq!(batch, vec![json!({ "id": 3, "name": "Alice", "active": false })]);
+        assert!(reader.next_batch(2).unwrap().is_empty());
+
+        let mut reader = RowReader::new(Cursor::new("id,name\nabc,Bob\n"), ImportFormat::Csv, &schema);
+        assert_eq!(reader.next_batch(10), Err("line 2, column id: \"abc\" is not an integer".to_string()));
+    }
+
+    #[test]
+    fn test_read_ndjson() {
+        let file = "{\"id\":1,\"name\":\"Bob\"}\n\n{\"id\":2,\"name\":null}\n[1, 2]\n";
+        let mut reader = RowReader::new(Cursor::new(file), ImportFormat::Ndjson, &json!({}));
+
+        let batch = reader.next_batch(2).unwrap();
+        assert_eq!(batch, vec![json!({ "id": 1, "name": "Bob" }), json!({ "id": 2, "name": null })]);
+        assert_eq!(reader.next_batch(2), Err("line 4: expected an object".to_string()));
+    }
+
+    #[test]
+    fn test_import_format() {
+        assert_eq!(ImportFormat::from_file_name("rows.CSV"), Some(ImportFormat::Csv));
+        assert_eq!(ImportFormat::from_file_name("rows.jsonl"), Some(ImportFormat::Ndjson));
+        assert_eq!(ImportFormat::from_file_name("rows"), None);
+        assert_eq!(ImportFormat::from_content_type("text/csv; charset=utf-8"), Some(ImportFormat::Csv));
+    }
+}
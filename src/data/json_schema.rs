@@ -0,0 +1,169 @@
+use std::fmt;
+
+use serde_json::Value;
+
+/// A value that doesn't conform to its schema, e.g. `data.schema.columns[0].name: is required`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemaError {
+    /// where the value is in the payload
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+fn type_matches(type_name: &str, value: &Value) -> bool {
+    match type_name {
+        "null" => value.is_null(),
+        "boolean" => value.is_boolean(),
+        "integer" => value.is_i64() || value.is_u64(),
+        "number" => value.is_number(),
+        "string" => value.is_string(),
+        "array" => value.is_array(),
+        "object" => value.is_object(),
+        _ => true,
+    }
+}
+
+fn error(path: &str, message: String) -> SchemaError {
+    SchemaError { path: path.to_owned(), message }
+}
+
+/// Checks the value against the schema and returns the errors of every field, none if it conforms.
+/// Only a part of JSON Schema is understood: `type`, `enum`, `properties`, `required`, `items`, `minItems`,
+/// `minLength`, `minimum` and `anyOf`, the other keywords are ignored
+pub fn validate(schema: &Value, value: &Value, path: &str) -> Vec<SchemaError> {
+    let mut errors = vec![];
+    validate_at(schema, value, path, &mut errors);
+    errors
+}
+
+fn validate_at(schema: &Value, value: &Value, path: &str, errors: &mut Vec<SchemaError>) {
+    let types: Vec<&str> = match &schema["type"] {
+        Value::String(type_name) => vec![type_name.as_str()],
+        Value::Array(type_names) => type_names.iter().filter_map(|x| x.as_str()).collect(),
+        _ => vec![],
+    };
+    if !types.is_empty() && !types.iter().any(|x| type_matches(x, value)) {
+        let message = match types.as_slice() {
+            [type_name] => format!("must be of type {}", type_name),
+            _ => format!("must be of one of the types {}", types.join(", ")),
+        };
+        errors.push(error(path, message));
+        return;
+    }
+
+    if let Some(allowed) = schema["enum"].as_array() {
+        if !allowed.contains(value) {
+            let allowed: Vec<String> = allowed.iter().map(|x| x.to_string()).collect();
+            errors.push(error(path, format!("must be one of {}", allowed.join(", "))));
+            return;
+        }
+    }
+
+    if let Some(any_of) = schema["anyOf"].as_array() {
+        if !any_of.iter().any(|x| validate(x, value, path).is_empty()) {
+            errors.push(error(path, "doesn't match any of the allowed shapes".to_string()));
+            return;
+        }
+    }
+
+    match value {
+        Value::Object(object) => {
+            if let Some(required) = schema["required"].as_array() {
+                for name in required.iter().filter_map(|x| x.as_str()) {
+                    if !object.contains_key(name) {
+                        errors.push(error(&format!("{}.{}", path, name), "is required".to_string()));
+                    }
+                }
+            }
+            if let Some(properties) = schema["properties"].as_object() {
+                for (name, property_schema) in properties {
+                    if let Some(property) = object.get(name) {
+                        validate_at(property_schema, property, &format!("{}.{}", path, name), errors);
+                    }
+                }
+            }
+        },
+        Value::Array(items) => {
+            if let Some(min_items) = schema["minItems"].as_u64() {
+                if (items.len() as u64) < min_items {
+                    errors.push(error(path, format!("must have at least {} items", min_items)));
+                }
+            }
+            if schema["items"].is_object() {
+                for (index, item) in items.iter().enumerate() {
+                    validate_at(&schema["items"], item, &format!("{}[{}]", path, index), errors);
+                }
+            }
+        },
+        Value::String(text) => {
+            if let Some(min_length) = schema["minLength"].as_u64() {
+                if (text.chars().count() as u64) < min_length {
+                    errors.push(error(path, format!("must be at least {} characters long", min_length)));
+                }
+            }
+        },
+        Value::Number(number) => {
+            if let (Some(minimum), Some(number)) = (schema["minimum"].as_f64(), number.as_f64()) {
+                if number < minimum {
+                    errors.push(error(path, format!("must be at least {}", minimum)));
+                }
+            }
+        },
+        _ => (),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_validate() {
+        let schema = json!({
+            "type": "object",
+            "required": ["name", "schema"],
+            "properties": {
+                "name": { "type": "string", "minLength": 1 },
+                "audit": { "type": ["boolean", "string"] },
+                "schema": {
+                    "type": "object",
+                    "properties": {
+                        "columns": {
+                            "type": "array",
+                            "items": { "type": "object", "required": ["name"] }
+                        }
+                    }
+                },
+                "mode": { "enum": ["replace", "append"] }
+            }
+        });
+
+        assert!(validate(&schema, &json!({ "name": "users", "schema": { "columns": [{ "name": "id" }] } }), "data").is_empty());
+
+        let mut errors: Vec<String> = validate(&schema, &json!({
+            "name": "",
+            "audit": 1,
+            "schema": { "columns": [{ "name": "id" }, {}] },
+            "mode": "merge"
+        }), "data")
+            .iter()
+            .map(|x| x.to_string())
+            .collect();
+        errors.sort();
+        assert_eq!(errors, vec![
+            "data.audit: must be of one of the types boolean, string",
+            "data.mode: must be one of \"replace\", \"append\"",
+            "data.name: must be at least 1 characters long",
+            "data.schema.columns[1].name: is required",
+        ]);
+
+        let errors = validate(&schema, &json!([]), "data");
+        assert_eq!(errors, vec![SchemaError { path: "data".to_string(), message: "must be of type object".to_string() }]);
+    }
+}
@@ -0,0 +1,266 @@
+
+use std::collections::HashMap;
+
+use inflector::Inflector;
+use serde_json;
+
+use data::error::DatastoreError;
+
+pub const KEY_CASE_HEADER: &'static str = "X-Key-Case";
+
+/// How the column names in table data are presented to the client
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum KeyCase {
+    /// column names are passed through as they are stored
+    Preserve,
+    /// column names are sent as camelCase, and mapped back to the stored name on the way in
+    CamelCase,
+}
+
+impl Default for KeyCase {
+    fn default() -> Self {
+        KeyCase::Preserve
+    }
+}
+
+impl KeyCase {
+    pub fn from_header(header: &[u8]) -> Option<Self> {
+        let value = String::from_utf8_lossy(header).trim().to_lowercase();
+        match value.as_str() {
+            "preserve" | "none" => Some(KeyCase::Preserve),
+            "camelcase" | "camel" => Some(KeyCase::CamelCase),
+            _ => None,
+        }
+    }
+
+    fn convert(&self, key: &str) -> String {
+        match self {
+            KeyCase::Preserve => key.to_owned(),
+            KeyCase::CamelCase => key.to_camel_case(),
+        }
+    }
+}
+
+/// Bidirectional mapping between the stored column names and the names the client sees
+#[derive(Debug, Clone)]
+pub struct KeyMapping {
+    to_client: HashMap<String, String>,
+    from_client: HashMap<String, String>,
+}
+
+impl KeyMapping {
+    /// Build the mapping, fails if two columns end up with the same client name
+    pub fn new(key_case: &KeyCase, column_names: &[String]) -> Result<Self, DatastoreError> {
+        let mut to_client = HashMap::new();
+        let mut from_client: HashMap<String, String> = HashMap::new();
+
+        for column_name in column_names {
+            let client_name = key_case.convert(column_name);
+            if let Some(existing) = from_client.get(&client_name) {
+                return Err(DatastoreError::KeyCollision(format!(
+                    "columns `{}` and `{}` both map to `{}`", existing, column_name, client_name)));
+            }
+
+            to_client.insert(column_name.to_owned(), client_name.to_owned());
+            from_client.insert(client_name, column_name.to_owned());
+        }
+
+        Ok(Self { to_client, from_client })
+    }
+
+    /// Reads the column names out of the table schema
+    pub fn for_schema(key_case: &KeyCase, schema: &serde_json::Value) -> Result<Self, DatastoreError> {
+        let column_names: Vec<String> = schema["columns"]
+            .as_array()
+            .map(|columns| {
+                columns
+                    .iter()
+                    .filter_map(|column| column["name"].as_str().map(|x| x.to_owned()))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Self::new(key_case, &column_names)
+    }
+
+    pub fn to_client(&self, value: serde_json::Value) -> serde_json::Value {
+        rename_columns(value, &self.to_client)
+    }
+
+    pub fn from_client(&self, value: serde_json::Value) -> serde_json::Value {
+        rename_columns(value, &self.from_client)
+    }
+}
+
+/// column names show up either as object keys (row format) or as strings inside a list of
+/// column names (flat format), the values of a column are never touched
+fn rename_columns(value: serde_json::Value, mapping: &HashMap<String, String>) -> serde_json::Value {
+    match value {
+        serde_json::Value::Object(obj) => {
+            let renamed = obj
+                .into_iter()
+                .map(|(key, value)| match mapping.get(&key) {
+                    Some(new_key) => (new_key.to_owned(), value),
+                    None => {
+                        let value = match (key.as_str(), value) {
+                            ("columns", value) | ("keys", value) | ("values", value) | ("groupBy", value) => rename_column_list(value, mapping),
+                            ("filter", value) | ("orderBy", value) | ("aggregates", value) => rename_column_fields(value, mapping),
+                            (_, value) => rename_columns(value, mapping),
+                        };
+                        (key, value)
+                    },
+                })
+                .collect();
+            serde_json::Value::Object(renamed)
+        },
+        serde_json::Value::Array(arr) => {
+            serde_json::Value::Array(arr.into_iter().map(|x| rename_columns(x, mapping)).collect())
+        },
+        x => x,
+    }
+}
+
+fn rename_column_list(value: serde_json::Value, mapping: &HashMap<String, String>) -> serde_json::Value {
+    match value {
+        serde_json::Value::Array(arr) => {
+            let is_column_list = arr.iter().all(|x| x.is_string());
+            if is_column_list {
+                let renamed = arr
+                    .into_iter()
+                    .map(|x| {
+                        let name = x.as_str().unwrap_or_default().to_owned();
+                        serde_json::Value::String(mapping.get(&name).cloned().unwrap_or(name))
+                    })
+                    .collect();
+                serde_json::Value::Array(renamed)
+            } else {
+                serde_json::Value::Array(arr.into_iter().map(|x| rename_columns(x, mapping)).collect())
+            }
+        },
+        x => rename_columns(x, mapping),
+    }
+}
+
+/// filter expressions, sort orders and aggregates name their column in `column`, the compared values are left alone
+fn rename_column_fields(value: serde_json::Value, mapping: &HashMap<String, String>) -> serde_json::Value {
+    match value {
+        serde_json::Value::Object(obj) => {
+            let renamed = obj
+                .into_iter()
+                .map(|(key, value)| {
+                    let value = match (key.as_str(), value) {
+                        ("column", serde_json::Value::String(name)) => {
+                            serde_json::Value::String(mapping.get(&name).cloned().unwrap_or(name))
+                        },
+                        ("expressions", value) => rename_column_fields(value, mapping),
+                        (_, value) => value,
+                    };
+                    (key, value)
+                })
+                .collect();
+            serde_json::Value::Object(renamed)
+        },
+        serde_json::Value::Array(arr) => {
+            serde_json::Value::Array(arr.into_iter().map(|x| rename_column_fields(x, mapping)).collect())
+        },
+        x => x,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_rows_round_trip() {
+        let columns = vec!["user_id".to_string(), "first_name".to_string(), "age".to_string()];
+        let mapping = KeyMapping::new(&KeyCase::CamelCase, &columns).unwrap();
+
+        let stored = json!([
+            { "user_id": 1, "first_name": "Bob", "age": 42 },
+            { "user_id": 2, "first_name": "Alice", "age": 41 }
+        ]);
+        let client = mapping.to_client(stored.to_owned());
+
+        assert_eq!(client, json!([
+            { "userId": 1, "firstName": "Bob", "age": 42 },
+            { "userId": 2, "firstName": "Alice", "age": 41 }
+        ]));
+        assert_eq!(mapping.from_client(client), stored);
+    }
+
+    #[test]
+    fn test_flat_data() {
+        let columns = vec!["user_id".to_string(), "first_name".to_string()];
+        let mapping = KeyMapping::new(&KeyCase::CamelCase, &columns).unwrap();
+
+        let stored = json!({
+            "columns": { "keys": ["user_id"], "values": ["first_name"] },
+            "data": [{ "keys": [1], "values": ["Bob"] }]
+        });
+        let client = mapping.to_client(stored);
+
+        assert_eq!(client, json!({
+            "columns": { "keys": ["userId"], "values": ["firstName"] },
+            "data": [{ "keys": [1], "values": ["Bob"] }]
+        }));
+    }
+
+    #[test]
+    fn test_column_values_untouched() {
+        let columns = vec!["json_data".to_string()];
+        let mapping = KeyMapping::new(&KeyCase::CamelCase, &columns).unwrap();
+
+        let stored = json!([{ "json_data": { "json_data": "inner" } }]);
+        let client = mapping.to_client(stored);
+
+        assert_eq!(client, json!([{ "jsonData": { "json_data": "inner" } }]));
+    }
+
+    #[test]
+    fn test_collision() {
+        let columns = vec!["user_id".to_string(), "userId".to_string()];
+        let mapping = KeyMapping::new(&KeyCase::CamelCase, &columns);
+
+        assert!(mapping.is_err());
+
+        let mapping = KeyMapping::new(&KeyCase::Preserve, &columns);
+        assert!(mapping.is_ok());
+    }
+
+    #[test]
+    fn test_from_header() {
+        assert_eq!(KeyCase::from_header(b"camelCase"), Some(KeyCase::CamelCase));
+        assert_eq!(KeyCase::from_header(b"preserve"), Some(KeyCase::Preserve));
+        assert_eq!(KeyCase::from_header(b"kebab"), None);
+    }
+
+    #[test]
+    fn test_filter_and_order() {
+        let columns = vec!["user_id".to_string(), "first_name".to_string()];
+        let mapping = KeyMapping::new(&KeyCase::CamelCase, &columns).unwrap();
+
+        let client = json!({
+            "filter": {
+                "op": "and",
+                "expressions": [
+                    { "op": "equals", "column": "userId", "value": 1 },
+                    { "op": "in", "column": "firstName", "values": ["userId", "Bob"] }
+                ]
+            },
+            "orderBy": [{ "column": "firstName", "direction": "desc" }]
+        });
+
+        assert_eq!(mapping.from_client(client), json!({
+            "filter": {
+                "op": "and",
+                "expressions": [
+                    { "op": "equals", "column": "user_id", "value": 1 },
+                    { "op": "in", "column": "first_name", "values": ["userId", "Bob"] }
+                ]
+            },
+            "orderBy": [{ "column": "first_name", "direction": "desc" }]
+        }));
+    }
+}
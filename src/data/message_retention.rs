@@ -0,0 +1,40 @@
+use chrono::Duration;
+use chrono::NaiveDateTime;
+
+/// How long the published messages are kept, the ones past either limit are purged, see `PurgeMessages`.
+/// Both unset keeps the messages forever
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MessageRetention {
+    /// in seconds
+    pub max_age: Option<u64>,
+    /// the number of the newest messages kept in each channel
+    pub max_per_channel: Option<usize>,
+}
+
+impl MessageRetention {
+    pub fn is_unbounded(&self) -> bool {
+        self.max_age.is_none() && self.max_per_channel.is_none()
+    }
+
+    /// the messages sent before this are too old
+    pub fn cutoff(&self, now: NaiveDateTime) -> Option<NaiveDateTime> {
+        self.max_age.map(|max_age| now - Duration::seconds(max_age as i64))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_cutoff() {
+        let now = NaiveDateTime::from_timestamp(1_000_000, 0);
+        assert_eq!(MessageRetention::default().cutoff(now), None);
+        assert!(MessageRetention::default().is_unbounded());
+
+        let retention = MessageRetention { max_age: Some(3600), max_per_channel: None };
+        assert_eq!(retention.cutoff(now), Some(NaiveDateTime::from_timestamp(1_000_000 - 3600, 0)));
+        assert!(!retention.is_unbounded());
+    }
+}
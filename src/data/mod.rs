@@ -8,6 +8,27 @@ pub mod claims;
 pub mod channels;
 pub mod permissions;
 pub mod error;
+pub mod key_case;
+pub mod conditions;
+pub mod row_history;
+pub mod cron;
+pub mod schedule;
+pub mod usage;
+pub mod dependencies;
+pub mod result_format;
+pub mod script_run;
+pub mod script_secret;
+pub mod script_version;
+pub mod webhook;
+pub mod import;
+pub mod json_schema;
+pub mod audit;
+pub mod message_retention;
+pub mod dead_letter;
+pub mod event;
+
+/// The scope every entity is in unless it says otherwise, its tables are in the `public` schema
+pub const MAIN_SCOPE: &str = "main";
 
 pub trait Named {
     fn my_name(&self) -> &str;
@@ -24,6 +45,24 @@ pub struct DataStoreEntity {
     //pub domain_id: i64,
     pub description: String,
     pub schema: serde_json::Value,
+    /// keep the history of every row change, see `GetRowHistory`
+    #[serde(default)]
+    pub audit_rows: bool,
+    /// The table is created in the postgres schema of the same name, so that the tables of different
+    /// scopes can have the same name. Left out for the main scope, which is `public`.
+    /// The stored queries have to use the qualified name, e.g. `finance.invoices`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+}
+
+impl DataStoreEntity {
+    /// `None` for the main scope, however it was written
+    pub fn scope_name(&self) -> Option<&str> {
+        match &self.scope {
+            Some(scope) if scope != MAIN_SCOPE => Some(scope),
+            _ => None,
+        }
+    }
 }
 
 impl Named for DataStoreEntity {
@@ -45,6 +84,87 @@ pub struct DataQueryEntity {
     //pub domain_id: i64,
     pub description: String,
     pub statement: String,
+    /// checked before the query is run, the first declared param is `$1` and so on
+    #[serde(default)]
+    pub params: Vec<QueryParam>,
+    /// in milliseconds, overrides the default timeout of the datastore
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub statement_timeout: Option<u64>,
+    /// runs the query on its own, see `QuerySchedule`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schedule: Option<schedule::QuerySchedule>,
+}
+
+/// A declared parameter of a stored query
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueryParam {
+    pub name: String,
+    /// the data type as the datastore understands it, e.g. `"integer"` or `{"timestamp": {"withTZ": true}}`
+    pub data_type: serde_json::Value,
+    /// used when the param is left out
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<serde_json::Value>,
+    #[serde(default)]
+    pub required: bool,
+}
+
+/// A stored version of a query, every update adds one, see `GetQueryHistory`
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueryVersion {
+    /// pass it to `RestoreQueryVersion` to go back to this version
+    pub version: i64,
+    #[serde(flatten)]
+    pub query: DataQueryEntity,
+    pub is_deleted: bool,
+    pub modified_at: chrono::NaiveDateTime,
+    /// username, `None` if the user was removed since
+    pub modified_by: Option<String>,
+}
+
+/// A copy of a query result kept in the metastore, see `SnapshotQueryResult`
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QuerySnapshot {
+    pub query_name: String,
+    pub name: String,
+    pub params: serde_json::Value,
+    /// the result as returned by `RunQuery`, left out when listing the snapshots
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<serde_json::Value>,
+    pub row_count: i64,
+    /// set if the query returned more rows than were kept
+    pub truncated: bool,
+    pub created_at: chrono::NaiveDateTime,
+    /// username, `None` if it was taken by a scheduled job or the user was removed since
+    pub created_by: Option<String>,
+}
+
+/// The rows of a query result that are returned, the datastore never reads more than `offset + limit + 1` rows
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueryLimit {
+    pub limit: usize,
+    pub offset: usize,
+}
+
+/// How `RunQueryIntoTable` writes the rows of the result
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TableWriteMode {
+    /// the rows already in the table are deleted first
+    Replace,
+    /// rows with a key that is already in the table are skipped
+    Append,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueryResult {
+    pub data: serde_json::Value,
+    /// set if the query returned more rows than the limit
+    pub truncated: bool,
 }
 
 impl Named for DataQueryEntity {
@@ -53,14 +173,75 @@ impl Named for DataQueryEntity {
     }
 }
 
+impl DataQueryEntity {
+    /// the tables and views the statement refers to, see `dependencies::table_references`
+    pub fn table_references(&self) -> Vec<String> {
+        dependencies::table_references(&self.statement)
+    }
+}
+
 pub type ScriptParam = serde_json::Value;
 
+/// The runtime a script is run with
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScriptLanguage {
+    Python,
+    JavaScript,
+}
+
+impl Default for ScriptLanguage {
+    fn default() -> Self {
+        ScriptLanguage::Python
+    }
+}
+
+impl ScriptLanguage {
+    /// as stored in the `script_language` column
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ScriptLanguage::Python => "Python",
+            ScriptLanguage::JavaScript => "JavaScript",
+        }
+    }
+
+    pub fn from_str(language: &str) -> Option<Self> {
+        match language {
+            "Python" => Some(ScriptLanguage::Python),
+            "JavaScript" => Some(ScriptLanguage::JavaScript),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Script {
     pub name: String, //TODO: make sure this is an alphanumeric
     pub description: String,
     pub text: String,
+    #[serde(default)]
+    pub language: ScriptLanguage,
+    /// runs the script on its own, see `RunScheduledScripts`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schedule: Option<schedule::ScriptSchedule>,
+    /// the python packages the script needs, e.g. `requests==2.21.0`, the script is run in a virtualenv with them
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub requirements: Vec<String>,
+    /// the keys of the params the script takes, checked by `ValidateScript`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub params: Vec<DeclaredParam>,
+}
+
+/// A param a script takes, the params are passed as an object
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeclaredParam {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub required: bool,
 }
 
 impl Named for Script {
@@ -73,8 +254,14 @@ impl Named for Script {
 #[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct View {
-    pub name: String, //TODO: make sure this is an alphanumeric
+    pub name: String, // checked by the datastore, it's the name of the view in the database
     pub description: String,
+    /// the SELECT statement the view is created from
+    pub statement: String,
+    /// the rows are stored and only change when the view is refreshed, see `RefreshView`
+    #[serde(default)]
+    pub materialized: bool,
+    #[serde(default)]
     pub view_state: serde_json::Value,
 }
 
@@ -84,11 +271,37 @@ impl Named for View {
     }
 }
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
-pub struct Message {
-    pub data: serde_json::Value,
-    pub timestamp: chrono::NaiveDateTime,
-    //TODO: maybe add the user as well
+/// Posts the messages published on its channels to the url, see `webhook::sign` for the signature
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Webhook {
+    pub name: String,
+    pub description: String,
+    /// http or https
+    pub url: String,
+    /// the key of the signature, it is never returned so it has to be sent with every update
+    #[serde(default, skip_serializing)]
+    pub secret: String,
+    /// the secret as it's stored, it's encrypted before the webhook is created or updated
+    #[serde(skip)]
+    pub encrypted_secret: Option<webhook::EncryptedSecret>,
+    pub channels: Vec<channels::Channels>,
+    #[serde(default)]
+    pub retry: webhook::RetryPolicy,
+}
+
+impl Named for Webhook {
+    fn my_name(&self) -> &str {
+        &self.name
+    }
+}
+
+/// without the secret, the entities are logged when they are modified
+impl std::fmt::Debug for Webhook {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Webhook {{ name: {:?}, description: {:?}, url: {:?}, secret: \"***\", channels: {:?}, retry: {:?} }}",
+               &self.name, &self.description, &self.url, &self.channels, &self.retry)
+    }
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
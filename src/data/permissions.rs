@@ -1,6 +1,7 @@
 
 use state::ActionState;
 use model::entity::RawEntityTypes;
+use data::conditions::PermissionCondition;
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -53,6 +54,12 @@ pub enum Permission {
     // and add roles if the user has that role
     // and add permission to role if the user has that role and permission
 
+    /// only grants `permission` if the condition holds, see `data::conditions`
+    #[serde(rename_all = "camelCase")]
+    Conditional {
+        permission: Box<Permission>,
+        condition: PermissionCondition,
+    },
 }
 
 impl Permission {
@@ -127,6 +134,27 @@ impl Permission {
             email,
         }
     }
+
+    pub fn conditional(permission: Permission, condition: PermissionCondition) -> Self {
+        Permission::Conditional {
+            permission: Box::new(permission),
+            condition,
+        }
+    }
+
+    /// the entity type and name this permission applies to, if any
+    pub fn get_entity(&self) -> Option<(String, String)> {
+        match self {
+            Permission::GetEntity { type_name, entity_name } => Some((type_name.to_owned(), entity_name.to_owned())),
+            Permission::ModifyEntity { type_name, entity_name } => Some((type_name.to_owned(), entity_name.to_owned())),
+            Permission::GetTableData { table_name } => Some(("table".to_string(), table_name.to_owned())),
+            Permission::ModifyTableData { table_name } => Some(("table".to_string(), table_name.to_owned())),
+            Permission::RunQuery { query_name } => Some(("query".to_string(), query_name.to_owned())),
+            Permission::RunScript { script_name } => Some(("script".to_string(), script_name.to_owned())),
+            Permission::Conditional { permission, .. } => permission.get_entity(),
+            _ => None,
+        }
+    }
 }
 
 
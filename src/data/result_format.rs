@@ -0,0 +1,150 @@
+
+use serde_json::Map;
+use serde_json::Value;
+
+use data::error::DatastoreError;
+
+/// The layout of the rows in a table data or query result
+///
+/// The other fields of the result, like the page of a paginated table query, are left as they are
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ResultFormat {
+    /// as returned by the datastore, the key and value columns are kept apart
+    /// `{"columns": {"keys": ["id"], "values": ["name"]}, "data": [{"keys": [42], "values": ["Bob"]}]}`
+    Raw,
+    /// `{"data": [{"id": 42, "name": "Bob"}]}`
+    Rows,
+    /// the column names are only sent once, which is a lot smaller for wide results
+    /// `{"columns": ["id", "name"], "data": [[42, "Bob"]]}`
+    #[serde(alias = "columnar")]
+    Flat,
+    /// `{"data": [{"keys": {"id": 42}, "values": {"name": "Bob"}}]}`
+    Keyed,
+}
+
+impl Default for ResultFormat {
+    fn default() -> Self {
+        ResultFormat::Raw
+    }
+}
+
+fn column_names(columns: &Value, field: &str) -> Result<Vec<String>, DatastoreError> {
+    match &columns[field] {
+        Value::Null => Ok(vec![]),
+        value => serde_json::from_value(value.to_owned()).map_err(|_| DatastoreError::SerializationError),
+    }
+}
+
+fn row_values(row: &mut Value, field: &str) -> Vec<Value> {
+    match row[field].take() {
+        Value::Array(values) => values,
+        _ => vec![],
+    }
+}
+
+fn to_object(names: &[String], values: Vec<Value>) -> Value {
+    let object: Map<String, Value> = names.iter().cloned().zip(values).collect();
+    Value::Object(object)
+}
+
+impl ResultFormat {
+    pub fn apply(&self, result: Value) -> Result<Value, DatastoreError> {
+        if *self == ResultFormat::Raw {
+            return Ok(result);
+        }
+
+        let mut result = match result {
+            Value::Object(obj) => obj,
+            _ => return Err(DatastoreError::SerializationError),
+        };
+
+        let columns = result.remove("columns").ok_or(DatastoreError::SerializationError)?;
+        let key_names = column_names(&columns, "keys")?;
+        let value_names = column_names(&columns, "values")?;
+
+        let rows = match result.remove("data") {
+            Some(Value::Array(rows)) => rows,
+            _ => return Err(DatastoreError::SerializationError),
+        };
+
+        let data = rows
+            .into_iter()
+            .map(|mut row| {
+                let keys = row_values(&mut row, "keys");
+                let values = row_values(&mut row, "values");
+                match self {
+                    ResultFormat::Rows => {
+                        let mut object: Map<String, Value> = key_names.iter().cloned().zip(keys).collect();
+                        object.extend(value_names.iter().cloned().zip(values));
+                        Value::Object(object)
+                    },
+                    ResultFormat::Flat => {
+                        let mut flat_row = keys;
+                        flat_row.extend(values);
+                        Value::Array(flat_row)
+                    },
+                    _ => json!({
+                        "keys": to_object(&key_names, keys),
+                        "values": to_object(&value_names, values),
+                    }),
+                }
+            })
+            .collect();
+
+        if *self == ResultFormat::Flat {
+            let mut flat_columns = key_names;
+            flat_columns.extend(value_names);
+            result.insert("columns".to_string(), json!(flat_columns));
+        }
+        result.insert("data".to_string(), Value::Array(data));
+
+        Ok(Value::Object(result))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_apply_result_format() {
+        let result = json!({
+            "columns": { "keys": ["id"], "values": ["name", "age"] },
+            "data": [
+                { "keys": [42], "values": ["Bob", 30] },
+                { "keys": [43], "values": ["Alice", null] }
+            ],
+            "totalCount": 2
+        });
+
+        assert_eq!(ResultFormat::Raw.apply(result.to_owned()).unwrap(), result);
+
+        assert_eq!(ResultFormat::Rows.apply(result.to_owned()).unwrap(), json!({
+            "data": [
+                { "id": 42, "name": "Bob", "age": 30 },
+                { "id": 43, "name": "Alice", "age": null }
+            ],
+            "totalCount": 2
+        }));
+
+        assert_eq!(ResultFormat::Flat.apply(result.to_owned()).unwrap(), json!({
+            "columns": ["id", "name", "age"],
+            "data": [[42, "Bob", 30], [43, "Alice", null]],
+            "totalCount": 2
+        }));
+
+        assert_eq!(ResultFormat::Keyed.apply(result.to_owned()).unwrap(), json!({
+            "data": [
+                { "keys": { "id": 42 }, "values": { "name": "Bob", "age": 30 } },
+                { "keys": { "id": 43 }, "values": { "name": "Alice", "age": null } }
+            ],
+            "totalCount": 2
+        }));
+
+        assert!(ResultFormat::Rows.apply(json!([1, 2])).is_err());
+
+        let format: ResultFormat = serde_json::from_value(json!("columnar")).unwrap();
+        assert_eq!(format, ResultFormat::Flat);
+    }
+}
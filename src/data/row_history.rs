@@ -0,0 +1,159 @@
+
+use serde_json;
+
+/// What happened to a row of an audited table
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RowChange {
+    Insert,
+    Update,
+    Delete,
+}
+
+impl RowChange {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RowChange::Insert => "insert",
+            RowChange::Update => "update",
+            RowChange::Delete => "delete",
+        }
+    }
+
+    pub fn from_str(change: &str) -> Option<Self> {
+        match change {
+            "insert" => Some(RowChange::Insert),
+            "update" => Some(RowChange::Update),
+            "delete" => Some(RowChange::Delete),
+            _ => None,
+        }
+    }
+}
+
+/// A single row change before it is stored, the rows are objects keyed by the stored column names
+#[derive(Debug, Clone, PartialEq)]
+pub struct RowDelta {
+    pub change: RowChange,
+    pub key: serde_json::Value,
+    pub old_data: Option<serde_json::Value>,
+    pub new_data: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RowHistoryEntry {
+    pub change: RowChange,
+    pub key: serde_json::Value,
+    pub old_data: Option<serde_json::Value>,
+    pub new_data: Option<serde_json::Value>,
+    pub made_at: chrono::NaiveDateTime,
+    /// username, `None` if the user was removed since
+    pub made_by: Option<String>,
+}
+
+/// The primary key columns of a table schema, i.e. the `{"key": "column"}` constraints
+pub fn key_columns(schema: &serde_json::Value) -> Vec<String> {
+    schema["constraint"]
+        .as_array()
+        .map(|constraints| {
+            constraints
+                .iter()
+                .filter_map(|constraint| constraint["key"].as_str().map(|x| x.to_owned()))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Turns a table data result into one object per row
+pub fn table_rows(data: &serde_json::Value) -> Vec<serde_json::Map<String, serde_json::Value>> {
+    let column_names: Vec<String> = ["keys", "values"]
+        .iter()
+        .flat_map(|part| data["columns"][part].as_array().cloned().unwrap_or_default())
+        .map(|x| x.as_str().unwrap_or_default().to_owned())
+        .collect();
+
+    data["data"]
+        .as_array()
+        .map(|rows| {
+            rows
+                .iter()
+                .map(|row| {
+                    let values = ["keys", "values"]
+                        .iter()
+                        .flat_map(|part| row[part].as_array().cloned().unwrap_or_default());
+                    column_names.iter().cloned().zip(values).collect()
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// The key of a row, an object with the key columns, empty if the table has no primary key
+pub fn row_key(row: &serde_json::Map<String, serde_json::Value>, key_columns: &[String]) -> serde_json::Value {
+    let key: serde_json::Map<String, serde_json::Value> = key_columns
+        .iter()
+        .map(|column| (column.to_owned(), row.get(column).cloned().unwrap_or(serde_json::Value::Null)))
+        .collect();
+
+    serde_json::Value::Object(key)
+}
+
+/// One delta per returned row, inserted rows only have new data and deleted rows only old data
+/// for updates the old data is filled in when the deltas are stored
+pub fn row_deltas(change: RowChange, data: &serde_json::Value, key_columns: &[String]) -> Vec<RowDelta> {
+    table_rows(data)
+        .into_iter()
+        .map(|row| {
+            let key = row_key(&row, key_columns);
+            let row = serde_json::Value::Object(row);
+            let (old_data, new_data) = match change {
+                RowChange::Insert | RowChange::Update => (None, Some(row)),
+                RowChange::Delete => (Some(row), None),
+            };
+
+            RowDelta { change, key, old_data, new_data }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_row_deltas() {
+        let schema = json!({
+            "columns": [
+                { "name": "id", "dataType": "integer" },
+                { "name": "name", "dataType": "string" }
+            ],
+            "constraint": [
+                { "key": "id" },
+                { "unique": "name" }
+            ]
+        });
+        let key_columns = key_columns(&schema);
+        assert_eq!(key_columns, vec!["id".to_string()]);
+
+        let data = json!({
+            "columns": { "keys": [], "values": ["id", "name"] },
+            "data": [
+                { "keys": [], "values": [1, "Bob"] },
+                { "keys": [], "values": [2, null] }
+            ]
+        });
+
+        let deltas = row_deltas(RowChange::Insert, &data, &key_columns);
+        assert_eq!(deltas.len(), 2);
+        assert_eq!(deltas[0].key, json!({ "id": 1 }));
+        assert_eq!(deltas[0].old_data, None);
+        assert_eq!(deltas[0].new_data, Some(json!({ "id": 1, "name": "Bob" })));
+
+        let deltas = row_deltas(RowChange::Delete, &data, &key_columns);
+        assert_eq!(deltas[1].key, json!({ "id": 2 }));
+        assert_eq!(deltas[1].old_data, Some(json!({ "id": 2, "name": null })));
+        assert_eq!(deltas[1].new_data, None);
+
+        let deltas = row_deltas(RowChange::Update, &data, &[]);
+        assert_eq!(deltas[0].key, json!({}));
+    }
+}
@@ -0,0 +1,79 @@
+
+use serde_json;
+
+use data::cron::CronSchedule;
+
+/// When a stored query runs on its own, and what happens with the result
+/// the result is always published on the channel of the query
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QuerySchedule {
+    /// e.g. `*/15 * * * *`, the times are in utc
+    pub cron: String,
+    #[serde(default)]
+    pub params: serde_json::Value,
+    /// the rows are also inserted into this table, the columns have to match
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target_table: Option<String>,
+}
+
+impl QuerySchedule {
+    pub fn cron_schedule(&self) -> Result<CronSchedule, String> {
+        CronSchedule::parse(&self.cron)
+    }
+}
+
+/// What the scheduler does with the runs it missed, e.g. while the server was down
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum MissedRunPolicy {
+    /// wait for the next scheduled time
+    Skip,
+    /// run once to catch up, however many runs were missed
+    RunOnce,
+}
+
+impl Default for MissedRunPolicy {
+    fn default() -> Self {
+        MissedRunPolicy::Skip
+    }
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+/// When a script runs on its own, the runs are recorded like the other runs of the script
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScriptSchedule {
+    /// e.g. `0 3 * * *`, the times are in utc
+    pub cron: String,
+    /// passed to the script like the params of `runScript`
+    #[serde(default)]
+    pub params: serde_json::Value,
+    /// a disabled schedule is kept with the script, but doesn't run
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    #[serde(default)]
+    pub missed_runs: MissedRunPolicy,
+}
+
+impl ScriptSchedule {
+    pub fn cron_schedule(&self) -> Result<CronSchedule, String> {
+        CronSchedule::parse(&self.cron)
+    }
+}
+
+/// A single run of a scheduled query
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScheduledRun {
+    pub query_name: String,
+    pub started_at: chrono::NaiveDateTime,
+    pub duration_ms: i64,
+    pub succeeded: bool,
+    /// number of rows returned, `None` if the run failed
+    pub row_count: Option<i64>,
+    pub error: Option<String>,
+}
@@ -0,0 +1,23 @@
+
+/// A single run of a script, see `GetScriptRuns`
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScriptRun {
+    /// pass it to `GetScriptRunLog` for the output of the run
+    pub id: i64,
+    pub script_name: String,
+    pub started_at: chrono::NaiveDateTime,
+    pub duration_ms: i64,
+    pub successful: bool,
+    /// `None` if the script was killed by a signal
+    pub exit_code: Option<i32>,
+    /// username, `None` if it was run by a scheduled job or the user was removed since
+    pub run_by: Option<String>,
+    /// whether the scheduler ran it, see `RunScheduledScripts`
+    pub scheduled: bool,
+    /// left out when listing the runs
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stdout: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stderr: Option<String>,
+}
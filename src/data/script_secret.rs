@@ -0,0 +1,38 @@
+
+/// A secret of a script, the value is never sent back
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScriptSecret {
+    pub script_name: String,
+    /// the name of the environment variable the script gets it in
+    pub name: String,
+    pub modified_at: chrono::NaiveDateTime,
+    /// username, `None` if the user was removed since
+    pub modified_by: Option<String>,
+}
+
+/// Whether the name can be used as an environment variable, e.g. `API_TOKEN`
+pub fn is_valid_secret_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_uppercase() || c == '_' => {},
+        _ => return false,
+    };
+
+    chars.all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_')
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_is_valid_secret_name() {
+        assert!(is_valid_secret_name("API_TOKEN"));
+        assert!(is_valid_secret_name("_S3_KEY2"));
+        assert!(!is_valid_secret_name(""));
+        assert!(!is_valid_secret_name("2FA"));
+        assert!(!is_valid_secret_name("api_token"));
+        assert!(!is_valid_secret_name("API-TOKEN"));
+    }
+}
@@ -0,0 +1,96 @@
+use data::Script;
+
+/// A stored version of a script, every update adds one, see `GetScriptHistory`
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScriptVersion {
+    /// pass it to `RestoreScriptVersion` to go back to this version
+    pub version: i64,
+    #[serde(flatten)]
+    pub script: Script,
+    pub is_deleted: bool,
+    pub modified_at: chrono::NaiveDateTime,
+    /// username, `None` if the user was removed since
+    pub modified_by: Option<String>,
+    /// the changes to the text since the version before, see `text_diff`
+    pub diff: String,
+}
+
+/// `start,count` of a hunk with 1-based lines, a hunk without lines starts at the line before it
+fn hunk_range(start: usize, count: usize) -> String {
+    match count {
+        0 => format!("{},0", start),
+        1 => format!("{}", start + 1),
+        _ => format!("{},{}", start + 1, count),
+    }
+}
+
+/// The changed lines between the texts as a unified diff without context lines, empty if nothing changed
+pub fn text_diff(old: &str, new: &str) -> String {
+    let old_lines: Vec<&str> = old.lines().collect();
+    let new_lines: Vec<&str> = new.lines().collect();
+    let (old_len, new_len) = (old_lines.len(), new_lines.len());
+
+    // the length of the longest common subsequence of `old_lines[i..]` and `new_lines[j..]`
+    let mut common = vec![vec![0usize; new_len + 1]; old_len + 1];
+    for i in (0..old_len).rev() {
+        for j in (0..new_len).rev() {
+            common[i][j] = if old_lines[i] == new_lines[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    let mut diff = String::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old_len || j < new_len {
+        if i < old_len && j < new_len && old_lines[i] == new_lines[j] {
+            i += 1;
+            j += 1;
+            continue;
+        }
+
+        // everything until the next common line is a single hunk
+        let (old_start, new_start) = (i, j);
+        let mut removed = vec![];
+        let mut added = vec![];
+        while i < old_len || j < new_len {
+            if i < old_len && j < new_len && old_lines[i] == new_lines[j] {
+                break;
+            }
+
+            if j == new_len || (i < old_len && common[i + 1][j] >= common[i][j + 1]) {
+                removed.push(old_lines[i]);
+                i += 1;
+            } else {
+                added.push(new_lines[j]);
+                j += 1;
+            }
+        }
+
+        diff.push_str(&format!("@@ -{} +{} @@\n", hunk_range(old_start, removed.len()), hunk_range(new_start, added.len())));
+        for line in removed {
+            diff.push_str(&format!("-{}\n", line));
+        }
+        for line in added {
+            diff.push_str(&format!("+{}\n", line));
+        }
+    }
+
+    diff
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_text_diff() {
+        assert_eq!(text_diff("a\nb\nc", "a\nb\nc"), "");
+        assert_eq!(text_diff("a\nb\nc", "a\nx\nc\nd"), "@@ -2 +2 @@\n-b\n+x\n@@ -3,0 +4 @@\n+d\n");
+        assert_eq!(text_diff("", "print(1)\nprint(2)"), "@@ -0,0 +1,2 @@\n+print(1)\n+print(2)\n");
+        assert_eq!(text_diff("a\nb\nc\nd", "a\nd"), "@@ -2,2 +1,0 @@\n-b\n-c\n");
+    }
+}
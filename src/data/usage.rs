@@ -0,0 +1,23 @@
+
+/// How often an entity was used by `RunQuery`, `RunScript` and the table data actions
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EntityUsage {
+    /// `table`, `query` or `script`
+    #[serde(rename = "type")]
+    pub type_name: String,
+    pub name: String,
+    pub use_count: i64,
+    /// `None` if the entity was never used
+    pub last_used_at: Option<chrono::NaiveDateTime>,
+    /// the users that used it the most, most uses first
+    pub top_users: Vec<UserUsage>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserUsage {
+    /// `None` for the jobs the server runs by itself
+    pub username: Option<String>,
+    pub use_count: i64,
+}
@@ -12,3 +12,54 @@ pub enum OnDuplicate {
     Fail,
     Update,
 }
+
+/// A single step of a bulk table data change, e.g. `{"op": "delete", "keys": {"id": 1}}`
+/// the payloads are the same as for the single row actions
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[serde(tag = "op")]
+pub enum TableDataOperation {
+    Insert {
+        data: serde_json::Value,
+    },
+    Upsert {
+        data: serde_json::Value,
+    },
+    Update {
+        data: serde_json::Value,
+    },
+    Delete {
+        keys: serde_json::Value,
+    },
+}
+
+impl TableDataOperation {
+    pub fn name(&self) -> &'static str {
+        match self {
+            TableDataOperation::Insert { .. } => "insert",
+            TableDataOperation::Upsert { .. } => "upsert",
+            TableDataOperation::Update { .. } => "update",
+            TableDataOperation::Delete { .. } => "delete",
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_table_data_operation() {
+        let operations: Vec<TableDataOperation> = serde_json::from_value(json!([
+            { "op": "insert", "data": { "id": 1, "name": "Bob" } },
+            { "op": "update", "data": { "keys": { "id": 1 }, "values": { "name": "Rob" } } },
+            { "op": "delete", "keys": { "id": 1 } }
+        ])).unwrap();
+
+        assert_eq!(operations[0], TableDataOperation::Insert { data: json!({ "id": 1, "name": "Bob" }) });
+        assert_eq!(operations[2].name(), "delete");
+
+        let unknown: Result<TableDataOperation, _> = serde_json::from_value(json!({ "op": "truncate" }));
+        assert!(unknown.is_err());
+    }
+}
@@ -0,0 +1,223 @@
+use openssl::hash::MessageDigest;
+use openssl::pkey::PKey;
+use openssl::sign::Signer;
+
+use data::channels::Channels;
+
+/// the header with the signature of the body, `sha256=` followed by the hex of the HMAC-SHA256
+pub const SIGNATURE_HEADER: &'static str = "X-Kakapo-Signature";
+/// the header with the id of the delivery, the same across the retries so that the receiver can skip duplicates
+pub const DELIVERY_HEADER: &'static str = "X-Kakapo-Delivery";
+
+/// the longest wait between two attempts, however many attempts there were
+pub const MAX_BACKOFF_SECONDS: u64 = 60 * 60;
+
+/// How often a failed delivery is retried, the wait doubles after each attempt
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RetryPolicy {
+    /// including the first one, the delivery is given up after that
+    #[serde(default = "RetryPolicy::default_max_attempts")]
+    pub max_attempts: u32,
+    /// the wait after the first failed attempt
+    #[serde(default = "RetryPolicy::default_backoff_seconds")]
+    pub backoff_seconds: u64,
+}
+
+impl RetryPolicy {
+    fn default_max_attempts() -> u32 {
+        5
+    }
+
+    fn default_backoff_seconds() -> u64 {
+        30
+    }
+
+    /// the wait after the `attempts`th failed attempt
+    pub fn backoff(&self, attempts: u32) -> u64 {
+        let exponent = attempts.saturating_sub(1).min(32);
+        self.backoff_seconds
+            .saturating_mul(1u64 << exponent)
+            .min(MAX_BACKOFF_SECONDS)
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: Self::default_max_attempts(),
+            backoff_seconds: Self::default_backoff_seconds(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DeliveryStatus {
+    Pending,
+    Delivered,
+    /// every attempt failed
+    Failed,
+}
+
+impl DeliveryStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DeliveryStatus::Pending => "pending",
+            DeliveryStatus::Delivered => "delivered",
+            DeliveryStatus::Failed => "failed",
+        }
+    }
+
+    pub fn from_str(status: &str) -> Option<Self> {
+        match status {
+            "pending" => Some(DeliveryStatus::Pending),
+            "delivered" => Some(DeliveryStatus::Delivered),
+            "failed" => Some(DeliveryStatus::Failed),
+            _ => None,
+        }
+    }
+}
+
+/// A message sent, or to be sent, to a webhook, see `GetWebhookDeliveries`
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WebhookDelivery {
+    pub id: i64,
+    pub webhook_name: String,
+    pub channel: Channels,
+    pub action: String,
+    pub status: DeliveryStatus,
+    pub attempts: i32,
+    /// the status code of the last response, `None` if there was no response
+    pub last_status_code: Option<i32>,
+    pub last_error: Option<String>,
+    pub created_at: chrono::NaiveDateTime,
+    /// when the next attempt is due, only meaningful while it's pending
+    pub next_attempt_at: chrono::NaiveDateTime,
+    pub delivered_at: Option<chrono::NaiveDateTime>,
+}
+
+/// A delivery that is due, with everything needed to post it
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DueDelivery {
+    pub id: i64,
+    pub webhook_name: String,
+    pub url: String,
+    pub retry: RetryPolicy,
+    /// the attempts made so far
+    pub attempts: i32,
+    /// the json body of the request
+    pub body: String,
+    /// the value of the signature header, the secret itself stays in the metastore
+    pub signature: String,
+}
+
+/// A url attached to a channel by an admin, see `AddChannelWebhook`. Unlike the webhook entities, it
+/// isn't versioned nor scoped to a domain, every message published on the channel is posted to it
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChannelWebhook {
+    pub id: i64,
+    pub channel: Channels,
+    pub url: String,
+    pub retry: RetryPolicy,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+/// The secret signs the deliveries, it is never returned
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NewChannelWebhook {
+    pub channel: Channels,
+    pub url: String,
+    pub secret: String,
+    #[serde(default)]
+    pub retry: RetryPolicy,
+}
+
+/// A webhook secret encrypted with the secrets key of the server, see `script_secrets::encrypt`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EncryptedSecret {
+    pub nonce: Vec<u8>,
+    /// with the authentication tag appended
+    pub ciphertext: Vec<u8>,
+}
+
+impl EncryptedSecret {
+    /// as it's kept in the `webhook_info` of the webhook entities
+    pub fn to_value(&self) -> serde_json::Value {
+        json!({
+            "nonce": base64::encode(&self.nonce),
+            "ciphertext": base64::encode(&self.ciphertext),
+        })
+    }
+
+    pub fn from_value(value: &serde_json::Value) -> Option<Self> {
+        let decode = |field: &str| value[field].as_str().and_then(|x| base64::decode(x).ok());
+        Some(Self {
+            nonce: decode("nonce")?,
+            ciphertext: decode("ciphertext")?,
+        })
+    }
+}
+
+/// what the secret of a webhook entity is encrypted for, so that it can't be moved to another one
+pub fn webhook_secret_name(name: &str) -> String {
+    format!("webhook/{}", name)
+}
+
+/// same as `webhook_secret_name`, the url is unique for the channel
+pub fn channel_webhook_secret_name(url: &str) -> String {
+    format!("channel_webhook/{}", url)
+}
+
+/// The value of the signature header for the body, the receiver computes it again with the secret and compares
+pub fn sign(secret: &str, body: &[u8]) -> Result<String, openssl::error::ErrorStack> {
+    let key = PKey::hmac(secret.as_bytes())?;
+    let mut signer = Signer::new(MessageDigest::sha256(), &key)?;
+    signer.update(body)?;
+    let hmac = signer.sign_to_vec()?;
+
+    let hex: String = hmac
+        .iter()
+        .map(|x| format!("{:02x}", x))
+        .collect();
+
+    Ok(format!("sha256={}", hex))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_encrypted_secret_value() {
+        let secret = EncryptedSecret { nonce: vec![1, 2, 3], ciphertext: vec![4, 5, 6, 7] };
+        assert_eq!(EncryptedSecret::from_value(&secret.to_value()), Some(secret));
+        assert_eq!(EncryptedSecret::from_value(&json!("plain text")), None);
+    }
+
+    #[test]
+    fn test_sign() {
+        // from RFC 4231, test case 2
+        assert_eq!(
+            sign("Jefe", b"what do ya want for nothing?").unwrap(),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843");
+        assert_ne!(sign("other", b"what do ya want for nothing?").unwrap(), sign("Jefe", b"what do ya want for nothing?").unwrap());
+    }
+
+    #[test]
+    fn test_backoff() {
+        let retry = RetryPolicy { max_attempts: 10, backoff_seconds: 30 };
+        assert_eq!(retry.backoff(1), 30);
+        assert_eq!(retry.backoff(2), 60);
+        assert_eq!(retry.backoff(3), 120);
+        assert_eq!(retry.backoff(9), MAX_BACKOFF_SECONDS);
+        assert_eq!(retry.backoff(100), MAX_BACKOFF_SECONDS);
+
+        let retry: RetryPolicy = serde_json::from_value(json!({"maxAttempts": 3})).unwrap();
+        assert_eq!(retry, RetryPolicy { max_attempts: 3, backoff_seconds: 30 });
+    }
+}
@@ -0,0 +1,74 @@
+use std::time::Duration;
+
+use actix::Actor;
+use actix::Addr;
+use actix::Arbiter;
+use actix::AsyncContext;
+use actix::Context;
+
+use futures::Future;
+
+use connection::executor::Executor;
+use data::claims::AuthClaims;
+use data::message_retention::MessageRetention;
+use model::actions::PurgeMessages;
+use state::ActionState;
+use view::action_wrapper::ActionWrapper;
+use view::shutdown;
+
+/// Deletes the published messages past the retention once per interval, the purged volume is
+/// counted in the broadcast metrics by the action
+pub struct MessageCleanupJob {
+    executor: Addr<Executor>,
+    retention: MessageRetention,
+    interval: Duration,
+}
+
+impl MessageCleanupJob {
+    pub fn new(executor: Addr<Executor>, retention: MessageRetention, interval: Duration) -> Self {
+        Self {
+            executor,
+            retention,
+            interval,
+        }
+    }
+
+    fn run(&self) {
+        debug!("purging the messages past {:?}", &self.retention);
+
+        let action = PurgeMessages::<ActionState>::new(self.retention.to_owned());
+        let action_wrapper = ActionWrapper::new(Ok((None, action)))
+            .with_claims(AuthClaims::system());
+
+        let job = self.executor
+            .send(action_wrapper)
+            .then(move |res| {
+                match res {
+                    Ok(Ok(res)) => {
+                        let purged_messages = res.get_data_ref().purged_messages;
+                        if purged_messages > 0 {
+                            info!("message cleanup job purged {} messages", purged_messages);
+                        }
+                    },
+                    Ok(Err(err)) => error!("message cleanup job failed: {:?}", &err),
+                    Err(err) => error!("could not reach the executor for the message cleanup job: {:?}", &err),
+                };
+                Ok(())
+            });
+
+        Arbiter::spawn(job);
+    }
+}
+
+impl Actor for MessageCleanupJob {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        info!("Starting the message cleanup job, running every {:?}", &self.interval);
+        ctx.run_interval(self.interval, |job, _| {
+            if !shutdown::is_shutting_down() {
+                job.run();
+            }
+        });
+    }
+}
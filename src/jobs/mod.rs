@@ -0,0 +1,8 @@
+//! Background jobs the server runs on its own, they send their actions to the executor with the system claims
+
+pub mod message_cleanup;
+pub mod retention;
+pub mod scheduler;
+pub mod script_jobs;
+pub mod subscription_cleanup;
+pub mod webhooks;
@@ -0,0 +1,74 @@
+use std::time::Duration;
+
+use actix::Actor;
+use actix::Addr;
+use actix::Arbiter;
+use actix::AsyncContext;
+use actix::Context;
+
+use futures::Future;
+
+use connection::executor::Executor;
+use data::claims::AuthClaims;
+use model::actions::ApplyRetentionPolicies;
+use state::ActionState;
+use view::action_wrapper::ActionWrapper;
+use view::shutdown;
+
+/// Applies the retention policies of all the tables, in every domain, once per interval
+/// the progress and the results are published on the table channels by the action
+pub struct RetentionJob {
+    executor: Addr<Executor>,
+    domains: Vec<String>,
+    interval: Duration,
+}
+
+impl RetentionJob {
+    pub fn new(executor: Addr<Executor>, domains: Vec<String>, interval: Duration) -> Self {
+        Self {
+            executor,
+            domains,
+            interval,
+        }
+    }
+
+    fn run(&self) {
+        for domain in &self.domains {
+            debug!("applying the retention policies of domain {:?}", domain);
+
+            let action = ApplyRetentionPolicies::<ActionState>::new();
+            let action_wrapper = ActionWrapper::new(Ok((Some(domain.to_owned()), action)))
+                .with_claims(AuthClaims::system());
+
+            let domain = domain.to_owned();
+            let job = self.executor
+                .send(action_wrapper)
+                .then(move |res| {
+                    match res {
+                        Ok(Ok(res)) => {
+                            let deleted_rows: usize = res.get_data_ref().0.iter().map(|x| x.deleted_rows).sum();
+                            info!("retention job deleted {} rows in domain {:?}", deleted_rows, &domain);
+                        },
+                        Ok(Err(err)) => error!("retention job failed in domain {:?}: {:?}", &domain, &err),
+                        Err(err) => error!("could not reach the executor for the retention job: {:?}", &err),
+                    };
+                    Ok(())
+                });
+
+            Arbiter::spawn(job);
+        }
+    }
+}
+
+impl Actor for RetentionJob {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        info!("Starting the retention job, running every {:?}", &self.interval);
+        ctx.run_interval(self.interval, |job, _| {
+            if !shutdown::is_shutting_down() {
+                job.run();
+            }
+        });
+    }
+}
@@ -0,0 +1,132 @@
+use std::time::Duration;
+
+use actix::Actor;
+use actix::Addr;
+use actix::Arbiter;
+use actix::AsyncContext;
+use actix::Context;
+
+use chrono::Timelike;
+use futures::Future;
+
+use connection::executor::Executor;
+use data::claims::AuthClaims;
+use model::actions::RunScheduledQueries;
+use model::actions::RunScheduledScripts;
+use state::ActionState;
+use view::action_wrapper::ActionWrapper;
+use view::shutdown;
+
+/// checked more often than every minute, so that a late tick doesn't skip a minute
+const TICK_INTERVAL_SECS: u64 = 10;
+
+/// Runs the scheduled queries and scripts of every domain, once for each minute
+pub struct SchedulerJob {
+    executor: Addr<Executor>,
+    domains: Vec<String>,
+    schedule_queries: bool,
+    schedule_scripts: bool,
+    last_run: Option<chrono::NaiveDateTime>,
+}
+
+impl SchedulerJob {
+    pub fn new(executor: Addr<Executor>, domains: Vec<String>, schedule_queries: bool, schedule_scripts: bool) -> Self {
+        Self {
+            executor,
+            domains,
+            schedule_queries,
+            schedule_scripts,
+            last_run: None,
+        }
+    }
+
+    fn tick(&mut self) {
+        let now = chrono::Utc::now().naive_utc();
+        let minute = now
+            .with_second(0)
+            .and_then(|x| x.with_nanosecond(0))
+            .unwrap_or(now);
+
+        if self.last_run == Some(minute) {
+            return;
+        }
+        self.last_run = Some(minute);
+
+        for domain in &self.domains {
+            if self.schedule_queries {
+                self.run_queries(domain, minute);
+            }
+            if self.schedule_scripts {
+                self.run_scripts(domain, minute);
+            }
+        }
+    }
+
+    fn run_queries(&self, domain: &str, minute: chrono::NaiveDateTime) {
+        debug!("running the scheduled queries of domain {:?} for {}", domain, &minute);
+
+        let action = RunScheduledQueries::<ActionState>::new(minute);
+        let action_wrapper = ActionWrapper::new(Ok((Some(domain.to_owned()), action)))
+            .with_claims(AuthClaims::system());
+
+        let domain = domain.to_owned();
+        let job = self.executor
+            .send(action_wrapper)
+            .then(move |res| {
+                match res {
+                    Ok(Ok(res)) => {
+                        let runs = &res.get_data_ref().0;
+                        if !runs.is_empty() {
+                            info!("ran {} scheduled queries in domain {:?}", runs.len(), &domain);
+                        }
+                    },
+                    Ok(Err(err)) => error!("scheduled queries failed in domain {:?}: {:?}", &domain, &err),
+                    Err(err) => error!("could not reach the executor for the scheduled queries: {:?}", &err),
+                };
+                Ok(())
+            });
+
+        Arbiter::spawn(job);
+    }
+
+    /// the scripts run one after the other on the same executor thread
+    fn run_scripts(&self, domain: &str, minute: chrono::NaiveDateTime) {
+        debug!("running the scheduled scripts of domain {:?} for {}", domain, &minute);
+
+        let action = RunScheduledScripts::<ActionState>::new(minute);
+        let action_wrapper = ActionWrapper::new(Ok((Some(domain.to_owned()), action)))
+            .with_claims(AuthClaims::system());
+
+        let domain = domain.to_owned();
+        let job = self.executor
+            .send(action_wrapper)
+            .then(move |res| {
+                match res {
+                    Ok(Ok(res)) => {
+                        let runs = &res.get_data_ref().0;
+                        if !runs.is_empty() {
+                            info!("ran {} scheduled scripts in domain {:?}", runs.len(), &domain);
+                        }
+                    },
+                    Ok(Err(err)) => error!("scheduled scripts failed in domain {:?}: {:?}", &domain, &err),
+                    Err(err) => error!("could not reach the executor for the scheduled scripts: {:?}", &err),
+                };
+                Ok(())
+            });
+
+        Arbiter::spawn(job);
+    }
+}
+
+impl Actor for SchedulerJob {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        info!("Starting the scheduler");
+        ctx.run_interval(Duration::from_secs(TICK_INTERVAL_SECS), |job, _| {
+            if !shutdown::is_shutting_down() {
+                job.tick();
+            }
+        });
+    }
+}
@@ -0,0 +1,23 @@
+use actix::Addr;
+
+use connection::executor::Executor;
+use data::claims::AuthClaims;
+use model::actions::FinishScriptJob;
+use scripting::jobs::ScriptJob;
+use state::ActionState;
+use view::action_wrapper::ActionWrapper;
+
+/// Called by the script workers with every job they finished, the run is recorded and published on
+/// an executor, with the domain and the claims the job was submitted with
+pub fn finish_on_executor(executor: Addr<Executor>) -> impl Fn(ScriptJob) + Send + Sync + 'static {
+    move |job| {
+        debug!("finishing the script job {:?}", &job.id);
+
+        let claims = job.claims.to_owned().unwrap_or_else(AuthClaims::system);
+        let action = FinishScriptJob::<ActionState>::new(job.id);
+        let action_wrapper = ActionWrapper::new(Ok((job.domain, action)))
+            .with_claims(claims);
+
+        executor.do_send(action_wrapper);
+    }
+}
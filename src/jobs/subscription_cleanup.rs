@@ -0,0 +1,73 @@
+use std::time::Duration;
+
+use actix::Actor;
+use actix::Addr;
+use actix::Arbiter;
+use actix::AsyncContext;
+use actix::Context;
+
+use futures::Future;
+
+use connection::executor::Executor;
+use data::claims::AuthClaims;
+use model::actions::ExpireSubscriptions;
+use state::ActionState;
+use view::action_wrapper::ActionWrapper;
+use view::shutdown;
+
+/// Removes the subscriptions past their ttl once per interval, and the ones of the users that
+/// haven't logged in for `inactive_for` seconds if it is set
+pub struct SubscriptionCleanupJob {
+    executor: Addr<Executor>,
+    inactive_for: Option<u64>,
+    interval: Duration,
+}
+
+impl SubscriptionCleanupJob {
+    pub fn new(executor: Addr<Executor>, inactive_for: Option<u64>, interval: Duration) -> Self {
+        Self {
+            executor,
+            inactive_for,
+            interval,
+        }
+    }
+
+    fn run(&self) {
+        debug!("expiring the subscriptions, inactive for {:?} seconds", &self.inactive_for);
+
+        let action = ExpireSubscriptions::<ActionState>::new(self.inactive_for);
+        let action_wrapper = ActionWrapper::new(Ok((None, action)))
+            .with_claims(AuthClaims::system());
+
+        let job = self.executor
+            .send(action_wrapper)
+            .then(move |res| {
+                match res {
+                    Ok(Ok(res)) => {
+                        let expired_subscriptions = res.get_data_ref().expired_subscriptions;
+                        if expired_subscriptions > 0 {
+                            info!("subscription cleanup job removed {} subscriptions", expired_subscriptions);
+                        }
+                    },
+                    Ok(Err(err)) => error!("subscription cleanup job failed: {:?}", &err),
+                    Err(err) => error!("could not reach the executor for the subscription cleanup job: {:?}", &err),
+                };
+                Ok(())
+            });
+
+        Arbiter::spawn(job);
+    }
+}
+
+impl Actor for SubscriptionCleanupJob {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        info!("Starting the subscription cleanup job, running every {:?}", &self.interval);
+        ctx.run_interval(self.interval, |job, _| {
+            if !shutdown::is_shutting_down() {
+                job.run();
+            }
+        });
+    }
+}
@@ -0,0 +1,170 @@
+use std::time::Duration;
+
+use actix::Actor;
+use actix::Addr;
+use actix::Arbiter;
+use actix::AsyncContext;
+use actix::Context;
+use actix::MailboxError;
+use actix_web::client;
+
+use futures::Future;
+
+use connection::executor::Executor;
+use data::claims::AuthClaims;
+use data::webhook::DELIVERY_HEADER;
+use data::webhook::DueDelivery;
+use data::webhook::SIGNATURE_HEADER;
+use model::actions::ClaimChannelWebhookDeliveries;
+use model::actions::ClaimWebhookDeliveries;
+use model::actions::ActionResult;
+use model::actions::results::DueDeliveriesResult;
+use model::actions::RecordWebhookAttempt;
+use state::ActionState;
+use view::action_wrapper::ActionWrapper;
+use view::shutdown;
+use view::shutdown::InFlightGuard;
+
+const TICK_INTERVAL_SECS: u64 = 5;
+/// most deliveries posted by a domain in one tick, the rest are left for the next ones
+const DELIVERIES_PER_TICK: usize = 100;
+const REQUEST_TIMEOUT_SECS: u64 = 30;
+/// a claimed delivery is due again after this, in case the server stopped before its attempt was recorded
+const CLAIM_SECS: u64 = 2 * REQUEST_TIMEOUT_SECS;
+
+/// Posts the due webhook deliveries of every domain and of the channel webhooks, the outcome of each attempt
+/// is recorded by `RecordWebhookAttempt`
+pub struct WebhookJob {
+    executor: Addr<Executor>,
+    domains: Vec<String>,
+}
+
+impl WebhookJob {
+    pub fn new(executor: Addr<Executor>, domains: Vec<String>) -> Self {
+        Self {
+            executor,
+            domains,
+        }
+    }
+
+    fn tick(&self) {
+        for domain in &self.domains {
+            let action = ClaimWebhookDeliveries::<ActionState>::new(DELIVERIES_PER_TICK, CLAIM_SECS);
+            let action_wrapper = ActionWrapper::new(Ok((Some(domain.to_owned()), action)))
+                .with_claims(AuthClaims::system());
+
+            let domain = Some(domain.to_owned());
+            let executor = self.executor.clone();
+            let job = self.executor
+                .send(action_wrapper)
+                .then(move |res| {
+                    post_claimed_deliveries(executor, domain, res);
+                    Ok(())
+                });
+
+            Arbiter::spawn(job);
+        }
+
+        // the channel webhooks aren't scoped to a domain
+        let action = ClaimChannelWebhookDeliveries::<ActionState>::new(DELIVERIES_PER_TICK, CLAIM_SECS);
+        let action_wrapper = ActionWrapper::new(Ok((None, action)))
+            .with_claims(AuthClaims::system());
+
+        let executor = self.executor.clone();
+        let job = self.executor
+            .send(action_wrapper)
+            .then(move |res| {
+                post_claimed_deliveries(executor, None, res);
+                Ok(())
+            });
+
+        Arbiter::spawn(job);
+    }
+}
+
+fn post_claimed_deliveries(executor: Addr<Executor>, domain: Option<String>, res: Result<ActionResult<DueDeliveriesResult>, MailboxError>) {
+    match res {
+        Ok(Ok(res)) => {
+            let deliveries = res.get_data().0;
+            if !deliveries.is_empty() {
+                debug!("posting {} webhook deliveries of domain {:?}", deliveries.len(), &domain);
+            }
+            for delivery in deliveries {
+                // until the attempt is recorded, so that a shutdown doesn't post it twice
+                let in_flight_guard = InFlightGuard::enter();
+                let posted = post_delivery(executor.clone(), domain.to_owned(), delivery)
+                    .then(move |res| {
+                        drop(in_flight_guard);
+                        res
+                    });
+                Arbiter::spawn(posted);
+            }
+        },
+        Ok(Err(err)) => error!("could not get the webhook deliveries of domain {:?}: {:?}", &domain, &err),
+        Err(err) => error!("could not reach the executor for the webhook job: {:?}", &err),
+    };
+}
+
+fn post_delivery(executor: Addr<Executor>, domain: Option<String>, delivery: DueDelivery) -> Box<Future<Item = (), Error = ()>> {
+    let request = client::post(&delivery.url)
+        .header("Content-Type", "application/json")
+        .header(SIGNATURE_HEADER, delivery.signature.to_owned())
+        .header(DELIVERY_HEADER, delivery.id.to_string())
+        .timeout(Duration::from_secs(REQUEST_TIMEOUT_SECS))
+        .body(delivery.body.to_owned());
+
+    let request = match request {
+        Ok(request) => request,
+        Err(err) => return Box::new(record_attempt(executor, domain, delivery, None, Some(err.to_string()))),
+    };
+
+    let attempt = request
+        .send()
+        .then(move |res| {
+            let (status_code, error) = match res {
+                Ok(response) => (Some(response.status().as_u16() as i32), None),
+                Err(err) => (None, Some(err.to_string())),
+            };
+            record_attempt(executor, domain, delivery, status_code, error)
+        });
+
+    Box::new(attempt)
+}
+
+fn record_attempt(
+    executor: Addr<Executor>,
+    domain: Option<String>,
+    delivery: DueDelivery,
+    status_code: Option<i32>,
+    error: Option<String>,
+) -> impl Future<Item = (), Error = ()> {
+    debug!("webhook delivery {:?} to {:?}: status {:?}, error {:?}", delivery.id, &delivery.webhook_name, &status_code, &error);
+
+    let action = RecordWebhookAttempt::<ActionState>::new(delivery.id, delivery.attempts, delivery.retry, status_code, error);
+    let action_wrapper = ActionWrapper::new(Ok((domain.to_owned(), action)))
+        .with_claims(AuthClaims::system());
+
+    executor
+        .send(action_wrapper)
+        .then(move |res| {
+            match res {
+                Ok(Ok(_)) => (),
+                Ok(Err(err)) => error!("could not record the webhook delivery {:?} of domain {:?}: {:?}", delivery.id, &domain, &err),
+                Err(err) => error!("could not reach the executor for the webhook job: {:?}", &err),
+            };
+            Ok(())
+        })
+}
+
+impl Actor for WebhookJob {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        info!("Starting the webhook job, running every {}s", TICK_INTERVAL_SECS);
+        ctx.run_interval(Duration::from_secs(TICK_INTERVAL_SECS), |job, _| {
+            if !shutdown::is_shutting_down() {
+                job.tick();
+            }
+        });
+    }
+}
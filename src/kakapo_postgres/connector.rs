@@ -3,6 +3,10 @@ use diesel::r2d2::PooledConnection;
 use diesel::r2d2::Pool;
 use diesel::prelude::PgConnection;
 
+use std::time::Duration;
+use diesel::Connection;
+use diesel::connection::TransactionManager;
+
 use plugins::v1::Domain;
 use plugins::v1::Datastore;
 use plugins::v1::DomainBuilder;
@@ -10,6 +14,9 @@ use plugins::v1::DataStoreEntity;
 use plugins::v1::DatastoreError;
 use plugins::v1::DataQuery;
 use plugins::v1::DataQueryEntity;
+use plugins::v1::QueryLimit;
+use plugins::v1::QueryResult;
+use plugins::v1::View;
 
 use kakapo_postgres::data::Table;
 use kakapo_postgres::data::TableData;
@@ -18,40 +25,70 @@ use kakapo_postgres::data::KeyData;
 use kakapo_postgres::KakapoPostgres;
 use kakapo_postgres::update_state::UpdateTable;
 use kakapo_postgres::update_state::UpdateTableOps;
+use kakapo_postgres::update_state::plan_table_update;
+use kakapo_postgres::update_state::UpdateView;
+use kakapo_postgres::update_state::UpdateViewOps;
 use kakapo_postgres::table::CrudTable;
 use kakapo_postgres::table::CrudTableOps;
 use kakapo_postgres::data::Query;
 use kakapo_postgres::query::QueryTable;
 use kakapo_postgres::query::QueryTableOps;
+use kakapo_postgres::query::result_schema;
+use kakapo_postgres::data::RawTableData;
 use kakapo_postgres::data::QueryParams;
+use kakapo_postgres::data::TableQuery;
+use kakapo_postgres::data::PagedTableData;
+use kakapo_postgres::data::FilteredUpdate;
+use kakapo_postgres::data::FilteredDelete;
 
 #[derive(Clone)]
 pub struct KakapoPostgresDone {
     pool: Pool<ConnectionManager<PgConnection>>,
+    statement_timeout: Option<u64>,
 }
 
 
 pub struct KakapoPostgresConnection {
-    conn: PooledConnection<ConnectionManager<PgConnection>>
+    conn: PooledConnection<ConnectionManager<PgConnection>>,
+    statement_timeout: Option<u64>,
 }
 
 
 impl DomainBuilder for KakapoPostgres {
     fn build(&self) -> Box<Domain> {
         info!("Initializing postgres connection");
-        let database_url = format!(
-            "postgres://{}:{}@{}:{}/{}",
-            self.user,
-            self.pass,
-            self.host,
-            self.port,
-            self.db,
-        );
+        let database_url = match self.url {
+            Some(ref url) => url.to_owned(),
+            None => format!(
+                "postgres://{}:{}@{}:{}/{}",
+                self.user,
+                self.pass,
+                self.host,
+                self.port,
+                self.db,
+            ),
+        };
+        let database_url = self.tls_config.apply(&database_url);
         let manager = ConnectionManager::<PgConnection>::new(database_url);
-        let pool = Pool::builder().build(manager)
+        let mut builder = Pool::builder();
+        if let Some(connection_timeout) = self.connection_timeout {
+            builder = builder.connection_timeout(Duration::from_secs(connection_timeout));
+        }
+        let pool = builder.build(manager)
             .expect("Could not start connection");
 
-        Box::new(KakapoPostgresDone { pool })
+        Box::new(KakapoPostgresDone { pool, statement_timeout: self.statement_timeout })
+    }
+}
+
+impl KakapoPostgresDone {
+    /// the pool is exhausted or the database is down, either way there is no point waiting any longer
+    fn get_connection(&self) -> Result<PooledConnection<ConnectionManager<PgConnection>>, DatastoreError> {
+        self.pool.get()
+            .map_err(|err| {
+                warn!("Could not get a postgres connection: {:?}", &err);
+                DatastoreError::Unavailable
+            })
     }
 }
 
@@ -61,38 +98,55 @@ impl Domain for KakapoPostgresDone {
         "POSTGRES"
     }
 
-    fn connect_datastore(&self) -> Option<Box<Datastore>> {
+    fn connect_datastore(&self) -> Result<Box<Datastore>, DatastoreError> {
         debug!("connecting to the pool for datastore");
-        let conn = self.pool.get()
-            .expect("Could not get connection");
+        let conn = self.get_connection()?;
 
-        let postgres_connection = KakapoPostgresConnection { conn };
-        Some(Box::new(postgres_connection))
+        let postgres_connection = KakapoPostgresConnection { conn, statement_timeout: self.statement_timeout };
+        Ok(Box::new(postgres_connection))
     }
 
-    fn connect_query(&self) -> Option<Box<DataQuery>> {
+    fn connect_query(&self) -> Result<Box<DataQuery>, DatastoreError> {
         debug!("connecting to the pool for query");
-        let conn = self.pool.get()
-            .expect("Could not get connection");
+        let conn = self.get_connection()?;
+
+        let postgres_connection = KakapoPostgresConnection { conn, statement_timeout: self.statement_timeout };
+        Ok(Box::new(postgres_connection))
+    }
+}
 
-        let postgres_connection = KakapoPostgresConnection { conn };
-        Some(Box::new(postgres_connection))
+fn parse_table_query(query: &serde_json::Value) -> Result<TableQuery, DatastoreError> {
+    if query.is_null() {
+        Ok(TableQuery::default())
+    } else {
+        serde_json::from_value(query.to_owned())
+            .map_err(|_| DatastoreError::SerializationError)
     }
 }
 
 // All of this is just boilerplate -__-
 impl Datastore for KakapoPostgresConnection {
-    fn retrieve(&self, data_store: &DataStoreEntity, _: &serde_json::Value) -> Result<serde_json::Value, DatastoreError> {
+    fn retrieve(&self, data_store: &DataStoreEntity, query: &serde_json::Value) -> Result<serde_json::Value, DatastoreError> {
         let table: Result<Table, DatastoreError> = data_store.into();
         let table = table?;
 
+        let table_query = parse_table_query(query)?;
+
         let action = CrudTable::new(
             &table,
             &self.conn,
         );
 
-        let res = action.retrieve()?;
-        let res = serde_json::to_value(res)
+        let res = action.retrieve(&table_query)?;
+        let res = if table_query.is_paginated() {
+            let total_count = action.count(&table_query)?;
+            let start = table_query.start.unwrap_or(0);
+            let end = start + res.data.len() as i64;
+            serde_json::to_value(PagedTableData { table_data: res, start, end, total_count })
+        } else {
+            serde_json::to_value(res)
+        };
+        let res = res
             .map_err(|err| DatastoreError::SerializationError)?;
 
         Ok(res)
@@ -178,6 +232,111 @@ impl Datastore for KakapoPostgresConnection {
         Ok(res)
     }
 
+    fn update_where(&self, data_store: &DataStoreEntity, filtered_values: &serde_json::Value) -> Result<serde_json::Value, DatastoreError> {
+        let table: Result<Table, DatastoreError> = data_store.into();
+        let table = table?;
+
+        let filtered_update: FilteredUpdate = serde_json::from_value(filtered_values.to_owned())
+            .map_err(|_| DatastoreError::SerializationError)?;
+
+        let action = CrudTable::new(
+            &table,
+            &self.conn,
+        );
+
+        let res = action.update_where(&filtered_update.filter, filtered_update.values)?;
+        let res = serde_json::to_value(res)
+            .map_err(|_| DatastoreError::SerializationError)?;
+
+        Ok(res)
+    }
+
+    fn delete_where(&self, data_store: &DataStoreEntity, filter: &serde_json::Value) -> Result<serde_json::Value, DatastoreError> {
+        let table: Result<Table, DatastoreError> = data_store.into();
+        let table = table?;
+
+        let filtered_delete: FilteredDelete = serde_json::from_value(filter.to_owned())
+            .map_err(|_| DatastoreError::SerializationError)?;
+
+        let action = CrudTable::new(
+            &table,
+            &self.conn,
+        );
+
+        let res = action.delete_where(&filtered_delete.filter)?;
+        let res = serde_json::to_value(res)
+            .map_err(|_| DatastoreError::SerializationError)?;
+
+        Ok(res)
+    }
+
+    fn delete_all(&self, data_store: &DataStoreEntity) -> Result<serde_json::Value, DatastoreError> {
+        let table: Result<Table, DatastoreError> = data_store.into();
+        let table = table?;
+
+        let action = CrudTable::new(
+            &table,
+            &self.conn,
+        );
+
+        let res = action.delete_all()?;
+        let res = serde_json::to_value(res)
+            .map_err(|_| DatastoreError::SerializationError)?;
+
+        Ok(res)
+    }
+
+    fn stream(&self, data_store: &DataStoreEntity, query: &serde_json::Value, batch_size: usize, on_batch: &mut FnMut(serde_json::Value) -> Result<(), DatastoreError>) -> Result<(), DatastoreError> {
+        let table: Result<Table, DatastoreError> = data_store.into();
+        let table = table?;
+
+        let table_query = parse_table_query(query)?;
+
+        let action = CrudTable::new(
+            &table,
+            &self.conn,
+        );
+
+        action.stream(&table_query, batch_size, &mut |rows| {
+            let rows = serde_json::to_value(rows)
+                .map_err(|_| DatastoreError::SerializationError)?;
+            on_batch(rows)
+        })
+    }
+
+    fn preview_datastore_update(&self, old: &DataStoreEntity, new: &DataStoreEntity) -> Result<serde_json::Value, DatastoreError> {
+        let new: Result<Table, DatastoreError> = new.into();
+        let new = new?;
+
+        let old: Result<Table, DatastoreError> = old.into();
+        let old = old?;
+
+        let steps = plan_table_update(&old, &new)?;
+        let res = serde_json::to_value(steps)
+            .map_err(|_| DatastoreError::SerializationError)?;
+
+        Ok(res)
+    }
+
+    fn copy_datastore_data(&self, source: &DataStoreEntity, target: &DataStoreEntity) -> Result<usize, DatastoreError> {
+        let source: Result<Table, DatastoreError> = source.into();
+        let source = source?;
+
+        let target: Result<Table, DatastoreError> = target.into();
+        let target = target?;
+
+        let action = UpdateTable::new(&self.conn);
+        action.copy_table_data(&source, &target)
+    }
+
+    fn purge_expired_data(&self, table: &DataStoreEntity, batch_size: usize) -> Result<usize, DatastoreError> {
+        let table: Result<Table, DatastoreError> = table.into();
+        let table = table?;
+
+        let action = UpdateTable::new(&self.conn);
+        action.purge_expired_rows(&table, batch_size)
+    }
+
     fn on_datastore_created(&self, new: &DataStoreEntity) -> Result<(), DatastoreError> {
         let new: Result<Table, DatastoreError> = new.into();
         let new = new?;
@@ -204,22 +363,93 @@ impl Datastore for KakapoPostgresConnection {
         let action = UpdateTable::new(&self.conn);
         action.delete_table(&old)
     }
+
+    fn on_view_created(&self, new: &View) -> Result<(), DatastoreError> {
+        let action = UpdateView::new(&self.conn);
+        action.create_view(new)
+    }
+
+    fn on_view_updated(&self, old: &View, new: &View) -> Result<(), DatastoreError> {
+        let action = UpdateView::new(&self.conn);
+        action.update_view(old, new)
+    }
+
+    fn on_view_deleted(&self, old: &View) -> Result<(), DatastoreError> {
+        let action = UpdateView::new(&self.conn);
+        action.delete_view(old)
+    }
+
+    fn refresh_view(&self, view: &View) -> Result<(), DatastoreError> {
+        let action = UpdateView::new(&self.conn);
+        action.refresh_view(view)
+    }
+
+    /// diesel's transaction manager uses a savepoint when a transaction is already open
+    fn begin_transaction(&self) -> Result<(), DatastoreError> {
+        let conn: &PgConnection = &self.conn;
+        conn.transaction_manager()
+            .begin_transaction(conn)
+            .map_err(|err| DatastoreError::DbError(err.to_string()))
+    }
+
+    fn commit_transaction(&self) -> Result<(), DatastoreError> {
+        let conn: &PgConnection = &self.conn;
+        conn.transaction_manager()
+            .commit_transaction(conn)
+            .map_err(|err| DatastoreError::DbError(err.to_string()))
+    }
+
+    fn rollback_transaction(&self) -> Result<(), DatastoreError> {
+        let conn: &PgConnection = &self.conn;
+        conn.transaction_manager()
+            .rollback_transaction(conn)
+            .map_err(|err| DatastoreError::DbError(err.to_string()))
+    }
 }
 
 impl DataQuery for KakapoPostgresConnection {
-    fn query(&self, query: &DataQueryEntity, query_params: &serde_json::Value, format: &serde_json::Value) -> Result<serde_json::Value, DatastoreError> {
+    fn query(&self, query: &DataQueryEntity, query_params: &serde_json::Value, format: &serde_json::Value, limit: &QueryLimit) -> Result<QueryResult, DatastoreError> {
         let query: Result<Query, DatastoreError> = query.into();
         let query = query?;
 
         let query_params: QueryParams = serde_json::from_value(query_params.to_owned())
             .map_err(|_| DatastoreError::SerializationError)?;
 
-        let action = QueryTable::new(&self.conn);
-        let res = action.run_query(&query, query_params)?; //TODO: format
+        let action = QueryTable::new(&self.conn, self.statement_timeout);
+        let (res, truncated) = action.run_query(&query, query_params, limit)?; //TODO: format
 
-        let res = serde_json::to_value(res)
+        let data = serde_json::to_value(res)
             .map_err(|_| DatastoreError::SerializationError)?;
 
-        Ok(res)
+        Ok(QueryResult { data, truncated })
+    }
+
+    fn explain(&self, query: &DataQueryEntity, query_params: &serde_json::Value) -> Result<serde_json::Value, DatastoreError> {
+        let query: Result<Query, DatastoreError> = query.into();
+        let query = query?;
+
+        let query_params: QueryParams = serde_json::from_value(query_params.to_owned())
+            .map_err(|_| DatastoreError::SerializationError)?;
+
+        let action = QueryTable::new(&self.conn, self.statement_timeout);
+        action.explain_query(&query, query_params)
+    }
+
+    fn result_schema(&self, data: &serde_json::Value) -> Result<serde_json::Value, DatastoreError> {
+        let data: RawTableData = serde_json::from_value(data.to_owned())
+            .map_err(|_| DatastoreError::DeserializationError)?;
+
+        serde_json::to_value(result_schema(&data))
+            .map_err(|_| DatastoreError::SerializationError)
+    }
+
+    fn backend_id(&self) -> Result<i64, DatastoreError> {
+        let action = QueryTable::new(&self.conn, self.statement_timeout);
+        action.backend_pid()
+    }
+
+    fn cancel(&self, backend_id: i64) -> Result<bool, DatastoreError> {
+        let action = QueryTable::new(&self.conn, self.statement_timeout);
+        action.cancel_backend(backend_id)
     }
 }
\ No newline at end of file
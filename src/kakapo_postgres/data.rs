@@ -4,7 +4,7 @@ use plugins::v1::DataStoreEntity;
 use plugins::v1::DatastoreError;
 use plugins::v1::DataQueryEntity;
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub enum DataType {
     SmallInteger, //TODO: + Serial
@@ -22,20 +22,32 @@ pub enum DataType {
 
     Byte,
 
-    Timestamp { //TODO: precision?
+    Timestamp {
     #[serde(default, rename = "withTZ")]
-    with_tz: bool
+    with_tz: bool,
+    /// fractional digits kept for the seconds, postgres defaults to 6
+    #[serde(default)]
+    precision: Option<u32>
     },
     Date,
-    Time { //TODO: precision?
+    Time {
     #[serde(default, rename = "withTZ")]
-    with_tz: bool
+    with_tz: bool,
+    #[serde(default)]
+    precision: Option<u32>
     },
     //TODO: TimeInterval,
 
     Boolean,
-    //TODO: enum + geometric + net address + bit string + uuid +  ...
+    //TODO: enum + geometric + net address + bit string +  ...
     Json, //TODO: binary?
+
+    /// `generate` fills in a random uuid when the column is left out,
+    /// `gen_random_uuid()` needs the `pgcrypto` extension before postgres 13
+    Uuid {
+        #[serde(default)]
+        generate: bool
+    },
     //TODO: arrays
 }
 
@@ -89,6 +101,26 @@ mod date_serde {
     }
 }
 
+mod uuid_serde {
+    use serde::{Deserializer, Deserialize, Serializer, Serialize};
+
+    #[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+    struct UuidSerde {
+        #[serde(rename = "$uuid")]
+        uuid: uuid::Uuid
+    }
+
+    pub fn serialize<S: Serializer>(data: &uuid::Uuid, serializer: S) -> Result<S::Ok, S::Error> {
+        let input = UuidSerde { uuid: *data };
+        input.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<uuid::Uuid, D::Error> {
+        let res = UuidSerde::deserialize(deserializer)?;
+        Ok(res.uuid)
+    }
+}
+
 mod binary_serde {
     use base64;
     use serde::{Deserializer, Deserialize, Serializer, Serialize};
@@ -130,9 +162,39 @@ pub enum Value {
     Date(chrono::NaiveDate),
     #[serde(with = "binary_serde")]
     Binary(Vec<u8>),
+    #[serde(with = "uuid_serde")]
+    Uuid(uuid::Uuid),
     Json(serde_json::Value),
 }
 
+/// The results don't carry the column types, so they are taken from the values
+/// integers mixed with floats are widened, any other mix is written as text
+pub fn infer_data_type<'a, I>(values: I) -> DataType
+    where I: Iterator<Item = &'a Value>,
+{
+    values.fold(None, |data_type, value| {
+        let value_type = match value {
+            Value::Null => return data_type,
+            Value::String(_) => DataType::String,
+            Value::Integer(_) => DataType::BigInteger,
+            Value::Float(_) => DataType::DoubleFloat,
+            Value::Boolean(_) => DataType::Boolean,
+            Value::DateTime(_) => DataType::Timestamp { with_tz: false, precision: None },
+            Value::Date(_) => DataType::Date,
+            Value::Binary(_) => DataType::Byte,
+            Value::Json(_) => DataType::Json,
+            Value::Uuid(_) => DataType::Uuid { generate: false },
+        };
+
+        match data_type {
+            None => Some(value_type),
+            Some(DataType::BigInteger) if value_type == DataType::DoubleFloat => Some(DataType::DoubleFloat),
+            Some(DataType::DoubleFloat) if value_type == DataType::BigInteger => Some(DataType::DoubleFloat),
+            Some(data_type) => if data_type == value_type { Some(data_type) } else { Some(DataType::String) },
+        }
+    }).unwrap_or(DataType::String)
+}
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct RawTableDataColumns {
@@ -155,6 +217,105 @@ pub struct RawTableData  {
     pub data: Vec<RawTableDataData>,
 }
 
+/// Options for retrieving table data, `start` and `end` select the rows in `[start, end)`
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TableQuery {
+    #[serde(default)]
+    pub start: Option<i64>,
+    #[serde(default)]
+    pub end: Option<i64>,
+    /// columns to return, all of them if left out
+    #[serde(default)]
+    pub columns: Option<Vec<String>>,
+    #[serde(default)]
+    pub filter: Option<Expression>,
+    #[serde(default)]
+    pub order_by: Vec<OrderBy>,
+    #[serde(default)]
+    pub group_by: Vec<String>,
+    #[serde(default)]
+    pub aggregates: Vec<Aggregate>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SortDirection {
+    Asc,
+    Desc,
+}
+
+impl Default for SortDirection {
+    fn default() -> Self {
+        SortDirection::Asc
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OrderBy {
+    pub column: String,
+    #[serde(default)]
+    pub direction: SortDirection,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum AggregateFunction {
+    Count,
+    Sum,
+    Min,
+    Max,
+    Avg,
+}
+
+/// An aggregate in the select, `column` can only be left out for `count`
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Aggregate {
+    pub function: AggregateFunction,
+    #[serde(default)]
+    pub column: Option<String>,
+    /// name of the result column, defaults to `{function}_{column}`
+    #[serde(default)]
+    pub alias: Option<String>,
+}
+
+/// A page of table data, returned instead of `RawTableData` when the query is paginated
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PagedTableData {
+    #[serde(flatten)]
+    pub table_data: RawTableData,
+    pub start: i64,
+    pub end: i64,
+    pub total_count: i64,
+}
+
+/// Sets the values on every row matching the filter, instead of on the rows with the given keys
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FilteredUpdate {
+    pub filter: Expression,
+    pub values: LinkedHashMap<String, Value>,
+}
+
+/// Deletes every row matching the filter, the filter can't be left out so that the whole table
+/// is never deleted by accident
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FilteredDelete {
+    pub filter: Expression,
+}
+
+/// Result of an upsert, the rows are split by whether they were created or updated
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpsertedTableData {
+    pub inserted: RawTableData,
+    pub updated: RawTableData,
+}
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct KeyValuePairObject {
@@ -299,16 +460,26 @@ pub struct Column {
     pub default: Option<Value>,
     #[serde(default)]
     pub nullable: bool,
+    /// set when updating a table to rename the column instead of dropping it and adding a new one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub renamed_from: Option<String>,
+    /// sql expression the column is computed from, e.g. `price * quantity`, it can't be written to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub generated: Option<String>,
 }
 
 impl Column {
     pub fn get_name(&self) -> String {
         self.name.to_owned()
     }
+
+    pub fn is_generated(&self) -> bool {
+        self.generated.is_some()
+    }
 }
 
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 #[serde(tag = "op")]
 pub enum Expression {
@@ -332,10 +503,26 @@ pub enum Expression {
         column: String,
         values: Vec<Value>,
     },
+    And {
+        expressions: Vec<Expression>,
+    },
+    Or {
+        expressions: Vec<Expression>,
+    },
 }
 
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
+/// What happens to the referencing rows when the referenced row is deleted or its key is updated
+/// postgres defaults to `NO ACTION`, which fails like `Restrict` but only at the end of the statement
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ReferentialAction {
+    Cascade,
+    SetNull,
+    Restrict,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub enum Constraint {
     Key(String),
@@ -351,6 +538,10 @@ pub enum Constraint {
         foreign_table: String,
         #[serde(rename = "foreignColumn")]
         foreign_column: String,
+        #[serde(default, rename = "onDelete")]
+        on_delete: Option<ReferentialAction>,
+        #[serde(default, rename = "onUpdate")]
+        on_update: Option<ReferentialAction>,
     },
     ReferenceTogether {
         columns: Vec<String>,
@@ -358,17 +549,32 @@ pub enum Constraint {
         foreign_table: String,
         #[serde(rename = "foreignColumns")]
         foreign_columns: Vec<String>,
+        #[serde(default, rename = "onDelete")]
+        on_delete: Option<ReferentialAction>,
+        #[serde(default, rename = "onUpdate")]
+        on_update: Option<ReferentialAction>,
     },
 
 }
 
 
+/// Rows older than `days`, based on a timestamp or date column, are deleted by the retention job
+/// e.g. `{"column": "createdAt", "days": 90}`
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RetentionPolicy {
+    pub column: String,
+    pub days: u32,
+}
+
 // This is the same as SchemaModification::Create
 #[derive(Clone, Debug, Deserialize, Serialize, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct SchemaState {
     pub columns: Vec<Column>,
     pub constraint: Vec<Constraint>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retention: Option<RetentionPolicy>,
 }
 
 impl SchemaState {
@@ -383,9 +589,12 @@ impl SchemaState {
 #[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Table {
-    pub name: String, //TODO: make sure this is an alphanumeric, otherwise SQL injection!
+    pub name: String, // checked with `quote_identifier` before it's put in a statement
     pub description: String,
     pub schema: SchemaState,
+    /// the postgres schema of the table, `None` for `public`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
 }
 
 impl Table {
@@ -401,6 +610,7 @@ impl From<&DataStoreEntity> for Result<Table, DatastoreError> {
             description: item.description.to_owned(),
             schema: serde_json::from_value(item.schema.to_owned())
                 .map_err(|_| DatastoreError::SerializationError)?, //TODO: shouldn't copy this here
+            scope: item.scope_name().map(|x| x.to_owned()),
         })
     }
 }
@@ -409,9 +619,20 @@ impl From<&DataStoreEntity> for Result<Table, DatastoreError> {
 #[serde(rename_all = "camelCase")]
 #[serde(untagged)]
 pub enum QueryParams {
-    //TODO: implement named parameters, unfortunately postgres doesn't have named parameters so...
-    //Named(BTreeMap<String, Value>),
     Unnamed(Vec<Value>),
+    /// postgres doesn't have named parameters, so these are put in the order of the declared params
+    Named(LinkedHashMap<String, Value>),
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueryParamDeclaration {
+    pub name: String,
+    pub data_type: DataType,
+    #[serde(default)]
+    pub default: Option<Value>,
+    #[serde(default)]
+    pub required: bool,
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -420,14 +641,25 @@ pub struct Query {
     pub name: String, //TODO: make sure this is an alphanumeric
     pub description: String,
     pub statement: String,
+    pub params: Vec<QueryParamDeclaration>,
+    /// in milliseconds
+    pub statement_timeout: Option<u64>,
 }
 
 impl From<&DataQueryEntity> for Result<Query, DatastoreError> {
     fn from(item: &DataQueryEntity) -> Result<Query, DatastoreError> {
+        let params = item.params
+            .iter()
+            .map(|param| serde_json::to_value(param).and_then(serde_json::from_value))
+            .collect::<Result<Vec<QueryParamDeclaration>, _>>()
+            .map_err(|_| DatastoreError::SerializationError)?;
+
         Ok(Query {
             name: item.name.to_owned(),
             description: item.description.to_owned(),
             statement: item.statement.to_owned(),
+            params,
+            statement_timeout: item.statement_timeout,
         })
     }
 }
@@ -438,6 +670,8 @@ mod test {
 
     use serde_json::from_value;
 
+    use kakapo_postgres::methods::quote_identifier;
+
     #[test]
     fn test_deserialize_value() {
         let val: Value = from_value(json!(null)).unwrap();
@@ -467,6 +701,13 @@ mod test {
         let val: Value = from_value(json!({"$binary" : "3q2+7w=="})).unwrap();
         assert_eq!(val, Value::Binary(data));
 
+        let uuid = uuid::Uuid::parse_str("6f1e8a2c-2c5d-4b1e-9c7a-3d2b1a0f9e8d").unwrap();
+        let val: Value = from_value(json!({"$uuid" : "6f1e8a2c-2c5d-4b1e-9c7a-3d2b1a0f9e8d"})).unwrap();
+        assert_eq!(val, Value::Uuid(uuid));
+
+        let val: Value = from_value(json!({"$uuid" : "not a uuid"})).unwrap();
+        assert_eq!(val, Value::Json(json!({"$uuid" : "not a uuid"})));
+
         let data = json!({"hello" : "world"});
         let val: Value = from_value(json!({"hello" : "world"})).unwrap();
         assert_eq!(val, Value::Json(data));
@@ -486,8 +727,163 @@ mod test {
         let val = serde_json::to_value(&data).unwrap();
         assert_eq!(val, json!({"$binary" : "3q2+7w=="}));
 
+        let data = Value::Uuid(uuid::Uuid::parse_str("6f1e8a2c-2c5d-4b1e-9c7a-3d2b1a0f9e8d").unwrap());
+        let val = serde_json::to_value(&data).unwrap();
+        assert_eq!(val, json!({"$uuid" : "6f1e8a2c-2c5d-4b1e-9c7a-3d2b1a0f9e8d"}));
+
+        let data_type: DataType = from_value(json!({"timestamp" : {"withTZ" : true}})).unwrap();
+        assert_eq!(data_type, DataType::Timestamp { with_tz: true, precision: None });
+
+        let data_type: DataType = from_value(json!({"time" : {"precision" : 3}})).unwrap();
+        assert_eq!(data_type, DataType::Time { with_tz: false, precision: Some(3) });
+
+        let data_type: DataType = from_value(json!({"uuid" : {"generate" : true}})).unwrap();
+        assert_eq!(data_type, DataType::Uuid { generate: true });
+
         let data = Value::Json(json!({"hello" : "world"}));
         let val = serde_json::to_value(&data).unwrap();
         assert_eq!(val, json!({"hello" : "world"}));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_table_query_range() {
+        let query: TableQuery = from_value(json!({})).unwrap();
+        assert!(!query.is_paginated());
+        assert_eq!(query.limit_and_offset(), Some((None, 0)));
+
+        let query: TableQuery = from_value(json!({"start": 20, "end": 30})).unwrap();
+        assert!(query.is_paginated());
+        assert_eq!(query.limit_and_offset(), Some((Some(10), 20)));
+
+        let query: TableQuery = from_value(json!({"end": 5})).unwrap();
+        assert_eq!(query.limit_and_offset(), Some((Some(5), 0)));
+
+        let query: TableQuery = from_value(json!({"start": 10, "end": 5})).unwrap();
+        assert_eq!(query.limit_and_offset(), None);
+
+        let query: TableQuery = from_value(json!({"start": -1})).unwrap();
+        assert_eq!(query.limit_and_offset(), None);
+    }
+
+    #[test]
+    fn test_filter_to_sql() {
+        let columns = vec!["id".to_string(), "name".to_string()];
+        let filter: Expression = from_value(json!({
+            "op": "or",
+            "expressions": [
+                { "op": "greaterThan", "column": "id", "value": 10 },
+                { "op": "in", "column": "name", "values": ["a", "b"] },
+                { "op": "equals", "column": "name", "value": null }
+            ]
+        })).unwrap();
+
+        let mut params = vec![];
+        let sql = filter.to_sql(&columns, &mut params).unwrap();
+        assert_eq!(sql, r#"("id" > $1) OR ("name" IN ($2, $3)) OR ("name" IS NULL)"#);
+        assert_eq!(params, vec![
+            Value::Integer(10),
+            Value::String("a".to_string()),
+            Value::String("b".to_string()),
+        ]);
+
+        let filter: Expression = from_value(json!({ "op": "equals", "column": "id; DROP TABLE x", "value": 1 })).unwrap();
+        assert!(filter.to_sql(&columns, &mut vec![]).is_err());
+    }
+
+    #[test]
+    fn test_order_by_to_sql() {
+        let columns = vec!["id".to_string(), "name".to_string()];
+        let query: TableQuery = from_value(json!({
+            "orderBy": [
+                { "column": "name", "direction": "desc" },
+                { "column": "id" }
+            ]
+        })).unwrap();
+
+        let sql = query.order_by_sql(&columns).unwrap();
+        assert_eq!(sql, r#" ORDER BY "name" DESC, "id" ASC"#);
+
+        let query: TableQuery = from_value(json!({ "orderBy": [{ "column": "age" }] })).unwrap();
+        assert!(query.order_by_sql(&columns).is_err());
+
+        let query: TableQuery = from_value(json!({})).unwrap();
+        assert_eq!(query.order_by_sql(&columns).unwrap(), "");
+    }
+
+    #[test]
+    fn test_aggregate_to_sql() {
+        let columns = vec!["category".to_string(), "price".to_string()];
+        let query: TableQuery = from_value(json!({
+            "groupBy": ["category"],
+            "aggregates": [
+                { "function": "count" },
+                { "function": "avg", "column": "price", "alias": "averagePrice" }
+            ],
+            "orderBy": [{ "column": "averagePrice", "direction": "desc" }]
+        })).unwrap();
+
+        assert!(query.is_aggregate());
+        let (select, group_by) = query.select_sql(&columns).unwrap();
+        assert_eq!(select, r#""category", COUNT(*) AS "count", AVG("price")::DOUBLE PRECISION AS "averagePrice""#);
+        assert_eq!(group_by, r#" GROUP BY "category""#);
+        assert_eq!(query.output_columns(&columns), vec!["category", "count", "averagePrice"]);
+
+        let query: TableQuery = from_value(json!({ "aggregates": [{ "function": "sum" }] })).unwrap();
+        assert!(query.select_sql(&columns).is_err());
+
+        let query: TableQuery = from_value(json!({ "aggregates": [{ "function": "max", "column": "price", "alias": "a\"b" }] })).unwrap();
+        assert!(query.select_sql(&columns).is_err());
+
+        let query: TableQuery = from_value(json!({})).unwrap();
+        assert_eq!(query.select_sql(&columns).unwrap(), ("*".to_string(), "".to_string()));
+    }
+
+    #[test]
+    fn test_columns_to_sql() {
+        let columns = vec!["id".to_string(), "name".to_string(), "bio".to_string()];
+        let query: TableQuery = from_value(json!({ "columns": ["name", "id"] })).unwrap();
+
+        assert_eq!(query.select_sql(&columns).unwrap(), (r#""name", "id""#.to_string(), "".to_string()));
+        assert_eq!(query.output_columns(&columns), vec!["name", "id"]);
+
+        let query: TableQuery = from_value(json!({ "columns": ["password"] })).unwrap();
+        assert!(query.select_sql(&columns).is_err());
+
+        let query: TableQuery = from_value(json!({ "columns": [] })).unwrap();
+        assert!(query.select_sql(&columns).is_err());
+
+        let query: TableQuery = from_value(json!({ "columns": ["id"], "groupBy": ["name"] })).unwrap();
+        assert!(query.select_sql(&columns).is_err());
+    }
+
+    #[test]
+    fn test_filtered_changes() {
+        let update: FilteredUpdate = from_value(json!({
+            "filter": { "op": "lessThan", "column": "id", "value": 10 },
+            "values": { "name": "archived" }
+        })).unwrap();
+        assert_eq!(update.values.get("name"), Some(&Value::String("archived".to_string())));
+
+        let delete: Result<FilteredDelete, _> = from_value(json!({}));
+        assert!(delete.is_err());
+
+        let delete: FilteredDelete = from_value(json!({ "filter": { "op": "and", "expressions": [] } })).unwrap();
+        assert!(delete.filter.to_sql(&["id".to_string()], &mut vec![]).is_err());
+    }
+
+    #[test]
+    fn test_quote_identifier() {
+        assert_eq!(quote_identifier("user_id").unwrap(), r#""user_id""#);
+        assert_eq!(quote_identifier("_Table2").unwrap(), r#""_Table2""#);
+
+        assert!(quote_identifier("").is_err());
+        assert!(quote_identifier("2fast").is_err());
+        assert!(quote_identifier("my table").is_err());
+        assert!(quote_identifier(r#"x"; DROP TABLE y; --"#).is_err());
+        assert!(quote_identifier(&"a".repeat(64)).is_err());
+
+        let columns = vec!["id".to_string()];
+        let filter: Expression = from_value(json!({ "op": "equals", "column": "id", "value": 1 })).unwrap();
+        assert_eq!(filter.to_sql(&columns, &mut vec![]).unwrap(), r#""id" = $1"#);
+    }
+}
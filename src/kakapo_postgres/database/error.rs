@@ -7,6 +7,8 @@ pub enum DbError {
     NotFound,
     #[fail(display = "{}", 0)]
     QueryError(String),
+    #[fail(display = "statement timeout")]
+    Timeout,
     #[fail(display = "query is empty")]
     EmptyQuery,
     #[fail(display = "An unknown error occurred")]
@@ -1,5 +1,6 @@
 pub mod error;
 pub mod sql;
+pub mod statement_cache;
 mod error_parser;
 
 use kakapo_postgres::database::error::DbError;
@@ -25,11 +25,23 @@ use diesel::serialize::IsNull;
 use kakapo_postgres::database::DatabaseFunctions;
 use kakapo_postgres::database::error_parser;
 use kakapo_postgres::database::error::DbError;
+use kakapo_postgres::database::statement_cache;
+use kakapo_postgres::database::statement_cache::StatementKey;
 
 use kakapo_postgres::data::DataType;
 use kakapo_postgres::data::Value;
 use kakapo_postgres::data::RawTableData;
 
+/// the times are returned as text, the way they are sent, see `query::check_param`
+const TIME_FORMAT: &str = "%H:%M:%S%.f";
+
+/// sql state of a statement canceled by the `statement_timeout`
+const QUERY_CANCELED: &str = "57014";
+/// sql state of a prepared statement that doesn't exist
+const INVALID_STATEMENT_NAME: &str = "26000";
+/// sql state of "cached plan must not change result type"
+const CACHED_PLAN_CHANGED: &str = "0A000";
+
 struct InternalRawConnection {
     pub internal_connection: NonNull<pq_sys::PGconn>,
 }
@@ -59,6 +71,11 @@ impl ConnWrapper {
     fn p(&self) -> *mut pq_sys::PGconn {
         self.0
     }
+
+    /// the key of the connection's prepared statements
+    fn id(&self) -> usize {
+        self.0 as usize
+    }
 }
 
 impl ResultWrapper {
@@ -98,8 +115,22 @@ impl ResultWrapper {
 
         let type_oid = unsafe { pq_sys::PQftype(self.p(), col_idx as i32) };
         let data_type = match type_oid {
+            0x10 => Ok(DataType::Boolean),
+            0x11 => Ok(DataType::Byte),
+            0x14 => Ok(DataType::BigInteger),
+            0x15 => Ok(DataType::SmallInteger),
             0x17 => Ok(DataType::Integer),
             0x19 => Ok(DataType::String),
+            0x72 => Ok(DataType::Json),
+            0x2BC => Ok(DataType::Float),
+            0x2BD => Ok(DataType::DoubleFloat),
+            0x413 => Ok(DataType::VarChar { length: 0 }),
+            0x43A => Ok(DataType::Date),
+            0x43B => Ok(DataType::Time { with_tz: false, precision: None }),
+            0x45A => Ok(DataType::Timestamp { with_tz: false, precision: None }),
+            0x4A0 => Ok(DataType::Timestamp { with_tz: true, precision: None }),
+            0x4F2 => Ok(DataType::Time { with_tz: true, precision: None }),
+            0xB86 => Ok(DataType::Uuid { generate: false }),
             _ => Err(generate_error(&format!("could not understand oid : `0x{:X?}`", type_oid))), //TODO:....
         }?;
 
@@ -123,12 +154,16 @@ impl ResultWrapper {
 
                 DataType::Byte => Value::Binary(parse(<Vec<u8> as FromSql<sql_types::Binary, Pg>>::from_sql(bytes))?),
 
-                DataType::Timestamp { with_tz } => Value::DateTime(parse(<chrono::NaiveDateTime as FromSql<sql_types::Timestamp, Pg>>::from_sql(bytes))?),
+                // the time stamps with a time zone are in utc
+                DataType::Timestamp { with_tz: false, .. } => Value::DateTime(parse(<chrono::NaiveDateTime as FromSql<sql_types::Timestamp, Pg>>::from_sql(bytes))?),
+                DataType::Timestamp { with_tz: true, .. } => Value::DateTime(parse(<chrono::NaiveDateTime as FromSql<sql_types::Timestamptz, Pg>>::from_sql(bytes))?),
                 DataType::Date => Value::Date(parse(<chrono::NaiveDate as FromSql<sql_types::Date, Pg>>::from_sql(bytes))?),
-                DataType::Time { with_tz } => Value::DateTime(parse(<chrono::NaiveDateTime as FromSql<sql_types::Timestamp, Pg>>::from_sql(bytes))?),
+                DataType::Time { with_tz, .. } => Value::String(parse(time_from_sql(bytes, with_tz))?),
 
                 DataType::Boolean => Value::Boolean(parse(<bool as FromSql<sql_types::Bool, Pg>>::from_sql(bytes))?),
                 DataType::Json => Value::Json(parse(<serde_json::Value as FromSql<sql_types::Json, Pg>>::from_sql(bytes))?),
+                // sent as the 16 raw bytes in the binary format
+                DataType::Uuid { .. } => Value::Uuid(parse(uuid_from_sql(bytes))?),
             }
         };
 
@@ -184,19 +219,33 @@ impl ResultWrapper {
         }
     }
 
-    fn raw_error(&self) -> DbError {
+    fn sql_state(&self) -> Option<&str> {
         let field = 'C' as i32;
         let ptr = unsafe { pq_sys::PQresultErrorField(self.p(), field as raw::c_int) };
         if ptr.is_null() {
-            return DbError::Unknown;
+            return None;
         }
 
         let c_str = unsafe { CStr::from_ptr(ptr) };
-        if let Ok(res) = c_str.to_str() {
-            let description = error_parser::parse_pg_error_code(res);
-            DbError::QueryError(description.to_string())
-        } else {
-            DbError::Unknown
+        c_str.to_str().ok()
+    }
+
+    fn raw_error(&self) -> DbError {
+        match self.sql_state() {
+            Some(QUERY_CANCELED) => DbError::Timeout,
+            Some(res) => {
+                let description = error_parser::parse_pg_error_code(res);
+                DbError::QueryError(description.to_string())
+            },
+            None => DbError::Unknown,
+        }
+    }
+
+    /// the prepared statement doesn't exist on the connection, or the columns it returns changed
+    fn is_stale_statement(&self) -> bool {
+        match self.sql_state() {
+            Some(INVALID_STATEMENT_NAME) | Some(CACHED_PLAN_CHANGED) => true,
+            _ => false,
         }
     }
 
@@ -224,6 +273,30 @@ fn generate_error(fmt: &str) -> Error {
     )
 }
 type FromError = std::boxed::Box<(dyn std::error::Error + std::marker::Sync + std::marker::Send + 'static)>;
+fn uuid_from_sql(bytes: Option<&[u8]>) -> Result<uuid::Uuid, FromError> {
+    let bytes = bytes.ok_or("unexpected null for a uuid")?;
+    Ok(uuid::Uuid::from_slice(bytes)?)
+}
+
+/// a time with a time zone is the microseconds followed by the offset, in seconds west of utc
+fn time_from_sql(bytes: Option<&[u8]>, with_tz: bool) -> Result<String, FromError> {
+    let bytes = bytes.ok_or("unexpected null for a time")?;
+    if !with_tz {
+        let time = <chrono::NaiveTime as FromSql<sql_types::Time, Pg>>::from_sql(Some(bytes))?;
+        return Ok(time.format(TIME_FORMAT).to_string());
+    }
+
+    if bytes.len() != 12 {
+        return Err(format!("a time with a time zone must be 12 bytes, got {}", bytes.len()).into());
+    }
+    let time = <chrono::NaiveTime as FromSql<sql_types::Time, Pg>>::from_sql(Some(&bytes[..8]))?;
+    let seconds_west = <i32 as FromSql<sql_types::Integer, Pg>>::from_sql(Some(&bytes[8..]))?;
+    let offset = chrono::FixedOffset::west_opt(seconds_west)
+        .ok_or_else(|| format!("invalid time zone offset {}", seconds_west))?;
+
+    Ok(format!("{}{}", time.format(TIME_FORMAT), offset))
+}
+
 fn parse<T>(data: Result<T, FromError>) -> Result<T, Error> {
     data.or_else(|err| Err(Error::SerializationError(err)))
 }
@@ -276,6 +349,10 @@ fn final_execute(conn: &Conn, query: &str, params: Vec<Value>) -> Result<ResultW
                 let value = x;
                 <Vec<u8> as ToSql<sql_types::Binary, Pg>>::to_sql(&value, &mut bytes)
             },
+            Value::Uuid(x) => {
+                bytes.extend_from_slice(x.as_bytes());
+                Ok(IsNull::No)
+            },
             Value::Json(x) => {
                 let value = x;
                 <serde_json::Value as ToSql<sql_types::Json, Pg>>::to_sql(&value, &mut bytes)
@@ -297,6 +374,7 @@ fn final_execute(conn: &Conn, query: &str, params: Vec<Value>) -> Result<ResultW
             Value::Null => 0x0, //TODO: is this right?
             Value::Integer(_) => 0x17,
             Value::String(_) => 0x19,
+            Value::Uuid(_) => 0xB86,
             _ => 0x0, //TODO: fix
         }
     }).collect();
@@ -314,22 +392,98 @@ fn final_execute(conn: &Conn, query: &str, params: Vec<Value>) -> Result<ResultW
         .map(|data| data.as_ref().map(|d| d.len() as raw::c_int).unwrap_or(0))
         .collect::<Vec<_>>();
 
-    let internal_ptr = conn_wrapper.p();
-    let result = unsafe {
-        //TODO: you can cache this with `PQprepare`
-        pq_sys::PQexecParams(
-            internal_ptr,
-            query_cstring.as_ptr(),
+    let param_formats = vec![1 as raw::c_int; params_pointer.len()];
+
+    if !statement_cache::is_cacheable(query) {
+        let internal_ptr = conn_wrapper.p();
+        let result = unsafe {
+            pq_sys::PQexecParams(
+                internal_ptr,
+                query_cstring.as_ptr(),
+                params_pointer.len() as raw::c_int,
+                param_types.as_ptr(),
+                params_pointer.as_ptr(),
+                param_lengths.as_ptr(),
+                param_formats.as_ptr(),
+                1 as raw::c_int
+            )
+        };
+        let result = ResultWrapper(result);
+
+        if statement_cache::is_schema_change(query) && result.get_error().is_none() {
+            deallocate(&conn_wrapper, statement_cache::clear(conn_wrapper.id()));
+        }
+
+        return Ok(result);
+    }
+
+    let key = StatementKey {
+        query: query.to_owned(),
+        param_types: param_types.to_owned(),
+    };
+    let exec_prepared = |name: &CStr| ResultWrapper(unsafe {
+        pq_sys::PQexecPrepared(
+            conn_wrapper.p(),
+            name.as_ptr(),
             params_pointer.len() as raw::c_int,
-            param_types.as_ptr(),
             params_pointer.as_ptr(),
             param_lengths.as_ptr(),
-            vec![1 as raw::c_int; params_pointer.len()].as_ptr(),
+            param_formats.as_ptr(),
             1 as raw::c_int
         )
-    };
+    });
+
+    if let Some(name) = statement_cache::get(conn_wrapper.id(), &key) {
+        let result = exec_prepared(&CString::new(name)?);
+        if !result.is_stale_statement() {
+            return Ok(result);
+        }
+
+        // the table changed on another connection, or the connection was replaced by a new one
+        debug!("prepared statement for {:?} is stale", query);
+        deallocate(&conn_wrapper, statement_cache::remove(conn_wrapper.id(), &key));
+    }
+
+    let name = statement_cache::next_name();
+    let name_cstring = CString::new(name.to_owned())?;
+    let prepared = ResultWrapper(unsafe {
+        pq_sys::PQprepare(
+            conn_wrapper.p(),
+            name_cstring.as_ptr(),
+            query_cstring.as_ptr(),
+            param_types.len() as raw::c_int,
+            param_types.as_ptr(),
+        )
+    });
+    if prepared.get_error().is_some() {
+        // the statement is invalid, the caller gets the error from the prepare
+        return Ok(prepared);
+    }
+    deallocate(&conn_wrapper, statement_cache::insert(conn_wrapper.id(), key, name));
+
+    Ok(exec_prepared(&name_cstring))
+}
+
+/// the names are the generated ones, so they can be put in the statement as they are
+fn deallocate(conn_wrapper: &ConnWrapper, names: Vec<String>) {
+    let failed = names
+        .into_iter()
+        .filter(|name| {
+            let statement = CString::new(format!(r#"DEALLOCATE "{}";"#, name))
+                .expect("statement names don't have null bytes");
+            let result = ResultWrapper(unsafe { pq_sys::PQexec(conn_wrapper.p(), statement.as_ptr()) });
+            match result.get_error() {
+                Some(err) => {
+                    // e.g. in an aborted transaction, tried again with the next ones of the connection
+                    debug!("could not deallocate {}: {:?}", name, &err);
+                    true
+                },
+                None => false,
+            }
+        })
+        .collect();
 
-    Ok(ResultWrapper(result))
+    statement_cache::deallocate_failed(conn_wrapper.id(), failed);
 }
 
 impl DatabaseFunctions for Conn {
@@ -412,4 +566,31 @@ mod test {
 
         })
     }
+
+    #[test]
+    fn test_time_columns() {
+        with_state(|state| {
+            let table_name = format!("temp_table{}", random_identifier());
+
+            let conn = state.get_database();
+
+            conn.exec(&format!("CREATE TABLE {} (col_a TIME(3), col_b TIME WITH TIME ZONE, col_c TIMESTAMP WITH TIME ZONE);", &table_name), vec![]).unwrap();
+            conn.exec(&format!("INSERT INTO {}(col_a, col_b, col_c) VALUES ('16:20:00.5', '16:20:00+02', '2019-04-20 16:20:00+02');", &table_name), vec![]).unwrap();
+
+            let result = conn.exec(&format!("SELECT * FROM {};", &table_name), vec![]).unwrap();
+            let data: Vec<Vec<Value>> = result.data.into_iter().map(|x| x.values).collect();
+            assert_eq!(data, [[
+                Value::String("16:20:00.500".to_string()),
+                Value::String("16:20:00+02:00".to_string()),
+                Value::DateTime(chrono::NaiveDate::from_ymd(2019, 04, 20).and_hms(14, 20, 00)),
+            ]]);
+
+            // the times read back are written as they are
+            let (time, time_tz) = (&data[0][0], &data[0][1]);
+            conn.exec(&format!("INSERT INTO {}(col_a, col_b) SELECT $1::TEXT::TIME, $2::TEXT::TIMETZ;", &table_name), vec![time.to_owned(), time_tz.to_owned()]).unwrap();
+            let result = conn.exec(&format!("SELECT col_a, col_b FROM {} WHERE col_c IS NULL;", &table_name), vec![]).unwrap();
+            let data: Vec<Vec<Value>> = result.data.into_iter().map(|x| x.values).collect();
+            assert_eq!(data, [[time.to_owned(), time_tz.to_owned()]]);
+        })
+    }
 }
\ No newline at end of file
@@ -0,0 +1,243 @@
+use std::collections::HashMap;
+use std::mem;
+use std::sync::Mutex;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+
+/// the statements of a connection are all deallocated once it has this many
+const MAX_STATEMENTS_PER_CONNECTION: usize = 256;
+
+static HITS: AtomicUsize = AtomicUsize::new(0);
+static MISSES: AtomicUsize = AtomicUsize::new(0);
+static INVALIDATIONS: AtomicUsize = AtomicUsize::new(0);
+/// the names are unique across the connections, a connection that replaced a closed one
+/// at the same address prepares its statements again instead of colliding with the old ones
+static NEXT_STATEMENT_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// postgres infers the types of the params when the statement is prepared,
+/// so the same query with other param types is another statement
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct StatementKey {
+    pub query: String,
+    pub param_types: Vec<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StatementCacheStats {
+    pub hits: usize,
+    pub misses: usize,
+    /// the statements dropped after a schema change or because the connection had too many
+    pub invalidations: usize,
+}
+
+#[derive(Debug, Default)]
+struct ConnectionStatements {
+    prepared: HashMap<StatementKey, String>,
+    /// dropped from `prepared`, but the `DEALLOCATE` failed
+    failed: Vec<String>,
+}
+
+impl ConnectionStatements {
+    fn take_failed(&mut self) -> Vec<String> {
+        mem::replace(&mut self.failed, vec![])
+    }
+}
+
+/// The prepared statements by connection, which is identified by its libpq pointer. The connections
+/// come from a pool shared by the executor threads, so the statements are shared by them too: a schema
+/// change clears all the statements of its connection, whichever thread prepared them
+static STATEMENTS: Mutex<Option<HashMap<usize, ConnectionStatements>>> = Mutex::new(None);
+
+fn first_keyword(query: &str) -> String {
+    query
+        .trim_start()
+        .chars()
+        .take_while(|x| x.is_ascii_alphabetic())
+        .collect::<String>()
+        .to_uppercase()
+}
+
+/// only the data statements are prepared, the other ones aren't run often enough to be worth it
+pub fn is_cacheable(query: &str) -> bool {
+    match first_keyword(query).as_str() {
+        "SELECT" | "INSERT" | "UPDATE" | "DELETE" | "WITH" => true,
+        _ => false,
+    }
+}
+
+/// the prepared statements could return other columns after these
+pub fn is_schema_change(query: &str) -> bool {
+    match first_keyword(query).as_str() {
+        "CREATE" | "ALTER" | "DROP" => true,
+        _ => false,
+    }
+}
+
+pub fn next_name() -> String {
+    format!("kakapo_stmt_{}", NEXT_STATEMENT_ID.fetch_add(1, Ordering::SeqCst))
+}
+
+fn with_statements<T, F>(conn: usize, f: F) -> T
+    where F: FnOnce(&mut ConnectionStatements) -> T
+{
+    // the maps are left whole by a panic, so they are still usable
+    let mut statements = STATEMENTS.lock().unwrap_or_else(|err| err.into_inner());
+    let connection_statements = statements
+        .get_or_insert_with(HashMap::new)
+        .entry(conn)
+        .or_insert_with(ConnectionStatements::default);
+    f(connection_statements)
+}
+
+pub fn get(conn: usize, key: &StatementKey) -> Option<String> {
+    let name = with_statements(conn, |statements| statements.prepared.get(key).cloned());
+
+    match name {
+        Some(_) => HITS.fetch_add(1, Ordering::Relaxed),
+        None => MISSES.fetch_add(1, Ordering::Relaxed),
+    };
+
+    name
+}
+
+/// returns the statements that were dropped to make room, they still have to be deallocated
+pub fn insert(conn: usize, key: StatementKey, name: String) -> Vec<String> {
+    let (evicted, failed) = with_statements(conn, |statements| {
+        let evicted: Vec<String> = if statements.prepared.len() >= MAX_STATEMENTS_PER_CONNECTION {
+            statements.prepared.drain().map(|(_, name)| name).collect()
+        } else {
+            vec![]
+        };
+        statements.prepared.insert(key, name);
+
+        (evicted, statements.take_failed())
+    });
+
+    INVALIDATIONS.fetch_add(evicted.len(), Ordering::Relaxed);
+    evicted.into_iter().chain(failed).collect()
+}
+
+/// returns the dropped statement, it still has to be deallocated
+pub fn remove(conn: usize, key: &StatementKey) -> Vec<String> {
+    let (name, failed) = with_statements(conn, |statements| (statements.prepared.remove(key), statements.take_failed()));
+
+    if name.is_some() {
+        INVALIDATIONS.fetch_add(1, Ordering::Relaxed);
+    }
+    name.into_iter().chain(failed).collect()
+}
+
+/// drops all the statements of the connection, returns them so that they can be deallocated
+pub fn clear(conn: usize) -> Vec<String> {
+    let (names, failed) = with_statements(conn, |statements| {
+        let names: Vec<String> = statements.prepared.drain().map(|(_, name)| name).collect();
+        (names, statements.take_failed())
+    });
+
+    INVALIDATIONS.fetch_add(names.len(), Ordering::Relaxed);
+    names.into_iter().chain(failed).collect()
+}
+
+/// the statements that are still on the server, they are returned again by the next `insert`, `remove` or `clear`
+pub fn deallocate_failed(conn: usize, names: Vec<String>) {
+    if !names.is_empty() {
+        with_statements(conn, |statements| statements.failed.extend(names));
+    }
+}
+
+pub fn stats() -> StatementCacheStats {
+    StatementCacheStats {
+        hits: HITS.load(Ordering::Relaxed),
+        misses: MISSES.load(Ordering::Relaxed),
+        invalidations: INVALIDATIONS.load(Ordering::Relaxed),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::thread;
+
+    #[test]
+    fn test_cacheable_statements() {
+        assert!(is_cacheable("SELECT * FROM \"things\";"));
+        assert!(is_cacheable("\n  insert into \"things\" (\"id\") VALUES ($1) RETURNING *;"));
+        assert!(is_cacheable("WITH x AS (SELECT 1) SELECT * FROM x;"));
+        assert!(!is_cacheable("CREATE TABLE \"things\" (\"id\" INTEGER);"));
+        assert!(!is_cacheable("REFRESH MATERIALIZED VIEW \"things\";"));
+
+        assert!(is_schema_change("ALTER TABLE \"things\" ADD COLUMN \"name\" TEXT;"));
+        assert!(is_schema_change("drop table \"things\";"));
+        assert!(!is_schema_change("DELETE FROM \"things\";"));
+    }
+
+    #[test]
+    fn test_cache_by_connection() {
+        let key = StatementKey {
+            query: "SELECT * FROM \"things\" WHERE \"id\" = $1;".to_string(),
+            param_types: vec![0x17],
+        };
+        let other_types = StatementKey {
+            param_types: vec![0x19],
+            ..key.clone()
+        };
+        let before = stats();
+
+        assert_eq!(get(1, &key), None);
+        let name = next_name();
+        assert_eq!(insert(1, key.clone(), name.to_owned()), Vec::<String>::new());
+        assert_eq!(get(1, &key), Some(name.to_owned()));
+        assert_eq!(get(1, &other_types), None);
+        assert_eq!(get(2, &key), None);
+
+        assert_eq!(clear(1), vec![name]);
+        assert_eq!(get(1, &key), None);
+
+        // the other threads share the statements of the connection
+        let name = next_name();
+        insert(1, key.clone(), name.to_owned());
+        let other_thread = key.clone();
+        assert_eq!(thread::spawn(move || get(1, &other_thread)).join().unwrap(), Some(name.to_owned()));
+        assert_eq!(remove(1, &key), vec![name]);
+
+        let after = stats();
+        assert!(after.hits > before.hits);
+        assert!(after.misses >= before.misses + 4);
+        assert!(after.invalidations > before.invalidations);
+    }
+
+    #[test]
+    fn test_deallocate_failed() {
+        let key = StatementKey {
+            query: "SELECT 1;".to_string(),
+            param_types: vec![],
+        };
+
+        let name = next_name();
+        insert(4, key.clone(), name.to_owned());
+        deallocate_failed(4, remove(4, &key));
+        assert_eq!(get(4, &key), None);
+
+        // handed back with the next statements to deallocate
+        assert_eq!(clear(4), vec![name]);
+        assert!(clear(4).is_empty());
+    }
+
+    #[test]
+    fn test_evict_when_full() {
+        let key = |idx: usize| StatementKey {
+            query: format!("SELECT {};", idx),
+            param_types: vec![],
+        };
+
+        for idx in 0..MAX_STATEMENTS_PER_CONNECTION {
+            assert!(insert(3, key(idx), next_name()).is_empty());
+        }
+        let evicted = insert(3, key(MAX_STATEMENTS_PER_CONNECTION), next_name());
+        assert_eq!(evicted.len(), MAX_STATEMENTS_PER_CONNECTION);
+        assert!(get(3, &key(0)).is_none());
+        assert!(get(3, &key(MAX_STATEMENTS_PER_CONNECTION)).is_some());
+    }
+}
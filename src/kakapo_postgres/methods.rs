@@ -13,13 +13,87 @@ use kakapo_postgres::data::ObjectKeys;
 use kakapo_postgres::data::TabularKeys;
 use kakapo_postgres::data::TabularValues;
 use kakapo_postgres::data::Table;
+use kakapo_postgres::data::Constraint;
 use kakapo_postgres::data::QueryParams;
+use kakapo_postgres::data::TableQuery;
+use kakapo_postgres::data::Expression;
+use kakapo_postgres::data::SortDirection;
+use kakapo_postgres::data::Aggregate;
+use kakapo_postgres::data::AggregateFunction;
 use kakapo_postgres::utils::TableDataFormat;
 
+use plugins::v1::DatastoreError;
+
+/// postgres truncates longer identifiers, so they could silently refer to another table or column
+const MAX_IDENTIFIER_LENGTH: usize = 63;
+
 #[derive(Debug, Fail)]
 pub enum DataError {
     #[fail(display = "mismatched columns")]
     MismatchedColumns,
+    #[fail(display = "column {} does not exist", 0)]
+    UnknownColumn(String),
+    #[fail(display = "expression has no operands")]
+    EmptyExpression,
+    #[fail(display = "{:?} needs a column", 0)]
+    MissingAggregateColumn(AggregateFunction),
+    #[fail(display = "invalid alias {}", 0)]
+    InvalidAlias(String),
+    #[fail(display = "no columns selected")]
+    NoColumnsSelected,
+    #[fail(display = "columns can't be selected in an aggregate query, use groupBy instead")]
+    ColumnsWithAggregate,
+    #[fail(display = "invalid identifier {:?}", 0)]
+    InvalidIdentifier(String),
+    #[fail(display = "column {} is generated and can't be written", 0)]
+    GeneratedColumn(String),
+    #[fail(display = "the params can only be named if the query declares them")]
+    UndeclaredParams,
+}
+
+impl From<DataError> for DatastoreError {
+    fn from(data_error: DataError) -> Self {
+        match data_error {
+            DataError::InvalidIdentifier(name) => DatastoreError::InvalidIdentifier(name),
+            err => DatastoreError::InvalidQuery(err.to_string()),
+        }
+    }
+}
+
+/// Table and column names are put in the statements as they are, since they can't be parameters
+/// Only letters, digits and underscores are allowed, and the name can't start with a digit
+pub fn quote_identifier(name: &str) -> Result<String, DataError> {
+    let starts_with_digit = name.chars().next().map(|x| x.is_ascii_digit()).unwrap_or(true);
+    let is_valid = !starts_with_digit
+        && name.len() <= MAX_IDENTIFIER_LENGTH
+        && name.chars().all(|x| x.is_ascii_alphanumeric() || x == '_');
+
+    if is_valid {
+        Ok(format!(r#""{}""#, name))
+    } else {
+        Err(DataError::InvalidIdentifier(name.to_owned()))
+    }
+}
+
+/// The name of the table qualified with its schema, the ones without a scope are left to the search path
+pub fn quote_table(table: &Table) -> Result<String, DataError> {
+    quote_qualified(table.scope.as_ref().map(|x| x.as_str()), &table.name)
+}
+
+pub fn quote_qualified(scope: Option<&str>, name: &str) -> Result<String, DataError> {
+    match scope {
+        Some(scope) => Ok(format!("{}.{}", quote_identifier(scope)?, quote_identifier(name)?)),
+        None => quote_identifier(name),
+    }
+}
+
+/// Same as `quote_identifier`, but the column also has to be one of `column_names`
+pub fn quote_column(name: &str, column_names: &[String]) -> Result<String, DataError> {
+    if column_names.iter().any(|x| x == name) {
+        quote_identifier(name)
+    } else {
+        Err(DataError::UnknownColumn(name.to_owned()))
+    }
 }
 
 
@@ -275,12 +349,83 @@ impl Table {
     pub fn get_column_names(&self) -> Vec<String> {
         self.schema.get_column_names()
     }
+
+    /// Values can only be written to the columns that are not generated
+    pub fn check_writable(&self, names: &[String]) -> Result<(), DataError> {
+        let generated = self.schema.columns
+            .iter()
+            .find(|column| column.is_generated() && names.contains(&column.name));
+
+        match generated {
+            Some(column) => Err(DataError::GeneratedColumn(column.name.to_owned())),
+            None => Ok(()),
+        }
+    }
+
+    pub fn get_key_column_names(&self) -> Vec<String> {
+        self.schema.constraint
+            .iter()
+            .filter_map(|constraint| match constraint {
+                Constraint::Key(column) => Some(column.to_owned()),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+impl Expression {
+    /// The same expression with the columns renamed, used to compare constraints across column renames
+    pub fn with_renamed_columns(&self, rename: &Fn(&str) -> String) -> Expression {
+        match self {
+            Expression::Equals { column, value } => Expression::Equals { column: rename(column), value: value.to_owned() },
+            Expression::NotEqual { column, value } => Expression::NotEqual { column: rename(column), value: value.to_owned() },
+            Expression::GreaterThan { column, value } => Expression::GreaterThan { column: rename(column), value: value.to_owned() },
+            Expression::LessThan { column, value } => Expression::LessThan { column: rename(column), value: value.to_owned() },
+            Expression::In { column, values } => Expression::In { column: rename(column), values: values.to_owned() },
+            Expression::And { expressions } => Expression::And {
+                expressions: expressions.iter().map(|x| x.with_renamed_columns(rename)).collect(),
+            },
+            Expression::Or { expressions } => Expression::Or {
+                expressions: expressions.iter().map(|x| x.with_renamed_columns(rename)).collect(),
+            },
+        }
+    }
+}
+
+impl Constraint {
+    /// Only the columns of this table are renamed, the foreign ones belong to another table
+    pub fn with_renamed_columns(&self, rename: &Fn(&str) -> String) -> Constraint {
+        let rename_all = |columns: &[String]| columns.iter().map(|x| rename(x)).collect();
+
+        match self {
+            Constraint::Key(column) => Constraint::Key(rename(column)),
+            Constraint::Unique(column) => Constraint::Unique(rename(column)),
+            Constraint::UniqueTogether(columns) => Constraint::UniqueTogether(rename_all(columns)),
+            Constraint::Check(expression) => Constraint::Check(expression.with_renamed_columns(rename)),
+            Constraint::Reference { column, foreign_table, foreign_column, on_delete, on_update } => Constraint::Reference {
+                column: rename(column),
+                foreign_table: foreign_table.to_owned(),
+                foreign_column: foreign_column.to_owned(),
+                on_delete: on_delete.to_owned(),
+                on_update: on_update.to_owned(),
+            },
+            Constraint::ReferenceTogether { columns, foreign_table, foreign_columns, on_delete, on_update } => Constraint::ReferenceTogether {
+                columns: rename_all(columns),
+                foreign_table: foreign_table.to_owned(),
+                foreign_columns: foreign_columns.to_owned(),
+                on_delete: on_delete.to_owned(),
+                on_update: on_update.to_owned(),
+            },
+        }
+    }
 }
 
 impl QueryParams {
-    pub fn value_list(&self) -> Vec<Value> {
+    /// The params as they are, only queries without declared params can be run with these
+    pub fn value_list(&self) -> Result<Vec<Value>, DataError> {
         match self {
-            QueryParams::Unnamed(x) => x.to_owned()
+            QueryParams::Unnamed(x) => Ok(x.to_owned()),
+            QueryParams::Named(_) => Err(DataError::UndeclaredParams),
         }
     }
 }
@@ -290,3 +435,208 @@ impl Default for QueryParams {
         QueryParams::Unnamed(vec![])
     }
 }
+
+impl TableQuery {
+    pub fn is_paginated(&self) -> bool {
+        self.start.is_some() || self.end.is_some()
+    }
+
+    /// limit and offset for the select, `None` if the range is invalid
+    /// a missing limit means all the remaining rows
+    pub fn limit_and_offset(&self) -> Option<(Option<i64>, i64)> {
+        let offset = self.start.unwrap_or(0);
+        if offset < 0 {
+            return None;
+        }
+
+        match self.end {
+            Some(end) if end < offset => None,
+            Some(end) => Some((Some(end - offset), offset)),
+            None => Some((None, offset)),
+        }
+    }
+
+    pub fn is_aggregate(&self) -> bool {
+        !self.group_by.is_empty() || !self.aggregates.is_empty()
+    }
+
+    /// names of the columns in the result
+    pub fn output_columns(&self, column_names: &[String]) -> Vec<String> {
+        if !self.is_aggregate() {
+            return match &self.columns {
+                Some(columns) => columns.to_owned(),
+                None => column_names.to_vec(),
+            };
+        }
+
+        self.group_by
+            .iter()
+            .cloned()
+            .chain(self.aggregates.iter().map(|aggregate| aggregate.output_name()))
+            .collect()
+    }
+
+    /// the select list and the `GROUP BY` part of the select
+    pub fn select_sql(&self, column_names: &[String]) -> Result<(String, String), DataError> {
+        let quote_columns = |names: &[String]| {
+            names
+                .iter()
+                .map(|name| quote_column(name, column_names))
+                .collect::<Result<Vec<String>, DataError>>()
+        };
+
+        if !self.is_aggregate() {
+            return match &self.columns {
+                Some(columns) if columns.is_empty() => Err(DataError::NoColumnsSelected),
+                Some(columns) => Ok((quote_columns(columns)?.join(", "), "".to_string())),
+                None => Ok(("*".to_string(), "".to_string())),
+            };
+        }
+
+        if self.columns.is_some() {
+            return Err(DataError::ColumnsWithAggregate);
+        }
+
+        let group_by = quote_columns(&self.group_by)?;
+
+        let aggregates = self.aggregates
+            .iter()
+            .map(|aggregate| aggregate.to_sql(column_names))
+            .collect::<Result<Vec<String>, DataError>>()?;
+
+        let select = group_by.iter().chain(aggregates.iter()).cloned().collect::<Vec<String>>().join(", ");
+        let group_by = if group_by.is_empty() {
+            "".to_string()
+        } else {
+            format!(" GROUP BY {}", group_by.join(", "))
+        };
+
+        Ok((select, group_by))
+    }
+
+    /// the `ORDER BY` part of the select, empty if no order is given
+    pub fn order_by_sql(&self, column_names: &[String]) -> Result<String, DataError> {
+        if self.order_by.is_empty() {
+            return Ok("".to_string());
+        }
+
+        let orders = self.order_by
+            .iter()
+            .map(|order| {
+                let column = quote_column(&order.column, column_names)?;
+                let direction = match order.direction {
+                    SortDirection::Asc => "ASC",
+                    SortDirection::Desc => "DESC",
+                };
+                Ok(format!("{} {}", column, direction))
+            })
+            .collect::<Result<Vec<String>, DataError>>()?;
+
+        Ok(format!(" ORDER BY {}", orders.join(", ")))
+    }
+}
+
+impl Aggregate {
+    pub fn output_name(&self) -> String {
+        let function = match self.function {
+            AggregateFunction::Count => "count",
+            AggregateFunction::Sum => "sum",
+            AggregateFunction::Min => "min",
+            AggregateFunction::Max => "max",
+            AggregateFunction::Avg => "avg",
+        };
+
+        match (&self.alias, &self.column) {
+            (Some(alias), _) => alias.to_owned(),
+            (None, Some(column)) => format!("{}_{}", function, column),
+            (None, None) => function.to_string(),
+        }
+    }
+
+    pub fn to_sql(&self, column_names: &[String]) -> Result<String, DataError> {
+        let column = match &self.column {
+            Some(name) => Some(quote_column(name, column_names)?),
+            None => None,
+        };
+
+        let alias = self.output_name();
+        let quoted_alias = quote_identifier(&alias)
+            .map_err(|_| DataError::InvalidAlias(alias))?;
+
+        // numeric results are cast since the driver can't read postgres' `numeric` type
+        let aggregate = match (self.function, column) {
+            (AggregateFunction::Count, None) => "COUNT(*)".to_string(),
+            (AggregateFunction::Count, Some(column)) => format!("COUNT({})", column),
+            (AggregateFunction::Sum, Some(column)) => format!("SUM({})::DOUBLE PRECISION", column),
+            (AggregateFunction::Min, Some(column)) => format!("MIN({})", column),
+            (AggregateFunction::Max, Some(column)) => format!("MAX({})", column),
+            (AggregateFunction::Avg, Some(column)) => format!("AVG({})::DOUBLE PRECISION", column),
+            (function, None) => return Err(DataError::MissingAggregateColumn(function)),
+        };
+
+        Ok(format!("{} AS {}", aggregate, quoted_alias))
+    }
+}
+
+impl Expression {
+    /// Compiles the expression into a parameterized sql condition, the values are pushed into `params`
+    /// Only columns in `column_names` are allowed, since they are put in the statement as is
+    pub fn to_sql(&self, column_names: &[String], params: &mut Vec<Value>) -> Result<String, DataError> {
+        self.to_sql_with(column_names, &mut |value| push_param(params, value))
+    }
+
+    /// Compiles the expression with the values written in by `get_literal`, for the statements that
+    /// can't be parameterized, e.g. the checks of a table
+    pub fn to_sql_with_literals(&self, column_names: &[String], get_literal: fn(&Value) -> String) -> Result<String, DataError> {
+        self.to_sql_with(column_names, &mut |value| get_literal(value))
+    }
+
+    /// `write_value` returns what goes in the statement in place of the value
+    fn to_sql_with(&self, column_names: &[String], write_value: &mut FnMut(&Value) -> String) -> Result<String, DataError> {
+        let column = |name: &str| quote_column(name, column_names);
+
+        let res = match self {
+            Expression::Equals { column: name, value } => match value {
+                Value::Null => format!("{} IS NULL", column(name)?),
+                value => format!("{} = {}", column(name)?, write_value(value)),
+            },
+            Expression::NotEqual { column: name, value } => match value {
+                Value::Null => format!("{} IS NOT NULL", column(name)?),
+                value => format!("{} <> {}", column(name)?, write_value(value)),
+            },
+            Expression::GreaterThan { column: name, value } => format!("{} > {}", column(name)?, write_value(value)),
+            Expression::LessThan { column: name, value } => format!("{} < {}", column(name)?, write_value(value)),
+            Expression::In { column: name, values } => {
+                if values.is_empty() {
+                    "FALSE".to_string()
+                } else {
+                    let name = column(name)?;
+                    let placeholders: Vec<String> = values.iter().map(|value| write_value(value)).collect();
+                    format!("{} IN ({})", name, placeholders.join(", "))
+                }
+            },
+            Expression::And { expressions } => join_expressions(expressions, " AND ", column_names, write_value)?,
+            Expression::Or { expressions } => join_expressions(expressions, " OR ", column_names, write_value)?,
+        };
+
+        Ok(res)
+    }
+}
+
+fn push_param(params: &mut Vec<Value>, value: &Value) -> String {
+    params.push(value.to_owned());
+    format!("${}", params.len())
+}
+
+fn join_expressions(expressions: &[Expression], separator: &str, column_names: &[String], write_value: &mut FnMut(&Value) -> String) -> Result<String, DataError> {
+    if expressions.is_empty() {
+        return Err(DataError::EmptyExpression);
+    }
+
+    let compiled = expressions
+        .iter()
+        .map(|expression| expression.to_sql_with(column_names, write_value).map(|x| format!("({})", x)))
+        .collect::<Result<Vec<String>, DataError>>()?;
+
+    Ok(compiled.join(separator))
+}
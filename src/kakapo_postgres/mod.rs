@@ -1,12 +1,18 @@
 
 pub mod connector;
 pub mod utils;
-mod methods;
-mod table;
-mod query;
-mod database;
-mod data;
-mod update_state;
+// shared with the sqlite plugin
+pub(crate) mod methods;
+pub(crate) mod table;
+pub(crate) mod query;
+pub(crate) mod database;
+pub(crate) mod data;
+pub(crate) mod update_state;
+#[cfg(feature = "parquet-export")]
+pub mod parquet_export;
+
+use connection::tls::SslMode;
+use connection::tls::TlsConfig;
 
 
 #[derive(Clone)]
@@ -16,6 +22,13 @@ pub struct KakapoPostgres {
     pub host: String,
     pub port: u16,
     pub db: String,
+    /// used instead of the user, pass, host, port and db if it's set
+    pub url: Option<String>,
+    /// in milliseconds, for the queries that don't set their own
+    pub statement_timeout: Option<u64>,
+    /// how long to wait for a connection (in seconds), the r2d2 default is 30
+    pub connection_timeout: Option<u64>,
+    pub tls_config: TlsConfig,
 }
 
 impl KakapoPostgres {
@@ -26,6 +39,10 @@ impl KakapoPostgres {
             host: "127.0.0.1".to_string(),
             port: 5432,
             db: "postgres".to_string(),
+            url: None,
+            statement_timeout: None,
+            connection_timeout: None,
+            tls_config: TlsConfig::default(),
         }
     }
 
@@ -53,4 +70,37 @@ impl KakapoPostgres {
         self.db = db.to_string();
         self
     }
+
+    /// the connection url of the database with the tables, it can be a different one than the metastore's
+    pub fn url(mut self, url: &str) -> Self {
+        self.url = Some(url.to_string());
+        self
+    }
+
+    pub fn statement_timeout(mut self, millis: u64) -> Self {
+        self.statement_timeout = Some(millis);
+        self
+    }
+
+    /// the requests that can't get a connection in time fail with `ServiceUnavailable`
+    pub fn connection_timeout(mut self, seconds: u64) -> Self {
+        self.connection_timeout = Some(seconds);
+        self
+    }
+
+    pub fn ssl_mode(mut self, ssl_mode: SslMode) -> Self {
+        self.tls_config.ssl_mode = Some(ssl_mode);
+        self
+    }
+
+    pub fn ssl_root_cert(mut self, root_cert: &str) -> Self {
+        self.tls_config.root_cert = Some(root_cert.to_string());
+        self
+    }
+
+    pub fn ssl_client_cert(mut self, client_cert: &str, client_key: &str) -> Self {
+        self.tls_config.client_cert = Some(client_cert.to_string());
+        self.tls_config.client_key = Some(client_key.to_string());
+        self
+    }
 }
\ No newline at end of file
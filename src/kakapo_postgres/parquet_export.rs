@@ -0,0 +1,236 @@
+
+use std::sync::Arc;
+
+use arrow::array::ArrayRef;
+use arrow::array::BinaryArray;
+use arrow::array::BooleanArray;
+use arrow::array::Date32Array;
+use arrow::array::Float32Array;
+use arrow::array::Float64Array;
+use arrow::array::Int16Array;
+use arrow::array::Int32Array;
+use arrow::array::Int64Array;
+use arrow::array::StringArray;
+use arrow::array::Time64MicrosecondArray;
+use arrow::array::TimestampMicrosecondArray;
+use arrow::datatypes::DataType as ArrowDataType;
+use arrow::datatypes::Field;
+use arrow::datatypes::Schema;
+use arrow::datatypes::TimeUnit;
+use arrow::record_batch::RecordBatch;
+use parquet::arrow::ArrowWriter;
+
+use kakapo_postgres::data::DataType;
+use kakapo_postgres::data::RawTableData;
+use kakapo_postgres::data::infer_data_type;
+use kakapo_postgres::data::Value;
+use plugins::v1::DatastoreError;
+
+/// timezone stored with the timestamps that were declared `withTZ`, postgres returns them in utc
+const TIMESTAMP_TZ: &str = "UTC";
+
+pub fn arrow_type(data_type: &DataType) -> ArrowDataType {
+    match data_type {
+        DataType::SmallInteger => ArrowDataType::Int16,
+        DataType::Integer => ArrowDataType::Int32,
+        DataType::BigInteger => ArrowDataType::Int64,
+        DataType::Float => ArrowDataType::Float32,
+        DataType::DoubleFloat => ArrowDataType::Float64,
+        DataType::String => ArrowDataType::Utf8,
+        DataType::VarChar { .. } => ArrowDataType::Utf8,
+        DataType::Byte => ArrowDataType::Binary,
+        DataType::Timestamp { with_tz: false, .. } => ArrowDataType::Timestamp(TimeUnit::Microsecond, None),
+        DataType::Timestamp { with_tz: true, .. } => ArrowDataType::Timestamp(TimeUnit::Microsecond, Some(TIMESTAMP_TZ.to_string())),
+        DataType::Date => ArrowDataType::Date32,
+        DataType::Time { .. } => ArrowDataType::Time64(TimeUnit::Microsecond),
+        DataType::Boolean => ArrowDataType::Boolean,
+        DataType::Json => ArrowDataType::Utf8, // parquet has no json type, it's written as text
+        DataType::Uuid { .. } => ArrowDataType::Utf8,
+    }
+}
+
+fn as_integer(value: &Value) -> Option<i64> {
+    match value {
+        Value::Integer(x) => Some(*x),
+        _ => None,
+    }
+}
+
+fn as_float(value: &Value) -> Option<f64> {
+    match value {
+        Value::Integer(x) => Some(*x as f64),
+        Value::Float(x) => Some(*x),
+        _ => None,
+    }
+}
+
+fn as_text(value: &Value) -> Option<String> {
+    match value {
+        Value::Null => None,
+        Value::String(x) => Some(x.to_owned()),
+        Value::Json(x) => Some(x.to_string()),
+        Value::Uuid(x) => Some(x.to_hyphenated().to_string()),
+        x => serde_json::to_string(x).ok(),
+    }
+}
+
+fn as_micros(value: &Value) -> Option<i64> {
+    match value {
+        Value::DateTime(x) => Some(x.timestamp() * 1_000_000 + i64::from(x.timestamp_subsec_micros())),
+        _ => None,
+    }
+}
+
+fn as_days(value: &Value) -> Option<i32> {
+    match value {
+        Value::Date(x) => Some((*x - chrono::NaiveDate::from_ymd(1970, 1, 1)).num_days() as i32),
+        _ => None,
+    }
+}
+
+/// times are returned as text, e.g. `16:20:00`
+fn as_time_micros(value: &Value) -> Option<i64> {
+    use chrono::Timelike;
+
+    match value {
+        Value::String(x) => chrono::NaiveTime::parse_from_str(x, "%H:%M:%S%.f")
+            .ok()
+            .map(|x| i64::from(x.num_seconds_from_midnight()) * 1_000_000 + i64::from(x.nanosecond() / 1000)),
+        _ => None,
+    }
+}
+
+fn to_array(data_type: &DataType, values: &[&Value]) -> ArrayRef {
+    match data_type {
+        DataType::SmallInteger => Arc::new(Int16Array::from(values.iter().map(|x| as_integer(x).map(|x| x as i16)).collect::<Vec<_>>())),
+        DataType::Integer => Arc::new(Int32Array::from(values.iter().map(|x| as_integer(x).map(|x| x as i32)).collect::<Vec<_>>())),
+        DataType::BigInteger => Arc::new(Int64Array::from(values.iter().map(|x| as_integer(x)).collect::<Vec<_>>())),
+        DataType::Float => Arc::new(Float32Array::from(values.iter().map(|x| as_float(x).map(|x| x as f32)).collect::<Vec<_>>())),
+        DataType::DoubleFloat => Arc::new(Float64Array::from(values.iter().map(|x| as_float(x)).collect::<Vec<_>>())),
+        DataType::Byte => Arc::new(BinaryArray::from(values.iter()
+            .map(|x| match x {
+                Value::Binary(x) => Some(x.as_slice()),
+                _ => None,
+            })
+            .collect::<Vec<_>>())),
+        DataType::Timestamp { with_tz, .. } => Arc::new(TimestampMicrosecondArray::from_opt_vec(
+            values.iter().map(|x| as_micros(x)).collect(),
+            if *with_tz { Some(TIMESTAMP_TZ.to_string()) } else { None },
+        )),
+        DataType::Date => Arc::new(Date32Array::from(values.iter().map(|x| as_days(x)).collect::<Vec<_>>())),
+        DataType::Time { .. } => Arc::new(Time64MicrosecondArray::from(values.iter().map(|x| as_time_micros(x)).collect::<Vec<_>>())),
+        DataType::Boolean => Arc::new(BooleanArray::from(values.iter()
+            .map(|x| match x {
+                Value::Boolean(x) => Some(*x),
+                _ => None,
+            })
+            .collect::<Vec<_>>())),
+        DataType::String | DataType::VarChar { .. } | DataType::Json | DataType::Uuid { .. } => {
+            Arc::new(StringArray::from(values.iter().map(|x| as_text(x)).collect::<Vec<_>>()))
+        },
+    }
+}
+
+/// Writes the table data as a single row group, the key columns come before the value columns
+pub fn write_parquet(data: RawTableData, data_types: Option<Vec<DataType>>) -> Result<Vec<u8>, DatastoreError> {
+    let mut column_names = data.columns.keys;
+    column_names.extend(data.columns.values);
+
+    let rows: Vec<Vec<Value>> = data.data
+        .into_iter()
+        .map(|row| {
+            let mut values: Vec<Value> = row.keys
+                .into_iter()
+                .map(|key| key.into_value())
+                .collect();
+            values.extend(row.values);
+            values
+        })
+        .collect();
+
+    let columns: Vec<Vec<&Value>> = (0..column_names.len())
+        .map(|i| rows.iter().map(|row| row.get(i).unwrap_or(&Value::Null)).collect())
+        .collect();
+
+    let data_types = match data_types {
+        Some(data_types) => data_types,
+        None => columns.iter().map(|values| infer_data_type(values.iter().cloned())).collect(),
+    };
+    if data_types.len() != column_names.len() {
+        error!("got {} data types for {} columns", data_types.len(), column_names.len());
+        return Err(DatastoreError::SerializationError);
+    }
+
+    let fields = column_names
+        .iter()
+        .zip(data_types.iter())
+        .map(|(name, data_type)| Field::new(name, arrow_type(data_type), true))
+        .collect();
+    let arrays = columns
+        .iter()
+        .zip(data_types.iter())
+        .map(|(values, data_type)| to_array(data_type, values))
+        .collect();
+
+    let schema = Arc::new(Schema::new(fields));
+    let batch = RecordBatch::try_new(schema.to_owned(), arrays)
+        .map_err(|err| {
+            error!("could not build the record batch: {:?}", &err);
+            DatastoreError::SerializationError
+        })?;
+
+    let mut buffer = vec![];
+    ArrowWriter::try_new(&mut buffer, schema, None)
+        .and_then(|mut writer| {
+            writer.write(&batch)?;
+            writer.close()
+        })
+        .map_err(|err| {
+            error!("could not write parquet: {:?}", &err);
+            DatastoreError::SerializationError
+        })?;
+
+    Ok(buffer)
+}
+
+/// Converts a serialized `RawTableData` (or anything flattening it, like a page) to parquet
+pub fn to_parquet(data: &serde_json::Value) -> Result<Vec<u8>, DatastoreError> {
+    let table_data: RawTableData = serde_json::from_value(data.to_owned())
+        .map_err(|_| DatastoreError::DeserializationError)?;
+
+    write_parquet(table_data, None)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_infer_data_type() {
+        let values = vec![Value::Null, Value::Integer(1), Value::Float(1.5)];
+        assert_eq!(infer_data_type(values.iter()), DataType::DoubleFloat);
+
+        let values = vec![Value::Integer(1), Value::Boolean(true)];
+        assert_eq!(infer_data_type(values.iter()), DataType::String);
+
+        let values = vec![Value::Null];
+        assert_eq!(infer_data_type(values.iter()), DataType::String);
+
+        assert_eq!(arrow_type(&DataType::Timestamp { with_tz: true, precision: None }), ArrowDataType::Timestamp(TimeUnit::Microsecond, Some("UTC".to_string())));
+    }
+
+    #[test]
+    fn test_to_parquet() {
+        let data = json!({
+            "columns": { "keys": [], "values": ["id", "name", "created"] },
+            "data": [
+                { "keys": [], "values": [1, "Bob", {"$date": "2019-04-20"}] },
+                { "keys": [], "values": [2, null, null] }
+            ]
+        });
+
+        let res = to_parquet(&data).unwrap();
+        assert_eq!(&res[..4], b"PAR1");
+        assert_eq!(&res[res.len() - 4..], b"PAR1");
+    }
+}
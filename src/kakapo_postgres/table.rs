@@ -1,17 +1,43 @@
 
+use std::collections::HashSet;
+use linked_hash_map::LinkedHashMap;
+
 use kakapo_postgres::data::Table;
 use kakapo_postgres::data::RawTableData;
 use kakapo_postgres::data::ObjectValues;
 use kakapo_postgres::data::ObjectKeys;
 use kakapo_postgres::data::Value;
+use kakapo_postgres::data::TableQuery;
+use kakapo_postgres::data::UpsertedTableData;
+use kakapo_postgres::data::Expression;
+use kakapo_postgres::methods::quote_column;
+use kakapo_postgres::methods::quote_identifier;
+use kakapo_postgres::methods::quote_table;
 use kakapo_postgres::database::error::DbError;
 use kakapo_postgres::database::DatabaseFunctions;
 
+use diesel::Connection;
 use diesel::r2d2::PooledConnection;
 use diesel::r2d2::ConnectionManager;
 use diesel::prelude::PgConnection;
 use plugins::v1::DatastoreError;
 
+/// extra column returned by the upsert, true if the row was inserted
+const UPSERT_INSERTED_COLUMN: &str = "__kakapo_inserted";
+/// postgres doesn't allow more parameters than this in one statement
+const MAX_QUERY_PARAMS: usize = 65535;
+/// cursor used for streaming the table, only lives for the duration of the transaction
+const STREAM_CURSOR: &str = "__kakapo_stream";
+
+impl From<diesel::result::Error> for DatastoreError {
+    // only happens if the transaction itself can't be started or committed, the errors of the
+    // statements inside of it are returned as they are
+    fn from(diesel_error: diesel::result::Error) -> Self {
+        warn!("diesel_error: {:?}", &diesel_error);
+        DatastoreError::DbError(diesel_error.to_string())
+    }
+}
+
 pub struct CrudTable<'a> {
     conn: &'a PooledConnection<ConnectionManager<PgConnection>>,
     table: &'a Table,
@@ -21,63 +47,193 @@ impl<'a> CrudTable<'a> {
     pub fn new(table: &'a Table, conn: &'a PooledConnection<ConnectionManager<PgConnection>>) -> Self {
         Self { table, conn }
     }
+
+    /// select with the filter and grouping applied, without the order and range
+    fn select_statement(&self, query: &TableQuery, params: &mut Vec<Value>) -> Result<String, DatastoreError> {
+        let (select, group_by) = query.select_sql(&self.table.get_column_names())?;
+
+        Ok(format!(
+            r#"SELECT {} FROM {}{}{}"#,
+            select,
+            self.quoted_name()?,
+            self.where_clause(query, params)?,
+            group_by,
+        ))
+    }
+
+    /// full select statement for the query, including the order and range
+    fn retrieve_statement(&self, query: &TableQuery, params: &mut Vec<Value>) -> Result<String, DatastoreError> {
+        let (limit, offset) = query.limit_and_offset()
+            .ok_or_else(|| DatastoreError::InvalidQuery(format!("invalid range {:?} to {:?}", query.start, query.end)))?;
+
+        let mut statement = self.select_statement(query, params)?;
+        let order_by = query.order_by_sql(&query.output_columns(&self.table.get_column_names()))?;
+        statement = format!("{}{}", statement, order_by);
+
+        if let Some(limit) = limit {
+            params.push(Value::Integer(limit));
+            statement = format!("{} LIMIT ${}", statement, params.len());
+        }
+        params.push(Value::Integer(offset));
+        statement = format!("{} OFFSET ${}", statement, params.len());
+
+        Ok(statement)
+    }
+
+    fn where_clause(&self, query: &TableQuery, params: &mut Vec<Value>) -> Result<String, DatastoreError> {
+        match &query.filter {
+            Some(filter) => {
+                let condition = filter.to_sql(&self.table.get_column_names(), params)?;
+                Ok(format!(" WHERE {}", condition))
+            },
+            None => Ok("".to_string()),
+        }
+    }
+
+    fn quoted_name(&self) -> Result<String, DatastoreError> {
+        Ok(quote_table(&self.table)?)
+    }
+
+    /// quotes the columns, they all have to be in the table
+    fn quoted_columns(&self, names: &[String]) -> Result<Vec<String>, DatastoreError> {
+        let table_column_names = self.table.get_column_names();
+        let quoted = names
+            .iter()
+            .map(|name| quote_column(name, &table_column_names))
+            .collect::<Result<Vec<String>, _>>()?;
+
+        Ok(quoted)
+    }
 }
 
 
 pub trait CrudTableOps {
-    fn retrieve(&self) -> Result<RawTableData, DatastoreError>;
+    fn retrieve(&self, query: &TableQuery) -> Result<RawTableData, DatastoreError>;
+
+    fn count(&self, query: &TableQuery) -> Result<i64, DatastoreError>;
+
+    fn stream(&self, query: &TableQuery, batch_size: usize, on_batch: &mut FnMut(RawTableData) -> Result<(), DatastoreError>) -> Result<(), DatastoreError>;
 
     fn insert(&self, data: ObjectValues, fail_on_duplicate: bool) -> Result<RawTableData, DatastoreError>;
 
-    fn upsert(&self, data: ObjectValues) -> Result<RawTableData, DatastoreError>;
+    fn upsert(&self, data: ObjectValues) -> Result<UpsertedTableData, DatastoreError>;
 
     fn update(&self, keys: ObjectKeys, data: ObjectValues, fail_on_not_found: bool) -> Result<RawTableData, DatastoreError>;
 
     fn delete(&self, keys: ObjectKeys, fail_on_not_found: bool) -> Result<RawTableData, DatastoreError>;
+
+    fn update_where(&self, filter: &Expression, data: LinkedHashMap<String, Value>) -> Result<RawTableData, DatastoreError>;
+
+    fn delete_where(&self, filter: &Expression) -> Result<RawTableData, DatastoreError>;
+
+    fn delete_all(&self) -> Result<RawTableData, DatastoreError>;
 }
 
 impl<'a> CrudTableOps for CrudTable<'a> {
-    fn retrieve(&self) -> Result<RawTableData, DatastoreError> {
+    fn retrieve(&self, query: &TableQuery) -> Result<RawTableData, DatastoreError> {
+
+        let mut params = vec![];
+        let statement = self.retrieve_statement(query, &mut params)?;
 
-        let query = format!("SELECT * FROM {}", &self.table.name);
         self.conn
-            .exec(&query, vec![])
+            .exec(&statement, params)
             .or_else(|err| Err(DatastoreError::DbError(err.to_string())))
     }
 
+    fn count(&self, query: &TableQuery) -> Result<i64, DatastoreError> {
+
+        let mut params = vec![];
+        let statement = format!(r#"SELECT COUNT(*) FROM ({}) AS "rows""#, self.select_statement(query, &mut params)?);
+        let res = self.conn
+            .exec(&statement, params)
+            .or_else(|err| Err(DatastoreError::DbError(err.to_string())))?;
+
+        match res.data.first().and_then(|row| row.values.first()) {
+            Some(Value::Integer(count)) => Ok(*count),
+            _ => {
+                error!("could not get the row count from {:?}", &res);
+                Err(DatastoreError::Unknown)
+            },
+        }
+    }
+
+    fn stream(&self, query: &TableQuery, batch_size: usize, on_batch: &mut FnMut(RawTableData) -> Result<(), DatastoreError>) -> Result<(), DatastoreError> {
+        if batch_size == 0 {
+            return Err(DatastoreError::InvalidQuery("batch size must be positive".to_string()));
+        }
+
+        let mut params = vec![];
+        let statement = format!(
+            r#"DECLARE "{cursor}" NO SCROLL CURSOR FOR {select}"#,
+            cursor=STREAM_CURSOR,
+            select=self.retrieve_statement(query, &mut params)?,
+        );
+        let fetch = format!(r#"FETCH FORWARD {} FROM "{}""#, batch_size, STREAM_CURSOR);
+
+        // cursors only exist inside of a transaction
+        self.conn.transaction::<(), DatastoreError, _>(|| {
+            self.conn
+                .exec(&statement, params)
+                .or_else(|err| Err(DatastoreError::DbError(err.to_string())))?;
+
+            loop {
+                let rows = self.conn
+                    .exec(&fetch, vec![])
+                    .or_else(|err| Err(DatastoreError::DbError(err.to_string())))?;
+
+                let fetched = rows.data.len();
+                if fetched > 0 {
+                    on_batch(rows)?;
+                }
+                if fetched < batch_size {
+                    break;
+                }
+            }
+
+            self.conn
+                .exec(&format!(r#"CLOSE "{}""#, STREAM_CURSOR), vec![])
+                .or_else(|err| Err(DatastoreError::DbError(err.to_string())))?;
+
+            Ok(())
+        })
+    }
+
     fn insert(&self, data: ObjectValues, fail_on_duplicate: bool) -> Result<RawTableData, DatastoreError> {
 
         let table_column_names = self.table.get_column_names();
         let raw_data = data.as_list();
         let mut results = RawTableData::new(vec![], table_column_names.to_owned());
 
-        for row in raw_data {
-            let sql_column_names: Vec<String> = row.keys().map(|x| x.to_owned()).collect();
-            let column_counts: Vec<String> = sql_column_names.iter().enumerate()
-                .map(|(i, _)| format!("${}", i+1))
+        for batch in batch_rows(raw_data) {
+            let sql_column_names: Vec<String> = batch[0].keys().map(|x| x.to_owned()).collect();
+            self.table.check_writable(&sql_column_names)?;
+            let sql_column_names = self.quoted_columns(&sql_column_names)?;
+            let mut values: Vec<Value> = vec![];
+            let rows_params: Vec<String> = batch.into_iter()
+                .map(|row| {
+                    let column_counts: Vec<String> = row.into_iter()
+                        .map(|(_, value)| {
+                            values.push(value);
+                            format!("${}", values.len())
+                        })
+                        .collect();
+                    format!("({})", column_counts.join(", "))
+                })
                 .collect();
-            let values = row.values().map(|x| x.to_owned()).collect();
+
             let query = format!(
-                r#"INSERT INTO "{name}" ("{columns}") VALUES ({params}) RETURNING *;"#,
-                name=&self.table.name,
-                columns=sql_column_names.join(r#"", ""#),
-                params=column_counts.join(r#", "#),
+                r#"INSERT INTO {name} ({columns}) VALUES {rows}{on_conflict} RETURNING *;"#,
+                name=self.quoted_name()?,
+                columns=sql_column_names.join(", "),
+                rows=rows_params.join(", "),
+                on_conflict=if fail_on_duplicate { "" } else { " ON CONFLICT DO NOTHING" },
             );
 
-            let new_row = self.conn
+            let new_rows = self.conn
                 .exec(&query, values)
-                .or_else(|err| {
-                    match err {
-                        DbError::ConstraintError(_) => if !fail_on_duplicate {
-                            Ok(RawTableData::new(vec![], table_column_names.to_owned()))
-                        } else {
-                            Err(DatastoreError::DbError(err.to_string()))
-                        },
-                        _ => Err(DatastoreError::DbError(err.to_string())),
-                    }
-                })?;
+                .or_else(|err| Err(DatastoreError::DbError(err.to_string())))?;
 
-            results.append(new_row)
+            results.append(new_rows)
                 .or_else(|_| {
                     error!("columns names are mismatched");
                     Err(DatastoreError::Unknown)
@@ -87,39 +243,60 @@ impl<'a> CrudTableOps for CrudTable<'a> {
         Ok(results)
     }
 
-    fn upsert(&self, data: ObjectValues) -> Result<RawTableData, DatastoreError> {
-        //Note: doing this because I want to know whether it was an insert or update so that I can put in the correct data in the transactions table
-        // otherise, maybe ON CONFLICT with triggers would have been the proper choice
+    fn upsert(&self, data: ObjectValues) -> Result<UpsertedTableData, DatastoreError> {
+        // `xmax` is only set on a row version that replaced another one, so it is 0 for the inserted rows
+        // this lets us know whether it was an insert or update so that we can put in the correct data in the transactions table
         let table_column_names = self.table.get_column_names();
+        let key_column_names = self.table.get_key_column_names();
+        if key_column_names.is_empty() {
+            return Err(DatastoreError::InvalidQuery(format!("table {} has no key to upsert on", &self.table.name)));
+        }
+        let key_column_names = self.quoted_columns(&key_column_names)?;
+
         let raw_data = data.as_list();
-        let mut results = RawTableData::new(vec![], table_column_names.to_owned());
+        let mut results = UpsertedTableData {
+            inserted: RawTableData::new(vec![], table_column_names.to_owned()),
+            updated: RawTableData::new(vec![], table_column_names.to_owned()),
+        };
 
         for row in raw_data {
             let sql_column_names: Vec<String> = row.keys().map(|x| x.to_owned()).collect();
+            self.table.check_writable(&sql_column_names)?;
+            let sql_column_names = self.quoted_columns(&sql_column_names)?;
             let column_counts: Vec<String> = sql_column_names.iter().enumerate()
                 .map(|(i, _)| format!("${}", i+1))
                 .collect();
             let values = row.values().map(|x| x.to_owned()).collect();
             let query = format!(
-                r#"INSERT INTO "{name}" ("{columns}") VALUES ({params}) RETURNING *;"#,
-                name=&self.table.name,
-                columns=sql_column_names.join(r#"", ""#),
-                params=column_counts.join(r#", "#),
+                r#"INSERT INTO {name} ({columns}) VALUES ({params}) ON CONFLICT ({keys}) DO UPDATE SET {sets} RETURNING *, (xmax = 0) AS "{inserted}";"#,
+                name=self.quoted_name()?,
+                columns=sql_column_names.join(", "),
+                params=column_counts.join(", "),
+                keys=key_column_names.join(", "),
+                sets=sql_column_names.iter()
+                    .map(|x| format!("{col} = EXCLUDED.{col}", col=x))
+                    .collect::<Vec<String>>()
+                    .join(", "),
+                inserted=UPSERT_INSERTED_COLUMN,
             );
 
-            let new_row = self.conn
+            let mut new_row = self.conn
                 .exec(&query, values)
-                .or_else(|err| {
-                    match err {
-                        DbError::ConstraintError(_) => {
-                            //TODO: update value
-                            Err(DatastoreError::DbError(err.to_string()))
-                        },
-                        _ => Err(DatastoreError::DbError(err.to_string())),
-                    }
-                })?;
-
-            results.append(new_row)
+                .or_else(|err| Err(DatastoreError::DbError(err.to_string())))?;
+
+            // the inserted flag is the last column, it's removed from the result
+            new_row.columns.values.pop();
+            let mut inserted_rows = RawTableData::new(vec![], new_row.columns.values.to_owned());
+            let mut updated_rows = RawTableData::new(vec![], new_row.columns.values.to_owned());
+            for mut row_data in new_row.data {
+                match row_data.values.pop() {
+                    Some(Value::Boolean(true)) => inserted_rows.data.push(row_data),
+                    _ => updated_rows.data.push(row_data),
+                }
+            }
+
+            results.inserted.append(inserted_rows)
+                .and_then(|_| results.updated.append(updated_rows))
                 .or_else(|_| {
                     error!("columns names are mismatched");
                     Err(DatastoreError::Unknown)
@@ -138,7 +315,10 @@ impl<'a> CrudTableOps for CrudTable<'a> {
 
         for (key, row) in raw_keys.iter().zip(raw_data) {
             let column_names: Vec<String> = row.keys().map(|x| x.to_owned()).collect();
+            self.table.check_writable(&column_names)?;
+            let column_names = self.quoted_columns(&column_names)?;
             let key_names: Vec<String> = key.keys().map(|x| x.to_owned()).collect();
+            let key_names = self.quoted_columns(&key_names)?;
 
             let mut values: Vec<Value> = row.values().map(|x| x.to_owned()).collect();
             let key_values: Vec<Value> = key.values().map(|x| x.to_owned().into_value()).collect();
@@ -149,7 +329,7 @@ impl<'a> CrudTableOps for CrudTable<'a> {
 
             let query = format!(
                 "UPDATE {name} SET {sets} WHERE {id} RETURNING *", //"UPDATE table SET value1 = 1, value2 = 2 WHERE id = my_id"
-                name=&self.table.name,
+                name=self.quoted_name()?,
                 sets=column_names.iter().enumerate()
                     .map(|(i, x)| format!("{} = ${}", x, i+val_index))
                     .collect::<Vec<String>>()
@@ -187,36 +367,27 @@ impl<'a> CrudTableOps for CrudTable<'a> {
     fn delete(&self, keys: ObjectKeys, fail_on_not_found: bool) -> Result<RawTableData, DatastoreError> {
 
         let table_column_names = self.table.get_column_names();
-        let raw_keys = keys.as_list();
+        let raw_keys: Vec<LinkedHashMap<String, Value>> = keys
+            .as_list()
+            .into_iter()
+            .map(|key| key.into_iter().map(|(name, value)| (name, value.into_value())).collect())
+            .collect();
         let mut results = RawTableData::new(vec![], table_column_names.to_owned());
 
-        for key in raw_keys {
-            let key_names: Vec<String> = key.keys().map(|x| x.to_owned()).collect();
-            let values: Vec<Value> = key.values().map(|x| x.to_owned().into_value()).collect();
+        for batch in batch_rows(dedup_keys(raw_keys)) {
+            let num_keys = batch.len();
+            let (query, values) = delete_statement(self.table, batch)?;
 
-            let query = format!(
-                "DELETE FROM {name} WHERE {id} RETURNING *", //"DELETE table WHERE id = my_id"
-                name=&self.table.name,
-                id=key_names.iter().enumerate()
-                    .map(|(i, x)| format!("{} = ${}", x, i+1))
-                    .collect::<Vec<String>>()
-                    .join(" AND "),
-            );
-
-            let new_row = self.conn
+            let deleted_rows = self.conn
                 .exec(&query, values)
-                .or_else(|err| {
-                    match err {
-                        DbError::NotFound => if !fail_on_not_found {
-                            Ok(RawTableData::new(vec![], table_column_names.to_owned()))
-                        } else {
-                            Err(DatastoreError::DbError(err.to_string()))
-                        },
-                        _ => Err(DatastoreError::DbError(err.to_string())),
-                    }
-                })?;
+                .or_else(|err| Err(DatastoreError::DbError(err.to_string())))?;
 
-            results.append(new_row)
+            let (deleted_rows, num_missing) = split_matched_keys(deleted_rows, num_keys)?;
+            if fail_on_not_found && num_missing > 0 {
+                return Err(DatastoreError::RowsNotFound(num_missing));
+            }
+
+            results.append(deleted_rows)
                 .or_else(|_| {
                     error!("columns names are mismatched");
                     Err(DatastoreError::Unknown)
@@ -225,4 +396,316 @@ impl<'a> CrudTableOps for CrudTable<'a> {
 
         Ok(results)
     }
+
+    fn update_where(&self, filter: &Expression, data: LinkedHashMap<String, Value>) -> Result<RawTableData, DatastoreError> {
+        if data.is_empty() {
+            return Err(DatastoreError::InvalidQuery("no values to update".to_string()));
+        }
+
+        let updated_column_names: Vec<String> = data.keys().map(|x| x.to_owned()).collect();
+        self.table.check_writable(&updated_column_names)?;
+
+        let table_column_names = self.table.get_column_names();
+        let mut params: Vec<Value> = vec![];
+        let sets = data
+            .into_iter()
+            .map(|(column, value)| {
+                let column = quote_column(&column, &table_column_names)?;
+                params.push(value);
+                Ok(format!("{} = ${}", column, params.len()))
+            })
+            .collect::<Result<Vec<String>, DatastoreError>>()?;
+        let condition = filter.to_sql(&table_column_names, &mut params)?;
+
+        let query = format!(
+            "UPDATE {name} SET {sets} WHERE {condition} RETURNING *;",
+            name=self.quoted_name()?,
+            sets=sets.join(", "),
+            condition=condition,
+        );
+
+        self.conn
+            .exec(&query, params)
+            .or_else(|err| Err(DatastoreError::DbError(err.to_string())))
+    }
+
+    fn delete_where(&self, filter: &Expression) -> Result<RawTableData, DatastoreError> {
+        let mut params: Vec<Value> = vec![];
+        let condition = filter.to_sql(&self.table.get_column_names(), &mut params)?;
+
+        let query = format!(
+            "DELETE FROM {name} WHERE {condition} RETURNING *;",
+            name=self.quoted_name()?,
+            condition=condition,
+        );
+
+        self.conn
+            .exec(&query, params)
+            .or_else(|err| Err(DatastoreError::DbError(err.to_string())))
+    }
+
+    /// not a `TRUNCATE`, the deleted rows are returned for the row history
+    fn delete_all(&self) -> Result<RawTableData, DatastoreError> {
+        let query = format!("DELETE FROM {name} RETURNING *;", name=self.quoted_name()?);
+
+        self.conn
+            .exec(&query, vec![])
+            .or_else(|err| Err(DatastoreError::DbError(err.to_string())))
+    }
+}
+
+/// Added to the rows returned by `delete_statement`, the index of the key that matched the row
+pub const MATCHED_KEY_COLUMN: &str = "kakapo_matched_key";
+
+/// Deletes all the rows matching any of the keys in a single statement, the deleted rows are returned
+/// e.g. `DELETE FROM "table" WHERE ("a" = $1) OR ("a" = $2) RETURNING *, CASE WHEN ("a" = $1) THEN 0 WHEN ("a" = $2) THEN 1 END AS "kakapo_matched_key";`
+/// the keys have to be in a batch from `batch_rows`, so that they all name the same columns,
+/// and the returned rows have to go through `split_matched_keys`
+pub fn delete_statement(table: &Table, keys: Vec<LinkedHashMap<String, Value>>) -> Result<(String, Vec<Value>), DatastoreError> {
+    let table_column_names = table.get_column_names();
+    let mut values: Vec<Value> = vec![];
+
+    let conditions = keys
+        .into_iter()
+        .map(|key| {
+            if key.is_empty() {
+                return Err(DatastoreError::InvalidQuery("the key to delete is empty".to_string()));
+            }
+
+            let columns = key
+                .into_iter()
+                .map(|(name, value)| {
+                    let column = quote_column(&name, &table_column_names)?;
+                    values.push(value);
+                    Ok(format!("{} = ${}", column, values.len()))
+                })
+                .collect::<Result<Vec<String>, DatastoreError>>()?;
+            Ok(format!("({})", columns.join(" AND ")))
+        })
+        .collect::<Result<Vec<String>, DatastoreError>>()?;
+
+    if conditions.is_empty() {
+        return Err(DatastoreError::InvalidQuery("no keys to delete".to_string()));
+    }
+
+    let matched_keys: Vec<String> = conditions
+        .iter()
+        .enumerate()
+        .map(|(index, condition)| format!("WHEN {} THEN {}", condition, index))
+        .collect();
+
+    let query = format!(
+        "DELETE FROM {name} WHERE {conditions} RETURNING *, CASE {matched_keys} END AS {matched_key_column};",
+        name=quote_table(table)?,
+        conditions=conditions.join(" OR "),
+        matched_keys=matched_keys.join(" "),
+        matched_key_column=quote_identifier(MATCHED_KEY_COLUMN)?,
+    );
+
+    Ok((query, values))
+}
+
+/// Takes the `MATCHED_KEY_COLUMN` out of the rows deleted by `delete_statement`, and counts the
+/// keys that didn't match any row. A key matching several rows doesn't make up for another one
+pub fn split_matched_keys(mut deleted_rows: RawTableData, num_keys: usize) -> Result<(RawTableData, usize), DatastoreError> {
+    if deleted_rows.columns.values.pop().as_ref().map(String::as_str) != Some(MATCHED_KEY_COLUMN) {
+        error!("the deleted rows are missing the matched key column");
+        return Err(DatastoreError::Unknown);
+    }
+
+    let mut matched = vec![false; num_keys];
+    for row in deleted_rows.data.iter_mut() {
+        match row.values.pop() {
+            Some(Value::Integer(index)) if index >= 0 && (index as usize) < num_keys => matched[index as usize] = true,
+            value => {
+                error!("the deleted row matched an unknown key: {:?}", value);
+                return Err(DatastoreError::Unknown);
+            },
+        }
+    }
+
+    let num_missing = matched.into_iter().filter(|is_matched| !is_matched).count();
+    Ok((deleted_rows, num_missing))
+}
+
+/// Drops the keys that were already given, in any column order. Otherwise the repeated key
+/// wouldn't match anything, its rows are deleted by the first one
+pub fn dedup_keys(keys: Vec<LinkedHashMap<String, Value>>) -> Vec<LinkedHashMap<String, Value>> {
+    let mut seen = HashSet::new();
+
+    keys
+        .into_iter()
+        .filter(|key| {
+            let mut columns: Vec<(&String, &Value)> = key.iter().collect();
+            columns.sort_by(|(a, _), (b, _)| a.cmp(b));
+            // `Value` isn't hashable, the floats can't be
+            let signature = serde_json::to_string(&columns).unwrap_or_default();
+            seen.insert(signature)
+        })
+        .collect()
+}
+
+/// Splits the rows into batches of consecutive rows with the same columns, so that each batch
+/// can be inserted with a single statement without going over the parameter limit
+fn batch_rows(rows: Vec<LinkedHashMap<String, Value>>) -> Vec<Vec<LinkedHashMap<String, Value>>> {
+    batch_rows_with_limit(rows, MAX_QUERY_PARAMS)
+}
+
+/// Same as `batch_rows` for a database with a different parameter limit
+pub fn batch_rows_with_limit(rows: Vec<LinkedHashMap<String, Value>>, max_params: usize) -> Vec<Vec<LinkedHashMap<String, Value>>> {
+    let mut batches: Vec<Vec<LinkedHashMap<String, Value>>> = vec![];
+
+    for row in rows {
+        let fits_last_batch = match batches.last() {
+            Some(batch) => {
+                let num_params = (batch.len() + 1) * row.len();
+                num_params <= max_params && batch[0].keys().eq(row.keys())
+            },
+            None => false,
+        };
+
+        if fits_last_batch {
+            if let Some(batch) = batches.last_mut() {
+                batch.push(row);
+            }
+        } else {
+            batches.push(vec![row]);
+        }
+    }
+
+    batches
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use kakapo_postgres::data::Column;
+    use kakapo_postgres::data::DataType;
+    use kakapo_postgres::data::SchemaState;
+
+    fn key(values: Vec<(&str, Value)>) -> LinkedHashMap<String, Value> {
+        values.into_iter().map(|(name, value)| (name.to_string(), value)).collect()
+    }
+
+    fn test_table() -> Table {
+        let column = |name: &str| Column {
+            name: name.to_string(),
+            data_type: DataType::Integer,
+            default: None,
+            nullable: false,
+            renamed_from: None,
+            generated: None,
+        };
+        let total = Column {
+            generated: Some("amount * 2".to_string()),
+            ..column("total")
+        };
+
+        Table {
+            name: "orders".to_string(),
+            description: "".to_string(),
+            schema: SchemaState {
+                columns: vec![column("shop_id"), column("order_id"), column("amount"), total],
+                constraint: vec![],
+                retention: None,
+            },
+            scope: None,
+        }
+    }
+
+    #[test]
+    fn test_delete_statement() {
+        let table = test_table();
+        let keys = vec![
+            key(vec![("shop_id", Value::Integer(1)), ("order_id", Value::Integer(10))]),
+            key(vec![("shop_id", Value::Integer(2)), ("order_id", Value::Integer(20))]),
+        ];
+
+        let (query, values) = delete_statement(&table, keys).unwrap();
+        assert_eq!(query, concat!(
+            r#"DELETE FROM "orders" WHERE ("shop_id" = $1 AND "order_id" = $2) OR ("shop_id" = $3 AND "order_id" = $4) "#,
+            r#"RETURNING *, CASE WHEN ("shop_id" = $1 AND "order_id" = $2) THEN 0 WHEN ("shop_id" = $3 AND "order_id" = $4) THEN 1 END AS "kakapo_matched_key";"#,
+        ));
+        assert_eq!(values, vec![Value::Integer(1), Value::Integer(10), Value::Integer(2), Value::Integer(20)]);
+
+        let keys = vec![key(vec![("order_id", Value::Integer(10))])];
+        let (query, _) = delete_statement(&table, keys).unwrap();
+        assert_eq!(query, r#"DELETE FROM "orders" WHERE ("order_id" = $1) RETURNING *, CASE WHEN ("order_id" = $1) THEN 0 END AS "kakapo_matched_key";"#);
+    }
+
+    fn deleted_rows(matched_keys: Vec<i64>) -> RawTableData {
+        let rows = matched_keys
+            .into_iter()
+            .map(|index| vec![Value::Integer(10), Value::Integer(index)])
+            .collect();
+        RawTableData::new_and_fill(vec!["order_id".to_string(), MATCHED_KEY_COLUMN.to_string()], rows)
+    }
+
+    #[test]
+    fn test_split_matched_keys() {
+        let (rows, num_missing) = split_matched_keys(deleted_rows(vec![0, 1]), 2).unwrap();
+        assert_eq!(num_missing, 0);
+        assert_eq!(rows.columns.values, vec!["order_id".to_string()]);
+        assert_eq!(rows.data[0].values, vec![Value::Integer(10)]);
+
+        // the first key matched both rows, the second one none
+        let (rows, num_missing) = split_matched_keys(deleted_rows(vec![0, 0]), 2).unwrap();
+        assert_eq!(num_missing, 1);
+        assert_eq!(rows.data.len(), 2);
+
+        assert!(split_matched_keys(deleted_rows(vec![2]), 2).is_err());
+        let rows = RawTableData::new_and_fill(vec!["order_id".to_string()], vec![vec![Value::Integer(10)]]);
+        assert!(split_matched_keys(rows, 1).is_err());
+    }
+
+    #[test]
+    fn test_dedup_keys() {
+        let keys = vec![
+            key(vec![("shop_id", Value::Integer(1)), ("order_id", Value::Integer(10))]),
+            key(vec![("order_id", Value::Integer(10)), ("shop_id", Value::Integer(1))]),
+            key(vec![("shop_id", Value::Integer(1)), ("order_id", Value::Integer(10))]),
+            key(vec![("shop_id", Value::Integer(1)), ("order_id", Value::Integer(20))]),
+        ];
+
+        let keys = dedup_keys(keys);
+        assert_eq!(keys.len(), 2);
+        assert_eq!(keys[1]["order_id"], Value::Integer(20));
+    }
+
+    #[test]
+    fn test_delete_statement_invalid() {
+        let table = test_table();
+
+        assert!(delete_statement(&table, vec![]).is_err());
+        assert!(delete_statement(&table, vec![key(vec![])]).is_err());
+
+        let keys = vec![key(vec![("price", Value::Integer(1))])];
+        assert!(delete_statement(&table, keys).is_err());
+
+        let keys = vec![key(vec![("order_id; --", Value::Integer(1))])];
+        assert!(delete_statement(&table, keys).is_err());
+    }
+
+    #[test]
+    fn test_check_writable() {
+        let table = test_table();
+
+        assert!(table.check_writable(&["order_id".to_string(), "amount".to_string()]).is_ok());
+        assert!(table.check_writable(&["amount".to_string(), "total".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_batch_keys() {
+        let keys = vec![
+            key(vec![("order_id", Value::Integer(1))]),
+            key(vec![("order_id", Value::Integer(2))]),
+            key(vec![("shop_id", Value::Integer(1)), ("order_id", Value::Integer(3))]),
+        ];
+
+        let batches = batch_rows(keys);
+        assert_eq!(batches.len(), 2);
+        assert_eq!(batches[0].len(), 2);
+        assert_eq!(batches[1].len(), 1);
+    }
 }
@@ -1,5 +1,6 @@
 
 use diesel::RunQueryDsl;
+use diesel::Connection;
 
 use diesel::r2d2::PooledConnection;
 use diesel::r2d2::ConnectionManager;
@@ -13,11 +14,36 @@ use state::user_management::UserManagementOps;
 
 use kakapo_postgres::data::DataType;
 use kakapo_postgres::data::Table;
+use kakapo_postgres::data::Column;
+use kakapo_postgres::data::Constraint;
+use kakapo_postgres::data::ReferentialAction;
+use kakapo_postgres::data::Value;
+use kakapo_postgres::methods::quote_column;
+use kakapo_postgres::methods::quote_identifier;
+use kakapo_postgres::methods::quote_qualified;
+use kakapo_postgres::methods::quote_table;
 
 use plugins::v1::DatastoreError;
+use plugins::v1::View;
 
-fn get_sql_data_type(data_type: &DataType) -> String {
-    match data_type {
+/// the fractional digits of the seconds, postgres only keeps up to microseconds
+const MAX_TIME_PRECISION: u32 = 6;
+
+fn get_precision(precision: &Option<u32>) -> Result<String, DatastoreError> {
+    match precision {
+        Some(x) if *x > MAX_TIME_PRECISION => Err(DatastoreError::InvalidQuery(
+            format!("the precision of a time must be between 0 and {}, not {}", MAX_TIME_PRECISION, x))),
+        Some(x) => Ok(format!("({})", x)),
+        None => Ok("".to_string()),
+    }
+}
+
+fn get_time_zone(with_tz: bool) -> &'static str {
+    if with_tz { " WITH TIME ZONE" } else { "" }
+}
+
+fn get_sql_data_type(data_type: &DataType) -> Result<String, DatastoreError> {
+    let sql_data_type = match data_type {
         DataType::SmallInteger => format!("SMALLINT"),
         DataType::Integer => format!("INTEGER"),
         DataType::BigInteger => format!("BIGINT"),
@@ -30,18 +56,427 @@ fn get_sql_data_type(data_type: &DataType) -> String {
 
         DataType::Byte => format!("BYTEA"),
 
-        DataType::Timestamp { with_tz } => match with_tz {
-            true => format!("TIMESTAMP WITH TIME ZONE"),
-            false => format!("TIMESTAMP"),
-        },
-        DataType::Date => format!("SMALLINT"),
-        DataType::Time { with_tz } => format!("SMALLINT"), //TODO: with_tz
+        DataType::Timestamp { with_tz, precision } => format!("TIMESTAMP{}{}", get_precision(precision)?, get_time_zone(*with_tz)),
+        DataType::Date => format!("DATE"),
+        DataType::Time { with_tz, precision } => format!("TIME{}{}", get_precision(precision)?, get_time_zone(*with_tz)),
         //DataType::TimeInterval,
 
         DataType::Boolean => format!("BOOLEAN"),
 
         DataType::Json => format!("JSON"),
+
+        DataType::Uuid { .. } => format!("UUID"),
+    };
+
+    Ok(sql_data_type)
+}
+
+/// Renders a value as a sql literal for the column defaults, strings are escaped by doubling the quotes
+fn get_sql_literal(value: &Value) -> String {
+    let quote = |text: &str| format!("'{}'", text.replace('\'', "''"));
+
+    match value {
+        Value::Null => format!("NULL"),
+        Value::String(x) => quote(x),
+        Value::Integer(x) => format!("{}", x),
+        Value::Float(x) if x.is_finite() => format!("{:?}", x),
+        Value::Float(x) if x.is_nan() => format!("'NaN'::DOUBLE PRECISION"),
+        Value::Float(x) => format!("'{}Infinity'::DOUBLE PRECISION", if x.is_sign_negative() { "-" } else { "" }),
+        Value::Boolean(x) => if *x { format!("TRUE") } else { format!("FALSE") },
+        Value::DateTime(x) => format!("{}::TIMESTAMP", quote(&x.format("%Y-%m-%dT%H:%M:%S%.f").to_string())),
+        Value::Date(x) => format!("{}::DATE", quote(&x.format("%Y-%m-%d").to_string())),
+        Value::Binary(x) => {
+            let hex: String = x.iter().map(|byte| format!("{:02x}", byte)).collect();
+            format!("'\\x{}'::BYTEA", hex)
+        },
+        Value::Uuid(x) => format!("{}::UUID", quote(&x.to_hyphenated().to_string())),
+        Value::Json(x) => format!("{}::JSON", quote(&x.to_string())),
+    }
+}
+
+/// an explicit default takes precedence over the generated uuid, generated columns have no default
+fn get_column_default(column: &Column) -> Option<String> {
+    if column.is_generated() {
+        return None;
     }
+
+    match (&column.default, &column.data_type) {
+        (Some(default), _) => Some(get_sql_literal(default)),
+        (None, DataType::Uuid { generate: true }) => Some(format!("gen_random_uuid()")),
+        (None, _) => None,
+    }
+}
+
+fn get_column_definition(column: &Column) -> Result<String, DatastoreError> {
+    let mut definition = format!("{} {}", quote_identifier(&column.name)?, get_sql_data_type(&column.data_type)?);
+    //TODO: serial
+
+    if !column.nullable {
+        definition.push_str(" NOT NULL");
+    }
+
+    if let Some(default) = get_column_default(column) {
+        definition.push_str(&format!(" DEFAULT {}", default));
+    }
+
+    if let Some(expression) = &column.generated {
+        if column.default.is_some() {
+            Err(DatastoreError::InvalidQuery(format!("generated column {} can't have a default", &column.name)))?;
+        }
+        definition.push_str(&format!(" GENERATED ALWAYS AS ({}) STORED", expression));
+    }
+
+    Ok(definition)
+}
+
+pub fn quote_columns(names: &[String], column_names: &[String]) -> Result<String, DatastoreError> {
+    let quoted = names
+        .iter()
+        .map(|name| quote_column(name, column_names))
+        .collect::<Result<Vec<String>, _>>()?;
+
+    Ok(quoted.join(", "))
+}
+
+fn quote_foreign_columns(names: &[String]) -> Result<String, DatastoreError> {
+    let quoted = names
+        .iter()
+        .map(|name| quote_identifier(name))
+        .collect::<Result<Vec<String>, _>>()?;
+
+    Ok(quoted.join(", "))
+}
+
+fn get_referential_actions(on_delete: &Option<ReferentialAction>, on_update: &Option<ReferentialAction>) -> String {
+    let action = |action: &ReferentialAction| match action {
+        ReferentialAction::Cascade => "CASCADE",
+        ReferentialAction::SetNull => "SET NULL",
+        ReferentialAction::Restrict => "RESTRICT",
+    };
+
+    let mut actions = String::new();
+    if let Some(on_delete) = on_delete {
+        actions.push_str(&format!(" ON DELETE {}", action(on_delete)));
+    }
+    if let Some(on_update) = on_update {
+        actions.push_str(&format!(" ON UPDATE {}", action(on_update)));
+    }
+
+    actions
+}
+
+/// The table constraints, all the `key` constraints make up a single primary key
+/// Columns of this table have to exist, the foreign columns are only checked when postgres runs the statement
+fn get_constraint_definitions(constraints: &[Constraint], column_names: &[String]) -> Result<Vec<String>, DatastoreError> {
+    constraint_definitions(constraints, column_names, get_sql_literal)
+}
+
+/// Same as `get_constraint_definitions`, with the values of the checks written by `get_literal`
+pub fn constraint_definitions(constraints: &[Constraint], column_names: &[String], get_literal: fn(&Value) -> String) -> Result<Vec<String>, DatastoreError> {
+    let keys: Vec<String> = constraints
+        .iter()
+        .filter_map(|constraint| match constraint {
+            Constraint::Key(column) => Some(column.to_owned()),
+            _ => None,
+        })
+        .collect();
+
+    let mut definitions = vec![];
+    if !keys.is_empty() {
+        definitions.push(format!("PRIMARY KEY ({})", quote_columns(&keys, column_names)?));
+    }
+
+    for constraint in constraints {
+        let definition = match constraint {
+            Constraint::Key(_) => continue,
+            Constraint::Unique(column) => format!("UNIQUE ({})", quote_column(column, column_names)?),
+            Constraint::UniqueTogether(columns) => format!("UNIQUE ({})", quote_columns(columns, column_names)?),
+            // DDL can't be parameterized, so the values are written in as literals
+            Constraint::Check(expression) => format!("CHECK ({})", expression.to_sql_with_literals(column_names, get_literal)?),
+            Constraint::Reference { column, foreign_table, foreign_column, on_delete, on_update } => format!(
+                "FOREIGN KEY ({}) REFERENCES {} ({}){}",
+                quote_column(column, column_names)?,
+                quote_identifier(foreign_table)?,
+                quote_identifier(foreign_column)?,
+                get_referential_actions(on_delete, on_update),
+            ),
+            Constraint::ReferenceTogether { columns, foreign_table, foreign_columns, on_delete, on_update } => {
+                if columns.len() != foreign_columns.len() || columns.is_empty() {
+                    Err(DatastoreError::InvalidQuery(format!("reference to {} needs the same number of columns on both sides", foreign_table)))?;
+                }
+                format!(
+                    "FOREIGN KEY ({}) REFERENCES {} ({}){}",
+                    quote_columns(columns, column_names)?,
+                    quote_identifier(foreign_table)?,
+                    quote_foreign_columns(foreign_columns)?,
+                    get_referential_actions(on_delete, on_update),
+                )
+            },
+        };
+        definitions.push(definition);
+    }
+
+    Ok(definitions)
+}
+
+/// Whether every value of the old type fits in the new type, anything can be turned into text
+fn is_widening(old: &DataType, new: &DataType) -> bool {
+    // postgres keeps 6 fractional digits when there is no precision
+    let precision = |x: &Option<u32>| x.unwrap_or(6);
+
+    match (old, new) {
+        (old, new) if old == new => true,
+        (_, DataType::String) => true,
+        (DataType::SmallInteger, DataType::Integer) => true,
+        (DataType::SmallInteger, DataType::BigInteger) => true,
+        (DataType::Integer, DataType::BigInteger) => true,
+        (DataType::SmallInteger, DataType::DoubleFloat) => true,
+        (DataType::Integer, DataType::DoubleFloat) => true,
+        (DataType::Float, DataType::DoubleFloat) => true,
+        (DataType::VarChar { length: old_length }, DataType::VarChar { length: new_length }) => new_length >= old_length,
+        (DataType::Date, DataType::Timestamp { .. }) => true,
+        (DataType::Timestamp { with_tz: old_tz, precision: old_precision }, DataType::Timestamp { with_tz: new_tz, precision: new_precision }) =>
+            old_tz == new_tz && precision(new_precision) >= precision(old_precision),
+        (DataType::Time { with_tz: old_tz, precision: old_precision }, DataType::Time { with_tz: new_tz, precision: new_precision }) =>
+            old_tz == new_tz && precision(new_precision) >= precision(old_precision),
+        (DataType::Uuid { .. }, DataType::Uuid { .. }) => true,
+        _ => false,
+    }
+}
+
+/// A single statement of a table update
+/// `destructive` is set for the steps that can lose data, i.e. drops and narrowing type changes
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SchemaChangeStep {
+    pub statement: String,
+    pub destructive: bool,
+    pub description: String,
+}
+
+impl SchemaChangeStep {
+    pub fn new(table_name: &str, action: String, destructive: bool, description: String) -> Self {
+        Self {
+            statement: format!("ALTER TABLE {} {};", table_name, action),
+            destructive,
+            description,
+        }
+    }
+}
+
+/// The old column a new column is based on, either with the same name or the one it was renamed from
+pub fn find_old_column<'a>(old: &'a Table, new_column: &Column) -> Option<&'a Column> {
+    old.schema.columns
+        .iter()
+        .find(|x| x.name == new_column.name)
+        .or_else(|| {
+            let renamed_from = new_column.renamed_from.as_ref()?;
+            old.schema.columns.iter().find(|x| &x.name == renamed_from)
+        })
+}
+
+/// Computes the statements turning the `old` table into the `new` one, without running them
+/// Columns are matched by name or by `renamedFrom`, the renames come first so the data is kept,
+/// then the dropped columns, so a column can be replaced by one with the same name
+pub fn plan_table_update(old: &Table, new: &Table) -> Result<Vec<SchemaChangeStep>, DatastoreError> {
+    if new.schema.columns.len() == 0 {
+        Err(DatastoreError::NoColumns)?;
+    }
+
+    check_retention_policy(new)?;
+
+    let table_name = quote_table(new)?;
+    let mut steps = vec![];
+
+    if old.name != new.name {
+        steps.push(SchemaChangeStep {
+            statement: format!("ALTER TABLE {} RENAME TO {};", quote_table(old)?, quote_identifier(&new.name)?),
+            destructive: false,
+            description: format!("rename table {} to {}", &old.name, &new.name),
+        });
+    }
+
+    // the table is renamed in its old schema first
+    if old.scope != new.scope {
+        let renamed = quote_qualified(old.scope.as_ref().map(|x| x.as_str()), &new.name)?;
+        let (statement, description) = match &new.scope {
+            Some(scope) => {
+                steps.push(SchemaChangeStep {
+                    statement: get_create_schema_statement(scope)?,
+                    destructive: false,
+                    description: format!("create the schema of scope {}", scope),
+                });
+                (
+                    format!("ALTER TABLE {} SET SCHEMA {};", &renamed, quote_identifier(scope)?),
+                    format!("move table {} to scope {}", &new.name, scope),
+                )
+            },
+            None => (
+                format!("ALTER TABLE {} SET SCHEMA public;", &renamed),
+                format!("move table {} to the main scope", &new.name),
+            ),
+        };
+        steps.push(SchemaChangeStep {
+            statement,
+            destructive: false,
+            description,
+        });
+    }
+
+    // old name -> new name
+    let mut renames = vec![];
+    for new_column in &new.schema.columns {
+        let old_column = match find_old_column(old, new_column) {
+            Some(old_column) if old_column.name != new_column.name => old_column,
+            _ => continue,
+        };
+
+        if renames.iter().any(|(old_name, _)| old_name == &old_column.name) {
+            Err(DatastoreError::InvalidQuery(format!("column {} is renamed more than once", &old_column.name)))?;
+        }
+        renames.push((old_column.name.to_owned(), new_column.name.to_owned()));
+        steps.push(SchemaChangeStep::new(
+            &table_name,
+            format!("RENAME COLUMN {} TO {}", quote_identifier(&old_column.name)?, quote_identifier(&new_column.name)?),
+            false,
+            format!("rename column {} to {}", &old_column.name, &new_column.name)));
+    }
+
+    for old_column in &old.schema.columns {
+        let is_kept = new.schema.columns
+            .iter()
+            .any(|new_column| find_old_column(old, new_column).map(|x| x.name == old_column.name).unwrap_or(false));
+        if !is_kept {
+            steps.push(SchemaChangeStep::new(
+                &table_name,
+                format!("DROP COLUMN {}", quote_identifier(&old_column.name)?),
+                true,
+                format!("drop column {}", &old_column.name)));
+        }
+    }
+
+    for new_column in &new.schema.columns {
+        let column_name = quote_identifier(&new_column.name)?;
+        let old_column = match find_old_column(old, new_column) {
+            Some(old_column) => old_column,
+            None => {
+                steps.push(SchemaChangeStep::new(
+                    &table_name,
+                    format!("ADD COLUMN {}", get_column_definition(new_column)?),
+                    false,
+                    format!("add column {}", &new_column.name)));
+                continue;
+            },
+        };
+
+        // postgres can't change the expression of a generated column, it has to be dropped and added again
+        if old_column.generated != new_column.generated {
+            Err(DatastoreError::InvalidQuery(format!("the generated expression of column {} can't be changed", &new_column.name)))?;
+        }
+
+        let old_type = get_sql_data_type(&old_column.data_type)?;
+        let new_type = get_sql_data_type(&new_column.data_type)?;
+        if old_type != new_type {
+            // generated columns are computed again, so they can't have a USING clause
+            let using = if new_column.is_generated() {
+                "".to_string()
+            } else {
+                format!(" USING {}::{}", &column_name, &new_type)
+            };
+            steps.push(SchemaChangeStep::new(
+                &table_name,
+                format!("ALTER COLUMN {} TYPE {}{}", &column_name, &new_type, using),
+                !is_widening(&old_column.data_type, &new_column.data_type),
+                format!("change the type of {} from {} to {}", &new_column.name, &old_type, &new_type)));
+        }
+
+        if old_column.nullable != new_column.nullable {
+            let (action, description) = if new_column.nullable {
+                ("DROP NOT NULL", "allow nulls in")
+            } else {
+                ("SET NOT NULL", "disallow nulls in")
+            };
+            steps.push(SchemaChangeStep::new(
+                &table_name,
+                format!("ALTER COLUMN {} {}", &column_name, action),
+                false,
+                format!("{} {}", description, &new_column.name)));
+        }
+
+        let old_default = get_column_default(old_column);
+        let new_default = get_column_default(new_column);
+        if old_default != new_default {
+            let action = match &new_default {
+                Some(default) => format!("SET DEFAULT {}", default),
+                None => format!("DROP DEFAULT"),
+            };
+            steps.push(SchemaChangeStep::new(
+                &table_name,
+                format!("ALTER COLUMN {} {}", &column_name, action),
+                false,
+                format!("change the default of {}", &new_column.name)));
+        }
+    }
+
+    // postgres names the constraints itself, so they can only be added for now
+    // the renamed columns are renamed in the constraints as well by postgres
+    let rename = |name: &str| renames
+        .iter()
+        .find(|(old_name, _)| old_name == name)
+        .map(|(_, new_name)| new_name.to_owned())
+        .unwrap_or_else(|| name.to_owned());
+    let old_constraints: Vec<Constraint> = old.schema.constraint
+        .iter()
+        .map(|x| x.with_renamed_columns(&rename))
+        .collect();
+
+    let removed = old_constraints.iter().any(|x| !new.schema.constraint.contains(x));
+    if removed {
+        Err(DatastoreError::InvalidQuery(format!("removing constraints is not supported")))?;
+    }
+
+    let added: Vec<Constraint> = new.schema.constraint
+        .iter()
+        .filter(|x| !old_constraints.contains(x))
+        .cloned()
+        .collect();
+    let has_key = |constraints: &[Constraint]| constraints.iter().any(|x| match x {
+        Constraint::Key(_) => true,
+        _ => false,
+    });
+    if has_key(&added) && has_key(&old_constraints) {
+        Err(DatastoreError::InvalidQuery(format!("the primary key can't be changed")))?;
+    }
+
+    for definition in get_constraint_definitions(&added, &new.schema.get_column_names())? {
+        steps.push(SchemaChangeStep::new(
+            &table_name,
+            format!("ADD {}", &definition),
+            false,
+            format!("add {}", &definition)));
+    }
+
+    Ok(steps)
+}
+
+/// the schema of a scope is created with its first table, and kept when the last one is deleted
+fn get_create_schema_statement(scope: &str) -> Result<String, DatastoreError> {
+    Ok(format!("CREATE SCHEMA IF NOT EXISTS {};", quote_identifier(scope)?))
+}
+
+pub fn get_copy_statement(source: &Table, target: &Table) -> Result<String, DatastoreError> {
+    let column_names = target.get_column_names();
+    if source.get_column_names() != column_names {
+        Err(DatastoreError::InvalidQuery(format!("{} and {} don't have the same columns", &source.name, &target.name)))?;
+    }
+
+    let columns = quote_columns(&column_names, &column_names)?;
+    Ok(format!(
+        "INSERT INTO {} ({}) SELECT {} FROM {};",
+        quote_table(target)?,
+        &columns,
+        &columns,
+        quote_table(source)?))
 }
 
 pub struct UpdateTable<'a> {
@@ -60,6 +495,10 @@ pub trait UpdateTableOps {
     fn update_table(&self, old: &Table, new: &Table) -> Result<(), DatastoreError>;
 
     fn delete_table(&self, old: &Table) -> Result<(), DatastoreError>;
+
+    fn copy_table_data(&self, source: &Table, target: &Table) -> Result<usize, DatastoreError>;
+
+    fn purge_expired_rows(&self, table: &Table, batch_size: usize) -> Result<usize, DatastoreError>;
 }
 
 //modify table in database here
@@ -73,16 +512,25 @@ impl<'a> UpdateTableOps for UpdateTable<'a> {
             Err(DatastoreError::NoColumns)?;
         }
 
-        let formatted_columns: Vec<String> = columns.iter().map(|column| {
-            let col_name = &column.name;
-            let col_type = get_sql_data_type(&column.data_type);
-            //TODO: nullable + default + serial
-            format!("\"{}\" {}", col_name, col_type)
-        }).collect();
-        let command = format!("CREATE TABLE \"{}\" ({});", &new.name, formatted_columns.join(", "));
-        info!("DSL command: `{}`", &command);
+        check_retention_policy(new)?;
+
+        let mut definitions = columns
+            .iter()
+            .map(get_column_definition)
+            .collect::<Result<Vec<String>, DatastoreError>>()?;
+        definitions.extend(get_constraint_definitions(&schema.constraint, &schema.get_column_names())?);
+
+        if let Some(scope) = &new.scope {
+            let command = get_create_schema_statement(scope)?;
+            info!("DSL command: `{}`", &command);
+            diesel::sql_query(command)
+                .execute(self.conn)
+                .or_else(|err|
+                    Err(DatastoreError::DbError(err.to_string())))?;
+        }
 
-        //TODO: constraints...
+        let command = format!("CREATE TABLE {} ({});", quote_table(new)?, definitions.join(", "));
+        info!("DSL command: `{}`", &command);
 
         diesel::sql_query(command)
             .execute(self.conn)
@@ -95,8 +543,25 @@ impl<'a> UpdateTableOps for UpdateTable<'a> {
     }
 
     fn update_table(&self, old: &Table, new: &Table) -> Result<(), DatastoreError> {
-        unimplemented!();
-        let command = format!("ALTER TABLE \"{}\";", &old.name);
+        let steps = plan_table_update(old, new)?;
+
+        // a failing step shouldn't leave the table half updated
+        self.conn.transaction::<_, diesel::result::Error, _>(|| {
+            for step in &steps {
+                info!("DSL command: `{}`", &step.statement);
+                diesel::sql_query(step.statement.to_owned())
+                    .execute(self.conn)?;
+            }
+
+            Ok(())
+        }).or_else(|err|
+            Err(DatastoreError::DbError(err.to_string())))?;
+
+        Ok(())
+    }
+
+    fn delete_table(&self, old: &Table) -> Result<(), DatastoreError> {
+        let command = format!("DROP TABLE {};", quote_table(old)?);
         diesel::sql_query(command)
             .execute(self.conn)
             .or_else(|err|
@@ -105,13 +570,571 @@ impl<'a> UpdateTableOps for UpdateTable<'a> {
         Ok(())
     }
 
-    fn delete_table(&self, old: &Table) -> Result<(), DatastoreError> {
-        let command = format!("DROP TABLE \"{}\";", &old.name);
-        diesel::sql_query(command)
+    fn copy_table_data(&self, source: &Table, target: &Table) -> Result<usize, DatastoreError> {
+        let command = get_copy_statement(source, target)?;
+        info!("DSL command: `{}`", &command);
+
+        let copied_rows = diesel::sql_query(command)
             .execute(self.conn)
             .or_else(|err|
                 Err(DatastoreError::DbError(err.to_string())))?;
 
+        Ok(copied_rows)
+    }
+
+    fn purge_expired_rows(&self, table: &Table, batch_size: usize) -> Result<usize, DatastoreError> {
+        let command = get_purge_statement(table, batch_size)?;
+        debug!("DSL command: `{}`", &command);
+
+        diesel::sql_query(command)
+            .execute(self.conn)
+            .or_else(|err|
+                Err(DatastoreError::DbError(err.to_string())))
+    }
+}
+
+/// The retention column has to be a timestamp or a date of the table
+pub fn check_retention_policy(table: &Table) -> Result<(), DatastoreError> {
+    let retention = match &table.schema.retention {
+        Some(retention) => retention,
+        None => return Ok(()),
+    };
+
+    let column = table.schema.columns
+        .iter()
+        .find(|column| column.name == retention.column)
+        .ok_or_else(|| DatastoreError::InvalidQuery(format!("retention column {} does not exist", &retention.column)))?;
+
+    match column.data_type {
+        DataType::Timestamp { .. } | DataType::Date => {},
+        _ => Err(DatastoreError::InvalidQuery(format!("retention column {} must be a timestamp or a date", &retention.column)))?,
+    }
+
+    if retention.days == 0 {
+        Err(DatastoreError::InvalidQuery(format!("the retention period of {} must be at least a day", &table.name)))?;
+    }
+
+    Ok(())
+}
+
+/// Deletes a batch of the expired rows, postgres has no `DELETE ... LIMIT` so the rows are picked by `ctid`
+fn get_purge_statement(table: &Table, batch_size: usize) -> Result<String, DatastoreError> {
+    check_retention_policy(table)?;
+    let retention = table.schema.retention
+        .as_ref()
+        .ok_or_else(|| DatastoreError::InvalidQuery(format!("table {} has no retention policy", &table.name)))?;
+
+    if batch_size == 0 {
+        Err(DatastoreError::InvalidQuery("batch size must be positive".to_string()))?;
+    }
+
+    Ok(format!(
+        "DELETE FROM {name} WHERE ctid IN (SELECT ctid FROM {name} WHERE {column} < NOW() - INTERVAL '{days} days' LIMIT {limit});",
+        name=quote_table(table)?,
+        column=quote_column(&retention.column, &table.schema.get_column_names())?,
+        days=retention.days,
+        limit=batch_size,
+    ))
+}
+
+/// The statement without the trailing semicolon, so it can be put in a `CREATE VIEW`
+pub fn get_view_statement(view: &View) -> Result<String, DatastoreError> {
+    let statement = view.statement.trim().trim_end_matches(';').trim();
+    if statement.is_empty() {
+        Err(DatastoreError::InvalidQuery(format!("view {} has no statement", &view.name)))?;
+    }
+
+    Ok(statement.to_string())
+}
+
+fn get_view_kind(view: &View) -> &'static str {
+    if view.materialized { "MATERIALIZED VIEW" } else { "VIEW" }
+}
+
+fn get_create_view_statement(view: &View) -> Result<String, DatastoreError> {
+    Ok(format!("CREATE {} {} AS {};", get_view_kind(view), quote_identifier(&view.name)?, get_view_statement(view)?))
+}
+
+fn get_drop_view_statement(view: &View) -> Result<String, DatastoreError> {
+    Ok(format!("DROP {} {};", get_view_kind(view), quote_identifier(&view.name)?))
+}
+
+pub struct UpdateView<'a> {
+    conn: &'a PooledConnection<ConnectionManager<PgConnection>>,
+}
+
+impl<'a> UpdateView<'a> {
+    pub fn new(conn: &'a PooledConnection<ConnectionManager<PgConnection>>) -> Self {
+        Self { conn }
+    }
+
+    fn execute(&self, commands: Vec<String>) -> Result<(), DatastoreError> {
+        self.conn.transaction::<_, diesel::result::Error, _>(|| {
+            for command in commands {
+                info!("DSL command: `{}`", &command);
+                diesel::sql_query(command)
+                    .execute(self.conn)?;
+            }
+
+            Ok(())
+        }).or_else(|err|
+            Err(DatastoreError::DbError(err.to_string())))?;
+
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+pub trait UpdateViewOps {
+    fn create_view(&self, new: &View) -> Result<(), DatastoreError>;
+
+    fn update_view(&self, old: &View, new: &View) -> Result<(), DatastoreError>;
+
+    fn delete_view(&self, old: &View) -> Result<(), DatastoreError>;
+
+    fn refresh_view(&self, view: &View) -> Result<(), DatastoreError>;
+}
+
+impl<'a> UpdateViewOps for UpdateView<'a> {
+    fn create_view(&self, new: &View) -> Result<(), DatastoreError> {
+        self.execute(vec![get_create_view_statement(new)?])
+    }
+
+    /// the view is dropped and created again, `CREATE OR REPLACE` can't remove or rename columns
+    /// and a view can't be turned into a materialized one
+    fn update_view(&self, old: &View, new: &View) -> Result<(), DatastoreError> {
+        self.execute(vec![
+            get_drop_view_statement(old)?,
+            get_create_view_statement(new)?,
+        ])
+    }
+
+    fn delete_view(&self, old: &View) -> Result<(), DatastoreError> {
+        self.execute(vec![get_drop_view_statement(old)?])
+    }
+
+    fn refresh_view(&self, view: &View) -> Result<(), DatastoreError> {
+        if !view.materialized {
+            Err(DatastoreError::InvalidQuery(format!("{} is not a materialized view", &view.name)))?;
+        }
+
+        self.execute(vec![format!("REFRESH MATERIALIZED VIEW {};", quote_identifier(&view.name)?)])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use kakapo_postgres::data::SchemaState;
+
+    fn column(name: &str, data_type: DataType, default: Option<Value>, nullable: bool) -> Column {
+        Column { name: name.to_string(), data_type, default, nullable, renamed_from: None, generated: None }
+    }
+
+    #[test]
+    fn test_column_definition() {
+        let definition = |data_type, default, nullable| {
+            get_column_definition(&column("col", data_type, default, nullable)).unwrap()
+        };
+
+        assert_eq!(definition(DataType::SmallInteger, Some(Value::Integer(-3)), false), r#""col" SMALLINT NOT NULL DEFAULT -3"#);
+        assert_eq!(definition(DataType::Integer, None, true), r#""col" INTEGER"#);
+        assert_eq!(definition(DataType::BigInteger, Some(Value::Integer(42)), true), r#""col" BIGINT DEFAULT 42"#);
+        assert_eq!(definition(DataType::Float, Some(Value::Float(1.5)), false), r#""col" REAL NOT NULL DEFAULT 1.5"#);
+        assert_eq!(definition(DataType::DoubleFloat, Some(Value::Float(2.0)), true), r#""col" DOUBLE PRECISION DEFAULT 2.0"#);
+        assert_eq!(definition(DataType::String, Some(Value::String("it's".to_string())), false), r#""col" TEXT NOT NULL DEFAULT 'it''s'"#);
+        assert_eq!(definition(DataType::VarChar { length: 10 }, Some(Value::String("a".to_string())), true), r#""col" VARCHAR(10) DEFAULT 'a'"#);
+        assert_eq!(definition(DataType::Byte, Some(Value::Binary(vec![0xDE, 0xAD])), true), r#""col" BYTEA DEFAULT '\xdead'::BYTEA"#);
+        assert_eq!(
+            definition(DataType::Timestamp { with_tz: false, precision: None }, Some(Value::DateTime(chrono::NaiveDate::from_ymd(2019, 04, 20).and_hms(16, 20, 00))), true),
+            r#""col" TIMESTAMP DEFAULT '2019-04-20T16:20:00'::TIMESTAMP"#);
+        assert_eq!(definition(DataType::Timestamp { with_tz: true, precision: None }, None, false), r#""col" TIMESTAMP WITH TIME ZONE NOT NULL"#);
+        assert_eq!(
+            definition(DataType::Date, Some(Value::Date(chrono::NaiveDate::from_ymd(2019, 04, 20))), true),
+            r#""col" DATE DEFAULT '2019-04-20'::DATE"#);
+        assert_eq!(
+            definition(DataType::Time { with_tz: false, precision: None }, Some(Value::String("16:20:00".to_string())), true),
+            r#""col" TIME DEFAULT '16:20:00'"#);
+        assert_eq!(definition(DataType::Boolean, Some(Value::Boolean(false)), false), r#""col" BOOLEAN NOT NULL DEFAULT FALSE"#);
+        assert_eq!(definition(DataType::Json, Some(Value::Json(json!({"a": "b'c"}))), true), r#""col" JSON DEFAULT '{"a":"b''c"}'::JSON"#);
+        assert_eq!(definition(DataType::Uuid { generate: true }, None, false), r#""col" UUID NOT NULL DEFAULT gen_random_uuid()"#);
+
+        let uuid = uuid::Uuid::parse_str("6f1e8a2c-2c5d-4b1e-9c7a-3d2b1a0f9e8d").unwrap();
+        assert_eq!(
+            definition(DataType::Uuid { generate: true }, Some(Value::Uuid(uuid)), true),
+            r#""col" UUID DEFAULT '6f1e8a2c-2c5d-4b1e-9c7a-3d2b1a0f9e8d'::UUID"#);
+
+        assert_eq!(definition(DataType::Integer, Some(Value::Null), true), r#""col" INTEGER DEFAULT NULL"#);
+        assert!(get_column_definition(&column("bad name", DataType::Integer, None, true)).is_err());
+
+        let total = Column { generated: Some("price * quantity".to_string()), ..column("total", DataType::DoubleFloat, None, false) };
+        assert_eq!(
+            get_column_definition(&total).unwrap(),
+            r#""total" DOUBLE PRECISION NOT NULL GENERATED ALWAYS AS (price * quantity) STORED"#);
+
+        let with_default = Column { default: Some(Value::Float(0.0)), ..total };
+        assert!(get_column_definition(&with_default).is_err());
+    }
+
+    #[test]
+    fn test_sql_data_type() {
+        let cases = vec![
+            (DataType::SmallInteger, "SMALLINT"),
+            (DataType::Integer, "INTEGER"),
+            (DataType::BigInteger, "BIGINT"),
+            (DataType::Float, "REAL"),
+            (DataType::DoubleFloat, "DOUBLE PRECISION"),
+            (DataType::String, "TEXT"),
+            (DataType::VarChar { length: 255 }, "VARCHAR(255)"),
+            (DataType::Byte, "BYTEA"),
+            (DataType::Timestamp { with_tz: false, precision: None }, "TIMESTAMP"),
+            (DataType::Timestamp { with_tz: true, precision: None }, "TIMESTAMP WITH TIME ZONE"),
+            (DataType::Timestamp { with_tz: false, precision: Some(3) }, "TIMESTAMP(3)"),
+            (DataType::Timestamp { with_tz: true, precision: Some(0) }, "TIMESTAMP(0) WITH TIME ZONE"),
+            (DataType::Date, "DATE"),
+            (DataType::Time { with_tz: false, precision: None }, "TIME"),
+            (DataType::Time { with_tz: true, precision: None }, "TIME WITH TIME ZONE"),
+            (DataType::Time { with_tz: true, precision: Some(2) }, "TIME(2) WITH TIME ZONE"),
+            (DataType::Boolean, "BOOLEAN"),
+            (DataType::Json, "JSON"),
+            (DataType::Uuid { generate: false }, "UUID"),
+        ];
+
+        for (data_type, expected) in cases {
+            assert_eq!(get_sql_data_type(&data_type).unwrap(), expected, "{:?}", data_type);
+        }
+
+        let too_precise = DataType::Time { with_tz: false, precision: Some(7) };
+        assert!(get_sql_data_type(&too_precise).is_err());
+        assert!(get_column_definition(&column("col", too_precise, None, true)).is_err());
+    }
+
+    #[test]
+    fn test_constraint_definitions() {
+        let schema: SchemaState = serde_json::from_value(json!({
+            "columns": [
+                { "name": "id", "dataType": "integer" },
+                { "name": "version", "dataType": "integer" },
+                { "name": "name", "dataType": "string" },
+                { "name": "owner", "dataType": "integer" }
+            ],
+            "constraint": [
+                { "key": "id" },
+                { "unique": "name" },
+                { "key": "version" },
+                { "uniqueTogether": ["name", "owner"] },
+                { "check": { "op": "or", "expressions": [
+                    { "op": "greaterThan", "column": "version", "value": 0 },
+                    { "op": "equals", "column": "name", "value": "it's" }
+                ] } },
+                { "reference": { "column": "owner", "foreignTable": "users", "foreignColumn": "id", "onDelete": "setNull" } },
+                { "referenceTogether": {
+                    "columns": ["id", "version"], "foreignTable": "revisions", "foreignColumns": ["doc", "rev"],
+                    "onDelete": "cascade", "onUpdate": "restrict"
+                } }
+            ]
+        })).unwrap();
+
+        let definitions = get_constraint_definitions(&schema.constraint, &schema.get_column_names()).unwrap();
+        assert_eq!(definitions, vec![
+            r#"PRIMARY KEY ("id", "version")"#,
+            r#"UNIQUE ("name")"#,
+            r#"UNIQUE ("name", "owner")"#,
+            r#"CHECK (("version" > 0) OR ("name" = 'it''s'))"#,
+            r#"FOREIGN KEY ("owner") REFERENCES "users" ("id") ON DELETE SET NULL"#,
+            r#"FOREIGN KEY ("id", "version") REFERENCES "revisions" ("doc", "rev") ON DELETE CASCADE ON UPDATE RESTRICT"#,
+        ]);
+
+        // the literals are written as the expression is compiled, a placeholder in a value stays in its string
+        let placeholders = vec![Constraint::Check(serde_json::from_value(json!({ "op": "in", "column": "name", "values": [
+            "a", "$1') OR (TRUE", "it's $2"
+        ] })).unwrap())];
+        assert_eq!(
+            get_constraint_definitions(&placeholders, &schema.get_column_names()).unwrap(),
+            vec![r#"CHECK ("name" IN ('a', '$1'') OR (TRUE', 'it''s $2'))"#]);
+
+        let unknown = vec![Constraint::Unique("missing".to_string())];
+        assert!(get_constraint_definitions(&unknown, &schema.get_column_names()).is_err());
+
+        let unknown = vec![Constraint::Check(serde_json::from_value(json!({ "op": "lessThan", "column": "missing", "value": 1 })).unwrap())];
+        assert!(get_constraint_definitions(&unknown, &schema.get_column_names()).is_err());
+
+        let invalid = vec![Constraint::Reference {
+            column: "owner".to_string(),
+            foreign_table: "users; DROP TABLE users".to_string(),
+            foreign_column: "id".to_string(),
+            on_delete: None,
+            on_update: None,
+        }];
+        assert!(get_constraint_definitions(&invalid, &schema.get_column_names()).is_err());
+
+        let mismatched = vec![Constraint::ReferenceTogether {
+            columns: vec!["id".to_string()],
+            foreign_table: "revisions".to_string(),
+            foreign_columns: vec!["doc".to_string(), "rev".to_string()],
+            on_delete: None,
+            on_update: None,
+        }];
+        assert!(get_constraint_definitions(&mismatched, &schema.get_column_names()).is_err());
+    }
+
+    fn table(schema: serde_json::Value) -> Table {
+        Table {
+            name: "things".to_string(),
+            description: "".to_string(),
+            schema: serde_json::from_value(schema).unwrap(),
+            scope: None,
+        }
+    }
+
+    #[test]
+    fn test_plan_table_update() {
+        let old = table(json!({
+            "columns": [
+                { "name": "id", "dataType": "integer" },
+                { "name": "name", "dataType": { "varChar": { "length": 20 } } },
+                { "name": "price", "dataType": "doubleFloat" },
+                { "name": "legacy", "dataType": "string", "nullable": true }
+            ],
+            "constraint": [{ "key": "id" }]
+        }));
+        let new = table(json!({
+            "columns": [
+                { "name": "id", "dataType": "bigInteger" },
+                { "name": "name", "dataType": { "varChar": { "length": 10 } }, "nullable": true },
+                { "name": "price", "dataType": "doubleFloat", "default": 0 },
+                { "name": "created", "dataType": "date", "nullable": true }
+            ],
+            "constraint": [{ "key": "id" }, { "unique": "name" }]
+        }));
+
+        let steps = plan_table_update(&old, &new).unwrap();
+        let statements: Vec<(&str, bool)> = steps.iter().map(|x| (x.statement.as_str(), x.destructive)).collect();
+        assert_eq!(statements, vec![
+            (r#"ALTER TABLE "things" DROP COLUMN "legacy";"#, true),
+            (r#"ALTER TABLE "things" ALTER COLUMN "id" TYPE BIGINT USING "id"::BIGINT;"#, false),
+            (r#"ALTER TABLE "things" ALTER COLUMN "name" TYPE VARCHAR(10) USING "name"::VARCHAR(10);"#, true),
+            (r#"ALTER TABLE "things" ALTER COLUMN "name" DROP NOT NULL;"#, false),
+            (r#"ALTER TABLE "things" ALTER COLUMN "price" SET DEFAULT 0;"#, false),
+            (r#"ALTER TABLE "things" ADD COLUMN "created" DATE;"#, false),
+            (r#"ALTER TABLE "things" ADD UNIQUE ("name");"#, false),
+        ]);
+
+        assert_eq!(plan_table_update(&old, &old).unwrap(), vec![]);
+        assert!(plan_table_update(&new, &old).is_err()); // drops the unique constraint
+    }
+
+    #[test]
+    fn test_plan_renames() {
+        let old = table(json!({
+            "columns": [
+                { "name": "id", "dataType": "integer" },
+                { "name": "title", "dataType": "string" },
+                { "name": "body", "dataType": "string" }
+            ],
+            "constraint": [{ "key": "id" }, { "unique": "title" }]
+        }));
+        let mut new = table(json!({
+            "columns": [
+                { "name": "id", "dataType": "integer" },
+                { "name": "name", "dataType": "string", "renamedFrom": "title" },
+                { "name": "body", "dataType": "string" }
+            ],
+            "constraint": [{ "key": "id" }, { "unique": "name" }]
+        }));
+        new.name = "items".to_string();
+
+        let steps = plan_table_update(&old, &new).unwrap();
+        let statements: Vec<&str> = steps.iter().map(|x| x.statement.as_str()).collect();
+        assert_eq!(statements, vec![
+            r#"ALTER TABLE "things" RENAME TO "items";"#,
+            r#"ALTER TABLE "items" RENAME COLUMN "title" TO "name";"#,
+        ]);
+        assert!(steps.iter().all(|x| !x.destructive));
+
+        // the column is already renamed, so `renamedFrom` doesn't do anything anymore
+        assert_eq!(plan_table_update(&new, &new).unwrap(), vec![]);
+
+        let twice = table(json!({
+            "columns": [
+                { "name": "id", "dataType": "integer" },
+                { "name": "a", "dataType": "string", "renamedFrom": "title" },
+                { "name": "b", "dataType": "string", "renamedFrom": "title" }
+            ],
+            "constraint": [{ "key": "id" }]
+        }));
+        assert!(plan_table_update(&old, &twice).is_err());
+    }
+
+    #[test]
+    fn test_plan_scopes() {
+        let old = table(json!({
+            "columns": [{ "name": "id", "dataType": "integer" }],
+            "constraint": []
+        }));
+        let mut new = old.clone();
+        new.name = "invoices".to_string();
+        new.scope = Some("finance".to_string());
+
+        let statements: Vec<String> = plan_table_update(&old, &new)
+            .unwrap()
+            .into_iter()
+            .map(|x| x.statement)
+            .collect();
+        assert_eq!(statements, vec![
+            r#"ALTER TABLE "things" RENAME TO "invoices";"#.to_string(),
+            r#"CREATE SCHEMA IF NOT EXISTS "finance";"#.to_string(),
+            r#"ALTER TABLE "invoices" SET SCHEMA "finance";"#.to_string(),
+        ]);
+
+        let statements: Vec<String> = plan_table_update(&new, &old)
+            .unwrap()
+            .into_iter()
+            .map(|x| x.statement)
+            .collect();
+        assert_eq!(statements, vec![
+            r#"ALTER TABLE "finance"."invoices" RENAME TO "things";"#.to_string(),
+            r#"ALTER TABLE "finance"."things" SET SCHEMA public;"#.to_string(),
+        ]);
+
+        let mut copy = new.clone();
+        copy.name = "invoices_copy".to_string();
+        assert_eq!(
+            get_copy_statement(&new, &copy).unwrap(),
+            r#"INSERT INTO "finance"."invoices_copy" ("id") SELECT "id" FROM "finance"."invoices";"#);
+
+        new.scope = Some("finance; DROP TABLE users".to_string());
+        assert!(plan_table_update(&old, &new).is_err());
+    }
+
+    #[test]
+    fn test_plan_generated_columns() {
+        let old = table(json!({
+            "columns": [
+                { "name": "price", "dataType": "integer" },
+                { "name": "total", "dataType": "integer", "generated": "price * 2" }
+            ],
+            "constraint": []
+        }));
+
+        let widened = table(json!({
+            "columns": [
+                { "name": "price", "dataType": "integer" },
+                { "name": "total", "dataType": "bigInteger", "generated": "price * 2" }
+            ],
+            "constraint": []
+        }));
+        let statements: Vec<String> = plan_table_update(&old, &widened)
+            .unwrap()
+            .into_iter()
+            .map(|x| x.statement)
+            .collect();
+        assert_eq!(statements, vec![r#"ALTER TABLE "things" ALTER COLUMN "total" TYPE BIGINT;"#.to_string()]);
+
+        let changed = table(json!({
+            "columns": [
+                { "name": "price", "dataType": "integer" },
+                { "name": "total", "dataType": "integer", "generated": "price * 3" }
+            ],
+            "constraint": []
+        }));
+        assert!(plan_table_update(&old, &changed).is_err());
+    }
+
+    #[test]
+    fn test_purge_statement() {
+        let logs = table(json!({
+            "columns": [
+                { "name": "id", "dataType": "integer" },
+                { "name": "created_at", "dataType": { "timestamp": { "withTZ": true } } }
+            ],
+            "constraint": [{ "key": "id" }],
+            "retention": { "column": "created_at", "days": 90 }
+        }));
+        assert_eq!(
+            get_purge_statement(&logs, 500).unwrap(),
+            r#"DELETE FROM "things" WHERE ctid IN (SELECT ctid FROM "things" WHERE "created_at" < NOW() - INTERVAL '90 days' LIMIT 500);"#);
+        assert!(get_purge_statement(&logs, 0).is_err());
+
+        let no_policy = table(json!({
+            "columns": [{ "name": "id", "dataType": "integer" }],
+            "constraint": []
+        }));
+        assert!(get_purge_statement(&no_policy, 500).is_err());
+
+        let not_a_timestamp = table(json!({
+            "columns": [{ "name": "id", "dataType": "integer" }],
+            "constraint": [],
+            "retention": { "column": "id", "days": 90 }
+        }));
+        assert!(check_retention_policy(&not_a_timestamp).is_err());
+
+        let missing_column = table(json!({
+            "columns": [{ "name": "id", "dataType": "integer" }],
+            "constraint": [],
+            "retention": { "column": "created_at", "days": 90 }
+        }));
+        assert!(check_retention_policy(&missing_column).is_err());
+    }
+
+    #[test]
+    fn test_copy_statement() {
+        let source = table(json!({
+            "columns": [
+                { "name": "id", "dataType": "integer" },
+                { "name": "name", "dataType": "string" }
+            ],
+            "constraint": []
+        }));
+        let mut target = source.clone();
+        target.name = "things_copy".to_string();
+
+        assert_eq!(
+            get_copy_statement(&source, &target).unwrap(),
+            r#"INSERT INTO "things_copy" ("id", "name") SELECT "id", "name" FROM "things";"#);
+
+        target.schema.columns.pop();
+        assert!(get_copy_statement(&source, &target).is_err());
+    }
+
+    #[test]
+    fn test_create_view_statement() {
+        let view: View = serde_json::from_value(json!({
+            "name": "big_orders",
+            "description": "",
+            "statement": "SELECT * FROM orders WHERE total > 100; "
+        })).unwrap();
+        assert_eq!(
+            get_create_view_statement(&view).unwrap(),
+            r#"CREATE VIEW "big_orders" AS SELECT * FROM orders WHERE total > 100;"#);
+
+        let empty = View { statement: " ; ".to_string(), ..view.clone() };
+        assert!(get_create_view_statement(&empty).is_err());
+
+        let invalid = View { name: "big orders".to_string(), ..view.clone() };
+        assert!(get_create_view_statement(&invalid).is_err());
+
+        let materialized = View { materialized: true, ..view };
+        assert_eq!(
+            get_create_view_statement(&materialized).unwrap(),
+            r#"CREATE MATERIALIZED VIEW "big_orders" AS SELECT * FROM orders WHERE total > 100;"#);
+        assert_eq!(get_drop_view_statement(&materialized).unwrap(), r#"DROP MATERIALIZED VIEW "big_orders";"#);
+    }
+
+    #[test]
+    fn test_is_widening() {
+        assert!(is_widening(&DataType::SmallInteger, &DataType::BigInteger));
+        assert!(is_widening(&DataType::Json, &DataType::String));
+        assert!(is_widening(&DataType::Timestamp { with_tz: false, precision: Some(3) }, &DataType::Timestamp { with_tz: false, precision: None }));
+        assert!(!is_widening(&DataType::BigInteger, &DataType::Integer));
+        assert!(!is_widening(&DataType::DoubleFloat, &DataType::Float));
+        assert!(!is_widening(&DataType::String, &DataType::VarChar { length: 255 }));
+        assert!(!is_widening(&DataType::Timestamp { with_tz: false, precision: None }, &DataType::Timestamp { with_tz: true, precision: None }));
+    }
+
+    #[test]
+    fn test_sql_literal() {
+        assert_eq!(get_sql_literal(&Value::Float(std::f64::NAN)), "'NaN'::DOUBLE PRECISION");
+        assert_eq!(get_sql_literal(&Value::Float(std::f64::NEG_INFINITY)), "'-Infinity'::DOUBLE PRECISION");
+        assert_eq!(get_sql_literal(&Value::Boolean(true)), "TRUE");
+    }
+}
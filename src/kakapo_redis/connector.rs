@@ -6,6 +6,7 @@ use plugins::v1::DataStoreEntity;
 use plugins::v1::DatastoreError;
 use plugins::v1::DataQuery;
 use plugins::v1::DataQueryEntity;
+use plugins::v1::View;
 
 use kakapo_redis::KakapoRedis;
 use kakapo_redis::data::Keys;
@@ -57,17 +58,20 @@ impl Domain for KakapoRedisDone {
         "REDIS"
     }
 
-    fn connect_datastore(&self) -> Option<Box<Datastore>> {
+    fn connect_datastore(&self) -> Result<Box<Datastore>, DatastoreError> {
         debug!("connecting to the pool for datastore");
         let conn = self.pool.get()
-            .expect("Could not get connection");
+            .map_err(|err| {
+                warn!("Could not get a redis connection: {:?}", &err);
+                DatastoreError::Unavailable
+            })?;
 
         let redis_connection = KakapoRedisConnection { conn };
-        Some(Box::new(redis_connection))
+        Ok(Box::new(redis_connection))
     }
 
-    fn connect_query(&self) -> Option<Box<DataQuery>> {
-        None
+    fn connect_query(&self) -> Result<Box<DataQuery>, DatastoreError> {
+        Err(DatastoreError::NotSupported)
     }
 }
 
@@ -134,10 +138,38 @@ impl Datastore for KakapoRedisConnection {
         unimplemented!()
     }
 
+    fn update_where(&self, data_store: &DataStoreEntity, filtered_values: &serde_json::Value) -> Result<serde_json::Value, DatastoreError> {
+        Err(DatastoreError::NotSupported)
+    }
+
+    fn delete_where(&self, data_store: &DataStoreEntity, filter: &serde_json::Value) -> Result<serde_json::Value, DatastoreError> {
+        Err(DatastoreError::NotSupported)
+    }
+
+    fn delete_all(&self, data_store: &DataStoreEntity) -> Result<serde_json::Value, DatastoreError> {
+        Err(DatastoreError::NotSupported)
+    }
+
+    fn stream(&self, data_store: &DataStoreEntity, query: &serde_json::Value, batch_size: usize, on_batch: &mut FnMut(serde_json::Value) -> Result<(), DatastoreError>) -> Result<(), DatastoreError> {
+        Err(DatastoreError::NotSupported)
+    }
+
     fn on_datastore_created(&self, new: &DataStoreEntity) -> Result<(), DatastoreError> {
         unimplemented!()
     }
 
+    fn preview_datastore_update(&self, old: &DataStoreEntity, new: &DataStoreEntity) -> Result<serde_json::Value, DatastoreError> {
+        Err(DatastoreError::NotSupported)
+    }
+
+    fn copy_datastore_data(&self, source: &DataStoreEntity, target: &DataStoreEntity) -> Result<usize, DatastoreError> {
+        Err(DatastoreError::NotSupported)
+    }
+
+    fn purge_expired_data(&self, table: &DataStoreEntity, batch_size: usize) -> Result<usize, DatastoreError> {
+        Err(DatastoreError::NotSupported)
+    }
+
     fn on_datastore_updated(&self, old: &DataStoreEntity, new: &DataStoreEntity) -> Result<(), DatastoreError> {
         unimplemented!()
     }
@@ -145,4 +177,20 @@ impl Datastore for KakapoRedisConnection {
     fn on_datastore_deleted(&self, old: &DataStoreEntity) -> Result<(), DatastoreError> {
         unimplemented!()
     }
+
+    fn on_view_created(&self, new: &View) -> Result<(), DatastoreError> {
+        Err(DatastoreError::NotSupported)
+    }
+
+    fn on_view_updated(&self, old: &View, new: &View) -> Result<(), DatastoreError> {
+        Err(DatastoreError::NotSupported)
+    }
+
+    fn on_view_deleted(&self, old: &View) -> Result<(), DatastoreError> {
+        Err(DatastoreError::NotSupported)
+    }
+
+    fn refresh_view(&self, view: &View) -> Result<(), DatastoreError> {
+        Err(DatastoreError::NotSupported)
+    }
 }
\ No newline at end of file
@@ -0,0 +1,613 @@
+use std::cell::Cell;
+use std::sync::Arc;
+use std::sync::Condvar;
+use std::sync::Mutex;
+use std::sync::MutexGuard;
+use std::thread;
+use std::thread::ThreadId;
+use std::time::Duration;
+
+use rusqlite::Connection;
+
+use plugins::v1::Domain;
+use plugins::v1::Datastore;
+use plugins::v1::DomainBuilder;
+use plugins::v1::DataStoreEntity;
+use plugins::v1::DatastoreError;
+use plugins::v1::DataQuery;
+use plugins::v1::DataQueryEntity;
+use plugins::v1::QueryLimit;
+use plugins::v1::QueryResult;
+use plugins::v1::View;
+
+use kakapo_postgres::data::Table;
+use kakapo_postgres::data::TableData;
+use kakapo_postgres::data::KeyedTableData;
+use kakapo_postgres::data::KeyData;
+use kakapo_postgres::data::Query;
+use kakapo_postgres::data::RawTableData;
+use kakapo_postgres::data::QueryParams;
+use kakapo_postgres::data::TableQuery;
+use kakapo_postgres::data::PagedTableData;
+use kakapo_postgres::data::FilteredUpdate;
+use kakapo_postgres::data::FilteredDelete;
+use kakapo_postgres::database::DatabaseFunctions;
+use kakapo_postgres::database::error::DbError;
+use kakapo_postgres::query::bind_params;
+use kakapo_postgres::query::limit_statement;
+use kakapo_postgres::query::result_schema;
+use kakapo_postgres::table::CrudTableOps;
+use kakapo_postgres::update_state::UpdateTableOps;
+use kakapo_postgres::update_state::UpdateViewOps;
+
+use kakapo_sqlite::KakapoSqlite;
+use kakapo_sqlite::database::to_datastore_error;
+use kakapo_sqlite::table::SqliteTable;
+use kakapo_sqlite::update_state::UpdateTable;
+use kakapo_sqlite::update_state::UpdateView;
+use kakapo_sqlite::update_state::plan_table_update;
+
+/// how long a statement waits for the lock of the database file, before it fails with `Timeout`
+const BUSY_TIMEOUT_MILLIS: u64 = 5000;
+
+/// sqlite runs in the process and writes one at a time anyway, so there is a single connection
+/// instead of a pool. It's opened once and shared by every executor thread, see `KakapoSqlite::build`,
+/// which also lets all of them see the same `:memory:` database
+#[derive(Clone)]
+pub struct KakapoSqliteDone {
+    conn: Arc<Mutex<Connection>>,
+    transaction: Arc<OpenTransaction>,
+}
+
+/// Since the connection is shared, a transaction is owned by the thread of the request that began it,
+/// the other requests wait for it to be closed before running their statements so that they don't end up in it.
+/// The owner is only changed with the lock of the connection held
+#[derive(Default)]
+struct OpenTransaction {
+    owner: Mutex<Option<ThreadId>>,
+    closed: Condvar,
+}
+
+impl OpenTransaction {
+    fn set_owner(&self, owner: Option<ThreadId>) {
+        *self.owner.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) = owner;
+    }
+
+    /// true if another thread has a transaction open
+    fn is_foreign(&self) -> bool {
+        let owner = *self.owner.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        owner.map(|owner| owner != thread::current().id()).unwrap_or(false)
+    }
+}
+
+pub struct KakapoSqliteConnection {
+    conn: Arc<Mutex<Connection>>,
+    transaction: Arc<OpenTransaction>,
+    /// number of transactions begun with this connection that are still open, the nested ones are savepoints
+    depth: Cell<usize>,
+}
+
+/// Every executor thread builds the domain, they all get the connection opened by the first one
+impl DomainBuilder for KakapoSqlite {
+    fn build(&self) -> Box<Domain> {
+        let mut opened = self.opened
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        let done = opened
+            .get_or_insert_with(|| KakapoSqliteDone::open(&self.path))
+            .to_owned();
+
+        Box::new(done)
+    }
+}
+
+impl Domain for KakapoSqliteDone {
+
+    fn domain_type(&self) -> &'static str {
+        "SQLITE"
+    }
+
+    fn connect_datastore(&self) -> Result<Box<Datastore>, DatastoreError> {
+        Ok(Box::new(self.connect()))
+    }
+
+    fn connect_query(&self) -> Result<Box<DataQuery>, DatastoreError> {
+        Ok(Box::new(self.connect()))
+    }
+}
+
+impl KakapoSqliteDone {
+    fn open(path: &str) -> Self {
+        info!("Initializing sqlite connection to {:?}", path);
+        let conn = Connection::open(path)
+            .expect("Could not open the sqlite database");
+
+        // the references of the tables aren't checked otherwise
+        conn.execute_batch("PRAGMA foreign_keys = ON;")
+            .expect("Could not enable the foreign keys");
+        conn.busy_timeout(Duration::from_millis(BUSY_TIMEOUT_MILLIS))
+            .expect("Could not set the busy timeout");
+
+        Self {
+            conn: Arc::new(Mutex::new(conn)),
+            transaction: Arc::new(OpenTransaction::default()),
+        }
+    }
+
+    fn connect(&self) -> KakapoSqliteConnection {
+        KakapoSqliteConnection {
+            conn: self.conn.clone(),
+            transaction: self.transaction.clone(),
+            depth: Cell::new(0),
+        }
+    }
+}
+
+impl KakapoSqliteConnection {
+    /// a panic while the lock was held doesn't leave the connection in a bad state, sqlite rolls back by itself.
+    /// Waits for the transaction of another request to be closed
+    fn lock(&self) -> MutexGuard<Connection> {
+        let mut conn = self.conn
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        while self.transaction.is_foreign() {
+            conn = self.transaction.closed
+                .wait(conn)
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+        }
+
+        conn
+    }
+
+    /// the transaction is closed when its outermost savepoint is released or rolled back
+    fn close_savepoint<F>(&self, conn: &Connection, command: F) -> Result<(), DatastoreError>
+        where F: FnOnce(&str) -> String
+    {
+        let depth = self.depth.get()
+            .checked_sub(1)
+            .ok_or_else(|| DatastoreError::DbError("no transaction is open".to_string()))?;
+
+        let res = conn
+            .execute_batch(&command(&savepoint_name(depth)))
+            .map_err(to_datastore_error);
+        self.depth.set(depth);
+        if depth == 0 {
+            self.close_transaction(conn);
+        }
+
+        res
+    }
+
+    /// anything still open, e.g. after a failed release, is rolled back before the others can use the connection
+    fn close_transaction(&self, conn: &Connection) {
+        if !conn.is_autocommit() {
+            if let Err(err) = conn.execute_batch("ROLLBACK;") {
+                error!("could not roll back the sqlite transaction: {:?}", &err);
+            }
+        }
+        self.transaction.set_owner(None);
+        self.transaction.closed.notify_all();
+    }
+}
+
+/// the transaction can't stay open if the connection is dropped before it's committed, e.g. on a panic
+impl Drop for KakapoSqliteConnection {
+    fn drop(&mut self) {
+        if self.depth.get() > 0 {
+            // the transaction is this connection's, even if it's dropped on another thread
+            let conn = self.conn
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+            self.depth.set(0);
+            self.close_transaction(&conn);
+        }
+    }
+}
+
+fn savepoint_name(depth: usize) -> String {
+    format!("kakapo_transaction_{}", depth)
+}
+
+fn parse_table_query(query: &serde_json::Value) -> Result<TableQuery, DatastoreError> {
+    if query.is_null() {
+        Ok(TableQuery::default())
+    } else {
+        serde_json::from_value(query.to_owned())
+            .map_err(|_| DatastoreError::SerializationError)
+    }
+}
+
+fn to_json<T: serde::Serialize>(res: T) -> Result<serde_json::Value, DatastoreError> {
+    serde_json::to_value(res)
+        .map_err(|_| DatastoreError::SerializationError)
+}
+
+impl Datastore for KakapoSqliteConnection {
+    fn retrieve(&self, data_store: &DataStoreEntity, query: &serde_json::Value) -> Result<serde_json::Value, DatastoreError> {
+        let table: Result<Table, DatastoreError> = data_store.into();
+        let table = table?;
+
+        let table_query = parse_table_query(query)?;
+
+        let conn = self.lock();
+        let action = SqliteTable::new(&table, &conn);
+
+        let res = action.retrieve(&table_query)?;
+        if table_query.is_paginated() {
+            let total_count = action.count(&table_query)?;
+            let start = table_query.start.unwrap_or(0);
+            let end = start + res.data.len() as i64;
+            to_json(PagedTableData { table_data: res, start, end, total_count })
+        } else {
+            to_json(res)
+        }
+    }
+
+    fn insert(&self, data_store: &DataStoreEntity, rows: &serde_json::Value) -> Result<serde_json::Value, DatastoreError> {
+        let table: Result<Table, DatastoreError> = data_store.into();
+        let table = table?;
+
+        let data: TableData = serde_json::from_value(rows.to_owned())
+            .map_err(|_| DatastoreError::SerializationError)?;
+        let data = data.normalize();
+
+        let conn = self.lock();
+        let res = SqliteTable::new(&table, &conn).insert(data, true)?;
+        to_json(res)
+    }
+
+    fn upsert(&self, data_store: &DataStoreEntity, rows: &serde_json::Value) -> Result<serde_json::Value, DatastoreError> {
+        let table: Result<Table, DatastoreError> = data_store.into();
+        let table = table?;
+
+        let data: TableData = serde_json::from_value(rows.to_owned())
+            .map_err(|_| DatastoreError::SerializationError)?;
+        let data = data.normalize();
+
+        let conn = self.lock();
+        let res = SqliteTable::new(&table, &conn).upsert(data)?;
+        to_json(res)
+    }
+
+    fn update(&self, data_store: &DataStoreEntity, key_values: &serde_json::Value) -> Result<serde_json::Value, DatastoreError> {
+        let table: Result<Table, DatastoreError> = data_store.into();
+        let table = table?;
+
+        let keyed_data: KeyedTableData = serde_json::from_value(key_values.to_owned())
+            .map_err(|_| DatastoreError::SerializationError)?;
+        let (keys, data) = keyed_data.normalize();
+
+        let conn = self.lock();
+        let res = SqliteTable::new(&table, &conn).update(keys, data, false)?; // same as postgres, the missing keys are skipped
+        to_json(res)
+    }
+
+    fn delete(&self, data_store: &DataStoreEntity, keys: &serde_json::Value) -> Result<serde_json::Value, DatastoreError> {
+        let table: Result<Table, DatastoreError> = data_store.into();
+        let table = table?;
+
+        let keys: KeyData = serde_json::from_value(keys.to_owned())
+            .map_err(|_| DatastoreError::SerializationError)?;
+        let keys = keys.normalize();
+
+        let conn = self.lock();
+        let res = SqliteTable::new(&table, &conn).delete(keys, true)?;
+        to_json(res)
+    }
+
+    fn update_where(&self, data_store: &DataStoreEntity, filtered_values: &serde_json::Value) -> Result<serde_json::Value, DatastoreError> {
+        let table: Result<Table, DatastoreError> = data_store.into();
+        let table = table?;
+
+        let filtered_update: FilteredUpdate = serde_json::from_value(filtered_values.to_owned())
+            .map_err(|_| DatastoreError::SerializationError)?;
+
+        let conn = self.lock();
+        let res = SqliteTable::new(&table, &conn).update_where(&filtered_update.filter, filtered_update.values)?;
+        to_json(res)
+    }
+
+    fn delete_where(&self, data_store: &DataStoreEntity, filter: &serde_json::Value) -> Result<serde_json::Value, DatastoreError> {
+        let table: Result<Table, DatastoreError> = data_store.into();
+        let table = table?;
+
+        let filtered_delete: FilteredDelete = serde_json::from_value(filter.to_owned())
+            .map_err(|_| DatastoreError::SerializationError)?;
+
+        let conn = self.lock();
+        let res = SqliteTable::new(&table, &conn).delete_where(&filtered_delete.filter)?;
+        to_json(res)
+    }
+
+    fn delete_all(&self, data_store: &DataStoreEntity) -> Result<serde_json::Value, DatastoreError> {
+        let table: Result<Table, DatastoreError> = data_store.into();
+        let table = table?;
+
+        let conn = self.lock();
+        let res = SqliteTable::new(&table, &conn).delete_all()?;
+        to_json(res)
+    }
+
+    fn stream(&self, data_store: &DataStoreEntity, query: &serde_json::Value, batch_size: usize, on_batch: &mut FnMut(serde_json::Value) -> Result<(), DatastoreError>) -> Result<(), DatastoreError> {
+        let table: Result<Table, DatastoreError> = data_store.into();
+        let table = table?;
+
+        let table_query = parse_table_query(query)?;
+
+        let conn = self.lock();
+        SqliteTable::new(&table, &conn).stream(&table_query, batch_size, &mut |rows| {
+            on_batch(to_json(rows)?)
+        })
+    }
+
+    fn preview_datastore_update(&self, old: &DataStoreEntity, new: &DataStoreEntity) -> Result<serde_json::Value, DatastoreError> {
+        let new: Result<Table, DatastoreError> = new.into();
+        let new = new?;
+
+        let old: Result<Table, DatastoreError> = old.into();
+        let old = old?;
+
+        to_json(plan_table_update(&old, &new)?)
+    }
+
+    fn copy_datastore_data(&self, source: &DataStoreEntity, target: &DataStoreEntity) -> Result<usize, DatastoreError> {
+        let source: Result<Table, DatastoreError> = source.into();
+        let source = source?;
+
+        let target: Result<Table, DatastoreError> = target.into();
+        let target = target?;
+
+        let conn = self.lock();
+        UpdateTable::new(&conn).copy_table_data(&source, &target)
+    }
+
+    fn purge_expired_data(&self, table: &DataStoreEntity, batch_size: usize) -> Result<usize, DatastoreError> {
+        let table: Result<Table, DatastoreError> = table.into();
+        let table = table?;
+
+        let conn = self.lock();
+        UpdateTable::new(&conn).purge_expired_rows(&table, batch_size)
+    }
+
+    fn on_datastore_created(&self, new: &DataStoreEntity) -> Result<(), DatastoreError> {
+        let new: Result<Table, DatastoreError> = new.into();
+        let new = new?;
+
+        let conn = self.lock();
+        UpdateTable::new(&conn).create_table(&new)
+    }
+
+    fn on_datastore_updated(&self, old: &DataStoreEntity, new: &DataStoreEntity) -> Result<(), DatastoreError> {
+        let new: Result<Table, DatastoreError> = new.into();
+        let new = new?;
+
+        let old: Result<Table, DatastoreError> = old.into();
+        let old = old?;
+
+        let conn = self.lock();
+        UpdateTable::new(&conn).update_table(&old, &new)
+    }
+
+    fn on_datastore_deleted(&self, old: &DataStoreEntity) -> Result<(), DatastoreError> {
+        let old: Result<Table, DatastoreError> = old.into();
+        let old = old?;
+
+        let conn = self.lock();
+        UpdateTable::new(&conn).delete_table(&old)
+    }
+
+    fn on_view_created(&self, new: &View) -> Result<(), DatastoreError> {
+        let conn = self.lock();
+        UpdateView::new(&conn).create_view(new)
+    }
+
+    fn on_view_updated(&self, old: &View, new: &View) -> Result<(), DatastoreError> {
+        let conn = self.lock();
+        UpdateView::new(&conn).update_view(old, new)
+    }
+
+    fn on_view_deleted(&self, old: &View) -> Result<(), DatastoreError> {
+        let conn = self.lock();
+        UpdateView::new(&conn).delete_view(old)
+    }
+
+    fn refresh_view(&self, view: &View) -> Result<(), DatastoreError> {
+        let conn = self.lock();
+        UpdateView::new(&conn).refresh_view(view)
+    }
+
+    /// the outermost transaction is a savepoint as well, sqlite begins a transaction for it
+    fn begin_transaction(&self) -> Result<(), DatastoreError> {
+        let conn = self.lock();
+        let depth = self.depth.get();
+        conn.execute_batch(&format!("SAVEPOINT {};", savepoint_name(depth)))
+            .map_err(to_datastore_error)?;
+
+        if depth == 0 {
+            self.transaction.set_owner(Some(thread::current().id()));
+        }
+        self.depth.set(depth + 1);
+        Ok(())
+    }
+
+    fn commit_transaction(&self) -> Result<(), DatastoreError> {
+        let conn = self.lock();
+        self.close_savepoint(&conn, |name| format!("RELEASE {};", name))
+    }
+
+    /// rolling back to a savepoint keeps it open, it's released afterwards
+    fn rollback_transaction(&self) -> Result<(), DatastoreError> {
+        let conn = self.lock();
+        self.close_savepoint(&conn, |name| format!("ROLLBACK TO {0}; RELEASE {0};", name))
+    }
+}
+
+/// The statement timeout of the queries isn't applied, sqlite can only wait for the lock of the file
+impl DataQuery for KakapoSqliteConnection {
+    fn query(&self, query: &DataQueryEntity, query_params: &serde_json::Value, format: &serde_json::Value, limit: &QueryLimit) -> Result<QueryResult, DatastoreError> {
+        let query: Result<Query, DatastoreError> = query.into();
+        let query = query?;
+
+        let query_params: QueryParams = serde_json::from_value(query_params.to_owned())
+            .map_err(|_| DatastoreError::SerializationError)?;
+
+        let db_params = bind_params(&query.params, query_params)?;
+        let limited_statement = limit_statement(&query.statement, limit);
+
+        let mut result = self.lock()
+            .exec(limited_statement.as_ref().unwrap_or(&query.statement), db_params)
+            .map_err(|err| match err {
+                DbError::Timeout => DatastoreError::Timeout,
+                err => DatastoreError::DbError(err.to_string()),
+            })?;
+
+        if limited_statement.is_none() {
+            let skipped = limit.offset.min(result.data.len());
+            result.data.drain(..skipped);
+        }
+        let truncated = result.data.len() > limit.limit;
+        result.data.truncate(limit.limit);
+
+        Ok(QueryResult { data: to_json(result)?, truncated }) //TODO: format
+    }
+
+    /// The steps of `EXPLAIN QUERY PLAN`, sqlite has no json plan
+    fn explain(&self, query: &DataQueryEntity, query_params: &serde_json::Value) -> Result<serde_json::Value, DatastoreError> {
+        let query: Result<Query, DatastoreError> = query.into();
+        let query = query?;
+
+        let query_params: QueryParams = serde_json::from_value(query_params.to_owned())
+            .map_err(|_| DatastoreError::SerializationError)?;
+
+        let db_params = bind_params(&query.params, query_params)?;
+        let statement = format!("EXPLAIN QUERY PLAN {}", query.statement.trim());
+
+        let RawTableData { columns, data } = self.lock()
+            .exec(&statement, db_params)
+            .map_err(|err| DatastoreError::DbError(err.to_string()))?;
+
+        let steps: Vec<serde_json::Value> = data
+            .into_iter()
+            .map(|row| {
+                let step = columns.values
+                    .iter()
+                    .zip(row.values)
+                    .map(|(name, value)| Ok((name.to_owned(), to_json(value)?)))
+                    .collect::<Result<serde_json::Map<String, serde_json::Value>, DatastoreError>>()?;
+                Ok(serde_json::Value::Object(step))
+            })
+            .collect::<Result<_, DatastoreError>>()?;
+
+        Ok(serde_json::Value::Array(steps))
+    }
+
+    fn result_schema(&self, data: &serde_json::Value) -> Result<serde_json::Value, DatastoreError> {
+        let data: RawTableData = serde_json::from_value(data.to_owned())
+            .map_err(|_| DatastoreError::DeserializationError)?;
+
+        to_json(result_schema(&data))
+    }
+
+    /// sqlite runs in the server, there is no other session to cancel the statements from
+    fn backend_id(&self) -> Result<i64, DatastoreError> {
+        Err(DatastoreError::NotSupported)
+    }
+
+    fn cancel(&self, _backend_id: i64) -> Result<bool, DatastoreError> {
+        Err(DatastoreError::NotSupported)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::from_value;
+
+    fn row_count(datastore: &Datastore, table: &DataStoreEntity) -> usize {
+        let res = datastore.retrieve(table, &json!(null)).unwrap();
+        res["data"].as_array().unwrap().len()
+    }
+
+    #[test]
+    fn test_transaction() {
+        let domain = KakapoSqlite::new().build();
+        let datastore = domain.connect_datastore().unwrap();
+        let table: DataStoreEntity = from_value(json!({
+            "name": "orders",
+            "description": "",
+            "schema": {
+                "columns": [ { "name": "id", "dataType": "integer" } ],
+                "constraint": [ { "key": "id" } ]
+            }
+        })).unwrap();
+        datastore.on_datastore_created(&table).unwrap();
+
+        datastore.begin_transaction().unwrap();
+        datastore.insert(&table, &json!([{ "id": 1 }])).unwrap();
+
+        // the inner transaction is a savepoint, only its changes are rolled back
+        datastore.begin_transaction().unwrap();
+        datastore.insert(&table, &json!([{ "id": 2 }])).unwrap();
+        datastore.rollback_transaction().unwrap();
+        assert_eq!(row_count(&*datastore, &table), 1);
+
+        datastore.begin_transaction().unwrap();
+        datastore.insert(&table, &json!([{ "id": 3 }])).unwrap();
+        datastore.commit_transaction().unwrap();
+        datastore.rollback_transaction().unwrap();
+        assert_eq!(row_count(&*datastore, &table), 0);
+
+        datastore.begin_transaction().unwrap();
+        datastore.insert(&table, &json!([{ "id": 4 }])).unwrap();
+        datastore.commit_transaction().unwrap();
+        assert_eq!(row_count(&*datastore, &table), 1);
+
+        assert!(datastore.commit_transaction().is_err());
+
+        // the transaction is rolled back if the connection is dropped before it's committed
+        let other_datastore = domain.connect_datastore().unwrap();
+        other_datastore.begin_transaction().unwrap();
+        other_datastore.insert(&table, &json!([{ "id": 5 }])).unwrap();
+        drop(other_datastore);
+        assert_eq!(row_count(&*datastore, &table), 1);
+    }
+
+    #[test]
+    fn test_shared_by_executors() {
+        let sqlite = KakapoSqlite::new();
+        let table: DataStoreEntity = from_value(json!({
+            "name": "orders",
+            "description": "",
+            "schema": {
+                "columns": [ { "name": "id", "dataType": "integer" } ],
+                "constraint": [ { "key": "id" } ]
+            }
+        })).unwrap();
+
+        // every executor thread builds its own domain, like `Executor::create`
+        let executor = |sqlite: KakapoSqlite, table: DataStoreEntity, id: i64| thread::spawn(move || {
+            let datastore = sqlite.build().connect_datastore().unwrap();
+            if id == 1 {
+                datastore.on_datastore_created(&table).unwrap();
+            }
+            datastore.insert(&table, &json!([{ "id": id }])).unwrap();
+        });
+
+        executor(sqlite.clone(), table.clone(), 1).join().unwrap();
+        executor(sqlite.clone(), table.clone(), 2).join().unwrap();
+
+        let datastore = sqlite.build().connect_datastore().unwrap();
+        assert_eq!(row_count(&*datastore, &table), 2);
+
+        // a transaction of one executor holds back the statements of the others until it's closed
+        datastore.begin_transaction().unwrap();
+        datastore.insert(&table, &json!([{ "id": 3 }])).unwrap();
+        let other = executor(sqlite.clone(), table.clone(), 4);
+        thread::sleep(Duration::from_millis(100));
+        datastore.rollback_transaction().unwrap();
+        other.join().unwrap();
+        assert_eq!(row_count(&*datastore, &table), 3);
+    }
+}
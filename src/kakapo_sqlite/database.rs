@@ -0,0 +1,182 @@
+use rusqlite;
+use rusqlite::Connection;
+use rusqlite::ErrorCode;
+use rusqlite::Row;
+use rusqlite::types::Value as SqliteValue;
+use rusqlite::types::ValueRef;
+
+use kakapo_postgres::data::RawTableData;
+use kakapo_postgres::data::Value;
+use kakapo_postgres::database::DatabaseFunctions;
+use kakapo_postgres::database::error::DbError;
+
+use plugins::v1::DatastoreError;
+
+/// sqlite doesn't allow more parameters than this in one statement, since 3.32
+pub const MAX_QUERY_PARAMS: usize = 32766;
+/// timestamps are kept as text, in the format of sqlite's date functions so that they can be compared with them
+pub const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S%.f";
+pub const DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// The statements are built with the postgres placeholders, `$1` becomes `?1`
+/// the quoted strings and identifiers are left as they are
+pub fn to_sqlite_placeholders(statement: &str) -> String {
+    let mut converted = String::with_capacity(statement.len());
+    let mut quote: Option<char> = None;
+    let mut chars = statement.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match quote {
+            Some(open) => {
+                // a doubled quote closes and opens the string again
+                if c == open {
+                    quote = None;
+                }
+            },
+            None => match c {
+                '\'' | '"' => quote = Some(c),
+                '$' if chars.peek().map(|x| x.is_ascii_digit()).unwrap_or(false) => {
+                    converted.push('?');
+                    continue;
+                },
+                _ => {},
+            },
+        }
+        converted.push(c);
+    }
+
+    converted
+}
+
+/// sqlite only has integers, reals, text and blobs, booleans are 0 or 1 and the rest is text
+pub fn to_sqlite_value(value: Value) -> SqliteValue {
+    match value {
+        Value::Null => SqliteValue::Null,
+        Value::String(x) => SqliteValue::Text(x),
+        Value::Integer(x) => SqliteValue::Integer(x),
+        Value::Float(x) => SqliteValue::Real(x),
+        Value::Boolean(x) => SqliteValue::Integer(if x { 1 } else { 0 }),
+        Value::DateTime(x) => SqliteValue::Text(x.format(TIMESTAMP_FORMAT).to_string()),
+        Value::Date(x) => SqliteValue::Text(x.format(DATE_FORMAT).to_string()),
+        Value::Binary(x) => SqliteValue::Blob(x),
+        Value::Uuid(x) => SqliteValue::Text(x.to_hyphenated().to_string()),
+        Value::Json(x) => SqliteValue::Text(x.to_string()),
+    }
+}
+
+fn from_sqlite_value(value: ValueRef) -> Value {
+    match value {
+        ValueRef::Null => Value::Null,
+        ValueRef::Integer(x) => Value::Integer(x),
+        ValueRef::Real(x) => Value::Float(x),
+        ValueRef::Text(x) => Value::String(String::from_utf8_lossy(x).into_owned()),
+        ValueRef::Blob(x) => Value::Binary(x.to_vec()),
+    }
+}
+
+pub fn read_row(row: &Row, column_count: usize) -> Result<Vec<Value>, rusqlite::Error> {
+    (0..column_count)
+        .map(|i| row.get_ref(i).map(from_sqlite_value))
+        .collect()
+}
+
+pub fn db_error(err: rusqlite::Error) -> DbError {
+    match err {
+        rusqlite::Error::SqliteFailure(ref error, ref message) if error.code == ErrorCode::ConstraintViolation =>
+            DbError::ConstraintError(message.to_owned().unwrap_or_else(|| err.to_string())),
+        // another connection held the lock for longer than the busy timeout
+        rusqlite::Error::SqliteFailure(ref error, _) if error.code == ErrorCode::DatabaseBusy => DbError::Timeout,
+        err => DbError::QueryError(err.to_string()),
+    }
+}
+
+/// for the statements that are run without `exec`
+pub fn to_datastore_error(err: rusqlite::Error) -> DatastoreError {
+    DatastoreError::DbError(db_error(err).to_string())
+}
+
+/// Runs `f` in a savepoint, unlike `BEGIN` it can be nested in the transaction of
+/// `Datastore::begin_transaction`. Rolled back if `f` fails
+pub fn with_savepoint<T, F>(conn: &Connection, name: &str, f: F) -> Result<T, DatastoreError>
+    where F: FnOnce() -> Result<T, DatastoreError>
+{
+    conn.execute_batch(&format!("SAVEPOINT {};", name))
+        .map_err(to_datastore_error)?;
+
+    match f() {
+        Ok(res) => {
+            conn.execute_batch(&format!("RELEASE {};", name))
+                .map_err(to_datastore_error)?;
+            Ok(res)
+        },
+        Err(err) => {
+            // rolling back to a savepoint keeps it open
+            if let Err(rollback_err) = conn.execute_batch(&format!("ROLLBACK TO {0}; RELEASE {0};", name)) {
+                error!("could not roll back the savepoint {}: {:?}", name, &rollback_err);
+            }
+            Err(err)
+        },
+    }
+}
+
+/// The types of the values aren't known from the statement, see `SqliteTable` for the typed rows
+impl DatabaseFunctions for Connection {
+    fn exec(&self, query: &str, params: Vec<Value>) -> Result<RawTableData, DbError> {
+        if query.trim().is_empty() {
+            return Err(DbError::EmptyQuery);
+        }
+
+        let statement = to_sqlite_placeholders(query);
+        let mut prepared = self.prepare(&statement)
+            .map_err(db_error)?;
+        let column_names: Vec<String> = prepared
+            .column_names()
+            .into_iter()
+            .map(|x| x.to_string())
+            .collect();
+
+        let params = params.into_iter().map(to_sqlite_value);
+        let mut rows = prepared.query(rusqlite::params_from_iter(params))
+            .map_err(db_error)?;
+
+        let mut data = vec![];
+        while let Some(row) = rows.next().map_err(db_error)? {
+            data.push(read_row(row, column_names.len()).map_err(db_error)?);
+        }
+
+        Ok(RawTableData::new_and_fill(column_names, data))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_to_sqlite_placeholders() {
+        assert_eq!(
+            to_sqlite_placeholders(r#"UPDATE "t" SET "a" = $1 WHERE "b" = $12 AND "c" = '$3' AND "d" = 'it''s $4'"#),
+            r#"UPDATE "t" SET "a" = ?1 WHERE "b" = ?12 AND "c" = '$3' AND "d" = 'it''s $4'"#);
+        assert_eq!(to_sqlite_placeholders(r#"SELECT "$1", $ FROM "t""#), r#"SELECT "$1", $ FROM "t""#);
+    }
+
+    #[test]
+    fn test_exec() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.exec(r#"CREATE TABLE "t" ("id" INTEGER PRIMARY KEY, "name" TEXT, "data" BLOB);"#, vec![]).unwrap();
+
+        let res = conn.exec(
+            r#"INSERT INTO "t" ("id", "name", "data") VALUES ($1, $2, $3) RETURNING *;"#,
+            vec![Value::Integer(1), Value::String("one".to_string()), Value::Binary(vec![0xDE, 0xAD])]).unwrap();
+        assert_eq!(res.columns.values, vec!["id", "name", "data"]);
+        assert_eq!(res.data[0].values, vec![Value::Integer(1), Value::String("one".to_string()), Value::Binary(vec![0xDE, 0xAD])]);
+
+        let err = conn.exec(r#"INSERT INTO "t" ("id") VALUES ($1);"#, vec![Value::Integer(1)]).unwrap_err();
+        match err {
+            DbError::ConstraintError(_) => {},
+            err => panic!("expected a constraint error, got {:?}", err),
+        }
+
+        assert_eq!(conn.exec(" ", vec![]).unwrap_err(), DbError::EmptyQuery);
+    }
+}
@@ -0,0 +1,33 @@
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use kakapo_sqlite::connector::KakapoSqliteDone;
+
+pub mod connector;
+mod database;
+mod table;
+mod update_state;
+
+/// The tables and queries of a domain in a sqlite file, for small deployments and the tests that
+/// don't have a postgres server. The schema and the data format are the same as `KakapoPostgres`
+#[derive(Clone)]
+pub struct KakapoSqlite {
+    /// `:memory:` keeps the database in memory, it's gone once the server stops
+    pub path: String,
+    /// opened by the first executor that builds the domain, the other ones and the clones share it
+    opened: Arc<Mutex<Option<KakapoSqliteDone>>>,
+}
+
+impl KakapoSqlite {
+    pub fn new() -> Self {
+        Self {
+            path: ":memory:".to_string(),
+            opened: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    pub fn path(mut self, path: &str) -> Self {
+        self.path = path.to_string();
+        self
+    }
+}
@@ -0,0 +1,566 @@
+use std::mem;
+
+use chrono::NaiveDate;
+use chrono::NaiveDateTime;
+use linked_hash_map::LinkedHashMap;
+use rusqlite;
+use rusqlite::Connection;
+
+use kakapo_postgres::data::AggregateFunction;
+use kakapo_postgres::data::DataType;
+use kakapo_postgres::data::Expression;
+use kakapo_postgres::data::ObjectKeys;
+use kakapo_postgres::data::ObjectValues;
+use kakapo_postgres::data::RawTableData;
+use kakapo_postgres::data::Table;
+use kakapo_postgres::data::TableQuery;
+use kakapo_postgres::data::UpsertedTableData;
+use kakapo_postgres::data::Value;
+use kakapo_postgres::database::DatabaseFunctions;
+use kakapo_postgres::methods::quote_column;
+use kakapo_postgres::methods::quote_identifier;
+use kakapo_postgres::table::CrudTableOps;
+use kakapo_postgres::table::batch_rows_with_limit;
+use kakapo_postgres::table::dedup_keys;
+use kakapo_postgres::table::delete_statement;
+use kakapo_postgres::table::split_matched_keys;
+
+use kakapo_sqlite::database::DATE_FORMAT;
+use kakapo_sqlite::database::MAX_QUERY_PARAMS;
+use kakapo_sqlite::database::TIMESTAMP_FORMAT;
+use kakapo_sqlite::database::read_row;
+use kakapo_sqlite::database::to_datastore_error;
+use kakapo_sqlite::database::to_sqlite_placeholders;
+use kakapo_sqlite::database::to_sqlite_value;
+
+use plugins::v1::DatastoreError;
+
+/// Same as `CrudTable`, the rows sqlite returns are turned back into the types of the columns
+pub struct SqliteTable<'a> {
+    conn: &'a Connection,
+    table: &'a Table,
+}
+
+impl<'a> SqliteTable<'a> {
+    pub fn new(table: &'a Table, conn: &'a Connection) -> Self {
+        Self { table, conn }
+    }
+
+    fn exec(&self, statement: &str, params: Vec<Value>) -> Result<RawTableData, DatastoreError> {
+        self.conn
+            .exec(statement, params)
+            .map(|rows| self.with_column_types(rows))
+            .or_else(|err| Err(DatastoreError::DbError(err.to_string())))
+    }
+
+    fn with_column_types(&self, mut rows: RawTableData) -> RawTableData {
+        let data_types: Vec<Option<&DataType>> = rows.columns.values
+            .iter()
+            .map(|name| self.table.schema.columns.iter().find(|x| &x.name == name).map(|x| &x.data_type))
+            .collect();
+
+        for row in rows.data.iter_mut() {
+            let values = mem::replace(&mut row.values, vec![]);
+            row.values = values
+                .into_iter()
+                .zip(&data_types)
+                .map(|(value, data_type)| match data_type {
+                    Some(data_type) => from_column_type(value, data_type),
+                    None => value,
+                })
+                .collect();
+        }
+
+        rows
+    }
+
+    /// select with the filter and grouping applied, without the order and range
+    fn select_statement(&self, query: &TableQuery, params: &mut Vec<Value>) -> Result<String, DatastoreError> {
+        // `sum` and `avg` are cast to a postgres type
+        let unsupported = query.aggregates
+            .iter()
+            .find(|x| x.function == AggregateFunction::Sum || x.function == AggregateFunction::Avg);
+        if let Some(aggregate) = unsupported {
+            return Err(DatastoreError::InvalidQuery(format!("the {:?} aggregate is not supported by sqlite", aggregate.function)));
+        }
+
+        let (select, group_by) = query.select_sql(&self.table.get_column_names())?;
+
+        Ok(format!(
+            r#"SELECT {} FROM {}{}{}"#,
+            select,
+            self.quoted_name()?,
+            self.where_clause(query, params)?,
+            group_by,
+        ))
+    }
+
+    /// full select statement for the query, including the order and range
+    fn retrieve_statement(&self, query: &TableQuery, params: &mut Vec<Value>) -> Result<String, DatastoreError> {
+        let (limit, offset) = query.limit_and_offset()
+            .ok_or_else(|| DatastoreError::InvalidQuery(format!("invalid range {:?} to {:?}", query.start, query.end)))?;
+
+        let mut statement = self.select_statement(query, params)?;
+        let order_by = query.order_by_sql(&query.output_columns(&self.table.get_column_names()))?;
+        statement = format!("{}{}", statement, order_by);
+
+        // sqlite only has an offset after a limit, -1 is no limit
+        params.push(Value::Integer(limit.unwrap_or(-1)));
+        statement = format!("{} LIMIT ${}", statement, params.len());
+        params.push(Value::Integer(offset));
+        statement = format!("{} OFFSET ${}", statement, params.len());
+
+        Ok(statement)
+    }
+
+    fn where_clause(&self, query: &TableQuery, params: &mut Vec<Value>) -> Result<String, DatastoreError> {
+        match &query.filter {
+            Some(filter) => {
+                let condition = filter.to_sql(&self.table.get_column_names(), params)?;
+                Ok(format!(" WHERE {}", condition))
+            },
+            None => Ok("".to_string()),
+        }
+    }
+
+    fn quoted_name(&self) -> Result<String, DatastoreError> {
+        Ok(quote_identifier(&self.table.name)?)
+    }
+
+    /// quotes the columns, they all have to be in the table
+    fn quoted_columns(&self, names: &[String]) -> Result<Vec<String>, DatastoreError> {
+        let table_column_names = self.table.get_column_names();
+        let quoted = names
+            .iter()
+            .map(|name| quote_column(name, &table_column_names))
+            .collect::<Result<Vec<String>, _>>()?;
+
+        Ok(quoted)
+    }
+}
+
+/// The value as it was written, see `to_sqlite_value`, anything that doesn't parse is returned as it is
+fn from_column_type(value: Value, data_type: &DataType) -> Value {
+    match (value, data_type) {
+        (Value::Integer(x), DataType::Boolean) => Value::Boolean(x != 0),
+        (Value::Integer(x), DataType::Float) | (Value::Integer(x), DataType::DoubleFloat) => Value::Float(x as f64),
+        (Value::String(x), DataType::Timestamp { .. }) => match NaiveDateTime::parse_from_str(&x, TIMESTAMP_FORMAT) {
+            Ok(datetime) => Value::DateTime(datetime),
+            Err(_) => Value::String(x),
+        },
+        (Value::String(x), DataType::Date) => match NaiveDate::parse_from_str(&x, DATE_FORMAT) {
+            Ok(date) => Value::Date(date),
+            Err(_) => Value::String(x),
+        },
+        (Value::String(x), DataType::Uuid { .. }) => match uuid::Uuid::parse_str(&x) {
+            Ok(uuid) => Value::Uuid(uuid),
+            Err(_) => Value::String(x),
+        },
+        (Value::String(x), DataType::Json) => match serde_json::from_str(&x) {
+            Ok(json) => Value::Json(json),
+            Err(_) => Value::String(x),
+        },
+        (value, _) => value,
+    }
+}
+
+impl<'a> CrudTableOps for SqliteTable<'a> {
+    fn retrieve(&self, query: &TableQuery) -> Result<RawTableData, DatastoreError> {
+        let mut params = vec![];
+        let statement = self.retrieve_statement(query, &mut params)?;
+
+        self.exec(&statement, params)
+    }
+
+    fn count(&self, query: &TableQuery) -> Result<i64, DatastoreError> {
+        let mut params = vec![];
+        let statement = format!(r#"SELECT COUNT(*) FROM ({}) AS "rows""#, self.select_statement(query, &mut params)?);
+        let res = self.exec(&statement, params)?;
+
+        match res.data.first().and_then(|row| row.values.first()) {
+            Some(Value::Integer(count)) => Ok(*count),
+            _ => {
+                error!("could not get the row count from {:?}", &res);
+                Err(DatastoreError::Unknown)
+            },
+        }
+    }
+
+    /// The statement is stepped through instead of using a cursor, sqlite reads the rows as they are requested
+    fn stream(&self, query: &TableQuery, batch_size: usize, on_batch: &mut FnMut(RawTableData) -> Result<(), DatastoreError>) -> Result<(), DatastoreError> {
+        if batch_size == 0 {
+            return Err(DatastoreError::InvalidQuery("batch size must be positive".to_string()));
+        }
+
+        let mut params = vec![];
+        let statement = to_sqlite_placeholders(&self.retrieve_statement(query, &mut params)?);
+        let mut prepared = self.conn.prepare(&statement)
+            .map_err(to_datastore_error)?;
+        let column_names: Vec<String> = prepared
+            .column_names()
+            .into_iter()
+            .map(|x| x.to_string())
+            .collect();
+
+        let params = params.into_iter().map(to_sqlite_value);
+        let mut rows = prepared.query(rusqlite::params_from_iter(params))
+            .map_err(to_datastore_error)?;
+
+        let mut batch = vec![];
+        while let Some(row) = rows.next().map_err(to_datastore_error)? {
+            batch.push(read_row(row, column_names.len()).map_err(to_datastore_error)?);
+
+            if batch.len() == batch_size {
+                let batch = mem::replace(&mut batch, vec![]);
+                on_batch(self.with_column_types(RawTableData::new_and_fill(column_names.to_owned(), batch)))?;
+            }
+        }
+
+        if !batch.is_empty() {
+            on_batch(self.with_column_types(RawTableData::new_and_fill(column_names, batch)))?;
+        }
+
+        Ok(())
+    }
+
+    fn insert(&self, data: ObjectValues, fail_on_duplicate: bool) -> Result<RawTableData, DatastoreError> {
+        let table_column_names = self.table.get_column_names();
+        let raw_data = data.as_list();
+        let mut results = RawTableData::new(vec![], table_column_names.to_owned());
+
+        for batch in batch_rows_with_limit(raw_data, MAX_QUERY_PARAMS) {
+            let sql_column_names: Vec<String> = batch[0].keys().map(|x| x.to_owned()).collect();
+            self.table.check_writable(&sql_column_names)?;
+            let sql_column_names = self.quoted_columns(&sql_column_names)?;
+            let mut values: Vec<Value> = vec![];
+            let rows_params: Vec<String> = batch.into_iter()
+                .map(|row| {
+                    let column_counts: Vec<String> = row.into_iter()
+                        .map(|(_, value)| {
+                            values.push(value);
+                            format!("${}", values.len())
+                        })
+                        .collect();
+                    format!("({})", column_counts.join(", "))
+                })
+                .collect();
+
+            let query = format!(
+                r#"INSERT INTO {name} ({columns}) VALUES {rows}{on_conflict} RETURNING *;"#,
+                name=self.quoted_name()?,
+                columns=sql_column_names.join(", "),
+                rows=rows_params.join(", "),
+                on_conflict=if fail_on_duplicate { "" } else { " ON CONFLICT DO NOTHING" },
+            );
+
+            let new_rows = self.exec(&query, values)?;
+            results.append(new_rows)
+                .or_else(|_| {
+                    error!("columns names are mismatched");
+                    Err(DatastoreError::Unknown)
+                })?;
+        }
+
+        Ok(results)
+    }
+
+    /// sqlite can't tell if the upsert inserted the row, so the key is looked up first
+    /// nothing else writes in between, the connection is locked for the whole call
+    fn upsert(&self, data: ObjectValues) -> Result<UpsertedTableData, DatastoreError> {
+        let table_column_names = self.table.get_column_names();
+        let key_column_names = self.table.get_key_column_names();
+        if key_column_names.is_empty() {
+            return Err(DatastoreError::InvalidQuery(format!("table {} has no key to upsert on", &self.table.name)));
+        }
+        let quoted_key_column_names = self.quoted_columns(&key_column_names)?;
+
+        let raw_data = data.as_list();
+        let mut results = UpsertedTableData {
+            inserted: RawTableData::new(vec![], table_column_names.to_owned()),
+            updated: RawTableData::new(vec![], table_column_names.to_owned()),
+        };
+
+        for row in raw_data {
+            let sql_column_names: Vec<String> = row.keys().map(|x| x.to_owned()).collect();
+            self.table.check_writable(&sql_column_names)?;
+            let sql_column_names = self.quoted_columns(&sql_column_names)?;
+
+            let key_values = key_column_names
+                .iter()
+                .map(|name| row.get(name).cloned()
+                    .ok_or_else(|| DatastoreError::InvalidQuery(format!("the key {} is missing from the row to upsert", name))))
+                .collect::<Result<Vec<Value>, DatastoreError>>()?;
+            let exists_query = format!(
+                "SELECT COUNT(*) FROM {name} WHERE {keys};",
+                name=self.quoted_name()?,
+                keys=quoted_key_column_names.iter().enumerate()
+                    .map(|(i, x)| format!("{} = ${}", x, i+1))
+                    .collect::<Vec<String>>()
+                    .join(" AND "),
+            );
+            let exists = match self.exec(&exists_query, key_values)?.data.first().and_then(|row| row.values.first()) {
+                Some(Value::Integer(count)) => *count > 0,
+                _ => Err(DatastoreError::Unknown)?,
+            };
+
+            let column_counts: Vec<String> = sql_column_names.iter().enumerate()
+                .map(|(i, _)| format!("${}", i+1))
+                .collect();
+            let values = row.values().map(|x| x.to_owned()).collect();
+            let query = format!(
+                r#"INSERT INTO {name} ({columns}) VALUES ({params}) ON CONFLICT ({keys}) DO UPDATE SET {sets} RETURNING *;"#,
+                name=self.quoted_name()?,
+                columns=sql_column_names.join(", "),
+                params=column_counts.join(", "),
+                keys=quoted_key_column_names.join(", "),
+                sets=sql_column_names.iter()
+                    .map(|x| format!("{col} = excluded.{col}", col=x))
+                    .collect::<Vec<String>>()
+                    .join(", "),
+            );
+
+            let new_row = self.exec(&query, values)?;
+            let res = if exists {
+                results.updated.append(new_row)
+            } else {
+                results.inserted.append(new_row)
+            };
+            res.or_else(|_| {
+                error!("columns names are mismatched");
+                Err(DatastoreError::Unknown)
+            })?;
+        }
+
+        Ok(results)
+    }
+
+    fn update(&self, keys: ObjectKeys, data: ObjectValues, fail_on_not_found: bool) -> Result<RawTableData, DatastoreError> {
+        let table_column_names = self.table.get_column_names();
+        let raw_keys = keys.as_list();
+        let raw_data = data.as_list();
+        let mut results = RawTableData::new(vec![], table_column_names.to_owned());
+
+        for (key, row) in raw_keys.iter().zip(raw_data) {
+            let column_names: Vec<String> = row.keys().map(|x| x.to_owned()).collect();
+            self.table.check_writable(&column_names)?;
+            let column_names = self.quoted_columns(&column_names)?;
+            let key_names: Vec<String> = key.keys().map(|x| x.to_owned()).collect();
+            let key_names = self.quoted_columns(&key_names)?;
+
+            let mut values: Vec<Value> = row.values().map(|x| x.to_owned()).collect();
+            let key_values: Vec<Value> = key.values().map(|x| x.to_owned().into_value()).collect();
+            values.extend(key_values);
+
+            let val_index = 1;
+            let key_index = column_names.len() + 1;
+
+            let query = format!(
+                "UPDATE {name} SET {sets} WHERE {id} RETURNING *",
+                name=self.quoted_name()?,
+                sets=column_names.iter().enumerate()
+                    .map(|(i, x)| format!("{} = ${}", x, i+val_index))
+                    .collect::<Vec<String>>()
+                    .join(","),
+                id=key_names.iter().enumerate()
+                    .map(|(i, x)| format!("{} = ${}", x, i+key_index))
+                    .collect::<Vec<String>>()
+                    .join(" AND "),
+            );
+
+            let new_row = self.exec(&query, values)?;
+            if fail_on_not_found && new_row.data.is_empty() {
+                return Err(DatastoreError::RowsNotFound(1));
+            }
+
+            results.append(new_row)
+                .or_else(|_| {
+                    error!("columns names are mismatched");
+                    Err(DatastoreError::Unknown)
+                })?;
+        }
+
+        Ok(results)
+    }
+
+    fn delete(&self, keys: ObjectKeys, fail_on_not_found: bool) -> Result<RawTableData, DatastoreError> {
+        let table_column_names = self.table.get_column_names();
+        let raw_keys: Vec<LinkedHashMap<String, Value>> = keys
+            .as_list()
+            .into_iter()
+            .map(|key| key.into_iter().map(|(name, value)| (name, value.into_value())).collect())
+            .collect();
+        let mut results = RawTableData::new(vec![], table_column_names.to_owned());
+
+        for batch in batch_rows_with_limit(dedup_keys(raw_keys), MAX_QUERY_PARAMS) {
+            let num_keys = batch.len();
+            let (query, values) = delete_statement(self.table, batch)?;
+
+            let deleted_rows = self.exec(&query, values)?;
+
+            let (deleted_rows, num_missing) = split_matched_keys(deleted_rows, num_keys)?;
+            if fail_on_not_found && num_missing > 0 {
+                return Err(DatastoreError::RowsNotFound(num_missing));
+            }
+
+            results.append(deleted_rows)
+                .or_else(|_| {
+                    error!("columns names are mismatched");
+                    Err(DatastoreError::Unknown)
+                })?;
+        }
+
+        Ok(results)
+    }
+
+    fn update_where(&self, filter: &Expression, data: LinkedHashMap<String, Value>) -> Result<RawTableData, DatastoreError> {
+        if data.is_empty() {
+            return Err(DatastoreError::InvalidQuery("no values to update".to_string()));
+        }
+
+        let updated_column_names: Vec<String> = data.keys().map(|x| x.to_owned()).collect();
+        self.table.check_writable(&updated_column_names)?;
+
+        let table_column_names = self.table.get_column_names();
+        let mut params: Vec<Value> = vec![];
+        let sets = data
+            .into_iter()
+            .map(|(column, value)| {
+                let column = quote_column(&column, &table_column_names)?;
+                params.push(value);
+                Ok(format!("{} = ${}", column, params.len()))
+            })
+            .collect::<Result<Vec<String>, DatastoreError>>()?;
+        let condition = filter.to_sql(&table_column_names, &mut params)?;
+
+        let query = format!(
+            "UPDATE {name} SET {sets} WHERE {condition} RETURNING *;",
+            name=self.quoted_name()?,
+            sets=sets.join(", "),
+            condition=condition,
+        );
+
+        self.exec(&query, params)
+    }
+
+    fn delete_where(&self, filter: &Expression) -> Result<RawTableData, DatastoreError> {
+        let mut params: Vec<Value> = vec![];
+        let condition = filter.to_sql(&self.table.get_column_names(), &mut params)?;
+
+        let query = format!(
+            "DELETE FROM {name} WHERE {condition} RETURNING *;",
+            name=self.quoted_name()?,
+            condition=condition,
+        );
+
+        self.exec(&query, params)
+    }
+
+    fn delete_all(&self) -> Result<RawTableData, DatastoreError> {
+        let query = format!("DELETE FROM {name} RETURNING *;", name=self.quoted_name()?);
+
+        self.exec(&query, vec![])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use kakapo_postgres::data::Column;
+    use kakapo_postgres::data::Constraint;
+    use kakapo_postgres::data::IndexableValue;
+    use kakapo_postgres::data::SchemaState;
+
+    fn test_table() -> Table {
+        let column = |name: &str, data_type: DataType| Column {
+            name: name.to_string(),
+            data_type,
+            default: None,
+            nullable: true,
+            renamed_from: None,
+            generated: None,
+        };
+
+        Table {
+            name: "orders".to_string(),
+            description: "".to_string(),
+            schema: SchemaState {
+                columns: vec![
+                    column("order_id", DataType::Integer),
+                    column("paid", DataType::Boolean),
+                    column("created_at", DataType::Timestamp { with_tz: false, precision: None }),
+                ],
+                constraint: vec![Constraint::Key("order_id".to_string())],
+                retention: None,
+            },
+            scope: None,
+        }
+    }
+
+    fn row(order_id: i64, paid: bool) -> LinkedHashMap<String, Value> {
+        let created_at = NaiveDate::from_ymd(2019, 04, 20).and_hms(16, 20, 00);
+        vec![
+            ("order_id".to_string(), Value::Integer(order_id)),
+            ("paid".to_string(), Value::Boolean(paid)),
+            ("created_at".to_string(), Value::DateTime(created_at)),
+        ].into_iter().collect()
+    }
+
+    #[test]
+    fn test_insert_and_retrieve() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(r#"CREATE TABLE "orders" ("order_id" INTEGER, "paid" INTEGER, "created_at" TEXT, PRIMARY KEY ("order_id"));"#).unwrap();
+
+        let table = test_table();
+        let action = SqliteTable::new(&table, &conn);
+
+        let inserted = action.insert(ObjectValues::new(vec![row(1, false), row(2, true)]), true).unwrap();
+        assert_eq!(inserted.data.len(), 2);
+
+        let upserted = action.upsert(ObjectValues::new(vec![row(2, false), row(3, true)])).unwrap();
+        assert_eq!(upserted.updated.data.len(), 1);
+        assert_eq!(upserted.inserted.data.len(), 1);
+
+        let rows = action.retrieve(&TableQuery::default()).unwrap();
+        assert_eq!(rows.data.len(), 3);
+        assert_eq!(rows.data[1].values, row(2, false).values().cloned().collect::<Vec<Value>>());
+
+        let query = TableQuery { start: Some(1), ..TableQuery::default() };
+        assert_eq!(action.retrieve(&query).unwrap().data.len(), 2);
+        assert_eq!(action.count(&TableQuery::default()).unwrap(), 3);
+
+        let mut batches = vec![];
+        action.stream(&TableQuery::default(), 2, &mut |rows| {
+            batches.push(rows.data.len());
+            Ok(())
+        }).unwrap();
+        assert_eq!(batches, vec![2, 1]);
+    }
+
+    #[test]
+    fn test_delete_checks_each_key() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(r#"CREATE TABLE "orders" ("order_id" INTEGER, "paid" INTEGER, "created_at" TEXT);"#).unwrap();
+
+        let table = test_table();
+        let action = SqliteTable::new(&table, &conn);
+        action.insert(ObjectValues::new(vec![row(1, false), row(1, true), row(2, false)]), true).unwrap();
+
+        let order_id = |id: i64| vec![("order_id".to_string(), IndexableValue::Integer(id))].into_iter().collect();
+
+        // the repeated key isn't missing
+        let keys = ObjectKeys::new(vec![order_id(1), order_id(2), order_id(1)]);
+        let deleted = action.delete(keys, true).unwrap();
+        assert_eq!(deleted.data.len(), 3);
+        assert_eq!(deleted.columns.values, table.get_column_names());
+        assert_eq!(action.count(&TableQuery::default()).unwrap(), 0);
+
+        // the two rows of the first key don't make up for the missing second key
+        action.insert(ObjectValues::new(vec![row(1, false), row(1, true)]), true).unwrap();
+        let keys = ObjectKeys::new(vec![order_id(1), order_id(3)]);
+        match action.delete(keys, true) {
+            Err(DatastoreError::RowsNotFound(1)) => (),
+            res => panic!("expected one missing key, got {:?}", res),
+        }
+    }
+}
@@ -0,0 +1,467 @@
+use rusqlite::Connection;
+
+use kakapo_postgres::data::Column;
+use kakapo_postgres::data::Constraint;
+use kakapo_postgres::data::DataType;
+use kakapo_postgres::data::Table;
+use kakapo_postgres::data::Value;
+use kakapo_postgres::methods::quote_column;
+use kakapo_postgres::methods::quote_identifier;
+use kakapo_postgres::update_state::SchemaChangeStep;
+use kakapo_postgres::update_state::UpdateTableOps;
+use kakapo_postgres::update_state::UpdateViewOps;
+use kakapo_postgres::update_state::check_retention_policy;
+use kakapo_postgres::update_state::constraint_definitions;
+use kakapo_postgres::update_state::find_old_column;
+use kakapo_postgres::update_state::get_copy_statement;
+use kakapo_postgres::update_state::get_view_statement;
+
+use kakapo_sqlite::database::DATE_FORMAT;
+use kakapo_sqlite::database::TIMESTAMP_FORMAT;
+use kakapo_sqlite::database::to_datastore_error;
+use kakapo_sqlite::database::with_savepoint;
+
+use plugins::v1::DatastoreError;
+use plugins::v1::View;
+
+/// a random version 4 uuid, sqlite doesn't have a function for it
+const RANDOM_UUID: &str = "lower(hex(randomblob(4)) || '-' || hex(randomblob(2)) || '-4' || substr(hex(randomblob(2)), 2) || '-' || \
+    substr('89ab', 1 + (abs(random()) % 4), 1) || substr(hex(randomblob(2)), 2) || '-' || hex(randomblob(6)))";
+
+/// The declared type only sets the affinity of the column in sqlite, the values are kept as
+/// `to_sqlite_value` writes them, so the dates, uuids and json are text
+fn get_sql_data_type(data_type: &DataType) -> String {
+    match data_type {
+        DataType::SmallInteger => format!("INTEGER"),
+        DataType::Integer => format!("INTEGER"),
+        DataType::BigInteger => format!("INTEGER"),
+        DataType::Float => format!("REAL"),
+        DataType::DoubleFloat => format!("REAL"),
+
+        DataType::String => format!("TEXT"),
+        DataType::VarChar { .. } => format!("TEXT"),
+
+        DataType::Byte => format!("BLOB"),
+
+        DataType::Timestamp { .. } => format!("TEXT"),
+        DataType::Date => format!("TEXT"),
+        DataType::Time { .. } => format!("TEXT"),
+
+        DataType::Boolean => format!("INTEGER"),
+
+        DataType::Json => format!("TEXT"),
+
+        DataType::Uuid { .. } => format!("TEXT"),
+    }
+}
+
+/// Renders a value as a sql literal for the column defaults, in the format `to_sqlite_value` uses
+fn get_sql_literal(value: &Value) -> String {
+    let quote = |text: &str| format!("'{}'", text.replace('\'', "''"));
+
+    match value {
+        Value::Null => format!("NULL"),
+        Value::String(x) => quote(x),
+        Value::Integer(x) => format!("{}", x),
+        Value::Float(x) if x.is_finite() => format!("{:?}", x),
+        // sqlite turns a NaN into a null, and reads the overflowing reals as infinity
+        Value::Float(x) if x.is_nan() => format!("NULL"),
+        Value::Float(x) => format!("{}9e999", if x.is_sign_negative() { "-" } else { "" }),
+        Value::Boolean(x) => if *x { format!("1") } else { format!("0") },
+        Value::DateTime(x) => quote(&x.format(TIMESTAMP_FORMAT).to_string()),
+        Value::Date(x) => quote(&x.format(DATE_FORMAT).to_string()),
+        Value::Binary(x) => {
+            let hex: String = x.iter().map(|byte| format!("{:02x}", byte)).collect();
+            format!("X'{}'", hex)
+        },
+        Value::Uuid(x) => quote(&x.to_hyphenated().to_string()),
+        Value::Json(x) => quote(&x.to_string()),
+    }
+}
+
+/// an explicit default takes precedence over the generated uuid, generated columns have no default
+fn get_column_default(column: &Column) -> Option<String> {
+    if column.is_generated() {
+        return None;
+    }
+
+    match (&column.default, &column.data_type) {
+        (Some(default), _) => Some(get_sql_literal(default)),
+        (None, DataType::Uuid { generate: true }) => Some(format!("({})", RANDOM_UUID)),
+        (None, _) => None,
+    }
+}
+
+fn get_column_definition(column: &Column) -> Result<String, DatastoreError> {
+    let mut definition = format!("{} {}", quote_identifier(&column.name)?, get_sql_data_type(&column.data_type));
+
+    if !column.nullable {
+        definition.push_str(" NOT NULL");
+    }
+
+    if let Some(default) = get_column_default(column) {
+        definition.push_str(&format!(" DEFAULT {}", default));
+    }
+
+    if let Some(expression) = &column.generated {
+        if column.default.is_some() {
+            Err(DatastoreError::InvalidQuery(format!("generated column {} can't have a default", &column.name)))?;
+        }
+        definition.push_str(&format!(" GENERATED ALWAYS AS ({}) STORED", expression));
+    }
+
+    Ok(definition)
+}
+
+/// Same as the postgres plan, but sqlite can only rename the table and rename, drop and add
+/// columns. The other changes would need the table to be created again and the rows copied over
+pub fn plan_table_update(old: &Table, new: &Table) -> Result<Vec<SchemaChangeStep>, DatastoreError> {
+    if new.schema.columns.len() == 0 {
+        Err(DatastoreError::NoColumns)?;
+    }
+
+    check_retention_policy(new)?;
+
+    let not_supported = |change: String| DatastoreError::InvalidQuery(format!("{} is not supported by sqlite", change));
+    let table_name = quote_identifier(&new.name)?;
+    let mut steps = vec![];
+
+    if old.scope != new.scope {
+        Err(not_supported("moving the table to another scope".to_string()))?;
+    }
+
+    if old.name != new.name {
+        steps.push(SchemaChangeStep {
+            statement: format!("ALTER TABLE {} RENAME TO {};", quote_identifier(&old.name)?, &table_name),
+            destructive: false,
+            description: format!("rename table {} to {}", &old.name, &new.name),
+        });
+    }
+
+    // old name -> new name
+    let mut renames = vec![];
+    for new_column in &new.schema.columns {
+        let old_column = match find_old_column(old, new_column) {
+            Some(old_column) if old_column.name != new_column.name => old_column,
+            _ => continue,
+        };
+
+        if renames.iter().any(|(old_name, _)| old_name == &old_column.name) {
+            Err(DatastoreError::InvalidQuery(format!("column {} is renamed more than once", &old_column.name)))?;
+        }
+        renames.push((old_column.name.to_owned(), new_column.name.to_owned()));
+        steps.push(SchemaChangeStep::new(
+            &table_name,
+            format!("RENAME COLUMN {} TO {}", quote_identifier(&old_column.name)?, quote_identifier(&new_column.name)?),
+            false,
+            format!("rename column {} to {}", &old_column.name, &new_column.name)));
+    }
+
+    for old_column in &old.schema.columns {
+        let is_kept = new.schema.columns
+            .iter()
+            .any(|new_column| find_old_column(old, new_column).map(|x| x.name == old_column.name).unwrap_or(false));
+        if !is_kept {
+            steps.push(SchemaChangeStep::new(
+                &table_name,
+                format!("DROP COLUMN {}", quote_identifier(&old_column.name)?),
+                true,
+                format!("drop column {}", &old_column.name)));
+        }
+    }
+
+    for new_column in &new.schema.columns {
+        let old_column = match find_old_column(old, new_column) {
+            Some(old_column) => old_column,
+            None => {
+                steps.push(SchemaChangeStep::new(
+                    &table_name,
+                    format!("ADD COLUMN {}", get_column_definition(new_column)?),
+                    false,
+                    format!("add column {}", &new_column.name)));
+                continue;
+            },
+        };
+
+        if old_column.generated != new_column.generated {
+            Err(DatastoreError::InvalidQuery(format!("the generated expression of column {} can't be changed", &new_column.name)))?;
+        }
+
+        // the types that are kept the same way need no change, e.g. a timestamp turned into text
+        if get_sql_data_type(&old_column.data_type) != get_sql_data_type(&new_column.data_type) {
+            Err(not_supported(format!("changing the type of column {}", &new_column.name)))?;
+        }
+
+        if old_column.nullable != new_column.nullable {
+            Err(not_supported(format!("changing whether column {} allows nulls", &new_column.name)))?;
+        }
+
+        if get_column_default(old_column) != get_column_default(new_column) {
+            Err(not_supported(format!("changing the default of column {}", &new_column.name)))?;
+        }
+    }
+
+    let rename = |name: &str| renames
+        .iter()
+        .find(|(old_name, _)| old_name == name)
+        .map(|(_, new_name)| new_name.to_owned())
+        .unwrap_or_else(|| name.to_owned());
+    let old_constraints: Vec<Constraint> = old.schema.constraint
+        .iter()
+        .map(|x| x.with_renamed_columns(&rename))
+        .collect();
+
+    let is_changed = old_constraints.len() != new.schema.constraint.len()
+        || old_constraints.iter().any(|x| !new.schema.constraint.contains(x));
+    if is_changed {
+        Err(not_supported(format!("changing the constraints of table {}", &new.name)))?;
+    }
+
+    Ok(steps)
+}
+
+/// Deletes a batch of the expired rows, the cutoff is computed by sqlite in the format of the stored values
+fn get_purge_statement(table: &Table, batch_size: usize) -> Result<String, DatastoreError> {
+    check_retention_policy(table)?;
+    let retention = table.schema.retention
+        .as_ref()
+        .ok_or_else(|| DatastoreError::InvalidQuery(format!("table {} has no retention policy", &table.name)))?;
+
+    if batch_size == 0 {
+        Err(DatastoreError::InvalidQuery("batch size must be positive".to_string()))?;
+    }
+
+    let cutoff_function = match table.schema.columns.iter().find(|x| x.name == retention.column).map(|x| &x.data_type) {
+        Some(DataType::Date) => "date",
+        _ => "datetime",
+    };
+
+    Ok(format!(
+        "DELETE FROM {name} WHERE rowid IN (SELECT rowid FROM {name} WHERE {column} < {function}('now', '-{days} days') LIMIT {limit});",
+        name=quote_identifier(&table.name)?,
+        column=quote_column(&retention.column, &table.schema.get_column_names())?,
+        function=cutoff_function,
+        days=retention.days,
+        limit=batch_size,
+    ))
+}
+
+pub struct UpdateTable<'a> {
+    conn: &'a Connection,
+}
+
+impl<'a> UpdateTable<'a> {
+    pub fn new(conn: &'a Connection) -> Self {
+        Self { conn }
+    }
+}
+
+impl<'a> UpdateTableOps for UpdateTable<'a> {
+    fn create_table(&self, new: &Table) -> Result<(), DatastoreError> {
+        let schema = &new.schema;
+        let columns = &schema.columns;
+
+        if columns.len() == 0 {
+            Err(DatastoreError::NoColumns)?;
+        }
+
+        check_retention_policy(new)?;
+
+        // sqlite has no schemas, the table would be in the same namespace as the others
+        if new.scope.is_some() {
+            Err(DatastoreError::InvalidQuery("scopes are not supported by sqlite".to_string()))?;
+        }
+
+        let mut definitions = columns
+            .iter()
+            .map(get_column_definition)
+            .collect::<Result<Vec<String>, DatastoreError>>()?;
+        definitions.extend(constraint_definitions(&schema.constraint, &schema.get_column_names(), get_sql_literal)?);
+
+        let command = format!("CREATE TABLE {} ({});", quote_identifier(&new.name)?, definitions.join(", "));
+        info!("DSL command: `{}`", &command);
+
+        self.conn
+            .execute_batch(&command)
+            .map_err(to_datastore_error)
+    }
+
+    fn update_table(&self, old: &Table, new: &Table) -> Result<(), DatastoreError> {
+        let steps = plan_table_update(old, new)?;
+
+        // the schema changes are transactional in sqlite as well
+        with_savepoint(self.conn, "update_table", || {
+            for step in &steps {
+                info!("DSL command: `{}`", &step.statement);
+                self.conn
+                    .execute_batch(&step.statement)
+                    .map_err(to_datastore_error)?;
+            }
+            Ok(())
+        })
+    }
+
+    fn delete_table(&self, old: &Table) -> Result<(), DatastoreError> {
+        let command = format!("DROP TABLE {};", quote_identifier(&old.name)?);
+        self.conn
+            .execute_batch(&command)
+            .map_err(to_datastore_error)
+    }
+
+    fn copy_table_data(&self, source: &Table, target: &Table) -> Result<usize, DatastoreError> {
+        let command = get_copy_statement(source, target)?;
+        info!("DSL command: `{}`", &command);
+
+        self.conn
+            .execute(&command, [])
+            .map_err(to_datastore_error)
+    }
+
+    fn purge_expired_rows(&self, table: &Table, batch_size: usize) -> Result<usize, DatastoreError> {
+        let command = get_purge_statement(table, batch_size)?;
+        debug!("DSL command: `{}`", &command);
+
+        self.conn
+            .execute(&command, [])
+            .map_err(to_datastore_error)
+    }
+}
+
+pub struct UpdateView<'a> {
+    conn: &'a Connection,
+}
+
+impl<'a> UpdateView<'a> {
+    pub fn new(conn: &'a Connection) -> Self {
+        Self { conn }
+    }
+
+    fn execute(&self, commands: Vec<String>) -> Result<(), DatastoreError> {
+        with_savepoint(self.conn, "update_view", || {
+            for command in commands {
+                info!("DSL command: `{}`", &command);
+                self.conn
+                    .execute_batch(&command)
+                    .map_err(to_datastore_error)?;
+            }
+            Ok(())
+        })
+    }
+}
+
+/// sqlite doesn't have materialized views
+fn get_create_view_statement(view: &View) -> Result<String, DatastoreError> {
+    if view.materialized {
+        Err(DatastoreError::InvalidQuery(format!("materialized views are not supported by sqlite")))?;
+    }
+
+    Ok(format!("CREATE VIEW {} AS {};", quote_identifier(&view.name)?, get_view_statement(view)?))
+}
+
+impl<'a> UpdateViewOps for UpdateView<'a> {
+    fn create_view(&self, new: &View) -> Result<(), DatastoreError> {
+        self.execute(vec![get_create_view_statement(new)?])
+    }
+
+    fn update_view(&self, old: &View, new: &View) -> Result<(), DatastoreError> {
+        self.execute(vec![
+            format!("DROP VIEW {};", quote_identifier(&old.name)?),
+            get_create_view_statement(new)?,
+        ])
+    }
+
+    fn delete_view(&self, old: &View) -> Result<(), DatastoreError> {
+        self.execute(vec![format!("DROP VIEW {};", quote_identifier(&old.name)?)])
+    }
+
+    fn refresh_view(&self, view: &View) -> Result<(), DatastoreError> {
+        Err(DatastoreError::InvalidQuery(format!("{} is not a materialized view", &view.name)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use kakapo_postgres::data::SchemaState;
+
+    fn column(name: &str, data_type: DataType, default: Option<Value>, nullable: bool) -> Column {
+        Column { name: name.to_string(), data_type, default, nullable, renamed_from: None, generated: None }
+    }
+
+    fn table(columns: Vec<Column>) -> Table {
+        Table {
+            name: "orders".to_string(),
+            description: "".to_string(),
+            schema: SchemaState { columns, constraint: vec![], retention: None },
+            scope: None,
+        }
+    }
+
+    #[test]
+    fn test_column_definition() {
+        let definition = |data_type, default, nullable| {
+            get_column_definition(&column("col", data_type, default, nullable)).unwrap()
+        };
+
+        assert_eq!(definition(DataType::BigInteger, Some(Value::Integer(42)), false), r#""col" INTEGER NOT NULL DEFAULT 42"#);
+        assert_eq!(definition(DataType::DoubleFloat, None, true), r#""col" REAL"#);
+        assert_eq!(definition(DataType::VarChar { length: 10 }, Some(Value::String("it's".to_string())), true), r#""col" TEXT DEFAULT 'it''s'"#);
+        assert_eq!(definition(DataType::Byte, Some(Value::Binary(vec![0xDE, 0xAD])), true), r#""col" BLOB DEFAULT X'dead'"#);
+        assert_eq!(
+            definition(DataType::Timestamp { with_tz: false, precision: None }, Some(Value::DateTime(chrono::NaiveDate::from_ymd(2019, 04, 20).and_hms(16, 20, 00))), true),
+            r#""col" TEXT DEFAULT '2019-04-20 16:20:00'"#);
+        assert_eq!(definition(DataType::Boolean, Some(Value::Boolean(true)), false), r#""col" INTEGER NOT NULL DEFAULT 1"#);
+        assert!(definition(DataType::Uuid { generate: true }, None, false).starts_with(r#""col" TEXT NOT NULL DEFAULT (lower(hex(randomblob(4)) ||"#));
+    }
+
+    #[test]
+    fn test_plan_table_update() {
+        let old = table(vec![
+            column("id", DataType::Integer, None, false),
+            column("name", DataType::String, None, true),
+            column("note", DataType::String, None, true),
+        ]);
+
+        let mut renamed = column("title", DataType::String, None, true);
+        renamed.renamed_from = Some("name".to_string());
+        let new = table(vec![
+            column("id", DataType::BigInteger, None, false),
+            renamed,
+            column("created_at", DataType::Timestamp { with_tz: false, precision: None }, None, true),
+        ]);
+
+        let statements: Vec<String> = plan_table_update(&old, &new)
+            .unwrap()
+            .into_iter()
+            .map(|x| x.statement)
+            .collect();
+        assert_eq!(statements, vec![
+            r#"ALTER TABLE "orders" RENAME COLUMN "name" TO "title";"#,
+            r#"ALTER TABLE "orders" DROP COLUMN "note";"#,
+            r#"ALTER TABLE "orders" ADD COLUMN "created_at" TEXT;"#,
+        ]);
+
+        let not_nullable = table(vec![
+            column("id", DataType::Integer, None, false),
+            column("name", DataType::String, None, false),
+            column("note", DataType::String, None, true),
+        ]);
+        assert!(plan_table_update(&old, &not_nullable).is_err());
+    }
+
+    #[test]
+    fn test_create_and_update_table() {
+        let conn = Connection::open_in_memory().unwrap();
+        let action = UpdateTable::new(&conn);
+
+        let old = table(vec![column("id", DataType::Integer, None, false), column("name", DataType::String, None, true)]);
+        action.create_table(&old).unwrap();
+
+        let new = table(vec![column("id", DataType::Integer, None, false), column("paid", DataType::Boolean, Some(Value::Boolean(false)), false)]);
+        action.update_table(&old, &new).unwrap();
+
+        conn.execute(r#"INSERT INTO "orders" ("id") VALUES (1);"#, []).unwrap();
+        let paid: i64 = conn.query_row(r#"SELECT "paid" FROM "orders";"#, [], |row| row.get(0)).unwrap();
+        assert_eq!(paid, 0);
+    }
+}
@@ -4,6 +4,8 @@
 extern crate actix;
 extern crate actix_web;
 extern crate argonautica;
+#[cfg(feature = "parquet-export")]
+extern crate arrow;
 extern crate base64;
 extern crate bcrypt;
 extern crate bigdecimal;
@@ -20,6 +22,8 @@ extern crate futures;
 extern crate inflector;
 extern crate json;
 extern crate jsonwebtoken;
+#[cfg(feature = "kafka-sink")]
+extern crate kafka;
 extern crate linked_hash_map;
 #[macro_use]
 extern crate log;
@@ -27,12 +31,17 @@ extern crate num_cpus;
 extern crate r2d2;
 extern crate r2d2_redis;
 extern crate rand;
+#[cfg(feature = "sqlite")]
+extern crate rusqlite;
+extern crate rustls;
 extern crate serde;
 #[macro_use]
 extern crate serde_json;
 #[macro_use]
 extern crate serde_derive;
 extern crate openssl;
+#[cfg(feature = "parquet-export")]
+extern crate parquet;
 extern crate tempfile;
 #[macro_use]
 extern crate time_test;
@@ -51,9 +60,12 @@ mod metastore;
 mod broker;
 mod server;
 mod state;
+mod jobs;
 
 pub mod kakapo_postgres; //TODO: move this outside
 pub mod kakapo_redis; //TODO: move this outside
+#[cfg(feature = "sqlite")]
+pub mod kakapo_sqlite; //TODO: move this outside
 
 pub mod plugins;
 
@@ -69,8 +81,23 @@ use actix_web::middleware::cors::CorsBuilder;
 pub use connection::AppStateBuilder;
 pub use connection::AppState;
 pub use connection::AppStateLike;
+pub use connection::tls::SslMode;
+pub use view::rate_limit::RateLimitConfig;
+pub use view::request_id::log_format;
+pub use view::cors::CorsConfig;
+pub use view::compression::CompressionConfig;
+pub use view::body_limit::BodyLimits;
+pub use view::https::HttpsConfig;
+pub use view::static_assets::StaticAssets;
+pub use view::static_assets::EmbeddedFile;
+pub use broker::event_sink::KafkaSinkConfig;
 pub use metastore::setup_admin;
+pub use metastore::migrations::run_migrations;
 pub use server::Server;
+pub use server::MIGRATE_ONLY_FLAG;
+pub use model::actions::policy::DecoratorPolicy;
+pub use model::actions::policy::ActionClass;
+pub use model::actions::decorator::Decorator;
 
 use actix_web::test::TestApp;
 use env_logger::Builder;
@@ -124,6 +151,7 @@ mod test {
             .num_threads(1)
             .password_secret("Hello World Hello Wold")
             .token_secret("Hello World Hello Wold")
+            .secrets_key("Hello World Hello Wold")
             .add_plugin("Sirocco", plugin);
 
         Server::new()
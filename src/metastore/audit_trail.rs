@@ -0,0 +1,165 @@
+use diesel::prelude::*;
+use diesel;
+use diesel::sql_types::BigInt;
+use diesel::sql_types::Jsonb;
+use diesel::sql_types::Nullable;
+use diesel::sql_types::Text;
+use diesel::sql_types::Timestamp;
+
+use data::audit::AuditEntry;
+use data::audit::AuditFilter;
+use data::audit::AuditOutcome;
+use data::audit::AuditTarget;
+use data::audit::NewAuditEntry;
+use data::error::DatastoreError;
+
+use state::AuditTrail;
+use state::audit_trail::AuditTrailOps;
+
+#[derive(Debug, QueryableByName)]
+struct RawAuditEntry {
+    #[sql_type = "BigInt"]
+    audit_entry_id: i64,
+    #[sql_type = "Nullable<Text>"]
+    username: Option<String>,
+    #[sql_type = "Text"]
+    procedure: String,
+    #[sql_type = "Nullable<Text>"]
+    entity_type: Option<String>,
+    #[sql_type = "Nullable<Text>"]
+    entity_name: Option<String>,
+    #[sql_type = "Nullable<Text>"]
+    domain: Option<String>,
+    #[sql_type = "Nullable<Text>"]
+    request_id: Option<String>,
+    #[sql_type = "Nullable<Jsonb>"]
+    before: Option<serde_json::Value>,
+    #[sql_type = "Nullable<Jsonb>"]
+    after: Option<serde_json::Value>,
+    #[sql_type = "Text"]
+    outcome: String,
+    #[sql_type = "Nullable<Text>"]
+    error: Option<String>,
+    #[sql_type = "Timestamp"]
+    created_at: chrono::NaiveDateTime,
+    /// the count of all the matching entries, not only the ones of the page
+    #[sql_type = "BigInt"]
+    total_count: i64,
+}
+
+impl<'a> AuditTrailOps for AuditTrail<'a> {
+    fn record(&self, entry: &NewAuditEntry) -> Result<(), DatastoreError> {
+        let query = r#"
+        INSERT INTO "audit_entry"
+            ("username", "procedure", "entity_type", "entity_name", "domain", "request_id", "before", "after", "outcome", "error")
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10);
+        "#;
+
+        diesel::sql_query(query)
+            .bind::<Nullable<Text>, _>(&entry.username)
+            .bind::<Text, _>(&entry.procedure)
+            .bind::<Nullable<Text>, _>(&entry.target.entity_type)
+            .bind::<Nullable<Text>, _>(&entry.target.entity_name)
+            .bind::<Nullable<Text>, _>(&entry.domain)
+            .bind::<Nullable<Text>, _>(&entry.request_id)
+            .bind::<Nullable<Jsonb>, _>(&entry.before)
+            .bind::<Nullable<Jsonb>, _>(&entry.after)
+            .bind::<Text, _>(entry.outcome.as_str())
+            .bind::<Nullable<Text>, _>(&entry.error)
+            .execute(self.conn)
+            .map_err(|err| DatastoreError::DbError(err.to_string()))?;
+
+        Ok(())
+    }
+
+    fn get_entries(&self, filter: &AuditFilter, limit: usize, offset: usize) -> Result<(Vec<AuditEntry>, usize), DatastoreError> {
+        let query = r#"
+        SELECT
+            "audit_entry_id", "username", "procedure", "entity_type", "entity_name", "domain", "request_id",
+            "before", "after", "outcome", "error", "created_at", COUNT(*) OVER () AS "total_count"
+        FROM "audit_entry"
+        WHERE ($1::VARCHAR IS NULL OR "username" = $1)
+            AND ($2::VARCHAR IS NULL OR "procedure" = $2)
+            AND ($3::VARCHAR IS NULL OR "domain" = $3)
+            AND ($4::VARCHAR IS NULL OR "entity_type" = $4)
+            AND ($5::VARCHAR IS NULL OR "entity_name" = $5)
+        ORDER BY "created_at" DESC, "audit_entry_id" DESC
+        LIMIT $6 OFFSET $7;
+        "#;
+
+        let raw_entries: Vec<RawAuditEntry> = diesel::sql_query(query)
+            .bind::<Nullable<Text>, _>(&filter.username)
+            .bind::<Nullable<Text>, _>(&filter.procedure)
+            .bind::<Nullable<Text>, _>(&filter.domain)
+            .bind::<Nullable<Text>, _>(&filter.entity_type)
+            .bind::<Nullable<Text>, _>(&filter.entity_name)
+            .bind::<BigInt, _>(limit as i64)
+            .bind::<BigInt, _>(offset as i64)
+            .load(self.conn)
+            .map_err(|err| DatastoreError::DbError(err.to_string()))?;
+
+        // past the last page there is no row to count with, so the total is counted on its own
+        let total_count = match raw_entries.first() {
+            Some(raw_entry) => raw_entry.total_count as usize,
+            None if offset > 0 => self.count_entries(filter)?,
+            None => 0,
+        };
+
+        let entries = raw_entries
+            .into_iter()
+            .map(|raw_entry| {
+                let outcome = AuditOutcome::from_str(&raw_entry.outcome)
+                    .ok_or(DatastoreError::InvalidState)?;
+
+                Ok(AuditEntry {
+                    id: raw_entry.audit_entry_id,
+                    username: raw_entry.username,
+                    procedure: raw_entry.procedure,
+                    target: AuditTarget {
+                        entity_type: raw_entry.entity_type,
+                        entity_name: raw_entry.entity_name,
+                    },
+                    domain: raw_entry.domain,
+                    request_id: raw_entry.request_id,
+                    before: raw_entry.before,
+                    after: raw_entry.after,
+                    outcome,
+                    error: raw_entry.error,
+                    created_at: raw_entry.created_at,
+                })
+            })
+            .collect::<Result<Vec<_>, DatastoreError>>()?;
+
+        Ok((entries, total_count))
+    }
+}
+
+#[derive(Debug, QueryableByName)]
+struct RawCount {
+    #[sql_type = "BigInt"]
+    count: i64,
+}
+
+impl<'a> AuditTrail<'a> {
+    fn count_entries(&self, filter: &AuditFilter) -> Result<usize, DatastoreError> {
+        let query = r#"
+        SELECT COUNT(*) AS "count" FROM "audit_entry"
+        WHERE ($1::VARCHAR IS NULL OR "username" = $1)
+            AND ($2::VARCHAR IS NULL OR "procedure" = $2)
+            AND ($3::VARCHAR IS NULL OR "domain" = $3)
+            AND ($4::VARCHAR IS NULL OR "entity_type" = $4)
+            AND ($5::VARCHAR IS NULL OR "entity_name" = $5);
+        "#;
+
+        let result: Vec<RawCount> = diesel::sql_query(query)
+            .bind::<Nullable<Text>, _>(&filter.username)
+            .bind::<Nullable<Text>, _>(&filter.procedure)
+            .bind::<Nullable<Text>, _>(&filter.domain)
+            .bind::<Nullable<Text>, _>(&filter.entity_type)
+            .bind::<Nullable<Text>, _>(&filter.entity_name)
+            .load(self.conn)
+            .map_err(|err| DatastoreError::DbError(err.to_string()))?;
+
+        Ok(result.first().map(|x| x.count as usize).unwrap_or(0))
+    }
+}
@@ -96,6 +96,7 @@ impl<'a> AuthenticationOps for Authentication<'a> {
                 UserManagementError::InternalError(err.to_string())
             })?;
 
+        self.touch_last_seen(user.user_id, now)?;
 
         self.build_jwt_token(now, user, session_token.token)
     }
@@ -130,6 +131,7 @@ impl<'a> AuthenticationOps for Authentication<'a> {
 
 
         let duration = self.jwt_duration;
+        self.touch_last_seen(user.user_id, now)?;
 
         let user = UserInfo {
             user_id: user.user_id,
@@ -159,6 +161,19 @@ impl<'a> AuthenticationOps for Authentication<'a> {
 
 
 impl<'a> Authentication<'a>  {
+    /// the inactive users lose their subscriptions, see `PubSubOps::expire_subscriptions`
+    fn touch_last_seen(&self, user_id: i64, now: chrono::DateTime<Utc>) -> Result<(), UserManagementError> {
+        diesel::update(schema::user::table.filter(schema::user::columns::user_id.eq(user_id)))
+            .set(schema::user::columns::last_seen_at.eq(Some(now.naive_utc())))
+            .execute(self.conn)
+            .map_err(|err| {
+                error!("Could not update the last seen time of the user: {:?}", &err);
+                UserManagementError::InternalError(err.to_string())
+            })?;
+
+        Ok(())
+    }
+
     fn build_jwt_token(&self, now: chrono::DateTime<Utc>, user: UserInfo, refresh_token_string: String) -> Result<SessionToken, UserManagementError> {
         let duration = self.jwt_duration;
         let refresh_duration = self.jwt_refresh_duration;
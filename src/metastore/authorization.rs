@@ -6,6 +6,7 @@ use data::claims::AuthClaims;
 
 use metastore::dbdata::RawPermission;
 use diesel::sql_types::BigInt;
+use diesel::sql_types::Text;
 use diesel::RunQueryDsl;
 use connection::executor::Conn;
 
@@ -65,6 +66,26 @@ impl<'a> AuthorizationOps for Authorization<'a> {
     fn username(&self) -> Option<String> {
         self.claims.to_owned().map(|x| x.get_username())
     }
+
+    fn claims(&self) -> Option<AuthClaims> {
+        self.claims.to_owned()
+    }
+
+    fn entity_tags(&self, type_name: &str, entity_name: &str) -> Vec<String> {
+        match self.get_entity_tags(type_name, entity_name) {
+            Ok(res) => res,
+            Err(err) => {
+                error!("encountered an error when trying to get entity tags: {:?}", err);
+                vec![]
+            }
+        }
+    }
+}
+
+#[derive(Debug, QueryableByName)]
+struct RawTagName {
+    #[sql_type = "Text"]
+    name: String,
 }
 
 impl<'a> Authorization<'a> {
@@ -93,6 +114,34 @@ impl<'a> Authorization<'a> {
         Ok(permissions)
     }
 
+    fn get_entity_tags(&self, type_name: &str, entity_name: &str) -> Result<Vec<String>, UserManagementError> {
+        let entity_table = match type_name {
+            "table" => "table_schema",
+            "query" => "query",
+            "script" => "script",
+            "view" => "view",
+            "webhook" => "webhook",
+            _ => return Ok(vec![]),
+        };
+
+        let query = format!(r#"
+        SELECT
+            DISTINCT "tag"."name" FROM "tag"
+        INNER JOIN "entity_tag"
+            ON "tag"."tag_id" = "entity_tag"."tag_id"
+        INNER JOIN "{entity_table}"
+            ON "entity_tag"."entity_id" = "{entity_table}"."entity_id"
+        WHERE "{entity_table}"."name" = $1 AND NOT "{entity_table}"."is_deleted";
+        "#, entity_table = entity_table);
+
+        let result: Vec<RawTagName> = diesel::sql_query(query)
+            .bind::<Text, _>(entity_name)
+            .load(self.conn)
+            .or_else(|err| Err(UserManagementError::InternalError(err.to_string())))?;
+
+        Ok(result.into_iter().map(|x| x.name).collect())
+    }
+
     fn get_all_permissions(&self) -> Result<Vec<Permission>, UserManagementError> {
 
         let query = r#"
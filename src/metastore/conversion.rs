@@ -18,11 +18,14 @@ use metastore::dbdata::RawScript;
 use metastore::dbdata::NewRawScript;
 use metastore::dbdata::RawView;
 use metastore::dbdata::NewRawView;
+use metastore::dbdata::RawWebhook;
+use metastore::dbdata::NewRawWebhook;
 use model::entity::ConvertRaw;
 use model::entity::GenerateRaw;
 use model::entity::RawEntityTypes;
 use data::channels::GetEntityChannel;
 use data::channels::Defaults;
+use data::webhook::EncryptedSecret;
 
 
 impl ConvertRaw<data::DataStoreEntity> for dbdata::RawTable {
@@ -30,7 +33,9 @@ impl ConvertRaw<data::DataStoreEntity> for dbdata::RawTable {
         data::DataStoreEntity {
             name: self.my_name().to_owned(),
             description: self.description.to_owned(),
-            schema: self.table_data.to_owned()
+            schema: self.table_data.to_owned(),
+            audit_rows: self.audit_rows,
+            scope: self.scope.to_owned(),
         }
     }
 }
@@ -41,6 +46,9 @@ impl ConvertRaw<data::DataQueryEntity> for dbdata::RawQuery {
             name: self.name.to_owned(),
             description: self.description.to_owned(),
             statement: self.statement.to_owned(),
+            params: serde_json::from_value(self.query_info["params"].to_owned()).unwrap_or_default(),
+            statement_timeout: self.query_info["statementTimeout"].as_u64(),
+            schedule: serde_json::from_value(self.query_info["schedule"].to_owned()).unwrap_or_default(),
         }
     }
 }
@@ -51,6 +59,13 @@ impl ConvertRaw<data::Script> for dbdata::RawScript {
             name: self.my_name().to_owned(),
             description: self.description.to_owned(),
             text: self.script_text.to_owned(),
+            language: data::ScriptLanguage::from_str(&self.script_language).unwrap_or_else(|| {
+                warn!("unknown language {:?} for the script {:?}, running it as python", &self.script_language, &self.name);
+                data::ScriptLanguage::default()
+            }),
+            schedule: serde_json::from_value(self.script_info["schedule"].to_owned()).unwrap_or_default(),
+            requirements: serde_json::from_value(self.script_info["requirements"].to_owned()).unwrap_or_default(),
+            params: serde_json::from_value(self.script_info["params"].to_owned()).unwrap_or_default(),
         }
     }
 }
@@ -60,11 +75,28 @@ impl ConvertRaw<data::View> for dbdata::RawView {
         data::View {
             name: self.my_name().to_owned(),
             description: self.description.to_owned(),
+            statement: self.statement.to_owned(),
+            materialized: self.materialized,
             view_state: self.view_state.to_owned(),
         }
     }
 }
 
+impl ConvertRaw<data::Webhook> for dbdata::RawWebhook {
+    fn convert(&self) -> data::Webhook {
+        data::Webhook {
+            name: self.my_name().to_owned(),
+            description: self.description.to_owned(),
+            url: self.url.to_owned(),
+            // only the webhooks stored before the secrets were encrypted have it in plain text
+            secret: self.webhook_info["secret"].as_str().unwrap_or_default().to_string(),
+            encrypted_secret: EncryptedSecret::from_value(&self.webhook_info["secret"]),
+            channels: serde_json::from_value(self.webhook_info["channels"].to_owned()).unwrap_or_default(),
+            retry: serde_json::from_value(self.webhook_info["retry"].to_owned()).unwrap_or_default(),
+        }
+    }
+}
+
 
 impl GenerateRaw<data::DataStoreEntity> for dbdata::NewRawTable {
     fn new(data: &data::DataStoreEntity, entity_id: i64, modified_by: i64) -> Self {
@@ -74,7 +106,9 @@ impl GenerateRaw<data::DataStoreEntity> for dbdata::NewRawTable {
             description: data.description.to_owned(),
             table_data: data.schema.to_owned(),
             is_deleted: false,
-            modified_by
+            modified_by,
+            audit_rows: data.audit_rows,
+            scope: data.scope_name().map(|x| x.to_owned()),
         }
     }
 
@@ -85,7 +119,9 @@ impl GenerateRaw<data::DataStoreEntity> for dbdata::NewRawTable {
             description: "".to_string(),
             table_data: json!({}),
             is_deleted: true,
-            modified_by
+            modified_by,
+            audit_rows: false,
+            scope: None,
         }
     }
 }
@@ -97,7 +133,11 @@ impl GenerateRaw<data::DataQueryEntity> for dbdata::NewRawQuery {
             name: data.my_name().to_owned(),
             description: data.description.to_owned(),
             statement: data.statement.to_owned(),
-            query_info: serde_json::to_value(json!({})).unwrap_or_default(),
+            query_info: json!({
+                "params": data.params,
+                "statementTimeout": data.statement_timeout,
+                "schedule": data.schedule,
+            }),
             is_deleted: false,
             modified_by
         }
@@ -122,9 +162,13 @@ impl GenerateRaw<data::Script> for dbdata::NewRawScript {
             entity_id,
             name: data.my_name().to_owned(),
             description: data.description.to_owned(),
-            script_language: "Python".to_string(), //Only Python is supported right now
+            script_language: data.language.as_str().to_string(),
             script_text: data.text.to_owned(),
-            script_info: serde_json::to_value(json!({})).unwrap_or_default(),
+            script_info: json!({
+                "schedule": data.schedule,
+                "requirements": data.requirements,
+                "params": data.params,
+            }),
             is_deleted: false,
             modified_by,
         }
@@ -135,7 +179,7 @@ impl GenerateRaw<data::Script> for dbdata::NewRawScript {
             entity_id,
             name,
             description: "".to_string(),
-            script_language: "Python".to_string(),
+            script_language: data::ScriptLanguage::default().as_str().to_string(),
             script_text: "".to_string(),
             script_info: serde_json::to_value(json!({})).unwrap_or_default(),
             is_deleted: true,
@@ -154,6 +198,8 @@ impl GenerateRaw<data::View> for dbdata::NewRawView {
             view_info: serde_json::to_value(json!({})).unwrap_or_default(),
             is_deleted: false,
             modified_by,
+            statement: data.statement.to_owned(),
+            materialized: data.materialized,
         }
     }
 
@@ -166,6 +212,38 @@ impl GenerateRaw<data::View> for dbdata::NewRawView {
             view_info: serde_json::to_value(json!({})).unwrap_or_default(),
             is_deleted: true,
             modified_by,
+            statement: "".to_string(),
+            materialized: false,
+        }
+    }
+}
+
+impl GenerateRaw<data::Webhook> for dbdata::NewRawWebhook {
+    fn new(data: &data::Webhook, entity_id: i64, modified_by: i64) -> Self {
+        dbdata::NewRawWebhook {
+            entity_id,
+            name: data.my_name().to_owned(),
+            description: data.description.to_owned(),
+            url: data.url.to_owned(),
+            webhook_info: json!({
+                "secret": data.encrypted_secret.as_ref().map(|x| x.to_value()),
+                "channels": data.channels,
+                "retry": data.retry,
+            }),
+            is_deleted: false,
+            modified_by,
+        }
+    }
+
+    fn tombstone(name: String, entity_id: i64, modified_by: i64) -> Self {
+        dbdata::NewRawWebhook {
+            entity_id,
+            name,
+            description: "".to_string(),
+            url: "".to_string(),
+            webhook_info: serde_json::to_value(json!({})).unwrap_or_default(),
+            is_deleted: true,
+            modified_by,
         }
     }
 }
@@ -177,6 +255,10 @@ impl RawEntityTypes for data::DataStoreEntity {
     type Data = RawTable;
     type NewData = NewRawTable;
 
+    fn scope_name(&self) -> Option<&str> {
+        self.scope_name()
+    }
+
 }
 
 impl RawEntityTypes for data::DataQueryEntity {
@@ -206,6 +288,15 @@ impl RawEntityTypes for data::View {
 
 }
 
+impl RawEntityTypes for data::Webhook {
+    const TYPE_NAME: &'static str = "webhook";
+    const TYPE_NAME_PLURAL: &'static str = "webhooks";
+
+    type Data = RawWebhook;
+    type NewData = NewRawWebhook;
+
+}
+
 //TODO: this is entity to channel, make something channel to entity
 impl GetEntityChannel for data::Script {
     fn entity_channel(name: &str) -> Defaults {
@@ -219,6 +310,12 @@ impl GetEntityChannel for data::View {
     }
 }
 
+impl GetEntityChannel for data::Webhook {
+    fn entity_channel(name: &str) -> Defaults {
+        Defaults::Webhook(name.to_string())
+    }
+}
+
 impl GetEntityChannel for data::DataStoreEntity {
     fn entity_channel(name: &str) -> Defaults {
         Defaults::Table(name.to_string())
@@ -7,9 +7,15 @@ use serde_json;
 
 use metastore::schema::entity;
 use metastore::schema::table_schema;
+use metastore::schema::table_row_history;
+use metastore::schema::query_scheduled_run;
+use metastore::schema::query_snapshot;
+use metastore::schema::script_run;
+use metastore::schema::entity_usage;
 use metastore::schema::query;
 use metastore::schema::script;
 use metastore::schema::view;
+use metastore::schema::webhook;
 use metastore::schema::user;
 use metastore::schema::permission;
 use metastore::schema::role;
@@ -55,6 +61,8 @@ pub struct RawTable {
     pub is_deleted: bool,
     pub modified_at: NaiveDateTime,
     pub modified_by: i64,
+    pub audit_rows: bool,
+    pub scope: Option<String>,
 }
 
 impl Named for RawTable {
@@ -72,6 +80,8 @@ pub struct NewRawTable {
     pub table_data: serde_json::Value,
     pub is_deleted: bool,
     pub modified_by: i64,
+    pub audit_rows: bool,
+    pub scope: Option<String>,
 }
 
 impl Named for NewRawTable {
@@ -80,6 +90,61 @@ impl Named for NewRawTable {
     }
 }
 
+#[derive(Debug, Deserialize, Insertable)]
+#[table_name = "table_row_history"]
+pub struct NewRawRowHistory {
+    pub entity_id: i64,
+    pub row_key: serde_json::Value,
+    pub change: String,
+    pub old_data: Option<serde_json::Value>,
+    pub new_data: Option<serde_json::Value>,
+    pub made_by: Option<i64>,
+}
+
+#[derive(Debug, Deserialize, Insertable)]
+#[table_name = "query_scheduled_run"]
+pub struct NewRawScheduledRun {
+    pub entity_id: i64,
+    pub started_at: NaiveDateTime,
+    pub duration_ms: i64,
+    pub succeeded: bool,
+    pub row_count: Option<i64>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Insertable)]
+#[table_name = "query_snapshot"]
+pub struct NewRawQuerySnapshot {
+    pub entity_id: i64,
+    pub name: String,
+    pub params: serde_json::Value,
+    pub data: serde_json::Value,
+    pub row_count: i64,
+    pub truncated: bool,
+    pub created_by: Option<i64>,
+}
+
+#[derive(Debug, Deserialize, Insertable)]
+#[table_name = "script_run"]
+pub struct NewRawScriptRun {
+    pub entity_id: i64,
+    pub started_at: NaiveDateTime,
+    pub duration_ms: i64,
+    pub successful: bool,
+    pub exit_code: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+    pub run_by: Option<i64>,
+    pub scheduled: bool,
+}
+
+#[derive(Debug, Deserialize, Insertable)]
+#[table_name = "entity_usage"]
+pub struct NewRawEntityUsage {
+    pub entity_id: i64,
+    pub used_by: Option<i64>,
+}
+
 #[derive(Identifiable, Associations, Debug, Queryable, QueryableByName, Clone)]
 #[primary_key(query_id)]
 #[table_name = "query"]
@@ -176,6 +241,8 @@ pub struct RawView {
     pub is_deleted: bool,
     pub modified_at: NaiveDateTime,
     pub modified_by: i64,
+    pub statement: String,
+    pub materialized: bool,
 }
 
 impl Named for RawView {
@@ -194,6 +261,8 @@ pub struct NewRawView {
     pub view_info: serde_json::Value,
     pub is_deleted: bool,
     pub modified_by: i64,
+    pub statement: String,
+    pub materialized: bool,
 }
 
 impl Named for NewRawView {
@@ -202,6 +271,46 @@ impl Named for NewRawView {
     }
 }
 
+#[derive(Identifiable, Associations, Debug, Queryable, QueryableByName, Clone)]
+#[primary_key(webhook_id)]
+#[table_name = "webhook"]
+#[belongs_to(RawEntity, foreign_key = "entity_id")]
+pub struct RawWebhook {
+    pub webhook_id: i64,
+    pub entity_id: i64,
+    pub name: String,
+    pub description: String,
+    pub url: String,
+    pub webhook_info: serde_json::Value,
+    pub is_deleted: bool,
+    pub modified_at: NaiveDateTime,
+    pub modified_by: i64,
+}
+
+impl Named for RawWebhook {
+    fn my_name(&self) -> &str {
+        &self.name
+    }
+}
+
+#[derive(Debug, Deserialize, Insertable)]
+#[table_name = "webhook"]
+pub struct NewRawWebhook {
+    pub entity_id: i64,
+    pub name: String,
+    pub description: String,
+    pub url: String,
+    pub webhook_info: serde_json::Value,
+    pub is_deleted: bool,
+    pub modified_by: i64,
+}
+
+impl Named for NewRawWebhook {
+    fn my_name(&self) -> &str {
+        &self.name
+    }
+}
+
 
 #[derive(Debug, Deserialize, Insertable)]
 #[table_name = "user"]
@@ -223,6 +332,7 @@ pub struct RawUser {
     pub display_name: String,
     pub user_info: serde_json::Value,
     pub joined_at: chrono::NaiveDateTime,
+    pub last_seen_at: Option<chrono::NaiveDateTime>,
 }
 
 #[derive(Debug, Deserialize, Insertable)]
@@ -326,6 +436,7 @@ pub struct NewRawChannel {
 pub struct RawChannel {
     pub channel_id: i64,
     pub data: serde_json::Value,
+    pub last_sequence: i64,
 }
 
 #[derive(Debug, Deserialize, Insertable)]
@@ -333,6 +444,8 @@ pub struct RawChannel {
 pub struct NewRawUserChannel {
     pub user_id: i64,
     pub channel_id: i64,
+    pub durable: bool,
+    pub expires_at: Option<chrono::NaiveDateTime>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, Queryable, QueryableByName)]
@@ -342,6 +455,8 @@ pub struct RawUserChannel {
     pub user_id: i64,
     pub channel_id: i64,
     pub subscribed_at: chrono::NaiveDateTime,
+    pub durable: bool,
+    pub expires_at: Option<chrono::NaiveDateTime>,
 }
 
 #[derive(Debug, Deserialize, Insertable)]
@@ -349,6 +464,10 @@ pub struct RawUserChannel {
 pub struct NewRawMessage {
     pub channel_id: i64,
     pub data: serde_json::Value,
+    pub sequence: i64,
+    pub action: String,
+    pub actor: Option<String>,
+    pub schema_version: i32,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, Queryable, QueryableByName)]
@@ -358,6 +477,10 @@ pub struct RawMessage {
     pub channel_id: i64,
     pub data: serde_json::Value,
     pub sent_at: chrono::NaiveDateTime,
+    pub sequence: i64,
+    pub action: String,
+    pub actor: Option<String>,
+    pub schema_version: i32,
 }
 
 #[derive(Debug, Deserialize, Insertable)]
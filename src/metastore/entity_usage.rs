@@ -0,0 +1,196 @@
+use std::collections::HashMap;
+
+use diesel::prelude::*;
+use diesel;
+use diesel::sql_types::Array;
+use diesel::sql_types::BigInt;
+use diesel::sql_types::Nullable;
+use diesel::sql_types::Text;
+use diesel::sql_types::Timestamp;
+
+use data::error::DatastoreError;
+use data::usage::EntityUsage;
+use data::usage::UserUsage;
+use metastore::schema;
+use metastore::dbdata;
+
+use state::EntityUsageRecorder;
+use state::entity_usage::EntityUsageOps;
+
+#[derive(Debug, QueryableByName)]
+struct RawEntityId {
+    #[sql_type = "BigInt"]
+    entity_id: i64,
+}
+
+#[derive(Debug, QueryableByName)]
+struct RawEntityUsage {
+    #[sql_type = "BigInt"]
+    entity_id: i64,
+    #[sql_type = "Text"]
+    name: String,
+    #[sql_type = "BigInt"]
+    use_count: i64,
+    #[sql_type = "Nullable<Timestamp>"]
+    last_used_at: Option<chrono::NaiveDateTime>,
+}
+
+#[derive(Debug, QueryableByName)]
+struct RawUserUsage {
+    #[sql_type = "BigInt"]
+    entity_id: i64,
+    #[sql_type = "Nullable<Text>"]
+    username: Option<String>,
+    #[sql_type = "BigInt"]
+    use_count: i64,
+}
+
+/// the metastore table holding the versions of the entity type
+fn entity_table(type_name: &str) -> Result<&'static str, DatastoreError> {
+    match type_name {
+        "table" => Ok("table_schema"),
+        "query" => Ok("query"),
+        "script" => Ok("script"),
+        _ => {
+            error!("usage is not recorded for {:?}", type_name);
+            Err(DatastoreError::InvalidState)
+        },
+    }
+}
+
+impl<'a> EntityUsageRecorder<'a> {
+    fn get_entity_id(&self, type_name: &str, entity_name: &str) -> Result<Option<i64>, DatastoreError> {
+        let query = format!(r#"
+        SELECT "{entity_table}"."entity_id" FROM "{entity_table}"
+        INNER JOIN "entity"
+            ON "{entity_table}"."entity_id" = "entity"."entity_id"
+        INNER JOIN "domain"
+            ON "entity"."domain_id" = "domain"."domain_id"
+        WHERE "{entity_table}"."name" = $1 AND "domain"."name" = $2 AND NOT "{entity_table}"."is_deleted"
+        ORDER BY "{entity_table}"."modified_at" DESC
+        LIMIT 1;
+        "#, entity_table = entity_table(type_name)?);
+
+        let domain_name = self.domain_name.to_owned().unwrap_or_default();
+        let result: Vec<RawEntityId> = diesel::sql_query(query)
+            .bind::<Text, _>(entity_name)
+            .bind::<Text, _>(&domain_name)
+            .load(self.conn)
+            .map_err(|err| DatastoreError::DbError(err.to_string()))?;
+
+        Ok(result.first().map(|x| x.entity_id))
+    }
+
+    fn get_top_users(&self, entity_ids: Vec<i64>, top_users: usize) -> Result<HashMap<i64, Vec<UserUsage>>, DatastoreError> {
+        let query = r#"
+        SELECT
+            "entity_usage"."entity_id",
+            "user"."username",
+            COUNT(*) AS "use_count"
+        FROM "entity_usage"
+        LEFT JOIN "user"
+            ON "entity_usage"."used_by" = "user"."user_id"
+        WHERE "entity_usage"."entity_id" = ANY($1)
+        GROUP BY "entity_usage"."entity_id", "user"."username"
+        ORDER BY "entity_usage"."entity_id", "use_count" DESC, "user"."username";
+        "#;
+
+        let raw_users: Vec<RawUserUsage> = diesel::sql_query(query)
+            .bind::<Array<BigInt>, _>(entity_ids)
+            .load(self.conn)
+            .map_err(|err| DatastoreError::DbError(err.to_string()))?;
+
+        let mut users: HashMap<i64, Vec<UserUsage>> = HashMap::new();
+        for raw_user in raw_users {
+            let entity_users = users.entry(raw_user.entity_id).or_insert_with(Vec::new);
+            if entity_users.len() < top_users {
+                entity_users.push(UserUsage {
+                    username: raw_user.username,
+                    use_count: raw_user.use_count,
+                });
+            }
+        }
+
+        Ok(users)
+    }
+}
+
+impl<'a> EntityUsageOps for EntityUsageRecorder<'a> {
+    fn record_usage(&self, type_name: &str, entity_name: &str) -> Result<(), DatastoreError> {
+        let entity_id = match self.get_entity_id(type_name, entity_name)? {
+            Some(entity_id) => entity_id,
+            None => {
+                // e.g. the table data actions on a view
+                debug!("not recording the usage of {} {:?}, it's not stored", type_name, entity_name);
+                return Ok(());
+            },
+        };
+
+        let raw_usage = dbdata::NewRawEntityUsage {
+            entity_id,
+            used_by: self.claims
+                .to_owned()
+                .filter(|x| !x.is_system())
+                .map(|x| x.get_user_id()),
+        };
+
+        diesel::insert_into(schema::entity_usage::table)
+            .values(&raw_usage)
+            .execute(self.conn)
+            .map_err(|err| DatastoreError::DbError(err.to_string()))?;
+
+        Ok(())
+    }
+
+    fn get_usage(&self, type_name: &str, top_users: usize) -> Result<Vec<EntityUsage>, DatastoreError> {
+        // the latest version of every entity, the deleted ones are left out afterwards
+        let query = format!(r#"
+        WITH "current" AS (
+            SELECT DISTINCT ON ("{entity_table}"."entity_id")
+                "{entity_table}"."entity_id",
+                "{entity_table}"."name",
+                "{entity_table}"."is_deleted"
+            FROM "{entity_table}"
+            INNER JOIN "entity"
+                ON "{entity_table}"."entity_id" = "entity"."entity_id"
+            INNER JOIN "domain"
+                ON "entity"."domain_id" = "domain"."domain_id"
+            WHERE "domain"."name" = $1
+            ORDER BY "{entity_table}"."entity_id", "{entity_table}"."modified_at" DESC
+        )
+        SELECT
+            "current"."entity_id",
+            "current"."name",
+            COUNT("entity_usage"."entity_usage_id") AS "use_count",
+            MAX("entity_usage"."used_at") AS "last_used_at"
+        FROM "current"
+        LEFT JOIN "entity_usage"
+            ON "current"."entity_id" = "entity_usage"."entity_id"
+        WHERE NOT "current"."is_deleted"
+        GROUP BY "current"."entity_id", "current"."name"
+        ORDER BY "use_count" DESC, "current"."name" ASC;
+        "#, entity_table = entity_table(type_name)?);
+
+        let domain_name = self.domain_name.to_owned().unwrap_or_default();
+        let raw_usages: Vec<RawEntityUsage> = diesel::sql_query(query)
+            .bind::<Text, _>(&domain_name)
+            .load(self.conn)
+            .map_err(|err| DatastoreError::DbError(err.to_string()))?;
+
+        let entity_ids = raw_usages.iter().map(|x| x.entity_id).collect();
+        let mut users = self.get_top_users(entity_ids, top_users)?;
+
+        let usages = raw_usages
+            .into_iter()
+            .map(|raw_usage| EntityUsage {
+                type_name: type_name.to_owned(),
+                top_users: users.remove(&raw_usage.entity_id).unwrap_or_default(),
+                name: raw_usage.name,
+                use_count: raw_usage.use_count,
+                last_used_at: raw_usage.last_used_at,
+            })
+            .collect();
+
+        Ok(usages)
+    }
+}
@@ -0,0 +1,204 @@
+use diesel::prelude::*;
+use diesel;
+use diesel::connection::SimpleConnection;
+use diesel::sql_types::Bool;
+use diesel::sql_types::Text;
+
+use metastore::schema;
+
+/// A metastore migration, its name is recorded in the `version` table once it is applied
+pub struct Migration {
+    pub name: &'static str,
+    pub up: &'static str,
+}
+
+macro_rules! migration {
+    ($name:expr) => {
+        Migration {
+            name: $name,
+            up: include_str!(concat!("../../migrations/", $name, "/up.sql")),
+        }
+    };
+}
+
+/// oldest first, new migrations have to be added here as well
+pub const MIGRATIONS: &[Migration] = &[
+    migration!("2018-10-04-133027_create_versioning"),
+    migration!("2018-10-04-133230_create_meta_tables"),
+    migration!("2018-10-08-150445_setup_admin"),
+    migration!("2019-04-25-120000_create_row_history"),
+    migration!("2019-04-27-120000_add_view_statement"),
+    migration!("2019-04-28-120000_add_materialized_views"),
+    migration!("2019-05-02-120000_create_query_scheduled_runs"),
+    migration!("2019-05-04-120000_record_entity_usage"),
+    migration!("2019-05-05-120000_create_query_snapshots"),
+    migration!("2019-05-06-120000_create_script_runs"),
+    migration!("2019-05-07-120000_schedule_scripts"),
+    migration!("2019-05-08-120000_create_script_secrets"),
+    migration!("2019-05-09-120000_create_webhooks"),
+    migration!("2019-05-10-120000_add_table_scope"),
+    migration!("2019-05-11-120000_create_audit_trail"),
+    migration!("2019-05-12-120000_index_messages"),
+    migration!("2019-05-13-120000_durable_subscriptions"),
+    migration!("2019-05-14-120000_create_channel_webhooks"),
+    migration!("2019-05-15-120000_create_dead_letters"),
+    migration!("2019-05-16-120000_subscription_expiry"),
+    migration!("2019-05-17-120000_create_event_envelope"),
+    migration!("2019-05-18-120000_encrypt_webhook_secrets"),
+];
+
+#[derive(Debug, QueryableByName)]
+struct TableExists {
+    #[sql_type = "Bool"]
+    exists: bool,
+}
+
+#[derive(Debug, QueryableByName)]
+struct DieselMigration {
+    #[sql_type = "Text"]
+    version: String,
+}
+
+fn table_exists(conn: &PgConnection, table_name: &str) -> Result<bool, String> {
+    let result: Vec<TableExists> = diesel::sql_query(r#"SELECT to_regclass($1) IS NOT NULL AS "exists";"#)
+        .bind::<Text, _>(format!("public.{}", table_name))
+        .load(conn)
+        .map_err(|err| err.to_string())?;
+
+    Ok(result.first().map(|x| x.exists).unwrap_or(false))
+}
+
+fn applied_migrations(conn: &PgConnection) -> Result<Vec<String>, String> {
+    if !table_exists(conn, "version")? {
+        return Ok(vec![]);
+    }
+
+    schema::version::table
+        .select(schema::version::columns::version_update)
+        .load::<String>(conn)
+        .map_err(|err| err.to_string())
+}
+
+fn record_migration(conn: &PgConnection, name: &str) -> Result<(), String> {
+    diesel::insert_into(schema::version::table)
+        .values(schema::version::columns::version_update.eq(name))
+        .on_conflict(schema::version::columns::version_update)
+        .do_nothing()
+        .execute(conn)
+        .map(|_| ())
+        .map_err(|err| err.to_string())
+}
+
+/// The metastores set up with the diesel cli have the migrations in `__diesel_schema_migrations`
+/// instead, e.g. `20181004133027` for `2018-10-04-133027_create_versioning`
+fn adopt_diesel_migrations(conn: &PgConnection) -> Result<Vec<String>, String> {
+    if !table_exists(conn, "__diesel_schema_migrations")? {
+        return Ok(vec![]);
+    }
+
+    let diesel_versions: Vec<String> = diesel::sql_query(r#"SELECT "version" FROM "__diesel_schema_migrations";"#)
+        .load::<DieselMigration>(conn)
+        .map_err(|err| err.to_string())?
+        .into_iter()
+        .map(|x| x.version)
+        .collect();
+
+    let mut adopted = vec![];
+    for migration in MIGRATIONS {
+        if diesel_versions.contains(&diesel_version(migration.name)) {
+            info!("migration {} was applied by diesel", migration.name);
+            record_migration(conn, migration.name)?;
+            adopted.push(migration.name.to_string());
+        }
+    }
+
+    Ok(adopted)
+}
+
+fn diesel_version(name: &str) -> String {
+    name
+        .split('_')
+        .next()
+        .unwrap_or_default()
+        .chars()
+        .filter(|x| x.is_ascii_digit())
+        .collect()
+}
+
+/// Applies the migrations that aren't recorded in the `version` table, each one in its own transaction.
+/// Returns the names of the ones that were applied
+pub fn run_migrations(database_url: &str) -> Result<Vec<String>, String> {
+    let conn = PgConnection::establish(database_url)
+        .map_err(|err| {
+            error!("Could not run the migrations, couldn't establish connection: {:?}", &err);
+            err.to_string()
+        })?;
+
+    let mut applied = applied_migrations(&conn)?;
+    let has_version_table = table_exists(&conn, "version")?;
+    if has_version_table && !MIGRATIONS.iter().any(|x| applied.iter().any(|name| name == x.name)) {
+        let adopted = adopt_diesel_migrations(&conn)?;
+        if adopted.is_empty() {
+            // the schema was set up by hand, running the migrations again would wipe the users
+            return Err("the metastore has a version table but no recorded migrations, \
+                record the applied ones in it before running the migrations".to_string());
+        }
+        applied.extend(adopted);
+    }
+
+    let mut newly_applied = vec![];
+    for migration in MIGRATIONS {
+        if applied.iter().any(|name| name == migration.name) {
+            continue;
+        }
+
+        info!("applying migration {}", migration.name);
+        conn
+            .transaction::<_, diesel::result::Error, _>(|| {
+                conn.batch_execute(migration.up)?;
+                diesel::insert_into(schema::version::table)
+                    .values(schema::version::columns::version_update.eq(migration.name))
+                    .execute(&conn)?;
+                Ok(())
+            })
+            .map_err(|err| {
+                error!("migration {} failed: {:?}", migration.name, &err);
+                format!("migration {} failed: {}", migration.name, err)
+            })?;
+        newly_applied.push(migration.name.to_string());
+    }
+
+    Ok(newly_applied)
+}
+
+/// the newest migration recorded in the `version` table
+pub fn current_version(conn: &PgConnection) -> Result<Option<&'static str>, String> {
+    let applied = applied_migrations(conn)?;
+    let version = MIGRATIONS
+        .iter()
+        .rev()
+        .find(|x| applied.iter().any(|name| name == x.name))
+        .map(|x| x.name);
+
+    Ok(version)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_migrations_are_ordered() {
+        let names: Vec<&str> = MIGRATIONS.iter().map(|x| x.name).collect();
+        let mut sorted = names.to_owned();
+        sorted.sort();
+        assert_eq!(names, sorted);
+        assert!(MIGRATIONS.iter().all(|x| !x.up.trim().is_empty()));
+    }
+
+    #[test]
+    fn test_diesel_version() {
+        assert_eq!(diesel_version("2018-10-04-133027_create_versioning"), "20181004133027");
+        assert_eq!(diesel_version("2019-05-09-120000_create_webhooks"), "20190509120000");
+    }
+}
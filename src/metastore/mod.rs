@@ -4,6 +4,17 @@ pub mod domain_management;
 pub mod authorization;
 pub mod authentication;
 pub mod pub_sub;
+pub mod row_history;
+pub mod scheduled_runs;
+pub mod query_history;
+pub mod query_snapshots;
+pub mod script_runs;
+pub mod script_secrets;
+pub mod script_history;
+pub mod webhook_deliveries;
+pub mod audit_trail;
+pub mod entity_usage;
+pub mod migrations;
 mod conversion;
 mod dbdata;
 mod schema;
@@ -40,7 +51,7 @@ use plugins::v1::Domain;
 pub trait EntityCrudOps
     where Self: Sized + Debug,
 {
-    fn get_all(state: &EntityRetrieverController) -> Result<Vec<Self>, EntityError>;
+    fn get_all(state: &EntityRetrieverController, show_deleted: bool) -> Result<Vec<Self>, EntityError>;
 
     fn get_one(state: &EntityRetrieverController, name: &str) -> Result<Option<Self>, EntityError>;
 
@@ -129,6 +140,29 @@ pub fn setup_admin(database_url: &str, username: &str, email: &str, display_name
 }
 
 const ADMIN_USER_ID: i64 = 1;
+/// created by the setup migration
+const MAIN_SCOPE_ID: i64 = 1;
+
+/// the scopes are created the first time an entity is put in them
+fn get_scope_id(conn: &Conn, scope_name: Option<&str>) -> Result<i64, EntityError> {
+    let scope_name = match scope_name {
+        Some(x) => x,
+        None => return Ok(MAIN_SCOPE_ID),
+    };
+
+    diesel::insert_into(schema::scope::table)
+        .values(schema::scope::columns::name.eq(scope_name))
+        .on_conflict(schema::scope::columns::name)
+        .do_nothing()
+        .execute(conn)
+        .map_err(|err| EntityError::InternalError(err.to_string()))?;
+
+    schema::scope::table
+        .filter(schema::scope::columns::name.eq(scope_name))
+        .select(schema::scope::columns::scope_id)
+        .get_result::<i64>(conn)
+        .map_err(|err| EntityError::InternalError(err.to_string()))
+}
 
 fn get_user_id(controller: &EntityModifierController) -> Option<i64> {
     match controller.claims {
@@ -185,9 +219,9 @@ macro_rules! make_crud_ops {
 
         impl EntityCrudOps for $EntityType {
 
-            fn get_all(state: &EntityRetrieverController) -> Result<Vec<$EntityType>, EntityError> {
+            fn get_all(state: &EntityRetrieverController, show_deleted: bool) -> Result<Vec<$EntityType>, EntityError> {
                 let domain_id = get_controller_domain_id(state).ok_or_else(|| EntityError::Unknown)?;
-                $entity::get_all::<$EntityType>(state.conn, domain_id)
+                $entity::get_all::<$EntityType>(state.conn, domain_id, show_deleted)
             }
 
             fn get_one(state: &EntityRetrieverController, name: &str) -> Result<Option<$EntityType>, EntityError> {
@@ -293,13 +327,12 @@ macro_rules! implement_retriever_and_modifier {
         pub fn get_all<O>(
             conn: &Conn,
             domain_id: i64,
+            show_deleted: bool,
         ) -> Result<Vec<O>, EntityError>
         where
             RD: ConvertRaw<O>,
         {
-            let entities: Vec<RD> = query_all_entities(conn, domain_id, false)?;
-
-            unimplemented!(); //TODO: should filter by the domain name
+            let entities: Vec<RD> = query_all_entities(conn, domain_id, show_deleted)?;
 
             let ok_result = entities
                 .into_iter()
@@ -340,7 +373,7 @@ macro_rules! implement_retriever_and_modifier {
                 RD: ConvertRaw<O>,
         {
             let new_raw_entity = NewRawEntity {
-                scope_id: 1, //TODO: right now scopes haven't been implemented
+                scope_id: get_scope_id(conn, object.scope_name())?,
                 domain_id,
                 created_by: user_id,
             };
@@ -479,6 +512,15 @@ macro_rules! implement_retriever_and_modifier {
 
             match entities {
                 Some(entity) => {
+                    // a rename is stored as a new version of the same entity, so the name has to be free
+                    let new_name = object.my_name().to_owned();
+                    if new_name != object_name {
+                        let existing: Option<RD> = query_entities_by_name(conn, domain_id, new_name.to_owned())?;
+                        if existing.is_some() {
+                            return Err(EntityError::NameTaken(new_name));
+                        }
+                    }
+
                     let new_val = update_internal(conn, user_id, domain_id, entity.entity_id, object)?;
                     Ok(Updated::Success {
                         old: entity.convert(),
@@ -524,6 +566,7 @@ make_crud_ops!(table, data::DataStoreEntity);
 make_crud_ops!(query, data::DataQueryEntity);
 make_crud_ops!(script, data::Script);
 make_crud_ops!(view, data::View);
+make_crud_ops!(webhook, data::Webhook);
 
 pub mod table {
     implement_retriever_and_modifier!(data::DataStoreEntity, table_schema);
@@ -539,4 +582,8 @@ pub mod script {
 
 pub mod view {
     implement_retriever_and_modifier!(data::View, view);
+}
+
+pub mod webhook {
+    implement_retriever_and_modifier!(data::Webhook, webhook);
 }
\ No newline at end of file
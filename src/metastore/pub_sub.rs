@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 
 use diesel::prelude::*;
 use diesel;
@@ -7,6 +8,8 @@ use data::channels::Channels;
 use data::channels::Subscription;
 use metastore::schema;
 use metastore::dbdata;
+use metastore::webhook_deliveries::enqueue_channel_deliveries;
+use metastore::webhook_deliveries::enqueue_deliveries;
 use connection::executor::Conn;
 use diesel::result::Error as DbError;
 use diesel::result::DatabaseErrorKind as DbErrKind;
@@ -15,38 +18,216 @@ use data::auth::User;
 use state::error::BroadcastError;
 use state::PubSubOps;
 use state::PublishCallback;
-use data::Message;
+use data::event::Event;
+use data::event::EVENT_SCHEMA_VERSION;
+use data::message_retention::MessageRetention;
+use data::dead_letter::ACK_TIMEOUT_SECS;
+use data::dead_letter::DeadLetter;
+use data::dead_letter::DeadLetterTarget;
+use data::dead_letter::MAX_PENDING_ATTEMPTS;
 use diesel::types;
 
+/// the columns of a message and the data of its channel, see `EVENT_COLUMNS`
+#[derive(Debug, QueryableByName)]
+struct RawEvent {
+    #[sql_type = "types::BigInt"]
+    message_id: i64,
+    #[sql_type = "types::Jsonb"]
+    data: serde_json::Value,
+    #[sql_type = "types::Timestamp"]
+    sent_at: chrono::NaiveDateTime,
+    #[sql_type = "types::BigInt"]
+    sequence: i64,
+    #[sql_type = "types::Text"]
+    action: String,
+    #[sql_type = "types::Nullable<types::Text>"]
+    actor: Option<String>,
+    #[sql_type = "types::Integer"]
+    schema_version: i32,
+    #[sql_type = "types::Jsonb"]
+    channel: serde_json::Value,
+}
+
+impl RawEvent {
+    fn into_event(self) -> Result<Event, BroadcastError> {
+        let message_id = self.message_id;
+        let channel = serde_json::from_value(self.channel)
+            .map_err(|err| {
+                error!("Could not deserialize the channel of message {}: {:?}", message_id, &err);
+                BroadcastError::Unknown
+            })?;
+
+        Ok(Event {
+            id: message_id,
+            sequence: self.sequence,
+            channel,
+            action: self.action,
+            actor: self.actor,
+            timestamp: self.sent_at,
+            schema_version: self.schema_version,
+            data: self.data,
+        })
+    }
+}
+
+/// what `RawEvent` needs, from the messages joined with their channel
+const EVENT_COLUMNS: &str = r#"
+    "message"."message_id", "message"."data", "message"."sent_at", "message"."sequence",
+    "message"."action", "message"."actor", "message"."schema_version", "channel"."data" AS "channel"
+"#;
+
+#[derive(Debug, QueryableByName)]
+struct RawDeadLetter {
+    #[sql_type = "types::BigInt"]
+    dead_letter_id: i64,
+    #[sql_type = "types::Jsonb"]
+    channel: serde_json::Value,
+    #[sql_type = "types::Jsonb"]
+    payload: serde_json::Value,
+    #[sql_type = "types::Integer"]
+    attempts: i32,
+    #[sql_type = "types::Nullable<types::Text>"]
+    last_error: Option<String>,
+    #[sql_type = "types::Nullable<types::BigInt>"]
+    webhook_delivery_id: Option<i64>,
+    #[sql_type = "types::Nullable<types::BigInt>"]
+    user_channel_id: Option<i64>,
+    #[sql_type = "types::Nullable<types::BigInt>"]
+    message_id: Option<i64>,
+    #[sql_type = "types::Nullable<types::Text>"]
+    username: Option<String>,
+    #[sql_type = "types::Timestamp"]
+    created_at: chrono::NaiveDateTime,
+}
+
+impl RawDeadLetter {
+    fn into_dead_letter(self) -> Result<DeadLetter, BroadcastError> {
+        let channel = serde_json::from_value(self.channel)
+            .map_err(|err| {
+                error!("Could not deserialize the channel of dead letter {}: {:?}", self.dead_letter_id, &err);
+                BroadcastError::Unknown
+            })?;
+
+        let target = match (self.webhook_delivery_id, self.username) {
+            (Some(delivery_id), _) => DeadLetterTarget::Webhook { delivery_id },
+            (None, Some(username)) => DeadLetterTarget::Subscriber { username },
+            (None, None) => {
+                error!("The dead letter {} has no target", self.dead_letter_id);
+                return Err(BroadcastError::Unknown);
+            },
+        };
+
+        Ok(DeadLetter {
+            id: self.dead_letter_id,
+            channel,
+            data: self.payload,
+            target,
+            attempts: self.attempts,
+            last_error: self.last_error,
+            created_at: self.created_at,
+        })
+    }
+}
+
+/// the dead letters with the username of their subscriber, if they are for one
+const DEAD_LETTERS: &'static str = r#"
+    SELECT "dead_letter".*, "user"."username" FROM "dead_letter"
+    LEFT JOIN "user_channel"
+        ON "dead_letter"."user_channel_id" = "user_channel"."user_channel_id"
+    LEFT JOIN "user"
+        ON "user_channel"."user_id" = "user"."user_id"
+"#;
+
+#[derive(Debug, QueryableByName)]
+struct RawCount {
+    #[sql_type = "types::BigInt"]
+    count: i64,
+}
+
+#[derive(Debug, QueryableByName)]
+struct RawSubscriberCount {
+    #[sql_type = "types::Jsonb"]
+    channel: serde_json::Value,
+    #[sql_type = "types::BigInt"]
+    subscribers: i64,
+}
+
 impl<'a> PubSubOps for PublishCallback<'a> {
 
     fn publish(&self, channel: Channels, action_name: String, action_result: &serde_json::Value) -> Result<(), BroadcastError> {
 
         let raw_channel = get_or_create_channel(self.conn, &channel)?;
+        let sequence = next_sequence(self.conn, raw_channel.channel_id)?;
 
         let raw_message = dbdata::NewRawMessage {
             channel_id: raw_channel.channel_id,
             data: action_result.to_owned(),
+            sequence,
+            action: action_name.to_owned(),
+            actor: self.claims.as_ref().map(|claims| claims.username.to_owned()),
+            schema_version: EVENT_SCHEMA_VERSION,
         };
 
-        diesel::insert_into(schema::message::table)
+        let inserted_message = diesel::insert_into(schema::message::table)
             .values(&raw_message)
-            .execute(self.conn)
+            .get_result::<dbdata::RawMessage>(self.conn)
             .map_err(|err| {
                 println!("Could not get or create err: {:?}", &err);
 
                 BroadcastError::InternalError(err.to_string())
             })?;
 
+        // kept for the durable subscribers until they ack it, even if none of their sessions is open
+        let query = r#"
+        INSERT INTO "pending_message" ("user_channel_id", "message_id")
+        SELECT "user_channel_id", $2 FROM "user_channel"
+        WHERE "channel_id" = $1 AND "durable";
+        "#;
+        diesel::sql_query(query)
+            .bind::<types::BigInt, _>(raw_channel.channel_id)
+            .bind::<types::BigInt, _>(inserted_message.message_id)
+            .execute(self.conn)
+            .map_err(|err| BroadcastError::InternalError(err.to_string()))?;
+
+        let deliveries = enqueue_deliveries(self.conn, self.domain_name, &channel, &action_name, action_result)
+            .and_then(|entity_deliveries| {
+                enqueue_channel_deliveries(self.conn, raw_channel.channel_id, &channel, &action_name, action_result)
+                    .map(|channel_deliveries| entity_deliveries + channel_deliveries)
+            })
+            .map_err(|err| BroadcastError::InternalError(err.to_string()))?;
+        if deliveries > 0 {
+            debug!("queued {} webhook deliveries for {:?}", deliveries, &channel);
+        }
+
+        if let Some(kafka_sink) = self.kafka_sink {
+            let event = Event {
+                id: inserted_message.message_id,
+                sequence: inserted_message.sequence,
+                channel,
+                action: inserted_message.action,
+                actor: inserted_message.actor,
+                timestamp: inserted_message.sent_at,
+                schema_version: inserted_message.schema_version,
+                data: inserted_message.data,
+            };
+            kafka_sink.send(&event, self.domain_name);
+        }
+
         Ok(())
     }
 
-    fn subscribe(&self, user_id: i64, channel: Channels) -> Result<Subscription, BroadcastError> {
+    fn subscribe(
+        &self,
+        user_id: i64,
+        channel: Channels,
+        durable: bool,
+        expires_at: Option<chrono::NaiveDateTime>,
+    ) -> Result<Subscription, BroadcastError> {
         info!("subscribing to channels: {:?}", &channel);
 
         let raw_user = get_user(self.conn, user_id)?;
         let raw_channel = get_or_create_channel(self.conn, &channel)?;
-        let raw_user_channel = create_user_channel(self.conn, raw_user.user_id, raw_channel.channel_id)?;
+        let raw_user_channel = create_user_channel(self.conn, raw_user.user_id, raw_channel.channel_id, durable, expires_at)?;
 
         let user = User {
             username: raw_user.username,
@@ -54,7 +235,12 @@ impl<'a> PubSubOps for PublishCallback<'a> {
             display_name: raw_user.display_name,
         };
 
-        Ok(Subscription { user, channel })
+        Ok(Subscription {
+            user,
+            channel,
+            durable: raw_user_channel.durable,
+            expires_at: raw_user_channel.expires_at,
+        })
     }
 
     fn unsubscribe(&self, user_id: i64, channel: Channels) -> Result<Subscription, BroadcastError> {
@@ -71,14 +257,19 @@ impl<'a> PubSubOps for PublishCallback<'a> {
             display_name: raw_user.display_name,
         };
 
-        Ok(Subscription { user, channel })
+        Ok(Subscription {
+            user,
+            channel,
+            durable: raw_user_channel.durable,
+            expires_at: raw_user_channel.expires_at,
+        })
     }
 
-    fn unsubscribe_all(&self, user_id: i64) -> Result<(), BroadcastError> {
+    fn unsubscribe_all(&self, user_id: i64, keep_durable: bool) -> Result<(), BroadcastError> {
         info!("unsubscribing user channels");
 
         let raw_user = get_user(self.conn, user_id)?;
-        remove_user_from_all_channels(self.conn, raw_user.user_id)?;
+        remove_user_from_all_channels(self.conn, raw_user.user_id, keep_durable)?;
         //TODO: if it's the last user subscribing to the channel, delete channel
 
         Ok(())
@@ -120,39 +311,322 @@ impl<'a> PubSubOps for PublishCallback<'a> {
         Ok(users)
     }
 
+    fn get_subscriptions(&self, user_id: i64) -> Result<Vec<Subscription>, BroadcastError> {
+        let raw_user = get_user(self.conn, user_id)?;
+        let raw_subscriptions: Vec<(dbdata::RawChannel, dbdata::RawUserChannel)> = schema::channel::table
+            .inner_join(schema::user_channel::table)
+            .filter(schema::user_channel::columns::user_id.eq(raw_user.user_id))
+            .order(schema::user_channel::columns::subscribed_at.asc())
+            .load(self.conn)
+            .map_err(|err| BroadcastError::InternalError(err.to_string()))?;
+
+        let user = User {
+            username: raw_user.username,
+            email: raw_user.email,
+            display_name: raw_user.display_name,
+        };
+
+        raw_subscriptions
+            .into_iter()
+            .map(|(raw_channel, raw_user_channel)| {
+                let channel = serde_json::from_value(raw_channel.data)
+                    .map_err(|err| {
+                        error!("Could not deserialize channel {}: {:?}", raw_channel.channel_id, &err);
+                        BroadcastError::Unknown
+                    })?;
+
+                Ok(Subscription {
+                    user: user.to_owned(),
+                    channel,
+                    durable: raw_user_channel.durable,
+                    expires_at: raw_user_channel.expires_at,
+                })
+            })
+            .collect()
+    }
+
+    fn count_subscribers(&self, channels: &[Channels]) -> Result<HashMap<Channels, i64>, BroadcastError> {
+        let query = r#"
+        SELECT
+            "channel"."data" AS "channel",
+            COUNT(DISTINCT "user_channel"."user_id") AS "subscribers"
+        FROM "channel"
+        INNER JOIN "user_channel"
+            ON "channel"."channel_id" = "user_channel"."channel_id"
+        WHERE "channel"."data" = ANY($1)
+        GROUP BY "channel"."data";
+        "#;
+
+        let channels_json = channels
+            .iter()
+            .map(|channel| serde_json::to_value(channel))
+            .collect::<Result<Vec<serde_json::Value>, _>>()
+            .map_err(|err| {
+                error!("Could not serialize the channels error: {:?}", &err);
+                BroadcastError::Unknown
+            })?;
+        let raw_counts: Vec<RawSubscriberCount> = diesel::sql_query(query)
+            .bind::<types::Array<types::Jsonb>, _>(&channels_json)
+            .load(self.conn)
+            .map_err(|err| BroadcastError::InternalError(err.to_string()))?;
+
+        raw_counts
+            .into_iter()
+            .map(|raw_count| {
+                let channel = serde_json::from_value(raw_count.channel)
+                    .map_err(|err| {
+                        error!("Could not deserialize channel: {:?}", &err);
+                        BroadcastError::Unknown
+                    })?;
+
+                Ok((channel, raw_count.subscribers))
+            })
+            .collect()
+    }
+
     fn get_messages(
         &self,
         user_id: i64,
         start_time: chrono::NaiveDateTime,
         end_time: chrono::NaiveDateTime,
-    ) -> Result<Vec<Message>, BroadcastError> {
-        let query = r#"
-        SELECT
-            * FROM "message"
+    ) -> Result<Vec<Event>, BroadcastError> {
+        let query = format!(r#"
+        SELECT {}
+        FROM "message"
         INNER JOIN "user_channel"
             ON "message"."channel_id" = "user_channel"."channel_id"
+        INNER JOIN "channel"
+            ON "message"."channel_id" = "channel"."channel_id"
         WHERE "user_channel"."user_id" = $1 AND "message"."sent_at" >= $2 AND "message"."sent_at" < $3
+            AND NOT "user_channel"."durable"
         ORDER BY "message"."sent_at" ASC;
-        "#;
+        "#, EVENT_COLUMNS);
 
-        let raw_messages: Vec<dbdata::RawMessage> = diesel::sql_query(query)
+        let raw_events: Vec<RawEvent> = diesel::sql_query(query)
             .bind::<types::BigInt, _>(user_id)
             .bind::<types::Timestamp, _>(&start_time)
             .bind::<types::Timestamp, _>(&end_time)
             .load(self.conn)
             .map_err(|err| BroadcastError::InternalError(err.to_string()))?;
 
+        raw_events
+            .into_iter()
+            .map(|raw_event| raw_event.into_event())
+            .collect()
+    }
+
+    fn get_pending_messages(&self, user_id: i64, redeliver: bool) -> Result<Vec<Event>, BroadcastError> {
+        // the messages sent too many times without an ack, e.g. the client can't handle them
+        let query = r#"
+        WITH "dead" AS (
+            DELETE FROM "pending_message"
+            USING "user_channel"
+            WHERE "pending_message"."user_channel_id" = "user_channel"."user_channel_id"
+                AND "user_channel"."user_id" = $1
+                AND "pending_message"."attempts" >= $2
+                AND "pending_message"."delivered_at" < NOW() - make_interval(secs => $3)
+            RETURNING "pending_message".*
+        )
+        INSERT INTO "dead_letter" ("channel", "payload", "attempts", "last_error", "user_channel_id", "message_id")
+        SELECT "channel"."data", "message"."data", "dead"."attempts", 'not acknowledged', "dead"."user_channel_id", "dead"."message_id"
+        FROM "dead"
+        INNER JOIN "message"
+            ON "dead"."message_id" = "message"."message_id"
+        INNER JOIN "channel"
+            ON "message"."channel_id" = "channel"."channel_id";
+        "#;
+
+        let dead = diesel::sql_query(query)
+            .bind::<types::BigInt, _>(user_id)
+            .bind::<types::Integer, _>(MAX_PENDING_ATTEMPTS)
+            .bind::<types::Double, _>(ACK_TIMEOUT_SECS as f64)
+            .execute(self.conn)
+            .map_err(|err| BroadcastError::InternalError(err.to_string()))?;
+        if dead > 0 {
+            warn!("{} messages of user {} were never acknowledged, they were moved to the dead letters", dead, user_id);
+        }
+
+        let query = format!(r#"
+        WITH "sent" AS (
+            UPDATE "pending_message" SET
+                "delivered_at" = NOW(),
+                "attempts" = "pending_message"."attempts" + 1
+            FROM "user_channel"
+            WHERE "pending_message"."user_channel_id" = "user_channel"."user_channel_id"
+                AND "user_channel"."user_id" = $1
+                AND ($2 OR "pending_message"."delivered_at" IS NULL
+                    OR "pending_message"."delivered_at" < NOW() - make_interval(secs => $3))
+            RETURNING "pending_message"."message_id"
+        )
+        SELECT
+            DISTINCT ON("message"."message_id") {}
+        FROM "message"
+        INNER JOIN "sent"
+            ON "message"."message_id" = "sent"."message_id"
+        INNER JOIN "channel"
+            ON "message"."channel_id" = "channel"."channel_id"
+        ORDER BY "message"."message_id" ASC;
+        "#, EVENT_COLUMNS);
+
+        let raw_events: Vec<RawEvent> = diesel::sql_query(query)
+            .bind::<types::BigInt, _>(user_id)
+            .bind::<types::Bool, _>(redeliver)
+            .bind::<types::Double, _>(ACK_TIMEOUT_SECS as f64)
+            .load(self.conn)
+            .map_err(|err| BroadcastError::InternalError(err.to_string()))?;
 
-        let messages: Vec<Message> = raw_messages
+        raw_events
             .into_iter()
-            .map(|raw_message| Message {
-                data: raw_message.data,
-                timestamp: raw_message.sent_at,
-            })
-            .collect();
+            .map(|raw_event| raw_event.into_event())
+            .collect()
+    }
+
+    fn ack_messages(&self, user_id: i64, message_ids: &[i64]) -> Result<usize, BroadcastError> {
+        let query = r#"
+        DELETE FROM "pending_message"
+        USING "user_channel"
+        WHERE "pending_message"."user_channel_id" = "user_channel"."user_channel_id"
+            AND "user_channel"."user_id" = $1
+            AND "pending_message"."message_id" = ANY($2);
+        "#;
+
+        diesel::sql_query(query)
+            .bind::<types::BigInt, _>(user_id)
+            .bind::<types::Array<types::BigInt>, _>(message_ids)
+            .execute(self.conn)
+            .map_err(|err| BroadcastError::InternalError(err.to_string()))
+    }
+
+    fn get_dead_letters(&self, limit: usize, offset: usize) -> Result<(Vec<DeadLetter>, usize), BroadcastError> {
+        let query = format!(r#"
+        {dead_letters}
+        ORDER BY "dead_letter"."dead_letter_id" DESC
+        LIMIT $1 OFFSET $2;
+        "#, dead_letters = DEAD_LETTERS);
+
+        let raw_dead_letters: Vec<RawDeadLetter> = diesel::sql_query(query)
+            .bind::<types::BigInt, _>(limit as i64)
+            .bind::<types::BigInt, _>(offset as i64)
+            .load(self.conn)
+            .map_err(|err| BroadcastError::InternalError(err.to_string()))?;
 
-        Ok(messages)
+        let raw_counts: Vec<RawCount> = diesel::sql_query(r#"SELECT COUNT(*) AS "count" FROM "dead_letter";"#)
+            .load(self.conn)
+            .map_err(|err| BroadcastError::InternalError(err.to_string()))?;
+        let total_count = raw_counts.first().map(|x| x.count as usize).unwrap_or(0);
+
+        let dead_letters = raw_dead_letters
+            .into_iter()
+            .map(|raw_dead_letter| raw_dead_letter.into_dead_letter())
+            .collect::<Result<Vec<DeadLetter>, BroadcastError>>()?;
+
+        Ok((dead_letters, total_count))
+    }
+
+    fn redeliver_dead_letter(&self, id: i64) -> Result<Option<DeadLetter>, BroadcastError> {
+        let query = format!(r#"
+        {dead_letters}
+        WHERE "dead_letter"."dead_letter_id" = $1
+        FOR UPDATE OF "dead_letter";
+        "#, dead_letters = DEAD_LETTERS);
+
+        let raw_dead_letters: Vec<RawDeadLetter> = diesel::sql_query(query)
+            .bind::<types::BigInt, _>(id)
+            .load(self.conn)
+            .map_err(|err| BroadcastError::InternalError(err.to_string()))?;
+        let raw_dead_letter = match raw_dead_letters.into_iter().next() {
+            Some(raw_dead_letter) => raw_dead_letter,
+            None => return Ok(None),
+        };
+
+        // the delivery is retried from scratch, or the message is pending again for the subscriber
+        let requeued = match (raw_dead_letter.webhook_delivery_id, raw_dead_letter.user_channel_id, raw_dead_letter.message_id) {
+            (Some(webhook_delivery_id), _, _) => {
+                let query = r#"
+                UPDATE "webhook_delivery" SET "status" = 'pending', "attempts" = 0, "next_attempt_at" = NOW()
+                WHERE "webhook_delivery_id" = $1;
+                "#;
+
+                diesel::sql_query(query)
+                    .bind::<types::BigInt, _>(webhook_delivery_id)
+                    .execute(self.conn)
+            },
+            (None, Some(user_channel_id), Some(message_id)) => {
+                let query = r#"
+                INSERT INTO "pending_message" ("user_channel_id", "message_id") VALUES ($1, $2)
+                ON CONFLICT DO NOTHING;
+                "#;
+
+                diesel::sql_query(query)
+                    .bind::<types::BigInt, _>(user_channel_id)
+                    .bind::<types::BigInt, _>(message_id)
+                    .execute(self.conn)
+            },
+            _ => {
+                error!("The dead letter {} has no target", id);
+                return Err(BroadcastError::Unknown);
+            },
+        };
+        requeued.map_err(|err| BroadcastError::InternalError(err.to_string()))?;
 
+        diesel::delete(schema::dead_letter::table)
+            .filter(schema::dead_letter::columns::dead_letter_id.eq(id))
+            .execute(self.conn)
+            .map_err(|err| BroadcastError::InternalError(err.to_string()))?;
+
+        raw_dead_letter
+            .into_dead_letter()
+            .map(Some)
+    }
+
+    fn expire_subscriptions(&self, inactive_for: Option<u64>) -> Result<usize, BroadcastError> {
+        // subscribing counts as being active, e.g. for the users that never logged in since this was tracked
+        let query = r#"
+        DELETE FROM "user_channel"
+        USING "user"
+        WHERE "user_channel"."user_id" = "user"."user_id"
+            AND ("user_channel"."expires_at" < NOW()
+                OR ($1 AND GREATEST("user"."last_seen_at", "user_channel"."subscribed_at") < NOW() - make_interval(secs => $2)));
+        "#;
+
+        diesel::sql_query(query)
+            .bind::<types::Bool, _>(inactive_for.is_some())
+            .bind::<types::Double, _>(inactive_for.unwrap_or(0) as f64)
+            .execute(self.conn)
+            .map_err(|err| BroadcastError::InternalError(err.to_string()))
+    }
+
+    fn purge_messages(&self, retention: &MessageRetention) -> Result<usize, BroadcastError> {
+        let mut purged = 0;
+
+        if let Some(cutoff) = retention.cutoff(chrono::Utc::now().naive_utc()) {
+            purged += diesel::delete(schema::message::table)
+                .filter(schema::message::columns::sent_at.lt(cutoff))
+                .execute(self.conn)
+                .map_err(|err| BroadcastError::InternalError(err.to_string()))?;
+        }
+
+        if let Some(max_per_channel) = retention.max_per_channel {
+            let query = r#"
+            DELETE FROM "message"
+            WHERE "message_id" IN (
+                SELECT "message_id" FROM (
+                    SELECT
+                        "message_id",
+                        ROW_NUMBER() OVER (PARTITION BY "channel_id" ORDER BY "sent_at" DESC, "message_id" DESC) AS "position"
+                    FROM "message"
+                ) AS "ranked"
+                WHERE "ranked"."position" > $1
+            );
+            "#;
+
+            purged += diesel::sql_query(query)
+                .bind::<types::BigInt, _>(max_per_channel as i64)
+                .execute(self.conn)
+                .map_err(|err| BroadcastError::InternalError(err.to_string()))?;
+        }
+
+        Ok(purged)
     }
 
     fn permissions_removed(&self) -> Result<(), BroadcastError> {
@@ -174,7 +648,7 @@ fn get_user(conn: &Conn, user_id: i64) -> Result<dbdata::RawUser, BroadcastError
 
 }
 
-fn get_or_create_channel(conn: &Conn, channel: &Channels) -> Result<dbdata::RawChannel, BroadcastError> {
+pub fn get_or_create_channel(conn: &Conn, channel: &Channels) -> Result<dbdata::RawChannel, BroadcastError> {
     let channel_json = serde_json::to_value(channel)
         .map_err(|err| {
             error!("Could not serialize value {:?} error: {:?}", &channel, &err);
@@ -202,6 +676,16 @@ fn get_or_create_channel(conn: &Conn, channel: &Channels) -> Result<dbdata::RawC
         })
 }
 
+/// the sequence of the next message of the channel, the row stays locked until the transaction ends
+/// so that the messages of a channel are numbered in the order they are published
+fn next_sequence(conn: &Conn, channel_id: i64) -> Result<i64, BroadcastError> {
+    diesel::update(schema::channel::table.filter(schema::channel::columns::channel_id.eq(channel_id)))
+        .set(schema::channel::columns::last_sequence.eq(schema::channel::columns::last_sequence + 1))
+        .returning(schema::channel::columns::last_sequence)
+        .get_result::<i64>(conn)
+        .map_err(|err| BroadcastError::InternalError(err.to_string()))
+}
+
 fn get_channel(conn: &Conn, channel: &Channels) -> Result<dbdata::RawChannel, BroadcastError> {
     let channel_json = serde_json::to_value(channel)
         .map_err(|err| {
@@ -221,8 +705,14 @@ fn get_channel(conn: &Conn, channel: &Channels) -> Result<dbdata::RawChannel, Br
         })
 }
 
-fn create_user_channel(conn: &Conn, user_id: i64, channel_id: i64) -> Result<dbdata::RawUserChannel, BroadcastError> {
-    let user_channel_value = dbdata::NewRawUserChannel { user_id, channel_id, };
+fn create_user_channel(
+    conn: &Conn,
+    user_id: i64,
+    channel_id: i64,
+    durable: bool,
+    expires_at: Option<chrono::NaiveDateTime>,
+) -> Result<dbdata::RawUserChannel, BroadcastError> {
+    let user_channel_value = dbdata::NewRawUserChannel { user_id, channel_id, durable, expires_at };
 
     diesel::insert_into(schema::user_channel::table)
         .values(&user_channel_value)
@@ -253,10 +743,15 @@ fn remove_user_channel(conn: &Conn, user_id: i64, channel_id: i64) -> Result<dbd
         })
 }
 
-fn remove_user_from_all_channels(conn: &Conn, user_id: i64) -> Result<Vec<dbdata::RawUserChannel>, BroadcastError> {
-
-    diesel::delete(schema::user_channel::table)
+fn remove_user_from_all_channels(conn: &Conn, user_id: i64, keep_durable: bool) -> Result<Vec<dbdata::RawUserChannel>, BroadcastError> {
+    let mut query = diesel::delete(schema::user_channel::table)
         .filter(schema::user_channel::columns::user_id.eq(&user_id))
+        .into_boxed();
+    if keep_durable {
+        query = query.filter(schema::user_channel::columns::durable.eq(false));
+    }
+
+    query
         .get_results::<dbdata::RawUserChannel>(conn)
         .map_err(|err| match err {
             DbError::NotFound => {
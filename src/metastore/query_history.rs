@@ -0,0 +1,81 @@
+use std::collections::HashMap;
+
+use diesel::prelude::*;
+use diesel;
+use diesel::sql_types::BigInt;
+use diesel::sql_types::Text;
+
+use data;
+use data::error::DatastoreError;
+use metastore::schema;
+use metastore::dbdata::RawQuery;
+use model::entity::ConvertRaw;
+
+use state::QueryHistory;
+use state::query_history::QueryHistoryOps;
+
+impl<'a> QueryHistory<'a> {
+    /// every version of the entity the query currently belongs to, newest first
+    fn get_versions(&self, query_name: &str) -> Result<Vec<RawQuery>, DatastoreError> {
+        let query = r#"
+        SELECT "query".* FROM "query"
+        WHERE "query"."entity_id" = (
+            SELECT "current"."entity_id" FROM "query" AS "current"
+            INNER JOIN "entity"
+                ON "current"."entity_id" = "entity"."entity_id"
+            INNER JOIN "domain"
+                ON "entity"."domain_id" = "domain"."domain_id"
+            WHERE "current"."name" = $1 AND "domain"."name" = $2 AND NOT "current"."is_deleted"
+            ORDER BY "current"."modified_at" DESC
+            LIMIT 1
+        )
+        ORDER BY "query"."modified_at" DESC, "query"."query_id" DESC;
+        "#;
+
+        let domain_name = self.domain_name.to_owned().unwrap_or_default();
+        diesel::sql_query(query)
+            .bind::<Text, _>(query_name)
+            .bind::<Text, _>(&domain_name)
+            .load(self.conn)
+            .map_err(|err| DatastoreError::DbError(err.to_string()))
+    }
+}
+
+impl<'a> QueryHistoryOps for QueryHistory<'a> {
+    fn get_query_history(&self, query_name: &str) -> Result<Vec<data::QueryVersion>, DatastoreError> {
+        let versions = self.get_versions(query_name)?;
+
+        let user_ids: Vec<i64> = versions.iter().map(|x| x.modified_by).collect();
+        let usernames: HashMap<i64, String> = schema::user::table
+            .select((schema::user::columns::user_id, schema::user::columns::username))
+            .filter(schema::user::columns::user_id.eq_any(user_ids))
+            .load::<(i64, String)>(self.conn)
+            .map_err(|err| DatastoreError::DbError(err.to_string()))?
+            .into_iter()
+            .collect();
+
+        let history = versions
+            .iter()
+            .map(|raw_query| data::QueryVersion {
+                version: raw_query.query_id,
+                query: raw_query.convert(),
+                is_deleted: raw_query.is_deleted,
+                modified_at: raw_query.modified_at,
+                modified_by: usernames.get(&raw_query.modified_by).cloned(),
+            })
+            .collect();
+
+        Ok(history)
+    }
+
+    fn get_query_version(&self, query_name: &str, version: i64) -> Result<Option<data::DataQueryEntity>, DatastoreError> {
+        let versions = self.get_versions(query_name)?;
+
+        let query = versions
+            .iter()
+            .find(|raw_query| raw_query.query_id == version && !raw_query.is_deleted)
+            .map(|raw_query| raw_query.convert());
+
+        Ok(query)
+    }
+}
@@ -0,0 +1,153 @@
+use diesel::prelude::*;
+use diesel;
+use diesel::result::Error as DbError;
+use diesel::result::DatabaseErrorKind as DbErrKind;
+use diesel::sql_types::BigInt;
+use diesel::sql_types::Bool;
+use diesel::sql_types::Json;
+use diesel::sql_types::Nullable;
+use diesel::sql_types::Text;
+use diesel::sql_types::Timestamp;
+
+use data;
+use data::error::DatastoreError;
+use metastore::schema;
+use metastore::dbdata;
+
+use state::QuerySnapshots;
+use state::query_snapshots::QuerySnapshotsOps;
+
+#[derive(Debug, QueryableByName)]
+struct RawEntityId {
+    #[sql_type = "BigInt"]
+    entity_id: i64,
+}
+
+#[derive(Debug, QueryableByName)]
+struct RawQuerySnapshot {
+    #[sql_type = "Text"]
+    name: String,
+    #[sql_type = "Json"]
+    params: serde_json::Value,
+    #[sql_type = "Nullable<Json>"]
+    data: Option<serde_json::Value>,
+    #[sql_type = "BigInt"]
+    row_count: i64,
+    #[sql_type = "Bool"]
+    truncated: bool,
+    #[sql_type = "Timestamp"]
+    created_at: chrono::NaiveDateTime,
+    #[sql_type = "Nullable<Text>"]
+    created_by: Option<String>,
+}
+
+impl<'a> QuerySnapshots<'a> {
+    fn get_entity_id(&self, query_name: &str) -> Result<i64, DatastoreError> {
+        let query = r#"
+        SELECT "query"."entity_id" FROM "query"
+        INNER JOIN "entity"
+            ON "query"."entity_id" = "entity"."entity_id"
+        INNER JOIN "domain"
+            ON "entity"."domain_id" = "domain"."domain_id"
+        WHERE "query"."name" = $1 AND "domain"."name" = $2 AND NOT "query"."is_deleted"
+        ORDER BY "query"."modified_at" DESC
+        LIMIT 1;
+        "#;
+
+        let domain_name = self.domain_name.to_owned().unwrap_or_default();
+        let result: Vec<RawEntityId> = diesel::sql_query(query)
+            .bind::<Text, _>(query_name)
+            .bind::<Text, _>(&domain_name)
+            .load(self.conn)
+            .map_err(|err| DatastoreError::DbError(err.to_string()))?;
+
+        result
+            .first()
+            .map(|x| x.entity_id)
+            .ok_or_else(|| {
+                error!("could not find the query {:?} in domain {:?}", query_name, &domain_name);
+                DatastoreError::InvalidState
+            })
+    }
+
+    /// the data is only read if `name` is set
+    fn load_snapshots(&self, query_name: &str, name: Option<&str>) -> Result<Vec<data::QuerySnapshot>, DatastoreError> {
+        let entity_id = self.get_entity_id(query_name)?;
+
+        let query = r#"
+        SELECT
+            "query_snapshot"."name",
+            "query_snapshot"."params",
+            CASE WHEN $2::VARCHAR IS NULL THEN NULL ELSE "query_snapshot"."data" END AS "data",
+            "query_snapshot"."row_count",
+            "query_snapshot"."truncated",
+            "query_snapshot"."created_at",
+            "user"."username" AS "created_by"
+        FROM "query_snapshot"
+        LEFT JOIN "user"
+            ON "query_snapshot"."created_by" = "user"."user_id"
+        WHERE "query_snapshot"."entity_id" = $1 AND ($2::VARCHAR IS NULL OR "query_snapshot"."name" = $2)
+        ORDER BY "query_snapshot"."created_at" DESC, "query_snapshot"."query_snapshot_id" DESC;
+        "#;
+
+        let raw_snapshots: Vec<RawQuerySnapshot> = diesel::sql_query(query)
+            .bind::<BigInt, _>(entity_id)
+            .bind::<Nullable<Text>, _>(name)
+            .load(self.conn)
+            .map_err(|err| DatastoreError::DbError(err.to_string()))?;
+
+        let snapshots = raw_snapshots
+            .into_iter()
+            .map(|raw_snapshot| data::QuerySnapshot {
+                query_name: query_name.to_owned(),
+                name: raw_snapshot.name,
+                params: raw_snapshot.params,
+                data: raw_snapshot.data,
+                row_count: raw_snapshot.row_count,
+                truncated: raw_snapshot.truncated,
+                created_at: raw_snapshot.created_at,
+                created_by: raw_snapshot.created_by,
+            })
+            .collect();
+
+        Ok(snapshots)
+    }
+}
+
+impl<'a> QuerySnapshotsOps for QuerySnapshots<'a> {
+    fn save_snapshot(&self, snapshot: &data::QuerySnapshot) -> Result<(), DatastoreError> {
+        let entity_id = self.get_entity_id(&snapshot.query_name)?;
+
+        let raw_snapshot = dbdata::NewRawQuerySnapshot {
+            entity_id,
+            name: snapshot.name.to_owned(),
+            params: snapshot.params.to_owned(),
+            data: snapshot.data.to_owned().unwrap_or_default(),
+            row_count: snapshot.row_count,
+            truncated: snapshot.truncated,
+            created_by: self.claims
+                .to_owned()
+                .filter(|x| !x.is_system())
+                .map(|x| x.get_user_id()),
+        };
+
+        diesel::insert_into(schema::query_snapshot::table)
+            .values(&raw_snapshot)
+            .execute(self.conn)
+            .map_err(|err| match err {
+                DbError::DatabaseError(DbErrKind::UniqueViolation, _) => DatastoreError::AlreadyExists,
+                _ => DatastoreError::DbError(err.to_string()),
+            })?;
+
+        Ok(())
+    }
+
+    fn get_snapshot(&self, query_name: &str, name: &str) -> Result<Option<data::QuerySnapshot>, DatastoreError> {
+        let mut snapshots = self.load_snapshots(query_name, Some(name))?;
+        Ok(snapshots.pop())
+    }
+
+    fn get_snapshots(&self, query_name: &str) -> Result<Vec<data::QuerySnapshot>, DatastoreError> {
+        self.load_snapshots(query_name, None)
+    }
+}
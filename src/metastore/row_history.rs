@@ -0,0 +1,170 @@
+use diesel::prelude::*;
+use diesel;
+use diesel::result::Error as DbError;
+use diesel::sql_types::BigInt;
+use diesel::sql_types::Json;
+use diesel::sql_types::Jsonb;
+use diesel::sql_types::Nullable;
+use diesel::sql_types::Text;
+use diesel::sql_types::Timestamp;
+
+use connection::executor::Conn;
+use data::error::DatastoreError;
+use data::row_history::RowChange;
+use data::row_history::RowDelta;
+use data::row_history::RowHistoryEntry;
+use metastore::schema;
+use metastore::dbdata;
+
+use state::RowHistory;
+use state::row_history::RowHistoryOps;
+
+#[derive(Debug, QueryableByName)]
+struct RawEntityId {
+    #[sql_type = "BigInt"]
+    entity_id: i64,
+}
+
+#[derive(Debug, QueryableByName)]
+struct RawRowHistoryEntry {
+    #[sql_type = "Text"]
+    change: String,
+    #[sql_type = "Jsonb"]
+    row_key: serde_json::Value,
+    #[sql_type = "Nullable<Json>"]
+    old_data: Option<serde_json::Value>,
+    #[sql_type = "Nullable<Json>"]
+    new_data: Option<serde_json::Value>,
+    #[sql_type = "Timestamp"]
+    made_at: chrono::NaiveDateTime,
+    #[sql_type = "Nullable<Text>"]
+    made_by: Option<String>,
+}
+
+impl<'a> RowHistory<'a> {
+    fn get_entity_id(&self, table_name: &str) -> Result<i64, DatastoreError> {
+        let query = r#"
+        SELECT "table_schema"."entity_id" FROM "table_schema"
+        INNER JOIN "entity"
+            ON "table_schema"."entity_id" = "entity"."entity_id"
+        INNER JOIN "domain"
+            ON "entity"."domain_id" = "domain"."domain_id"
+        WHERE "table_schema"."name" = $1 AND "domain"."name" = $2 AND NOT "table_schema"."is_deleted"
+        ORDER BY "table_schema"."modified_at" DESC
+        LIMIT 1;
+        "#;
+
+        let domain_name = self.domain_name.to_owned().unwrap_or_default();
+        let result: Vec<RawEntityId> = diesel::sql_query(query)
+            .bind::<Text, _>(table_name)
+            .bind::<Text, _>(&domain_name)
+            .load(self.conn)
+            .map_err(|err| DatastoreError::DbError(err.to_string()))?;
+
+        result
+            .first()
+            .map(|x| x.entity_id)
+            .ok_or_else(|| {
+                error!("could not find the table {:?} in domain {:?}", table_name, &domain_name);
+                DatastoreError::InvalidState
+            })
+    }
+
+    /// the updates don't return the previous values, so the last recorded state of the row is used
+    fn get_last_data(&self, entity_id: i64, key: &serde_json::Value) -> Result<Option<serde_json::Value>, DatastoreError> {
+        schema::table_row_history::table
+            .select(schema::table_row_history::columns::new_data)
+            .filter(schema::table_row_history::columns::entity_id.eq(entity_id))
+            .filter(schema::table_row_history::columns::row_key.eq(key))
+            .order((
+                schema::table_row_history::columns::made_at.desc(),
+                schema::table_row_history::columns::row_history_id.desc(),
+            ))
+            .first::<Option<serde_json::Value>>(self.conn)
+            .or_else(|err| match err {
+                DbError::NotFound => Ok(None),
+                _ => Err(DatastoreError::DbError(err.to_string())),
+            })
+    }
+}
+
+impl<'a> RowHistoryOps for RowHistory<'a> {
+    fn record_changes(&self, table_name: &str, deltas: Vec<RowDelta>) -> Result<(), DatastoreError> {
+        if deltas.is_empty() {
+            return Ok(());
+        }
+
+        let entity_id = self.get_entity_id(table_name)?;
+        let made_by = self.claims.to_owned().map(|x| x.get_user_id());
+
+        let mut raw_deltas = vec![];
+        for delta in deltas {
+            let old_data = match delta.change {
+                RowChange::Update => self.get_last_data(entity_id, &delta.key)?,
+                _ => delta.old_data,
+            };
+
+            raw_deltas.push(dbdata::NewRawRowHistory {
+                entity_id,
+                row_key: delta.key,
+                change: delta.change.as_str().to_string(),
+                old_data,
+                new_data: delta.new_data,
+                made_by,
+            });
+        }
+        debug!("recording {} row changes for {:?}", raw_deltas.len(), table_name);
+
+        diesel::insert_into(schema::table_row_history::table)
+            .values(&raw_deltas)
+            .execute(self.conn)
+            .map_err(|err| DatastoreError::DbError(err.to_string()))?;
+
+        Ok(())
+    }
+
+    fn get_row_history(&self, table_name: &str, key: &serde_json::Value) -> Result<Vec<RowHistoryEntry>, DatastoreError> {
+        let entity_id = self.get_entity_id(table_name)?;
+
+        let query = r#"
+        SELECT
+            "table_row_history"."change",
+            "table_row_history"."row_key",
+            "table_row_history"."old_data",
+            "table_row_history"."new_data",
+            "table_row_history"."made_at",
+            "user"."username" AS "made_by"
+        FROM "table_row_history"
+        LEFT JOIN "user"
+            ON "table_row_history"."made_by" = "user"."user_id"
+        WHERE "table_row_history"."entity_id" = $1 AND "table_row_history"."row_key" = $2
+        ORDER BY "table_row_history"."made_at" ASC, "table_row_history"."row_history_id" ASC;
+        "#;
+
+        let raw_entries: Vec<RawRowHistoryEntry> = diesel::sql_query(query)
+            .bind::<BigInt, _>(entity_id)
+            .bind::<Jsonb, _>(key)
+            .load(self.conn)
+            .map_err(|err| DatastoreError::DbError(err.to_string()))?;
+
+        raw_entries
+            .into_iter()
+            .map(|raw_entry| {
+                let change = RowChange::from_str(&raw_entry.change)
+                    .ok_or_else(|| {
+                        error!("unknown row change {:?}", &raw_entry.change);
+                        DatastoreError::InvalidState
+                    })?;
+
+                Ok(RowHistoryEntry {
+                    change,
+                    key: raw_entry.row_key,
+                    old_data: raw_entry.old_data,
+                    new_data: raw_entry.new_data,
+                    made_at: raw_entry.made_at,
+                    made_by: raw_entry.made_by,
+                })
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,119 @@
+use diesel::prelude::*;
+use diesel;
+use diesel::sql_types::BigInt;
+use diesel::sql_types::Bool;
+use diesel::sql_types::Nullable;
+use diesel::sql_types::Text;
+use diesel::sql_types::Timestamp;
+
+use data::error::DatastoreError;
+use data::schedule::ScheduledRun;
+use metastore::schema;
+use metastore::dbdata;
+
+use state::ScheduledRuns;
+use state::scheduled_runs::ScheduledRunsOps;
+
+#[derive(Debug, QueryableByName)]
+struct RawEntityId {
+    #[sql_type = "BigInt"]
+    entity_id: i64,
+}
+
+#[derive(Debug, QueryableByName)]
+struct RawScheduledRun {
+    #[sql_type = "Timestamp"]
+    started_at: chrono::NaiveDateTime,
+    #[sql_type = "BigInt"]
+    duration_ms: i64,
+    #[sql_type = "Bool"]
+    succeeded: bool,
+    #[sql_type = "Nullable<BigInt>"]
+    row_count: Option<i64>,
+    #[sql_type = "Nullable<Text>"]
+    error: Option<String>,
+}
+
+impl<'a> ScheduledRuns<'a> {
+    fn get_entity_id(&self, query_name: &str) -> Result<i64, DatastoreError> {
+        let query = r#"
+        SELECT "query"."entity_id" FROM "query"
+        INNER JOIN "entity"
+            ON "query"."entity_id" = "entity"."entity_id"
+        INNER JOIN "domain"
+            ON "entity"."domain_id" = "domain"."domain_id"
+        WHERE "query"."name" = $1 AND "domain"."name" = $2 AND NOT "query"."is_deleted"
+        ORDER BY "query"."modified_at" DESC
+        LIMIT 1;
+        "#;
+
+        let domain_name = self.domain_name.to_owned().unwrap_or_default();
+        let result: Vec<RawEntityId> = diesel::sql_query(query)
+            .bind::<Text, _>(query_name)
+            .bind::<Text, _>(&domain_name)
+            .load(self.conn)
+            .map_err(|err| DatastoreError::DbError(err.to_string()))?;
+
+        result
+            .first()
+            .map(|x| x.entity_id)
+            .ok_or_else(|| {
+                error!("could not find the query {:?} in domain {:?}", query_name, &domain_name);
+                DatastoreError::InvalidState
+            })
+    }
+}
+
+impl<'a> ScheduledRunsOps for ScheduledRuns<'a> {
+    fn record_run(&self, run: &ScheduledRun) -> Result<(), DatastoreError> {
+        let entity_id = self.get_entity_id(&run.query_name)?;
+
+        let raw_run = dbdata::NewRawScheduledRun {
+            entity_id,
+            started_at: run.started_at,
+            duration_ms: run.duration_ms,
+            succeeded: run.succeeded,
+            row_count: run.row_count,
+            error: run.error.to_owned(),
+        };
+
+        diesel::insert_into(schema::query_scheduled_run::table)
+            .values(&raw_run)
+            .execute(self.conn)
+            .map_err(|err| DatastoreError::DbError(err.to_string()))?;
+
+        Ok(())
+    }
+
+    fn get_runs(&self, query_name: &str, limit: usize) -> Result<Vec<ScheduledRun>, DatastoreError> {
+        let entity_id = self.get_entity_id(query_name)?;
+
+        let query = r#"
+        SELECT "started_at", "duration_ms", "succeeded", "row_count", "error"
+        FROM "query_scheduled_run"
+        WHERE "entity_id" = $1
+        ORDER BY "started_at" DESC, "scheduled_run_id" DESC
+        LIMIT $2;
+        "#;
+
+        let raw_runs: Vec<RawScheduledRun> = diesel::sql_query(query)
+            .bind::<BigInt, _>(entity_id)
+            .bind::<BigInt, _>(limit as i64)
+            .load(self.conn)
+            .map_err(|err| DatastoreError::DbError(err.to_string()))?;
+
+        let runs = raw_runs
+            .into_iter()
+            .map(|raw_run| ScheduledRun {
+                query_name: query_name.to_owned(),
+                started_at: raw_run.started_at,
+                duration_ms: raw_run.duration_ms,
+                succeeded: raw_run.succeeded,
+                row_count: raw_run.row_count,
+                error: raw_run.error,
+            })
+            .collect();
+
+        Ok(runs)
+    }
+}
@@ -1,7 +1,53 @@
+table! {
+    audit_entry (audit_entry_id) {
+        audit_entry_id -> Int8,
+        username -> Nullable<Varchar>,
+        procedure -> Varchar,
+        entity_type -> Nullable<Varchar>,
+        entity_name -> Nullable<Varchar>,
+        domain -> Nullable<Varchar>,
+        request_id -> Nullable<Varchar>,
+        before -> Nullable<Jsonb>,
+        after -> Nullable<Jsonb>,
+        outcome -> Varchar,
+        error -> Nullable<Varchar>,
+        created_at -> Timestamp,
+    }
+}
+
 table! {
     channel (channel_id) {
         channel_id -> Int8,
         data -> Jsonb,
+        last_sequence -> Int8,
+    }
+}
+
+table! {
+    channel_webhook (channel_webhook_id) {
+        channel_webhook_id -> Int8,
+        channel_id -> Int8,
+        url -> Varchar,
+        secret -> Nullable<Varchar>,
+        secret_nonce -> Nullable<Bytea>,
+        secret_ciphertext -> Nullable<Bytea>,
+        retry -> Jsonb,
+        created_at -> Timestamp,
+        created_by -> Nullable<Int8>,
+    }
+}
+
+table! {
+    dead_letter (dead_letter_id) {
+        dead_letter_id -> Int8,
+        channel -> Jsonb,
+        payload -> Jsonb,
+        attempts -> Int4,
+        last_error -> Nullable<Varchar>,
+        webhook_delivery_id -> Nullable<Int8>,
+        user_channel_id -> Nullable<Int8>,
+        message_id -> Nullable<Int8>,
+        created_at -> Timestamp,
     }
 }
 
@@ -40,7 +86,7 @@ table! {
         entity_usage_id -> Int8,
         entity_id -> Int8,
         used_at -> Timestamp,
-        used_by -> Int8,
+        used_by -> Nullable<Int8>,
     }
 }
 
@@ -61,6 +107,19 @@ table! {
         channel_id -> Int8,
         data -> Jsonb,
         sent_at -> Timestamp,
+        sequence -> Int8,
+        action -> Varchar,
+        actor -> Nullable<Varchar>,
+        schema_version -> Int4,
+    }
+}
+
+table! {
+    pending_message (user_channel_id, message_id) {
+        user_channel_id -> Int8,
+        message_id -> Int8,
+        delivered_at -> Nullable<Timestamp>,
+        attempts -> Int4,
     }
 }
 
@@ -85,6 +144,32 @@ table! {
     }
 }
 
+table! {
+    query_scheduled_run (scheduled_run_id) {
+        scheduled_run_id -> Int8,
+        entity_id -> Int8,
+        started_at -> Timestamp,
+        duration_ms -> Int8,
+        succeeded -> Bool,
+        row_count -> Nullable<Int8>,
+        error -> Nullable<Varchar>,
+    }
+}
+
+table! {
+    query_snapshot (query_snapshot_id) {
+        query_snapshot_id -> Int8,
+        entity_id -> Int8,
+        name -> Varchar,
+        params -> Json,
+        data -> Json,
+        row_count -> Int8,
+        truncated -> Bool,
+        created_at -> Timestamp,
+        created_by -> Nullable<Int8>,
+    }
+}
+
 table! {
     role (role_id) {
         role_id -> Int8,
@@ -126,6 +211,33 @@ table! {
     }
 }
 
+table! {
+    script_run (script_run_id) {
+        script_run_id -> Int8,
+        entity_id -> Int8,
+        started_at -> Timestamp,
+        duration_ms -> Int8,
+        successful -> Bool,
+        exit_code -> Nullable<Int4>,
+        stdout -> Varchar,
+        stderr -> Varchar,
+        run_by -> Nullable<Int8>,
+        scheduled -> Bool,
+    }
+}
+
+table! {
+    script_secret (script_secret_id) {
+        script_secret_id -> Int8,
+        entity_id -> Int8,
+        name -> Varchar,
+        nonce -> Bytea,
+        ciphertext -> Bytea,
+        modified_at -> Timestamp,
+        modified_by -> Nullable<Int8>,
+    }
+}
+
 table! {
     session (session_id) {
         session_id -> Int8,
@@ -146,6 +258,21 @@ table! {
         is_deleted -> Bool,
         modified_at -> Timestamp,
         modified_by -> Int8,
+        audit_rows -> Bool,
+        scope -> Nullable<Varchar>,
+    }
+}
+
+table! {
+    table_row_history (row_history_id) {
+        row_history_id -> Int8,
+        entity_id -> Int8,
+        row_key -> Jsonb,
+        change -> Varchar,
+        old_data -> Nullable<Json>,
+        new_data -> Nullable<Json>,
+        made_at -> Timestamp,
+        made_by -> Nullable<Int8>,
     }
 }
 
@@ -178,6 +305,7 @@ table! {
         display_name -> Varchar,
         user_info -> Json,
         joined_at -> Timestamp,
+        last_seen_at -> Nullable<Timestamp>,
     }
 }
 
@@ -187,6 +315,8 @@ table! {
         user_id -> Int8,
         channel_id -> Int8,
         subscribed_at -> Timestamp,
+        durable -> Bool,
+        expires_at -> Nullable<Timestamp>,
     }
 }
 
@@ -217,9 +347,48 @@ table! {
         is_deleted -> Bool,
         modified_at -> Timestamp,
         modified_by -> Int8,
+        statement -> Varchar,
+        materialized -> Bool,
+    }
+}
+
+table! {
+    webhook (webhook_id) {
+        webhook_id -> Int8,
+        entity_id -> Int8,
+        name -> Varchar,
+        description -> Varchar,
+        url -> Varchar,
+        webhook_info -> Json,
+        is_deleted -> Bool,
+        modified_at -> Timestamp,
+        modified_by -> Int8,
+    }
+}
+
+table! {
+    webhook_delivery (webhook_delivery_id) {
+        webhook_delivery_id -> Int8,
+        entity_id -> Nullable<Int8>,
+        channel -> Jsonb,
+        action -> Varchar,
+        payload -> Jsonb,
+        status -> Varchar,
+        attempts -> Int4,
+        last_status_code -> Nullable<Int4>,
+        last_error -> Nullable<Varchar>,
+        created_at -> Timestamp,
+        next_attempt_at -> Timestamp,
+        delivered_at -> Nullable<Timestamp>,
+        channel_webhook_id -> Nullable<Int8>,
     }
 }
 
+joinable!(channel_webhook -> channel (channel_id));
+joinable!(channel_webhook -> user (created_by));
+joinable!(dead_letter -> message (message_id));
+joinable!(dead_letter -> user_channel (user_channel_id));
+joinable!(dead_letter -> webhook_delivery (webhook_delivery_id));
 joinable!(entity -> domain (domain_id));
 joinable!(entity -> scope (scope_id));
 joinable!(entity -> user (created_by));
@@ -228,13 +397,24 @@ joinable!(entity_tag -> tag (tag_id));
 joinable!(entity_usage -> entity (entity_id));
 joinable!(entity_usage -> user (used_by));
 joinable!(message -> channel (channel_id));
+joinable!(pending_message -> message (message_id));
+joinable!(pending_message -> user_channel (user_channel_id));
 joinable!(query -> entity (entity_id));
 joinable!(query -> user (modified_by));
+joinable!(query_scheduled_run -> entity (entity_id));
+joinable!(query_snapshot -> entity (entity_id));
+joinable!(query_snapshot -> user (created_by));
 joinable!(role_permission -> permission (permission_id));
 joinable!(role_permission -> role (role_id));
 joinable!(script -> entity (entity_id));
 joinable!(script -> user (modified_by));
+joinable!(script_run -> entity (entity_id));
+joinable!(script_run -> user (run_by));
+joinable!(script_secret -> entity (entity_id));
+joinable!(script_secret -> user (modified_by));
 joinable!(session -> user (user_id));
+joinable!(table_row_history -> entity (entity_id));
+joinable!(table_row_history -> user (made_by));
 joinable!(table_schema -> entity (entity_id));
 joinable!(table_schema -> user (modified_by));
 joinable!(table_schema_transaction -> table_schema (table_schema_id));
@@ -245,22 +425,35 @@ joinable!(user_role -> role (role_id));
 joinable!(user_role -> user (user_id));
 joinable!(view -> entity (entity_id));
 joinable!(view -> user (modified_by));
+joinable!(webhook -> entity (entity_id));
+joinable!(webhook -> user (modified_by));
+joinable!(webhook_delivery -> entity (entity_id));
+joinable!(webhook_delivery -> channel_webhook (channel_webhook_id));
 
 allow_tables_to_appear_in_same_query!(
+    audit_entry,
     channel,
+    channel_webhook,
+    dead_letter,
     domain,
     entity,
     entity_tag,
     entity_usage,
     invitation,
     message,
+    pending_message,
     permission,
     query,
+    query_scheduled_run,
+    query_snapshot,
     role,
     role_permission,
     scope,
     script,
+    script_run,
+    script_secret,
     session,
+    table_row_history,
     table_schema,
     table_schema_transaction,
     tag,
@@ -269,4 +462,6 @@ allow_tables_to_appear_in_same_query!(
     user_role,
     version,
     view,
+    webhook,
+    webhook_delivery,
 );
@@ -0,0 +1,88 @@
+use std::collections::HashMap;
+
+use diesel::prelude::*;
+use diesel;
+use diesel::sql_types::Text;
+
+use data;
+use data::error::DatastoreError;
+use data::script_version::ScriptVersion;
+use data::script_version::text_diff;
+use metastore::schema;
+use metastore::dbdata::RawScript;
+use model::entity::ConvertRaw;
+
+use state::ScriptHistory;
+use state::script_history::ScriptHistoryOps;
+
+impl<'a> ScriptHistory<'a> {
+    /// every version of the entity the script currently belongs to, newest first
+    fn get_versions(&self, script_name: &str) -> Result<Vec<RawScript>, DatastoreError> {
+        let query = r#"
+        SELECT "script".* FROM "script"
+        WHERE "script"."entity_id" = (
+            SELECT "current"."entity_id" FROM "script" AS "current"
+            INNER JOIN "entity"
+                ON "current"."entity_id" = "entity"."entity_id"
+            INNER JOIN "domain"
+                ON "entity"."domain_id" = "domain"."domain_id"
+            WHERE "current"."name" = $1 AND "domain"."name" = $2 AND NOT "current"."is_deleted"
+            ORDER BY "current"."modified_at" DESC
+            LIMIT 1
+        )
+        ORDER BY "script"."modified_at" DESC, "script"."script_id" DESC;
+        "#;
+
+        let domain_name = self.domain_name.to_owned().unwrap_or_default();
+        diesel::sql_query(query)
+            .bind::<Text, _>(script_name)
+            .bind::<Text, _>(&domain_name)
+            .load(self.conn)
+            .map_err(|err| DatastoreError::DbError(err.to_string()))
+    }
+}
+
+impl<'a> ScriptHistoryOps for ScriptHistory<'a> {
+    fn get_script_history(&self, script_name: &str) -> Result<Vec<ScriptVersion>, DatastoreError> {
+        let versions = self.get_versions(script_name)?;
+
+        let user_ids: Vec<i64> = versions.iter().map(|x| x.modified_by).collect();
+        let usernames: HashMap<i64, String> = schema::user::table
+            .select((schema::user::columns::user_id, schema::user::columns::username))
+            .filter(schema::user::columns::user_id.eq_any(user_ids))
+            .load::<(i64, String)>(self.conn)
+            .map_err(|err| DatastoreError::DbError(err.to_string()))?
+            .into_iter()
+            .collect();
+
+        let history = versions
+            .iter()
+            .enumerate()
+            .map(|(idx, raw_script)| {
+                // the versions are newest first, so the one before is the next one
+                let previous_text = versions.get(idx + 1).map(|x| x.script_text.as_str()).unwrap_or_default();
+                ScriptVersion {
+                    version: raw_script.script_id,
+                    script: raw_script.convert(),
+                    is_deleted: raw_script.is_deleted,
+                    modified_at: raw_script.modified_at,
+                    modified_by: usernames.get(&raw_script.modified_by).cloned(),
+                    diff: text_diff(previous_text, &raw_script.script_text),
+                }
+            })
+            .collect();
+
+        Ok(history)
+    }
+
+    fn get_script_version(&self, script_name: &str, version: i64) -> Result<Option<data::Script>, DatastoreError> {
+        let versions = self.get_versions(script_name)?;
+
+        let script = versions
+            .iter()
+            .find(|raw_script| raw_script.script_id == version && !raw_script.is_deleted)
+            .map(|raw_script| raw_script.convert());
+
+        Ok(script)
+    }
+}
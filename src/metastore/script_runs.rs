@@ -0,0 +1,182 @@
+use diesel::prelude::*;
+use diesel;
+use diesel::sql_types::BigInt;
+use diesel::sql_types::Bool;
+use diesel::sql_types::Integer;
+use diesel::sql_types::Nullable;
+use diesel::sql_types::Text;
+use diesel::sql_types::Timestamp;
+
+use data::error::DatastoreError;
+use data::script_run::ScriptRun;
+use metastore::schema;
+use metastore::dbdata;
+
+use state::ScriptRunRecorder;
+use state::script_runs::ScriptRunsOps;
+
+#[derive(Debug, QueryableByName)]
+struct RawEntityId {
+    #[sql_type = "BigInt"]
+    entity_id: i64,
+}
+
+#[derive(Debug, QueryableByName)]
+struct RawScriptRun {
+    #[sql_type = "BigInt"]
+    script_run_id: i64,
+    #[sql_type = "Timestamp"]
+    started_at: chrono::NaiveDateTime,
+    #[sql_type = "BigInt"]
+    duration_ms: i64,
+    #[sql_type = "Bool"]
+    successful: bool,
+    #[sql_type = "Nullable<Integer>"]
+    exit_code: Option<i32>,
+    #[sql_type = "Nullable<Text>"]
+    run_by: Option<String>,
+    #[sql_type = "Bool"]
+    scheduled: bool,
+    #[sql_type = "Nullable<Text>"]
+    stdout: Option<String>,
+    #[sql_type = "Nullable<Text>"]
+    stderr: Option<String>,
+}
+
+#[derive(Debug, QueryableByName)]
+struct RawLastRun {
+    #[sql_type = "Nullable<Timestamp>"]
+    started_at: Option<chrono::NaiveDateTime>,
+}
+
+impl<'a> ScriptRunRecorder<'a> {
+    fn get_entity_id(&self, script_name: &str) -> Result<i64, DatastoreError> {
+        let query = r#"
+        SELECT "script"."entity_id" FROM "script"
+        INNER JOIN "entity"
+            ON "script"."entity_id" = "entity"."entity_id"
+        INNER JOIN "domain"
+            ON "entity"."domain_id" = "domain"."domain_id"
+        WHERE "script"."name" = $1 AND "domain"."name" = $2 AND NOT "script"."is_deleted"
+        ORDER BY "script"."modified_at" DESC
+        LIMIT 1;
+        "#;
+
+        let domain_name = self.domain_name.to_owned().unwrap_or_default();
+        let result: Vec<RawEntityId> = diesel::sql_query(query)
+            .bind::<Text, _>(script_name)
+            .bind::<Text, _>(&domain_name)
+            .load(self.conn)
+            .map_err(|err| DatastoreError::DbError(err.to_string()))?;
+
+        result
+            .first()
+            .map(|x| x.entity_id)
+            .ok_or_else(|| {
+                error!("could not find the script {:?} in domain {:?}", script_name, &domain_name);
+                DatastoreError::InvalidState
+            })
+    }
+
+    /// the output is only read for a single run
+    fn load_runs(&self, script_name: &str, id: Option<i64>, limit: usize) -> Result<Vec<ScriptRun>, DatastoreError> {
+        let entity_id = self.get_entity_id(script_name)?;
+
+        let query = r#"
+        SELECT
+            "script_run"."script_run_id",
+            "script_run"."started_at",
+            "script_run"."duration_ms",
+            "script_run"."successful",
+            "script_run"."exit_code",
+            "user"."username" AS "run_by",
+            "script_run"."scheduled",
+            CASE WHEN $2::BIGINT IS NULL THEN NULL ELSE "script_run"."stdout" END AS "stdout",
+            CASE WHEN $2::BIGINT IS NULL THEN NULL ELSE "script_run"."stderr" END AS "stderr"
+        FROM "script_run"
+        LEFT JOIN "user"
+            ON "script_run"."run_by" = "user"."user_id"
+        WHERE "script_run"."entity_id" = $1 AND ($2::BIGINT IS NULL OR "script_run"."script_run_id" = $2)
+        ORDER BY "script_run"."started_at" DESC, "script_run"."script_run_id" DESC
+        LIMIT $3;
+        "#;
+
+        let raw_runs: Vec<RawScriptRun> = diesel::sql_query(query)
+            .bind::<BigInt, _>(entity_id)
+            .bind::<Nullable<BigInt>, _>(id)
+            .bind::<BigInt, _>(limit as i64)
+            .load(self.conn)
+            .map_err(|err| DatastoreError::DbError(err.to_string()))?;
+
+        let runs = raw_runs
+            .into_iter()
+            .map(|raw_run| ScriptRun {
+                id: raw_run.script_run_id,
+                script_name: script_name.to_owned(),
+                started_at: raw_run.started_at,
+                duration_ms: raw_run.duration_ms,
+                successful: raw_run.successful,
+                exit_code: raw_run.exit_code,
+                run_by: raw_run.run_by,
+                scheduled: raw_run.scheduled,
+                stdout: raw_run.stdout,
+                stderr: raw_run.stderr,
+            })
+            .collect();
+
+        Ok(runs)
+    }
+}
+
+impl<'a> ScriptRunsOps for ScriptRunRecorder<'a> {
+    fn record_run(&self, run: &ScriptRun) -> Result<i64, DatastoreError> {
+        let entity_id = self.get_entity_id(&run.script_name)?;
+
+        let raw_run = dbdata::NewRawScriptRun {
+            entity_id,
+            started_at: run.started_at,
+            duration_ms: run.duration_ms,
+            successful: run.successful,
+            exit_code: run.exit_code,
+            stdout: run.stdout.to_owned().unwrap_or_default(),
+            stderr: run.stderr.to_owned().unwrap_or_default(),
+            run_by: self.claims
+                .to_owned()
+                .filter(|x| !x.is_system())
+                .map(|x| x.get_user_id()),
+            scheduled: run.scheduled,
+        };
+
+        diesel::insert_into(schema::script_run::table)
+            .values(&raw_run)
+            .returning(schema::script_run::columns::script_run_id)
+            .get_result(self.conn)
+            .map_err(|err| DatastoreError::DbError(err.to_string()))
+    }
+
+    fn get_runs(&self, script_name: &str, limit: usize) -> Result<Vec<ScriptRun>, DatastoreError> {
+        self.load_runs(script_name, None, limit)
+    }
+
+    fn get_run(&self, script_name: &str, id: i64) -> Result<Option<ScriptRun>, DatastoreError> {
+        let mut runs = self.load_runs(script_name, Some(id), 1)?;
+        Ok(runs.pop())
+    }
+
+    fn last_scheduled_run(&self, script_name: &str) -> Result<Option<chrono::NaiveDateTime>, DatastoreError> {
+        let entity_id = self.get_entity_id(script_name)?;
+
+        let query = r#"
+        SELECT MAX("script_run"."started_at") AS "started_at"
+        FROM "script_run"
+        WHERE "script_run"."entity_id" = $1 AND "script_run"."scheduled";
+        "#;
+
+        let result: Vec<RawLastRun> = diesel::sql_query(query)
+            .bind::<BigInt, _>(entity_id)
+            .load(self.conn)
+            .map_err(|err| DatastoreError::DbError(err.to_string()))?;
+
+        Ok(result.first().and_then(|x| x.started_at))
+    }
+}
@@ -0,0 +1,249 @@
+use std::collections::HashMap;
+
+use diesel::prelude::*;
+use diesel;
+use diesel::sql_types::BigInt;
+use diesel::sql_types::Binary;
+use diesel::sql_types::Nullable;
+use diesel::sql_types::Text;
+use diesel::sql_types::Timestamp;
+
+use openssl::rand::rand_bytes;
+use openssl::sha::sha256;
+use openssl::symm::Cipher;
+use openssl::symm::decrypt_aead;
+use openssl::symm::encrypt_aead;
+
+use data::error::DatastoreError;
+use data::script_secret::ScriptSecret;
+use metastore::schema;
+
+use state::ScriptSecretStore;
+use state::script_secrets::ScriptSecretsOps;
+
+const NONCE_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+
+#[derive(Debug, QueryableByName)]
+struct RawEntityId {
+    #[sql_type = "BigInt"]
+    entity_id: i64,
+}
+
+#[derive(Debug, QueryableByName)]
+struct RawScriptSecret {
+    #[sql_type = "Text"]
+    name: String,
+    #[sql_type = "Timestamp"]
+    modified_at: chrono::NaiveDateTime,
+    #[sql_type = "Nullable<Text>"]
+    modified_by: Option<String>,
+}
+
+#[derive(Debug, QueryableByName)]
+struct RawSecretValue {
+    #[sql_type = "Text"]
+    name: String,
+    #[sql_type = "Binary"]
+    nonce: Vec<u8>,
+    #[sql_type = "Binary"]
+    ciphertext: Vec<u8>,
+}
+
+/// aes-256-gcm, the name of the secret is authenticated as well so that the values can't be swapped.
+/// The secrets of the webhooks are encrypted with it as well
+pub fn encrypt(secrets_key: &str, name: &str, value: &str) -> Result<(Vec<u8>, Vec<u8>), DatastoreError> {
+    let key = sha256(secrets_key.as_bytes());
+    let mut nonce = vec![0; NONCE_LEN];
+    let mut tag = vec![0; TAG_LEN];
+
+    rand_bytes(&mut nonce)
+        .and_then(|_| encrypt_aead(Cipher::aes_256_gcm(), &key, Some(&nonce), name.as_bytes(), value.as_bytes(), &mut tag))
+        .map(|mut ciphertext| {
+            ciphertext.extend(tag);
+            (nonce, ciphertext)
+        })
+        .map_err(|err| {
+            error!("could not encrypt the secret {:?}: {:?}", name, &err);
+            DatastoreError::InternalError
+        })
+}
+
+pub fn decrypt(secrets_key: &str, name: &str, nonce: &[u8], ciphertext: &[u8]) -> Result<String, DatastoreError> {
+    if ciphertext.len() < TAG_LEN {
+        return Err(DatastoreError::DecryptionError(name.to_owned()));
+    }
+
+    let key = sha256(secrets_key.as_bytes());
+    let (data, tag) = ciphertext.split_at(ciphertext.len() - TAG_LEN);
+
+    decrypt_aead(Cipher::aes_256_gcm(), &key, Some(nonce), name.as_bytes(), data, tag)
+        .ok()
+        .and_then(|value| String::from_utf8(value).ok())
+        .ok_or_else(|| DatastoreError::DecryptionError(name.to_owned()))
+}
+
+impl<'a> ScriptSecretStore<'a> {
+    fn get_secrets_key(&self) -> Result<&'a str, DatastoreError> {
+        match self.secrets_key {
+            Some(secrets_key) => Ok(secrets_key.as_str()),
+            None => Err(DatastoreError::NoSecretsKey),
+        }
+    }
+
+    fn get_entity_id(&self, script_name: &str) -> Result<i64, DatastoreError> {
+        let query = r#"
+        SELECT "script"."entity_id" FROM "script"
+        INNER JOIN "entity"
+            ON "script"."entity_id" = "entity"."entity_id"
+        INNER JOIN "domain"
+            ON "entity"."domain_id" = "domain"."domain_id"
+        WHERE "script"."name" = $1 AND "domain"."name" = $2 AND NOT "script"."is_deleted"
+        ORDER BY "script"."modified_at" DESC
+        LIMIT 1;
+        "#;
+
+        let domain_name = self.domain_name.to_owned().unwrap_or_default();
+        let result: Vec<RawEntityId> = diesel::sql_query(query)
+            .bind::<Text, _>(script_name)
+            .bind::<Text, _>(&domain_name)
+            .load(self.conn)
+            .map_err(|err| DatastoreError::DbError(err.to_string()))?;
+
+        result
+            .first()
+            .map(|x| x.entity_id)
+            .ok_or_else(|| {
+                error!("could not find the script {:?} in domain {:?}", script_name, &domain_name);
+                DatastoreError::InvalidState
+            })
+    }
+}
+
+impl<'a> ScriptSecretsOps for ScriptSecretStore<'a> {
+    fn set_secret(&self, script_name: &str, name: &str, value: &str) -> Result<(), DatastoreError> {
+        let secrets_key = self.get_secrets_key()?;
+        let entity_id = self.get_entity_id(script_name)?;
+        let (nonce, ciphertext) = encrypt(secrets_key, name, value)?;
+
+        let query = r#"
+        INSERT INTO "script_secret" ("entity_id", "name", "nonce", "ciphertext", "modified_at", "modified_by")
+        VALUES ($1, $2, $3, $4, NOW(), $5)
+        ON CONFLICT ("entity_id", "name") DO UPDATE SET
+            "nonce" = EXCLUDED."nonce",
+            "ciphertext" = EXCLUDED."ciphertext",
+            "modified_at" = EXCLUDED."modified_at",
+            "modified_by" = EXCLUDED."modified_by";
+        "#;
+
+        let modified_by = self.claims
+            .to_owned()
+            .filter(|x| !x.is_system())
+            .map(|x| x.get_user_id());
+
+        diesel::sql_query(query)
+            .bind::<BigInt, _>(entity_id)
+            .bind::<Text, _>(name)
+            .bind::<Binary, _>(nonce)
+            .bind::<Binary, _>(ciphertext)
+            .bind::<Nullable<BigInt>, _>(modified_by)
+            .execute(self.conn)
+            .map_err(|err| DatastoreError::DbError(err.to_string()))?;
+
+        Ok(())
+    }
+
+    fn remove_secret(&self, script_name: &str, name: &str) -> Result<bool, DatastoreError> {
+        use metastore::schema::script_secret::columns;
+
+        let entity_id = self.get_entity_id(script_name)?;
+
+        let removed = diesel::delete(schema::script_secret::table)
+            .filter(columns::entity_id.eq(entity_id))
+            .filter(columns::name.eq(name))
+            .execute(self.conn)
+            .map_err(|err| DatastoreError::DbError(err.to_string()))?;
+
+        Ok(removed > 0)
+    }
+
+    fn get_secrets(&self, script_name: &str) -> Result<Vec<ScriptSecret>, DatastoreError> {
+        let entity_id = self.get_entity_id(script_name)?;
+
+        let query = r#"
+        SELECT
+            "script_secret"."name",
+            "script_secret"."modified_at",
+            "user"."username" AS "modified_by"
+        FROM "script_secret"
+        LEFT JOIN "user"
+            ON "script_secret"."modified_by" = "user"."user_id"
+        WHERE "script_secret"."entity_id" = $1
+        ORDER BY "script_secret"."name";
+        "#;
+
+        let raw_secrets: Vec<RawScriptSecret> = diesel::sql_query(query)
+            .bind::<BigInt, _>(entity_id)
+            .load(self.conn)
+            .map_err(|err| DatastoreError::DbError(err.to_string()))?;
+
+        let secrets = raw_secrets
+            .into_iter()
+            .map(|raw_secret| ScriptSecret {
+                script_name: script_name.to_owned(),
+                name: raw_secret.name,
+                modified_at: raw_secret.modified_at,
+                modified_by: raw_secret.modified_by,
+            })
+            .collect();
+
+        Ok(secrets)
+    }
+
+    fn get_secret_values(&self, script_name: &str) -> Result<HashMap<String, String>, DatastoreError> {
+        let entity_id = self.get_entity_id(script_name)?;
+
+        let query = r#"
+        SELECT "name", "nonce", "ciphertext" FROM "script_secret"
+        WHERE "entity_id" = $1;
+        "#;
+
+        let raw_values: Vec<RawSecretValue> = diesel::sql_query(query)
+            .bind::<BigInt, _>(entity_id)
+            .load(self.conn)
+            .map_err(|err| DatastoreError::DbError(err.to_string()))?;
+
+        // a script without secrets runs without the key as well
+        if raw_values.is_empty() {
+            return Ok(HashMap::new());
+        }
+
+        let secrets_key = self.get_secrets_key()?;
+        raw_values
+            .into_iter()
+            .map(|raw_value| {
+                let value = decrypt(secrets_key, &raw_value.name, &raw_value.nonce, &raw_value.ciphertext)?;
+                Ok((raw_value.name, value))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_encrypt_secret() {
+        let (nonce, ciphertext) = encrypt("server key", "API_TOKEN", "hunter2").unwrap();
+        assert_eq!(nonce.len(), NONCE_LEN);
+        assert_eq!(ciphertext.len(), "hunter2".len() + TAG_LEN);
+        assert_eq!(decrypt("server key", "API_TOKEN", &nonce, &ciphertext).unwrap(), "hunter2");
+
+        assert_eq!(decrypt("other key", "API_TOKEN", &nonce, &ciphertext), Err(DatastoreError::DecryptionError("API_TOKEN".to_string())));
+        assert!(decrypt("server key", "OTHER_TOKEN", &nonce, &ciphertext).is_err());
+
+        let (other_nonce, _) = encrypt("server key", "API_TOKEN", "hunter2").unwrap();
+        assert_ne!(nonce, other_nonce);
+    }
+}
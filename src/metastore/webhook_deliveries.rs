@@ -0,0 +1,597 @@
+use diesel::prelude::*;
+use diesel;
+use diesel::sql_types::BigInt;
+use diesel::sql_types::Binary;
+use diesel::sql_types::Bool;
+use diesel::sql_types::Double;
+use diesel::sql_types::Integer;
+use diesel::sql_types::Json;
+use diesel::sql_types::Jsonb;
+use diesel::sql_types::Nullable;
+use diesel::sql_types::Text;
+use diesel::sql_types::Timestamp;
+
+use diesel::result::DatabaseErrorKind;
+use diesel::result::Error as DbError;
+
+use connection::executor::Conn;
+use data::channels::Channels;
+use data::error::DatastoreError;
+use data::webhook::ChannelWebhook;
+use data::webhook::DeliveryStatus;
+use data::webhook::DueDelivery;
+use data::webhook::EncryptedSecret;
+use data::webhook::NewChannelWebhook;
+use data::webhook::RetryPolicy;
+use data::webhook::WebhookDelivery;
+use data::webhook::channel_webhook_secret_name;
+use data::webhook::sign;
+use data::webhook::webhook_secret_name;
+
+use metastore::pub_sub::get_or_create_channel;
+use metastore::script_secrets::decrypt;
+use metastore::script_secrets::encrypt;
+use state::WebhookDeliveryLog;
+use state::webhook_deliveries::WebhookDeliveriesOps;
+
+/// the latest version of every webhook, the deleted ones included
+const LATEST_WEBHOOKS: &'static str = r#"
+    SELECT DISTINCT ON ("webhook"."entity_id") "webhook".* FROM "webhook"
+    ORDER BY "webhook"."entity_id", "webhook"."modified_at" DESC
+"#;
+
+#[derive(Debug, QueryableByName)]
+struct RawEntityId {
+    #[sql_type = "BigInt"]
+    entity_id: i64,
+}
+
+#[derive(Debug, QueryableByName)]
+struct RawDueDelivery {
+    #[sql_type = "BigInt"]
+    webhook_delivery_id: i64,
+    #[sql_type = "Jsonb"]
+    channel: serde_json::Value,
+    #[sql_type = "Text"]
+    action: String,
+    #[sql_type = "Jsonb"]
+    payload: serde_json::Value,
+    #[sql_type = "Integer"]
+    attempts: i32,
+    #[sql_type = "Timestamp"]
+    created_at: chrono::NaiveDateTime,
+    #[sql_type = "Text"]
+    name: String,
+    #[sql_type = "Text"]
+    url: String,
+    #[sql_type = "Json"]
+    webhook_info: serde_json::Value,
+}
+
+#[derive(Debug, QueryableByName)]
+struct RawDueChannelDelivery {
+    #[sql_type = "BigInt"]
+    webhook_delivery_id: i64,
+    #[sql_type = "Jsonb"]
+    channel: serde_json::Value,
+    #[sql_type = "Text"]
+    action: String,
+    #[sql_type = "Jsonb"]
+    payload: serde_json::Value,
+    #[sql_type = "Integer"]
+    attempts: i32,
+    #[sql_type = "Timestamp"]
+    created_at: chrono::NaiveDateTime,
+    #[sql_type = "BigInt"]
+    channel_webhook_id: i64,
+    #[sql_type = "Text"]
+    url: String,
+    #[sql_type = "Nullable<Text>"]
+    secret: Option<String>,
+    #[sql_type = "Nullable<Binary>"]
+    secret_nonce: Option<Vec<u8>>,
+    #[sql_type = "Nullable<Binary>"]
+    secret_ciphertext: Option<Vec<u8>>,
+    #[sql_type = "Jsonb"]
+    retry: serde_json::Value,
+}
+
+#[derive(Debug, QueryableByName)]
+struct RawChannelWebhook {
+    #[sql_type = "BigInt"]
+    channel_webhook_id: i64,
+    #[sql_type = "Jsonb"]
+    channel: serde_json::Value,
+    #[sql_type = "Text"]
+    url: String,
+    #[sql_type = "Jsonb"]
+    retry: serde_json::Value,
+    #[sql_type = "Timestamp"]
+    created_at: chrono::NaiveDateTime,
+}
+
+impl RawChannelWebhook {
+    fn into_channel_webhook(self) -> Result<ChannelWebhook, DatastoreError> {
+        let channel = serde_json::from_value(self.channel)
+            .map_err(|_| DatastoreError::DeserializationError)?;
+
+        Ok(ChannelWebhook {
+            id: self.channel_webhook_id,
+            channel,
+            url: self.url,
+            retry: serde_json::from_value(self.retry).unwrap_or_default(),
+            created_at: self.created_at,
+        })
+    }
+}
+
+#[derive(Debug, QueryableByName)]
+struct RawWebhookDelivery {
+    #[sql_type = "BigInt"]
+    webhook_delivery_id: i64,
+    #[sql_type = "Jsonb"]
+    channel: serde_json::Value,
+    #[sql_type = "Text"]
+    action: String,
+    #[sql_type = "Text"]
+    status: String,
+    #[sql_type = "Integer"]
+    attempts: i32,
+    #[sql_type = "Nullable<Integer>"]
+    last_status_code: Option<i32>,
+    #[sql_type = "Nullable<Text>"]
+    last_error: Option<String>,
+    #[sql_type = "Timestamp"]
+    created_at: chrono::NaiveDateTime,
+    #[sql_type = "Timestamp"]
+    next_attempt_at: chrono::NaiveDateTime,
+    #[sql_type = "Nullable<Timestamp>"]
+    delivered_at: Option<chrono::NaiveDateTime>,
+}
+
+/// Queues the message for every webhook of the domain subscribed to the channel, returns how many there were
+/// called when the message is published, so that it is only sent if the action is committed
+pub fn enqueue_deliveries(
+    conn: &Conn,
+    domain_name: &Option<String>,
+    channel: &Channels,
+    action_name: &str,
+    data: &serde_json::Value,
+) -> Result<usize, DatastoreError> {
+    let domain_name = match domain_name {
+        Some(domain_name) => domain_name,
+        None => return Ok(0),
+    };
+
+    let query = format!(r#"
+    INSERT INTO "webhook_delivery" ("entity_id", "channel", "action", "payload")
+    SELECT "latest_webhook"."entity_id", $1, $2, $3 FROM ({latest_webhooks}) AS "latest_webhook"
+    INNER JOIN "entity"
+        ON "latest_webhook"."entity_id" = "entity"."entity_id"
+    INNER JOIN "domain"
+        ON "entity"."domain_id" = "domain"."domain_id"
+    WHERE "domain"."name" = $4 AND NOT "latest_webhook"."is_deleted"
+        AND ("latest_webhook"."webhook_info"::jsonb -> 'channels') @> jsonb_build_array($1);
+    "#, latest_webhooks = LATEST_WEBHOOKS);
+
+    let channel_json = serde_json::to_value(channel)
+        .map_err(|err| {
+            error!("Could not serialize value {:?} error: {:?}", channel, &err);
+            DatastoreError::SerializationError
+        })?;
+
+    diesel::sql_query(query)
+        .bind::<Jsonb, _>(&channel_json)
+        .bind::<Text, _>(action_name)
+        .bind::<Jsonb, _>(data)
+        .bind::<Text, _>(domain_name)
+        .execute(conn)
+        .map_err(|err| DatastoreError::DbError(err.to_string()))
+}
+
+/// Queues the message for every webhook attached to the channel, whatever the domain it was published from
+pub fn enqueue_channel_deliveries(
+    conn: &Conn,
+    channel_id: i64,
+    channel: &Channels,
+    action_name: &str,
+    data: &serde_json::Value,
+) -> Result<usize, DatastoreError> {
+    let query = r#"
+    INSERT INTO "webhook_delivery" ("channel_webhook_id", "channel", "action", "payload")
+    SELECT "channel_webhook_id", $2, $3, $4 FROM "channel_webhook"
+    WHERE "channel_id" = $1;
+    "#;
+
+    let channel_json = serde_json::to_value(channel)
+        .map_err(|err| {
+            error!("Could not serialize value {:?} error: {:?}", channel, &err);
+            DatastoreError::SerializationError
+        })?;
+
+    diesel::sql_query(query)
+        .bind::<BigInt, _>(channel_id)
+        .bind::<Jsonb, _>(&channel_json)
+        .bind::<Text, _>(action_name)
+        .bind::<Jsonb, _>(data)
+        .execute(conn)
+        .map_err(|err| DatastoreError::DbError(err.to_string()))
+}
+
+impl<'a> WebhookDeliveryLog<'a> {
+    fn get_entity_id(&self, webhook_name: &str) -> Result<i64, DatastoreError> {
+        let query = r#"
+        SELECT "webhook"."entity_id" FROM "webhook"
+        INNER JOIN "entity"
+            ON "webhook"."entity_id" = "entity"."entity_id"
+        INNER JOIN "domain"
+            ON "entity"."domain_id" = "domain"."domain_id"
+        WHERE "webhook"."name" = $1 AND "domain"."name" = $2 AND NOT "webhook"."is_deleted"
+        ORDER BY "webhook"."modified_at" DESC
+        LIMIT 1;
+        "#;
+
+        let domain_name = self.domain_name.to_owned().unwrap_or_default();
+        let result: Vec<RawEntityId> = diesel::sql_query(query)
+            .bind::<Text, _>(webhook_name)
+            .bind::<Text, _>(&domain_name)
+            .load(self.conn)
+            .map_err(|err| DatastoreError::DbError(err.to_string()))?;
+
+        result
+            .first()
+            .map(|x| x.entity_id)
+            .ok_or_else(|| {
+                error!("could not find the webhook {:?} in domain {:?}", webhook_name, &domain_name);
+                DatastoreError::InvalidState
+            })
+    }
+
+    fn get_secrets_key(&self) -> Result<&'a str, DatastoreError> {
+        match self.secrets_key {
+            Some(secrets_key) => Ok(secrets_key.as_str()),
+            None => Err(DatastoreError::NoSecretsKey),
+        }
+    }
+
+    /// the secrets stored before they were encrypted are still in plain text
+    fn decrypt_secret(&self, name: &str, plain_text: Option<&str>, encrypted: Option<EncryptedSecret>) -> Result<String, DatastoreError> {
+        match (plain_text, encrypted) {
+            (_, Some(encrypted)) => decrypt(self.get_secrets_key()?, name, &encrypted.nonce, &encrypted.ciphertext),
+            (Some(plain_text), None) => Ok(plain_text.to_owned()),
+            (None, None) => Err(DatastoreError::DecryptionError(name.to_owned())),
+        }
+    }
+}
+
+impl<'a> WebhookDeliveriesOps for WebhookDeliveryLog<'a> {
+    fn claim_due_deliveries(&self, limit: usize, claim_seconds: u64) -> Result<Vec<DueDelivery>, DatastoreError> {
+        let query = format!(r#"
+        WITH "latest_webhook" AS ({latest_webhooks}), "due" AS (
+            SELECT "webhook_delivery"."webhook_delivery_id" FROM "webhook_delivery"
+            INNER JOIN "latest_webhook"
+                ON "webhook_delivery"."entity_id" = "latest_webhook"."entity_id"
+            INNER JOIN "entity"
+                ON "webhook_delivery"."entity_id" = "entity"."entity_id"
+            INNER JOIN "domain"
+                ON "entity"."domain_id" = "domain"."domain_id"
+            WHERE "domain"."name" = $1 AND NOT "latest_webhook"."is_deleted"
+                AND "webhook_delivery"."status" = 'pending' AND "webhook_delivery"."next_attempt_at" <= NOW()
+            ORDER BY "webhook_delivery"."next_attempt_at"
+            LIMIT $2
+            FOR UPDATE OF "webhook_delivery" SKIP LOCKED
+        )
+        UPDATE "webhook_delivery" SET "next_attempt_at" = NOW() + make_interval(secs => $3)
+        FROM "due", "latest_webhook"
+        WHERE "webhook_delivery"."webhook_delivery_id" = "due"."webhook_delivery_id"
+            AND "webhook_delivery"."entity_id" = "latest_webhook"."entity_id"
+        RETURNING
+            "webhook_delivery"."webhook_delivery_id",
+            "webhook_delivery"."channel",
+            "webhook_delivery"."action",
+            "webhook_delivery"."payload",
+            "webhook_delivery"."attempts",
+            "webhook_delivery"."created_at",
+            "latest_webhook"."name",
+            "latest_webhook"."url",
+            "latest_webhook"."webhook_info";
+        "#, latest_webhooks = LATEST_WEBHOOKS);
+
+        let domain_name = self.domain_name.to_owned().unwrap_or_default();
+        let raw_deliveries: Vec<RawDueDelivery> = diesel::sql_query(query)
+            .bind::<Text, _>(&domain_name)
+            .bind::<BigInt, _>(limit as i64)
+            .bind::<Double, _>(claim_seconds as f64)
+            .load(self.conn)
+            .map_err(|err| DatastoreError::DbError(err.to_string()))?;
+
+        raw_deliveries
+            .into_iter()
+            .map(|raw_delivery| {
+                let body = json!({
+                    "id": raw_delivery.webhook_delivery_id,
+                    "webhook": raw_delivery.name,
+                    "channel": raw_delivery.channel,
+                    "action": raw_delivery.action,
+                    "data": raw_delivery.payload,
+                    "publishedAt": raw_delivery.created_at,
+                }).to_string();
+
+                let raw_secret = &raw_delivery.webhook_info["secret"];
+                let secret = self.decrypt_secret(
+                    &webhook_secret_name(&raw_delivery.name),
+                    raw_secret.as_str(),
+                    EncryptedSecret::from_value(raw_secret))?;
+                let signature = sign(&secret, body.as_bytes())
+                    .map_err(|err| {
+                        error!("could not sign the delivery {:?}: {:?}", raw_delivery.webhook_delivery_id, &err);
+                        DatastoreError::InternalError
+                    })?;
+
+                Ok(DueDelivery {
+                    id: raw_delivery.webhook_delivery_id,
+                    webhook_name: raw_delivery.name,
+                    url: raw_delivery.url,
+                    retry: serde_json::from_value(raw_delivery.webhook_info["retry"].to_owned()).unwrap_or_default(),
+                    attempts: raw_delivery.attempts,
+                    body,
+                    signature,
+                })
+            })
+            .collect()
+    }
+
+    fn record_attempt(
+        &self,
+        id: i64,
+        attempts: i32,
+        retry: &RetryPolicy,
+        status_code: Option<i32>,
+        error: Option<String>,
+    ) -> Result<(), DatastoreError> {
+        let query = r#"
+        UPDATE "webhook_delivery" SET
+            "attempts" = "attempts" + 1,
+            "last_status_code" = $2,
+            "last_error" = $3,
+            "status" = CASE
+                WHEN $4 THEN 'delivered'
+                WHEN "attempts" + 1 >= $5 THEN 'failed'
+                ELSE 'pending'
+            END,
+            "delivered_at" = CASE WHEN $4 THEN NOW() ELSE NULL END,
+            "next_attempt_at" = NOW() + make_interval(secs => $6)
+        WHERE "webhook_delivery_id" = $1;
+        "#;
+
+        let delivered = error.is_none() && status_code.map(|x| x >= 200 && x < 300).unwrap_or(false);
+        let backoff = retry.backoff(attempts as u32 + 1);
+
+        diesel::sql_query(query)
+            .bind::<BigInt, _>(id)
+            .bind::<Nullable<Integer>, _>(status_code)
+            .bind::<Nullable<Text>, _>(error)
+            .bind::<Bool, _>(delivered)
+            .bind::<Integer, _>(retry.max_attempts as i32)
+            .bind::<Double, _>(backoff as f64)
+            .execute(self.conn)
+            .map_err(|err| DatastoreError::DbError(err.to_string()))?;
+
+        // given up, it's kept as a dead letter until an admin redelivers it
+        let query = r#"
+        INSERT INTO "dead_letter" ("channel", "payload", "attempts", "last_error", "webhook_delivery_id")
+        SELECT "channel", "payload", "attempts", "last_error", "webhook_delivery_id" FROM "webhook_delivery"
+        WHERE "webhook_delivery_id" = $1 AND "status" = 'failed'
+        ON CONFLICT ("webhook_delivery_id") DO NOTHING;
+        "#;
+
+        diesel::sql_query(query)
+            .bind::<BigInt, _>(id)
+            .execute(self.conn)
+            .map_err(|err| DatastoreError::DbError(err.to_string()))?;
+
+        Ok(())
+    }
+
+    fn get_deliveries(&self, webhook_name: &str, limit: usize) -> Result<Vec<WebhookDelivery>, DatastoreError> {
+        let entity_id = self.get_entity_id(webhook_name)?;
+
+        let query = r#"
+        SELECT
+            "webhook_delivery_id", "channel", "action", "status", "attempts", "last_status_code", "last_error",
+            "created_at", "next_attempt_at", "delivered_at"
+        FROM "webhook_delivery"
+        WHERE "entity_id" = $1
+        ORDER BY "created_at" DESC, "webhook_delivery_id" DESC
+        LIMIT $2;
+        "#;
+
+        let raw_deliveries: Vec<RawWebhookDelivery> = diesel::sql_query(query)
+            .bind::<BigInt, _>(entity_id)
+            .bind::<BigInt, _>(limit as i64)
+            .load(self.conn)
+            .map_err(|err| DatastoreError::DbError(err.to_string()))?;
+
+        raw_deliveries
+            .into_iter()
+            .map(|raw_delivery| {
+                let channel = serde_json::from_value(raw_delivery.channel)
+                    .map_err(|_| DatastoreError::SerializationError)?;
+                let status = DeliveryStatus::from_str(&raw_delivery.status)
+                    .ok_or(DatastoreError::InvalidState)?;
+
+                Ok(WebhookDelivery {
+                    id: raw_delivery.webhook_delivery_id,
+                    webhook_name: webhook_name.to_owned(),
+                    channel,
+                    action: raw_delivery.action,
+                    status,
+                    attempts: raw_delivery.attempts,
+                    last_status_code: raw_delivery.last_status_code,
+                    last_error: raw_delivery.last_error,
+                    created_at: raw_delivery.created_at,
+                    next_attempt_at: raw_delivery.next_attempt_at,
+                    delivered_at: raw_delivery.delivered_at,
+                })
+            })
+            .collect()
+    }
+
+    fn claim_due_channel_deliveries(&self, limit: usize, claim_seconds: u64) -> Result<Vec<DueDelivery>, DatastoreError> {
+        let query = r#"
+        WITH "due" AS (
+            SELECT "webhook_delivery_id" FROM "webhook_delivery"
+            WHERE "channel_webhook_id" IS NOT NULL
+                AND "status" = 'pending' AND "next_attempt_at" <= NOW()
+            ORDER BY "next_attempt_at"
+            LIMIT $1
+            FOR UPDATE SKIP LOCKED
+        )
+        UPDATE "webhook_delivery" SET "next_attempt_at" = NOW() + make_interval(secs => $2)
+        FROM "due", "channel_webhook"
+        WHERE "webhook_delivery"."webhook_delivery_id" = "due"."webhook_delivery_id"
+            AND "webhook_delivery"."channel_webhook_id" = "channel_webhook"."channel_webhook_id"
+        RETURNING
+            "webhook_delivery"."webhook_delivery_id",
+            "webhook_delivery"."channel",
+            "webhook_delivery"."action",
+            "webhook_delivery"."payload",
+            "webhook_delivery"."attempts",
+            "webhook_delivery"."created_at",
+            "channel_webhook"."channel_webhook_id",
+            "channel_webhook"."url",
+            "channel_webhook"."secret",
+            "channel_webhook"."secret_nonce",
+            "channel_webhook"."secret_ciphertext",
+            "channel_webhook"."retry";
+        "#;
+
+        let raw_deliveries: Vec<RawDueChannelDelivery> = diesel::sql_query(query)
+            .bind::<BigInt, _>(limit as i64)
+            .bind::<Double, _>(claim_seconds as f64)
+            .load(self.conn)
+            .map_err(|err| DatastoreError::DbError(err.to_string()))?;
+
+        raw_deliveries
+            .into_iter()
+            .map(|raw_delivery| {
+                let body = json!({
+                    "id": raw_delivery.webhook_delivery_id,
+                    "channelWebhook": raw_delivery.channel_webhook_id,
+                    "channel": raw_delivery.channel,
+                    "action": raw_delivery.action,
+                    "data": raw_delivery.payload,
+                    "publishedAt": raw_delivery.created_at,
+                }).to_string();
+
+                let encrypted = match (raw_delivery.secret_nonce, raw_delivery.secret_ciphertext) {
+                    (Some(nonce), Some(ciphertext)) => Some(EncryptedSecret { nonce, ciphertext }),
+                    _ => None,
+                };
+                let secret = self.decrypt_secret(
+                    &channel_webhook_secret_name(&raw_delivery.url),
+                    raw_delivery.secret.as_ref().map(|x| x.as_str()),
+                    encrypted)?;
+                let signature = sign(&secret, body.as_bytes())
+                    .map_err(|err| {
+                        error!("could not sign the delivery {:?}: {:?}", raw_delivery.webhook_delivery_id, &err);
+                        DatastoreError::InternalError
+                    })?;
+
+                Ok(DueDelivery {
+                    id: raw_delivery.webhook_delivery_id,
+                    webhook_name: raw_delivery.url.to_owned(),
+                    url: raw_delivery.url,
+                    retry: serde_json::from_value(raw_delivery.retry).unwrap_or_default(),
+                    attempts: raw_delivery.attempts,
+                    body,
+                    signature,
+                })
+            })
+            .collect()
+    }
+
+    fn add_channel_webhook(&self, webhook: &NewChannelWebhook, created_by: Option<i64>) -> Result<ChannelWebhook, DatastoreError> {
+        let raw_channel = get_or_create_channel(self.conn, &webhook.channel)
+            .map_err(|err| DatastoreError::DbError(err.to_string()))?;
+
+        let query = r#"
+        WITH "created" AS (
+            INSERT INTO "channel_webhook" ("channel_id", "url", "secret_nonce", "secret_ciphertext", "retry", "created_by")
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING *
+        )
+        SELECT "created"."channel_webhook_id", "channel"."data" AS "channel", "created"."url", "created"."retry", "created"."created_at"
+        FROM "created"
+        INNER JOIN "channel"
+            ON "created"."channel_id" = "channel"."channel_id";
+        "#;
+
+        let retry = serde_json::to_value(&webhook.retry)
+            .map_err(|_| DatastoreError::SerializationError)?;
+        let (nonce, ciphertext) = encrypt(self.get_secrets_key()?, &channel_webhook_secret_name(&webhook.url), &webhook.secret)?;
+        let raw_webhooks: Vec<RawChannelWebhook> = diesel::sql_query(query)
+            .bind::<BigInt, _>(raw_channel.channel_id)
+            .bind::<Text, _>(&webhook.url)
+            .bind::<Binary, _>(&nonce)
+            .bind::<Binary, _>(&ciphertext)
+            .bind::<Jsonb, _>(&retry)
+            .bind::<Nullable<BigInt>, _>(created_by)
+            .load(self.conn)
+            .map_err(|err| match err {
+                DbError::DatabaseError(DatabaseErrorKind::UniqueViolation, _) => DatastoreError::AlreadyExists,
+                _ => DatastoreError::DbError(err.to_string()),
+            })?;
+
+        raw_webhooks
+            .into_iter()
+            .next()
+            .ok_or(DatastoreError::InvalidState)
+            .and_then(|raw_webhook| raw_webhook.into_channel_webhook())
+    }
+
+    fn remove_channel_webhook(&self, id: i64) -> Result<Option<ChannelWebhook>, DatastoreError> {
+        let query = r#"
+        WITH "removed" AS (
+            DELETE FROM "channel_webhook" WHERE "channel_webhook_id" = $1
+            RETURNING *
+        )
+        SELECT "removed"."channel_webhook_id", "channel"."data" AS "channel", "removed"."url", "removed"."retry", "removed"."created_at"
+        FROM "removed"
+        INNER JOIN "channel"
+            ON "removed"."channel_id" = "channel"."channel_id";
+        "#;
+
+        let raw_webhooks: Vec<RawChannelWebhook> = diesel::sql_query(query)
+            .bind::<BigInt, _>(id)
+            .load(self.conn)
+            .map_err(|err| DatastoreError::DbError(err.to_string()))?;
+
+        raw_webhooks
+            .into_iter()
+            .next()
+            .map(|raw_webhook| raw_webhook.into_channel_webhook())
+            .map_or(Ok(None), |res| res.map(Some))
+    }
+
+    fn get_channel_webhooks(&self) -> Result<Vec<ChannelWebhook>, DatastoreError> {
+        let query = r#"
+        SELECT
+            "channel_webhook"."channel_webhook_id", "channel"."data" AS "channel", "channel_webhook"."url",
+            "channel_webhook"."retry", "channel_webhook"."created_at"
+        FROM "channel_webhook"
+        INNER JOIN "channel"
+            ON "channel_webhook"."channel_id" = "channel"."channel_id"
+        ORDER BY "channel_webhook"."channel_webhook_id" ASC;
+        "#;
+
+        let raw_webhooks: Vec<RawChannelWebhook> = diesel::sql_query(query)
+            .load(self.conn)
+            .map_err(|err| DatastoreError::DbError(err.to_string()))?;
+
+        raw_webhooks
+            .into_iter()
+            .map(|raw_webhook| raw_webhook.into_channel_webhook())
+            .collect()
+    }
+}
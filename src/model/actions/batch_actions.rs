@@ -0,0 +1,153 @@
+use std::fmt::Debug;
+use std::marker::PhantomData;
+
+use model::actions::results::*;
+use model::actions::error::Error;
+use model::actions::Action;
+use model::actions::ActionRes;
+use model::actions::ActionResult;
+
+use state::StateFunctions;
+use state::ActionState;
+
+/// An action of a batch, with its type erased so that a batch can hold different ones
+pub trait BatchStep<S = ActionState>: Send + Debug {
+    /// the serialized result of the action
+    fn call_step(&self, state: &S) -> Result<serde_json::Value, Error>;
+}
+
+impl<A, S> BatchStep<S> for A
+    where
+        A: Action<S>,
+{
+    fn call_step(&self, state: &S) -> Result<serde_json::Value, Error> {
+        let res = self.call(state)?;
+        serde_json::to_value(res.get_data_ref())
+            .map_err(|err| Error::SerializationError(err.to_string()))
+    }
+}
+
+#[derive(Debug)]
+pub struct BatchCall<S = ActionState> {
+    pub procedure: String,
+    pub step: Box<BatchStep<S>>,
+}
+
+/// Runs the calls one after the other, in the order they were given. The calls keep their own decorators,
+/// so each one is checked as if it was called by itself
+/// If `atomic`, the calls run in one transaction which is rolled back when one of them fails, otherwise
+/// every call is run and the failed ones have their error in the results
+#[derive(Debug)]
+pub struct RunBatch<S = ActionState> {
+    pub calls: Vec<BatchCall<S>>,
+    pub atomic: bool,
+    pub phantom_data: PhantomData<(S)>,
+}
+
+impl<S> RunBatch<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    pub fn new(calls: Vec<BatchCall<S>>, atomic: bool) -> Self {
+        Self {
+            calls,
+            atomic,
+            phantom_data: PhantomData,
+        }
+    }
+
+    fn call_all(&self, state: &S) -> Result<Vec<BatchCallResult>, Error> {
+        let mut results = vec![];
+        for (index, call) in self.calls.iter().enumerate() {
+            debug!("batch call {}: {}", index, &call.procedure);
+            let result = match call.step.call_step(state) {
+                Ok(data) => BatchCallResult::ok(&call.procedure, data),
+                Err(err) => {
+                    if self.atomic {
+                        return Err(Error::BatchCallFailed {
+                            index,
+                            procedure: call.procedure.to_owned(),
+                            cause: Box::new(err),
+                        });
+                    }
+                    BatchCallResult::err(&call.procedure, &err)
+                },
+            };
+            results.push(result);
+        }
+        Ok(results)
+    }
+}
+
+impl<S> Action<S> for RunBatch<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    type Ret = BatchResult;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        debug!("Calling RunBatch");
+
+        let results = if self.atomic {
+            state.transaction(|| self.call_all(state))?
+        } else {
+            self.call_all(state)?
+        };
+
+        ActionRes::new("runBatch", BatchResult(results))
+    }
+
+    /// the calls can read and modify table data
+    fn runs_domain_queries() -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use test_common::*;
+
+    #[derive(Debug)]
+    struct Succeeds(i32);
+
+    impl<S> Action<S> for Succeeds {
+        type Ret = i32;
+        fn call(&self, _state: &S) -> ActionResult<Self::Ret> {
+            ActionRes::new("succeeds", self.0)
+        }
+    }
+
+    #[derive(Debug)]
+    struct Fails;
+
+    impl<S> Action<S> for Fails {
+        type Ret = ();
+        fn call(&self, _state: &S) -> ActionResult<Self::Ret> {
+            Err(Error::NotFound)
+        }
+    }
+
+    fn calls() -> Vec<BatchCall<MockState>> {
+        vec![
+            BatchCall { procedure: "first".to_string(), step: Box::new(Succeeds(1)) },
+            BatchCall { procedure: "second".to_string(), step: Box::new(Fails) },
+            BatchCall { procedure: "third".to_string(), step: Box::new(Succeeds(3)) },
+        ]
+    }
+
+    #[test]
+    fn test_run_batch() {
+        with_state(|state| {
+            let results = RunBatch::new(calls(), false).call(&state).unwrap().get_data().0;
+            let results: Vec<_> = results.into_iter().map(|x| serde_json::to_value(x).unwrap()).collect();
+            assert_eq!(results, vec![
+                json!({ "procedure": "first", "data": 1 }),
+                json!({ "procedure": "second", "error": "Not found" }),
+                json!({ "procedure": "third", "data": 3 }),
+            ]);
+
+            let err = RunBatch::new(calls(), true).call(&state).unwrap_err();
+            assert_eq!(err, Error::BatchCallFailed { index: 1, procedure: "second".to_string(), cause: Box::new(Error::NotFound) });
+        });
+    }
+}
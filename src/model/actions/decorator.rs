@@ -2,10 +2,18 @@
 use std::result::Result::Ok;
 use std::marker::PhantomData;
 use std::fmt;
+use std::collections::HashMap;
 use std::collections::HashSet;
+use std::time::Instant;
 
 use data::channels::Channels;
 use data::permissions::*;
+use data::conditions::ConditionContext;
+use data::conditions::ConditionTarget;
+use data::audit::AuditOutcome;
+use data::audit::AuditTarget;
+use data::audit::NewAuditEntry;
+use data::audit::before_and_after;
 
 use model::actions::error::Error;
 use model::actions::Action;
@@ -15,10 +23,34 @@ use model::actions::OkAction;
 use state::StateFunctions;
 use state::authorization::AuthorizationOps;
 use state::PubSubOps;
+use state::audit_trail::AuditTrailOps;
 use state::ActionState;
+use broker::metrics::Stage;
+
+/// Kinds of decorators an action can be wrapped in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Decorator {
+    PermissionRequired,
+    LoginRequired,
+    PermissionFor,
+    FilterListByPermission,
+    WriteAccess,
+    Transaction,
+    Dispatch,
+    Audit,
+}
+
+/// decorators of a decorated action, the new decorator goes in front of the inner ones
+pub fn with_decorator(decorator: Decorator, inner: Vec<Decorator>) -> Vec<Decorator> {
+    let mut decorators = vec![decorator];
+    decorators.extend(inner);
+    decorators
+}
 
+/// Permissions needed to run an action, either all of them or at least one
 #[derive(Debug, Clone)]
-enum Requirements {
+pub enum Requirements {
     AllOf(Vec<Permission>),
     AnyOf(Vec<Permission>),
 }
@@ -49,6 +81,45 @@ impl Requirements {
     }
 }
 
+/// Replaces every conditional permission by the permission it grants, if the condition holds
+/// for the current user and the target of the permission. Shared by all the permission decorators.
+/// The conditions are parsed when the permissions are loaded, the tags of each target are only loaded once
+pub fn resolve_conditional_permissions<S>(state: &S, permissions: HashSet<Permission>) -> HashSet<Permission>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    let authorization = state.get_authorization();
+    let claims = authorization.claims();
+    let mut targets: HashMap<(String, String), ConditionTarget> = HashMap::new();
+
+    permissions
+        .into_iter()
+        .filter_map(|permission| match permission {
+            Permission::Conditional { permission, condition } => {
+                let no_target = ConditionTarget::default();
+                let target = match permission.get_entity() {
+                    Some((type_name, entity_name)) => &*targets
+                        .entry((type_name.to_owned(), entity_name.to_owned()))
+                        .or_insert_with(|| ConditionTarget {
+                            tags: authorization.entity_tags(&type_name, &entity_name),
+                            type_name: Some(type_name),
+                            entity_name: Some(entity_name),
+                        }),
+                    None => &no_target,
+                };
+
+                let ctx = ConditionContext { claims: &claims, target };
+                if condition.evaluate(&ctx) {
+                    Some(*permission)
+                } else {
+                    None
+                }
+            },
+            x => Some(x),
+        })
+        .collect()
+}
+
 ///decorator for permission
 #[derive(Debug, Clone)]
 pub struct WithPermissionRequired<A, S = ActionState>
@@ -89,6 +160,14 @@ impl<A, S> WithPermissionRequired<A, S>
             phantom_data: PhantomData,
         }
     }
+
+    pub fn with_requirements(action: A, permissions: Requirements) -> Self {
+        Self {
+            action,
+            permissions,
+            phantom_data: PhantomData,
+        }
+    }
 }
 
 impl<A, S> Action<S> for WithPermissionRequired<A, S>
@@ -102,9 +181,14 @@ impl<A, S> Action<S> for WithPermissionRequired<A, S>
             return self.action.call(state);
         }
 
-        let user_permissions = state
+        let mut user_permissions = state
             .get_authorization()
             .permissions();
+        // every user has the permission on themselves, e.g. for subscribing to their own channel
+        if let Some(username) = state.get_authorization().username() {
+            user_permissions.insert(Permission::user(username));
+        }
+        let user_permissions = resolve_conditional_permissions(state, user_permissions);
         let is_permitted = self.permissions.is_permitted(&user_permissions);
 
         if is_permitted {
@@ -115,6 +199,14 @@ impl<A, S> Action<S> for WithPermissionRequired<A, S>
         }
 
     }
+
+    fn decorators() -> Vec<Decorator> {
+        with_decorator(Decorator::PermissionRequired, A::decorators())
+    }
+
+    fn runs_domain_queries() -> bool {
+        A::runs_domain_queries()
+    }
 }
 
 ///decorator for login
@@ -163,6 +255,14 @@ impl<A, S> Action<S> for WithLoginRequired<A, S>
             Err(Error::Unauthorized)
         }
     }
+
+    fn decorators() -> Vec<Decorator> {
+        with_decorator(Decorator::LoginRequired, A::decorators())
+    }
+
+    fn runs_domain_queries() -> bool {
+        A::runs_domain_queries()
+    }
 }
 
 ///decorator for permission after the value is returned
@@ -218,10 +318,18 @@ impl<A, S> Action<S> for WithPermissionFor<A, S>
         let user_permissions = state
             .get_authorization()
             .permissions();
+        let user_permissions = resolve_conditional_permissions(state, user_permissions);
 
         let all_permissions = state
             .get_authorization()
             .all_permissions();
+        let all_permissions: HashSet<Permission> = all_permissions
+            .into_iter()
+            .map(|permission| match permission {
+                Permission::Conditional { permission, .. } => *permission,
+                x => x,
+            })
+            .collect();
 
         let is_permitted = (self.required_permission)(&user_permissions, &all_permissions);
 
@@ -231,6 +339,62 @@ impl<A, S> Action<S> for WithPermissionFor<A, S>
             Err(Error::Unauthorized)
         }
     }
+
+    fn decorators() -> Vec<Decorator> {
+        with_decorator(Decorator::PermissionFor, A::decorators())
+    }
+
+    fn runs_domain_queries() -> bool {
+        A::runs_domain_queries()
+    }
+}
+
+///decorator for actions that modify state, rejected while the server is in read-only mode
+#[derive(Debug, Clone)]
+pub struct WithWriteAccess<A, S = ActionState>
+    where
+        A: Action<S>,
+        for<'a> S: StateFunctions<'a>,
+{
+    action: A,
+    phantom_data: PhantomData<(S)>,
+}
+
+impl<A, S> WithWriteAccess<A, S>
+    where
+        A: Action<S>,
+        for<'a> S: StateFunctions<'a>,
+{
+    pub fn new(action: A) -> Self {
+        Self {
+            action,
+            phantom_data: PhantomData,
+        }
+    }
+}
+
+impl<A, S> Action<S> for WithWriteAccess<A, S>
+    where
+        A: Action<S>,
+        for<'a> S: StateFunctions<'a>,
+{
+    type Ret = A::Ret;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        if state.is_read_only() {
+            debug!("Rejecting action, server is in read-only mode");
+            return Err(Error::ReadOnlyMode);
+        }
+
+        self.action.call(state)
+    }
+
+    fn decorators() -> Vec<Decorator> {
+        with_decorator(Decorator::WriteAccess, A::decorators())
+    }
+
+    fn runs_domain_queries() -> bool {
+        A::runs_domain_queries()
+    }
 }
 
 ///decorator for transactions
@@ -291,6 +455,14 @@ impl<A, S> Action<S> for WithTransaction<A, S>
         )
 
     }
+
+    fn decorators() -> Vec<Decorator> {
+        with_decorator(Decorator::Transaction, A::decorators())
+    }
+
+    fn runs_domain_queries() -> bool {
+        A::runs_domain_queries()
+    }
 }
 
 ///decorator for dispatching to channel
@@ -342,6 +514,7 @@ impl<A, S> Action<S> for WithDispatch<A, S>
         let data_ref = serde_json::to_value(result.get_data_ref().clone())
             .map_err(|err| Error::SerializationError(err.to_string()))?;
 
+        let publish_start = Instant::now();
         state
             .get_pub_sub()
             .publish(
@@ -350,6 +523,105 @@ impl<A, S> Action<S> for WithDispatch<A, S>
                 &data_ref)
             .map_err(Error::PublishError)?;
 
+        state
+            .get_broadcast_metrics()
+            .record(Stage::Publish, &result.get_name(), publish_start.elapsed());
+
         Ok(result)
     }
+
+    fn decorators() -> Vec<Decorator> {
+        with_decorator(Decorator::Dispatch, A::decorators())
+    }
+
+    fn runs_domain_queries() -> bool {
+        A::runs_domain_queries()
+    }
+}
+
+///decorator for recording the call in the audit trail, whether it succeeded or not.
+///It goes outside of the permission checks so that the rejected calls are recorded as well,
+///and outside of the transaction so that the failed calls are still recorded once it is rolled back
+#[derive(Clone)]
+pub struct WithAudit<A, S = ActionState>
+    where
+        A: Action<S>,
+{
+    action: A,
+    procedure: String,
+    target: AuditTarget,
+    phantom_data: PhantomData<S>,
+}
+
+impl<A, S> fmt::Debug for WithAudit<A, S>
+    where
+        A: Action<S>,
+        for<'a> S: StateFunctions<'a>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "WithAudit({:?})", &self.action)
+    }
+}
+
+impl<A, S> WithAudit<A, S>
+    where
+        A: Action<S>,
+        for<'a> S: StateFunctions<'a>,
+{
+    pub fn new(action: A, procedure: &str, target: AuditTarget) -> Self {
+        Self {
+            action,
+            procedure: procedure.to_owned(),
+            target,
+            phantom_data: PhantomData,
+        }
+    }
+}
+
+impl<A, S> Action<S> for WithAudit<A, S>
+    where
+        A: Action<S>,
+        for<'a> S: StateFunctions<'a>,
+{
+    type Ret = A::Ret;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        let result = self.action.call(state);
+
+        let (before, after, outcome, error) = match &result {
+            Ok(res) => {
+                let data = serde_json::to_value(res.get_data_ref()).unwrap_or_default();
+                let (before, after) = before_and_after(&data);
+                (before, after, AuditOutcome::Succeeded, None)
+            },
+            Err(err) => (None, None, AuditOutcome::Failed, Some(err.to_string())),
+        };
+
+        let entry = NewAuditEntry {
+            username: state.get_authorization().username(),
+            procedure: self.procedure.to_owned(),
+            target: self.target.to_owned(),
+            domain: state.get_domain_name(),
+            request_id: state.get_request_id(),
+            before,
+            after,
+            outcome,
+            error,
+        };
+        debug!("recording {:?} ({:?}) in the audit trail", &entry.procedure, &entry.outcome);
+
+        // the call already happened, so its result is returned even if it couldn't be recorded
+        if let Err(err) = state.get_audit_trail().record(&entry) {
+            error!("could not record {:?} in the audit trail: {:?}", &entry.procedure, &err);
+        }
+
+        result
+    }
+
+    fn decorators() -> Vec<Decorator> {
+        with_decorator(Decorator::Audit, A::decorators())
+    }
+
+    fn runs_domain_queries() -> bool {
+        A::runs_domain_queries()
+    }
 }
@@ -14,6 +14,7 @@ use model::actions::decorator::*;
 use model::actions::Action;
 use model::actions::ActionRes;
 use model::actions::ActionResult;
+use model::actions::paging::Paging;
 
 use model::entity::RetrieverFunctions;
 use model::entity::ModifierFunctions;
@@ -33,14 +34,16 @@ use state::domain_management::DomainManagementOps;
 ///get all tables
 #[derive(Debug, Clone)]
 pub struct GetAllDomains<S = ActionState> {
+    pub paging: Paging,
     pub phantom_data: PhantomData<(S)>,
 }
 
 impl<S> GetAllDomains<S>
     where for<'a> S: StateFunctions<'a>,
 {
-    pub fn new() -> WithLoginRequired<WithTransaction<Self, S>, S> {
+    pub fn new(paging: Paging) -> WithLoginRequired<WithTransaction<Self, S>, S> {
         let action = Self {
+            paging,
             phantom_data: PhantomData,
         };
 
@@ -54,14 +57,14 @@ impl<S> GetAllDomains<S>
 impl<S> Action<S> for GetAllDomains<S>
     where for<'a> S: StateFunctions<'a>,
 {
-    type Ret = Vec<DomainInfo>;
+    type Ret = ListResult<DomainInfo>;
     fn call(&self, state: &S) -> ActionResult<Self::Ret> {
         let data = state
             .get_domain_management()
             .get_all_domains()
             .map_err(|err| Error::DomainManagement(err))?;
 
-        ActionRes::new("getAllDomains", data)
+        ActionRes::new("getAllDomains", self.paging.apply(data))
     }
 }
 
@@ -7,6 +7,7 @@ use data::utils::OnNotFound;
 use data::Named;
 use data::channels::Channels;
 use data::permissions::*;
+use data::audit::AuditTarget;
 
 use inflector::Inflector;
 
@@ -16,6 +17,7 @@ use model::actions::decorator::*;
 use model::actions::Action;
 use model::actions::ActionRes;
 use model::actions::ActionResult;
+use model::actions::paging::Paging;
 
 use model::entity::RetrieverFunctions;
 use model::entity::ModifierFunctions;
@@ -29,6 +31,7 @@ use model::entity::update_state::UpdateActionFunctions;
 use state::StateFunctions;
 use state::ActionState;
 use state::authorization::AuthorizationOps;
+use state::PubSubOps;
 
 ///decorator for permission in listing items
 /// Only defined for GetAllEntities
@@ -73,7 +76,7 @@ impl<A, T, S> Action<S> for WithFilterListByPermission<A, T, S>
             return Err(Error::Unauthorized);
         }
 
-        let user_permissions = authorization.permissions();
+        let user_permissions = resolve_conditional_permissions(state, authorization.permissions());
 
         let raw_results = self.action.call(state)?;
 
@@ -96,6 +99,77 @@ impl<A, T, S> Action<S> for WithFilterListByPermission<A, T, S>
 
         ActionRes::new(&raw_results_name, GetAllEntitiesResult(filtered_results))
     }
+
+    fn decorators() -> Vec<Decorator> {
+        with_decorator(Decorator::FilterListByPermission, A::decorators())
+    }
+
+    fn runs_domain_queries() -> bool {
+        A::runs_domain_queries()
+    }
+}
+
+/// Applies the list options once the list is filtered by permission, so that the pages
+/// and the total count only have the entities the user can read
+#[derive(Debug, Clone)]
+pub struct WithListOptions<A, T, S = ActionState>
+    where
+        A: Action<S, Ret = GetAllEntitiesResult<T>>,
+        T: RawEntityTypes,
+        for<'a> S: StateFunctions<'a>,
+{
+    action: A,
+    detailed: bool,
+    paging: Paging,
+    phantom_data: PhantomData<(T, S)>,
+}
+
+impl<A, T, S> WithListOptions<A, T, S>
+    where
+        A: Action<S, Ret = GetAllEntitiesResult<T>>,
+        T: RawEntityTypes,
+        for<'a> S: StateFunctions<'a>,
+{
+    pub fn new(action: A, detailed: bool, paging: Paging) -> Self {
+        Self {
+            action,
+            detailed,
+            paging,
+            phantom_data: PhantomData,
+        }
+    }
+}
+
+impl<A, T, S> Action<S> for WithListOptions<A, T, S>
+    where
+        A: Action<S, Ret = GetAllEntitiesResult<T>>,
+        T: RawEntityTypes,
+        for<'a> S: StateFunctions<'a>,
+{
+    type Ret = ListResult<EntityListItem<T>>;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        let raw_results = self.action.call(state)?;
+        let raw_results_name = raw_results.get_name();
+
+        let GetAllEntitiesResult(entities) = raw_results.get_data();
+        let items = entities.into_iter()
+            .map(|x| if self.detailed {
+                EntityListItem::Detailed(x)
+            } else {
+                EntityListItem::Name(x.my_name().to_owned())
+            })
+            .collect();
+
+        ActionRes::new(&raw_results_name, self.paging.apply(items))
+    }
+
+    fn decorators() -> Vec<Decorator> {
+        A::decorators()
+    }
+
+    fn runs_domain_queries() -> bool {
+        A::runs_domain_queries()
+    }
 }
 
 ///get all tables
@@ -113,7 +187,7 @@ impl<T, S> GetAllEntities<T, S>
         T: RawEntityTypes,
         for<'a> S: StateFunctions<'a>,
 {
-    pub fn new(show_deleted: bool) -> WithFilterListByPermission<WithTransaction<Self, S>, T, S> {
+    pub fn new(show_deleted: bool, detailed: bool, paging: Paging) -> WithListOptions<WithFilterListByPermission<WithTransaction<Self, S>, T, S>, T, S> {
         let action = Self {
             show_deleted,
             phantom_data: PhantomData,
@@ -121,8 +195,9 @@ impl<T, S> GetAllEntities<T, S>
 
         let action_with_transaction = WithTransaction::new(action);
         let action_with_filter = WithFilterListByPermission::new(action_with_transaction);
+        let action_with_list_options = WithListOptions::new(action_with_filter, detailed, paging);
 
-        action_with_filter
+        action_with_list_options
     }
 }
 
@@ -133,10 +208,13 @@ impl<T, S> Action<S> for GetAllEntities<T, S>
 {
     type Ret = GetAllEntitiesResult<T>;
     fn call(&self, state: &S) -> ActionResult<Self::Ret> {
-        let entities: Vec<T> = state
-            .get_entity_retreiver_functions()
-            .get_all()
-            .or_else(|err| Err(Error::Entity(err)))?;
+        let retriever = state.get_entity_retreiver_functions();
+        let entities: Result<Vec<T>, _> = if self.show_deleted {
+            retriever.get_all_including_deleted()
+        } else {
+            retriever.get_all()
+        };
+        let entities = entities.or_else(|err| Err(Error::Entity(err)))?;
 
         let action_name =  format!("getAll{}", T::TYPE_NAME_PLURAL.to_pascal_case());
         ActionRes::new(&action_name, GetAllEntitiesResult::<T>(entities))
@@ -210,9 +288,10 @@ impl<T, S> CreateEntity<T, S>
         for<'a> S: StateFunctions<'a>,
         <Self as Action<S>>::Ret: Clone,
 {
-    pub fn new(data: T) -> WithPermissionFor<WithDispatch<WithTransaction<Self, S>, S>, S> {
+    pub fn new(data: T) -> WithAudit<WithPermissionFor<WithWriteAccess<WithDispatch<WithTransaction<Self, S>, S>, S>, S>, S> {
 
         let name = data.my_name().to_owned();
+        let audit_target = AuditTarget::new(T::TYPE_NAME, &name);
         let channel = Channels::entity::<T>(&name);
 
         let create_permission = Permission::create_entity::<T>();
@@ -227,9 +306,10 @@ impl<T, S> CreateEntity<T, S>
 
         let action_with_transaction = WithTransaction::new(action);
         let action_with_dispatch = WithDispatch::new(action_with_transaction, channel);
+        let action_with_write_access = WithWriteAccess::new(action_with_dispatch);
         let action_with_permission =
             WithPermissionFor::new(
-                action_with_dispatch,
+                action_with_write_access,
                 move |user_permissions, all_permissions| {
                     match on_duplicate {
                         OnDuplicate::Update => if all_permissions.contains(&update_permission) {
@@ -243,7 +323,9 @@ impl<T, S> CreateEntity<T, S>
 
 
 
-        action_with_permission
+        let action_with_audit = WithAudit::new(action_with_permission, &format!("create{}", T::TYPE_NAME.to_pascal_case()), audit_target);
+
+        action_with_audit
     }
 }
 
@@ -320,7 +402,8 @@ impl<T, S> UpdateEntity<T, S>
         T: RawEntityTypes + UpdateActionFunctions,
         for<'a> S: StateFunctions<'a>,
 {
-    pub fn new(name: String, data: T) -> WithPermissionRequired<WithDispatch<WithTransaction<Self, S>, S>, S> {
+    pub fn new(name: String, data: T) -> WithAudit<WithPermissionRequired<WithWriteAccess<WithDispatch<WithTransaction<Self, S>, S>, S>, S>, S> {
+        let audit_target = AuditTarget::new(T::TYPE_NAME, &name);
         let channel = Channels::entity::<T>(&name);
         let action = Self {
             name: name.to_owned(),
@@ -331,10 +414,29 @@ impl<T, S> UpdateEntity<T, S>
 
         let action_with_transaction = WithTransaction::new(action);
         let action_with_dispatch = WithDispatch::new(action_with_transaction, channel);
+        let action_with_write_access = WithWriteAccess::new(action_with_dispatch);
         let action_with_permission =
-            WithPermissionRequired::new(action_with_dispatch, Permission::modify_entity::<T>(name));
+            WithPermissionRequired::new(action_with_write_access, Permission::modify_entity::<T>(name));
 
-        action_with_permission
+        let action_with_audit = WithAudit::new(action_with_permission, &format!("update{}", T::TYPE_NAME.to_pascal_case()), audit_target);
+
+        action_with_audit
+    }
+
+    /// `WithDispatch` publishes on the channel of the old name, when the entity was renamed
+    /// the subscribers of the new name are told as well so they can resync
+    fn publish_rename(&self, state: &S, action_name: &str, result: &UpdateEntityResult<T>) -> Result<(), Error> {
+        let new_name = match result {
+            UpdateEntityResult::Updated { new, .. } if new.my_name() != self.name => new.my_name().to_owned(),
+            _ => return Ok(()),
+        };
+
+        let data = serde_json::to_value(result)
+            .map_err(|err| Error::SerializationError(err.to_string()))?;
+        state
+            .get_pub_sub()
+            .publish(Channels::entity::<T>(&new_name), action_name.to_owned(), &data)
+            .map_err(Error::PublishError)
     }
 }
 
@@ -347,7 +449,7 @@ impl<T, S> Action<S> for UpdateEntity<T, S>
     fn call(&self, state: &S) -> ActionResult<Self::Ret> {
         let action_name =  format!("update{}", T::TYPE_NAME.to_pascal_case());
 
-        match &self.on_not_found {
+        let result = match &self.on_not_found {
             OnNotFound::Ignore => {
                 state
                     .get_entity_modifier_function()
@@ -378,7 +480,11 @@ impl<T, S> Action<S> for UpdateEntity<T, S>
                         }
                     })
             },
-        }
+        }?;
+
+        self.publish_rename(state, &action_name, result.get_data_ref())?;
+
+        Ok(result)
     }
 }
 
@@ -399,7 +505,8 @@ impl<T, S> DeleteEntity<T, S>
         T: RawEntityTypes + UpdateActionFunctions,
         for<'a> S: StateFunctions<'a>,
 {
-    pub fn new(name: String) -> WithPermissionRequired<WithDispatch<WithTransaction<Self, S>, S>, S> {
+    pub fn new(name: String) -> WithAudit<WithPermissionRequired<WithWriteAccess<WithDispatch<WithTransaction<Self, S>, S>, S>, S>, S> {
+        let audit_target = AuditTarget::new(T::TYPE_NAME, &name);
         let channel = Channels::entity::<T>(&name);
         let action = Self {
             name: name.to_owned(),
@@ -409,10 +516,13 @@ impl<T, S> DeleteEntity<T, S>
 
         let action_with_transaction = WithTransaction::new(action);
         let action_with_dispatch = WithDispatch::new(action_with_transaction, channel);
+        let action_with_write_access = WithWriteAccess::new(action_with_dispatch);
         let action_with_permission =
-            WithPermissionRequired::new(action_with_dispatch, Permission::modify_entity::<T>(name));
+            WithPermissionRequired::new(action_with_write_access, Permission::modify_entity::<T>(name));
 
-        action_with_permission
+        let action_with_audit = WithAudit::new(action_with_permission, &format!("delete{}", T::TYPE_NAME.to_pascal_case()), audit_target);
+
+        action_with_audit
     }
 }
 
@@ -471,6 +581,22 @@ mod test {
     use test_common::with_state;
     use test_common::MockState;
 
+    use diesel::prelude::*;
+    use data::channels::Defaults;
+    use metastore::schema;
+
+    /// the data of the messages published on the channel, oldest first
+    fn published_on(state: &MockState, channel: &Channels) -> Vec<serde_json::Value> {
+        let channel = serde_json::to_value(channel).unwrap();
+        schema::message::table
+            .inner_join(schema::channel::table)
+            .filter(schema::channel::columns::data.eq(channel))
+            .order(schema::message::columns::message_id.asc())
+            .select(schema::message::columns::data)
+            .load(&state.0.database)
+            .unwrap()
+    }
+
     #[test]
     fn test_create_entity() {
 
@@ -550,4 +676,47 @@ mod test {
             }
         });
     }
+
+    #[test]
+    fn test_entity_actions_broadcast() {
+        with_state(|state| {
+            let name = format!("my_query_{}", random_identifier());
+            let channel = Channels::Defaults(Defaults::Query(name.to_owned()));
+            let new_query: data::DataQueryEntity = from_value(json!({
+                "name": name,
+                "description": "blah blah blah",
+                "statement": "SELECT * FROM a_table"
+            })).unwrap();
+
+            let create_action = CreateEntity::<data::DataQueryEntity, MockState>::new(new_query.to_owned());
+            create_action.call(&state).unwrap();
+
+            let messages = published_on(&state, &channel);
+            assert_eq!(messages.len(), 1);
+            assert_eq!(messages[0]["result"], json!("created"));
+            assert_eq!(messages[0]["new"]["name"], json!(name));
+
+            let updated_query = data::DataQueryEntity { description: "updated".to_string(), ..new_query };
+            let update_action = UpdateEntity::<data::DataQueryEntity, MockState>::new(name.to_owned(), updated_query);
+            update_action.call(&state).unwrap();
+
+            let messages = published_on(&state, &channel);
+            assert_eq!(messages.len(), 2);
+            assert_eq!(messages[1]["result"], json!("updated"));
+            assert_eq!(messages[1]["old"]["description"], json!("blah blah blah"));
+            assert_eq!(messages[1]["new"]["description"], json!("updated"));
+
+            let delete_action = DeleteEntity::<data::DataQueryEntity, MockState>::new(name.to_owned());
+            delete_action.call(&state).unwrap();
+
+            let messages = published_on(&state, &channel);
+            assert_eq!(messages.len(), 3);
+            assert_eq!(messages[2]["result"], json!("deleted"));
+            assert_eq!(messages[2]["id"], json!(name));
+
+            // nothing is published on the channels of the other entity types
+            let table_channel = Channels::Defaults(Defaults::Table(name.to_owned()));
+            assert!(published_on(&state, &table_channel).is_empty());
+        });
+    }
 }
\ No newline at end of file
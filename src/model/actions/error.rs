@@ -27,10 +27,63 @@ pub enum Error {
     NotFound,
     #[fail(display = "Already exists")]
     AlreadyExists,
+    #[fail(display = "Server is in read-only mode")]
+    ReadOnlyMode,
+    #[fail(display = "No database connection is available, try again in {} seconds", retry_after)]
+    ServiceUnavailable { retry_after: u64 },
+    #[fail(display = "{} did not finish within {} ms", procedure, timeout)]
+    TimedOut { procedure: String, timeout: u64 },
     #[fail(display = "{}", 0)]
     SerializationError(String),
     #[fail(display = "{}", 0)]
     PublishError(BroadcastError),
+    #[fail(display = "Call {} of the batch ({}) failed, the batch was rolled back: {}", index, procedure, cause)]
+    BatchCallFailed { index: usize, procedure: String, cause: Box<Error> },
     #[fail(display = "An unknown error occurred")]
     Unknown,
-}
\ No newline at end of file
+}
+
+impl Error {
+    /// more about the error than its message, e.g. the exit code and traceback of a failed script
+    pub fn details(&self) -> Option<serde_json::Value> {
+        match self {
+            Error::Script(err) => err
+                .diagnostics()
+                .and_then(|diagnostics| serde_json::to_value(diagnostics).ok()),
+            Error::ServiceUnavailable { retry_after } => Some(json!({ "retryAfter": retry_after })),
+            Error::TimedOut { procedure, timeout } => Some(json!({ "procedure": procedure, "timeout": timeout })),
+            Error::BatchCallFailed { index, procedure, cause } => Some(json!({
+                "index": index,
+                "procedure": procedure,
+                "cause": cause.envelope(),
+            })),
+            _ => None,
+        }
+    }
+
+    /// in seconds, for the errors the request can be retried after
+    pub fn retry_after(&self) -> Option<u64> {
+        match self {
+            Error::ServiceUnavailable { retry_after } => Some(*retry_after),
+            Error::BatchCallFailed { cause, .. } => cause.retry_after(),
+            _ => None,
+        }
+    }
+
+    /// the action took longer than its caller waits for it
+    pub fn is_timeout(&self) -> bool {
+        match self {
+            Error::TimedOut { .. } => true,
+            Error::BatchCallFailed { cause, .. } => cause.is_timeout(),
+            _ => false,
+        }
+    }
+
+    /// the error as it is sent to the clients, `{"error": "...", "details": {...}}`
+    pub fn envelope(&self) -> serde_json::Value {
+        match self.details() {
+            Some(details) => json!({ "error": self.to_string(), "details": details }),
+            None => json!({ "error": self.to_string() }),
+        }
+    }
+}
@@ -0,0 +1,213 @@
+use std::marker::PhantomData;
+
+use data::audit::AuditFilter;
+use data::audit::AuditTarget;
+use data::permissions::Permission;
+
+use model::actions::results::*;
+use model::actions::error::Error;
+use model::actions::decorator::*;
+use model::actions::Action;
+use model::actions::ActionRes;
+use model::actions::ActionResult;
+
+use state::StateFunctions;
+use state::ActionState;
+use state::audit_trail::AuditTrailOps;
+
+use kakapo_postgres::database::statement_cache;
+
+///toggle read-only mode, admin only
+#[derive(Debug, Clone)]
+pub struct SetReadOnlyMode<S = ActionState> {
+    pub read_only: bool,
+    pub phantom_data: PhantomData<(S)>,
+}
+
+impl<S> SetReadOnlyMode<S>
+    where for<'a> S: StateFunctions<'a>,
+{
+    pub fn new(read_only: bool) -> WithAudit<WithPermissionRequired<Self, S>, S> {
+        let audit_target = AuditTarget::none();
+        let action = Self {
+            read_only,
+            phantom_data: PhantomData,
+        };
+
+        let action = WithPermissionRequired::new(action, Permission::user_admin());
+
+        let action_with_audit = WithAudit::new(action, "setReadOnlyMode", audit_target);
+
+        action_with_audit
+    }
+}
+
+impl<S> Action<S> for SetReadOnlyMode<S>
+    where for<'a> S: StateFunctions<'a>,
+{
+    type Ret = ReadOnlyModeResult;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        debug!("Calling SetReadOnlyMode");
+
+        state.set_read_only(self.read_only);
+        info!("Read-only mode set to {}", self.read_only);
+
+        ActionRes::new("setReadOnlyMode", ReadOnlyModeResult { read_only: state.is_read_only() })
+    }
+}
+
+///latency histograms for the broadcast pipeline and the recent slow deliveries, admin only
+#[derive(Debug, Clone)]
+pub struct GetBroadcastMetrics<S = ActionState> {
+    pub phantom_data: PhantomData<(S)>,
+}
+
+impl<S> GetBroadcastMetrics<S>
+    where for<'a> S: StateFunctions<'a>,
+{
+    pub fn new() -> WithPermissionRequired<Self, S> {
+        let action = Self {
+            phantom_data: PhantomData,
+        };
+
+        let action = WithPermissionRequired::new(action, Permission::user_admin());
+
+        action
+    }
+}
+
+impl<S> Action<S> for GetBroadcastMetrics<S>
+    where for<'a> S: StateFunctions<'a>,
+{
+    type Ret = BroadcastMetricsResult;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        debug!("Calling GetBroadcastMetrics");
+
+        let snapshot = state
+            .get_broadcast_metrics()
+            .snapshot();
+
+        ActionRes::new("getBroadcastMetrics", BroadcastMetricsResult(snapshot))
+    }
+}
+
+///latency histograms and error counts of the database operations by kind, the time waited
+///for a connection by pool and the hits of the prepared statement cache, admin only
+#[derive(Debug, Clone)]
+pub struct GetDatabaseMetrics<S = ActionState> {
+    pub phantom_data: PhantomData<(S)>,
+}
+
+impl<S> GetDatabaseMetrics<S>
+    where for<'a> S: StateFunctions<'a>,
+{
+    pub fn new() -> WithPermissionRequired<Self, S> {
+        let action = Self {
+            phantom_data: PhantomData,
+        };
+
+        let action = WithPermissionRequired::new(action, Permission::user_admin());
+
+        action
+    }
+}
+
+impl<S> Action<S> for GetDatabaseMetrics<S>
+    where for<'a> S: StateFunctions<'a>,
+{
+    type Ret = DatabaseMetricsResult;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        debug!("Calling GetDatabaseMetrics");
+
+        let mut snapshot = state
+            .get_database_metrics()
+            .snapshot();
+        // counted by the postgres datastores of this process, not by the executors
+        snapshot["statementCache"] = json!(statement_cache::stats());
+
+        ActionRes::new("getDatabaseMetrics", DatabaseMetricsResult(snapshot))
+    }
+}
+
+///the recorded mutating calls, newest first, see `WithAudit`, admin only
+#[derive(Debug, Clone)]
+pub struct GetAuditTrail<S = ActionState> {
+    pub filter: AuditFilter,
+    pub limit: usize,
+    pub offset: usize,
+    pub phantom_data: PhantomData<(S)>,
+}
+
+impl<S> GetAuditTrail<S>
+    where for<'a> S: StateFunctions<'a>,
+{
+    pub fn new(filter: AuditFilter, limit: usize, offset: usize) -> WithPermissionRequired<Self, S> {
+        let action = Self {
+            filter,
+            limit,
+            offset,
+            phantom_data: PhantomData,
+        };
+
+        let action = WithPermissionRequired::new(action, Permission::user_admin());
+
+        action
+    }
+}
+
+impl<S> Action<S> for GetAuditTrail<S>
+    where for<'a> S: StateFunctions<'a>,
+{
+    type Ret = AuditTrailResult;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        debug!("Calling GetAuditTrail");
+
+        let (items, total_count) = state
+            .get_audit_trail()
+            .get_entries(&self.filter, self.limit, self.offset)
+            .map_err(Error::Datastore)?;
+
+        ActionRes::new("getAuditTrail", AuditTrailResult(ListPage { items, offset: self.offset, total_count }))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use test_common::*;
+
+    use model::actions::InsertTableData;
+
+    #[test]
+    fn test_read_only_mode() {
+        with_state(|state| {
+            let action = SetReadOnlyMode::<MockState>::new(true);
+            let result = action.call(&state).unwrap();
+            assert_eq!(result.get_data_ref().read_only, true);
+
+            let insert_action = InsertTableData::<MockState>::new("some_table".to_string(), json!([]));
+            let result = insert_action.call(&state);
+            assert_eq!(result.unwrap_err(), Error::ReadOnlyMode);
+
+            let action = SetReadOnlyMode::<MockState>::new(false);
+            let result = action.call(&state).unwrap();
+            assert_eq!(result.get_data_ref().read_only, false);
+        });
+    }
+
+    #[test]
+    fn test_get_audit_trail() {
+        with_state(|state| {
+            let action = SetReadOnlyMode::<MockState>::new(false);
+            action.call(&state).unwrap();
+
+            let filter = AuditFilter { procedure: Some("setReadOnlyMode".to_string()), ..AuditFilter::default() };
+            let action = GetAuditTrail::<MockState>::new(filter, 10, 0);
+            let result = action.call(&state).unwrap();
+            let page = &result.get_data_ref().0;
+            assert_eq!(page.total_count, 1);
+            assert_eq!(page.items[0].procedure, "setReadOnlyMode");
+            assert_eq!(page.items[0].after, Some(json!({ "readOnly": false })));
+        });
+    }
+}
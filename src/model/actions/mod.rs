@@ -1,7 +1,9 @@
 
 pub mod results;
 pub mod error;
-mod decorator;
+pub mod decorator;
+pub mod policy;
+pub mod paging;
 mod domain_actions;
 mod user_actions;
 mod entity_actions;
@@ -9,6 +11,10 @@ mod table_actions;
 mod query_actions;
 mod script_actions;
 mod pub_sub_actions;
+mod maintenance_actions;
+mod usage_actions;
+mod webhook_actions;
+mod batch_actions;
 
 
 use std::result::Result;
@@ -18,6 +24,7 @@ use std::fmt::Debug;
 use serde::Serialize;
 
 use model::actions::error::Error;
+use model::actions::decorator::Decorator;
 
 use state::ActionState;
 
@@ -28,6 +35,10 @@ pub use model::actions::table_actions::*;
 pub use model::actions::query_actions::*;
 pub use model::actions::script_actions::*;
 pub use model::actions::pub_sub_actions::*;
+pub use model::actions::maintenance_actions::*;
+pub use model::actions::usage_actions::*;
+pub use model::actions::webhook_actions::*;
+pub use model::actions::batch_actions::*;
 
 
 #[derive(Debug, Clone)]
@@ -83,5 +94,20 @@ pub trait Action<S = ActionState>
 {
     type Ret;
     fn call(&self, state: &S) -> ActionResult<Self::Ret>;
+
+    /// decorators wrapping this action, outermost first. Used for checking the decorator policy
+    fn decorators() -> Vec<Decorator>
+        where Self: Sized
+    {
+        vec![]
+    }
+
+    /// whether the action runs the statements of a domain, e.g. reading or modifying table data.
+    /// These are sent to the query executors, so that a slow query doesn't hold up the other actions
+    fn runs_domain_queries() -> bool
+        where Self: Sized
+    {
+        false
+    }
 }
 
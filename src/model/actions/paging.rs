@@ -0,0 +1,61 @@
+use model::actions::results::ListPage;
+use model::actions::results::ListResult;
+
+/// The `limit` and `offset` of the list procedures. The whole list is returned when neither is set,
+/// the same as before they could be paged
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Paging {
+    pub limit: Option<usize>,
+    pub offset: Option<usize>,
+}
+
+impl Paging {
+    pub fn new(limit: Option<usize>, offset: Option<usize>) -> Self {
+        Self { limit, offset }
+    }
+
+    pub fn is_paginated(&self) -> bool {
+        self.limit.is_some() || self.offset.is_some()
+    }
+
+    /// the page of the items, the total count is the one before paging
+    pub fn apply<T>(&self, items: Vec<T>) -> ListResult<T> {
+        if !self.is_paginated() {
+            return ListResult::All(items);
+        }
+
+        let total_count = items.len();
+        let offset = self.offset.unwrap_or(0);
+        let items: Vec<T> = items
+            .into_iter()
+            .skip(offset)
+            .take(self.limit.unwrap_or(usize::max_value()))
+            .collect();
+
+        ListResult::Page(ListPage { items, offset, total_count })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json;
+
+    #[test]
+    fn test_apply_paging() {
+        let items = vec!["a", "b", "c", "d", "e"];
+
+        let all = Paging::default().apply(items.to_owned());
+        assert_eq!(serde_json::to_value(all).unwrap(), json!(["a", "b", "c", "d", "e"]));
+
+        let page = Paging::new(Some(2), Some(1)).apply(items.to_owned());
+        assert_eq!(
+            serde_json::to_value(page).unwrap(),
+            json!({ "items": ["b", "c"], "offset": 1, "totalCount": 5 }));
+
+        let past_the_end = Paging::new(None, Some(10)).apply(items.to_owned());
+        assert_eq!(
+            serde_json::to_value(past_the_end).unwrap(),
+            json!({ "items": [], "offset": 10, "totalCount": 5 }));
+    }
+}
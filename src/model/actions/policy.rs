@@ -0,0 +1,116 @@
+
+use std::fmt;
+
+use model::actions::decorator::Decorator;
+
+/// Which actions a policy rule applies to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ActionClass {
+    All,
+    /// actions that modify state, i.e. the ones wrapped in `WithWriteAccess`
+    Writes,
+}
+
+impl ActionClass {
+    fn contains(&self, decorators: &[Decorator]) -> bool {
+        match self {
+            ActionClass::All => true,
+            ActionClass::Writes => decorators.contains(&Decorator::WriteAccess),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PolicyRule {
+    pub applies_to: ActionClass,
+    pub required: Vec<Decorator>,
+}
+
+/// Decorators that every action of a class must have, checked when the routes are registered
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DecoratorPolicy {
+    pub rules: Vec<PolicyRule>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PolicyViolation {
+    pub path: String,
+    pub missing: Vec<Decorator>,
+}
+
+/// All the procedures that don't conform to the policy
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PolicyViolations(pub Vec<PolicyViolation>);
+
+impl fmt::Display for PolicyViolations {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{} procedure(s) do not conform to the decorator policy:", self.0.len())?;
+        for violation in &self.0 {
+            writeln!(f, "  {} is missing {:?}", &violation.path, &violation.missing)?;
+        }
+        Ok(())
+    }
+}
+
+impl DecoratorPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn require(mut self, applies_to: ActionClass, required: Vec<Decorator>) -> Self {
+        self.rules.push(PolicyRule { applies_to, required });
+        self
+    }
+
+    /// the required decorators missing from an action with the given decorators
+    pub fn missing_decorators(&self, decorators: &[Decorator]) -> Vec<Decorator> {
+        let mut missing = vec![];
+        for rule in &self.rules {
+            if !rule.applies_to.contains(decorators) {
+                continue;
+            }
+
+            for required in &rule.required {
+                if !decorators.contains(required) && !missing.contains(required) {
+                    missing.push(*required);
+                }
+            }
+        }
+
+        missing
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_missing_decorators() {
+        let policy = DecoratorPolicy::new()
+            .require(ActionClass::Writes, vec![Decorator::Transaction, Decorator::Dispatch])
+            .require(ActionClass::All, vec![Decorator::Transaction]);
+
+        let write = vec![Decorator::PermissionRequired, Decorator::WriteAccess, Decorator::Transaction];
+        assert_eq!(policy.missing_decorators(&write), vec![Decorator::Dispatch]);
+
+        let read = vec![Decorator::LoginRequired];
+        assert_eq!(policy.missing_decorators(&read), vec![Decorator::Transaction]);
+
+        let conforming = vec![Decorator::WriteAccess, Decorator::Dispatch, Decorator::Transaction];
+        assert!(policy.missing_decorators(&conforming).is_empty());
+    }
+
+    #[test]
+    fn test_deserialize_policy() {
+        let policy: DecoratorPolicy = serde_json::from_value(json!({
+            "rules": [{ "appliesTo": "writes", "required": ["transaction", "dispatch"] }]
+        })).unwrap();
+
+        assert_eq!(policy.rules[0].applies_to, ActionClass::Writes);
+        assert_eq!(policy.rules[0].required, vec![Decorator::Transaction, Decorator::Dispatch]);
+    }
+}
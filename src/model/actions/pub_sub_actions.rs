@@ -16,9 +16,14 @@ use model::actions::Action;
 use model::actions::ActionRes;
 use model::actions::ActionResult;
 use model::entity::RetrieverFunctions;
+use model::entity::RawEntityTypes;
+use data::Named;
+use data::channels::ChannelInfo;
 use data::channels::Channels;
 use data::channels::Defaults;
 use data::channels::Sub;
+use data::message_retention::MessageRetention;
+use data::audit::AuditTarget;
 
 use state::PubSubOps;
 use state::ActionState;
@@ -28,6 +33,10 @@ use state::authorization::AuthorizationOps;
 #[derive(Debug)]
 pub struct SubscribeTo<S = ActionState>  {
     pub channel: Channels,
+    /// the messages are kept until they are acknowledged, see `GetPendingMessages`
+    pub durable: bool,
+    /// how long (in seconds) the subscription is kept, until it is removed by `ExpireSubscriptions`
+    pub ttl: Option<u64>,
     pub phantom_data: PhantomData<(S)>,
 }
 
@@ -35,12 +44,14 @@ impl<S> SubscribeTo<S>
     where
         for<'a> S: StateFunctions<'a>,
 {
-    pub fn new(channel: Channels) -> WithPermissionRequired<WithTransaction<Self, S>, S> {
+    pub fn new(channel: Channels, durable: bool, ttl: Option<u64>) -> WithPermissionRequired<WithTransaction<Self, S>, S> {
         debug!("new action SubscribeTo");
 
         let permission = channel.required_permission();
         let action = Self {
             channel,
+            durable,
+            ttl,
             phantom_data: PhantomData,
         };
 
@@ -64,10 +75,12 @@ impl<S> Action<S> for SubscribeTo<S>
             .get_authorization()
             .user_id()
             .ok_or_else(|| Error::Unauthorized)?;
+        let expires_at = self.ttl
+            .map(|ttl| chrono::Utc::now().naive_utc() + chrono::Duration::seconds(ttl as i64));
 
         state
             .get_pub_sub()
-            .subscribe(user_id, self.channel.to_owned())
+            .subscribe(user_id, self.channel.to_owned(), self.durable, expires_at)
             .map_err(|err| Error::PublishError(err))
             .and_then(|res| ActionRes::new("subscribeTo", SubscriptionResult::Subscribed(res)))
     }
@@ -122,6 +135,8 @@ impl<S> Action<S> for UnsubscribeFrom<S>
 
 #[derive(Debug)]
 pub struct UnsubscribeAll<S = ActionState>  {
+    /// only the subscriptions of the session are removed, e.g. when it closes
+    pub keep_durable: bool,
     pub phantom_data: PhantomData<(S)>,
 }
 
@@ -129,10 +144,11 @@ impl<S> UnsubscribeAll<S>
     where
             for<'a> S: StateFunctions<'a>,
 {
-    pub fn new() -> WithLoginRequired<WithTransaction<Self, S>, S> {
+    pub fn new(keep_durable: bool) -> WithLoginRequired<WithTransaction<Self, S>, S> {
         debug!("new action UnsubscribeFrom");
 
         let action = Self {
+            keep_durable,
             phantom_data: PhantomData,
         };
 
@@ -158,7 +174,7 @@ impl<S> Action<S> for UnsubscribeAll<S>
 
         state
             .get_pub_sub()
-            .unsubscribe_all(user_id)
+            .unsubscribe_all(user_id, self.keep_durable)
             .map_err(|err| Error::PublishError(err))
             .and_then(|res| ActionRes::new("unsubscribeFrom", SubscriptionResult::UnsubscribedAll))
     }
@@ -208,6 +224,145 @@ impl<S> Action<S> for GetSubscribers<S>
     }
 }
 
+///the channels of the entities of the domain that the user can read, with the number of their subscribers
+#[derive(Debug)]
+pub struct ListChannels<S = ActionState>  {
+    pub phantom_data: PhantomData<(S)>,
+}
+
+impl<S> ListChannels<S>
+    where
+            for<'a> S: StateFunctions<'a>,
+{
+    pub fn new() -> WithLoginRequired<WithTransaction<Self, S>, S> {
+        debug!("new action ListChannels");
+
+        let action = Self {
+            phantom_data: PhantomData,
+        };
+
+        let action = WithTransaction::new(action);
+        let action = WithLoginRequired::new(action);
+
+        action
+    }
+}
+
+fn entity_channels<T, R>(retriever: &R) -> Result<Vec<Channels>, Error>
+    where
+        T: RawEntityTypes,
+        R: RetrieverFunctions,
+{
+    let entities: Vec<T> = retriever
+        .get_all()
+        .or_else(|err| Err(Error::Entity(err)))?;
+
+    Ok(entities
+        .iter()
+        .map(|x| Channels::entity::<T>(x.my_name()))
+        .collect())
+}
+
+impl<S> Action<S> for ListChannels<S>
+    where
+            for<'a> S: StateFunctions<'a>,
+{
+    type Ret = ChannelsResult;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        debug!("Calling ListChannels");
+
+        let authorization = state.get_authorization();
+        let retriever = state.get_entity_retreiver_functions();
+
+        let mut channels = vec![];
+        if let Some(username) = authorization.username() {
+            channels.push(Channels::user(&username));
+        }
+
+        let tables: Vec<data::DataStoreEntity> = retriever
+            .get_all()
+            .or_else(|err| Err(Error::Entity(err)))?;
+        for table in tables {
+            channels.push(Channels::entity::<data::DataStoreEntity>(table.my_name()));
+            channels.push(Channels::table(table.my_name()));
+        }
+        channels.extend(entity_channels::<data::DataQueryEntity, _>(&retriever)?);
+        channels.extend(entity_channels::<data::Script, _>(&retriever)?);
+        channels.extend(entity_channels::<data::View, _>(&retriever)?);
+        channels.extend(entity_channels::<data::Webhook, _>(&retriever)?);
+
+        // the same permissions as `SubscribeTo`, so every listed channel can be subscribed to
+        if !authorization.is_admin() {
+            let mut user_permissions = authorization.permissions();
+            if let Some(username) = authorization.username() {
+                user_permissions.insert(Permission::user(username));
+            }
+            let user_permissions = resolve_conditional_permissions(state, user_permissions);
+            channels.retain(|x| user_permissions.contains(&x.required_permission()));
+        }
+
+        let subscriber_counts = state
+            .get_pub_sub()
+            .count_subscribers(&channels)
+            .map_err(|err| Error::PublishError(err))?;
+
+        let channels = channels
+            .into_iter()
+            .map(|channel| {
+                let subscribers = subscriber_counts.get(&channel).cloned().unwrap_or(0);
+                ChannelInfo { channel, subscribers }
+            })
+            .collect();
+
+        ActionRes::new("listChannels", ChannelsResult(channels))
+    }
+}
+
+///the channels the user is subscribed to
+#[derive(Debug)]
+pub struct ListMySubscriptions<S = ActionState>  {
+    pub phantom_data: PhantomData<(S)>,
+}
+
+impl<S> ListMySubscriptions<S>
+    where
+            for<'a> S: StateFunctions<'a>,
+{
+    pub fn new() -> WithLoginRequired<WithTransaction<Self, S>, S> {
+        debug!("new action ListMySubscriptions");
+
+        let action = Self {
+            phantom_data: PhantomData,
+        };
+
+        let action = WithTransaction::new(action);
+        let action = WithLoginRequired::new(action);
+
+        action
+    }
+}
+
+impl<S> Action<S> for ListMySubscriptions<S>
+    where
+            for<'a> S: StateFunctions<'a>,
+{
+    type Ret = SubscriptionsResult;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        debug!("Calling ListMySubscriptions");
+
+        let user_id = state
+            .get_authorization()
+            .user_id()
+            .ok_or_else(|| Error::Unauthorized)?;
+
+        state
+            .get_pub_sub()
+            .get_subscriptions(user_id)
+            .map_err(|err| Error::PublishError(err))
+            .and_then(|res| ActionRes::new("listMySubscriptions", SubscriptionsResult(res)))
+    }
+}
+
 #[derive(Debug)]
 pub struct GetMessages<S = ActionState>  {
     pub start_time: chrono::NaiveDateTime,
@@ -239,7 +394,7 @@ impl<S> Action<S> for GetMessages<S>
     where
             for<'a> S: StateFunctions<'a>,
 {
-    type Ret = Vec<data::Message>;
+    type Ret = Vec<data::event::Event>;
     fn call(&self, state: &S) -> ActionResult<Self::Ret> {
         debug!("Calling GetMessages");
 
@@ -256,6 +411,287 @@ impl<S> Action<S> for GetMessages<S>
     }
 }
 
+///the messages of the durable subscriptions that the client didn't acknowledge yet
+#[derive(Debug)]
+pub struct GetPendingMessages<S = ActionState>  {
+    /// the ones that were already sent are sent again, e.g. after reconnecting
+    pub redeliver: bool,
+    pub phantom_data: PhantomData<(S)>,
+}
+
+impl<S> GetPendingMessages<S>
+    where
+            for<'a> S: StateFunctions<'a>,
+{
+    pub fn new(redeliver: bool) -> WithLoginRequired<WithTransaction<Self, S>, S> {
+        debug!("new action GetPendingMessages");
+
+        let action = Self {
+            redeliver,
+            phantom_data: PhantomData,
+        };
+
+        let action = WithTransaction::new(action);
+        let action = WithLoginRequired::new(action);
+
+        action
+    }
+}
+
+impl<S> Action<S> for GetPendingMessages<S>
+    where
+            for<'a> S: StateFunctions<'a>,
+{
+    type Ret = Vec<data::event::Event>;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        debug!("Calling GetPendingMessages");
+
+        let user_id = state
+            .get_authorization()
+            .user_id()
+            .ok_or_else(|| Error::Unauthorized)?;
+
+        state
+            .get_pub_sub()
+            .get_pending_messages(user_id, self.redeliver)
+            .map_err(|err| Error::PublishError(err))
+            .and_then(|res| ActionRes::new("getPendingMessages", res))
+    }
+}
+
+///the messages of the durable subscriptions won't be sent again
+#[derive(Debug)]
+pub struct AckMessages<S = ActionState>  {
+    pub message_ids: Vec<i64>,
+    pub phantom_data: PhantomData<(S)>,
+}
+
+impl<S> AckMessages<S>
+    where
+            for<'a> S: StateFunctions<'a>,
+{
+    pub fn new(message_ids: Vec<i64>) -> WithLoginRequired<WithTransaction<Self, S>, S> {
+        debug!("new action AckMessages");
+
+        let action = Self {
+            message_ids,
+            phantom_data: PhantomData,
+        };
+
+        let action = WithTransaction::new(action);
+        let action = WithLoginRequired::new(action);
+
+        action
+    }
+}
+
+impl<S> Action<S> for AckMessages<S>
+    where
+            for<'a> S: StateFunctions<'a>,
+{
+    type Ret = AckMessagesResult;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        debug!("Calling AckMessages");
+
+        let user_id = state
+            .get_authorization()
+            .user_id()
+            .ok_or_else(|| Error::Unauthorized)?;
+
+        state
+            .get_pub_sub()
+            .ack_messages(user_id, &self.message_ids)
+            .map_err(|err| Error::PublishError(err))
+            .and_then(|acked| ActionRes::new("ackMessages", AckMessagesResult { acked }))
+    }
+}
+
+///deletes the published messages past the retention, admin only
+///the cleanup job runs it with the retention of the server, see `AppStateBuilder::message_retention`
+#[derive(Debug)]
+pub struct PurgeMessages<S = ActionState>  {
+    pub retention: MessageRetention,
+    pub phantom_data: PhantomData<(S)>,
+}
+
+impl<S> PurgeMessages<S>
+    where
+            for<'a> S: StateFunctions<'a>,
+{
+    pub fn new(retention: MessageRetention) -> WithAudit<WithPermissionRequired<WithWriteAccess<WithTransaction<Self, S>, S>, S>, S> {
+        debug!("new action PurgeMessages");
+
+        let audit_target = AuditTarget::none();
+        let action = Self {
+            retention,
+            phantom_data: PhantomData,
+        };
+
+        let action = WithTransaction::new(action);
+        let action = WithWriteAccess::new(action);
+        let action = WithPermissionRequired::new(action, Permission::user_admin());
+        let action = WithAudit::new(action, "purgeMessages", audit_target);
+
+        action
+    }
+}
+
+impl<S> Action<S> for PurgeMessages<S>
+    where
+            for<'a> S: StateFunctions<'a>,
+{
+    type Ret = PurgeMessagesResult;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        debug!("Calling PurgeMessages");
+
+        let purged_messages = state
+            .get_pub_sub()
+            .purge_messages(&self.retention)
+            .map_err(|err| Error::PublishError(err))?;
+        state.get_broadcast_metrics().record_purge(purged_messages);
+
+        ActionRes::new("purgeMessages", PurgeMessagesResult { purged_messages })
+    }
+}
+
+///removes the subscriptions past their ttl, and with `inactive_for` (in seconds) the subscriptions
+///of the users that haven't logged in since, admin only
+///the cleanup job runs it with the inactivity of the server, see `AppStateBuilder::subscription_inactivity`
+#[derive(Debug)]
+pub struct ExpireSubscriptions<S = ActionState>  {
+    pub inactive_for: Option<u64>,
+    pub phantom_data: PhantomData<(S)>,
+}
+
+impl<S> ExpireSubscriptions<S>
+    where
+            for<'a> S: StateFunctions<'a>,
+{
+    pub fn new(inactive_for: Option<u64>) -> WithAudit<WithPermissionRequired<WithWriteAccess<WithTransaction<Self, S>, S>, S>, S> {
+        debug!("new action ExpireSubscriptions");
+
+        let audit_target = AuditTarget::none();
+        let action = Self {
+            inactive_for,
+            phantom_data: PhantomData,
+        };
+
+        let action = WithTransaction::new(action);
+        let action = WithWriteAccess::new(action);
+        let action = WithPermissionRequired::new(action, Permission::user_admin());
+        let action = WithAudit::new(action, "expireSubscriptions", audit_target);
+
+        action
+    }
+}
+
+impl<S> Action<S> for ExpireSubscriptions<S>
+    where
+            for<'a> S: StateFunctions<'a>,
+{
+    type Ret = ExpireSubscriptionsResult;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        debug!("Calling ExpireSubscriptions");
+
+        state
+            .get_pub_sub()
+            .expire_subscriptions(self.inactive_for)
+            .map_err(|err| Error::PublishError(err))
+            .and_then(|expired_subscriptions| ActionRes::new("expireSubscriptions", ExpireSubscriptionsResult { expired_subscriptions }))
+    }
+}
+
+///the messages that couldn't be delivered, newest first, admin only
+#[derive(Debug)]
+pub struct GetDeadLetters<S = ActionState>  {
+    pub limit: usize,
+    pub offset: usize,
+    pub phantom_data: PhantomData<(S)>,
+}
+
+impl<S> GetDeadLetters<S>
+    where
+            for<'a> S: StateFunctions<'a>,
+{
+    pub fn new(limit: usize, offset: usize) -> WithPermissionRequired<WithTransaction<Self, S>, S> {
+        debug!("new action GetDeadLetters");
+
+        let action = Self {
+            limit,
+            offset,
+            phantom_data: PhantomData,
+        };
+
+        let action = WithTransaction::new(action);
+        let action = WithPermissionRequired::new(action, Permission::user_admin());
+
+        action
+    }
+}
+
+impl<S> Action<S> for GetDeadLetters<S>
+    where
+            for<'a> S: StateFunctions<'a>,
+{
+    type Ret = DeadLettersResult;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        debug!("Calling GetDeadLetters");
+
+        let (items, total_count) = state
+            .get_pub_sub()
+            .get_dead_letters(self.limit, self.offset)
+            .map_err(|err| Error::PublishError(err))?;
+
+        ActionRes::new("getDeadLetters", DeadLettersResult(ListPage { items, offset: self.offset, total_count }))
+    }
+}
+
+///delivers a dead letter again, as if it was just published, admin only
+#[derive(Debug)]
+pub struct RedeliverDeadLetter<S = ActionState>  {
+    pub id: i64,
+    pub phantom_data: PhantomData<(S)>,
+}
+
+impl<S> RedeliverDeadLetter<S>
+    where
+            for<'a> S: StateFunctions<'a>,
+{
+    pub fn new(id: i64) -> WithAudit<WithPermissionRequired<WithWriteAccess<WithTransaction<Self, S>, S>, S>, S> {
+        debug!("new action RedeliverDeadLetter");
+
+        let audit_target = AuditTarget::new("deadLetter", &id.to_string());
+        let action = Self {
+            id,
+            phantom_data: PhantomData,
+        };
+
+        let action = WithTransaction::new(action);
+        let action = WithWriteAccess::new(action);
+        let action = WithPermissionRequired::new(action, Permission::user_admin());
+        let action = WithAudit::new(action, "redeliverDeadLetter", audit_target);
+
+        action
+    }
+}
+
+impl<S> Action<S> for RedeliverDeadLetter<S>
+    where
+            for<'a> S: StateFunctions<'a>,
+{
+    type Ret = DeadLetterResult;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        debug!("Calling RedeliverDeadLetter");
+
+        state
+            .get_pub_sub()
+            .redeliver_dead_letter(self.id)
+            .map_err(|err| Error::PublishError(err))?
+            .ok_or(Error::NotFound)
+            .and_then(|res| ActionRes::new("redeliverDeadLetter", DeadLetterResult(res)))
+    }
+}
+
 impl Channels {
     fn required_permission(&self) -> Permission {
         match self {
@@ -263,8 +699,130 @@ impl Channels {
             Channels::Defaults(Defaults::Query(name)) => Permission::read_entity::<data::DataQueryEntity>(name.to_owned()),
             Channels::Defaults(Defaults::Script(name)) => Permission::read_entity::<data::Script>(name.to_owned()),
             Channels::Defaults(Defaults::View(name)) => Permission::read_entity::<data::View>(name.to_owned()),
+            Channels::Defaults(Defaults::Webhook(name)) => Permission::read_entity::<data::Webhook>(name.to_owned()),
             Channels::Defaults(Defaults::TableData(name)) => Permission::get_table_data(name.to_owned()),
+            Channels::Defaults(Defaults::User(username)) => Permission::user(username.to_owned()),
             Channels::Subscribers(Sub::Subscribers(channel)) => Channels::Defaults(channel.to_owned()).required_permission(),
         }
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use serde_json::from_value;
+    use model::actions::entity_actions::CreateEntity;
+    use test_common::random_identifier;
+    use test_common::with_state;
+    use test_common::MockState;
+
+    use diesel;
+    use diesel::RunQueryDsl;
+    use data::dead_letter::MAX_PENDING_ATTEMPTS;
+    use data::dead_letter::DeadLetterTarget;
+    use data::event::EVENT_SCHEMA_VERSION;
+
+    #[test]
+    fn test_list_channels_and_subscriptions() {
+        with_state(|state| {
+            let name = format!("my_query_{}", random_identifier());
+            let new_query: data::DataQueryEntity = from_value(json!({
+                "name": name,
+                "description": "blah blah blah",
+                "statement": "SELECT * FROM a_table"
+            })).unwrap();
+            CreateEntity::<data::DataQueryEntity, MockState>::new(new_query).call(&state).unwrap();
+
+            let channel = Channels::Defaults(Defaults::Query(name.to_owned()));
+            let subscribers_of = |channels: &[ChannelInfo]| channels
+                .iter()
+                .find(|x| x.channel == channel)
+                .map(|x| x.subscribers);
+
+            let ChannelsResult(channels) = ListChannels::<MockState>::new().call(&state).unwrap().get_data();
+            assert_eq!(subscribers_of(&channels), Some(0));
+
+            SubscribeTo::<MockState>::new(channel.to_owned(), false, None).call(&state).unwrap();
+
+            let ChannelsResult(channels) = ListChannels::<MockState>::new().call(&state).unwrap().get_data();
+            assert_eq!(subscribers_of(&channels), Some(1));
+
+            let SubscriptionsResult(subscriptions) = ListMySubscriptions::<MockState>::new().call(&state).unwrap().get_data();
+            assert!(subscriptions.iter().any(|x| x.channel == channel && !x.durable));
+        });
+    }
+
+    #[test]
+    fn test_expire_subscriptions() {
+        with_state(|state| {
+            let expiring = Channels::table(&format!("my_table_{}", random_identifier()));
+            let kept = Channels::table(&format!("my_table_{}", random_identifier()));
+            SubscribeTo::<MockState>::new(expiring.to_owned(), false, Some(0)).call(&state).unwrap();
+            SubscribeTo::<MockState>::new(kept.to_owned(), false, Some(60 * 60)).call(&state).unwrap();
+
+            let ExpireSubscriptionsResult { expired_subscriptions } = ExpireSubscriptions::<MockState>::new(None).call(&state).unwrap().get_data();
+            assert!(expired_subscriptions >= 1);
+
+            let SubscriptionsResult(subscriptions) = ListMySubscriptions::<MockState>::new().call(&state).unwrap().get_data();
+            assert!(!subscriptions.iter().any(|x| x.channel == expiring));
+            assert!(subscriptions.iter().any(|x| x.channel == kept && x.expires_at.is_some()));
+        });
+    }
+
+    #[test]
+    fn test_event_envelope() {
+        with_state(|state| {
+            let channel = Channels::table(&format!("my_table_{}", random_identifier()));
+            SubscribeTo::<MockState>::new(channel.to_owned(), true, None).call(&state).unwrap();
+            state.get_pub_sub().publish(channel.to_owned(), "insertTableData".to_string(), &json!([{ "id": 1 }])).unwrap();
+            state.get_pub_sub().publish(channel.to_owned(), "deleteTableData".to_string(), &json!([{ "id": 1 }])).unwrap();
+
+            let events: Vec<_> = GetPendingMessages::<MockState>::new(false)
+                .call(&state)
+                .unwrap()
+                .get_data()
+                .into_iter()
+                .filter(|x| x.channel == channel)
+                .collect();
+            assert_eq!(events.len(), 2);
+            assert_eq!(events[0].action, "insertTableData");
+            assert_eq!(events[1].action, "deleteTableData");
+            assert_eq!(events[1].sequence, events[0].sequence + 1);
+            assert_eq!(events[0].actor, Some("Admin".to_string()));
+            assert!(events.iter().all(|x| x.schema_version == EVENT_SCHEMA_VERSION));
+        });
+    }
+
+    #[test]
+    fn test_dead_letters() {
+        with_state(|state| {
+            let channel = Channels::table(&format!("my_table_{}", random_identifier()));
+            SubscribeTo::<MockState>::new(channel.to_owned(), true, None).call(&state).unwrap();
+            state.get_pub_sub().publish(channel.to_owned(), "insertTableData".to_string(), &json!([{ "id": 1 }])).unwrap();
+
+            let pending = GetPendingMessages::<MockState>::new(false).call(&state).unwrap().get_data();
+            assert!(pending.iter().any(|x| x.channel == channel));
+
+            // sent over and over, and still not acknowledged
+            diesel::sql_query(r#"UPDATE "pending_message" SET "attempts" = $1, "delivered_at" = NOW() - INTERVAL '1 hour'"#)
+                .bind::<diesel::sql_types::Integer, _>(MAX_PENDING_ATTEMPTS)
+                .execute(&state.0.database)
+                .unwrap();
+
+            let pending = GetPendingMessages::<MockState>::new(false).call(&state).unwrap().get_data();
+            assert!(!pending.iter().any(|x| x.channel == channel));
+
+            let DeadLettersResult(page) = GetDeadLetters::<MockState>::new(10, 0).call(&state).unwrap().get_data();
+            let dead_letter = page.items.iter().find(|x| x.channel == channel).unwrap();
+            assert_eq!(dead_letter.data, json!([{ "id": 1 }]));
+            assert_eq!(dead_letter.target, DeadLetterTarget::Subscriber { username: "admin".to_string() });
+
+            let DeadLetterResult(redelivered) = RedeliverDeadLetter::<MockState>::new(dead_letter.id).call(&state).unwrap().get_data();
+            assert_eq!(redelivered.id, dead_letter.id);
+            assert_eq!(RedeliverDeadLetter::<MockState>::new(dead_letter.id).call(&state).unwrap_err(), Error::NotFound);
+
+            let pending = GetPendingMessages::<MockState>::new(false).call(&state).unwrap().get_data();
+            assert!(pending.iter().any(|x| x.channel == channel));
+        });
+    }
+}
@@ -1,6 +1,7 @@
 
 use std::result::Result::Ok;
 use std::marker::PhantomData;
+use std::time::Instant;
 
 use model::actions::results::*;
 use model::actions::error::Error;
@@ -8,23 +9,44 @@ use model::query;
 
 use data;
 use data::permissions::*;
+use data::channels::Channels;
+use data::schedule::QuerySchedule;
+use data::schedule::ScheduledRun;
+use data::result_format::ResultFormat;
+use data::error::DatastoreError;
+use data::audit::AuditTarget;
 
 use model::actions::decorator::*;
 use model::actions::Action;
 use model::actions::ActionRes;
 use model::actions::ActionResult;
+use model::actions::entity_actions::WithFilterListByPermission;
+use model::actions::usage_actions::record_usage;
 use model::entity::RetrieverFunctions;
+use model::entity::ModifierFunctions;
+use model::entity::results::Created;
+use model::entity::results::Updated;
 use model::query::QueryActionOps;
+use model::table::DatastoreActionOps;
 
 use state::StateFunctions;
 use state::ActionState;
+use state::PubSubOps;
+use state::authorization::AuthorizationOps;
+
+/// most rows returned by a query, whatever limit is asked for
+pub const MAX_QUERY_ROWS: usize = 10000;
+
+/// number of runs returned by `ListScheduledRuns`
+pub const SCHEDULED_RUNS_LIMIT: usize = 100;
 
 // Query Action
 #[derive(Debug)]
 pub struct RunQuery<S = ActionState>  {
     pub query_name: String,
     pub params: serde_json::Value,
-    pub format: serde_json::Value,
+    pub format: ResultFormat,
+    pub limit: data::QueryLimit,
     pub phantom_data: PhantomData<(S)>,
 }
 
@@ -32,11 +54,14 @@ impl<S> RunQuery<S>
     where
         for<'a> S: StateFunctions<'a>,
 {
-    pub fn new(query_name: String, params: serde_json::Value) -> WithPermissionRequired<WithTransaction<Self, S>, S> {
+    /// `limit` is capped to `MAX_QUERY_ROWS`, which is also the default
+    pub fn new(query_name: String, params: serde_json::Value, format: ResultFormat, limit: Option<usize>, offset: usize) -> WithPermissionRequired<WithTransaction<Self, S>, S> {
+        let limit = limit.unwrap_or(MAX_QUERY_ROWS).min(MAX_QUERY_ROWS);
         let action = Self {
             query_name: query_name.to_owned(),
             params,
-            format: json!({}), //TODO:... example: TableDataFormat::Rows
+            format,
+            limit: data::QueryLimit { limit, offset },
             phantom_data: PhantomData,
         };
 
@@ -67,9 +92,882 @@ impl<S> Action<S> for RunQuery<S>
             .and_then(|query| {
                 state
                     .get_query_controller()
-                    .run_query(&query, &self.params, &self.format)
+                    .run_query(&query, &self.params, &json!({}), &self.limit)
                     .map_err(|err| Error::Datastore(err))
             })
-            .and_then(|res| ActionRes::new("runQuery", RunQueryResult(res)))
+            .and_then(|res| {
+                if res.truncated {
+                    debug!("the result of {:?} was truncated to {} rows", &self.query_name, self.limit.limit);
+                }
+                let data = self.format
+                    .apply(res.data)
+                    .map_err(|err| Error::Datastore(err))?;
+                record_usage(state, "query", &self.query_name);
+                ActionRes::new("runQuery", RunQueryResult { data, truncated: res.truncated })
+            })
+    }
+
+    fn runs_domain_queries() -> bool {
+        true
+    }
+}
+
+/// Shows how the query would be run with the given params, needs the same permission as running it
+#[derive(Debug)]
+pub struct ExplainQuery<S = ActionState>  {
+    pub query_name: String,
+    pub params: serde_json::Value,
+    pub phantom_data: PhantomData<(S)>,
+}
+
+impl<S> ExplainQuery<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    pub fn new(query_name: String, params: serde_json::Value) -> WithPermissionRequired<WithTransaction<Self, S>, S> {
+        let action = Self {
+            query_name: query_name.to_owned(),
+            params,
+            phantom_data: PhantomData,
+        };
+
+        let action_with_transaction = WithTransaction::new(action);
+        let action_with_permission =
+            WithPermissionRequired::new(action_with_transaction, Permission::run_query(query_name));
+
+        action_with_permission
+    }
+}
+
+impl<S> Action<S> for ExplainQuery<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    type Ret = ExplainQueryResult;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        debug!("Calling ExplainQuery");
+
+        state
+            .get_entity_retreiver_functions()
+            .get_one(&self.query_name)
+            .map_err(|err| Error::Entity(err))
+            .and_then(|res| match res {
+                Some(query) => Ok(query),
+                None => Err(Error::NotFound),
+            })
+            .and_then(|query| {
+                state
+                    .get_query_controller()
+                    .explain_query(&query, &self.params)
+                    .map_err(|err| Error::Datastore(err))
+            })
+            .and_then(|res| ActionRes::new("explainQuery", ExplainQueryResult(res)))
+    }
+}
+
+/// Inserts the rows of the query result into the table, duplicates are skipped
+/// returns the number of inserted rows
+fn insert_result_rows<S>(state: &S, table: &data::DataStoreEntity, data: &serde_json::Value) -> Result<usize, Error>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    let rows = data::row_history::table_rows(data);
+    if rows.is_empty() {
+        return Ok(0);
+    }
+
+    let inserted = state
+        .get_table_controller()
+        .insert_row(table, &json!(rows), false)
+        .map_err(|err| Error::Datastore(err))?;
+
+    Ok(data::row_history::table_rows(&inserted).len())
+}
+
+fn write_to_table<S>(state: &S, table_name: &str, data: &serde_json::Value) -> Result<(), Error>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    let table: data::DataStoreEntity = state
+        .get_entity_retreiver_functions()
+        .get_one(table_name)
+        .map_err(|err| Error::Entity(err))
+        .and_then(|res| res.ok_or(Error::NotFound))?;
+
+    insert_result_rows(state, &table, data)?;
+
+    Ok(())
+}
+
+/// Runs a query and writes its result into a table, the table is created from the result if it doesn't exist
+/// The whole result is written, a query returning more than `MAX_QUERY_ROWS` rows fails
+#[derive(Debug)]
+pub struct RunQueryIntoTable<S = ActionState> {
+    pub query_name: String,
+    pub target_table: String,
+    pub mode: data::TableWriteMode,
+    pub params: serde_json::Value,
+    pub phantom_data: PhantomData<(S)>,
+}
+
+impl<S> RunQueryIntoTable<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    pub fn new(query_name: String, target_table: String, mode: data::TableWriteMode, params: serde_json::Value) -> WithAudit<WithPermissionFor<WithWriteAccess<WithDispatch<WithTransaction<Self, S>, S>, S>, S>, S> {
+        let audit_target = AuditTarget::new("table", &target_table);
+        let channel = Channels::table(&target_table);
+        let run_permission = Permission::run_query(query_name.to_owned());
+        let write_permission = Permission::modify_table_data(target_table.to_owned());
+
+        let action = Self {
+            query_name,
+            target_table,
+            mode,
+            params,
+            phantom_data: PhantomData,
+        };
+
+        let action_with_transaction = WithTransaction::new(action);
+        let action_with_dispatch = WithDispatch::new(action_with_transaction, channel);
+        let action_with_write_access = WithWriteAccess::new(action_with_dispatch);
+        let action_with_permission =
+            WithPermissionFor::new(
+                action_with_write_access,
+                move |user_permissions, _all_permissions| {
+                    user_permissions.contains(&run_permission) && user_permissions.contains(&write_permission)
+                });
+
+        let action_with_audit = WithAudit::new(action_with_permission, "runQueryIntoTable", audit_target);
+
+        action_with_audit
+    }
+
+    /// creating the table needs the permission to create tables as well
+    fn create_table(&self, state: &S, data: &serde_json::Value) -> Result<data::DataStoreEntity, Error> {
+        let authorization = state.get_authorization();
+        if !authorization.is_admin() && !authorization.permissions().contains(&Permission::create_entity::<data::DataStoreEntity>()) {
+            return Err(Error::Unauthorized);
+        }
+
+        let schema = state
+            .get_query_controller()
+            .result_schema(data)
+            .map_err(|err| Error::Datastore(err))?;
+
+        let table = data::DataStoreEntity {
+            name: self.target_table.to_owned(),
+            description: format!("Result of the query {}", &self.query_name),
+            schema,
+            audit_rows: false,
+            scope: None,
+        };
+
+        state
+            .get_entity_modifier_function()
+            .create(table)
+            .map_err(|err| Error::Entity(err))
+            .and_then(|res| match res {
+                Created::Success { new } => Ok(new),
+                Created::Fail { .. } => Err(Error::AlreadyExists),
+            })
+    }
+}
+
+impl<S> Action<S> for RunQueryIntoTable<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    type Ret = QueryIntoTableResult;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        debug!("Calling RunQueryIntoTable");
+
+        let query: data::DataQueryEntity = state
+            .get_entity_retreiver_functions()
+            .get_one(&self.query_name)
+            .map_err(|err| Error::Entity(err))
+            .and_then(|res| res.ok_or(Error::NotFound))?;
+
+        let limit = data::QueryLimit { limit: MAX_QUERY_ROWS, offset: 0 };
+        let res = state
+            .get_query_controller()
+            .run_query(&query, &self.params, &json!({}), &limit)
+            .map_err(|err| Error::Datastore(err))?;
+        if res.truncated {
+            return Err(Error::Datastore(DatastoreError::InvalidQuery(format!("the query returned more than {} rows", MAX_QUERY_ROWS))));
+        }
+
+        let existing: Option<data::DataStoreEntity> = state
+            .get_entity_retreiver_functions()
+            .get_one(&self.target_table)
+            .map_err(|err| Error::Entity(err))?;
+
+        let (table, created) = match existing {
+            Some(table) => (table, false),
+            None => (self.create_table(state, &res.data)?, true),
+        };
+
+        let deleted_rows = match self.mode {
+            data::TableWriteMode::Replace if !created => {
+                let deleted = state
+                    .get_table_controller()
+                    .delete_all_rows(&table)
+                    .map_err(|err| Error::Datastore(err))?;
+                data::row_history::table_rows(&deleted).len()
+            },
+            _ => 0,
+        };
+
+        let inserted_rows = insert_result_rows(state, &table, &res.data)?;
+        info!("wrote {} rows of {} into {} ({} deleted)", inserted_rows, &self.query_name, &self.target_table, deleted_rows);
+
+        ActionRes::new("runQueryIntoTable", QueryIntoTableResult { table, created, deleted_rows, inserted_rows })
+    }
+}
+
+/// Runs the query once and records the run, the run is returned even if the query failed
+fn run_scheduled_query<S>(state: &S, query: &data::DataQueryEntity, schedule: &QuerySchedule) -> Result<ScheduledRun, Error>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    let started_at = chrono::Utc::now().naive_utc();
+    let timer = Instant::now();
+    let limit = data::QueryLimit { limit: MAX_QUERY_ROWS, offset: 0 };
+
+    let result = state
+        .get_query_controller()
+        .run_query(query, &schedule.params, &json!({}), &limit)
+        .map_err(|err| Error::Datastore(err))
+        .and_then(|res| {
+            if let Some(target_table) = &schedule.target_table {
+                write_to_table(state, target_table, &res.data)?;
+            }
+            Ok(res)
+        });
+
+    let elapsed = timer.elapsed();
+    let (row_count, error) = match &result {
+        Ok(res) => (res.data["data"].as_array().map(|rows| rows.len() as i64), None),
+        Err(err) => (None, Some(err.to_string())),
+    };
+    let run = ScheduledRun {
+        query_name: query.name.to_owned(),
+        started_at,
+        duration_ms: (elapsed.as_secs() * 1000 + u64::from(elapsed.subsec_millis())) as i64,
+        succeeded: result.is_ok(),
+        row_count,
+        error,
+    };
+
+    match &run.error {
+        None => info!("scheduled query {} returned {:?} rows in {}ms", &query.name, &run.row_count, run.duration_ms),
+        Some(err) => warn!("scheduled query {} failed: {}", &query.name, err),
+    };
+
+    state
+        .get_query_controller()
+        .record_scheduled_run(&run)
+        .map_err(|err| Error::Datastore(err))?;
+
+    let message = json!({
+        "run": &run,
+        "result": result.ok().map(|res| RunQueryResult { data: res.data, truncated: res.truncated }),
+    });
+    state
+        .get_pub_sub()
+        .publish(Channels::entity::<data::DataQueryEntity>(&query.name), "scheduledRun".to_string(), &message)
+        .map_err(Error::PublishError)?;
+
+    Ok(run)
+}
+
+/// Runs every query whose schedule matches the given minute, called by the scheduler job
+/// A failing query doesn't stop the others, its run is recorded with the error
+#[derive(Debug)]
+pub struct RunScheduledQueries<S = ActionState> {
+    pub time: chrono::NaiveDateTime,
+    pub phantom_data: PhantomData<(S)>,
+}
+
+impl<S> RunScheduledQueries<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    pub fn new(time: chrono::NaiveDateTime) -> WithPermissionRequired<WithWriteAccess<Self, S>, S> {
+        let action = Self {
+            time,
+            phantom_data: PhantomData,
+        };
+
+        let action_with_write_access = WithWriteAccess::new(action);
+        let action_with_permission = WithPermissionRequired::new(action_with_write_access, Permission::user_admin());
+
+        action_with_permission
+    }
+}
+
+impl<S> Action<S> for RunScheduledQueries<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    type Ret = ScheduledRunsResult;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        debug!("Calling RunScheduledQueries");
+
+        let queries: Vec<data::DataQueryEntity> = state
+            .get_entity_retreiver_functions()
+            .get_all()
+            .or_else(|err| Err(Error::Entity(err)))?;
+
+        let runs = queries
+            .iter()
+            .filter_map(|query| {
+                let schedule = query.schedule.as_ref()?;
+                match schedule.cron_schedule() {
+                    Ok(cron_schedule) => if cron_schedule.matches(&self.time) { Some((query, schedule)) } else { None },
+                    Err(err) => {
+                        warn!("the schedule of {} is invalid: {}", &query.name, err);
+                        None
+                    },
+                }
+            })
+            .filter_map(|(query, schedule)| {
+                run_scheduled_query(state, query, schedule)
+                    .map_err(|err| error!("could not run the scheduled query {}: {:?}", &query.name, &err))
+                    .ok()
+            })
+            .collect();
+
+        ActionRes::new("runScheduledQueries", ScheduledRunsResult(runs))
+    }
+}
+
+/// The latest runs of a scheduled query, newest first
+#[derive(Debug)]
+pub struct ListScheduledRuns<S = ActionState> {
+    pub query_name: String,
+    pub phantom_data: PhantomData<(S)>,
+}
+
+impl<S> ListScheduledRuns<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    pub fn new(query_name: String) -> WithPermissionRequired<Self, S> {
+        let action = Self {
+            query_name: query_name.to_owned(),
+            phantom_data: PhantomData,
+        };
+
+        let action_with_permission =
+            WithPermissionRequired::new(action, Permission::run_query(query_name));
+
+        action_with_permission
+    }
+}
+
+impl<S> Action<S> for ListScheduledRuns<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    type Ret = ScheduledRunsResult;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        debug!("Calling ListScheduledRuns");
+
+        state
+            .get_entity_retreiver_functions()
+            .get_one(&self.query_name)
+            .map_err(|err| Error::Entity(err))
+            .and_then(|res| match res {
+                Some(query) => Ok(query),
+                None => Err(Error::NotFound),
+            })
+            .and_then(|query| {
+                state
+                    .get_query_controller()
+                    .scheduled_runs(&query, SCHEDULED_RUNS_LIMIT)
+                    .map_err(|err| Error::Datastore(err))
+            })
+            .and_then(|res| ActionRes::new("listScheduledRuns", ScheduledRunsResult(res)))
+    }
+}
+
+/// Every stored version of a query, newest first
+#[derive(Debug)]
+pub struct GetQueryHistory<S = ActionState> {
+    pub query_name: String,
+    pub phantom_data: PhantomData<(S)>,
+}
+
+impl<S> GetQueryHistory<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    pub fn new(query_name: String) -> WithPermissionRequired<WithTransaction<Self, S>, S> {
+        let action = Self {
+            query_name: query_name.to_owned(),
+            phantom_data: PhantomData,
+        };
+
+        let action_with_transaction = WithTransaction::new(action);
+        let action_with_permission =
+            WithPermissionRequired::new(action_with_transaction, Permission::read_entity::<data::DataQueryEntity>(query_name));
+
+        action_with_permission
+    }
+}
+
+impl<S> Action<S> for GetQueryHistory<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    type Ret = QueryHistoryResult;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        debug!("Calling GetQueryHistory");
+
+        let history = state
+            .get_query_controller()
+            .query_history(&self.query_name)
+            .map_err(|err| Error::Datastore(err))?;
+
+        if history.is_empty() {
+            return Err(Error::NotFound);
+        }
+
+        ActionRes::new("getQueryHistory", QueryHistoryResult(history))
+    }
+}
+
+/// Stores an older version of the query as its newest version, the query keeps its current name
+#[derive(Debug)]
+pub struct RestoreQueryVersion<S = ActionState> {
+    pub query_name: String,
+    pub version: i64,
+    pub phantom_data: PhantomData<(S)>,
+}
+
+impl<S> RestoreQueryVersion<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    pub fn new(query_name: String, version: i64) -> WithAudit<WithPermissionRequired<WithWriteAccess<WithDispatch<WithTransaction<Self, S>, S>, S>, S>, S> {
+        let audit_target = AuditTarget::new("query", &query_name);
+        let channel = Channels::entity::<data::DataQueryEntity>(&query_name);
+        let action = Self {
+            query_name: query_name.to_owned(),
+            version,
+            phantom_data: PhantomData,
+        };
+
+        let action_with_transaction = WithTransaction::new(action);
+        let action_with_dispatch = WithDispatch::new(action_with_transaction, channel);
+        let action_with_write_access = WithWriteAccess::new(action_with_dispatch);
+        let action_with_permission =
+            WithPermissionRequired::new(action_with_write_access, Permission::modify_entity::<data::DataQueryEntity>(query_name));
+
+        let action_with_audit = WithAudit::new(action_with_permission, "restoreQueryVersion", audit_target);
+
+        action_with_audit
+    }
+}
+
+impl<S> Action<S> for RestoreQueryVersion<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    type Ret = UpdateEntityResult<data::DataQueryEntity>;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        debug!("Calling RestoreQueryVersion");
+
+        let mut restored = state
+            .get_query_controller()
+            .query_version(&self.query_name, self.version)
+            .map_err(|err| Error::Datastore(err))?
+            .ok_or(Error::NotFound)?;
+        restored.name = self.query_name.to_owned();
+
+        state
+            .get_entity_modifier_function()
+            .update((&self.query_name, restored))
+            .or_else(|err| Err(Error::Entity(err)))
+            .and_then(|res| match res {
+                Updated::Success { old, new } =>
+                    ActionRes::new("restoreQueryVersion", UpdateEntityResult::Updated { id: self.query_name.to_owned(), old, new }),
+                Updated::Fail => Err(Error::NotFound),
+            })
+    }
+}
+
+/// Runs the query and keeps a copy of its result under the given name, so that it can be looked at
+/// later even if the data or the query changed since, the names can't be reused
+#[derive(Debug)]
+pub struct SnapshotQueryResult<S = ActionState> {
+    pub query_name: String,
+    pub snapshot_name: String,
+    pub params: serde_json::Value,
+    pub phantom_data: PhantomData<(S)>,
+}
+
+impl<S> SnapshotQueryResult<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    pub fn new(query_name: String, snapshot_name: String, params: serde_json::Value) -> WithAudit<WithPermissionRequired<WithWriteAccess<WithTransaction<Self, S>, S>, S>, S> {
+        let audit_target = AuditTarget::new("query", &query_name);
+        let action = Self {
+            query_name: query_name.to_owned(),
+            snapshot_name,
+            params,
+            phantom_data: PhantomData,
+        };
+
+        let action_with_transaction = WithTransaction::new(action);
+        let action_with_write_access = WithWriteAccess::new(action_with_transaction);
+        let action_with_permission =
+            WithPermissionRequired::new(action_with_write_access, Permission::run_query(query_name));
+
+        let action_with_audit = WithAudit::new(action_with_permission, "snapshotQueryResult", audit_target);
+
+        action_with_audit
+    }
+}
+
+impl<S> Action<S> for SnapshotQueryResult<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    type Ret = QuerySnapshotResult;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        debug!("Calling SnapshotQueryResult");
+
+        let query: data::DataQueryEntity = state
+            .get_entity_retreiver_functions()
+            .get_one(&self.query_name)
+            .map_err(|err| Error::Entity(err))
+            .and_then(|res| res.ok_or(Error::NotFound))?;
+
+        let limit = data::QueryLimit { limit: MAX_QUERY_ROWS, offset: 0 };
+        let query_controller = state.get_query_controller();
+        let res = query_controller
+            .run_query(&query, &self.params, &json!({}), &limit)
+            .map_err(|err| Error::Datastore(err))?;
+
+        let row_count = res.data["data"].as_array().map(|x| x.len()).unwrap_or(0);
+        let snapshot = data::QuerySnapshot {
+            query_name: self.query_name.to_owned(),
+            name: self.snapshot_name.to_owned(),
+            params: self.params.to_owned(),
+            data: Some(res.data),
+            row_count: row_count as i64,
+            truncated: res.truncated,
+            created_at: chrono::Utc::now().naive_utc(),
+            created_by: state.get_authorization().username(),
+        };
+
+        query_controller
+            .save_snapshot(&snapshot)
+            .map_err(|err| match err {
+                DatastoreError::AlreadyExists => Error::AlreadyExists,
+                err => Error::Datastore(err),
+            })?;
+
+        record_usage(state, "query", &self.query_name);
+        ActionRes::new("snapshotQueryResult", QuerySnapshotResult(snapshot))
+    }
+}
+
+/// A snapshot of the query result taken with `SnapshotQueryResult`, in the requested format
+#[derive(Debug)]
+pub struct GetQuerySnapshot<S = ActionState> {
+    pub query_name: String,
+    pub snapshot_name: String,
+    pub format: ResultFormat,
+    pub phantom_data: PhantomData<(S)>,
+}
+
+impl<S> GetQuerySnapshot<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    pub fn new(query_name: String, snapshot_name: String, format: ResultFormat) -> WithPermissionRequired<WithTransaction<Self, S>, S> {
+        let action = Self {
+            query_name: query_name.to_owned(),
+            snapshot_name,
+            format,
+            phantom_data: PhantomData,
+        };
+
+        let action_with_transaction = WithTransaction::new(action);
+        let action_with_permission =
+            WithPermissionRequired::new(action_with_transaction, Permission::run_query(query_name));
+
+        action_with_permission
+    }
+}
+
+impl<S> Action<S> for GetQuerySnapshot<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    type Ret = QuerySnapshotResult;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        debug!("Calling GetQuerySnapshot");
+
+        let mut snapshot = state
+            .get_query_controller()
+            .query_snapshot(&self.query_name, &self.snapshot_name)
+            .map_err(|err| Error::Datastore(err))?
+            .ok_or(Error::NotFound)?;
+
+        snapshot.data = match snapshot.data {
+            Some(data) => Some(self.format.apply(data).map_err(|err| Error::Datastore(err))?),
+            None => None,
+        };
+
+        ActionRes::new("getQuerySnapshot", QuerySnapshotResult(snapshot))
+    }
+}
+
+/// The snapshots taken of the query, newest first, without their data
+#[derive(Debug)]
+pub struct ListQuerySnapshots<S = ActionState> {
+    pub query_name: String,
+    pub phantom_data: PhantomData<(S)>,
+}
+
+impl<S> ListQuerySnapshots<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    pub fn new(query_name: String) -> WithPermissionRequired<WithTransaction<Self, S>, S> {
+        let action = Self {
+            query_name: query_name.to_owned(),
+            phantom_data: PhantomData,
+        };
+
+        let action_with_transaction = WithTransaction::new(action);
+        let action_with_permission =
+            WithPermissionRequired::new(action_with_transaction, Permission::run_query(query_name));
+
+        action_with_permission
+    }
+}
+
+impl<S> Action<S> for ListQuerySnapshots<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    type Ret = QuerySnapshotsResult;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        debug!("Calling ListQuerySnapshots");
+
+        state
+            .get_query_controller()
+            .query_snapshots(&self.query_name)
+            .map_err(|err| Error::Datastore(err))
+            .and_then(|res| ActionRes::new("listQuerySnapshots", QuerySnapshotsResult(res)))
+    }
+}
+
+/// The stored queries whose statement refers to the table
+pub fn dependent_queries<S>(state: &S, table_name: &str) -> Result<Vec<data::DataQueryEntity>, Error>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    let queries: Vec<data::DataQueryEntity> = state
+        .get_entity_retreiver_functions()
+        .get_all()
+        .map_err(|err| Error::Entity(err))?;
+
+    let dependent_queries = queries
+        .into_iter()
+        .filter(|query| query.table_references().iter().any(|x| x == table_name))
+        .collect();
+
+    Ok(dependent_queries)
+}
+
+/// The tables and views the statement of the query refers to
+#[derive(Debug)]
+pub struct GetQueryDependencies<S = ActionState> {
+    pub query_name: String,
+    pub phantom_data: PhantomData<(S)>,
+}
+
+impl<S> GetQueryDependencies<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    pub fn new(query_name: String) -> WithPermissionRequired<WithTransaction<Self, S>, S> {
+        let action = Self {
+            query_name: query_name.to_owned(),
+            phantom_data: PhantomData,
+        };
+
+        let action_with_transaction = WithTransaction::new(action);
+        let action_with_permission =
+            WithPermissionRequired::new(action_with_transaction, Permission::read_entity::<data::DataQueryEntity>(query_name));
+
+        action_with_permission
+    }
+}
+
+impl<S> Action<S> for GetQueryDependencies<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    type Ret = QueryDependenciesResult;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        debug!("Calling GetQueryDependencies");
+
+        state
+            .get_entity_retreiver_functions()
+            .get_one(&self.query_name)
+            .map_err(|err| Error::Entity(err))
+            .and_then(|res: Option<data::DataQueryEntity>| {
+                match res {
+                    Some(query) => Ok(query),
+                    None => Err(Error::NotFound),
+                }
+            })
+            .and_then(|query| {
+                let tables = query.table_references();
+                ActionRes::new("getQueryDependencies", QueryDependenciesResult { query: query.name, tables })
+            })
+    }
+}
+
+/// The stored queries reading from the table, so that they can be checked before changing its schema
+/// only the queries the user can see are listed
+#[derive(Debug)]
+pub struct GetTableDependents<S = ActionState> {
+    pub table_name: String,
+    pub phantom_data: PhantomData<(S)>,
+}
+
+impl<S> GetTableDependents<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    pub fn new(table_name: String) -> WithPermissionRequired<WithFilterListByPermission<WithTransaction<Self, S>, data::DataQueryEntity, S>, S> {
+        let action = Self {
+            table_name: table_name.to_owned(),
+            phantom_data: PhantomData,
+        };
+
+        let action_with_transaction = WithTransaction::new(action);
+        let action_with_filter = WithFilterListByPermission::new(action_with_transaction);
+        let action_with_permission =
+            WithPermissionRequired::new(action_with_filter, Permission::read_entity::<data::DataStoreEntity>(table_name));
+
+        action_with_permission
+    }
+}
+
+impl<S> Action<S> for GetTableDependents<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    type Ret = GetAllEntitiesResult<data::DataQueryEntity>;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        debug!("Calling GetTableDependents");
+
+        let queries = dependent_queries(state, &self.table_name)?;
+        ActionRes::new("getTableDependents", GetAllEntitiesResult(queries))
+    }
+}
+
+/// The queries running on every domain, admin only
+#[derive(Debug)]
+pub struct ListRunningQueries<S = ActionState> {
+    pub phantom_data: PhantomData<(S)>,
+}
+
+impl<S> ListRunningQueries<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    pub fn new() -> WithPermissionRequired<Self, S> {
+        let action = Self {
+            phantom_data: PhantomData,
+        };
+
+        let action = WithPermissionRequired::new(action, Permission::user_admin());
+
+        action
+    }
+}
+
+impl<S> Action<S> for ListRunningQueries<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    type Ret = RunningQueriesResult;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        debug!("Calling ListRunningQueries");
+
+        let running_queries = state
+            .get_running_queries()
+            .list();
+
+        ActionRes::new("listRunningQueries", RunningQueriesResult(running_queries))
+    }
+}
+
+/// Cancels a running query, the `RunQuery` fails with `The query was canceled`
+/// Admins can cancel any query, other users only the ones they started
+#[derive(Debug)]
+pub struct CancelRunningQuery<S = ActionState> {
+    pub id: usize,
+    pub phantom_data: PhantomData<(S)>,
+}
+
+impl<S> CancelRunningQuery<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    pub fn new(id: usize) -> WithAudit<WithLoginRequired<Self, S>, S> {
+        let audit_target = AuditTarget::new("runningQuery", &id.to_string());
+        let action = Self {
+            id,
+            phantom_data: PhantomData,
+        };
+
+        let action = WithLoginRequired::new(action);
+        let action = WithAudit::new(action, "cancelRunningQuery", audit_target);
+
+        action
+    }
+}
+
+impl<S> Action<S> for CancelRunningQuery<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    type Ret = CancelQueryResult;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        debug!("Calling CancelRunningQuery");
+
+        let running_query = state
+            .get_running_queries()
+            .get(self.id)
+            .ok_or_else(|| Error::NotFound)?;
+
+        let authorization = state.get_authorization();
+        if !authorization.is_admin() && authorization.username() != running_query.username {
+            return Err(Error::Unauthorized);
+        }
+
+        state
+            .get_query_controller()
+            .cancel_query(self.id)
+            .map_err(|err| Error::Datastore(err))
+            .and_then(|res| match res {
+                Some(running_query) => {
+                    info!("canceled the query {:?} ({})", &running_query.query_name, running_query.id);
+                    ActionRes::new("cancelRunningQuery", CancelQueryResult(running_query))
+                },
+                None => Err(Error::NotFound),
+            })
     }
 }
@@ -1,13 +1,52 @@
 
 
 use data;
+use data::audit::AuditEntry;
 use data::auth::Invitation;
+use data::channels::ChannelInfo;
 use data::channels::Channels;
 use data::channels::Subscription;
+use data::dead_letter::DeadLetter;
+use data::schedule::ScheduledRun;
+use data::script_run::ScriptRun;
+use data::script_secret::ScriptSecret;
+use data::script_version::ScriptVersion;
+use data::webhook::ChannelWebhook;
+use data::webhook::DueDelivery;
+use data::webhook::WebhookDelivery;
+use scripting::jobs::ScriptJob;
+use data::usage::EntityUsage;
+use model::running_queries::RunningQuery;
+use model::actions::error::Error;
 
 #[derive(Debug, Clone, Serialize)]
 pub struct GetAllEntitiesResult<T>(pub Vec<T>);
 
+/// the entity, or only its name when the list isn't `detailed`
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub enum EntityListItem<T> {
+    Detailed(T),
+    Name(String),
+}
+
+/// A page of a list, returned instead of the whole list when the request has a limit or an offset
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListPage<T> {
+    pub items: Vec<T>,
+    pub offset: usize,
+    /// the number of items in the whole list
+    pub total_count: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub enum ListResult<T> {
+    All(Vec<T>),
+    Page(ListPage<T>),
+}
+
 #[derive(Debug, Clone, Serialize)]
 pub struct GetEntityResult<T>(pub T);
 
@@ -59,6 +98,21 @@ pub enum DeleteEntityResult<T> {
 #[derive(Debug, Clone, Serialize)]
 pub struct GetTableDataResult(pub serde_json::Value);
 
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportTableDataResult {
+    pub row_count: usize,
+}
+
+/// Rows inserted from an uploaded file so far, also sent as the progress of the import
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportTableDataResult {
+    pub table_name: String,
+    pub imported_rows: usize,
+    pub batches: usize,
+}
+
 #[derive(Debug, Clone, Serialize)]
 pub struct InsertTableDataResult(pub serde_json::Value);
 
@@ -68,18 +122,181 @@ pub struct ModifyTableDataResult(pub serde_json::Value);
 #[derive(Debug, Clone, Serialize)]
 pub struct RemoveTableDataResult(pub serde_json::Value);
 
+/// The rows returned by each operation of a bulk change, in the order they were applied
+#[derive(Debug, Clone, Serialize)]
+pub struct BulkModifyTableDataResult(pub Vec<serde_json::Value>);
+
+/// Number of rows changed by a filtered update or delete
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AffectedRowsResult {
+    pub affected_rows: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RowHistoryResult(pub Vec<data::row_history::RowHistoryEntry>);
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CopyTableResult {
+    pub new: data::DataStoreEntity,
+    /// `None` if only the schema was copied
+    pub copied_rows: Option<usize>,
+}
+
+/// Rows removed by the retention policy of a table
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RetentionResult {
+    pub table_name: String,
+    pub deleted_rows: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RetentionPoliciesResult(pub Vec<RetentionResult>);
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RefreshViewResult {
+    pub name: String,
+    pub refreshed_at: chrono::NaiveDateTime,
+}
+
+/// The statements a table update would run, as returned by the datastore
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SchemaChangePreviewResult {
+    pub steps: serde_json::Value,
+    /// the stored queries reading from the table, the change might break them
+    pub dependent_queries: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RunQueryResult {
+    #[serde(flatten)]
+    pub data: serde_json::Value,
+    /// set if there were more rows than the limit
+    pub truncated: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ExplainQueryResult(pub serde_json::Value);
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueryIntoTableResult {
+    pub table: data::DataStoreEntity,
+    /// set if the table was created from the result
+    pub created: bool,
+    pub deleted_rows: usize,
+    pub inserted_rows: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ScheduledRunsResult(pub Vec<ScheduledRun>);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct QueryHistoryResult(pub Vec<data::QueryVersion>);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct QuerySnapshotResult(pub data::QuerySnapshot);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct QuerySnapshotsResult(pub Vec<data::QuerySnapshot>);
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueryDependenciesResult {
+    pub query: String,
+    pub tables: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RunningQueriesResult(pub Vec<RunningQuery>);
+
 #[derive(Debug, Clone, Serialize)]
-pub struct RunQueryResult(pub serde_json::Value);
+pub struct CancelQueryResult(pub RunningQuery);
 
 #[derive(Debug, Clone, Serialize)]
 pub struct RunScriptResult(pub serde_json::Value);
 
+#[derive(Debug, Clone, Serialize)]
+pub struct ScriptRunsResult(pub Vec<ScriptRun>);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ScriptRunResult(pub ScriptRun);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ScriptSecretsResult(pub Vec<ScriptSecret>);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ScriptJobResult(pub ScriptJob);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ScriptHistoryResult(pub Vec<ScriptVersion>);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct WebhookDeliveriesResult(pub Vec<WebhookDelivery>);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DueDeliveriesResult(pub Vec<DueDelivery>);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ChannelWebhookResult(pub ChannelWebhook);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ChannelWebhooksResult(pub Vec<ChannelWebhook>);
+
+/// the number of the messages that were still pending, the others were acknowledged before
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AckMessagesResult {
+    pub acked: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DeadLetterResult(pub DeadLetter);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DeadLettersResult(pub ListPage<DeadLetter>);
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PurgeMessagesResult {
+    pub purged_messages: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExpireSubscriptionsResult {
+    pub expired_subscriptions: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReadOnlyModeResult {
+    pub read_only: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BroadcastMetricsResult(pub serde_json::Value);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DatabaseMetricsResult(pub serde_json::Value);
+
+/// always a page, the audit trail is too long to be returned whole
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditTrailResult(pub ListPage<AuditEntry>);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EntityUsageResult(pub Vec<EntityUsage>);
 
 #[derive(Debug, Clone, Serialize)]
 pub struct UserResult(pub data::auth::User);
 
 #[derive(Debug, Clone, Serialize)]
-pub struct AllUsersResult(pub Vec<data::auth::User>);
+pub struct AllUsersResult(pub ListResult<data::auth::User>);
 
 #[derive(Debug, Clone, Serialize)]
 pub struct InvitationResult(pub Invitation);
@@ -88,7 +305,13 @@ pub struct InvitationResult(pub Invitation);
 pub struct RoleResult(pub data::auth::Role);
 
 #[derive(Debug, Clone, Serialize)]
-pub struct AllRolesResult(pub Vec<data::auth::Role>);
+pub struct AllRolesResult(pub ListResult<data::auth::Role>);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ChannelsResult(pub Vec<ChannelInfo>);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SubscriptionsResult(pub Vec<Subscription>);
 
 #[derive(Debug, Clone, Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -97,4 +320,40 @@ pub enum SubscriptionResult {
     Subscribed(Subscription),
     Unsubscribed(Subscription),
     UnsubscribedAll,
-}
\ No newline at end of file
+}
+/// The outcome of one call of a batch, either its data or its error
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchCallResult {
+    pub procedure: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<serde_json::Value>,
+}
+
+impl BatchCallResult {
+    pub fn ok(procedure: &str, data: serde_json::Value) -> Self {
+        Self {
+            procedure: procedure.to_owned(),
+            data: Some(data),
+            error: None,
+            details: None,
+        }
+    }
+
+    pub fn err(procedure: &str, err: &Error) -> Self {
+        Self {
+            procedure: procedure.to_owned(),
+            data: None,
+            error: Some(err.to_string()),
+            details: err.details(),
+        }
+    }
+}
+
+/// The results of the calls of a batch, in the order they were made
+#[derive(Debug, Clone, Serialize)]
+pub struct BatchResult(pub Vec<BatchCallResult>);
@@ -1,79 +1,970 @@
 use std::result::Result::Ok;
 use std::marker::PhantomData;
+use std::fmt;
+use std::time::Instant;
+
+use futures::Future;
+use futures::Sink;
+use futures::sync::mpsc;
 
 use data;
 use data::Named;
+use data::channels::Channels;
+use data::schedule::MissedRunPolicy;
+use data::schedule::ScriptSchedule;
+use data::error::DatastoreError;
+use data::script_run::ScriptRun;
+use data::script_secret::is_valid_secret_name;
 
 use data::permissions::Permission;
+use data::audit::AuditTarget;
+
+use model::actions::decorator::*;
+use model::actions::results::*;
+use model::actions::error::Error;
+use model::actions::Action;
+use model::actions::ActionRes;
+use model::actions::ActionResult;
+use model::actions::usage_actions::record_usage;
+use model::entity::RetrieverFunctions;
+use model::entity::ModifierFunctions;
+use model::entity::results::Updated;
+use model::entity::error::EntityError;
+
+use scripting::ScriptFunctions;
+use scripting::ScriptOutputLine;
+use scripting::ScriptResult;
+use scripting::error::ScriptError;
+use scripting::jobs::JobStatus;
+use scripting::jobs::ScriptJob;
+use scripting::validation;
+use scripting::validation::ScriptValidation;
+
+use state::StateFunctions;
+use state::ActionState;
+use state::PubSubOps;
+use state::authorization::AuthorizationOps;
+use state::script_runs::ScriptRunsOps;
+use state::script_secrets::ScriptSecretsOps;
+use state::script_history::ScriptHistoryOps;
+
+/// most runs returned by `GetScriptRuns`
+pub const SCRIPT_RUNS_LIMIT: usize = 100;
+
+/// how far back the scheduler looks for a missed run, with the `runOnce` policy
+pub const MISSED_RUNS_LOOKBACK_DAYS: i64 = 7;
+
+/// Runs the script and records the run with its output
+fn run_and_record<S>(
+    state: &S,
+    script: &data::Script,
+    param: &data::ScriptParam,
+    scheduled: bool,
+    on_stdout: &mut FnMut(&str),
+) -> Result<(ScriptResult, ScriptRun), Error>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    let env = state
+        .get_script_secrets()
+        .get_secret_values(script.my_name())
+        .map_err(Error::Datastore)?;
+
+    let started_at = chrono::Utc::now().naive_utc();
+    let start = Instant::now();
+    let res = state
+        .get_script_runner()
+        .run_streaming(script, param, &env, on_stdout)
+        .map_err(Error::Script)?;
+
+    let elapsed = start.elapsed();
+    let mut run = ScriptRun {
+        id: 0,
+        script_name: script.my_name().to_owned(),
+        started_at,
+        duration_ms: (elapsed.as_secs() * 1000 + u64::from(elapsed.subsec_millis())) as i64,
+        successful: res.successful,
+        exit_code: res.exit_code,
+        run_by: None,
+        scheduled,
+        stdout: Some(res.stdout.to_owned()),
+        stderr: Some(res.stderr.to_owned()),
+    };
+    // the script already ran, so it's not failed if the log can't be stored
+    match state.get_script_runs().record_run(&run) {
+        Ok(id) => run.id = id,
+        Err(err) => warn!("could not record the run of the script {:?}: {:?}", script.my_name(), &err),
+    };
+
+    Ok((res, run))
+}
+
+// Script Action
+#[derive(Debug)]
+pub struct RunScript<S = ActionState>  {
+    pub script_name: String,
+    pub param: data::ScriptParam,
+    pub phantom_data: PhantomData<(S)>,
+}
+
+impl<S> RunScript<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    pub fn new(script_name: String, param: data::ScriptParam) -> WithAudit<WithPermissionRequired<WithWriteAccess<WithTransaction<Self, S>, S>, S>, S> {
+        let audit_target = AuditTarget::new("script", &script_name);
+        let action = Self {
+            script_name: script_name.to_owned(),
+            param,
+            phantom_data: PhantomData,
+        };
+
+        let action_with_transaction = WithTransaction::new(action);
+        let action_with_write_access = WithWriteAccess::new(action_with_transaction);
+        let action_with_permission =
+            WithPermissionRequired::new(action_with_write_access, Permission::run_script(script_name));
+
+        let action_with_audit = WithAudit::new(action_with_permission, "runScript", audit_target);
+
+        action_with_audit
+    }
+}
+
+impl<S> Action<S> for RunScript<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    type Ret = ScriptResult;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        debug!("Calling RunScript");
+
+        state
+            .get_entity_retreiver_functions()
+            .get_one::<data::Script>(&self.script_name)
+            .map_err(Error::Entity)
+            .and_then(|res| match res {
+                Some(query) => Ok(query),
+                None => Err(Error::NotFound),
+            })
+            .and_then(|script| run_and_record(state, &script, &self.param, false, &mut |_| {}))
+            .map(|(res, _)| res)
+            .and_then(|res| {
+                record_usage(state, "script", &self.script_name);
+                ActionRes::new("runScript", res)
+            })
+    }
+}
+
+/// Same as `RunScript`, but every line the script prints to its stdout is sent through the channel
+/// as soon as it is printed, so that the progress of a long running script can be followed
+#[derive(Debug)]
+pub struct StreamScript<S = ActionState>  {
+    pub script_name: String,
+    pub param: data::ScriptParam,
+    pub sender: mpsc::Sender<ScriptOutputLine>,
+    pub phantom_data: PhantomData<(S)>,
+}
+
+impl<S> StreamScript<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    pub fn new(script_name: String, param: data::ScriptParam, sender: mpsc::Sender<ScriptOutputLine>) -> WithAudit<WithPermissionRequired<WithWriteAccess<WithTransaction<Self, S>, S>, S>, S> {
+        let audit_target = AuditTarget::new("script", &script_name);
+        let action = Self {
+            script_name: script_name.to_owned(),
+            param,
+            sender,
+            phantom_data: PhantomData,
+        };
+
+        let action_with_transaction = WithTransaction::new(action);
+        let action_with_write_access = WithWriteAccess::new(action_with_transaction);
+        let action_with_permission =
+            WithPermissionRequired::new(action_with_write_access, Permission::run_script(script_name));
+
+        let action_with_audit = WithAudit::new(action_with_permission, "runScript", audit_target);
+
+        action_with_audit
+    }
+}
+
+impl<S> Action<S> for StreamScript<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    type Ret = ScriptResult;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        debug!("Calling StreamScript");
+
+        let script = state
+            .get_entity_retreiver_functions()
+            .get_one::<data::Script>(&self.script_name)
+            .map_err(Error::Entity)?
+            .ok_or(Error::NotFound)?;
+
+        // the script keeps running if the client went away, only the output is dropped
+        let mut is_closed = false;
+        let (res, _) = run_and_record(state, &script, &self.param, false, &mut |line| {
+            if is_closed {
+                return;
+            }
+
+            let output_line = ScriptOutputLine {
+                script_name: self.script_name.to_owned(),
+                line: line.to_owned(),
+            };
+            // blocks the script until the client has caught up
+            if self.sender.clone().send(output_line).wait().is_err() {
+                warn!("the output stream of the script {:?} was closed", &self.script_name);
+                is_closed = true;
+            }
+        })?;
+
+        record_usage(state, "script", &self.script_name);
+        ActionRes::new("streamScript", res)
+    }
+}
+
+/// Queues the script on the script workers instead of running it on the executor
+/// Returns the job right away, see `GetJobStatus` and `GetJobResult`
+#[derive(Debug)]
+pub struct RunScriptAsync<S = ActionState>  {
+    pub script_name: String,
+    pub param: data::ScriptParam,
+    pub phantom_data: PhantomData<(S)>,
+}
+
+impl<S> RunScriptAsync<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    pub fn new(script_name: String, param: data::ScriptParam) -> WithAudit<WithPermissionRequired<WithWriteAccess<WithTransaction<Self, S>, S>, S>, S> {
+        let audit_target = AuditTarget::new("script", &script_name);
+        let action = Self {
+            script_name: script_name.to_owned(),
+            param,
+            phantom_data: PhantomData,
+        };
+
+        let action_with_transaction = WithTransaction::new(action);
+        let action_with_write_access = WithWriteAccess::new(action_with_transaction);
+        let action_with_permission =
+            WithPermissionRequired::new(action_with_write_access, Permission::run_script(script_name));
+
+        let action_with_audit = WithAudit::new(action_with_permission, "runScriptAsync", audit_target);
+
+        action_with_audit
+    }
+}
+
+impl<S> Action<S> for RunScriptAsync<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    type Ret = ScriptJobResult;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        debug!("Calling RunScriptAsync");
+
+        let script = state
+            .get_entity_retreiver_functions()
+            .get_one::<data::Script>(&self.script_name)
+            .map_err(Error::Entity)?
+            .ok_or(Error::NotFound)?;
+
+        let env = state
+            .get_script_secrets()
+            .get_secret_values(&self.script_name)
+            .map_err(Error::Datastore)?;
+
+        let authorization = state.get_authorization();
+        let job = state
+            .get_script_jobs()
+            .submit(
+                Box::new(state.get_script_runner()),
+                script,
+                self.param.to_owned(),
+                env,
+                state.get_domain_name(),
+                authorization.claims(),
+            )
+            .map_err(Error::Script)?;
+
+        record_usage(state, "script", &self.script_name);
+        ActionRes::new("runScriptAsync", ScriptJobResult(job))
+    }
+}
+
+/// the job if it was submitted by the user, only the admins see the jobs of everyone
+fn get_own_job<S>(state: &S, job_id: &str) -> Result<ScriptJob, Error>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    let job = state
+        .get_script_jobs()
+        .get(job_id)
+        .ok_or(Error::NotFound)?;
+
+    let authorization = state.get_authorization();
+    let submitted_by = job.claims.as_ref().map(|x| x.get_user_id());
+    if authorization.is_admin() || (submitted_by.is_some() && submitted_by == authorization.user_id()) {
+        Ok(job)
+    } else {
+        Err(Error::NotFound)
+    }
+}
+
+/// The status of a script job, without its output
+#[derive(Debug)]
+pub struct GetJobStatus<S = ActionState> {
+    pub job_id: String,
+    pub phantom_data: PhantomData<(S)>,
+}
+
+impl<S> GetJobStatus<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    pub fn new(job_id: String) -> WithLoginRequired<Self, S> {
+        let action = Self {
+            job_id,
+            phantom_data: PhantomData,
+        };
+
+        WithLoginRequired::new(action)
+    }
+}
+
+impl<S> Action<S> for GetJobStatus<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    type Ret = ScriptJobResult;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        debug!("Calling GetJobStatus");
+
+        get_own_job(state, &self.job_id)
+            .and_then(|job| ActionRes::new("getJobStatus", ScriptJobResult(job)))
+    }
+}
+
+/// The result of a finished script job, the same as `RunScript` returns
+#[derive(Debug)]
+pub struct GetJobResult<S = ActionState> {
+    pub job_id: String,
+    pub phantom_data: PhantomData<(S)>,
+}
+
+impl<S> GetJobResult<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    pub fn new(job_id: String) -> WithLoginRequired<Self, S> {
+        let action = Self {
+            job_id,
+            phantom_data: PhantomData,
+        };
+
+        WithLoginRequired::new(action)
+    }
+}
+
+impl<S> Action<S> for GetJobResult<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    type Ret = ScriptResult;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        debug!("Calling GetJobResult");
+
+        let job = get_own_job(state, &self.job_id)?;
+        match (job.status, job.result, job.failure) {
+            (JobStatus::Finished, Some(res), _) => ActionRes::new("getJobResult", res),
+            (JobStatus::Failed, _, Some(failure)) => Err(Error::Script(failure)),
+            (JobStatus::Failed, _, None) => Err(Error::Script(ScriptError::ExecuteError(job.error.unwrap_or_default()))),
+            _ => Err(Error::Script(ScriptError::JobNotFinished(job.id))),
+        }
+    }
+}
+
+/// Records the run of a finished job and publishes it on the channel of the script
+/// Sent by the script workers, with the claims the job was submitted with
+#[derive(Debug)]
+pub struct FinishScriptJob<S = ActionState> {
+    pub job_id: String,
+    pub phantom_data: PhantomData<(S)>,
+}
+
+impl<S> FinishScriptJob<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    pub fn new(job_id: String) -> WithLoginRequired<WithTransaction<Self, S>, S> {
+        let action = Self {
+            job_id,
+            phantom_data: PhantomData,
+        };
+
+        let action_with_transaction = WithTransaction::new(action);
+        let action_with_login = WithLoginRequired::new(action_with_transaction);
+
+        action_with_login
+    }
+}
+
+impl<S> Action<S> for FinishScriptJob<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    type Ret = ScriptJobResult;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        debug!("Calling FinishScriptJob");
+
+        let job = get_own_job(state, &self.job_id)?;
+
+        if let (Some(res), Some(started_at), Some(finished_at)) = (&job.result, job.started_at, job.finished_at) {
+            let run = ScriptRun {
+                id: 0,
+                script_name: job.script_name.to_owned(),
+                started_at,
+                duration_ms: (finished_at - started_at).num_milliseconds(),
+                successful: res.successful,
+                exit_code: res.exit_code,
+                run_by: None,
+                scheduled: false,
+                stdout: Some(res.stdout.to_owned()),
+                stderr: Some(res.stderr.to_owned()),
+            };
+            if let Err(err) = state.get_script_runs().record_run(&run) {
+                warn!("could not record the run of the script job {:?}: {:?}", &job.id, &err);
+            }
+        }
+
+        let message = json!({
+            "job": &job,
+            "result": &job.result,
+        });
+        state
+            .get_pub_sub()
+            .publish(Channels::entity::<data::Script>(&job.script_name), "scriptJobFinished".to_string(), &message)
+            .map_err(Error::PublishError)?;
+
+        ActionRes::new("finishScriptJob", ScriptJobResult(job))
+    }
+}
+
+/// Whether the scheduler should run the script in the given minute
+fn is_due<S>(state: &S, script: &data::Script, schedule: &ScriptSchedule, time: &chrono::NaiveDateTime) -> Result<bool, Error>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    let cron_schedule = schedule
+        .cron_schedule()
+        .map_err(|err| {
+            warn!("the schedule of {} is invalid: {}", &script.name, &err);
+            Error::Entity(EntityError::InvalidSchedule(err))
+        })?;
+
+    if cron_schedule.matches(time) {
+        return Ok(true);
+    }
+
+    if schedule.missed_runs == MissedRunPolicy::Skip {
+        return Ok(false);
+    }
+
+    // a script that never ran on its schedule hasn't missed anything
+    let last_run = state
+        .get_script_runs()
+        .last_scheduled_run(&script.name)
+        .map_err(Error::Datastore)?;
+
+    Ok(last_run
+        .map(|last_run| {
+            let lookback = *time - chrono::Duration::days(MISSED_RUNS_LOOKBACK_DAYS);
+            let after = if last_run > lookback { last_run } else { lookback };
+            cron_schedule.latest_match(&after, time).is_some()
+        })
+        .unwrap_or(false))
+}
+
+/// Runs every script with an enabled schedule that is due in the given minute, called by the scheduler job
+/// The runs are recorded with the other runs of the script, and published on the channel of the script
+#[derive(Debug)]
+pub struct RunScheduledScripts<S = ActionState> {
+    pub time: chrono::NaiveDateTime,
+    pub phantom_data: PhantomData<(S)>,
+}
+
+impl<S> RunScheduledScripts<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    pub fn new(time: chrono::NaiveDateTime) -> WithPermissionRequired<WithWriteAccess<Self, S>, S> {
+        let action = Self {
+            time,
+            phantom_data: PhantomData,
+        };
+
+        let action_with_write_access = WithWriteAccess::new(action);
+        let action_with_permission = WithPermissionRequired::new(action_with_write_access, Permission::user_admin());
+
+        action_with_permission
+    }
+}
+
+impl<S> Action<S> for RunScheduledScripts<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    type Ret = ScriptRunsResult;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        debug!("Calling RunScheduledScripts");
+
+        let scripts: Vec<data::Script> = state
+            .get_entity_retreiver_functions()
+            .get_all()
+            .or_else(|err| Err(Error::Entity(err)))?;
+
+        let runs = scripts
+            .iter()
+            .filter_map(|script| {
+                let schedule = script.schedule.as_ref().filter(|x| x.enabled)?;
+                match is_due(state, script, schedule, &self.time) {
+                    Ok(true) => Some((script, schedule)),
+                    Ok(false) => None,
+                    Err(err) => {
+                        error!("could not check the schedule of the script {}: {:?}", &script.name, &err);
+                        None
+                    },
+                }
+            })
+            .filter_map(|(script, schedule)| {
+                run_and_record(state, script, &schedule.params, true, &mut |_| {})
+                    .map_err(|err| error!("could not run the scheduled script {}: {:?}", &script.name, &err))
+                    .ok()
+            })
+            .map(|(res, run)| {
+                info!("scheduled script {} exited with {:?} in {}ms", &run.script_name, &run.exit_code, run.duration_ms);
+
+                let message = json!({
+                    "run": &run,
+                    "result": &res,
+                });
+                if let Err(err) = state
+                    .get_pub_sub()
+                    .publish(Channels::entity::<data::Script>(&run.script_name), "scheduledRun".to_string(), &message) {
+                    warn!("could not publish the scheduled run of {}: {:?}", &run.script_name, &err);
+                }
+
+                // the output stays in the log
+                ScriptRun { stdout: None, stderr: None, ..run }
+            })
+            .collect();
+
+        ActionRes::new("runScheduledScripts", ScriptRunsResult(runs))
+    }
+}
+
+/// Checks the syntax and the params of the script without running it, the same checks are
+/// made when the script is saved. `param` is checked against the declared params if there is one
+#[derive(Debug)]
+pub struct ValidateScript<S = ActionState> {
+    pub script_name: String,
+    pub param: Option<data::ScriptParam>,
+    pub phantom_data: PhantomData<(S)>,
+}
+
+impl<S> ValidateScript<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    pub fn new(script_name: String, param: Option<data::ScriptParam>) -> WithPermissionRequired<WithTransaction<Self, S>, S> {
+        let action = Self {
+            script_name: script_name.to_owned(),
+            param,
+            phantom_data: PhantomData,
+        };
+
+        let action_with_transaction = WithTransaction::new(action);
+        let action_with_permission =
+            WithPermissionRequired::new(action_with_transaction, Permission::read_entity::<data::Script>(script_name));
+
+        action_with_permission
+    }
+}
+
+impl<S> Action<S> for ValidateScript<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    type Ret = ScriptValidation;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        debug!("Calling ValidateScript");
+
+        state
+            .get_entity_retreiver_functions()
+            .get_one::<data::Script>(&self.script_name)
+            .map_err(Error::Entity)
+            .and_then(|res| match res {
+                Some(script) => Ok(script),
+                None => Err(Error::NotFound),
+            })
+            .and_then(|script| {
+                validation::validate(&state.get_script_runner(), &script, self.param.as_ref())
+                    .map_err(Error::Script)
+            })
+            .and_then(|res| ActionRes::new("validateScript", res))
+    }
+}
+
+/// The latest runs of the script without their output, newest first
+#[derive(Debug)]
+pub struct GetScriptRuns<S = ActionState> {
+    pub script_name: String,
+    pub phantom_data: PhantomData<(S)>,
+}
+
+impl<S> GetScriptRuns<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    pub fn new(script_name: String) -> WithPermissionRequired<WithTransaction<Self, S>, S> {
+        let action = Self {
+            script_name: script_name.to_owned(),
+            phantom_data: PhantomData,
+        };
+
+        let action_with_transaction = WithTransaction::new(action);
+        let action_with_permission =
+            WithPermissionRequired::new(action_with_transaction, Permission::read_entity::<data::Script>(script_name));
 
-use model::actions::decorator::*;
-use model::actions::results::*;
-use model::actions::error::Error;
-use model::actions::Action;
-use model::actions::ActionRes;
-use model::actions::ActionResult;
-use model::entity::RetrieverFunctions;
+        action_with_permission
+    }
+}
 
-use scripting::ScriptFunctions;
-use scripting::ScriptResult;
+impl<S> Action<S> for GetScriptRuns<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    type Ret = ScriptRunsResult;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        debug!("Calling GetScriptRuns");
 
-use state::StateFunctions;
-use state::ActionState;
+        state
+            .get_script_runs()
+            .get_runs(&self.script_name, SCRIPT_RUNS_LIMIT)
+            .map_err(|err| Error::Datastore(err))
+            .and_then(|res| ActionRes::new("getScriptRuns", ScriptRunsResult(res)))
+    }
+}
 
-// Script Action
+/// A single run of the script with its stdout and stderr
 #[derive(Debug)]
-pub struct RunScript<S = ActionState>  {
+pub struct GetScriptRunLog<S = ActionState> {
     pub script_name: String,
-    pub param: data::ScriptParam,
+    pub id: i64,
     pub phantom_data: PhantomData<(S)>,
 }
 
-impl<S> RunScript<S>
+impl<S> GetScriptRunLog<S>
     where
         for<'a> S: StateFunctions<'a>,
 {
-    pub fn new(script_name: String, param: data::ScriptParam) -> WithPermissionRequired<WithTransaction<Self, S>, S> {
+    pub fn new(script_name: String, id: i64) -> WithPermissionRequired<WithTransaction<Self, S>, S> {
         let action = Self {
             script_name: script_name.to_owned(),
-            param,
+            id,
             phantom_data: PhantomData,
         };
 
         let action_with_transaction = WithTransaction::new(action);
         let action_with_permission =
-            WithPermissionRequired::new(action_with_transaction, Permission::run_script(script_name));
+            WithPermissionRequired::new(action_with_transaction, Permission::read_entity::<data::Script>(script_name));
 
         action_with_permission
     }
 }
 
-impl<S> Action<S> for RunScript<S>
+impl<S> Action<S> for GetScriptRunLog<S>
     where
         for<'a> S: StateFunctions<'a>,
 {
-    type Ret = ScriptResult;
+    type Ret = ScriptRunResult;
     fn call(&self, state: &S) -> ActionResult<Self::Ret> {
-        debug!("Calling RunScript");
+        debug!("Calling GetScriptRunLog");
 
         state
-            .get_entity_retreiver_functions()
-            .get_one::<data::Script>(&self.script_name)
-            .map_err(Error::Entity)
+            .get_script_runs()
+            .get_run(&self.script_name, self.id)
+            .map_err(|err| Error::Datastore(err))
             .and_then(|res| match res {
-                Some(query) => Ok(query),
+                Some(run) => ActionRes::new("getScriptRunLog", ScriptRunResult(run)),
                 None => Err(Error::NotFound),
             })
-            .and_then(|script| {
-                state
-                    .get_script_runner()
-                    .run(&script, &self.param)
-                    .map_err(Error::Script)
-            })
-            .and_then(|res| ActionRes::new("runScript", res))
     }
 }
 
 
+/// Adds a secret to the script, or replaces its value. Admin only
+/// The script gets it as an environment variable of the same name
+pub struct SetScriptSecret<S = ActionState> {
+    pub script_name: String,
+    pub name: String,
+    pub value: String,
+    pub phantom_data: PhantomData<(S)>,
+}
+
+/// the value is left out, the actions are logged
+impl<S> fmt::Debug for SetScriptSecret<S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "SetScriptSecret {{ script_name: {:?}, name: {:?}, value: \"***\" }}", &self.script_name, &self.name)
+    }
+}
+
+impl<S> SetScriptSecret<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    pub fn new(script_name: String, name: String, value: String) -> WithAudit<WithPermissionRequired<WithWriteAccess<WithTransaction<Self, S>, S>, S>, S> {
+        let audit_target = AuditTarget::new("script", &script_name);
+        let action = Self {
+            script_name,
+            name,
+            value,
+            phantom_data: PhantomData,
+        };
+
+        let action_with_transaction = WithTransaction::new(action);
+        let action_with_write_access = WithWriteAccess::new(action_with_transaction);
+        let action_with_permission = WithPermissionRequired::new(action_with_write_access, Permission::user_admin());
+
+        let action_with_audit = WithAudit::new(action_with_permission, "setScriptSecret", audit_target);
+
+        action_with_audit
+    }
+}
+
+impl<S> Action<S> for SetScriptSecret<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    type Ret = ScriptSecretsResult;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        debug!("Calling SetScriptSecret");
+
+        if !is_valid_secret_name(&self.name) {
+            return Err(Error::Datastore(DatastoreError::InvalidParam(
+                format!("Invalid secret name {:?}, only uppercase letters, digits and underscores are allowed", &self.name))));
+        }
+
+        let script_secrets = state.get_script_secrets();
+        script_secrets
+            .set_secret(&self.script_name, &self.name, &self.value)
+            .and_then(|_| script_secrets.get_secrets(&self.script_name))
+            .map_err(Error::Datastore)
+            .and_then(|res| ActionRes::new("setScriptSecret", ScriptSecretsResult(res)))
+    }
+}
+
+/// Removes a secret from the script. Admin only
+#[derive(Debug)]
+pub struct RemoveScriptSecret<S = ActionState> {
+    pub script_name: String,
+    pub name: String,
+    pub phantom_data: PhantomData<(S)>,
+}
+
+impl<S> RemoveScriptSecret<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    pub fn new(script_name: String, name: String) -> WithAudit<WithPermissionRequired<WithWriteAccess<WithTransaction<Self, S>, S>, S>, S> {
+        let audit_target = AuditTarget::new("script", &script_name);
+        let action = Self {
+            script_name,
+            name,
+            phantom_data: PhantomData,
+        };
+
+        let action_with_transaction = WithTransaction::new(action);
+        let action_with_write_access = WithWriteAccess::new(action_with_transaction);
+        let action_with_permission = WithPermissionRequired::new(action_with_write_access, Permission::user_admin());
+
+        let action_with_audit = WithAudit::new(action_with_permission, "removeScriptSecret", audit_target);
+
+        action_with_audit
+    }
+}
+
+impl<S> Action<S> for RemoveScriptSecret<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    type Ret = ScriptSecretsResult;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        debug!("Calling RemoveScriptSecret");
+
+        let script_secrets = state.get_script_secrets();
+        let removed = script_secrets
+            .remove_secret(&self.script_name, &self.name)
+            .map_err(Error::Datastore)?;
+        if !removed {
+            return Err(Error::NotFound);
+        }
+
+        script_secrets
+            .get_secrets(&self.script_name)
+            .map_err(Error::Datastore)
+            .and_then(|res| ActionRes::new("removeScriptSecret", ScriptSecretsResult(res)))
+    }
+}
+
+/// The names of the secrets of the script, without the values. Admin only
+#[derive(Debug)]
+pub struct GetScriptSecrets<S = ActionState> {
+    pub script_name: String,
+    pub phantom_data: PhantomData<(S)>,
+}
+
+impl<S> GetScriptSecrets<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    pub fn new(script_name: String) -> WithPermissionRequired<WithTransaction<Self, S>, S> {
+        let action = Self {
+            script_name,
+            phantom_data: PhantomData,
+        };
+
+        let action_with_transaction = WithTransaction::new(action);
+        let action_with_permission = WithPermissionRequired::new(action_with_transaction, Permission::user_admin());
+
+        action_with_permission
+    }
+}
+
+impl<S> Action<S> for GetScriptSecrets<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    type Ret = ScriptSecretsResult;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        debug!("Calling GetScriptSecrets");
+
+        state
+            .get_script_secrets()
+            .get_secrets(&self.script_name)
+            .map_err(Error::Datastore)
+            .and_then(|res| ActionRes::new("getScriptSecrets", ScriptSecretsResult(res)))
+    }
+}
+
+/// Every stored version of a script with the changes to its text, newest first
+#[derive(Debug)]
+pub struct GetScriptHistory<S = ActionState> {
+    pub script_name: String,
+    pub phantom_data: PhantomData<(S)>,
+}
+
+impl<S> GetScriptHistory<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    pub fn new(script_name: String) -> WithPermissionRequired<WithTransaction<Self, S>, S> {
+        let action = Self {
+            script_name: script_name.to_owned(),
+            phantom_data: PhantomData,
+        };
+
+        let action_with_transaction = WithTransaction::new(action);
+        let action_with_permission =
+            WithPermissionRequired::new(action_with_transaction, Permission::read_entity::<data::Script>(script_name));
+
+        action_with_permission
+    }
+}
+
+impl<S> Action<S> for GetScriptHistory<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    type Ret = ScriptHistoryResult;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        debug!("Calling GetScriptHistory");
+
+        let history = state
+            .get_script_history()
+            .get_script_history(&self.script_name)
+            .map_err(Error::Datastore)?;
+
+        if history.is_empty() {
+            return Err(Error::NotFound);
+        }
+
+        ActionRes::new("getScriptHistory", ScriptHistoryResult(history))
+    }
+}
+
+/// Stores an older version of the script as its newest version, the script keeps its current name
+#[derive(Debug)]
+pub struct RestoreScriptVersion<S = ActionState> {
+    pub script_name: String,
+    pub version: i64,
+    pub phantom_data: PhantomData<(S)>,
+}
+
+impl<S> RestoreScriptVersion<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    pub fn new(script_name: String, version: i64) -> WithAudit<WithPermissionRequired<WithWriteAccess<WithDispatch<WithTransaction<Self, S>, S>, S>, S>, S> {
+        let audit_target = AuditTarget::new("script", &script_name);
+        let channel = Channels::entity::<data::Script>(&script_name);
+        let action = Self {
+            script_name: script_name.to_owned(),
+            version,
+            phantom_data: PhantomData,
+        };
+
+        let action_with_transaction = WithTransaction::new(action);
+        let action_with_dispatch = WithDispatch::new(action_with_transaction, channel);
+        let action_with_write_access = WithWriteAccess::new(action_with_dispatch);
+        let action_with_permission =
+            WithPermissionRequired::new(action_with_write_access, Permission::modify_entity::<data::Script>(script_name));
+
+        let action_with_audit = WithAudit::new(action_with_permission, "restoreScriptVersion", audit_target);
+
+        action_with_audit
+    }
+}
+
+impl<S> Action<S> for RestoreScriptVersion<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    type Ret = UpdateEntityResult<data::Script>;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        debug!("Calling RestoreScriptVersion");
+
+        let mut restored = state
+            .get_script_history()
+            .get_script_version(&self.script_name, self.version)
+            .map_err(Error::Datastore)?
+            .ok_or(Error::NotFound)?;
+        restored.name = self.script_name.to_owned();
+
+        state
+            .get_entity_modifier_function()
+            .update((&self.script_name, restored))
+            .map_err(Error::Entity)
+            .and_then(|res| match res {
+                Updated::Success { old, new } =>
+                    ActionRes::new("restoreScriptVersion", UpdateEntityResult::Updated { id: self.script_name.to_owned(), old, new }),
+                Updated::Fail => Err(Error::NotFound),
+            })
+    }
+}
 
 #[cfg(test)]
 mod test {
@@ -83,6 +974,10 @@ mod test {
     use serde_json::from_value;
     use test_common::*;
     use model::actions::entity_actions;
+    use scripting::jobs::ScriptJobs;
+    use std::thread;
+    use std::time::Duration;
+    use futures::Stream;
 
     #[test]
     fn test_run_script() {
@@ -111,13 +1006,205 @@ with open(filename, 'w') as f:
             let data = result.unwrap().get_data();
 
             let params = json!({"Hello": "World"});
-            let create_action = RunScript::<MockState>::new(script_name, params);
+            let create_action = RunScript::<MockState>::new(script_name.to_owned(), params);
             let result = create_action.call(&state);
             let data = result.unwrap().get_data();
             assert_eq!(data.successful, true);
             assert_eq!(data.stdout, "Hello World\n{\"Hello\":\"World\"}\n");
             assert_eq!(data.stderr, "Bye World\n");
             assert_eq!(data.output, json!({"bye": "world"}));
+            assert_eq!(data.exit_code, Some(0));
+
+            let runs_action = GetScriptRuns::<MockState>::new(script_name.to_owned());
+            let runs = runs_action.call(&state).unwrap().get_data().0;
+            assert_eq!(runs.len(), 1);
+            assert_eq!(runs[0].successful, true);
+            assert_eq!(runs[0].stdout, None);
+
+            let log_action = GetScriptRunLog::<MockState>::new(script_name.to_owned(), runs[0].id);
+            let run = log_action.call(&state).unwrap().get_data().0;
+            assert_eq!(run.stderr, Some("Bye World\n".to_string()));
+        });
+    }
+
+    #[test]
+    fn test_script_secrets() {
+        with_state(|state| {
+            let script_name = format!("my_script{}", random_identifier());
+            let script: data::Script = from_value(json!({
+                "name": script_name.to_owned(),
+                "description": "script description",
+                "text": r#"
+import os
+
+print(os.environ['API_TOKEN'])
+                "#
+            })).unwrap();
+
+            let create_action = entity_actions::CreateEntity::<data::Script, MockState>::new(script);
+            create_action.call(&state).unwrap();
+
+            let set_action = SetScriptSecret::<MockState>::new(script_name.to_owned(), "api-token".to_string(), "hunter2".to_string());
+            assert!(set_action.call(&state).is_err());
+
+            let set_action = SetScriptSecret::<MockState>::new(script_name.to_owned(), "API_TOKEN".to_string(), "hunter2".to_string());
+            assert!(!format!("{:?}", &set_action).contains("hunter2"));
+            let secrets = set_action.call(&state).unwrap().get_data().0;
+            assert_eq!(secrets.len(), 1);
+            assert_eq!(secrets[0].name, "API_TOKEN");
+
+            let run_action = RunScript::<MockState>::new(script_name.to_owned(), json!({}));
+            let data = run_action.call(&state).unwrap().get_data();
+            assert_eq!(data.stdout, "hunter2\n");
+
+            let remove_action = RemoveScriptSecret::<MockState>::new(script_name.to_owned(), "API_TOKEN".to_string());
+            let secrets = remove_action.call(&state).unwrap().get_data().0;
+            assert!(secrets.is_empty());
+
+            let remove_action = RemoveScriptSecret::<MockState>::new(script_name.to_owned(), "API_TOKEN".to_string());
+            assert_eq!(remove_action.call(&state).unwrap_err(), Error::NotFound);
+        });
+    }
+
+    #[test]
+    fn test_run_script_async() {
+        with_state(|state| {
+            let script_name = format!("my_script{}", random_identifier());
+            let script: data::Script = from_value(json!({
+                "name": script_name.to_owned(),
+                "description": "script description",
+                "text": "print('Hello World')"
+            })).unwrap();
+
+            let create_action = entity_actions::CreateEntity::<data::Script, MockState>::new(script);
+            create_action.call(&state).unwrap();
+
+            let run_action = RunScriptAsync::<MockState>::new(script_name.to_owned(), json!({}));
+            assert_eq!(run_action.call(&state).unwrap_err(), Error::Script(ScriptError::NoJobWorkers));
+
+            ScriptJobs::start_workers(&state.get_script_jobs(), 1, |_| {});
+
+            let run_action = RunScriptAsync::<MockState>::new(script_name.to_owned(), json!({}));
+            let job = run_action.call(&state).unwrap().get_data().0;
+            assert_eq!(job.script_name, script_name);
+
+            let mut status = JobStatus::Queued;
+            for _ in 0..100 {
+                let status_action = GetJobStatus::<MockState>::new(job.id.to_owned());
+                status = status_action.call(&state).unwrap().get_data().0.status;
+                if status == JobStatus::Finished {
+                    break;
+                }
+                thread::sleep(Duration::from_millis(100));
+            }
+            assert_eq!(status, JobStatus::Finished);
+
+            let result_action = GetJobResult::<MockState>::new(job.id.to_owned());
+            let data = result_action.call(&state).unwrap().get_data();
+            assert_eq!(data.stdout, "Hello World\n");
+
+            let status_action = GetJobStatus::<MockState>::new("not a job".to_string());
+            assert_eq!(status_action.call(&state).unwrap_err(), Error::NotFound);
+        });
+    }
+
+    #[test]
+    fn test_stream_script() {
+        with_state(|state| {
+            let script_name = format!("my_script{}", random_identifier());
+            let script: data::Script = from_value(json!({
+                "name": script_name.to_owned(),
+                "description": "script description",
+                "text": "print('Hello')\nprint('World')"
+            })).unwrap();
+
+            let create_action = entity_actions::CreateEntity::<data::Script, MockState>::new(script);
+            create_action.call(&state).unwrap();
+
+            let (sender, receiver) = mpsc::channel(4);
+            // the sender is dropped with the action, which ends the stream
+            let data = StreamScript::<MockState>::new(script_name.to_owned(), json!({}), sender)
+                .call(&state)
+                .unwrap()
+                .get_data();
+            assert_eq!(data.stdout, "Hello\nWorld\n");
+
+            let lines: Vec<String> = receiver
+                .wait()
+                .filter_map(|x| x.ok())
+                .map(|x| x.line)
+                .collect();
+            assert_eq!(lines, vec!["Hello", "World"]);
+        });
+    }
+
+    #[test]
+    fn test_restore_script_version() {
+        with_state(|state| {
+            let script_name = format!("my_script{}", random_identifier());
+            let script: data::Script = from_value(json!({
+                "name": script_name.to_owned(),
+                "description": "script description",
+                "text": "print('Hello World')"
+            })).unwrap();
+
+            let create_action = entity_actions::CreateEntity::<data::Script, MockState>::new(script.to_owned());
+            create_action.call(&state).unwrap();
+
+            let updated = data::Script { text: "print('Oops')".to_string(), ..script };
+            let update_action = entity_actions::UpdateEntity::<data::Script, MockState>::new(script_name.to_owned(), updated);
+            update_action.call(&state).unwrap();
+
+            let history_action = GetScriptHistory::<MockState>::new(script_name.to_owned());
+            let history = history_action.call(&state).unwrap().get_data().0;
+            assert_eq!(history.len(), 2);
+            assert_eq!(history[0].script.text, "print('Oops')");
+            assert_eq!(history[0].diff, "@@ -1 +1 @@\n-print('Hello World')\n+print('Oops')\n");
+
+            let restore_action = RestoreScriptVersion::<MockState>::new(script_name.to_owned(), history[1].version);
+            restore_action.call(&state).unwrap();
+
+            let run_action = RunScript::<MockState>::new(script_name.to_owned(), json!({}));
+            let data = run_action.call(&state).unwrap().get_data();
+            assert_eq!(data.stdout, "Hello World\n");
+
+            let restore_action = RestoreScriptVersion::<MockState>::new(script_name.to_owned(), -1);
+            assert_eq!(restore_action.call(&state).unwrap_err(), Error::NotFound);
+        });
+    }
+    #[test]
+    fn test_validate_script() {
+        with_state(|state| {
+            let script_name = format!("my_script{}", random_identifier());
+            let script: data::Script = from_value(json!({
+                "name": script_name.to_owned(),
+                "description": "script description",
+                "text": "print('Hello World'",
+                "params": [{ "name": "table", "required": true }],
+            })).unwrap();
+
+            let create_action = entity_actions::CreateEntity::<data::Script, MockState>::new(script.to_owned());
+            match create_action.call(&state).unwrap_err() {
+                Error::Entity(EntityError::InvalidScript(message)) => assert!(message.starts_with("line 1: SyntaxError")),
+                err => panic!("expected an invalid script, got {:?}", err),
+            };
+
+            let fixed = data::Script { text: "print('Hello World')".to_string(), ..script };
+            let create_action = entity_actions::CreateEntity::<data::Script, MockState>::new(fixed);
+            create_action.call(&state).unwrap();
+
+            let validate_action = ValidateScript::<MockState>::new(script_name.to_owned(), None);
+            let validation = validate_action.call(&state).unwrap().get_data();
+            assert!(validation.valid);
+
+            let validate_action = ValidateScript::<MockState>::new(script_name.to_owned(), Some(json!({ "limit": 10 })));
+            let validation = validate_action.call(&state).unwrap().get_data();
+            assert!(!validation.valid);
+            assert_eq!(validation.syntax_error, None);
+            assert_eq!(validation.param_errors, vec![
+                r#"the param "table" is required"#.to_string(),
+                r#"the param "limit" isn't declared"#.to_string(),
+            ]);
         });
     }
 }
\ No newline at end of file
@@ -1,6 +1,13 @@
 
 use std::result::Result::Ok;
 use std::marker::PhantomData;
+use std::io::BufReader;
+
+use tempfile::NamedTempFile;
+
+use futures::Future;
+use futures::Sink;
+use futures::sync::mpsc;
 
 use data;
 
@@ -9,27 +16,45 @@ use model::actions::error::Error;
 use data::utils::OnDuplicate;
 
 use data::utils::OnNotFound;
+use data::utils::TableDataOperation;
+use data::result_format::ResultFormat;
+use data::import::ImportFormat;
+use data::import::RowReader;
 
 use data::channels::Channels;
 use data::permissions::Permission;
+use data::audit::AuditTarget;
 
 use model::actions::decorator::*;
 use model::actions::Action;
 use model::actions::ActionRes;
 use model::actions::ActionResult;
+use model::actions::query_actions::dependent_queries;
+use model::actions::usage_actions::record_usage;
 
 use model::entity::RetrieverFunctions;
+use model::entity::ModifierFunctions;
+use model::entity::results::Created;
 use model::table::DatastoreActionOps;
+use data::error::DatastoreError;
 
 use state::ActionState;
 use state::StateFunctions;
+use state::PubSubOps;
+use state::authorization::AuthorizationOps;
+
+/// rows deleted per statement when a retention policy is applied
+pub const RETENTION_BATCH_SIZE: usize = 1000;
+
+/// rows inserted per statement when a file is imported
+pub const IMPORT_BATCH_SIZE: usize = 1000;
 
 // Table Actions
 #[derive(Debug)]
 pub struct QueryTableData<S = ActionState> {
     pub table_name: String,
     pub query: serde_json::Value,
-    pub format: serde_json::Value,
+    pub format: ResultFormat,
     pub phantom_data: PhantomData<(S)>,
 }
 
@@ -37,11 +62,11 @@ impl<S> QueryTableData<S>
     where
         for<'a> S: StateFunctions<'a>,
 {
-    pub fn new(table_name: String, query: serde_json::Value) -> WithPermissionRequired<WithTransaction<Self, S>, S> {
+    pub fn new(table_name: String, query: serde_json::Value, format: ResultFormat) -> WithPermissionRequired<WithTransaction<Self, S>, S> {
         let action = Self {
             table_name: table_name.to_owned(),
             query,
-            format: json!({}), //TODO:...
+            format,
             phantom_data: PhantomData,
         };
 
@@ -75,12 +100,471 @@ impl<S> Action<S> for QueryTableData<S>
                 state
                     .get_table_controller()
                     .query(&table, &self.query)
+                    .and_then(|res| self.format.apply(res))
+                    .map_err(|err| Error::Datastore(err))
+            })
+            .and_then(|res| {
+                record_usage(state, "table", &self.table_name);
+                ActionRes::new("queryTableData", GetTableDataResult(res))
+            })
+    }
+
+    fn runs_domain_queries() -> bool {
+        true
+    }
+}
+
+/// Same as `QueryTableData`, but the rows are sent through the channel in batches as they are
+/// read from the table instead of being returned all at once
+#[derive(Debug)]
+pub struct ExportTableData<S = ActionState> {
+    pub table_name: String,
+    pub query: serde_json::Value,
+    pub batch_size: usize,
+    pub sender: mpsc::Sender<serde_json::Value>,
+    pub phantom_data: PhantomData<(S)>,
+}
+
+impl<S> ExportTableData<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    pub fn new(table_name: String, query: serde_json::Value, batch_size: usize, sender: mpsc::Sender<serde_json::Value>) -> WithPermissionRequired<Self, S> {
+        let action = Self {
+            table_name: table_name.to_owned(),
+            query,
+            batch_size,
+            sender,
+            phantom_data: PhantomData,
+        };
+
+        WithPermissionRequired::new(action, Permission::get_table_data(table_name))
+    }
+}
+
+impl<S> Action<S> for ExportTableData<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    type Ret = ExportTableDataResult;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        debug!("Calling ExportTableData");
+
+        let mut row_count = 0;
+        state
+            .get_entity_retreiver_functions()
+            .get_one( &self.table_name)
+            .map_err(|err| Error::Entity(err))
+            .and_then(|res: Option<data::DataStoreEntity>| {
+                match res {
+                    Some(table) => Ok(table),
+                    None => Err(Error::NotFound),
+                }
+            })
+            .and_then(|table| {
+                state
+                    .get_table_controller()
+                    .stream_rows(&table, &self.query, self.batch_size, &mut |rows| {
+                        row_count += rows["data"].as_array().map(|x| x.len()).unwrap_or(0);
+                        // blocks the executor until the client has caught up
+                        self.sender
+                            .clone()
+                            .send(rows)
+                            .wait()
+                            .map(|_| ())
+                            .map_err(|_| DatastoreError::StreamClosed)
+                    })
                     .map_err(|err| Error::Datastore(err))
             })
-            .and_then(|res| ActionRes::new("queryTableData", GetTableDataResult(res)))
+            .and_then(|_| {
+                record_usage(state, "table", &self.table_name);
+                ActionRes::new("exportTableData", ExportTableDataResult { row_count })
+            })
+    }
+
+    fn runs_domain_queries() -> bool {
+        true
+    }
+}
+
+/// Inserts the rows of an uploaded csv or ndjson file, see `view::import`
+/// The file is read batch by batch, and each batch is committed on its own so that a big file doesn't hold
+/// a transaction open. The progress is published on the channel of the user after every batch
+#[derive(Debug)]
+pub struct ImportTableData<S = ActionState> {
+    pub table_name: String,
+    pub file: NamedTempFile,
+    pub format: ImportFormat,
+    pub batch_size: usize,
+    pub phantom_data: PhantomData<(S)>,
+}
+
+impl<S> ImportTableData<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    pub fn new(table_name: String, file: NamedTempFile, format: ImportFormat) -> WithAudit<WithPermissionRequired<WithWriteAccess<Self, S>, S>, S> {
+        let audit_target = AuditTarget::new("table", &table_name);
+        let action = Self {
+            table_name: table_name.to_owned(),
+            file,
+            format,
+            batch_size: IMPORT_BATCH_SIZE,
+            phantom_data: PhantomData,
+        };
+
+        let action_with_write_access = WithWriteAccess::new(action);
+        let action_with_permission =
+            WithPermissionRequired::new(action_with_write_access, Permission::modify_table_data(table_name));
+
+        let action_with_audit = WithAudit::new(action_with_permission, "importTableData", audit_target);
+
+        action_with_audit
+    }
+
+    fn publish_progress(&self, state: &S, channel: &Option<Channels>, action_name: &str, result: &ImportTableDataResult) -> Result<(), Error> {
+        let channel = match channel {
+            Some(channel) => channel,
+            None => return Ok(()),
+        };
+
+        let progress = serde_json::to_value(result)
+            .map_err(|err| Error::SerializationError(err.to_string()))?;
+        state
+            .get_pub_sub()
+            .publish(channel.to_owned(), action_name.to_string(), &progress)
+            .map_err(Error::PublishError)
+    }
+}
+
+impl<S> Action<S> for ImportTableData<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    type Ret = ImportTableDataResult;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        debug!("Calling ImportTableData");
+
+        let table: data::DataStoreEntity = state
+            .get_entity_retreiver_functions()
+            .get_one(&self.table_name)
+            .map_err(|err| Error::Entity(err))?
+            .ok_or(Error::NotFound)?;
+
+        let file = self.file
+            .reopen()
+            .map_err(|err| Error::Datastore(DatastoreError::FileSystemError(err.to_string())))?;
+        let mut reader = RowReader::new(BufReader::new(file), self.format, &table.schema);
+
+        let channel = state
+            .get_authorization()
+            .username()
+            .map(|username| Channels::user(&username));
+        let mut result = ImportTableDataResult {
+            table_name: table.name.to_owned(),
+            imported_rows: 0,
+            batches: 0,
+        };
+
+        loop {
+            let rows = reader
+                .next_batch(self.batch_size)
+                .map_err(|err| Error::Datastore(DatastoreError::InvalidParam(err)))?;
+            if rows.is_empty() {
+                break;
+            }
+
+            let row_count = rows.len();
+            state
+                .get_table_controller()
+                .insert_row(&table, &serde_json::Value::Array(rows), false)
+                .map_err(|err| Error::Datastore(err))?;
+            result.imported_rows += row_count;
+            result.batches += 1;
+
+            self.publish_progress(state, &channel, "importProgress", &result)?;
+        }
+
+        info!("imported {} rows into {}", result.imported_rows, &table.name);
+        self.publish_progress(state, &channel, "importCompleted", &result)?;
+        record_usage(state, "table", &self.table_name);
+
+        ActionRes::new("importTableData", result)
+    }
+
+    fn runs_domain_queries() -> bool {
+        true
     }
 }
 
+/// All the recorded changes of a single row, only kept for the tables with `auditRows`
+#[derive(Debug)]
+pub struct GetRowHistory<S = ActionState> {
+    pub table_name: String,
+    pub key: serde_json::Value,
+    pub phantom_data: PhantomData<(S)>,
+}
+
+impl<S> GetRowHistory<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    pub fn new(table_name: String, key: serde_json::Value) -> WithPermissionRequired<WithTransaction<Self, S>, S> {
+        let action = Self {
+            table_name: table_name.to_owned(),
+            key,
+            phantom_data: PhantomData,
+        };
+
+        let action_with_transaction = WithTransaction::new(action);
+        let action_with_permission =
+            WithPermissionRequired::new(action_with_transaction, Permission::get_table_data(table_name));
+
+        action_with_permission
+    }
+}
+
+impl<S> Action<S> for GetRowHistory<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    type Ret = RowHistoryResult;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        debug!("Calling GetRowHistory");
+
+        state
+            .get_entity_retreiver_functions()
+            .get_one( &self.table_name)
+            .map_err(|err| Error::Entity(err))
+            .and_then(|res: Option<data::DataStoreEntity>| {
+                match res {
+                    Some(table) => Ok(table),
+                    None => Err(Error::NotFound),
+                }
+            })
+            .and_then(|table| {
+                state
+                    .get_table_controller()
+                    .row_history(&table, &self.key)
+                    .map_err(|err| Error::Datastore(err))
+            })
+            .and_then(|res| ActionRes::new("getRowHistory", RowHistoryResult(res)))
+    }
+}
+
+/// Computes what updating the table to `data` would do, without changing anything
+/// so clients can ask for a confirmation before the destructive steps, the stored queries reading
+/// from the table are listed as well since the change might break them
+#[derive(Debug)]
+pub struct PreviewSchemaChange<S = ActionState> {
+    pub table_name: String,
+    pub data: data::DataStoreEntity,
+    pub phantom_data: PhantomData<(S)>,
+}
+
+impl<S> PreviewSchemaChange<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    pub fn new(table_name: String, data: data::DataStoreEntity) -> WithPermissionRequired<WithTransaction<Self, S>, S> {
+        let action = Self {
+            table_name: table_name.to_owned(),
+            data,
+            phantom_data: PhantomData,
+        };
+
+        let action_with_transaction = WithTransaction::new(action);
+        let action_with_permission =
+            WithPermissionRequired::new(action_with_transaction, Permission::modify_entity::<data::DataStoreEntity>(table_name));
+
+        action_with_permission
+    }
+}
+
+impl<S> Action<S> for PreviewSchemaChange<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    type Ret = SchemaChangePreviewResult;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        debug!("Calling PreviewSchemaChange");
+
+        state
+            .get_entity_retreiver_functions()
+            .get_one( &self.table_name)
+            .map_err(|err| Error::Entity(err))
+            .and_then(|res: Option<data::DataStoreEntity>| {
+                match res {
+                    Some(table) => Ok(table),
+                    None => Err(Error::NotFound),
+                }
+            })
+            .and_then(|table| {
+                state
+                    .get_table_controller()
+                    .preview_update(&table, &self.data)
+                    .map_err(|err| Error::Datastore(err))
+            })
+            .and_then(|steps| {
+                let dependent_queries = dependent_queries(state, &self.table_name)?
+                    .into_iter()
+                    .map(|query| query.name)
+                    .collect();
+                ActionRes::new("previewSchemaChange", SchemaChangePreviewResult { steps, dependent_queries })
+            })
+    }
+}
+
+/// Creates `target` with the schema of `source`, and copies the rows as well if `with_data` is set
+#[derive(Debug)]
+pub struct CopyTable<S = ActionState> {
+    pub source: String,
+    pub target: String,
+    pub with_data: bool,
+    pub phantom_data: PhantomData<(S)>,
+}
+
+impl<S> CopyTable<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    pub fn new(source: String, target: String, with_data: bool) -> WithAudit<WithPermissionFor<WithWriteAccess<WithDispatch<WithTransaction<Self, S>, S>, S>, S>, S> {
+        let audit_target = AuditTarget::new("table", &target);
+        let channel = Channels::entity::<data::DataStoreEntity>(&target);
+        let create_permission = Permission::create_entity::<data::DataStoreEntity>();
+        let read_permission = Permission::get_table_data(source.to_owned());
+
+        let action = Self {
+            source,
+            target,
+            with_data,
+            phantom_data: PhantomData,
+        };
+
+        let action_with_transaction = WithTransaction::new(action);
+        let action_with_dispatch = WithDispatch::new(action_with_transaction, channel);
+        let action_with_write_access = WithWriteAccess::new(action_with_dispatch);
+        let action_with_permission =
+            WithPermissionFor::new(
+                action_with_write_access,
+                move |user_permissions, _all_permissions| {
+                    user_permissions.contains(&create_permission) && user_permissions.contains(&read_permission)
+                });
+
+        let action_with_audit = WithAudit::new(action_with_permission, "copyTable", audit_target);
+
+        action_with_audit
+    }
+}
+
+impl<S> Action<S> for CopyTable<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    type Ret = CopyTableResult;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        debug!("Calling CopyTable");
+
+        let source = state
+            .get_entity_retreiver_functions()
+            .get_one( &self.source)
+            .map_err(|err| Error::Entity(err))
+            .and_then(|res: Option<data::DataStoreEntity>| {
+                match res {
+                    Some(table) => Ok(table),
+                    None => Err(Error::NotFound),
+                }
+            })?;
+
+        // creating it through the entity modifier creates the table in the datastore as well
+        let target = data::DataStoreEntity {
+            name: self.target.to_owned(),
+            ..source.clone()
+        };
+        let new = state
+            .get_entity_modifier_function()
+            .create(target)
+            .map_err(|err| Error::Entity(err))
+            .and_then(|res| match res {
+                Created::Success { new } => Ok(new),
+                Created::Fail { .. } => Err(Error::AlreadyExists),
+            })?;
+
+        let copied_rows = if self.with_data {
+            let copied_rows = state
+                .get_table_controller()
+                .copy_rows(&source, &new)
+                .map_err(|err| Error::Datastore(err))?;
+            Some(copied_rows)
+        } else {
+            None
+        };
+
+        ActionRes::new("copyTable", CopyTableResult { new, copied_rows })
+    }
+}
+
+/// Runs the statement of a materialized view again, the subscribers of the view are told when it's done
+#[derive(Debug)]
+pub struct RefreshView<S = ActionState> {
+    pub view_name: String,
+    pub phantom_data: PhantomData<(S)>,
+}
+
+impl<S> RefreshView<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    pub fn new(view_name: String) -> WithAudit<WithPermissionRequired<WithWriteAccess<WithDispatch<WithTransaction<Self, S>, S>, S>, S>, S> {
+        let audit_target = AuditTarget::new("view", &view_name);
+        let channel = Channels::entity::<data::View>(&view_name);
+        let action = Self {
+            view_name: view_name.to_owned(),
+            phantom_data: PhantomData,
+        };
+
+        let action_with_transaction = WithTransaction::new(action);
+        let action_with_dispatch = WithDispatch::new(action_with_transaction, channel);
+        let action_with_write_access = WithWriteAccess::new(action_with_dispatch);
+        let action_with_permission =
+            WithPermissionRequired::new(action_with_write_access, Permission::modify_entity::<data::View>(view_name));
+
+        let action_with_audit = WithAudit::new(action_with_permission, "refreshView", audit_target);
+
+        action_with_audit
+    }
+}
+
+impl<S> Action<S> for RefreshView<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    type Ret = RefreshViewResult;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        debug!("Calling RefreshView");
+
+        state
+            .get_entity_retreiver_functions()
+            .get_one( &self.view_name)
+            .map_err(|err| Error::Entity(err))
+            .and_then(|res: Option<data::View>| {
+                match res {
+                    Some(view) => Ok(view),
+                    None => Err(Error::NotFound),
+                }
+            })
+            .and_then(|view| {
+                state
+                    .get_table_controller()
+                    .refresh_view(&view)
+                    .map_err(|err| Error::Datastore(err))
+            })
+            .and_then(|_| ActionRes::new("refreshView", RefreshViewResult {
+                name: self.view_name.to_owned(),
+                refreshed_at: chrono::Utc::now().naive_utc(),
+            }))
+    }
+}
 
 #[derive(Debug)]
 pub struct InsertTableData<S = ActionState> {
@@ -95,7 +579,8 @@ impl<S> InsertTableData<S>
     where
         for<'a> S: StateFunctions<'a>,
 {
-    pub fn new(table_name: String, data: serde_json::Value) -> WithPermissionRequired<WithDispatch<WithTransaction<Self, S>, S>, S> {
+    pub fn new(table_name: String, data: serde_json::Value) -> WithAudit<WithPermissionRequired<WithWriteAccess<WithDispatch<WithTransaction<Self, S>, S>, S>, S>, S> {
+        let audit_target = AuditTarget::new("table", &table_name);
         let channel = Channels::table(&table_name);
         let action = Self {
             table_name: table_name.to_owned(),
@@ -107,10 +592,13 @@ impl<S> InsertTableData<S>
 
         let action_with_transaction = WithTransaction::new(action);
         let action_with_dispatch = WithDispatch::new(action_with_transaction, channel);
+        let action_with_write_access = WithWriteAccess::new(action_with_dispatch);
         let action_with_permission =
-            WithPermissionRequired::new(action_with_dispatch, Permission::modify_table_data(table_name));
+            WithPermissionRequired::new(action_with_write_access, Permission::modify_table_data(table_name));
 
-        action_with_permission
+        let action_with_audit = WithAudit::new(action_with_permission, "insertTableData", audit_target);
+
+        action_with_audit
     }
 }
 
@@ -140,7 +628,14 @@ impl<S> Action<S> for InsertTableData<S>
                     OnDuplicate::Fail => table_controller.insert_row(&table, &self.data, true)
                 }.or_else(|err| Err(Error::Datastore(err)))
             })
-            .and_then(|res| ActionRes::new("insertTableData", InsertTableDataResult(res)))
+            .and_then(|res| {
+                record_usage(state, "table", &self.table_name);
+                ActionRes::new("insertTableData", InsertTableDataResult(res))
+            })
+    }
+
+    fn runs_domain_queries() -> bool {
+        true
     }
 }
 
@@ -157,7 +652,8 @@ impl<S> ModifyTableData<S>
     where
         for<'a> S: StateFunctions<'a>,
 {
-    pub fn new(table_name: String, keyed_data: serde_json::Value) -> WithPermissionRequired<WithDispatch<WithTransaction<Self, S>, S>, S> {
+    pub fn new(table_name: String, keyed_data: serde_json::Value) -> WithAudit<WithPermissionRequired<WithWriteAccess<WithDispatch<WithTransaction<Self, S>, S>, S>, S>, S> {
+        let audit_target = AuditTarget::new("table", &table_name);
         let channel = Channels::table(&table_name);
         let action = Self {
             table_name: table_name.to_owned(),
@@ -169,10 +665,13 @@ impl<S> ModifyTableData<S>
 
         let action_with_transaction = WithTransaction::new(action);
         let action_with_dispatch = WithDispatch::new(action_with_transaction, channel);
+        let action_with_write_access = WithWriteAccess::new(action_with_dispatch);
         let action_with_permission =
-            WithPermissionRequired::new(action_with_dispatch, Permission::modify_table_data(table_name));
+            WithPermissionRequired::new(action_with_write_access, Permission::modify_table_data(table_name));
 
-        action_with_permission
+        let action_with_audit = WithAudit::new(action_with_permission, "modifyTableData", audit_target);
+
+        action_with_audit
     }
 }
 
@@ -201,7 +700,14 @@ impl<S> Action<S> for ModifyTableData<S>
                     OnNotFound::Fail => table_controller.update_row(&table, &self.keyed_data, true)
                 }.or_else(|err| Err(Error::Datastore(err)))
             })
-            .and_then(|res| ActionRes::new("modifyTableData", ModifyTableDataResult(res)))
+            .and_then(|res| {
+                record_usage(state, "table", &self.table_name);
+                ActionRes::new("modifyTableData", ModifyTableDataResult(res))
+            })
+    }
+
+    fn runs_domain_queries() -> bool {
+        true
     }
 }
 
@@ -218,7 +724,8 @@ impl<S> RemoveTableData<S>
     where
         for<'a> S: StateFunctions<'a>,
 {
-    pub fn new(table_name: String, keys: serde_json::Value) -> WithPermissionRequired<WithDispatch<WithTransaction<Self, S>, S>, S> {
+    pub fn new(table_name: String, keys: serde_json::Value) -> WithAudit<WithPermissionRequired<WithWriteAccess<WithDispatch<WithTransaction<Self, S>, S>, S>, S>, S> {
+        let audit_target = AuditTarget::new("table", &table_name);
         let channel = Channels::table(&table_name);
         let action = Self {
             table_name: table_name.to_owned(),
@@ -230,10 +737,13 @@ impl<S> RemoveTableData<S>
 
         let action_with_transaction = WithTransaction::new(action);
         let action_with_dispatch = WithDispatch::new(action_with_transaction, channel);
+        let action_with_write_access = WithWriteAccess::new(action_with_dispatch);
         let action_with_permission =
-            WithPermissionRequired::new(action_with_dispatch, Permission::modify_table_data(table_name));
+            WithPermissionRequired::new(action_with_write_access, Permission::modify_table_data(table_name));
 
-        action_with_permission
+        let action_with_audit = WithAudit::new(action_with_permission, "removeTableData", audit_target);
+
+        action_with_audit
     }
 }
 
@@ -262,7 +772,396 @@ impl<S> Action<S> for RemoveTableData<S>
                     OnNotFound::Fail => table_controller.delete_row(&table, &self.keys, true)
                 }.or_else(|err| Err(Error::Datastore(err)))
             })
-            .and_then(|res| ActionRes::new("removeTableData", RemoveTableDataResult(res)))
+            .and_then(|res| {
+                record_usage(state, "table", &self.table_name);
+                ActionRes::new("removeTableData", RemoveTableDataResult(res))
+            })
+    }
+
+    fn runs_domain_queries() -> bool {
+        true
+    }
+}
+
+/// Applies a list of inserts, updates and deletes in order, in a single transaction
+/// a duplicate insert or a missing row fails the whole batch, so either every operation is applied or none
+#[derive(Debug)]
+pub struct BulkModifyTableData<S = ActionState> {
+    pub table_name: String,
+    pub operations: Vec<TableDataOperation>,
+    /// each operation runs in a savepoint, the failed ones are rolled back and returned as
+    /// `{"error": ...}` while the rest are kept. Otherwise the first failure rolls back the batch
+    pub skip_failed: bool,
+    pub phantom_data: PhantomData<(S)>,
+}
+
+impl<S> BulkModifyTableData<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    pub fn new(table_name: String, operations: Vec<TableDataOperation>, skip_failed: bool) -> WithAudit<WithPermissionRequired<WithWriteAccess<WithDispatch<WithTransaction<Self, S>, S>, S>, S>, S> {
+        let audit_target = AuditTarget::new("table", &table_name);
+        let channel = Channels::table(&table_name);
+        let action = Self {
+            table_name: table_name.to_owned(),
+            operations,
+            skip_failed,
+            phantom_data: PhantomData,
+        };
+
+        let action_with_transaction = WithTransaction::new(action);
+        let action_with_dispatch = WithDispatch::new(action_with_transaction, channel);
+        let action_with_write_access = WithWriteAccess::new(action_with_dispatch);
+        let action_with_permission =
+            WithPermissionRequired::new(action_with_write_access, Permission::modify_table_data(table_name));
+
+        let action_with_audit = WithAudit::new(action_with_permission, "bulkModifyTableData", audit_target);
+
+        action_with_audit
+    }
+}
+
+impl<S> Action<S> for BulkModifyTableData<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    type Ret = BulkModifyTableDataResult;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        debug!("Calling BulkModifyTableData");
+
+        let table: data::DataStoreEntity = state
+            .get_entity_retreiver_functions()
+            .get_one(&self.table_name)
+            .or_else(|err| Err(Error::Entity(err)))
+            .and_then(|res: Option<data::DataStoreEntity>| {
+                match res {
+                    Some(table) => Ok(table),
+                    None => Err(Error::NotFound),
+                }
+            })?;
+
+        let table_controller = state.get_table_controller();
+        let mut results = vec![];
+        for (i, operation) in self.operations.iter().enumerate() {
+            let apply = || match operation {
+                TableDataOperation::Insert { data } => table_controller.insert_row(&table, data, true),
+                TableDataOperation::Upsert { data } => table_controller.upsert_row(&table, data),
+                TableDataOperation::Update { data } => table_controller.update_row(&table, data, true),
+                TableDataOperation::Delete { keys } => table_controller.delete_row(&table, keys, true),
+            }.map_err(Error::Datastore);
+
+            let res = if self.skip_failed {
+                state
+                    .transaction::<_, Error, _>(apply)
+                    .unwrap_or_else(|err| {
+                        debug!("operation #{} ({}) failed, rolled back to its savepoint", i, operation.name());
+                        err.envelope()
+                    })
+            } else {
+                apply().or_else(|err| {
+                    debug!("operation #{} ({}) failed, rolling back the batch", i, operation.name());
+                    Err(err)
+                })?
+            };
+
+            results.push(res);
+        }
+
+        record_usage(state, "table", &self.table_name);
+        ActionRes::new("bulkModifyTableData", BulkModifyTableDataResult(results))
+    }
+
+    fn runs_domain_queries() -> bool {
+        true
+    }
+}
+
+/// Updates every row matching the filter with the same values, e.g.
+/// `{"filter": {"op": "lessThan", ...}, "values": {"archived": true}}`
+#[derive(Debug)]
+pub struct ModifyTableDataByFilter<S = ActionState> {
+    pub table_name: String,
+    pub filtered_values: serde_json::Value,
+    pub phantom_data: PhantomData<(S)>,
+}
+
+impl<S> ModifyTableDataByFilter<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    pub fn new(table_name: String, filtered_values: serde_json::Value) -> WithAudit<WithPermissionRequired<WithWriteAccess<WithDispatch<WithTransaction<Self, S>, S>, S>, S>, S> {
+        let audit_target = AuditTarget::new("table", &table_name);
+        let channel = Channels::table(&table_name);
+        let action = Self {
+            table_name: table_name.to_owned(),
+            filtered_values,
+            phantom_data: PhantomData,
+        };
+
+        let action_with_transaction = WithTransaction::new(action);
+        let action_with_dispatch = WithDispatch::new(action_with_transaction, channel);
+        let action_with_write_access = WithWriteAccess::new(action_with_dispatch);
+        let action_with_permission =
+            WithPermissionRequired::new(action_with_write_access, Permission::modify_table_data(table_name));
+
+        let action_with_audit = WithAudit::new(action_with_permission, "modifyTableDataByFilter", audit_target);
+
+        action_with_audit
+    }
+}
+
+impl<S> Action<S> for ModifyTableDataByFilter<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    type Ret = AffectedRowsResult;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        debug!("Calling ModifyTableDataByFilter");
+
+        state
+            .get_entity_retreiver_functions()
+            .get_one(&self.table_name)
+            .or_else(|err| Err(Error::Entity(err)))
+            .and_then(|res: Option<data::DataStoreEntity>| {
+                match res {
+                    Some(table) => Ok(table),
+                    None => Err(Error::NotFound),
+                }
+            })
+            .and_then(|table| {
+                state
+                    .get_table_controller()
+                    .update_rows_where(&table, &self.filtered_values)
+                    .or_else(|err| Err(Error::Datastore(err)))
+            })
+            .and_then(|res| {
+                let affected_rows = res["data"].as_array().map(|x| x.len()).unwrap_or(0);
+                record_usage(state, "table", &self.table_name);
+                ActionRes::new("modifyTableDataByFilter", AffectedRowsResult { affected_rows })
+            })
+    }
+
+    fn runs_domain_queries() -> bool {
+        true
+    }
+}
+
+/// Deletes every row matching the filter, e.g. `{"filter": {"op": "lessThan", ...}}`
+#[derive(Debug)]
+pub struct RemoveTableDataByFilter<S = ActionState> {
+    pub table_name: String,
+    pub filter: serde_json::Value,
+    pub phantom_data: PhantomData<(S)>,
+}
+
+impl<S> RemoveTableDataByFilter<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    pub fn new(table_name: String, filter: serde_json::Value) -> WithAudit<WithPermissionRequired<WithWriteAccess<WithDispatch<WithTransaction<Self, S>, S>, S>, S>, S> {
+        let audit_target = AuditTarget::new("table", &table_name);
+        let channel = Channels::table(&table_name);
+        let action = Self {
+            table_name: table_name.to_owned(),
+            filter,
+            phantom_data: PhantomData,
+        };
+
+        let action_with_transaction = WithTransaction::new(action);
+        let action_with_dispatch = WithDispatch::new(action_with_transaction, channel);
+        let action_with_write_access = WithWriteAccess::new(action_with_dispatch);
+        let action_with_permission =
+            WithPermissionRequired::new(action_with_write_access, Permission::modify_table_data(table_name));
+
+        let action_with_audit = WithAudit::new(action_with_permission, "removeTableDataByFilter", audit_target);
+
+        action_with_audit
+    }
+}
+
+impl<S> Action<S> for RemoveTableDataByFilter<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    type Ret = AffectedRowsResult;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        debug!("Calling RemoveTableDataByFilter");
+
+        state
+            .get_entity_retreiver_functions()
+            .get_one(&self.table_name)
+            .or_else(|err| Err(Error::Entity(err)))
+            .and_then(|res: Option<data::DataStoreEntity>| {
+                match res {
+                    Some(table) => Ok(table),
+                    None => Err(Error::NotFound),
+                }
+            })
+            .and_then(|table| {
+                state
+                    .get_table_controller()
+                    .delete_rows_where(&table, &self.filter)
+                    .or_else(|err| Err(Error::Datastore(err)))
+            })
+            .and_then(|res| {
+                let affected_rows = res["data"].as_array().map(|x| x.len()).unwrap_or(0);
+                record_usage(state, "table", &self.table_name);
+                ActionRes::new("removeTableDataByFilter", AffectedRowsResult { affected_rows })
+            })
+    }
+
+    fn runs_domain_queries() -> bool {
+        true
+    }
+}
+
+/// Deletes the expired rows batch by batch, each batch is committed on its own so the table isn't locked
+/// for the whole purge. The progress is published on the table channel after every batch, and the totals once it's done
+fn apply_retention_policy<S>(state: &S, table: &data::DataStoreEntity, batch_size: usize) -> Result<RetentionResult, Error>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    let channel = Channels::table(&table.name);
+    let mut result = RetentionResult {
+        table_name: table.name.to_owned(),
+        deleted_rows: 0,
+    };
+
+    loop {
+        let deleted_rows = state
+            .get_table_controller()
+            .purge_expired_rows(table, batch_size)
+            .map_err(|err| Error::Datastore(err))?;
+        result.deleted_rows += deleted_rows;
+
+        if deleted_rows < batch_size {
+            break;
+        }
+
+        let progress = serde_json::to_value(&result)
+            .map_err(|err| Error::SerializationError(err.to_string()))?;
+        state
+            .get_pub_sub()
+            .publish(channel.to_owned(), "retentionProgress".to_string(), &progress)
+            .map_err(Error::PublishError)?;
+    }
+
+    info!("retention policy of {} deleted {} rows", &table.name, result.deleted_rows);
+    let done = serde_json::to_value(&result)
+        .map_err(|err| Error::SerializationError(err.to_string()))?;
+    state
+        .get_pub_sub()
+        .publish(channel, "retentionCompleted".to_string(), &done)
+        .map_err(Error::PublishError)?;
+
+    Ok(result)
+}
+
+/// Deletes the rows of the table that are older than its retention policy, see `RetentionPolicy`
+/// The rows are deleted in batches outside of a transaction, see `apply_retention_policy`
+#[derive(Debug)]
+pub struct ApplyRetentionPolicy<S = ActionState> {
+    pub table_name: String,
+    pub batch_size: usize,
+    pub phantom_data: PhantomData<(S)>,
+}
+
+impl<S> ApplyRetentionPolicy<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    pub fn new(table_name: String) -> WithAudit<WithPermissionRequired<WithWriteAccess<Self, S>, S>, S> {
+        let audit_target = AuditTarget::new("table", &table_name);
+        let action = Self {
+            table_name: table_name.to_owned(),
+            batch_size: RETENTION_BATCH_SIZE,
+            phantom_data: PhantomData,
+        };
+
+        let action_with_write_access = WithWriteAccess::new(action);
+        let action_with_permission =
+            WithPermissionRequired::new(action_with_write_access, Permission::modify_table_data(table_name));
+
+        let action_with_audit = WithAudit::new(action_with_permission, "applyRetentionPolicy", audit_target);
+
+        action_with_audit
+    }
+}
+
+impl<S> Action<S> for ApplyRetentionPolicy<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    type Ret = RetentionResult;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        debug!("Calling ApplyRetentionPolicy");
+
+        state
+            .get_entity_retreiver_functions()
+            .get_one(&self.table_name)
+            .or_else(|err| Err(Error::Entity(err)))
+            .and_then(|res: Option<data::DataStoreEntity>| {
+                match res {
+                    Some(table) => Ok(table),
+                    None => Err(Error::NotFound),
+                }
+            })
+            .and_then(|table| apply_retention_policy(state, &table, self.batch_size))
+            .and_then(|res| ActionRes::new("applyRetentionPolicy", res))
+    }
+}
+
+/// Applies the retention policy of every table in the domain that has one, admin only
+/// This is what the retention job runs, a table that fails is logged and skipped
+#[derive(Debug)]
+pub struct ApplyRetentionPolicies<S = ActionState> {
+    pub batch_size: usize,
+    pub phantom_data: PhantomData<(S)>,
+}
+
+impl<S> ApplyRetentionPolicies<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    pub fn new() -> WithAudit<WithPermissionRequired<WithWriteAccess<Self, S>, S>, S> {
+        let audit_target = AuditTarget::none();
+        let action = Self {
+            batch_size: RETENTION_BATCH_SIZE,
+            phantom_data: PhantomData,
+        };
+
+        let action_with_write_access = WithWriteAccess::new(action);
+        let action_with_permission = WithPermissionRequired::new(action_with_write_access, Permission::user_admin());
+        let action_with_audit = WithAudit::new(action_with_permission, "applyRetentionPolicies", audit_target);
+
+        action_with_audit
+    }
+}
+
+impl<S> Action<S> for ApplyRetentionPolicies<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    type Ret = RetentionPoliciesResult;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        debug!("Calling ApplyRetentionPolicies");
+
+        let tables: Vec<data::DataStoreEntity> = state
+            .get_entity_retreiver_functions()
+            .get_all()
+            .or_else(|err| Err(Error::Entity(err)))?;
+
+        let results = tables
+            .iter()
+            .filter(|table| table.schema["retention"].is_object())
+            .filter_map(|table| {
+                apply_retention_policy(state, table, self.batch_size)
+                    .map_err(|err| error!("could not apply the retention policy of {}: {:?}", &table.name, &err))
+                    .ok()
+            })
+            .collect();
+
+        ActionRes::new("applyRetentionPolicies", RetentionPoliciesResult(results))
     }
 }
 
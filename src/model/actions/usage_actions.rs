@@ -0,0 +1,73 @@
+use std::marker::PhantomData;
+
+use data::permissions::Permission;
+
+use model::actions::results::*;
+use model::actions::error::Error;
+use model::actions::decorator::*;
+use model::actions::Action;
+use model::actions::ActionRes;
+use model::actions::ActionResult;
+
+use state::StateFunctions;
+use state::ActionState;
+use state::entity_usage::EntityUsageOps;
+
+/// entity types whose usage is recorded
+const USAGE_TYPES: [&str; 3] = ["table", "query", "script"];
+
+/// number of users listed for every entity in `GetEntityUsage`
+pub const TOP_USERS: usize = 5;
+
+/// Records that the action used the entity, failing to do so doesn't fail the action
+pub fn record_usage<S>(state: &S, type_name: &str, entity_name: &str)
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    if let Err(err) = state.get_entity_usage().record_usage(type_name, entity_name) {
+        warn!("could not record the usage of {} {:?}: {:?}", type_name, entity_name, &err);
+    }
+}
+
+/// How often every table, query and script of the domain was used and by whom, admin only
+/// the entities that were never used are listed as well
+#[derive(Debug, Clone)]
+pub struct GetEntityUsage<S = ActionState> {
+    pub phantom_data: PhantomData<(S)>,
+}
+
+impl<S> GetEntityUsage<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    pub fn new() -> WithPermissionRequired<Self, S> {
+        let action = Self {
+            phantom_data: PhantomData,
+        };
+
+        let action = WithPermissionRequired::new(action, Permission::user_admin());
+
+        action
+    }
+}
+
+impl<S> Action<S> for GetEntityUsage<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    type Ret = EntityUsageResult;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        debug!("Calling GetEntityUsage");
+
+        let mut usages = vec![];
+        for type_name in USAGE_TYPES.iter() {
+            let type_usages = state
+                .get_entity_usage()
+                .get_usage(type_name, TOP_USERS)
+                .map_err(|err| Error::Datastore(err))?;
+            usages.extend(type_usages);
+        }
+
+        ActionRes::new("getEntityUsage", EntityUsageResult(usages))
+    }
+}
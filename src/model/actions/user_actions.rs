@@ -4,6 +4,7 @@ use std::marker::PhantomData;
 use data;
 use data::permissions::*;
 use data::auth::SessionToken;
+use data::audit::AuditTarget;
 
 use model::actions::results::*;
 use model::actions::error::Error;
@@ -11,6 +12,7 @@ use model::actions::decorator::*;
 use model::actions::Action;
 use model::actions::ActionRes;
 use model::actions::ActionResult;
+use model::actions::paging::Paging;
 use state::ActionState;
 use state::StateFunctions;
 use state::user_management::UserManagementOps;
@@ -150,14 +152,16 @@ impl<S> Action<S> for Logout<S>
 /// User Auth: Get All users
 #[derive(Debug)]
 pub struct GetAllUsers<S = ActionState> {
+    paging: Paging,
     phantom_data: PhantomData<(S)>,
 }
 
 impl<S> GetAllUsers<S>
     where for<'a> S: GetSecrets + StateFunctions<'a>,
 {
-    pub fn new() -> WithLoginRequired<WithTransaction<Self, S>, S> {
+    pub fn new(paging: Paging) -> WithLoginRequired<WithTransaction<Self, S>, S> {
         let action = Self {
+            paging,
             phantom_data: PhantomData,
         };
 
@@ -181,7 +185,7 @@ impl<S> Action<S> for GetAllUsers<S>
             .get_user_management() //TODO: this should be the responsibility of the authorization
             .get_all_users()
             .map_err(Error::UserManagement)
-            .and_then(|res| ActionRes::new("getAllUsers", AllUsersResult(res)))
+            .and_then(|res| ActionRes::new("getAllUsers", AllUsersResult(self.paging.apply(res))))
     }
 }
 
@@ -196,7 +200,8 @@ pub struct AddUser<S = ActionState> {
 impl<S> AddUser<S>
     where for<'a> S: GetSecrets + StateFunctions<'a>,
 {
-    pub fn new(user: data::auth::NewUser) -> WithPermissionRequired<WithTransaction<Self, S>, S> {
+    pub fn new(user: data::auth::NewUser) -> WithAudit<WithPermissionRequired<WithTransaction<Self, S>, S>, S> {
+        let audit_target = AuditTarget::new("user", &user.username);
         let action = Self {
             user,
             phantom_data: PhantomData,
@@ -206,7 +211,9 @@ impl<S> AddUser<S>
         let action_with_permission =
             WithPermissionRequired::new(action_with_transaction, Permission::user_admin());
 
-        action_with_permission
+        let action_with_audit = WithAudit::new(action_with_permission, "addUser", audit_target);
+
+        action_with_audit
     }
 }
 
@@ -235,7 +242,8 @@ pub struct RemoveUser<S = ActionState> {
 impl<S> RemoveUser<S>
     where for<'a> S: GetSecrets + StateFunctions<'a>,
 {
-    pub fn new(user_identifier: String) -> WithPermissionRequired<WithTransaction<Self, S>, S> {
+    pub fn new(user_identifier: String) -> WithAudit<WithPermissionRequired<WithTransaction<Self, S>, S>, S> {
+        let audit_target = AuditTarget::new("user", &user_identifier);
         let action = Self {
             user_identifier,
             phantom_data: PhantomData,
@@ -245,7 +253,9 @@ impl<S> RemoveUser<S>
         let action_with_permission =
             WithPermissionRequired::new(action_with_transaction, Permission::user_admin());
 
-        action_with_permission
+        let action_with_audit = WithAudit::new(action_with_permission, "removeUser", audit_target);
+
+        action_with_audit
     }
 }
 
@@ -281,7 +291,8 @@ pub struct InviteUser<S = ActionState> {
 impl<S> InviteUser<S>
     where for<'a> S: GetSecrets + StateFunctions<'a>,
 {
-    pub fn new(email: String) -> WithPermissionRequired<WithTransaction<Self, S>, S> {
+    pub fn new(email: String) -> WithAudit<WithPermissionRequired<WithTransaction<Self, S>, S>, S> {
+        let audit_target = AuditTarget::new("user", &email);
         let action = Self {
             email,
             phantom_data: PhantomData,
@@ -291,7 +302,9 @@ impl<S> InviteUser<S>
         let action_with_permission =
             WithPermissionRequired::new(action_with_transaction, Permission::user_admin());
 
-        action_with_permission
+        let action_with_audit = WithAudit::new(action_with_permission, "inviteUser", audit_target);
+
+        action_with_audit
     }
 }
 
@@ -326,7 +339,8 @@ pub struct SetupUser<S = ActionState> {
 impl<S> SetupUser<S>
     where for<'a> S: GetSecrets + StateFunctions<'a>,
 {
-    pub fn new(user: data::auth::NewUser) -> WithPermissionRequired<WithTransaction<Self, S>, S> {
+    pub fn new(user: data::auth::NewUser) -> WithAudit<WithPermissionRequired<WithTransaction<Self, S>, S>, S> {
+        let audit_target = AuditTarget::new("user", &user.username);
         let action = Self {
             user,
             phantom_data: PhantomData,
@@ -337,7 +351,9 @@ impl<S> SetupUser<S>
         let action_with_permission =
             WithPermissionRequired::new(action_with_transaction, Permission::user_admin());
 
-        action_with_permission
+        let action_with_audit = WithAudit::new(action_with_permission, "setupUser", audit_target);
+
+        action_with_audit
     }
 }
 
@@ -368,7 +384,8 @@ pub struct SetUserPassword<S = ActionState> {
 impl<S> SetUserPassword<S>
     where for<'a> S: GetSecrets + StateFunctions<'a>,
 {
-    pub fn new(user_identifier: String, password: String) -> WithPermissionRequired<WithTransaction<Self, S>, S> {
+    pub fn new(user_identifier: String, password: String) -> WithAudit<WithPermissionRequired<WithTransaction<Self, S>, S>, S> {
+        let audit_target = AuditTarget::new("user", &user_identifier);
         let required_permissions = vec![
             Permission::user(user_identifier.to_owned()),
             Permission::user_email(user_identifier.to_owned())];
@@ -385,7 +402,9 @@ impl<S> SetUserPassword<S>
                 action_with_transaction,
                 required_permissions);
 
-        action_with_permission
+        let action_with_audit = WithAudit::new(action_with_permission, "setUserPassword", audit_target);
+
+        action_with_audit
     }
 }
 
@@ -416,7 +435,8 @@ pub struct AddRole<S = ActionState> {
 impl<S> AddRole<S>
     where for<'a> S: GetSecrets + StateFunctions<'a>,
 {
-    pub fn new(role: data::auth::Role) -> WithPermissionRequired<WithTransaction<Self, S>, S> {
+    pub fn new(role: data::auth::Role) -> WithAudit<WithPermissionRequired<WithTransaction<Self, S>, S>, S> {
+        let audit_target = AuditTarget::new("role", &role.name);
         let action = Self {
             role,
             phantom_data: PhantomData,
@@ -426,7 +446,9 @@ impl<S> AddRole<S>
         let action_with_permission =
             WithPermissionRequired::new(action_with_transaction, Permission::user_admin()); //TODO: also needs the role, or maybe not, idk
 
-        action_with_permission
+        let action_with_audit = WithAudit::new(action_with_permission, "addRole", audit_target);
+
+        action_with_audit
     }
 }
 
@@ -455,7 +477,8 @@ pub struct RemoveRole<S = ActionState> {
 impl<S> RemoveRole<S>
     where for<'a> S: GetSecrets + StateFunctions<'a>,
 {
-    pub fn new(rolename: String) -> WithPermissionRequired<WithTransaction<Self, S>, S> {
+    pub fn new(rolename: String) -> WithAudit<WithPermissionRequired<WithTransaction<Self, S>, S>, S> {
+        let audit_target = AuditTarget::new("role", &rolename);
         let action = Self {
             rolename,
             phantom_data: PhantomData,
@@ -465,7 +488,9 @@ impl<S> RemoveRole<S>
         let action_with_permission =
             WithPermissionRequired::new(action_with_transaction, Permission::user_admin()); //TODO: also needs to have the role, or maybe not idk
 
-        action_with_permission
+        let action_with_audit = WithAudit::new(action_with_permission, "removeRole", audit_target);
+
+        action_with_audit
     }
 }
 
@@ -494,14 +519,16 @@ impl<S> Action<S> for RemoveRole<S>
 /// Role Auth: get all role
 #[derive(Debug)]
 pub struct GetAllRoles<S = ActionState> {
+    paging: Paging,
     phantom_data: PhantomData<(S)>,
 }
 
 impl<S> GetAllRoles<S>
     where for<'a> S: GetSecrets + StateFunctions<'a>,
 {
-    pub fn new() -> WithPermissionRequired<WithTransaction<Self, S>, S> {
+    pub fn new(paging: Paging) -> WithPermissionRequired<WithTransaction<Self, S>, S> {
         let action = Self {
+            paging,
             phantom_data: PhantomData,
         };
 
@@ -524,7 +551,7 @@ impl<S> Action<S> for GetAllRoles<S>
             .get_user_management()
             .get_all_roles()
             .or_else(|err| Err(Error::UserManagement(err)))
-            .and_then(|res| ActionRes::new("getAllRoles", AllRolesResult(res)))
+            .and_then(|res| ActionRes::new("getAllRoles", AllRolesResult(self.paging.apply(res))))
     }
 }
 
@@ -539,7 +566,8 @@ pub struct AttachPermissionForRole<S = ActionState> {
 impl<S> AttachPermissionForRole<S>
     where for<'a> S: GetSecrets + StateFunctions<'a>,
 {
-    pub fn new(rolename: String, permission: Permission) -> WithPermissionRequired<WithTransaction<Self, S>, S> {
+    pub fn new(rolename: String, permission: Permission) -> WithAudit<WithPermissionRequired<WithTransaction<Self, S>, S>, S> {
+        let audit_target = AuditTarget::new("role", &rolename);
         let required_permissions = vec![
             Permission::user_admin(),
             Permission::has_role(rolename.to_owned()),
@@ -555,7 +583,9 @@ impl<S> AttachPermissionForRole<S>
         let action_with_permission =
             WithPermissionRequired::new_all_of(action_with_transaction, required_permissions);
 
-        action_with_permission
+        let action_with_audit = WithAudit::new(action_with_permission, "attachPermissionForRole", audit_target);
+
+        action_with_audit
     }
 }
 
@@ -585,7 +615,8 @@ pub struct DetachPermissionForRole<S = ActionState> {
 impl<S> DetachPermissionForRole<S>
     where for<'a> S: GetSecrets + StateFunctions<'a>,
 {
-    pub fn new(rolename: String, permission: Permission) -> WithPermissionRequired<WithTransaction<Self, S>, S> {
+    pub fn new(rolename: String, permission: Permission) -> WithAudit<WithPermissionRequired<WithTransaction<Self, S>, S>, S> {
+        let audit_target = AuditTarget::new("role", &rolename);
         let required_permissions = vec![
             Permission::user_admin(),
             Permission::has_role(rolename.to_owned()),
@@ -601,7 +632,9 @@ impl<S> DetachPermissionForRole<S>
         let action_with_permission =
             WithPermissionRequired::new_all_of(action_with_transaction, required_permissions);
 
-        action_with_permission
+        let action_with_audit = WithAudit::new(action_with_permission, "detachPermissionForRole", audit_target);
+
+        action_with_audit
     }
 }
 
@@ -638,7 +671,8 @@ pub struct AttachRoleForUser<S = ActionState> {
 impl<S> AttachRoleForUser<S>
     where for<'a> S: GetSecrets + StateFunctions<'a>,
 {
-    pub fn new(user_identifier: String, rolename: String) -> WithPermissionRequired<WithTransaction<Self, S>, S> {
+    pub fn new(user_identifier: String, rolename: String) -> WithAudit<WithPermissionRequired<WithTransaction<Self, S>, S>, S> {
+        let audit_target = AuditTarget::new("user", &user_identifier);
         let required_permissions = vec![
             Permission::user_admin(),
             Permission::has_role(rolename.to_owned()),
@@ -653,7 +687,9 @@ impl<S> AttachRoleForUser<S>
         let action_with_permission =
             WithPermissionRequired::new_all_of(action_with_transaction, required_permissions);
 
-        action_with_permission
+        let action_with_audit = WithAudit::new(action_with_permission, "attachRoleForUser", audit_target);
+
+        action_with_audit
     }
 }
 
@@ -683,7 +719,8 @@ pub struct DetachRoleForUser<S = ActionState> {
 impl<S> DetachRoleForUser<S>
     where for<'a> S: GetSecrets + StateFunctions<'a>,
 {
-    pub fn new(user_identifier: String, rolename: String) -> WithPermissionRequired<WithTransaction<Self, S>, S> {
+    pub fn new(user_identifier: String, rolename: String) -> WithAudit<WithPermissionRequired<WithTransaction<Self, S>, S>, S> {
+        let audit_target = AuditTarget::new("user", &user_identifier);
         let required_permissions = vec![
             Permission::user_admin(),
             Permission::has_role(rolename.to_owned()),
@@ -698,7 +735,9 @@ impl<S> DetachRoleForUser<S>
         let action_with_permission =
             WithPermissionRequired::new_all_of(action_with_transaction, required_permissions);
 
-        action_with_permission
+        let action_with_audit = WithAudit::new(action_with_permission, "detachRoleForUser", audit_target);
+
+        action_with_audit
     }
 }
 
@@ -1030,9 +1069,12 @@ mod test {
                 let result = create_action.call(&state);
             }
 
-            let create_action = GetAllRoles::<MockState>::new();
+            let create_action = GetAllRoles::<MockState>::new(Paging::default());
             let result = create_action.call(&state);
-            let AllRolesResult(data) = result.unwrap().get_data();
+            let data = match result.unwrap().get_data() {
+                AllRolesResult(ListResult::All(data)) => data,
+                other => panic!("expected the whole list, got {:?}", other),
+            };
             let final_rolenames: Vec<String> = data.into_iter().map(|x| x.name).collect();
 
             for role in roles {
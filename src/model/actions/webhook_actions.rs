@@ -0,0 +1,415 @@
+use std::result::Result::Ok;
+use std::marker::PhantomData;
+
+use data;
+use data::audit::AuditTarget;
+use data::error::DatastoreError;
+use data::permissions::Permission;
+use data::webhook::NewChannelWebhook;
+use data::webhook::RetryPolicy;
+
+use model::actions::decorator::*;
+use model::actions::results::*;
+use model::actions::error::Error;
+use model::actions::Action;
+use model::actions::ActionRes;
+use model::actions::ActionResult;
+
+use state::StateFunctions;
+use state::ActionState;
+use state::authorization::AuthorizationOps;
+use state::webhook_deliveries::WebhookDeliveriesOps;
+
+/// most deliveries returned by `GetWebhookDeliveries`
+pub const WEBHOOK_DELIVERIES_LIMIT: usize = 100;
+
+/// The latest deliveries of the webhook with the outcome of their last attempt
+#[derive(Debug)]
+pub struct GetWebhookDeliveries<S = ActionState> {
+    pub webhook_name: String,
+    pub phantom_data: PhantomData<(S)>,
+}
+
+impl<S> GetWebhookDeliveries<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    pub fn new(webhook_name: String) -> WithPermissionRequired<WithTransaction<Self, S>, S> {
+        let action = Self {
+            webhook_name: webhook_name.to_owned(),
+            phantom_data: PhantomData,
+        };
+
+        let action_with_transaction = WithTransaction::new(action);
+        let action_with_permission =
+            WithPermissionRequired::new(action_with_transaction, Permission::read_entity::<data::Webhook>(webhook_name));
+
+        action_with_permission
+    }
+}
+
+impl<S> Action<S> for GetWebhookDeliveries<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    type Ret = WebhookDeliveriesResult;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        debug!("Calling GetWebhookDeliveries");
+
+        state
+            .get_webhook_deliveries()
+            .get_deliveries(&self.webhook_name, WEBHOOK_DELIVERIES_LIMIT)
+            .map_err(Error::Datastore)
+            .and_then(|res| ActionRes::new("getWebhookDeliveries", WebhookDeliveriesResult(res)))
+    }
+}
+
+/// Takes the deliveries of the domain that are due, for the webhook job to post them. Admin only
+#[derive(Debug)]
+pub struct ClaimWebhookDeliveries<S = ActionState> {
+    pub limit: usize,
+    /// how long the job has to post them before they are due again
+    pub claim_seconds: u64,
+    pub phantom_data: PhantomData<(S)>,
+}
+
+impl<S> ClaimWebhookDeliveries<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    pub fn new(limit: usize, claim_seconds: u64) -> WithPermissionRequired<WithWriteAccess<WithTransaction<Self, S>, S>, S> {
+        let action = Self {
+            limit,
+            claim_seconds,
+            phantom_data: PhantomData,
+        };
+
+        let action_with_transaction = WithTransaction::new(action);
+        let action_with_write_access = WithWriteAccess::new(action_with_transaction);
+        let action_with_permission = WithPermissionRequired::new(action_with_write_access, Permission::user_admin());
+
+        action_with_permission
+    }
+}
+
+impl<S> Action<S> for ClaimWebhookDeliveries<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    type Ret = DueDeliveriesResult;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        debug!("Calling ClaimWebhookDeliveries");
+
+        state
+            .get_webhook_deliveries()
+            .claim_due_deliveries(self.limit, self.claim_seconds)
+            .map_err(Error::Datastore)
+            .and_then(|res| ActionRes::new("claimWebhookDeliveries", DueDeliveriesResult(res)))
+    }
+}
+
+/// Records the outcome of posting a delivery, schedules the next attempt if it failed. Admin only
+#[derive(Debug)]
+pub struct RecordWebhookAttempt<S = ActionState> {
+    pub delivery_id: i64,
+    /// the attempts made before this one
+    pub attempts: i32,
+    pub retry: RetryPolicy,
+    pub status_code: Option<i32>,
+    pub error: Option<String>,
+    pub phantom_data: PhantomData<(S)>,
+}
+
+impl<S> RecordWebhookAttempt<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    pub fn new(
+        delivery_id: i64,
+        attempts: i32,
+        retry: RetryPolicy,
+        status_code: Option<i32>,
+        error: Option<String>,
+    ) -> WithPermissionRequired<WithWriteAccess<WithTransaction<Self, S>, S>, S> {
+        let action = Self {
+            delivery_id,
+            attempts,
+            retry,
+            status_code,
+            error,
+            phantom_data: PhantomData,
+        };
+
+        let action_with_transaction = WithTransaction::new(action);
+        let action_with_write_access = WithWriteAccess::new(action_with_transaction);
+        let action_with_permission = WithPermissionRequired::new(action_with_write_access, Permission::user_admin());
+
+        action_with_permission
+    }
+}
+
+impl<S> Action<S> for RecordWebhookAttempt<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    type Ret = ();
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        debug!("Calling RecordWebhookAttempt");
+
+        state
+            .get_webhook_deliveries()
+            .record_attempt(self.delivery_id, self.attempts, &self.retry, self.status_code, self.error.to_owned())
+            .map_err(Error::Datastore)
+            .and_then(|_| ActionRes::new("recordWebhookAttempt", ()))
+    }
+}
+
+/// Takes the deliveries of the channel webhooks that are due, of every domain. Admin only
+#[derive(Debug)]
+pub struct ClaimChannelWebhookDeliveries<S = ActionState> {
+    pub limit: usize,
+    /// how long the job has to post them before they are due again
+    pub claim_seconds: u64,
+    pub phantom_data: PhantomData<(S)>,
+}
+
+impl<S> ClaimChannelWebhookDeliveries<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    pub fn new(limit: usize, claim_seconds: u64) -> WithPermissionRequired<WithWriteAccess<WithTransaction<Self, S>, S>, S> {
+        let action = Self {
+            limit,
+            claim_seconds,
+            phantom_data: PhantomData,
+        };
+
+        let action_with_transaction = WithTransaction::new(action);
+        let action_with_write_access = WithWriteAccess::new(action_with_transaction);
+        let action_with_permission = WithPermissionRequired::new(action_with_write_access, Permission::user_admin());
+
+        action_with_permission
+    }
+}
+
+impl<S> Action<S> for ClaimChannelWebhookDeliveries<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    type Ret = DueDeliveriesResult;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        debug!("Calling ClaimChannelWebhookDeliveries");
+
+        state
+            .get_webhook_deliveries()
+            .claim_due_channel_deliveries(self.limit, self.claim_seconds)
+            .map_err(Error::Datastore)
+            .and_then(|res| ActionRes::new("claimChannelWebhookDeliveries", DueDeliveriesResult(res)))
+    }
+}
+
+/// Posts every message published on the channel to the url as well, signed with the secret. Admin only
+#[derive(Debug)]
+pub struct AddChannelWebhook<S = ActionState> {
+    pub webhook: NewChannelWebhook,
+    pub phantom_data: PhantomData<(S)>,
+}
+
+impl<S> AddChannelWebhook<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    pub fn new(webhook: NewChannelWebhook) -> WithAudit<WithPermissionRequired<WithWriteAccess<WithTransaction<Self, S>, S>, S>, S> {
+        let audit_target = AuditTarget::new("channelWebhook", &webhook.url);
+        let action = Self {
+            webhook,
+            phantom_data: PhantomData,
+        };
+
+        let action_with_transaction = WithTransaction::new(action);
+        let action_with_write_access = WithWriteAccess::new(action_with_transaction);
+        let action_with_permission = WithPermissionRequired::new(action_with_write_access, Permission::user_admin());
+        let action_with_audit = WithAudit::new(action_with_permission, "addChannelWebhook", audit_target);
+
+        action_with_audit
+    }
+}
+
+impl<S> Action<S> for AddChannelWebhook<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    type Ret = ChannelWebhookResult;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        debug!("Calling AddChannelWebhook");
+
+        let authorization = state.get_authorization();
+
+        state
+            .get_webhook_deliveries()
+            .add_channel_webhook(&self.webhook, authorization.user_id())
+            .map_err(|err| match err {
+                DatastoreError::AlreadyExists => Error::AlreadyExists,
+                _ => Error::Datastore(err),
+            })
+            .and_then(|res| ActionRes::new("addChannelWebhook", ChannelWebhookResult(res)))
+    }
+}
+
+/// Detaches the webhook from its channel, its pending deliveries are dropped. Admin only
+#[derive(Debug)]
+pub struct RemoveChannelWebhook<S = ActionState> {
+    pub id: i64,
+    pub phantom_data: PhantomData<(S)>,
+}
+
+impl<S> RemoveChannelWebhook<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    pub fn new(id: i64) -> WithAudit<WithPermissionRequired<WithWriteAccess<WithTransaction<Self, S>, S>, S>, S> {
+        let audit_target = AuditTarget::new("channelWebhook", &id.to_string());
+        let action = Self {
+            id,
+            phantom_data: PhantomData,
+        };
+
+        let action_with_transaction = WithTransaction::new(action);
+        let action_with_write_access = WithWriteAccess::new(action_with_transaction);
+        let action_with_permission = WithPermissionRequired::new(action_with_write_access, Permission::user_admin());
+        let action_with_audit = WithAudit::new(action_with_permission, "removeChannelWebhook", audit_target);
+
+        action_with_audit
+    }
+}
+
+impl<S> Action<S> for RemoveChannelWebhook<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    type Ret = ChannelWebhookResult;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        debug!("Calling RemoveChannelWebhook");
+
+        state
+            .get_webhook_deliveries()
+            .remove_channel_webhook(self.id)
+            .map_err(Error::Datastore)?
+            .ok_or(Error::NotFound)
+            .and_then(|res| ActionRes::new("removeChannelWebhook", ChannelWebhookResult(res)))
+    }
+}
+
+/// The webhooks attached to the channels, without their secrets. Admin only
+#[derive(Debug)]
+pub struct GetChannelWebhooks<S = ActionState> {
+    pub phantom_data: PhantomData<(S)>,
+}
+
+impl<S> GetChannelWebhooks<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    pub fn new() -> WithPermissionRequired<WithTransaction<Self, S>, S> {
+        let action = Self {
+            phantom_data: PhantomData,
+        };
+
+        let action_with_transaction = WithTransaction::new(action);
+        let action_with_permission = WithPermissionRequired::new(action_with_transaction, Permission::user_admin());
+
+        action_with_permission
+    }
+}
+
+impl<S> Action<S> for GetChannelWebhooks<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    type Ret = ChannelWebhooksResult;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        debug!("Calling GetChannelWebhooks");
+
+        state
+            .get_webhook_deliveries()
+            .get_channel_webhooks()
+            .map_err(Error::Datastore)
+            .and_then(|res| ActionRes::new("getChannelWebhooks", ChannelWebhooksResult(res)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use serde_json::from_value;
+    use data::channels::Channels;
+    use model::actions::entity_actions::CreateEntity;
+    use model::actions::entity_actions::GetEntity;
+    use state::PubSubOps;
+    use test_common::random_identifier;
+    use test_common::with_state;
+    use test_common::MockState;
+
+    #[test]
+    fn test_channel_webhooks() {
+        with_state(|state| {
+            let channel = Channels::table(&format!("my_table_{}", random_identifier()));
+            let webhook: NewChannelWebhook = from_value(json!({
+                "channel": channel,
+                "url": "https://example.com/hooks/kakapo",
+                "secret": "shh",
+            })).unwrap();
+
+            let ChannelWebhookResult(added) = AddChannelWebhook::<MockState>::new(webhook.to_owned()).call(&state).unwrap().get_data();
+            assert_eq!(added.channel, channel);
+            assert_eq!(added.retry, RetryPolicy::default());
+
+            let result = AddChannelWebhook::<MockState>::new(webhook).call(&state);
+            assert_eq!(result.unwrap_err(), Error::AlreadyExists);
+
+            let ChannelWebhooksResult(webhooks) = GetChannelWebhooks::<MockState>::new().call(&state).unwrap().get_data();
+            assert!(webhooks.iter().any(|x| x.id == added.id));
+
+            state.get_pub_sub().publish(channel.to_owned(), "insertTableData".to_string(), &json!([{ "id": 1 }])).unwrap();
+
+            let deliveries = state.get_webhook_deliveries().claim_due_channel_deliveries(100, 60).unwrap();
+            let delivery = deliveries.iter().find(|x| x.url == added.url).unwrap();
+            let body: serde_json::Value = serde_json::from_str(&delivery.body).unwrap();
+            assert_eq!(body["channelWebhook"], json!(added.id));
+            assert_eq!(body["action"], json!("insertTableData"));
+            assert_eq!(delivery.signature, data::webhook::sign("shh", delivery.body.as_bytes()).unwrap());
+
+            let ChannelWebhookResult(removed) = RemoveChannelWebhook::<MockState>::new(added.id).call(&state).unwrap().get_data();
+            assert_eq!(removed.id, added.id);
+            assert_eq!(RemoveChannelWebhook::<MockState>::new(added.id).call(&state).unwrap_err(), Error::NotFound);
+        });
+    }
+
+    #[test]
+    fn test_webhook_secret() {
+        with_state(|state| {
+            let name = format!("my_webhook_{}", random_identifier());
+            let channel = Channels::table(&format!("my_table_{}", random_identifier()));
+            let webhook: data::Webhook = from_value(json!({
+                "name": name,
+                "description": "",
+                "url": "https://example.com/hooks/kakapo",
+                "secret": "hunter2",
+                "channels": [channel],
+            })).unwrap();
+            CreateEntity::<data::Webhook, MockState>::new(webhook).call(&state).unwrap();
+
+            // only the encrypted secret is kept
+            let GetEntityResult(stored) = GetEntity::<data::Webhook, MockState>::new(name.to_owned()).call(&state).unwrap().get_data();
+            assert_eq!(stored.secret, "");
+            assert!(stored.encrypted_secret.is_some());
+
+            state.get_pub_sub().publish(channel.to_owned(), "insertTableData".to_string(), &json!([{ "id": 1 }])).unwrap();
+
+            let deliveries = state.get_webhook_deliveries().claim_due_deliveries(100, 60).unwrap();
+            let delivery = deliveries.iter().find(|x| x.webhook_name == name).unwrap();
+            assert_eq!(delivery.signature, data::webhook::sign("hunter2", delivery.body.as_bytes()).unwrap());
+        });
+    }
+}
@@ -13,6 +13,16 @@ pub enum EntityError {
     InvalidState,
     #[fail(display = "No Columns found, every table must have at least one column")]
     NoColumns,
+    #[fail(display = "Can't rename, {} already exists", 0)]
+    NameTaken(String),
+    #[fail(display = "Invalid schedule: {}", 0)]
+    InvalidSchedule(String),
+    #[fail(display = "Invalid requirements: {}", 0)]
+    InvalidRequirements(String),
+    #[fail(display = "Invalid webhook: {}", 0)]
+    InvalidWebhook(String),
+    #[fail(display = "Invalid script: {}", 0)]
+    InvalidScript(String),
     #[fail(display = "An unknown error occurred")]
     Unknown,
 }
@@ -42,6 +42,11 @@ pub trait RawEntityTypes
 
     type Data;
     type NewData;
+
+    /// the metastore scope the entity is created in, `None` for the main scope
+    fn scope_name(&self) -> Option<&str> {
+        None
+    }
 }
 
 pub trait ConvertRaw<T> {
@@ -53,29 +58,32 @@ pub trait GenerateRaw<T> {
     fn tombstone(name: String, entity_id: i64, modified_by: i64) -> Self;
 }
 
-pub struct EntityRetrieverController<'a> {
-    pub conn: &'a Conn, //TODO: database specific, dependency inject here
+/// `D` is the connection to the metastore, see `DatabaseConnection`
+pub struct EntityRetrieverController<'a, D: 'a = Conn> {
+    pub conn: &'a D,
     pub claims: &'a Option<AuthClaims>,
     pub domain_name: &'a Option<String>,
 }
 
-pub struct EntityModifierController<'a> {
-    pub conn: &'a Conn, //TODO: database specific, dependency inject here
+pub struct EntityModifierController<'a, D: 'a = Conn> {
+    pub conn: &'a D,
     pub domain_conn: &'a Result<Box<Datastore>, DomainError>,
     pub claims: &'a Option<AuthClaims>,
     pub scripting: &'a Scripting,
     pub user_management: UserManagement<'a>, //Entities need to get access to user management for updating data
     pub domain_name: &'a Option<String>,
+    /// encrypts the secrets of the entities before they are stored, see `UpdateActionFunctions::prepare_entity`
+    pub secrets_key: &'a Option<String>,
 }
 
-impl<'a> EntityRetrieverController<'a> {
+impl<'a, D> EntityRetrieverController<'a, D> {
     pub fn get_domain_name(&self) -> Option<String> {
         self.domain_name
             .to_owned()
     }
 }
 
-impl<'a> EntityModifierController<'a> {
+impl<'a, D> EntityModifierController<'a, D> {
     pub fn get_role_name(&self) -> Option<String> {
         self.claims
             .to_owned()
@@ -96,6 +104,11 @@ pub trait RetrieverFunctions {
         where
             O: RawEntityTypes;
 
+    /// same as `get_all`, with the deleted values at their last version as well
+    fn get_all_including_deleted<O>(&self) -> Result<Vec<O>, EntityError>
+        where
+            O: RawEntityTypes;
+
     /// filters the values by the name, and returns the value if it exists
     /// if it doesn't exist it retuns none
     fn get_one<O>(&self, name: &str) -> Result<Option<O>, EntityError>
@@ -132,7 +145,14 @@ impl<'a> RetrieverFunctions for EntityRetrieverController<'a> {
         where
             O: RawEntityTypes,
     {
-        O::get_all(self)
+        O::get_all(self, false)
+    }
+
+    fn get_all_including_deleted<O>(&self) -> Result<Vec<O>, EntityError>
+        where
+            O: RawEntityTypes,
+    {
+        O::get_all(self, true)
     }
 
     fn get_one<O>(&self, name: &str) -> Result<Option<O>, EntityError>
@@ -147,6 +167,7 @@ impl<'a> ModifierFunctions for EntityModifierController<'a> {
     fn create<O>(&self, object: O) -> Result<Created<O>, EntityError>
         where O: RawEntityTypes + UpdateActionFunctions,
     {
+        let object = O::prepare_entity(self, object)?;
         O::create(self, object)
             .and_then(|res| {
                 debug!("result in table, now updating state: {:?}", res);
@@ -157,6 +178,7 @@ impl<'a> ModifierFunctions for EntityModifierController<'a> {
     fn upsert<O>(&self, object: O) -> Result<Upserted<O>, EntityError>
         where O: RawEntityTypes + UpdateActionFunctions
     {
+        let object = O::prepare_entity(self, object)?;
         O::upsert(self, object)
             .and_then(|res| {
                 debug!("result in table, now updating state: {:?}", res);
@@ -167,7 +189,9 @@ impl<'a> ModifierFunctions for EntityModifierController<'a> {
     fn update<O>(&self, name_object: (&str, O)) -> Result<Updated<O>, EntityError>
         where O: RawEntityTypes + UpdateActionFunctions
     {
-        O::update(self, name_object)
+        let (name, object) = name_object;
+        let object = O::prepare_entity(self, object)?;
+        O::update(self, (name, object))
             .and_then(|res| {
                 debug!("result in table, now updating state: {:?}", res);
                 res.update_state(self)
@@ -7,22 +7,31 @@ use model::entity::RawEntityTypes;
 
 use data;
 use data::permissions::Permission;
+use data::webhook::EncryptedSecret;
+use data::webhook::webhook_secret_name;
 use data::Named;
 
+use metastore::script_secrets::encrypt;
+
 use state::user_management::UserManagementOps;
 
 pub trait UpdateActionFunctions
-    where Self: UpdatePermissionFunctions
+    where Self: UpdatePermissionFunctions + Sized
 {
-    fn create_entity(controller: &EntityModifierController, new: &Self) -> Result<(), EntityError>;
-    fn update_entity(controller: &EntityModifierController, old_table: &Self, new_table: &Self) -> Result<(), EntityError>;
-    fn delete_entity(controller: &EntityModifierController, old: &Self) -> Result<(), EntityError>;
+    /// Checks the entity before it's created or updated, and changes what has to be before it's stored
+    fn prepare_entity<D>(controller: &EntityModifierController<D>, new: Self) -> Result<Self, EntityError> {
+        Ok(new)
+    }
+
+    fn create_entity<D>(controller: &EntityModifierController<D>, new: &Self) -> Result<(), EntityError>;
+    fn update_entity<D>(controller: &EntityModifierController<D>, old_table: &Self, new_table: &Self) -> Result<(), EntityError>;
+    fn delete_entity<D>(controller: &EntityModifierController<D>, old: &Self) -> Result<(), EntityError>;
 }
 
 pub trait UpdatePermissionFunctions {
-    fn create_permission(controller: &EntityModifierController, new: &Self) -> Result<(), EntityError>;
-    fn update_permission(controller: &EntityModifierController, old_table: &Self, new_table: &Self) -> Result<(), EntityError>;
-    fn delete_permission(controller: &EntityModifierController, old: &Self) -> Result<(), EntityError>;
+    fn create_permission<D>(controller: &EntityModifierController<D>, new: &Self) -> Result<(), EntityError>;
+    fn update_permission<D>(controller: &EntityModifierController<D>, old_table: &Self, new_table: &Self) -> Result<(), EntityError>;
+    fn delete_permission<D>(controller: &EntityModifierController<D>, old: &Self) -> Result<(), EntityError>;
 }
 
 /// This trait does something action specific after the database updates
@@ -33,14 +42,14 @@ pub trait UpdateState<T>
         Self: Sized,
         T: Debug + RawEntityTypes,
 {
-    fn update_state(self, state: &EntityModifierController) -> Result<Self, EntityError>;
+    fn update_state<D>(self, state: &EntityModifierController<D>) -> Result<Self, EntityError>;
 }
 
 //Created
 impl<T> UpdateState<T> for Created<T>
     where T: Debug + RawEntityTypes + UpdateActionFunctions
 {
-    fn update_state(self, state: &EntityModifierController) -> Result<Self, EntityError> {
+    fn update_state<D>(self, state: &EntityModifierController<D>) -> Result<Self, EntityError> {
         info!("new: {:?}", &self);
         let res = match &self {
             Created::Success { new } => {
@@ -58,7 +67,7 @@ impl<T> UpdateState<T> for Created<T>
 impl<T> UpdateState<T> for Upserted<T>
     where T: Debug + RawEntityTypes + UpdateActionFunctions
 {
-    fn update_state(self, state: &EntityModifierController) -> Result<Self, EntityError> {
+    fn update_state<D>(self, state: &EntityModifierController<D>) -> Result<Self, EntityError> {
         let res = match &self {
             Upserted::Update { old, new } => {
                 T::update_entity(&state, &old, &new)?;
@@ -78,7 +87,7 @@ impl<T> UpdateState<T> for Upserted<T>
 impl<T> UpdateState<T> for Updated<T>
     where T: Debug + RawEntityTypes + UpdateActionFunctions
 {
-    fn update_state(self, state: &EntityModifierController) -> Result<Self, EntityError> {
+    fn update_state<D>(self, state: &EntityModifierController<D>) -> Result<Self, EntityError> {
         let res = match &self {
             Updated::Success { old, new } => {
                 T::update_entity(&state, &old, &new)?;
@@ -95,7 +104,7 @@ impl<T> UpdateState<T> for Updated<T>
 impl<T> UpdateState<T> for Deleted<T>
     where T: Debug + RawEntityTypes + UpdateActionFunctions
 {
-    fn update_state(self, state: &EntityModifierController) -> Result<Self, EntityError> {
+    fn update_state<D>(self, state: &EntityModifierController<D>) -> Result<Self, EntityError> {
         let res = match &self {
             Deleted::Success { old } => {
                 T::delete_entity(&state, &old)?;
@@ -108,33 +117,126 @@ impl<T> UpdateState<T> for Deleted<T>
     }
 }
 
-///Nothing needed here
+/// the schedule has to be valid, otherwise the query would silently never run
+fn check_query_schedule(query: &data::DataQueryEntity) -> Result<(), EntityError> {
+    match &query.schedule {
+        Some(schedule) => schedule
+            .cron_schedule()
+            .map(|_| ())
+            .map_err(EntityError::InvalidSchedule),
+        None => Ok(()),
+    }
+}
+
+/// the secret isn't returned, so it can't be kept from the old version and has to be sent every time
+fn check_webhook(webhook: &data::Webhook) -> Result<(), EntityError> {
+    if !webhook.url.starts_with("http://") && !webhook.url.starts_with("https://") {
+        return Err(EntityError::InvalidWebhook(format!("{:?} is not an http or https url", &webhook.url)));
+    }
+
+    if webhook.secret.is_empty() {
+        return Err(EntityError::InvalidWebhook("the secret is required".to_string()));
+    }
+
+    if webhook.channels.is_empty() {
+        return Err(EntityError::InvalidWebhook("the webhook has to be subscribed to at least one channel".to_string()));
+    }
+
+    if webhook.retry.max_attempts == 0 {
+        return Err(EntityError::InvalidWebhook("maxAttempts has to be at least 1".to_string()));
+    }
+
+    Ok(())
+}
+
+///Nothing needed here besides checking the schedule
 ///maybe have stored procedures here for some speedup
 impl UpdateActionFunctions for data::DataQueryEntity {
-    fn create_entity(controller: &EntityModifierController, new: &data::DataQueryEntity) -> Result<(), EntityError> {
+    fn create_entity<D>(controller: &EntityModifierController<D>, new: &data::DataQueryEntity) -> Result<(), EntityError> {
+        check_query_schedule(new)
+    }
+
+    fn update_entity<D>(controller: &EntityModifierController<D>, old: &data::DataQueryEntity, new: &data::DataQueryEntity) -> Result<(), EntityError> {
+        check_query_schedule(new)
+    }
+
+    fn delete_entity<D>(controller: &EntityModifierController<D>, old: &data::DataQueryEntity) -> Result<(), EntityError> {
+        Ok(())
+    }
+}
+
+///Nothing needed here besides checking the webhook and encrypting its secret, the deliveries are queued when the messages are published
+impl UpdateActionFunctions for data::Webhook {
+    fn prepare_entity<D>(controller: &EntityModifierController<D>, new: data::Webhook) -> Result<data::Webhook, EntityError> {
+        check_webhook(&new)?;
+
+        let secrets_key = controller.secrets_key
+            .as_ref()
+            .ok_or_else(|| EntityError::InvalidWebhook("no secrets key is configured on the server to store the secret".to_string()))?;
+        let (nonce, ciphertext) = encrypt(secrets_key, &webhook_secret_name(&new.name), &new.secret)
+            .map_err(|err| EntityError::InternalError(err.to_string()))?;
+
+        Ok(data::Webhook {
+            secret: "".to_string(),
+            encrypted_secret: Some(EncryptedSecret { nonce, ciphertext }),
+            ..new
+        })
+    }
+
+    fn create_entity<D>(controller: &EntityModifierController<D>, new: &data::Webhook) -> Result<(), EntityError> {
         Ok(())
     }
 
-    fn update_entity(controller: &EntityModifierController, old: &data::DataQueryEntity, new: &data::DataQueryEntity) -> Result<(), EntityError> {
+    fn update_entity<D>(controller: &EntityModifierController<D>, old: &data::Webhook, new: &data::Webhook) -> Result<(), EntityError> {
         Ok(())
     }
 
-    fn delete_entity(controller: &EntityModifierController, old: &data::DataQueryEntity) -> Result<(), EntityError> {
+    fn delete_entity<D>(controller: &EntityModifierController<D>, old: &data::Webhook) -> Result<(), EntityError> {
         Ok(())
     }
 }
 
-///Nothing needed here
+///creates the view in the domain's database
 impl UpdateActionFunctions for data::View {
-    fn create_entity(controller: &EntityModifierController, new: &data::View) -> Result<(), EntityError> {
+    fn create_entity<D>(controller: &EntityModifierController<D>, new: &data::View) -> Result<(), EntityError> {
+        match controller.domain_conn {
+            Ok(conn) => {
+                conn.on_view_created(new)
+                    .map_err(|err| EntityError::InternalError(err.to_string()))?;
+            },
+            Err(err) => {
+                warn!("Could not get the controller for updating the state: {:?}", &err);
+            }
+        }
+
         Ok(())
     }
 
-    fn update_entity(controller: &EntityModifierController, old: &data::View, new: &data::View) -> Result<(), EntityError> {
+    fn update_entity<D>(controller: &EntityModifierController<D>, old: &data::View, new: &data::View) -> Result<(), EntityError> {
+        match controller.domain_conn {
+            Ok(conn) => {
+                conn.on_view_updated(old, new)
+                    .map_err(|err| EntityError::InternalError(err.to_string()))?;
+            },
+            Err(err) => {
+                warn!("Could not get the controller for updating the state: {:?}", &err);
+            }
+        }
+
         Ok(())
     }
 
-    fn delete_entity(controller: &EntityModifierController, old: &data::View) -> Result<(), EntityError> {
+    fn delete_entity<D>(controller: &EntityModifierController<D>, old: &data::View) -> Result<(), EntityError> {
+        match controller.domain_conn {
+            Ok(conn) => {
+                conn.on_view_deleted(old)
+                    .map_err(|err| EntityError::InternalError(err.to_string()))?;
+            },
+            Err(err) => {
+                warn!("Could not get the controller for updating the state: {:?}", &err);
+            }
+        }
+
         Ok(())
     }
 }
@@ -142,7 +244,7 @@ impl UpdateActionFunctions for data::View {
 //TODO: brind some othe the stuff from table here
 ///Nothing needed here
 impl UpdateActionFunctions for data::DataStoreEntity {
-    fn create_entity(controller: &EntityModifierController, new: &data::DataStoreEntity) -> Result<(), EntityError> {
+    fn create_entity<D>(controller: &EntityModifierController<D>, new: &data::DataStoreEntity) -> Result<(), EntityError> {
         match controller.domain_conn {
             Ok(conn) => {
                 conn.on_datastore_created(new)
@@ -156,7 +258,7 @@ impl UpdateActionFunctions for data::DataStoreEntity {
         Ok(())
     }
 
-    fn update_entity(controller: &EntityModifierController, old: &data::DataStoreEntity, new: &data::DataStoreEntity) -> Result<(), EntityError> {
+    fn update_entity<D>(controller: &EntityModifierController<D>, old: &data::DataStoreEntity, new: &data::DataStoreEntity) -> Result<(), EntityError> {
         match controller.domain_conn {
             Ok(conn) => {
                 conn.on_datastore_updated(old, new)
@@ -170,7 +272,7 @@ impl UpdateActionFunctions for data::DataStoreEntity {
         Ok(())
     }
 
-    fn delete_entity(controller: &EntityModifierController, old: &data::DataStoreEntity) -> Result<(), EntityError> {
+    fn delete_entity<D>(controller: &EntityModifierController<D>, old: &data::DataStoreEntity) -> Result<(), EntityError> {
         match controller.domain_conn {
             Ok(conn) => {
                 conn.on_datastore_deleted(old)
@@ -186,7 +288,7 @@ impl UpdateActionFunctions for data::DataStoreEntity {
 }
 
 impl UpdatePermissionFunctions for data::DataQueryEntity {
-    fn create_permission(controller: &EntityModifierController, new: &data::DataQueryEntity) -> Result<(), EntityError> {
+    fn create_permission<D>(controller: &EntityModifierController<D>, new: &data::DataQueryEntity) -> Result<(), EntityError> {
         /* TODO:...
         let permission_list = vec![
             Permission::read_entity::<Query>(new.my_name().to_owned()),
@@ -211,7 +313,7 @@ impl UpdatePermissionFunctions for data::DataQueryEntity {
         Ok(())
     }
 
-    fn update_permission(controller: &EntityModifierController, old: &data::DataQueryEntity, new: &data::DataQueryEntity) -> Result<(), EntityError> {
+    fn update_permission<D>(controller: &EntityModifierController<D>, old: &data::DataQueryEntity, new: &data::DataQueryEntity) -> Result<(), EntityError> {
         /* TODO:...
         let old_name = old.my_name().to_owned();
         let new_name = new.my_name().to_owned();
@@ -240,7 +342,7 @@ impl UpdatePermissionFunctions for data::DataQueryEntity {
         Ok(())
     }
 
-    fn delete_permission(controller: &EntityModifierController, old: &data::DataQueryEntity) -> Result<(), EntityError> {
+    fn delete_permission<D>(controller: &EntityModifierController<D>, old: &data::DataQueryEntity) -> Result<(), EntityError> {
         /* TODO:...
         let permission_list = vec![
             Permission::read_entity::<Query>(old.my_name().to_owned()),
@@ -260,7 +362,7 @@ impl UpdatePermissionFunctions for data::DataQueryEntity {
 
 ///mdodify table permissions in database here
 impl UpdatePermissionFunctions for data::DataStoreEntity {
-    fn create_permission(controller: &EntityModifierController, new: &data::DataStoreEntity) -> Result<(), EntityError> {
+    fn create_permission<D>(controller: &EntityModifierController<D>, new: &data::DataStoreEntity) -> Result<(), EntityError> {
         /* TODO:...
         let permission_list = vec![
             Permission::read_entity::<Table>(new.my_name().to_owned()),
@@ -286,7 +388,7 @@ impl UpdatePermissionFunctions for data::DataStoreEntity {
         Ok(())
     }
 
-    fn update_permission(controller: &EntityModifierController, old: &data::DataStoreEntity, new: &data::DataStoreEntity) -> Result<(), EntityError> {
+    fn update_permission<D>(controller: &EntityModifierController<D>, old: &data::DataStoreEntity, new: &data::DataStoreEntity) -> Result<(), EntityError> {
         /* TODO:...
         let old_name = old.my_name().to_owned();
         let new_name = new.my_name().to_owned();
@@ -319,7 +421,7 @@ impl UpdatePermissionFunctions for data::DataStoreEntity {
         Ok(())
     }
 
-    fn delete_permission(controller: &EntityModifierController, old: &data::DataStoreEntity) -> Result<(), EntityError> {
+    fn delete_permission<D>(controller: &EntityModifierController<D>, old: &data::DataStoreEntity) -> Result<(), EntityError> {
         /* TODO:...
         let permission_list = vec![
             Permission::read_entity::<Table>(old.my_name().to_owned()),
@@ -338,17 +440,32 @@ impl UpdatePermissionFunctions for data::DataStoreEntity {
     }
 }
 
+///Nothing needed here
+impl UpdatePermissionFunctions for data::Webhook {
+    fn create_permission<D>(controller: &EntityModifierController<D>, new: &data::Webhook) -> Result<(), EntityError> {
+        Ok(())
+    }
+
+    fn update_permission<D>(controller: &EntityModifierController<D>, old: &data::Webhook, new: &data::Webhook) -> Result<(), EntityError> {
+        Ok(())
+    }
+
+    fn delete_permission<D>(controller: &EntityModifierController<D>, old: &data::Webhook) -> Result<(), EntityError> {
+        Ok(())
+    }
+}
+
 ///Nothing needed here
 impl UpdatePermissionFunctions for data::View {
-    fn create_permission(controller: &EntityModifierController, new: &data::View) -> Result<(), EntityError> {
+    fn create_permission<D>(controller: &EntityModifierController<D>, new: &data::View) -> Result<(), EntityError> {
         Ok(())
     }
 
-    fn update_permission(controller: &EntityModifierController, old: &data::View, new: &data::View) -> Result<(), EntityError> {
+    fn update_permission<D>(controller: &EntityModifierController<D>, old: &data::View, new: &data::View) -> Result<(), EntityError> {
         Ok(())
     }
 
-    fn delete_permission(controller: &EntityModifierController, old: &data::View) -> Result<(), EntityError> {
+    fn delete_permission<D>(controller: &EntityModifierController<D>, old: &data::View) -> Result<(), EntityError> {
         Ok(())
     }
 }
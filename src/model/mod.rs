@@ -3,3 +3,4 @@ pub mod actions;
 pub mod entity;
 pub mod table;
 pub mod query;
+pub mod running_queries;
@@ -1,30 +1,175 @@
 
 use std::fmt::Debug;
+use std::sync::Arc;
+use std::time::Instant;
 
 use data;
+use data::claims::AuthClaims;
+use data::schedule::ScheduledRun;
+use model::running_queries::RunningQueries;
+use model::running_queries::RunningQuery;
 
 use state::StateFunctions;
 use state::authorization::AuthorizationOps;
 use state::ActionState;
+use state::ScheduledRuns;
+use state::scheduled_runs::ScheduledRunsOps;
+use state::QueryHistory;
+use state::query_history::QueryHistoryOps;
+use state::QuerySnapshots;
+use state::query_snapshots::QuerySnapshotsOps;
 use connection::executor::DomainError;
+use connection::metrics::DatabaseMetrics;
 
 use plugins::v1::DataQuery;
 use plugins::v1::DatastoreError;
+use plugins::v1::QueryLimit;
+use plugins::v1::QueryResult;
 
 pub struct QueryAction<'a> {
     pub conn: &'a Result<Box<DataQuery>, DomainError>,
+    pub scheduled_runs: ScheduledRuns<'a>,
+    pub query_history: QueryHistory<'a>,
+    pub query_snapshots: QuerySnapshots<'a>,
+    pub running_queries: Arc<RunningQueries>,
+    pub metrics: &'a DatabaseMetrics,
+    pub domain_name: &'a Option<String>,
+    pub claims: &'a Option<AuthClaims>,
+    /// when the caller stops waiting for the action, see `ActionWrapper::with_timeout`
+    pub deadline: Option<Instant>,
 }
 
 pub trait QueryActionOps {
-    fn run_query(&self, query: &data::DataQueryEntity, params: &serde_json::Value, format: &serde_json::Value) -> Result<serde_json::Value, DatastoreError>;
+    fn run_query(&self, query: &data::DataQueryEntity, params: &serde_json::Value, format: &serde_json::Value, limit: &QueryLimit) -> Result<QueryResult, DatastoreError>;
+    fn explain_query(&self, query: &data::DataQueryEntity, params: &serde_json::Value) -> Result<serde_json::Value, DatastoreError>;
+
+    /// A table schema for the rows of the query result
+    fn result_schema(&self, data: &serde_json::Value) -> Result<serde_json::Value, DatastoreError>;
+
+    /// Cancels a query running in this domain, returns none if there is no such query
+    fn cancel_query(&self, id: usize) -> Result<Option<RunningQuery>, DatastoreError>;
+
+    fn record_scheduled_run(&self, run: &ScheduledRun) -> Result<(), DatastoreError>;
+
+    fn scheduled_runs(&self, query: &data::DataQueryEntity, limit: usize) -> Result<Vec<ScheduledRun>, DatastoreError>;
+
+    fn query_history(&self, query_name: &str) -> Result<Vec<data::QueryVersion>, DatastoreError>;
+
+    fn query_version(&self, query_name: &str, version: i64) -> Result<Option<data::DataQueryEntity>, DatastoreError>;
+
+    fn save_snapshot(&self, snapshot: &data::QuerySnapshot) -> Result<(), DatastoreError>;
+
+    fn query_snapshot(&self, query_name: &str, name: &str) -> Result<Option<data::QuerySnapshot>, DatastoreError>;
+
+    /// without the data, newest first
+    fn query_snapshots(&self, query_name: &str) -> Result<Vec<data::QuerySnapshot>, DatastoreError>;
 }
 
 
 impl<'a> QueryActionOps for QueryAction<'a> {
-    fn run_query(&self, query: &data::DataQueryEntity, params: &serde_json::Value, format: &serde_json::Value) -> Result<serde_json::Value, DatastoreError>  {
+    fn run_query(&self, query: &data::DataQueryEntity, params: &serde_json::Value, format: &serde_json::Value, limit: &QueryLimit) -> Result<QueryResult, DatastoreError>  {
+        let conn = match self.conn {
+            Ok(conn) => conn,
+            Err(err) => return Err(err.into()),
+        };
+
+        // the database cancels the statement when the caller stops waiting, so that it doesn't hold the executor.
+        // It replaces the timeout of the datastore, which is only known to the connection
+        let timed_query;
+        let query = match self.deadline {
+            Some(deadline) => {
+                let now = Instant::now();
+                if now >= deadline {
+                    return Err(DatastoreError::Timeout);
+                }
+                let remaining = deadline - now;
+                let remaining = remaining.as_secs() * 1000 + u64::from(remaining.subsec_millis());
+                timed_query = data::DataQueryEntity {
+                    statement_timeout: Some(query.statement_timeout.map(|x| x.min(remaining)).unwrap_or(remaining)),
+                    ..query.to_owned()
+                };
+                &timed_query
+            },
+            None => query,
+        };
+
+        let backend_id = conn.backend_id()?;
+        let username = self.claims.to_owned().map(|x| x.get_username());
+        let running_query = RunningQueries::start(&self.running_queries, &query.name, self.domain_name.to_owned(), username, backend_id);
+
+        self.metrics.time_operation("query", || conn.query(query, params, format, limit))
+            .map_err(|err| match err {
+                DatastoreError::Timeout if running_query.is_canceled() => DatastoreError::Canceled,
+                err => err,
+            })
+    }
+
+    fn explain_query(&self, query: &data::DataQueryEntity, params: &serde_json::Value) -> Result<serde_json::Value, DatastoreError> {
         match self.conn {
-            Ok(conn) => conn.query(query, params, format),
+            Ok(conn) => self.metrics.time_operation("explain", || conn.explain(query, params)),
             Err(err) => Err(err.into())
         }
     }
-}
\ No newline at end of file
+
+    fn result_schema(&self, data: &serde_json::Value) -> Result<serde_json::Value, DatastoreError> {
+        match self.conn {
+            Ok(conn) => conn.result_schema(data),
+            Err(err) => Err(err.into())
+        }
+    }
+
+    fn cancel_query(&self, id: usize) -> Result<Option<RunningQuery>, DatastoreError> {
+        let conn = match self.conn {
+            Ok(conn) => conn,
+            Err(err) => return Err(err.into()),
+        };
+
+        // the backend can only be reached through a connection to the same database
+        let running_query = match self.running_queries.get(id) {
+            Some(running_query) => running_query,
+            None => return Ok(None),
+        };
+        if &running_query.domain != self.domain_name {
+            return Ok(None);
+        }
+
+        if !self.running_queries.mark_canceled(id) {
+            return Ok(None);
+        }
+
+        let canceled = conn.cancel(running_query.backend_id)?;
+        if !canceled {
+            warn!("could not cancel the backend {} running {:?}", running_query.backend_id, &running_query.query_name);
+        }
+
+        Ok(self.running_queries.get(id).or(Some(running_query)))
+    }
+
+    fn record_scheduled_run(&self, run: &ScheduledRun) -> Result<(), DatastoreError> {
+        self.scheduled_runs.record_run(run)
+    }
+
+    fn scheduled_runs(&self, query: &data::DataQueryEntity, limit: usize) -> Result<Vec<ScheduledRun>, DatastoreError> {
+        self.scheduled_runs.get_runs(&query.name, limit)
+    }
+
+    fn query_history(&self, query_name: &str) -> Result<Vec<data::QueryVersion>, DatastoreError> {
+        self.query_history.get_query_history(query_name)
+    }
+
+    fn query_version(&self, query_name: &str, version: i64) -> Result<Option<data::DataQueryEntity>, DatastoreError> {
+        self.query_history.get_query_version(query_name, version)
+    }
+
+    fn save_snapshot(&self, snapshot: &data::QuerySnapshot) -> Result<(), DatastoreError> {
+        self.query_snapshots.save_snapshot(snapshot)
+    }
+
+    fn query_snapshot(&self, query_name: &str, name: &str) -> Result<Option<data::QuerySnapshot>, DatastoreError> {
+        self.query_snapshots.get_snapshot(query_name, name)
+    }
+
+    fn query_snapshots(&self, query_name: &str) -> Result<Vec<data::QuerySnapshot>, DatastoreError> {
+        self.query_snapshots.get_snapshots(query_name)
+    }
+}
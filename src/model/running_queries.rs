@@ -0,0 +1,134 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::MutexGuard;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+
+/// A `RunQuery` that is being executed right now
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RunningQuery {
+    pub id: usize,
+    pub query_name: String,
+    pub domain: Option<String>,
+    pub username: Option<String>,
+    pub started_at: chrono::NaiveDateTime,
+    /// the database session running the statement, e.g. the postgres backend pid
+    #[serde(skip)]
+    pub backend_id: i64,
+    pub canceled: bool,
+}
+
+/// The queries in flight on all the executors, so that they can be listed and canceled from any of them
+#[derive(Debug, Default)]
+pub struct RunningQueries {
+    next_id: AtomicUsize,
+    queries: Mutex<BTreeMap<usize, RunningQuery>>,
+}
+
+impl RunningQueries {
+    fn lock(&self) -> MutexGuard<BTreeMap<usize, RunningQuery>> {
+        // every change to the map is a single insert, update or remove, so it is still usable after a panic
+        self.queries.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// The query is tracked until the returned guard is dropped
+    pub fn start(running_queries: &Arc<Self>, query_name: &str, domain: Option<String>, username: Option<String>, backend_id: i64) -> RunningQueryGuard {
+        let id = running_queries.next_id.fetch_add(1, Ordering::SeqCst) + 1;
+        let running_query = RunningQuery {
+            id,
+            query_name: query_name.to_owned(),
+            domain,
+            username,
+            started_at: chrono::Utc::now().naive_utc(),
+            backend_id,
+            canceled: false,
+        };
+
+        debug!("tracking running query {:?}", &running_query);
+        running_queries.lock().insert(id, running_query);
+
+        RunningQueryGuard {
+            id,
+            running_queries: running_queries.clone(),
+        }
+    }
+
+    pub fn get(&self, id: usize) -> Option<RunningQuery> {
+        self.lock().get(&id).cloned()
+    }
+
+    /// oldest first
+    pub fn list(&self) -> Vec<RunningQuery> {
+        self.lock().values().cloned().collect()
+    }
+
+    /// returns false if the query already finished
+    pub fn mark_canceled(&self, id: usize) -> bool {
+        match self.lock().get_mut(&id) {
+            Some(running_query) => {
+                running_query.canceled = true;
+                true
+            },
+            None => false,
+        }
+    }
+
+    fn finish(&self, id: usize) {
+        self.lock().remove(&id);
+    }
+}
+
+pub struct RunningQueryGuard {
+    id: usize,
+    running_queries: Arc<RunningQueries>,
+}
+
+impl RunningQueryGuard {
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    pub fn is_canceled(&self) -> bool {
+        self.running_queries
+            .get(self.id)
+            .map(|x| x.canceled)
+            .unwrap_or(false)
+    }
+}
+
+impl Drop for RunningQueryGuard {
+    fn drop(&mut self) {
+        self.running_queries.finish(self.id);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_running_queries() {
+        let running_queries = Arc::new(RunningQueries::default());
+
+        let first = RunningQueries::start(&running_queries, "foo", Some("main".to_string()), Some("Admin".to_string()), 42);
+        let second = RunningQueries::start(&running_queries, "bar", Some("main".to_string()), None, 43);
+
+        let listed = running_queries.list();
+        assert_eq!(listed.len(), 2);
+        assert_eq!(listed[0].query_name, "foo");
+        assert_eq!(listed[1].backend_id, 43);
+
+        assert!(!first.is_canceled());
+        assert!(running_queries.mark_canceled(first.id()));
+        assert!(first.is_canceled());
+        assert!(!second.is_canceled());
+
+        let first_id = first.id();
+        drop(first);
+        assert!(running_queries.get(first_id).is_none());
+        assert!(!running_queries.mark_canceled(first_id));
+        assert_eq!(running_queries.list().len(), 1);
+    }
+}
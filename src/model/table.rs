@@ -2,19 +2,36 @@
 use data;
 use data::Named;
 use data::error::DatastoreError;
+use data::key_case::KeyCase;
+use data::key_case::KeyMapping;
+use data::row_history;
+use data::row_history::RowChange;
+use data::row_history::RowHistoryEntry;
 
+use connection::executor::Conn;
 use connection::executor::DomainError;
+use connection::metrics::DatabaseMetrics;
 
 use plugins::v1::Datastore;
 
+use state::RowHistory;
+use state::row_history::RowHistoryOps;
 
-pub struct DatastoreAction<'a> {
+
+/// `D` is the connection to the metastore, which keeps the row history, see `DatabaseConnection`
+pub struct DatastoreAction<'a, D: 'a = Conn> {
     pub conn: &'a Result<Box<Datastore>, DomainError>,
+    pub key_case: &'a KeyCase,
+    /// the datastore calls are timed by kind, the row history isn't part of them
+    pub metrics: &'a DatabaseMetrics,
+    pub row_history: RowHistory<'a, D>,
 }
 
 pub trait DatastoreActionOps {
     fn query(&self, table: &data::DataStoreEntity, query: &serde_json::Value) -> Result<serde_json::Value, DatastoreError>;
 
+    fn stream_rows(&self, table: &data::DataStoreEntity, query: &serde_json::Value, batch_size: usize, on_batch: &mut FnMut(serde_json::Value) -> Result<(), DatastoreError>) -> Result<(), DatastoreError>;
+
     fn insert_row(&self, table: &data::DataStoreEntity, data: &serde_json::Value, fail_on_duplicate: bool) -> Result<serde_json::Value, DatastoreError>;
 
     fn upsert_row(&self, table: &data::DataStoreEntity, data: &serde_json::Value) -> Result<serde_json::Value, DatastoreError>;
@@ -22,6 +39,22 @@ pub trait DatastoreActionOps {
     fn update_row(&self, table: &data::DataStoreEntity, keyed_data: &serde_json::Value, fail_on_not_found: bool) -> Result<serde_json::Value, DatastoreError>;
 
     fn delete_row(&self, table: &data::DataStoreEntity, keys: &serde_json::Value, fail_on_not_found: bool) -> Result<serde_json::Value, DatastoreError>;
+
+    fn update_rows_where(&self, table: &data::DataStoreEntity, filtered_values: &serde_json::Value) -> Result<serde_json::Value, DatastoreError>;
+
+    fn delete_rows_where(&self, table: &data::DataStoreEntity, filter: &serde_json::Value) -> Result<serde_json::Value, DatastoreError>;
+
+    fn delete_all_rows(&self, table: &data::DataStoreEntity) -> Result<serde_json::Value, DatastoreError>;
+
+    fn row_history(&self, table: &data::DataStoreEntity, key: &serde_json::Value) -> Result<Vec<RowHistoryEntry>, DatastoreError>;
+
+    fn preview_update(&self, old: &data::DataStoreEntity, new: &data::DataStoreEntity) -> Result<serde_json::Value, DatastoreError>;
+
+    fn copy_rows(&self, source: &data::DataStoreEntity, target: &data::DataStoreEntity) -> Result<usize, DatastoreError>;
+
+    fn purge_expired_rows(&self, table: &data::DataStoreEntity, batch_size: usize) -> Result<usize, DatastoreError>;
+
+    fn refresh_view(&self, view: &data::View) -> Result<(), DatastoreError>;
 }
 
 impl From<&DomainError> for DatastoreError {
@@ -31,44 +64,182 @@ impl From<&DomainError> for DatastoreError {
             DomainError::Unknown => DatastoreError::Unknown,
             DomainError::DatastoreNotAvailable => DatastoreError::NotSupported,
             DomainError::QueryNotAvailable => DatastoreError::NotSupported,
+            DomainError::Unavailable => DatastoreError::Unavailable,
         }
     }
 }
 
-impl<'a> DatastoreActionOps for DatastoreAction<'a> {
+impl<'a, D> DatastoreAction<'a, D>
+    where RowHistory<'a, D>: RowHistoryOps,
+{
+    fn get_key_mapping(&self, table: &data::DataStoreEntity) -> Result<Option<KeyMapping>, DatastoreError> {
+        match self.key_case {
+            KeyCase::Preserve => Ok(None),
+            key_case => KeyMapping::for_schema(key_case, &table.schema).map(Some),
+        }
+    }
+
+    /// maps the client's column names to the stored ones, runs the op and maps the result back
+    fn with_key_mapping<F>(&self, table: &data::DataStoreEntity, input: &serde_json::Value, f: F) -> Result<serde_json::Value, DatastoreError>
+        where F: FnOnce(&Box<Datastore>, &serde_json::Value) -> Result<serde_json::Value, DatastoreError>
+    {
+        let conn = match self.conn {
+            Ok(conn) => conn,
+            Err(err) => return Err(err.into()),
+        };
+
+        match self.get_key_mapping(table)? {
+            None => f(conn, input),
+            Some(mapping) => {
+                let input = mapping.from_client(input.to_owned());
+                f(conn, &input).map(|res| mapping.to_client(res))
+            },
+        }
+    }
+
+    /// only kept for the tables with `auditRows`, the data is recorded with the stored column names
+    fn record_changes(&self, table: &data::DataStoreEntity, change: RowChange, data: &serde_json::Value) -> Result<(), DatastoreError> {
+        if !table.audit_rows {
+            return Ok(());
+        }
+
+        let key_columns = row_history::key_columns(&table.schema);
+        let deltas = row_history::row_deltas(change, data, &key_columns);
+        self.row_history.record_changes(table.my_name(), deltas)
+    }
+}
+
+impl<'a, D> DatastoreActionOps for DatastoreAction<'a, D>
+    where RowHistory<'a, D>: RowHistoryOps,
+{
     fn query(&self, table: &data::DataStoreEntity, query: &serde_json::Value) -> Result<serde_json::Value, DatastoreError> {
-        match self.conn {
-            Ok(conn) => conn.retrieve(table, query),
-            Err(err) => Err(err.into())
+        self.with_key_mapping(table, query, |conn, query| self.metrics.time_operation("retrieve", || conn.retrieve(table, query)))
+    }
+
+    fn stream_rows(&self, table: &data::DataStoreEntity, query: &serde_json::Value, batch_size: usize, on_batch: &mut FnMut(serde_json::Value) -> Result<(), DatastoreError>) -> Result<(), DatastoreError> {
+        let conn = match self.conn {
+            Ok(conn) => conn,
+            Err(err) => return Err(err.into()),
+        };
+
+        // every batch has to be mapped back on its own
+        match self.get_key_mapping(table)? {
+            None => self.metrics.time_operation("stream", || conn.stream(table, query, batch_size, on_batch)),
+            Some(mapping) => {
+                let query = mapping.from_client(query.to_owned());
+                self.metrics.time_operation("stream", || conn.stream(table, &query, batch_size, &mut |rows| on_batch(mapping.to_client(rows))))
+            },
         }
     }
 
     fn insert_row(&self, table: &data::DataStoreEntity, data: &serde_json::Value, fail_on_duplicate: bool) -> Result<serde_json::Value, DatastoreError> {
-        match self.conn {
-            Ok(conn) => conn.insert(table, data),
-            Err(err) => Err(err.into())
-        }
+        self.with_key_mapping(table, data, |conn, data| {
+            let res = self.metrics.time_operation("insert", || conn.insert(table, data))?;
+            self.record_changes(table, RowChange::Insert, &res)?;
+            Ok(res)
+        })
     }
 
     fn upsert_row(&self, table: &data::DataStoreEntity, data: &serde_json::Value) -> Result<serde_json::Value, DatastoreError> {
+        self.with_key_mapping(table, data, |conn, data| {
+            let res = self.metrics.time_operation("upsert", || conn.upsert(table, data))?;
+            self.record_changes(table, RowChange::Insert, &res["inserted"])?;
+            self.record_changes(table, RowChange::Update, &res["updated"])?;
+            Ok(res)
+        })
+    }
+
+    fn update_row(&self, table: &data::DataStoreEntity, keyed_data: &serde_json::Value, fail_on_not_found: bool) -> Result<serde_json::Value, DatastoreError> {
+        self.with_key_mapping(table, keyed_data, |conn, keyed_data| {
+            let res = self.metrics.time_operation("update", || conn.update(table, keyed_data))?;
+            self.record_changes(table, RowChange::Update, &res)?;
+            Ok(res)
+        })
+    }
+
+    fn delete_row(&self, table: &data::DataStoreEntity, keys: &serde_json::Value, fail_on_not_found: bool) -> Result<serde_json::Value, DatastoreError> {
+        self.with_key_mapping(table, keys, |conn, keys| {
+            let res = self.metrics.time_operation("delete", || conn.delete(table, keys))?;
+            self.record_changes(table, RowChange::Delete, &res)?;
+            Ok(res)
+        })
+    }
+
+    fn update_rows_where(&self, table: &data::DataStoreEntity, filtered_values: &serde_json::Value) -> Result<serde_json::Value, DatastoreError> {
+        self.with_key_mapping(table, filtered_values, |conn, filtered_values| {
+            let res = self.metrics.time_operation("updateWhere", || conn.update_where(table, filtered_values))?;
+            self.record_changes(table, RowChange::Update, &res)?;
+            Ok(res)
+        })
+    }
+
+    fn delete_rows_where(&self, table: &data::DataStoreEntity, filter: &serde_json::Value) -> Result<serde_json::Value, DatastoreError> {
+        self.with_key_mapping(table, filter, |conn, filter| {
+            let res = self.metrics.time_operation("deleteWhere", || conn.delete_where(table, filter))?;
+            self.record_changes(table, RowChange::Delete, &res)?;
+            Ok(res)
+        })
+    }
+
+    fn delete_all_rows(&self, table: &data::DataStoreEntity) -> Result<serde_json::Value, DatastoreError> {
+        self.with_key_mapping(table, &json!({}), |conn, _| {
+            let res = self.metrics.time_operation("deleteAll", || conn.delete_all(table))?;
+            self.record_changes(table, RowChange::Delete, &res)?;
+            Ok(res)
+        })
+    }
+
+    fn row_history(&self, table: &data::DataStoreEntity, key: &serde_json::Value) -> Result<Vec<RowHistoryEntry>, DatastoreError> {
+        let mapping = self.get_key_mapping(table)?;
+        let key = match &mapping {
+            None => key.to_owned(),
+            Some(mapping) => mapping.from_client(key.to_owned()),
+        };
+
+        let entries = self.row_history.get_row_history(table.my_name(), &key)?;
+
+        // the key and the rows are mapped on their own, a column could be called `key`
+        match mapping {
+            None => Ok(entries),
+            Some(mapping) => Ok(entries
+                .into_iter()
+                .map(|entry| RowHistoryEntry {
+                    key: mapping.to_client(entry.key),
+                    old_data: entry.old_data.map(|x| mapping.to_client(x)),
+                    new_data: entry.new_data.map(|x| mapping.to_client(x)),
+                    ..entry
+                })
+                .collect()),
+        }
+    }
+
+    /// the schema uses the stored column names, so there is nothing to map
+    fn preview_update(&self, old: &data::DataStoreEntity, new: &data::DataStoreEntity) -> Result<serde_json::Value, DatastoreError> {
         match self.conn {
-            Ok(conn) => conn.upsert(table, data),
-            Err(err) => Err(err.into())
+            Ok(conn) => conn.preview_datastore_update(old, new),
+            Err(err) => Err(err.into()),
         }
     }
 
-    fn update_row(&self, table: &data::DataStoreEntity, keyed_data: &serde_json::Value, fail_on_not_found: bool) -> Result<serde_json::Value, DatastoreError> {
+    /// the rows aren't recorded in the row history, the copy is a new table
+    fn copy_rows(&self, source: &data::DataStoreEntity, target: &data::DataStoreEntity) -> Result<usize, DatastoreError> {
         match self.conn {
-            Ok(conn) => conn.update(table, keyed_data),
-            Err(err) => Err(err.into())
+            Ok(conn) => self.metrics.time_operation("copy", || conn.copy_datastore_data(source, target)),
+            Err(err) => Err(err.into()),
         }
     }
 
-    fn delete_row(&self, table: &data::DataStoreEntity, keys: &serde_json::Value, fail_on_not_found: bool) -> Result<serde_json::Value, DatastoreError> {
+    fn purge_expired_rows(&self, table: &data::DataStoreEntity, batch_size: usize) -> Result<usize, DatastoreError> {
         match self.conn {
-            Ok(conn) => conn.delete(table, keys),
-            Err(err) => Err(err.into())
+            Ok(conn) => self.metrics.time_operation("purgeExpired", || conn.purge_expired_data(table, batch_size)),
+            Err(err) => Err(err.into()),
         }
+    }
 
+    fn refresh_view(&self, view: &data::View) -> Result<(), DatastoreError> {
+        match self.conn {
+            Ok(conn) => self.metrics.time_operation("refreshView", || conn.refresh_view(view)),
+            Err(err) => Err(err.into()),
+        }
     }
-}
\ No newline at end of file
+}
@@ -4,6 +4,9 @@ use serde::de::DeserializeOwned;
 
 pub use data::DataStoreEntity;
 pub use data::DataQueryEntity;
+pub use data::QueryLimit;
+pub use data::QueryResult;
+pub use data::View;
 pub use data::error::DatastoreError;
 
 pub trait DomainBuilder
@@ -18,8 +21,10 @@ pub trait Domain
         Self: Send + Sync,
 {
     fn domain_type(&self) -> &'static str;
-    fn connect_datastore(&self) -> Option<Box<Datastore>>;
-    fn connect_query(&self) -> Option<Box<DataQuery>>;
+    /// `NotSupported` if the domain has no datastore, `Unavailable` if no connection could be
+    /// checked out in time
+    fn connect_datastore(&self) -> Result<Box<Datastore>, DatastoreError>;
+    fn connect_query(&self) -> Result<Box<DataQuery>, DatastoreError>;
 }
 
 type Rows = serde_json::Value;
@@ -54,9 +59,53 @@ pub trait Datastore:
     fn update(&self, data_store: &DataStoreEntity, key_values: &KeyValues) -> Result<Dataset, DatastoreError>;
     fn delete(&self, data_store: &DataStoreEntity, keys: &Keys) -> Result<Dataset, DatastoreError>;
 
+    /// Same as `update` and `delete`, but for all the rows matching a filter instead of by key
+    /// the changed rows are returned
+    fn update_where(&self, data_store: &DataStoreEntity, filtered_values: &serde_json::Value) -> Result<Dataset, DatastoreError>;
+    fn delete_where(&self, data_store: &DataStoreEntity, filter: &serde_json::Value) -> Result<Dataset, DatastoreError>;
+
+    /// Deletes every row of the table, the deleted rows are returned
+    fn delete_all(&self, data_store: &DataStoreEntity) -> Result<Dataset, DatastoreError>;
+
+    /// Same as `retrieve` but the rows are passed to `on_batch` in chunks of at most `batch_size`
+    /// instead of being collected, for tables that are too big to keep in memory
+    fn stream(&self, data_store: &DataStoreEntity, query: &serde_json::Value, batch_size: usize, on_batch: &mut FnMut(Dataset) -> Result<(), DatastoreError>) -> Result<(), DatastoreError>;
+
+    /// The changes `on_datastore_updated` would make, without making them
+    fn preview_datastore_update(&self, old: &DataStoreEntity, new: &DataStoreEntity) -> Result<Dataset, DatastoreError>;
+
+    /// Copies all the rows of `source` into `target`, both have the same columns, returns the number of copied rows
+    fn copy_datastore_data(&self, source: &DataStoreEntity, target: &DataStoreEntity) -> Result<usize, DatastoreError>;
+
+    /// Deletes up to `batch_size` rows that are older than the retention policy of the table, returns the number of deleted rows
+    fn purge_expired_data(&self, table: &DataStoreEntity, batch_size: usize) -> Result<usize, DatastoreError>;
+
     fn on_datastore_created(&self, new: &DataStoreEntity) -> Result<(), DatastoreError>;
     fn on_datastore_updated(&self, old: &DataStoreEntity, new: &DataStoreEntity) -> Result<(), DatastoreError>;
     fn on_datastore_deleted(&self, old: &DataStoreEntity) -> Result<(), DatastoreError>;
+
+    /// Views are read only tables defined by a statement, they can be queried like any other table
+    fn on_view_created(&self, new: &View) -> Result<(), DatastoreError>;
+    fn on_view_updated(&self, old: &View, new: &View) -> Result<(), DatastoreError>;
+    fn on_view_deleted(&self, old: &View) -> Result<(), DatastoreError>;
+
+    /// Runs the statement of a materialized view again
+    fn refresh_view(&self, view: &View) -> Result<(), DatastoreError>;
+
+    /// Used by `StateFunctions::transaction` so that the changes to the data are rolled back with the
+    /// metastore's. A transaction that is started inside of another one should be a savepoint,
+    /// rolling it back only undoes the changes made since it was started
+    fn begin_transaction(&self) -> Result<(), DatastoreError> {
+        Ok(())
+    }
+
+    fn commit_transaction(&self) -> Result<(), DatastoreError> {
+        Ok(())
+    }
+
+    fn rollback_transaction(&self) -> Result<(), DatastoreError> {
+        Ok(())
+    }
 }
 
 type QueryParams = serde_json::Value;
@@ -66,6 +115,18 @@ pub trait DataQuery
     where
         Self: Send
 {
-    fn query(&self, query: &DataQueryEntity, query_params: &QueryParams, format: &QueryFormat) -> Result<Dataset, DatastoreError>; //TODO: rename to DatasetError
+    fn query(&self, query: &DataQueryEntity, query_params: &QueryParams, format: &QueryFormat, limit: &QueryLimit) -> Result<QueryResult, DatastoreError>; //TODO: rename to DatasetError
+
+    /// The plan of the query with the given params, the query itself is not run
+    fn explain(&self, query: &DataQueryEntity, query_params: &QueryParams) -> Result<serde_json::Value, DatastoreError>;
+
+    /// A table schema that can hold the rows of a query result, as used by `DataStoreEntity`
+    fn result_schema(&self, data: &serde_json::Value) -> Result<serde_json::Value, DatastoreError>;
+
+    /// Identifies the database session the queries of this connection run in
+    fn backend_id(&self) -> Result<i64, DatastoreError>;
+
+    /// Cancels the statement running in the given session, returns false if nothing was running
+    fn cancel(&self, backend_id: i64) -> Result<bool, DatastoreError>;
 }
 
@@ -0,0 +1,132 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::process::Command;
+
+use data::ScriptLanguage;
+use scripting::error::ScriptError;
+
+/// where the directory of the script is mounted in the container, read only
+const CONTAINER_SCRIPT_DIR: &'static str = "/script";
+/// where the file with the params, and later the output, is mounted in the container
+const CONTAINER_IO_FILE: &'static str = "/io/params.json";
+
+/// Runs the scripts in short lived containers instead of on the host, for the deployments that
+/// can't trust the scripts with the server process
+///
+/// A container only sees the directory of its script and the params file, it has no network
+/// unless `network` is set
+#[derive(Clone, Debug)]
+pub struct ContainerConfig {
+    /// `docker` or `podman`, they take the same arguments
+    pub engine: String,
+    /// has to have `python3` in the path
+    pub python_image: String,
+    /// has to have `qjs` in the path
+    pub javascript_image: String,
+    pub network: bool,
+}
+
+impl ContainerConfig {
+    pub fn new(engine: &str, python_image: &str, javascript_image: &str) -> Self {
+        Self {
+            engine: engine.to_string(),
+            python_image: python_image.to_string(),
+            javascript_image: javascript_image.to_string(),
+            network: false,
+        }
+    }
+
+    pub fn image(&self, language: &ScriptLanguage) -> &str {
+        match language {
+            ScriptLanguage::Python => &self.python_image,
+            ScriptLanguage::JavaScript => &self.javascript_image,
+        }
+    }
+
+    /// The `run` command of the engine, running `interpreter` on the script in the image
+    ///
+    /// The values of the environment variables are set on the engine rather than passed as
+    /// arguments, so that the secrets don't show up in the process list
+    pub fn command(
+        &self,
+        image: &str,
+        interpreter: &str,
+        interpreter_args: &[&str],
+        script_dir: &Path,
+        file_name: &str,
+        io_file: &Path,
+        env: &HashMap<String, String>,
+    ) -> Result<Command, ScriptError> {
+        // the engine needs absolute paths to mount them
+        let script_dir = script_dir.canonicalize()
+            .map_err(|err| ScriptError::IOError(err.to_string()))?;
+        let io_file = io_file.canonicalize()
+            .map_err(|err| ScriptError::IOError(err.to_string()))?;
+
+        let mut env_names: Vec<&str> = env.keys().map(|x| x.as_str()).collect();
+        env_names.sort();
+
+        let mut command = Command::new(&self.engine);
+        command
+            .args(self.run_args(image, interpreter, interpreter_args, &script_dir, file_name, &io_file, &env_names))
+            .envs(env);
+
+        Ok(command)
+    }
+
+    fn run_args(
+        &self,
+        image: &str,
+        interpreter: &str,
+        interpreter_args: &[&str],
+        script_dir: &Path,
+        file_name: &str,
+        io_file: &Path,
+        env_names: &[&str],
+    ) -> Vec<String> {
+        let mut args = vec![
+            "run".to_string(),
+            "--rm".to_string(),
+            format!("--volume={}:{}:ro", script_dir.display(), CONTAINER_SCRIPT_DIR),
+            format!("--volume={}:{}", io_file.display(), CONTAINER_IO_FILE),
+            format!("--workdir={}", CONTAINER_SCRIPT_DIR),
+        ];
+
+        if !self.network {
+            args.push("--network=none".to_string());
+        }
+
+        args.extend(env_names.iter().map(|name| format!("--env={}", name)));
+
+        args.push(image.to_string());
+        args.push(interpreter.to_string());
+        args.extend(interpreter_args.iter().map(|x| x.to_string()));
+        args.push(file_name.to_string());
+        args.push(CONTAINER_IO_FILE.to_string());
+
+        args
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_run_args() {
+        let mut config = ContainerConfig::new("podman", "python:3-slim", "quickjs");
+        let args = config.run_args(
+            "python:3-slim", "python3", &["-u"], Path::new("/scripts/foo"), "script.py", Path::new("/tmp/params"), &["API_TOKEN"]);
+
+        assert_eq!(args, vec![
+            "run", "--rm",
+            "--volume=/scripts/foo:/script:ro", "--volume=/tmp/params:/io/params.json", "--workdir=/script",
+            "--network=none", "--env=API_TOKEN",
+            "python:3-slim", "python3", "-u", "script.py", "/io/params.json",
+        ]);
+
+        config.network = true;
+        let args = config.run_args("quickjs", "qjs", &[], Path::new("/scripts/foo"), "script.js", Path::new("/tmp/params"), &[]);
+        assert!(!args.contains(&"--network=none".to_string()));
+    }
+}
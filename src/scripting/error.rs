@@ -1,14 +1,196 @@
+use std::fmt;
 
+/// most lines of the stderr kept in the diagnostics, from the end
+pub const STDERR_EXCERPT_LINES: usize = 20;
+/// most characters of the stderr kept in the diagnostics, from the end
+pub const STDERR_EXCERPT_CHARS: usize = 4000;
 
+const PYTHON_TRACEBACK: &'static str = "Traceback (most recent call last):";
 
-#[derive(Debug, Fail, PartialEq, Eq)]
+#[derive(Debug, Clone, Fail, PartialEq, Eq)]
 pub enum ScriptError {
     #[fail(display = "io error: {:?}", 0)]
     IOError(String),
     #[fail(display = "could not execute {:?}", 0)]
     ExecuteError(String),
-    #[fail(display = "runtime error: {:?}", 0)]
-    RuntimeError(String),
+    #[fail(display = "runtime error: {}", 0)]
+    RuntimeError(ScriptDiagnostics),
+    #[fail(display = "no workers are running the script jobs")]
+    NoJobWorkers,
+    #[fail(display = "the job {} hasn't finished yet", 0)]
+    JobNotFinished(String),
+    #[fail(display = "could not use the requirements: {}", 0)]
+    RequirementsError(String),
+    #[fail(display = "could not install the requirements: {}", 0)]
+    InstallError(ScriptDiagnostics),
     #[fail(display = "An unknown error occurred")]
     Unknown,
 }
+
+impl ScriptError {
+    /// what went wrong in the process, for the `details` of the error responses
+    pub fn diagnostics(&self) -> Option<&ScriptDiagnostics> {
+        match self {
+            ScriptError::RuntimeError(diagnostics) => Some(diagnostics),
+            ScriptError::InstallError(diagnostics) => Some(diagnostics),
+            _ => None,
+        }
+    }
+}
+
+/// Why a process of a script failed, taken from its exit code and stderr
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScriptDiagnostics {
+    /// `None` if the process was killed by a signal
+    pub exit_code: Option<i32>,
+    /// the end of the stderr, see `STDERR_EXCERPT_LINES`
+    pub stderr: String,
+    /// the line of the script the error was raised on, if the stderr has a traceback
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub line: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub traceback: Option<String>,
+}
+
+impl ScriptDiagnostics {
+    /// `file_name` is the name of the script file, the frames of the other files are not the script's lines
+    /// `None` for the processes that don't run the script, e.g. pip
+    pub fn new(exit_code: Option<i32>, stderr: &str, file_name: Option<&str>) -> Self {
+        Self {
+            exit_code,
+            stderr: stderr_excerpt(stderr),
+            line: file_name.and_then(|x| error_line(stderr, x)),
+            traceback: traceback(stderr),
+        }
+    }
+
+    /// the last line of the stderr, usually the error itself
+    pub fn message(&self) -> Option<&str> {
+        self.stderr
+            .lines()
+            .map(|x| x.trim())
+            .filter(|x| !x.is_empty())
+            .last()
+    }
+}
+
+impl fmt::Display for ScriptDiagnostics {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.exit_code {
+            Some(exit_code) => write!(f, "exited with code {}", exit_code)?,
+            None => write!(f, "killed by a signal")?,
+        };
+
+        if let Some(line) = self.line {
+            write!(f, " on line {}", line)?;
+        }
+
+        if let Some(message) = self.message() {
+            write!(f, ": {}", message)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn stderr_excerpt(stderr: &str) -> String {
+    let lines: Vec<&str> = stderr.trim_end().lines().collect();
+    let excerpt = lines[lines.len().saturating_sub(STDERR_EXCERPT_LINES)..].join("\n");
+
+    match excerpt.char_indices().rev().nth(STDERR_EXCERPT_CHARS - 1) {
+        Some((start, _)) => excerpt[start..].to_string(),
+        None => excerpt,
+    }
+}
+
+/// the frames of the script file, python has `File "/path/script.py", line 3`, quickjs has `at f (script.js:3)`
+fn error_line(stderr: &str, file_name: &str) -> Option<u32> {
+    let python_frame = format!("{}\", line ", file_name);
+    let python_line = stderr
+        .lines()
+        .filter_map(|line| {
+            let start = line.find(&python_frame)? + python_frame.len();
+            leading_number(&line[start..])
+        })
+        .last(); // the innermost frame is the last one
+
+    let javascript_frame = format!("{}:", file_name);
+    let javascript_line = || stderr
+        .lines()
+        .filter_map(|line| {
+            let start = line.find(&javascript_frame)? + javascript_frame.len();
+            leading_number(&line[start..])
+        })
+        .next(); // the innermost frame is the first one
+
+    python_line.or_else(javascript_line)
+}
+
+fn leading_number(text: &str) -> Option<u32> {
+    let digits: String = text.chars().take_while(|x| x.is_ascii_digit()).collect();
+    digits.parse().ok()
+}
+
+/// the last python traceback, or the javascript error with its stack
+fn traceback(stderr: &str) -> Option<String> {
+    if let Some(start) = stderr.rfind(PYTHON_TRACEBACK) {
+        return Some(stderr[start..].trim_end().to_string());
+    }
+
+    let lines: Vec<&str> = stderr.lines().collect();
+    let first_frame = lines.iter().position(|x| x.trim_start().starts_with("at "))?;
+    let frames = lines[first_frame..]
+        .iter()
+        .take_while(|x| x.trim_start().starts_with("at "))
+        .count();
+
+    // the error itself is on the line before the stack
+    let start = first_frame.saturating_sub(1);
+    Some(lines[start..first_frame + frames].join("\n"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_python_diagnostics() {
+        let stderr = r#"loading the params
+Traceback (most recent call last):
+  File "script.py", line 7, in <module>
+    main()
+  File "script.py", line 4, in main
+    print(undefined_name)
+NameError: name 'undefined_name' is not defined
+"#;
+        let diagnostics = ScriptDiagnostics::new(Some(1), stderr, Some("script.py"));
+        assert_eq!(diagnostics.line, Some(4));
+        assert!(diagnostics.traceback.as_ref().unwrap().starts_with(PYTHON_TRACEBACK));
+        assert!(!diagnostics.traceback.as_ref().unwrap().contains("loading the params"));
+        assert_eq!(diagnostics.message(), Some("NameError: name 'undefined_name' is not defined"));
+        assert_eq!(diagnostics.to_string(), "exited with code 1 on line 4: NameError: name 'undefined_name' is not defined");
+    }
+
+    #[test]
+    fn test_javascript_diagnostics() {
+        let stderr = "ReferenceError: 'foo' is not defined\n    at main (script.js:3)\n    at <eval> (script.js:6)\n";
+        let diagnostics = ScriptDiagnostics::new(Some(1), stderr, Some("script.js"));
+        assert_eq!(diagnostics.line, Some(3));
+        assert_eq!(diagnostics.traceback, Some(stderr.trim_end().to_string()));
+    }
+
+    #[test]
+    fn test_stderr_excerpt() {
+        let stderr: Vec<String> = (0..100).map(|x| format!("line {}", x)).collect();
+        let diagnostics = ScriptDiagnostics::new(None, &stderr.join("\n"), Some("script.py"));
+        assert_eq!(diagnostics.stderr.lines().count(), STDERR_EXCERPT_LINES);
+        assert_eq!(diagnostics.stderr.lines().last(), Some("line 99"));
+        assert_eq!(diagnostics.line, None);
+        assert_eq!(diagnostics.traceback, None);
+        assert_eq!(diagnostics.to_string(), "killed by a signal: line 99");
+
+        let long_line = "x".repeat(STDERR_EXCERPT_CHARS * 2);
+        assert_eq!(stderr_excerpt(&long_line).len(), STDERR_EXCERPT_CHARS);
+    }
+}
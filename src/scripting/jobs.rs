@@ -0,0 +1,261 @@
+use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::MutexGuard;
+use std::sync::mpsc;
+use std::thread;
+
+use uuid::Uuid;
+
+use data::Script;
+use data::claims::AuthClaims;
+use scripting::ScriptFunctions;
+use scripting::ScriptResult;
+use scripting::error::ScriptError;
+
+/// how many finished jobs are kept for `GetJobResult`, the oldest ones are dropped first
+pub const FINISHED_JOBS_LIMIT: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    /// the script ran, whether it exited successfully is in the result
+    Finished,
+    /// the script could not be run at all
+    Failed,
+}
+
+/// A script run started with `RunScriptAsync`
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScriptJob {
+    pub id: String,
+    pub script_name: String,
+    pub status: JobStatus,
+    pub submitted_at: chrono::NaiveDateTime,
+    pub submitted_by: Option<String>,
+    pub started_at: Option<chrono::NaiveDateTime>,
+    pub finished_at: Option<chrono::NaiveDateTime>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// the job is finished with the domain and claims it was submitted with
+    #[serde(skip)]
+    pub domain: Option<String>,
+    #[serde(skip)]
+    pub claims: Option<AuthClaims>,
+    #[serde(skip)]
+    pub result: Option<ScriptResult>,
+    /// the error the job failed with, `error` is its message
+    #[serde(skip)]
+    pub failure: Option<ScriptError>,
+}
+
+impl ScriptJob {
+    pub fn is_done(&self) -> bool {
+        self.status == JobStatus::Finished || self.status == JobStatus::Failed
+    }
+}
+
+struct QueuedJob {
+    id: String,
+    runner: Box<ScriptFunctions + Send>,
+    script: Script,
+    params: serde_json::Value,
+    env: HashMap<String, String>,
+}
+
+#[derive(Debug, Default)]
+struct JobList {
+    jobs: HashMap<String, ScriptJob>,
+    /// oldest first
+    finished: VecDeque<String>,
+}
+
+/// The script jobs of all the executors, they are run by a pool of workers so that they don't block the executors
+#[derive(Debug, Default)]
+pub struct ScriptJobs {
+    jobs: Mutex<JobList>,
+    queue: Mutex<Option<mpsc::Sender<QueuedJob>>>,
+}
+
+impl ScriptJobs {
+    fn lock(&self) -> MutexGuard<JobList> {
+        // every change to the list is a single insert, update or remove, so it is still usable after a panic
+        self.jobs.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// Starts the workers, `on_done` is called by the worker with every job it finished
+    pub fn start_workers<F>(script_jobs: &Arc<Self>, workers: usize, on_done: F)
+        where
+            F: Fn(ScriptJob) + Send + Sync + 'static,
+    {
+        let (sender, receiver) = mpsc::channel::<QueuedJob>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        let on_done = Arc::new(on_done);
+
+        for worker in 0..workers {
+            let script_jobs = script_jobs.clone();
+            let receiver = receiver.clone();
+            let on_done = on_done.clone();
+
+            thread::Builder::new()
+                .name(format!("script-worker-{}", worker))
+                .spawn(move || loop {
+                    let queued_job = match receiver.lock().unwrap_or_else(|err| err.into_inner()).recv() {
+                        Ok(queued_job) => queued_job,
+                        Err(_) => break, // the queue was dropped
+                    };
+
+                    if let Some(job) = script_jobs.run(queued_job) {
+                        on_done(job);
+                    }
+                })
+                .expect("Could not start the script workers");
+        }
+
+        *script_jobs.queue.lock().unwrap_or_else(|err| err.into_inner()) = Some(sender);
+        info!("Started {} script workers", workers);
+    }
+
+    /// Queues the script, returns the job right away
+    pub fn submit(
+        &self,
+        runner: Box<ScriptFunctions + Send>,
+        script: Script,
+        params: serde_json::Value,
+        env: HashMap<String, String>,
+        domain: Option<String>,
+        claims: Option<AuthClaims>,
+    ) -> Result<ScriptJob, ScriptError> {
+        let queue = self.queue.lock().unwrap_or_else(|err| err.into_inner());
+        let sender = queue.as_ref().ok_or(ScriptError::NoJobWorkers)?;
+
+        let id = Uuid::new_v4().to_hyphenated().to_string();
+        let job = ScriptJob {
+            id: id.to_owned(),
+            script_name: script.name.to_owned(),
+            status: JobStatus::Queued,
+            submitted_at: chrono::Utc::now().naive_utc(),
+            submitted_by: claims.as_ref().filter(|x| !x.is_system()).map(|x| x.get_username()),
+            started_at: None,
+            finished_at: None,
+            error: None,
+            domain,
+            claims,
+            result: None,
+            failure: None,
+        };
+        self.lock().jobs.insert(id.to_owned(), job.to_owned());
+
+        let queued_job = QueuedJob { id: id.to_owned(), runner, script, params, env };
+        if sender.send(queued_job).is_err() {
+            self.lock().jobs.remove(&id);
+            return Err(ScriptError::NoJobWorkers);
+        }
+
+        debug!("queued script job {:?}", &job);
+        Ok(job)
+    }
+
+    pub fn get(&self, id: &str) -> Option<ScriptJob> {
+        self.lock().jobs.get(id).cloned()
+    }
+
+    fn update<F>(&self, id: &str, f: F) -> Option<ScriptJob>
+        where F: FnOnce(&mut ScriptJob),
+    {
+        let mut list = self.lock();
+        let job = {
+            let job = list.jobs.get_mut(id)?;
+            f(job);
+            job.to_owned()
+        };
+
+        if job.is_done() {
+            list.finished.push_back(id.to_owned());
+            while list.finished.len() > FINISHED_JOBS_LIMIT {
+                if let Some(oldest) = list.finished.pop_front() {
+                    list.jobs.remove(&oldest);
+                }
+            }
+        }
+
+        Some(job)
+    }
+
+    fn run(&self, queued_job: QueuedJob) -> Option<ScriptJob> {
+        self.update(&queued_job.id, |job| {
+            job.status = JobStatus::Running;
+            job.started_at = Some(chrono::Utc::now().naive_utc());
+        })?;
+
+        let res = queued_job.runner.run(&queued_job.script, &queued_job.params, &queued_job.env);
+
+        self.update(&queued_job.id, |job| {
+            job.finished_at = Some(chrono::Utc::now().naive_utc());
+            match res {
+                Ok(res) => {
+                    job.status = JobStatus::Finished;
+                    job.result = Some(res);
+                },
+                Err(err) => {
+                    warn!("could not run the script job {:?}: {:?}", &job.id, &err);
+                    job.status = JobStatus::Failed;
+                    job.error = Some(err.to_string());
+                    job.failure = Some(err);
+                },
+            };
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use scripting::Scripting;
+
+    #[test]
+    fn test_finished_jobs_limit() {
+        let script_jobs = ScriptJobs::default();
+        let now = chrono::Utc::now().naive_utc();
+        for i in 0..FINISHED_JOBS_LIMIT + 1 {
+            let id = format!("job{}", i);
+            script_jobs.lock().jobs.insert(id.to_owned(), ScriptJob {
+                id: id.to_owned(),
+                script_name: "foo".to_string(),
+                status: JobStatus::Running,
+                submitted_at: now,
+                submitted_by: None,
+                started_at: Some(now),
+                finished_at: None,
+                error: None,
+                domain: None,
+                claims: None,
+                result: None,
+                failure: None,
+            });
+            script_jobs.update(&id, |job| job.status = JobStatus::Failed);
+        }
+
+        assert!(script_jobs.get("job0").is_none());
+        assert_eq!(script_jobs.get("job1").map(|x| x.status), Some(JobStatus::Failed));
+        assert_eq!(script_jobs.lock().jobs.len(), FINISHED_JOBS_LIMIT);
+    }
+
+    #[test]
+    fn test_submit_without_workers() {
+        let script_jobs = ScriptJobs::default();
+        let script: Script = serde_json::from_value(json!({
+            "name": "foo",
+            "description": "",
+            "text": "",
+        })).unwrap();
+
+        let res = script_jobs.submit(Box::new(Scripting::new("./target/path/to/scripts".into())), script, json!({}), HashMap::new(), None, None);
+        assert_eq!(res.unwrap_err(), ScriptError::NoJobWorkers);
+    }
+}
@@ -1,20 +1,33 @@
 
+pub mod container;
 pub mod error;
+pub mod jobs;
 pub mod update_state;
+pub mod validation;
+pub mod virtualenv;
 
+use std::collections::HashMap;
 use std::fs;
 use std::env;
+use std::path::Path;
 use std::path::PathBuf;
 use std::process::Command;
 use std::process::Stdio;
+use std::io::BufRead;
+use std::io::BufReader;
 use std::io::Write;
 use std::io::Read;
-use std::str::from_utf8;
+use std::thread;
 
 use tempfile;
 
+use scripting::container::ContainerConfig;
+use scripting::error::ScriptDiagnostics;
 use scripting::error::ScriptError;
+use scripting::virtualenv::Virtualenvs;
+use scripting::virtualenv::VIRTUALENVS_DIR;
 use data::Script;
+use data::ScriptLanguage;
 use data::Named;
 
 
@@ -23,38 +36,139 @@ use data::Named;
 /// - Better permissioning
 ///     - Every script should have it's own user with it's own user
 /// - More run options
-///     - Run on docker, serverless
-/// - library support (i.e. pip install ..., custom libraries)
+///     - Run on serverless
+/// - library support (custom libraries)
 /// - Versioning scripts ( + Full git integration)
-/// - More languages
+/// - More languages (python and javascript for now)
 /// - Cron support
 /// - More efficient updates (i.e. don't upload the entire script all the time)
 
 pub trait ScriptFunctions {
-    fn run(&self, script: &Script, params: &serde_json::Value) -> Result<ScriptResult, ScriptError>;
+    /// `env` is added to the environment of the script, e.g. its secrets
+    fn run(&self, script: &Script, params: &serde_json::Value, env: &HashMap<String, String>) -> Result<ScriptResult, ScriptError> {
+        self.run_streaming(script, params, env, &mut |_| {})
+    }
+
+    /// Same as `run`, `on_stdout` is called with every line of the stdout as soon as the script prints it
+    fn run_streaming(
+        &self,
+        script: &Script,
+        params: &serde_json::Value,
+        env: &HashMap<String, String>,
+        on_stdout: &mut FnMut(&str),
+    ) -> Result<ScriptResult, ScriptError>;
+
+    /// Parses the script without running it, `Some` with the syntax error if it doesn't parse
+    fn check_syntax(&self, script: &Script) -> Result<Option<ScriptDiagnostics>, ScriptError>;
 }
 
 #[derive(Clone, Debug)]
 pub struct Scripting {
     script_home: PathBuf,
+    python: LocalRunner,
+    javascript: LocalRunner,
+}
+
+/// Runs the scripts of a language with a local interpreter
+///
+/// The params are written to a file whose path is passed as the first argument, the script writes
+/// its output back into the same file
+#[derive(Clone, Debug)]
+pub struct LocalRunner {
+    script_home: PathBuf,
+    interpreter: &'static str,
+    interpreter_args: &'static [&'static str],
+    file_name: &'static str,
+    /// parses the script given as its first argument, see `check_syntax`
+    checker: &'static str,
+    checker_name: &'static str,
+    /// for the scripts with requirements, only python has them
+    virtualenvs: Option<Virtualenvs>,
+    /// runs the interpreter in a container rather than on the host, see `Scripting::with_container`
+    container: Option<ContainerConfig>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ScriptResult {
     pub successful: bool,
+    /// `None` if the script was killed by a signal
+    pub exit_code: Option<i32>,
     pub stdout: String,
     pub stderr: String,
     pub output: serde_json::Value,
+    /// the exit code, the line and the traceback of a failed run, see `ScriptDiagnostics`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub diagnostics: Option<ScriptDiagnostics>,
+}
+
+/// A line of the stdout of a running script, see `StreamScript`
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScriptOutputLine {
+    pub script_name: String,
+    pub line: String,
 }
 
 const PYTHON: &'static str = "python3";
-const SCRIPT_NAME: &'static str = "script.py";
+/// unbuffered, so that the output can be streamed while the script runs
+const PYTHON_ARGS: &'static [&'static str] = &["-u"];
+const PYTHON_SCRIPT_NAME: &'static str = "script.py";
+/// like `py_compile`, but the bytecode isn't written, the script directory is read-only in the containers
+const PYTHON_CHECKER: &'static str = r#"import sys
+
+with open(sys.argv[1]) as script:
+    compile(script.read(), sys.argv[1], 'exec')
+"#;
+const PYTHON_CHECKER_NAME: &'static str = "check.py";
+
+/// quickjs, `--std` exposes the `std` and `os` modules to read and write the io file
+const JAVASCRIPT: &'static str = "qjs";
+const JAVASCRIPT_ARGS: &'static [&'static str] = &["--std"];
+const JAVASCRIPT_SCRIPT_NAME: &'static str = "script.js";
+/// the body of a function is parsed when the function is made, it only runs when it's called
+const JAVASCRIPT_CHECKER: &'static str = r#"new Function(std.loadFile(scriptArgs[1]));
+"#;
+const JAVASCRIPT_CHECKER_NAME: &'static str = "check.js";
 
 impl Scripting {
     pub fn new(script_home: PathBuf) -> Self {
         Self {
-            script_home
+            python: LocalRunner {
+                script_home: script_home.to_owned(),
+                interpreter: PYTHON,
+                interpreter_args: PYTHON_ARGS,
+                file_name: PYTHON_SCRIPT_NAME,
+                checker: PYTHON_CHECKER,
+                checker_name: PYTHON_CHECKER_NAME,
+                virtualenvs: Some(Virtualenvs::new(script_home.join(VIRTUALENVS_DIR))),
+                container: None,
+            },
+            javascript: LocalRunner {
+                script_home: script_home.to_owned(),
+                interpreter: JAVASCRIPT,
+                interpreter_args: JAVASCRIPT_ARGS,
+                file_name: JAVASCRIPT_SCRIPT_NAME,
+                checker: JAVASCRIPT_CHECKER,
+                checker_name: JAVASCRIPT_CHECKER_NAME,
+                virtualenvs: None,
+                container: None,
+            },
+            script_home,
+        }
+    }
+
+    /// Runs the scripts in containers, `None` runs them on the host
+    pub fn with_container(mut self, container: Option<ContainerConfig>) -> Self {
+        self.python.container = container.to_owned();
+        self.javascript.container = container;
+        self
+    }
+
+    pub fn get_runner(&self, language: &ScriptLanguage) -> &LocalRunner {
+        match language {
+            ScriptLanguage::Python => &self.python,
+            ScriptLanguage::JavaScript => &self.javascript,
         }
     }
 
@@ -69,23 +183,58 @@ impl Scripting {
         path
     }
 
-    pub fn get_script_path(&self, script_name: &str) -> PathBuf {
-        let mut path = self.get_home();
-        path.push(script_name);
-        path.push(SCRIPT_NAME);
+    pub fn get_script_path(&self, script_name: &str, language: &ScriptLanguage) -> PathBuf {
+        let mut path = self.get_script_home(script_name);
+        path.push(self.get_runner(language).file_name);
 
         path
     }
 }
 
 impl ScriptFunctions for Scripting {
+    fn run_streaming(
+        &self,
+        script: &Script,
+        params: &serde_json::Value,
+        env: &HashMap<String, String>,
+        on_stdout: &mut FnMut(&str),
+    ) -> Result<ScriptResult, ScriptError> {
+        self.get_runner(&script.language).run_streaming(script, params, env, on_stdout)
+    }
+
+    fn check_syntax(&self, script: &Script) -> Result<Option<ScriptDiagnostics>, ScriptError> {
+        self.get_runner(&script.language).check_syntax(script)
+    }
+}
 
-    fn run(&self, script: &Script, params: &serde_json::Value) -> Result<ScriptResult, ScriptError> {
-        let script_home = &self.script_home;
-        let path = self.get_script_home(script.my_name());
+impl ScriptFunctions for LocalRunner {
 
-        env::set_current_dir(path)
-            .map_err(|err| ScriptError::IOError(err.to_string()))?;
+    fn run_streaming(
+        &self,
+        script: &Script,
+        params: &serde_json::Value,
+        env: &HashMap<String, String>,
+        on_stdout: &mut FnMut(&str),
+    ) -> Result<ScriptResult, ScriptError> {
+        let is_in_container = self.container.is_some();
+        if is_in_container && !script.requirements.is_empty() {
+            return Err(ScriptError::RequirementsError("they can't be installed in a container, the image has to have them".to_string()));
+        }
+
+        // the python of a virtualenv is a path, made absolute since the script runs in its own directory
+        // and the script home can be relative
+        let interpreter = match self.virtualenvs {
+            Some(ref virtualenvs) if !script.requirements.is_empty() => {
+                let python = virtualenvs.get_python(self.interpreter, &script.requirements)?;
+                env::current_dir()
+                    .map_err(|err| ScriptError::IOError(err.to_string()))?
+                    .join(python)
+            },
+            _ => PathBuf::from(self.interpreter),
+        };
+
+        let mut path = self.script_home.to_owned();
+        path.push(script.my_name());
 
         let mut temp = tempfile::NamedTempFile::new()
             .map_err(|err| ScriptError::IOError(err.to_string()))?;
@@ -100,13 +249,72 @@ impl ScriptFunctions for Scripting {
         io_file.write_all(&params_text.as_bytes())
             .map_err(|err| ScriptError::IOError(err.to_string()))?;
 
-        let output = Command::new(PYTHON)
-            .arg(SCRIPT_NAME)
-            .arg(&io_file_path)
-            .output()
+        let mut command = match self.container {
+            Some(ref container) => container.command(
+                container.image(&script.language),
+                self.interpreter,
+                self.interpreter_args,
+                &path,
+                self.file_name,
+                Path::new(&io_file_path),
+                env,
+            )?,
+            None => {
+                // the working directory of the script only, the jobs run on a few threads at once
+                let mut command = Command::new(interpreter);
+                command
+                    .args(self.interpreter_args)
+                    .arg(self.file_name)
+                    .arg(&io_file_path)
+                    .envs(env)
+                    .current_dir(&path);
+                command
+            },
+        };
+
+        let mut child = command
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|err| ScriptError::ExecuteError(err.to_string()))?;
+
+        // the stderr is read on its own thread, otherwise the script blocks once the pipe is full
+        let stderr_reader = child.stderr.take().map(|mut child_stderr| thread::spawn(move || {
+            let mut stderr = vec![];
+            let _ = child_stderr.read_to_end(&mut stderr);
+            stderr
+        }));
+
+        let mut stdout = String::new();
+        if let Some(child_stdout) = child.stdout.take() {
+            let mut reader = BufReader::new(child_stdout);
+            let mut line = vec![];
+            loop {
+                line.clear();
+                match reader.read_until(b'\n', &mut line) {
+                    Ok(0) => break,
+                    Ok(_) => {
+                        let line = String::from_utf8_lossy(&line);
+                        on_stdout(line.trim_end_matches(|x| x == '\n' || x == '\r'));
+                        stdout.push_str(&line);
+                    },
+                    Err(err) => {
+                        warn!("could not read the stdout of the script: {:?}", &err);
+                        break;
+                    },
+                }
+            }
+        }
+
+        let status = child.wait()
             .map_err(|err| ScriptError::ExecuteError(err.to_string()))?;
+        let stderr = stderr_reader
+            .and_then(|x| x.join().ok())
+            .map(|x| String::from_utf8_lossy(&x).into_owned())
+            .unwrap_or_default();
 
-        let is_successful = output.status.success();
+        let is_successful = status.success();
 
         if is_successful {
             info!("Ran script successfully");
@@ -116,20 +324,77 @@ impl ScriptFunctions for Scripting {
 
             Ok(ScriptResult {
                 successful: is_successful,
-                stdout: from_utf8(&output.stdout).unwrap_or_default().to_string(),
-                stderr: from_utf8(&output.stderr).unwrap_or_default().to_string(),
+                exit_code: status.code(),
+                stdout,
+                stderr,
                 output: output_value,
+                diagnostics: None,
             })
 
         } else {
-            warn!("Could not run the script successfuly, failed with error");
+            let diagnostics = ScriptDiagnostics::new(status.code(), &stderr, Some(self.file_name));
+            warn!("Could not run the script successfuly, {}", &diagnostics);
 
             Ok(ScriptResult {
                 successful: is_successful,
-                stdout: from_utf8(&output.stdout).unwrap_or_default().to_string(),
-                stderr: from_utf8(&output.stderr).unwrap_or_default().to_string(),
+                exit_code: status.code(),
+                stdout,
+                stderr,
                 output: serde_json::Value::default(),
+                diagnostics: Some(diagnostics),
             })
         }
     }
+
+    fn check_syntax(&self, script: &Script) -> Result<Option<ScriptDiagnostics>, ScriptError> {
+        // the text is checked rather than the file, the script isn't saved yet when it's created
+        let check_dir = tempfile::tempdir()
+            .map_err(|err| ScriptError::IOError(err.to_string()))?;
+        fs::write(check_dir.path().join(self.file_name), &script.text)
+            .map_err(|err| ScriptError::IOError(err.to_string()))?;
+        fs::write(check_dir.path().join(self.checker_name), self.checker)
+            .map_err(|err| ScriptError::IOError(err.to_string()))?;
+
+        let mut checker_args = self.interpreter_args.to_vec();
+        checker_args.push(self.checker_name);
+
+        let mut command = match self.container {
+            Some(ref container) => {
+                // the checker ignores it, the containers always have one
+                let io_file = check_dir.path().join("params.json");
+                fs::write(&io_file, "null")
+                    .map_err(|err| ScriptError::IOError(err.to_string()))?;
+
+                container.command(
+                    container.image(&script.language),
+                    self.interpreter,
+                    &checker_args,
+                    check_dir.path(),
+                    self.file_name,
+                    &io_file,
+                    &HashMap::new(),
+                )?
+            },
+            None => {
+                let mut command = Command::new(self.interpreter);
+                command
+                    .args(&checker_args)
+                    .arg(self.file_name)
+                    .current_dir(check_dir.path());
+                command
+            },
+        };
+
+        let output = command
+            .stdin(Stdio::null())
+            .output()
+            .map_err(|err| ScriptError::ExecuteError(err.to_string()))?;
+
+        if output.status.success() {
+            Ok(None)
+        } else {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            Ok(Some(ScriptDiagnostics::new(output.status.code(), &stderr, Some(self.file_name))))
+        }
+    }
 }
@@ -8,23 +8,72 @@ use data::permissions::Permission;
 
 
 use model::entity::error::EntityError;
+use scripting::Scripting;
+use scripting::validation;
+use scripting::virtualenv::is_valid_requirement;
 use model::entity::EntityModifierController;
 use model::entity::RawEntityTypes;
 use model::entity::update_state::UpdateActionFunctions;
 use model::entity::update_state::UpdatePermissionFunctions;
 use state::user_management::UserManagementOps;
 
+fn check_script_schedule(script: &data::Script) -> Result<(), EntityError> {
+    match &script.schedule {
+        Some(schedule) => schedule
+            .cron_schedule()
+            .map(|_| ())
+            .map_err(EntityError::InvalidSchedule),
+        None => Ok(()),
+    }
+}
+
+fn check_script_requirements(script: &data::Script) -> Result<(), EntityError> {
+    if script.requirements.is_empty() {
+        return Ok(());
+    }
+
+    if script.language != data::ScriptLanguage::Python {
+        return Err(EntityError::InvalidRequirements("only the python scripts can have requirements".to_string()));
+    }
+
+    match script.requirements.iter().find(|x| !is_valid_requirement(x)) {
+        Some(requirement) => Err(EntityError::InvalidRequirements(format!("{:?} is not a requirement", requirement))),
+        None => Ok(()),
+    }
+}
+
+/// so that the broken scripts are rejected when they are saved rather than when they run
+fn check_script(scripting: &Scripting, script: &data::Script) -> Result<(), EntityError> {
+    let validation = match validation::validate(scripting, script, None) {
+        Ok(validation) => validation,
+        Err(err) => {
+            // e.g. the interpreter isn't installed here, the runs fail with the same error
+            warn!("could not validate the script {:?}, saving it anyway: {:?}", script.my_name(), &err);
+            return Ok(());
+        },
+    };
+
+    match validation.error_message() {
+        Some(message) => Err(EntityError::InvalidScript(message)),
+        None => Ok(()),
+    }
+}
+
 //TODO: there could be different types of script runners
 // docker, serverless, or local
 // currently we only have local
 
 impl UpdateActionFunctions for data::Script {
-    fn create_entity(controller: &EntityModifierController, new: &data::Script) -> Result<(), EntityError> {
+    fn create_entity<D>(controller: &EntityModifierController<D>, new: &data::Script) -> Result<(), EntityError> {
+        check_script_schedule(new)?;
+        check_script_requirements(new)?;
+        check_script(controller.scripting, new)?;
+
         info!("Creating the directory for script {:?}", &new.my_name());
         let script_name = &new.my_name();
 
         let path_dir = controller.scripting.get_script_home(&script_name);
-        let script_path = controller.scripting.get_script_path(&script_name);
+        let script_path = controller.scripting.get_script_path(&script_name, &new.language);
 
         fs::create_dir_all(&path_dir)
             .map_err(|err| EntityError::FileSystemError(format!("Could not create directory: {}", err.to_string())))?;
@@ -37,12 +86,16 @@ impl UpdateActionFunctions for data::Script {
 
         info!("created the file for script {:?} at {:?}", &new.my_name(), &script_path);
 
-        //TODO: pip env this
+        // the virtualenv with the requirements is made on the first run, see `Virtualenvs`
 
         Ok(())
     }
 
-    fn update_entity(controller: &EntityModifierController, old: &data::Script, new: &data::Script) -> Result<(), EntityError> {
+    fn update_entity<D>(controller: &EntityModifierController<D>, old: &data::Script, new: &data::Script) -> Result<(), EntityError> {
+        // before the old files are removed
+        check_script_schedule(new)?;
+        check_script_requirements(new)?;
+        check_script(controller.scripting, new)?;
 
         data::Script::delete_entity(controller, old)?;
         data::Script::create_entity(controller, new)?;
@@ -50,7 +103,7 @@ impl UpdateActionFunctions for data::Script {
         Ok(())
     }
 
-    fn delete_entity(controller: &EntityModifierController, old: &data::Script) -> Result<(), EntityError> {
+    fn delete_entity<D>(controller: &EntityModifierController<D>, old: &data::Script) -> Result<(), EntityError> {
         info!("Deleting the directory for script {:?}", &old.my_name());
         let script_name = &old.my_name();
 
@@ -67,7 +120,7 @@ impl UpdateActionFunctions for data::Script {
 }
 
 impl UpdatePermissionFunctions for data::Script {
-    fn create_permission(controller: &EntityModifierController, new: &data::Script) -> Result<(), EntityError> {
+    fn create_permission<D>(controller: &EntityModifierController<D>, new: &data::Script) -> Result<(), EntityError> {
         let permission_list = vec![
             Permission::read_entity::<data::Script>(new.my_name().to_owned()),
             Permission::modify_entity::<data::Script>(new.my_name().to_owned()),
@@ -91,7 +144,7 @@ impl UpdatePermissionFunctions for data::Script {
         Ok(())
     }
 
-    fn update_permission(controller: &EntityModifierController, old: &data::Script, new: &data::Script) -> Result<(), EntityError> {
+    fn update_permission<D>(controller: &EntityModifierController<D>, old: &data::Script, new: &data::Script) -> Result<(), EntityError> {
         let old_name = old.my_name().to_owned();
         let new_name = new.my_name().to_owned();
 
@@ -119,7 +172,7 @@ impl UpdatePermissionFunctions for data::Script {
         Ok(())
     }
 
-    fn delete_permission(controller: &EntityModifierController, old: &data::Script) -> Result<(), EntityError> {
+    fn delete_permission<D>(controller: &EntityModifierController<D>, old: &data::Script) -> Result<(), EntityError> {
 
         let permission_list = vec![
             Permission::read_entity::<data::Script>(old.my_name().to_owned()),
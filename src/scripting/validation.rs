@@ -0,0 +1,158 @@
+use std::collections::HashSet;
+
+use serde_json;
+
+use data::DeclaredParam;
+use data::Script;
+use scripting::ScriptFunctions;
+use scripting::error::ScriptDiagnostics;
+use scripting::error::ScriptError;
+
+/// What is wrong with a script, found without running it, see `ValidateScript`
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScriptValidation {
+    pub valid: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub syntax_error: Option<ScriptDiagnostics>,
+    /// about the declared params, and the params checked against them
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub param_errors: Vec<String>,
+}
+
+impl ScriptValidation {
+    pub fn new(syntax_error: Option<ScriptDiagnostics>, param_errors: Vec<String>) -> Self {
+        Self {
+            valid: syntax_error.is_none() && param_errors.is_empty(),
+            syntax_error,
+            param_errors,
+        }
+    }
+
+    /// every problem in one message, `None` if the script is valid
+    pub fn error_message(&self) -> Option<String> {
+        let mut errors = vec![];
+
+        if let Some(ref syntax_error) = self.syntax_error {
+            let message = syntax_error.message().unwrap_or("the script can't be parsed");
+            match syntax_error.line {
+                Some(line) => errors.push(format!("line {}: {}", line, message)),
+                None => errors.push(message.to_string()),
+            };
+        }
+        errors.extend(self.param_errors.iter().cloned());
+
+        if errors.is_empty() {
+            None
+        } else {
+            Some(errors.join(", "))
+        }
+    }
+}
+
+/// Checks the syntax and the declared params of the script, the params of its schedule, and
+/// `params` if there are some
+pub fn validate<SF>(runner: &SF, script: &Script, params: Option<&serde_json::Value>) -> Result<ScriptValidation, ScriptError>
+    where SF: ScriptFunctions,
+{
+    let syntax_error = runner.check_syntax(script)?;
+
+    let mut param_errors = check_declared_params(&script.params);
+    if let Some(ref schedule) = script.schedule {
+        let schedule_errors = check_params(&script.params, &schedule.params);
+        param_errors.extend(schedule_errors.into_iter().map(|x| format!("in the schedule, {}", x)));
+    }
+    if let Some(params) = params {
+        param_errors.extend(check_params(&script.params, params));
+    }
+
+    Ok(ScriptValidation::new(syntax_error, param_errors))
+}
+
+pub fn check_declared_params(declared: &[DeclaredParam]) -> Vec<String> {
+    let mut names = HashSet::new();
+    let mut errors = vec![];
+
+    for param in declared {
+        if param.name.is_empty() {
+            errors.push("a declared param has no name".to_string());
+        } else if !names.insert(param.name.as_str()) {
+            errors.push(format!("the param {:?} is declared twice", &param.name));
+        }
+    }
+
+    errors
+}
+
+/// The required params have to be there and the other keys have to be declared
+/// the scripts that don't declare their params take anything
+pub fn check_params(declared: &[DeclaredParam], params: &serde_json::Value) -> Vec<String> {
+    if declared.is_empty() {
+        return vec![];
+    }
+
+    let no_params = serde_json::Map::new();
+    let keys = match params {
+        serde_json::Value::Object(keys) => keys,
+        serde_json::Value::Null => &no_params,
+        _ => return vec!["the params have to be an object".to_string()],
+    };
+
+    let missing = declared
+        .iter()
+        .filter(|param| param.required && !keys.contains_key(&param.name))
+        .map(|param| format!("the param {:?} is required", &param.name));
+    let unknown = keys
+        .keys()
+        .filter(|key| !declared.iter().any(|param| &param.name == *key))
+        .map(|key| format!("the param {:?} isn't declared", key));
+
+    missing.chain(unknown).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn declared() -> Vec<DeclaredParam> {
+        serde_json::from_value(json!([
+            { "name": "table", "required": true },
+            { "name": "limit" },
+        ])).unwrap()
+    }
+
+    #[test]
+    fn test_check_params() {
+        assert!(check_params(&declared(), &json!({ "table": "foo", "limit": 10 })).is_empty());
+        assert!(check_params(&declared(), &json!({ "table": "foo" })).is_empty());
+        assert!(check_params(&[], &json!([1, 2, 3])).is_empty());
+
+        assert_eq!(check_params(&declared(), &json!(null)), vec![r#"the param "table" is required"#]);
+        assert_eq!(check_params(&declared(), &json!({ "table": "foo", "offset": 10 })), vec![r#"the param "offset" isn't declared"#]);
+        assert_eq!(check_params(&declared(), &json!(["foo"])), vec!["the params have to be an object"]);
+    }
+
+    #[test]
+    fn test_check_declared_params() {
+        assert!(check_declared_params(&declared()).is_empty());
+
+        let mut twice = declared();
+        twice.push(DeclaredParam { name: "limit".to_string(), description: "".to_string(), required: false });
+        assert_eq!(check_declared_params(&twice), vec![r#"the param "limit" is declared twice"#]);
+    }
+
+    #[test]
+    fn test_error_message() {
+        let syntax_error = ScriptDiagnostics::new(
+            Some(1),
+            "  File \"script.py\", line 2\n    print('foo'\n              ^\nSyntaxError: unexpected EOF while parsing\n",
+            Some("script.py"));
+        let validation = ScriptValidation::new(Some(syntax_error), vec![r#"the param "table" is required"#.to_string()]);
+
+        assert!(!validation.valid);
+        assert_eq!(
+            validation.error_message(),
+            Some(r#"line 2: SyntaxError: unexpected EOF while parsing, the param "table" is required"#.to_string()));
+        assert_eq!(ScriptValidation::new(None, vec![]).error_message(), None);
+    }
+}
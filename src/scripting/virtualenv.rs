@@ -0,0 +1,151 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+use std::process::Stdio;
+
+use openssl::sha::sha256;
+use tempfile;
+
+use scripting::error::ScriptDiagnostics;
+use scripting::error::ScriptError;
+
+/// where the virtualenvs are kept, in the script home
+pub const VIRTUALENVS_DIR: &'static str = ".virtualenvs";
+const REQUIREMENTS_FILE: &'static str = "requirements.txt";
+
+/// The virtualenvs of the python scripts with requirements, one for every set of requirements so
+/// that the scripts with the same requirements share it
+///
+/// A virtualenv is made the first time a script needs it and kept for the next runs
+#[derive(Clone, Debug)]
+pub struct Virtualenvs {
+    home: PathBuf,
+}
+
+/// Whether the requirement can go in a requirements file, e.g. `requests==2.21.0`
+/// The pip options, like `--index-url`, are not allowed
+pub fn is_valid_requirement(requirement: &str) -> bool {
+    let requirement = requirement.trim();
+    !requirement.is_empty() &&
+        !requirement.starts_with('-') &&
+        !requirement.contains(|c| c == '\n' || c == '\r')
+}
+
+impl Virtualenvs {
+    pub fn new(home: PathBuf) -> Self {
+        Self { home }
+    }
+
+    /// the name of the virtualenv of the requirements, their order and duplicates don't matter
+    pub fn env_name(requirements: &[String]) -> String {
+        let mut requirements: Vec<&str> = requirements.iter().map(|x| x.trim()).collect();
+        requirements.sort();
+        requirements.dedup();
+
+        sha256(requirements.join("\n").as_bytes())
+            .iter()
+            .take(16)
+            .map(|x| format!("{:02x}", x))
+            .collect()
+    }
+
+    /// The python of the virtualenv with the requirements installed, it is made if there isn't one yet
+    pub fn get_python(&self, interpreter: &str, requirements: &[String]) -> Result<PathBuf, ScriptError> {
+        if let Some(requirement) = requirements.iter().find(|x| !is_valid_requirement(x)) {
+            return Err(ScriptError::RequirementsError(format!("invalid requirement {:?}", requirement)));
+        }
+
+        let env_name = Self::env_name(requirements);
+        let mut env_path = self.home.to_owned();
+        env_path.push(&env_name);
+
+        let python = |path: &PathBuf| {
+            let mut python = path.to_owned();
+            python.push("bin");
+            python.push("python");
+            python
+        };
+        if python(&env_path).exists() {
+            return Ok(python(&env_path));
+        }
+
+        info!("Creating the virtualenv {:?} for {:?}", &env_name, requirements);
+        fs::create_dir_all(&self.home)
+            .map_err(|err| ScriptError::IOError(err.to_string()))?;
+
+        // made next to it and moved in place once the requirements are installed, so that a
+        // virtualenv that failed half way is never used
+        let temp_dir = tempfile::Builder::new()
+            .prefix(&format!("{}.", &env_name))
+            .tempdir_in(&self.home)
+            .map_err(|err| ScriptError::IOError(err.to_string()))?;
+        let temp_path = temp_dir.path().to_path_buf();
+
+        run_command(Command::new(interpreter).arg("-m").arg("venv").arg(&temp_path))?;
+
+        let mut requirements_path = temp_path.to_owned();
+        requirements_path.push(REQUIREMENTS_FILE);
+        let requirements_text: String = requirements.iter().map(|x| format!("{}\n", x.trim())).collect();
+        fs::write(&requirements_path, requirements_text)
+            .map_err(|err| ScriptError::IOError(err.to_string()))?;
+
+        // through the python rather than the `pip` script, the scripts of a virtualenv have its path
+        // in them and stop working once it's moved
+        run_command(Command::new(python(&temp_path))
+            .args(&["-m", "pip", "install", "--disable-pip-version-check", "-r"])
+            .arg(&requirements_path))?;
+
+        match fs::rename(temp_dir.into_path(), &env_path) {
+            Ok(_) => {},
+            // another run made the same virtualenv in the meantime
+            Err(_) if python(&env_path).exists() => {
+                let _ = fs::remove_dir_all(&temp_path);
+            },
+            Err(err) => {
+                let _ = fs::remove_dir_all(&temp_path);
+                return Err(ScriptError::IOError(err.to_string()));
+            },
+        };
+
+        info!("Created the virtualenv {:?}", &env_name);
+        Ok(python(&env_path))
+    }
+}
+
+fn run_command(command: &mut Command) -> Result<(), ScriptError> {
+    let output = command
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|err| ScriptError::ExecuteError(err.to_string()))?;
+
+    if output.status.success() {
+        Ok(())
+    } else {
+        let stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+        warn!("could not set up the virtualenv: {:?}", &stderr);
+        Err(ScriptError::InstallError(ScriptDiagnostics::new(output.status.code(), &stderr, None)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_env_name() {
+        let requirements = vec!["requests==2.21.0".to_string(), "numpy".to_string()];
+        let same_requirements = vec!["numpy".to_string(), " requests==2.21.0".to_string(), "numpy".to_string()];
+        assert_eq!(Virtualenvs::env_name(&requirements), Virtualenvs::env_name(&same_requirements));
+        assert_eq!(Virtualenvs::env_name(&requirements).len(), 32);
+        assert_ne!(Virtualenvs::env_name(&requirements), Virtualenvs::env_name(&["numpy".to_string()]));
+    }
+
+    #[test]
+    fn test_is_valid_requirement() {
+        assert!(is_valid_requirement("requests>=2.0,<3"));
+        assert!(is_valid_requirement("pandas[excel]"));
+        assert!(!is_valid_requirement(""));
+        assert!(!is_valid_requirement("--index-url http://example.com"));
+        assert!(!is_valid_requirement("numpy\n-e ."));
+    }
+}
@@ -1,38 +1,68 @@
-use std::path::PathBuf;
+use std::env;
 use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
 
 use actix::prelude::*;
 use actix;
-use actix_web::fs;
 use actix_web::middleware::Logger;
-use actix_web::http;
 use actix_web::middleware::cors::Cors;
-
-
-use openssl::ssl::SslAcceptor;
-use openssl::ssl::SslFiletype;
-use openssl::ssl::SslMethod;
 use actix_web::App;
+use actix_web::HttpRequest;
 
 use AppStateBuilder;
 use AppState;
 
 use view::extensions::ProcedureExt;
+use view::extensions::PolicyCheck;
+use view::rate_limit::RateLimit;
+use view::rate_limit::RateLimitConfig;
+use view::rate_limit::RateLimiter;
+use view::request_id::RequestIdHeader;
+use view::cors::CorsConfig;
+use view::cors::OriginFilter;
+use view::compression::Compression;
+use view::compression::CompressionConfig;
+use view::body_limit::BodyLimits;
+use view::https::HttpsConfig;
+use view::versioning::ApiVersionHeaders;
+use view::shutdown::GracefulShutdown;
+use view::static_assets::StaticAssets;
+use model::actions::policy::DecoratorPolicy;
 
 pub struct Server {
     system: actix::SystemRunner,
     host: String,
     port: u16,
-    frontend_path: Option<PathBuf>,
+    static_assets: Option<StaticAssets>,
+    decorator_policy: DecoratorPolicy,
+    rate_limit: RateLimitConfig,
+    cors: CorsConfig,
+    compression: CompressionConfig,
+    body_limits: BodyLimits,
+    https: Option<HttpsConfig>,
+    shutdown_timeout: Duration,
+    migrate_only: bool,
 }
 
+/// `run` only applies the metastore migrations when the process is started with it
+pub const MIGRATE_ONLY_FLAG: &str = "--migrate-only";
+
 impl Server {
     pub fn new() -> Self {
         Self {
             system: actix::System::new("Kakapo"),
             host: "127.0.0.1".to_string(),
             port: 1845,
-            frontend_path: None,
+            static_assets: None,
+            decorator_policy: DecoratorPolicy::default(),
+            rate_limit: RateLimitConfig::default(),
+            cors: CorsConfig::default(),
+            compression: CompressionConfig::default(),
+            body_limits: BodyLimits::default(),
+            https: None,
+            shutdown_timeout: Duration::from_secs(30),
+            migrate_only: false,
         }
     }
 
@@ -46,53 +76,162 @@ impl Server {
         self
     }
 
-    pub fn frontend_path(mut self, frontend_path: &Path) -> Self {
-        self.frontend_path = Some(frontend_path.to_path_buf());
+    pub fn frontend_path(self, frontend_path: &Path) -> Self {
+        self.static_assets(StaticAssets::directory(frontend_path))
+    }
+
+    /// Serves a bundle of static files, e.g. the admin frontend, next to the api.
+    /// The paths that aren't an api route or a file get the index of the bundle, unless the fallback is off
+    pub fn static_assets(mut self, static_assets: StaticAssets) -> Self {
+        self.static_assets = Some(static_assets);
+        self
+    }
+
+    /// Decorators required for the actions, the server won't start if an action doesn't conform
+    pub fn decorator_policy(mut self, decorator_policy: DecoratorPolicy) -> Self {
+        self.decorator_policy = decorator_policy;
+        self
+    }
+
+    /// Limits the procedure calls by user and by ip, the clients over the limit get a 429
+    /// until their oldest call leaves the window
+    pub fn rate_limit(mut self, rate_limit: RateLimitConfig) -> Self {
+        self.rate_limit = rate_limit;
+        self
+    }
+
+    /// The origins, methods and headers the browser frontends on other domains can use
+    pub fn cors(mut self, cors: CorsConfig) -> Self {
+        self.cors = cors;
+        self
+    }
+
+    /// whether the responses are compressed, and from which size
+    pub fn compression(mut self, compression: CompressionConfig) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// the maximum size of the request bodies, with their own limits for the table data and the login
+    pub fn body_limits(mut self, body_limits: BodyLimits) -> Self {
+        self.body_limits = body_limits;
+        self
+    }
+
+    /// Serves https and wss instead of http and ws, with the certificate and the key of the config
+    pub fn https(mut self, https: HttpsConfig) -> Self {
+        self.https = Some(https);
+        self
+    }
+
+    /// How long a SIGTERM or a SIGINT waits for the open requests and the running actions before stopping
+    pub fn shutdown_timeout(mut self, shutdown_timeout: Duration) -> Self {
+        self.shutdown_timeout = shutdown_timeout;
+        self
+    }
+
+    /// apply the metastore migrations and exit instead of starting the server, same as `--migrate-only`
+    pub fn migrate_only(mut self, migrate_only: bool) -> Self {
+        self.migrate_only = migrate_only;
         self
     }
 
     pub fn run(self, state_builder: AppStateBuilder) -> i32 {
 
+        if self.migrate_only || env::args().any(|x| x == MIGRATE_ONLY_FLAG) {
+            return match state_builder.migrate() {
+                Ok(applied) => {
+                    info!("applied {} migrations", applied.len());
+                    0
+                },
+                Err(err) => {
+                    error!("Could not run the migrations, {}", err);
+                    1
+                },
+            };
+        }
+
+        let mut policy_check = PolicyCheck::<AppState>::new(self.decorator_policy.to_owned());
+        policy_check.add_routes(&self.body_limits);
+        if let Err(violations) = policy_check.verify() {
+            error!("Could not start server, {}", violations);
+            return 1;
+        }
+
+        if let Err(err) = self.cors.validate() {
+            error!("Could not start server, {}", err);
+            return 1;
+        }
+
+        if let Some(Err(err)) = self.static_assets.as_ref().map(|x| x.validate()) {
+            error!("Could not start server, {}", err);
+            return 1;
+        }
+
+        let tls_config = match self.https.as_ref().map(|x| x.server_config()) {
+            Some(Ok(tls_config)) => Some(tls_config),
+            Some(Err(err)) => {
+                error!("Could not start server, {}", err);
+                return 1;
+            },
+            None => None,
+        };
+
         let server_addr = (&self.host[..], self.port);
-        let is_secure = false;
 
-        let state = state_builder.done();
+        let state = match state_builder.done() {
+            Ok(state) => state,
+            Err(err) => {
+                error!("Could not start server, {}", err);
+                return 1;
+            },
+        };
 
-        let frontend_path = self.frontend_path;
+        let shutdown_timeout = self.shutdown_timeout;
+        let static_assets = self.static_assets;
+        let cors_config = self.cors;
+        let compression = self.compression;
+        let body_limits = self.body_limits;
+
+        let rate_limiter = if self.rate_limit.is_enabled() {
+            Some(Arc::new(RateLimiter::new(self.rate_limit.to_owned())))
+        } else {
+            None
+        };
 
         let mut server_cfg = actix_web::server::new(move || {
 
-            let app = App::with_state(state.clone())
-                .middleware(Logger::new("Responded [%s] %b bytes %Dms"))
-                .middleware(Logger::new(r#"Requested [%r] FROM %a "%{User-Agent}i""#))
+            let mut app = App::with_state(state.clone())
+                .middleware(Logger::new("Responded [%s] %b bytes %Dms [%{X-Request-Id}o]"))
+                .middleware(Logger::new(r#"Requested [%r] FROM %a "%{User-Agent}i" [%{X-Request-Id}o]"#))
+                .middleware(RequestIdHeader)
+                .middleware(ApiVersionHeaders)
+                .middleware(Compression::new(compression));
+
+            if cors_config.has_origin_patterns() {
+                app = app.middleware(OriginFilter::new(cors_config.to_owned()));
+            }
+
+            if let Some(ref rate_limiter) = rate_limiter {
+                app = app.middleware(RateLimit::new(rate_limiter.clone()));
+            }
+
+            let cors_config = cors_config.to_owned();
+            let app = app
                 .configure(move |app| {
-                    Cors::for_app(app)
-                        .allowed_origin("http://localhost:3000")
-                        .allowed_origin("http://localhost:1845")
-                        .allowed_methods(vec!["GET", "POST", "PUT", "DELETE"])
-                        .allowed_headers(vec![http::header::AUTHORIZATION, http::header::ACCEPT])
-                        .allowed_header(http::header::CONTENT_TYPE)
-                        .max_age(3600)
-                        .add_routes()
+                    let mut cors = Cors::for_app(app);
+                    cors_config.apply(&mut cors);
+                    cors
+                        .add_routes(&body_limits)
                         .register()
                 });
 
 
-            if let Some(ref static_files) = frontend_path {
-
-                let mut index_file = static_files.to_owned();
-                index_file.push("index.html");
-
-                app.resource("/", |r| {
-                        r.method(http::Method::GET).f(move |req| {
-                            fs::NamedFile::open(index_file.to_owned())
-                        })
-                    })
-                    .handler(
-                    "/",
-                    fs::StaticFiles::new(&static_files)
-                        .unwrap()
-                        .show_files_listing())
+            // after the routes of the api, so that they have precedence
+            if let Some(ref static_assets) = static_assets {
+                let mount_path = static_assets.mount_path.to_owned();
+                let static_assets = static_assets.to_owned();
+                app.handler(&mount_path, move |req: &HttpRequest<AppState>| static_assets.respond(req))
             } else {
                 app
             }
@@ -105,34 +244,24 @@ impl Server {
             .keep_alive(30);
 
 
-        debug!("is_secure: {:?}", is_secure);
-        let http_server = /* if is_secure {
-            let ssl_cert_privkey_path = Env::ssl_cert_privkey_path();
-            let ssl_cert_fullchain_path = Env::ssl_cert_fullchain_path();
-
-            let mut ssl_builder = SslAcceptor::mozilla_intermediate(SslMethod::tls()).unwrap();
-            ssl_builder
-                .set_private_key_file(ssl_cert_privkey_path, SslFiletype::PEM)
-                .unwrap();
-            ssl_builder.set_certificate_chain_file(ssl_cert_fullchain_path).unwrap();
-
-
-            server_cfg
-                .bind_ssl(server_addr, ssl_builder)
-                .unwrap()
-
-        } else {
-        */
-            server_cfg
+        let http_server = match tls_config {
+            Some(tls_config) => {
+                info!("serving https");
+                server_cfg
+                    .bind_rustls(server_addr, tls_config)
+                    .unwrap()
+            },
+            None => server_cfg
                 .bind(server_addr)
-                .unwrap();
-        /*
+                .unwrap(),
         };
-        */
 
-        http_server
-            .shutdown_timeout(30)
+        // the signals are handled by `GracefulShutdown`, which also waits for the actions of the jobs
+        let http_server = http_server
+            .shutdown_timeout(shutdown_timeout.as_secs() as u16)
+            .disable_signals()
             .start();
+        GracefulShutdown::new(http_server, shutdown_timeout).start();
 
         info!("Kakapo server started on \"{:?}\"", server_addr);
 
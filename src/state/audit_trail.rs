@@ -0,0 +1,11 @@
+use data::audit::AuditEntry;
+use data::audit::AuditFilter;
+use data::audit::NewAuditEntry;
+use data::error::DatastoreError;
+
+pub trait AuditTrailOps {
+    fn record(&self, entry: &NewAuditEntry) -> Result<(), DatastoreError>;
+
+    /// the page of the matching entries, newest first, and how many match in all
+    fn get_entries(&self, filter: &AuditFilter, limit: usize, offset: usize) -> Result<(Vec<AuditEntry>, usize), DatastoreError>;
+}
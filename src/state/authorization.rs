@@ -1,5 +1,6 @@
 use std::collections::HashSet;
 use data::permissions::Permission;
+use data::claims::AuthClaims;
 
 use state::error::UserManagementError;
 
@@ -19,4 +20,9 @@ pub trait AuthorizationOps {
 
     fn username(&self) -> Option<String>;
 
+    fn claims(&self) -> Option<AuthClaims>;
+
+    /// tags attached to the entity, used for evaluating conditional permissions
+    fn entity_tags(&self, type_name: &str, entity_name: &str) -> Vec<String>;
+
 }
\ No newline at end of file
@@ -0,0 +1,10 @@
+use data::error::DatastoreError;
+use data::usage::EntityUsage;
+
+pub trait EntityUsageOps {
+    /// `type_name` is `table`, `query` or `script`, the use is attributed to the current user
+    fn record_usage(&self, type_name: &str, entity_name: &str) -> Result<(), DatastoreError>;
+
+    /// the usage of every entity of the type in the domain, including the ones that were never used, most used first
+    fn get_usage(&self, type_name: &str, top_users: usize) -> Result<Vec<EntityUsage>, DatastoreError>;
+}
@@ -4,19 +4,33 @@ pub mod authentication;
 pub mod authorization;
 pub mod user_management;
 pub mod domain_management;
+pub mod row_history;
+pub mod scheduled_runs;
+pub mod query_history;
+pub mod query_snapshots;
+pub mod entity_usage;
+pub mod script_runs;
+pub mod script_secrets;
+pub mod script_history;
+pub mod webhook_deliveries;
+pub mod audit_trail;
 
 use serde_json;
 
+use std::collections::HashMap;
 use std::fmt::Debug;
 use std::fmt;
 use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::time::Instant;
 
-use diesel::Connection;
 use serde::Serialize;
 
 
 use model::actions::error::Error;
 
+use connection::database::DatabaseConnection;
 use connection::executor::Conn;
 use connection::executor::Secrets;
 use connection::executor::DomainError;
@@ -36,6 +50,12 @@ use state::authentication::AuthenticationOps;
 use state::user_management::UserManagementOps;
 use state::domain_management::DomainManagementOps;
 use state::error::BroadcastError;
+use state::entity_usage::EntityUsageOps;
+use state::script_runs::ScriptRunsOps;
+use state::script_secrets::ScriptSecretsOps;
+use state::script_history::ScriptHistoryOps;
+use state::webhook_deliveries::WebhookDeliveriesOps;
+use state::audit_trail::AuditTrailOps;
 
 use scripting::ScriptFunctions;
 use scripting::Scripting;
@@ -43,16 +63,27 @@ use scripting::Scripting;
 use data::claims::AuthClaims;
 use data::channels::Channels;
 use data::channels::Subscription;
+use data::dead_letter::DeadLetter;
 use data::auth::User;
-use data::Message;
+use data::event::Event;
+use data::message_retention::MessageRetention;
+use data::key_case::KeyCase;
+use broker::metrics::BroadcastMetrics;
+use broker::event_sink::KafkaSink;
+use connection::metrics::DatabaseMetrics;
+use model::running_queries::RunningQueries;
+use scripting::jobs::ScriptJobs;
 use plugins::v1::Datastore;
 use plugins::v1::DataQuery;
+use plugins::v1::DatastoreError;
 use model::query::QueryActionOps;
 use model::query::QueryAction;
 
 
-pub struct ActionState {
-    pub database: Conn, //TODO: this should be templated
+/// `database` is the metastore, with the entities, users, permissions and messages.
+/// The table data and the queries go through the domain's connections, which can be in another database
+pub struct ActionState<D = Conn> {
+    pub database: D,
     pub scripting: Scripting,
     pub claims: Option<AuthClaims>,
     pub secrets: Secrets,
@@ -62,9 +93,20 @@ pub struct ActionState {
     pub jwt_issuer: String,
     pub jwt_duration: i64,
     pub jwt_refresh_duration: i64,
+    pub key_case: KeyCase,
+    pub request_id: Option<String>,
+    pub read_only: Arc<AtomicBool>,
+    pub broadcast_metrics: Arc<BroadcastMetrics>,
+    pub database_metrics: Arc<DatabaseMetrics>,
+    pub running_queries: Arc<RunningQueries>,
+    pub script_jobs: Arc<ScriptJobs>,
+    /// mirrors the published messages, see `AppStateBuilder::kafka_sink`
+    pub kafka_sink: Option<Arc<KafkaSink>>,
+    /// see `ActionWrapper::with_timeout`
+    pub deadline: Option<Instant>,
 }
 
-impl fmt::Debug for ActionState {
+impl<D> fmt::Debug for ActionState<D> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "ActionState")
     }
@@ -75,8 +117,15 @@ pub trait StateFunctions<'a>
         Self: Debug + Send,
         Self::TableController: DatastoreActionOps,
         Self::QueryController: QueryActionOps,
-        Self::Scripting: ScriptFunctions,
+        Self::Scripting: ScriptFunctions + Send + 'static,
+        Self::Database: DatabaseConnection,
         Self::PubSub: PubSubOps,
+        Self::EntityUsage: EntityUsageOps,
+        Self::ScriptRuns: ScriptRunsOps,
+        Self::ScriptSecrets: ScriptSecretsOps,
+        Self::ScriptHistory: ScriptHistoryOps,
+        Self::WebhookDeliveries: WebhookDeliveriesOps,
+        Self::AuditTrail: AuditTrailOps,
         Self::EmailSender: EmailOps,
         //TODO: managementstore
         Self::EntityRetrieverFunctions: RetrieverFunctions,
@@ -126,8 +175,46 @@ pub trait StateFunctions<'a>
     type PubSub;
     fn get_pub_sub(&'a self) -> Self::PubSub;
 
+    type EntityUsage;
+    fn get_entity_usage(&'a self) -> Self::EntityUsage;
+
+    type ScriptRuns;
+    fn get_script_runs(&'a self) -> Self::ScriptRuns;
+
+    type ScriptSecrets;
+    fn get_script_secrets(&'a self) -> Self::ScriptSecrets;
+
+    type ScriptHistory;
+    fn get_script_history(&'a self) -> Self::ScriptHistory;
+
+    type WebhookDeliveries;
+    fn get_webhook_deliveries(&'a self) -> Self::WebhookDeliveries;
+
+    type AuditTrail;
+    fn get_audit_trail(&'a self) -> Self::AuditTrail;
+
+    /// Runs `f` in a transaction of the metastore and of the domain's datastore, both are rolled back
+    /// if it returns an error. Calling it inside of `f` uses savepoints, so an error only rolls back
+    /// what was done inside of the inner call, e.g. one of the operations of a bulk change
     fn transaction<G, E, F>(&self, f: F) -> Result<G, E> //TODO: why is it a diesel::result::Error?
         where F: FnOnce() -> Result<G, E>, E: From<diesel::result::Error>;
+
+    fn get_domain_name(&self) -> Option<String>;
+
+    fn get_request_id(&self) -> Option<String>;
+
+    // maintenance
+    fn is_read_only(&self) -> bool;
+
+    fn set_read_only(&self, read_only: bool);
+
+    fn get_broadcast_metrics(&self) -> Arc<BroadcastMetrics>;
+
+    fn get_database_metrics(&self) -> Arc<DatabaseMetrics>;
+
+    fn get_running_queries(&self) -> Arc<RunningQueries>;
+
+    fn get_script_jobs(&self) -> Arc<ScriptJobs>;
 }
 
 
@@ -188,6 +275,7 @@ impl<'a> StateFunctions<'a> for ActionState {
             scripting: &self.scripting,
             user_management,
             domain_name: &self.domain_name,
+            secrets_key: &self.secrets.secrets_key,
         }
     }
 
@@ -195,6 +283,13 @@ impl<'a> StateFunctions<'a> for ActionState {
     fn get_table_controller(&'a self) -> Self::TableController { //TODO: rename datastore
         DatastoreAction {
             conn: &self.datastore_conn,
+            key_case: &self.key_case,
+            metrics: &self.database_metrics,
+            row_history: RowHistory {
+                conn: &self.database,
+                claims: &self.claims,
+                domain_name: &self.domain_name,
+            },
         }
     }
 
@@ -202,6 +297,24 @@ impl<'a> StateFunctions<'a> for ActionState {
     fn get_query_controller(&'a self) -> Self::QueryController {
         QueryAction {
             conn: &self.query_conn,
+            scheduled_runs: ScheduledRuns {
+                conn: &self.database,
+                domain_name: &self.domain_name,
+            },
+            query_history: QueryHistory {
+                conn: &self.database,
+                domain_name: &self.domain_name,
+            },
+            query_snapshots: QuerySnapshots {
+                conn: &self.database,
+                claims: &self.claims,
+                domain_name: &self.domain_name,
+            },
+            running_queries: self.running_queries.clone(),
+            metrics: &self.database_metrics,
+            domain_name: &self.domain_name,
+            claims: &self.claims,
+            deadline: self.deadline,
         }
     }
 
@@ -224,20 +337,133 @@ impl<'a> StateFunctions<'a> for ActionState {
     fn get_pub_sub(&'a self) -> Self::PubSub {
         PublishCallback {
             conn: &self.database,
+            domain_name: &self.domain_name,
+            claims: &self.claims,
+            kafka_sink: &self.kafka_sink,
+        }
+    }
+
+    type EntityUsage = EntityUsageRecorder<'a>;
+    fn get_entity_usage(&'a self) -> Self::EntityUsage {
+        EntityUsageRecorder {
+            conn: &self.database,
+            claims: &self.claims,
+            domain_name: &self.domain_name,
+        }
+    }
+
+    type ScriptRuns = ScriptRunRecorder<'a>;
+    fn get_script_runs(&'a self) -> Self::ScriptRuns {
+        ScriptRunRecorder {
+            conn: &self.database,
+            claims: &self.claims,
+            domain_name: &self.domain_name,
+        }
+    }
+
+    type ScriptSecrets = ScriptSecretStore<'a>;
+    fn get_script_secrets(&'a self) -> Self::ScriptSecrets {
+        ScriptSecretStore {
+            conn: &self.database,
+            claims: &self.claims,
+            domain_name: &self.domain_name,
+            secrets_key: &self.secrets.secrets_key,
+        }
+    }
+
+    type ScriptHistory = ScriptHistory<'a>;
+    fn get_script_history(&'a self) -> Self::ScriptHistory {
+        ScriptHistory {
+            conn: &self.database,
+            domain_name: &self.domain_name,
+        }
+    }
+
+    type WebhookDeliveries = WebhookDeliveryLog<'a>;
+    fn get_webhook_deliveries(&'a self) -> Self::WebhookDeliveries {
+        WebhookDeliveryLog {
+            conn: &self.database,
+            domain_name: &self.domain_name,
+            secrets_key: &self.secrets.secrets_key,
+        }
+    }
+
+    type AuditTrail = AuditTrail<'a>;
+    fn get_audit_trail(&'a self) -> Self::AuditTrail {
+        AuditTrail {
+            conn: &self.database,
         }
     }
 
     fn transaction<G, E, F>(&self, f: F) -> Result<G, E> //TODO: should work for all state actions
         where F: FnOnce() -> Result<G, E>, E: From<diesel::result::Error> {
-        let conn = &self.database;
-        conn.transaction::<G, E, _>(f)
+        DatabaseConnection::transaction(&self.database, || match self.datastore_conn {
+            Ok(ref datastore) => datastore_transaction(&**datastore, f),
+            Err(_) => f(),
+        })
+    }
+
+    fn get_domain_name(&self) -> Option<String> {
+        self.domain_name.to_owned()
+    }
+
+    fn get_request_id(&self) -> Option<String> {
+        self.request_id.to_owned()
+    }
+
+    fn is_read_only(&self) -> bool {
+        self.read_only.load(Ordering::SeqCst)
+    }
+
+    fn set_read_only(&self, read_only: bool) {
+        self.read_only.store(read_only, Ordering::SeqCst)
+    }
+
+    fn get_broadcast_metrics(&self) -> Arc<BroadcastMetrics> {
+        self.broadcast_metrics.clone()
+    }
+
+    fn get_database_metrics(&self) -> Arc<DatabaseMetrics> {
+        self.database_metrics.clone()
+    }
+
+    fn get_running_queries(&self) -> Arc<RunningQueries> {
+        self.running_queries.clone()
+    }
+
+    fn get_script_jobs(&self) -> Arc<ScriptJobs> {
+        self.script_jobs.clone()
     }
 }
 
-impl ActionState {
+/// The datastore is committed before the metastore, its changes stay if the metastore's commit fails
+fn datastore_transaction<G, E, F>(datastore: &Datastore, f: F) -> Result<G, E>
+    where F: FnOnce() -> Result<G, E>, E: From<diesel::result::Error>
+{
+    let to_transaction_error = |err: DatastoreError| {
+        error!("datastore transaction failed: {:?}", &err);
+        E::from(diesel::result::Error::RollbackTransaction)
+    };
+
+    datastore.begin_transaction().map_err(&to_transaction_error)?;
+    match f() {
+        Ok(res) => {
+            datastore.commit_transaction().map_err(&to_transaction_error)?;
+            Ok(res)
+        },
+        Err(err) => {
+            if let Err(rollback_err) = datastore.rollback_transaction() {
+                error!("could not roll back the datastore transaction: {:?}", &rollback_err);
+            }
+            Err(err)
+        },
+    }
+}
+
+impl<D> ActionState<D> {
     //TODO: this has too many parameters
     pub fn new(
-        database: Conn,
+        database: D,
         scripting: Scripting,
         claims: Option<AuthClaims>,
         secrets: Secrets,
@@ -259,8 +485,64 @@ impl ActionState {
             jwt_issuer, //TODO: put these in config
             jwt_duration,
             jwt_refresh_duration,
+            key_case: KeyCase::default(),
+            request_id: None,
+            read_only: Arc::new(AtomicBool::new(false)),
+            broadcast_metrics: Arc::new(BroadcastMetrics::default()),
+            database_metrics: Arc::new(DatabaseMetrics::default()),
+            running_queries: Arc::new(RunningQueries::default()),
+            script_jobs: Arc::new(ScriptJobs::default()),
+            kafka_sink: None,
+            deadline: None,
         }
     }
+
+    pub fn with_key_case(mut self, key_case: KeyCase) -> Self {
+        self.key_case = key_case;
+        self
+    }
+
+    /// the id of the request the action runs for, none for the actions the server runs by itself
+    pub fn with_request_id(mut self, request_id: Option<String>) -> Self {
+        self.request_id = request_id;
+        self
+    }
+
+    /// shared between all the executors, so that toggling it affects the whole server
+    pub fn with_read_only(mut self, read_only: Arc<AtomicBool>) -> Self {
+        self.read_only = read_only;
+        self
+    }
+
+    pub fn with_broadcast_metrics(mut self, broadcast_metrics: Arc<BroadcastMetrics>) -> Self {
+        self.broadcast_metrics = broadcast_metrics;
+        self
+    }
+
+    pub fn with_database_metrics(mut self, database_metrics: Arc<DatabaseMetrics>) -> Self {
+        self.database_metrics = database_metrics;
+        self
+    }
+
+    pub fn with_running_queries(mut self, running_queries: Arc<RunningQueries>) -> Self {
+        self.running_queries = running_queries;
+        self
+    }
+
+    pub fn with_script_jobs(mut self, script_jobs: Arc<ScriptJobs>) -> Self {
+        self.script_jobs = script_jobs;
+        self
+    }
+
+    pub fn with_kafka_sink(mut self, kafka_sink: Option<Arc<KafkaSink>>) -> Self {
+        self.kafka_sink = kafka_sink;
+        self
+    }
+
+    pub fn with_deadline(mut self, deadline: Option<Instant>) -> Self {
+        self.deadline = deadline;
+        self
+    }
 }
 
 pub struct Authentication<'a> {
@@ -288,32 +570,130 @@ pub struct DomainManagement<'a> {
 
 pub struct PublishCallback<'a> {
     pub conn: &'a Conn,
+    /// the webhooks of the domain get the published messages as well
+    pub domain_name: &'a Option<String>,
+    /// the caller is the actor of the published events
+    pub claims: &'a Option<AuthClaims>,
+    pub kafka_sink: &'a Option<Arc<KafkaSink>>,
+}
+
+pub struct RowHistory<'a, D: 'a = Conn> {
+    pub conn: &'a D,
+    pub claims: &'a Option<AuthClaims>,
+    pub domain_name: &'a Option<String>,
+}
+
+pub struct ScheduledRuns<'a> {
+    pub conn: &'a Conn,
+    pub domain_name: &'a Option<String>,
+}
+
+pub struct QueryHistory<'a> {
+    pub conn: &'a Conn,
+    pub domain_name: &'a Option<String>,
+}
+
+pub struct QuerySnapshots<'a> {
+    pub conn: &'a Conn,
+    pub claims: &'a Option<AuthClaims>,
+    pub domain_name: &'a Option<String>,
+}
+
+pub struct EntityUsageRecorder<'a> {
+    pub conn: &'a Conn,
+    pub claims: &'a Option<AuthClaims>,
+    pub domain_name: &'a Option<String>,
+}
+
+pub struct ScriptRunRecorder<'a> {
+    pub conn: &'a Conn,
+    pub claims: &'a Option<AuthClaims>,
+    pub domain_name: &'a Option<String>,
+}
+
+pub struct ScriptSecretStore<'a> {
+    pub conn: &'a Conn,
+    pub claims: &'a Option<AuthClaims>,
+    pub domain_name: &'a Option<String>,
+    pub secrets_key: &'a Option<String>,
+}
+
+pub struct ScriptHistory<'a> {
+    pub conn: &'a Conn,
+    pub domain_name: &'a Option<String>,
+}
+
+pub struct WebhookDeliveryLog<'a> {
+    pub conn: &'a Conn,
+    pub domain_name: &'a Option<String>,
+    /// the secrets of the webhooks are encrypted with it
+    pub secrets_key: &'a Option<String>,
+}
+
+/// not scoped to the domain, the admins see the calls of all of them
+pub struct AuditTrail<'a> {
+    pub conn: &'a Conn,
 }
 
 pub trait PubSubOps {
 
     fn publish(&self, channel: Channels, action_name: String, action_result: &serde_json::Value) -> Result<(), BroadcastError>;
 
-    fn subscribe(&self, user_id: i64, channel: Channels) -> Result<Subscription, BroadcastError>;
+    /// the subscription is removed by the cleanup after `expires_at`, if it is set
+    fn subscribe(
+        &self,
+        user_id: i64,
+        channel: Channels,
+        durable: bool,
+        expires_at: Option<chrono::NaiveDateTime>,
+    ) -> Result<Subscription, BroadcastError>;
 
     fn unsubscribe(&self, user_id: i64, channel: Channels) -> Result<Subscription, BroadcastError>;
 
-    fn unsubscribe_all(&self, user_id: i64) -> Result<(), BroadcastError>;
+    /// the durable subscriptions are kept with `keep_durable`, e.g. when the session closes
+    fn unsubscribe_all(&self, user_id: i64, keep_durable: bool) -> Result<(), BroadcastError>;
 
     fn get_subscribers(&self, channel: Channels) -> Result<Vec<User>, BroadcastError>;
 
+    /// the channels the user is subscribed to, the oldest subscription first
+    fn get_subscriptions(&self, user_id: i64) -> Result<Vec<Subscription>, BroadcastError>;
+
+    /// the number of users subscribed to each of the channels, the ones without subscribers are left out
+    fn count_subscribers(&self, channels: &[Channels]) -> Result<HashMap<Channels, i64>, BroadcastError>;
+
     fn get_messages(
         &self,
         user_id: i64,
         start_time: chrono::NaiveDateTime,
         end_time: chrono::NaiveDateTime,
-    ) -> Result<Vec<Message>, BroadcastError>;
+    ) -> Result<Vec<Event>, BroadcastError>;
+
+    /// the unacknowledged messages of the durable subscriptions that weren't sent yet or weren't acknowledged
+    /// in time, or all of them with `redeliver`, they are marked as sent. The ones sent too many times are
+    /// moved to the dead letters instead
+    fn get_pending_messages(&self, user_id: i64, redeliver: bool) -> Result<Vec<Event>, BroadcastError>;
+
+    /// the messages won't be sent again, returns how many of them were pending
+    fn ack_messages(&self, user_id: i64, message_ids: &[i64]) -> Result<usize, BroadcastError>;
+
+    /// the messages that couldn't be delivered, newest first, with the number of all of them
+    fn get_dead_letters(&self, limit: usize, offset: usize) -> Result<(Vec<DeadLetter>, usize), BroadcastError>;
+
+    /// delivers the message again, with all its attempts, `None` if there is no such dead letter
+    fn redeliver_dead_letter(&self, id: i64) -> Result<Option<DeadLetter>, BroadcastError>;
+
+    /// removes the expired subscriptions, and with `inactive_for` (in seconds) the ones of the users that
+    /// haven't logged in nor subscribed since, returns how many were removed
+    fn expire_subscriptions(&self, inactive_for: Option<u64>) -> Result<usize, BroadcastError>;
+
+    /// deletes the messages past the retention, returns how many were deleted
+    fn purge_messages(&self, retention: &MessageRetention) -> Result<usize, BroadcastError>;
 
     // Some user permissions have been removed so they must be purged
     fn permissions_removed(&self) -> Result<(), BroadcastError>;
 }
 
-impl GetSecrets for ActionState {
+impl<D> GetSecrets for ActionState<D> {
     fn get_token_secret(&self) -> String {
         self.secrets.token_secret.to_owned()
     }
@@ -0,0 +1,10 @@
+use data;
+use data::error::DatastoreError;
+
+pub trait QueryHistoryOps {
+    /// every stored version of the query, including the renames and deletions, newest first
+    fn get_query_history(&self, query_name: &str) -> Result<Vec<data::QueryVersion>, DatastoreError>;
+
+    /// the query as it was stored in the given version, `None` if the version belongs to another query
+    fn get_query_version(&self, query_name: &str, version: i64) -> Result<Option<data::DataQueryEntity>, DatastoreError>;
+}
@@ -0,0 +1,12 @@
+use data;
+use data::error::DatastoreError;
+
+pub trait QuerySnapshotsOps {
+    /// fails with `AlreadyExists` if the query already has a snapshot with that name
+    fn save_snapshot(&self, snapshot: &data::QuerySnapshot) -> Result<(), DatastoreError>;
+
+    fn get_snapshot(&self, query_name: &str, name: &str) -> Result<Option<data::QuerySnapshot>, DatastoreError>;
+
+    /// every snapshot of the query without the data, newest first
+    fn get_snapshots(&self, query_name: &str) -> Result<Vec<data::QuerySnapshot>, DatastoreError>;
+}
@@ -0,0 +1,11 @@
+use data::error::DatastoreError;
+use data::row_history::RowDelta;
+use data::row_history::RowHistoryEntry;
+
+pub trait RowHistoryOps {
+    /// stores the changes made to the rows of the table by the current user
+    fn record_changes(&self, table_name: &str, deltas: Vec<RowDelta>) -> Result<(), DatastoreError>;
+
+    /// all the changes made to a single row, oldest first
+    fn get_row_history(&self, table_name: &str, key: &serde_json::Value) -> Result<Vec<RowHistoryEntry>, DatastoreError>;
+}
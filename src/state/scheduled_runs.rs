@@ -0,0 +1,9 @@
+use data::error::DatastoreError;
+use data::schedule::ScheduledRun;
+
+pub trait ScheduledRunsOps {
+    fn record_run(&self, run: &ScheduledRun) -> Result<(), DatastoreError>;
+
+    /// the latest runs of the query, newest first
+    fn get_runs(&self, query_name: &str, limit: usize) -> Result<Vec<ScheduledRun>, DatastoreError>;
+}
@@ -0,0 +1,11 @@
+use data;
+use data::error::DatastoreError;
+use data::script_version::ScriptVersion;
+
+pub trait ScriptHistoryOps {
+    /// every stored version of the script, including the renames and deletions, newest first
+    fn get_script_history(&self, script_name: &str) -> Result<Vec<ScriptVersion>, DatastoreError>;
+
+    /// the script as it was stored in the given version, `None` if the version belongs to another script
+    fn get_script_version(&self, script_name: &str, version: i64) -> Result<Option<data::Script>, DatastoreError>;
+}
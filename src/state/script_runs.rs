@@ -0,0 +1,16 @@
+use data::error::DatastoreError;
+use data::script_run::ScriptRun;
+
+pub trait ScriptRunsOps {
+    /// the id of the run is left out, the id of the stored run is returned
+    fn record_run(&self, run: &ScriptRun) -> Result<i64, DatastoreError>;
+
+    /// the latest runs of the script without their output, newest first
+    fn get_runs(&self, script_name: &str, limit: usize) -> Result<Vec<ScriptRun>, DatastoreError>;
+
+    /// `None` if there is no such run of the script
+    fn get_run(&self, script_name: &str, id: i64) -> Result<Option<ScriptRun>, DatastoreError>;
+
+    /// when the scheduler last ran the script, `None` if it never did
+    fn last_scheduled_run(&self, script_name: &str) -> Result<Option<chrono::NaiveDateTime>, DatastoreError>;
+}
@@ -0,0 +1,18 @@
+use std::collections::HashMap;
+
+use data::error::DatastoreError;
+use data::script_secret::ScriptSecret;
+
+pub trait ScriptSecretsOps {
+    /// adds the secret or replaces its value
+    fn set_secret(&self, script_name: &str, name: &str, value: &str) -> Result<(), DatastoreError>;
+
+    /// `false` if the script had no such secret
+    fn remove_secret(&self, script_name: &str, name: &str) -> Result<bool, DatastoreError>;
+
+    /// the secrets of the script without their values, sorted by name
+    fn get_secrets(&self, script_name: &str) -> Result<Vec<ScriptSecret>, DatastoreError>;
+
+    /// the decrypted values by name, passed to the script as its environment
+    fn get_secret_values(&self, script_name: &str) -> Result<HashMap<String, String>, DatastoreError>;
+}
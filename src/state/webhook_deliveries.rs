@@ -0,0 +1,37 @@
+use data::error::DatastoreError;
+use data::webhook::ChannelWebhook;
+use data::webhook::DueDelivery;
+use data::webhook::NewChannelWebhook;
+use data::webhook::RetryPolicy;
+use data::webhook::WebhookDelivery;
+
+pub trait WebhookDeliveriesOps {
+    /// the pending deliveries of the domain that are due, oldest first
+    /// they are not due again for `claim_seconds`, so that they are not sent twice while they are being sent
+    fn claim_due_deliveries(&self, limit: usize, claim_seconds: u64) -> Result<Vec<DueDelivery>, DatastoreError>;
+
+    /// `status_code` is `None` if there was no response, the delivery is given up once it runs out of attempts
+    /// and kept as a dead letter
+    fn record_attempt(
+        &self,
+        id: i64,
+        attempts: i32,
+        retry: &RetryPolicy,
+        status_code: Option<i32>,
+        error: Option<String>,
+    ) -> Result<(), DatastoreError>;
+
+    /// the latest deliveries of the webhook, newest first
+    fn get_deliveries(&self, webhook_name: &str, limit: usize) -> Result<Vec<WebhookDelivery>, DatastoreError>;
+
+    /// same as `claim_due_deliveries` for the channel webhooks, which aren't scoped to a domain
+    fn claim_due_channel_deliveries(&self, limit: usize, claim_seconds: u64) -> Result<Vec<DueDelivery>, DatastoreError>;
+
+    /// a url can only be attached once to a channel
+    fn add_channel_webhook(&self, webhook: &NewChannelWebhook, created_by: Option<i64>) -> Result<ChannelWebhook, DatastoreError>;
+
+    /// the pending deliveries of the webhook are dropped with it, `None` if there is no such webhook
+    fn remove_channel_webhook(&self, id: i64) -> Result<Option<ChannelWebhook>, DatastoreError>;
+
+    fn get_channel_webhooks(&self) -> Result<Vec<ChannelWebhook>, DatastoreError>;
+}
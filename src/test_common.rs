@@ -37,6 +37,7 @@ use connection::executor::Executor;
 use state::PubSubOps;
 use data::channels::Channels;
 use view::extensions::ProcedureExt;
+use view::body_limit::BodyLimits;
 use actix_web::ws::ClientReader;
 use actix_web::ws::ClientWriter;
 use futures::Stream;
@@ -44,6 +45,10 @@ use actix_web::ws::Message;
 use connection::GetSecrets;
 use state::error::BroadcastError;
 use connection::executor::DomainError;
+use broker::metrics::BroadcastMetrics;
+use connection::metrics::DatabaseMetrics;
+use model::running_queries::RunningQueries;
+use scripting::jobs::ScriptJobs;
 
 
 pub fn random_identifier() -> String {
@@ -78,18 +83,21 @@ pub fn build_server() -> TestServer {
             .script_path("./local")
             .token_secret(TEST_KEY)
             .password_secret(TEST_KEY)
+            .secrets_key(TEST_KEY)
             .issuer("THE_ISSUER")
             .token_duration(600)
             .refresh_token_duration(60 * 60 * 24 * 7)
             .num_threads(1)
-            .done();
+            .query_threads(1)
+            .done()
+            .unwrap();
 
         TestState(Box::new(state))
     });
 
     server_builder
         .start(move |app| {
-            app.add_routes();
+            app.add_routes(&BodyLimits::default());
         })
 }
 
@@ -169,6 +177,14 @@ impl AppStateLike for TestState {
     fn connect(&self) -> &Addr<Executor> {
         self.0.connect()
     }
+
+    fn connect_queries(&self) -> &Addr<Executor> {
+        self.0.connect_queries()
+    }
+
+    fn broadcast_metrics(&self) -> &Arc<BroadcastMetrics> {
+        self.0.broadcast_metrics()
+    }
 }
 
 impl GetSecrets for TestState {
@@ -264,6 +280,36 @@ impl<'a> StateFunctions<'a> for MockState {
         self.0.get_pub_sub()
     }
 
+    type EntityUsage = <ActionState as StateFunctions<'a>>::EntityUsage;
+    fn get_entity_usage(&'a self) -> Self::EntityUsage {
+        self.0.get_entity_usage()
+    }
+
+    type ScriptRuns = <ActionState as StateFunctions<'a>>::ScriptRuns;
+    fn get_script_runs(&'a self) -> Self::ScriptRuns {
+        self.0.get_script_runs()
+    }
+
+    type ScriptSecrets = <ActionState as StateFunctions<'a>>::ScriptSecrets;
+    fn get_script_secrets(&'a self) -> Self::ScriptSecrets {
+        self.0.get_script_secrets()
+    }
+
+    type ScriptHistory = <ActionState as StateFunctions<'a>>::ScriptHistory;
+    fn get_script_history(&'a self) -> Self::ScriptHistory {
+        self.0.get_script_history()
+    }
+
+    type WebhookDeliveries = <ActionState as StateFunctions<'a>>::WebhookDeliveries;
+    fn get_webhook_deliveries(&'a self) -> Self::WebhookDeliveries {
+        self.0.get_webhook_deliveries()
+    }
+
+    type AuditTrail = <ActionState as StateFunctions<'a>>::AuditTrail;
+    fn get_audit_trail(&'a self) -> Self::AuditTrail {
+        self.0.get_audit_trail()
+    }
+
     fn transaction<G, E, F>(&self, f: F) -> Result<G, E>
         where
             F: FnOnce() -> Result<G, E>,
@@ -271,6 +317,38 @@ impl<'a> StateFunctions<'a> for MockState {
     {
         self.0.transaction(f)
     }
+
+    fn get_domain_name(&self) -> Option<String> {
+        self.0.get_domain_name()
+    }
+
+    fn get_request_id(&self) -> Option<String> {
+        self.0.get_request_id()
+    }
+
+    fn is_read_only(&self) -> bool {
+        self.0.is_read_only()
+    }
+
+    fn set_read_only(&self, read_only: bool) {
+        self.0.set_read_only(read_only)
+    }
+
+    fn get_broadcast_metrics(&self) -> Arc<BroadcastMetrics> {
+        self.0.get_broadcast_metrics()
+    }
+
+    fn get_database_metrics(&self) -> Arc<DatabaseMetrics> {
+        self.0.get_database_metrics()
+    }
+
+    fn get_running_queries(&self) -> Arc<RunningQueries> {
+        self.0.get_running_queries()
+    }
+
+    fn get_script_jobs(&self) -> Arc<ScriptJobs> {
+        self.0.get_script_jobs()
+    }
 }
 
 impl GetSecrets for MockState {
@@ -293,6 +371,7 @@ pub fn with_state<F>(f: F)
     let secrets = Secrets {
         token_secret: "A".to_string(),
         password_secret: "B".to_string(),
+        secrets_key: Some("C".to_string()),
     };
 
     let state = ActionState::new(
@@ -332,6 +411,7 @@ pub fn with_state_no_transaction<F>(f: F)
     let secrets = Secrets {
         token_secret: "A".to_string(),
         password_secret: "B".to_string(),
+        secrets_key: Some("C".to_string()),
     };
 
     let state = ActionState::new(
@@ -2,6 +2,8 @@
 use actix::prelude::*;
 
 use connection::executor::Executor;
+use connection::executor::DomainError;
+use connection::query_limit::QueryLimit;
 use actix::dev::MessageResponse;
 
 use model::actions::Action;
@@ -13,8 +15,14 @@ use scripting::Scripting;
 use std::str;
 use jsonwebtoken;
 use std::fmt;
+use std::time::Duration;
+use std::time::Instant;
+use futures::Future;
 use view::bearer_token::parse_bearer_token;
 use state::PublishCallback;
+use data::key_case::KeyCase;
+use view::request_id;
+use view::shutdown::InFlightGuard;
 
 
 pub struct ActionWrapper<A>
@@ -22,7 +30,28 @@ pub struct ActionWrapper<A>
 {
     action: Result<A, serde_json::Error>,
     auth_header: Option<Vec<u8>>,
+    /// used instead of the auth header, for the actions that the server runs by itself
+    claims: Option<AuthClaims>,
     domain_name: Option<String>,
+    key_case: KeyCase,
+    /// see `view::request_id`
+    request_id: Option<String>,
+    /// see `with_timeout`
+    deadline: Option<Deadline>,
+}
+
+#[derive(Debug, Clone)]
+struct Deadline {
+    procedure: String,
+    timeout: Duration,
+    at: Instant,
+}
+
+impl Deadline {
+    fn error(&self) -> Error {
+        let timeout = self.timeout.as_secs() * 1000 + u64::from(self.timeout.subsec_millis());
+        Error::TimedOut { procedure: self.procedure.to_owned(), timeout }
+    }
 }
 
 impl<A> fmt::Debug for ActionWrapper<A>
@@ -42,21 +71,33 @@ impl<A> ActionWrapper<A>
                 Self {
                     action: Ok(action),
                     auth_header: None,
+                    claims: None,
                     domain_name: Some(domain_name),
+                    key_case: KeyCase::default(),
+                    request_id: None,
+                    deadline: None,
                 }
             },
             Ok((None, action)) => {
                 Self {
                     action: Ok(action),
                     auth_header: None,
+                    claims: None,
                     domain_name: None,
+                    key_case: KeyCase::default(),
+                    request_id: None,
+                    deadline: None,
                 }
             },
             Err(err) => {
                 Self {
                     action: Err(err),
                     auth_header: None,
+                    claims: None,
                     domain_name: None,
+                    key_case: KeyCase::default(),
+                    request_id: None,
+                    deadline: None,
                 }
             }
         }
@@ -66,7 +107,11 @@ impl<A> ActionWrapper<A>
         Self {
             action: self.action,
             auth_header: Some(auth.to_owned()),
+            claims: self.claims,
             domain_name: self.domain_name,
+            key_case: self.key_case,
+            request_id: self.request_id,
+            deadline: self.deadline,
         }
     }
 
@@ -74,7 +119,62 @@ impl<A> ActionWrapper<A>
         Self {
             action: self.action,
             auth_header: self.auth_header,
+            claims: self.claims,
             domain_name: Some(domain_name.to_owned()),
+            key_case: self.key_case,
+            request_id: self.request_id,
+            deadline: self.deadline,
+        }
+    }
+
+    pub fn with_key_case(self, key_case: KeyCase) -> Self {
+        Self {
+            action: self.action,
+            auth_header: self.auth_header,
+            claims: self.claims,
+            domain_name: self.domain_name,
+            key_case,
+            request_id: self.request_id,
+            deadline: self.deadline,
+        }
+    }
+
+    pub fn with_request_id(self, request_id: &str) -> Self {
+        Self {
+            action: self.action,
+            auth_header: self.auth_header,
+            claims: self.claims,
+            domain_name: self.domain_name,
+            key_case: self.key_case,
+            request_id: Some(request_id.to_owned()),
+            deadline: self.deadline,
+        }
+    }
+
+    pub fn with_claims(self, claims: AuthClaims) -> Self {
+        Self {
+            action: self.action,
+            auth_header: self.auth_header,
+            claims: Some(claims),
+            domain_name: self.domain_name,
+            key_case: self.key_case,
+            request_id: self.request_id,
+            deadline: self.deadline,
+        }
+    }
+
+    /// The caller waits for the action until the timeout, then gets `Error::TimedOut`, see `send_action`.
+    /// The action is skipped if it didn't start by then, and the statements of its queries get what is left of it
+    pub fn with_timeout(self, procedure: &str, timeout: Duration) -> Self {
+        let deadline = Deadline {
+            procedure: procedure.to_owned(),
+            timeout,
+            at: Instant::now() + timeout,
+        };
+
+        Self {
+            deadline: Some(deadline),
+            ..self
         }
     }
 
@@ -111,6 +211,32 @@ impl<A> ActionWrapper<A>
 }
 
 
+/// Sends the action to the executors, the result is `Error::TimedOut` if it has a timeout and isn't done by then
+pub fn send_action<A>(executor: &Addr<Executor>, action_wrapper: ActionWrapper<A>) -> Box<Future<Item=ActionResult<A::Ret>, Error=MailboxError>>
+    where
+        A: Action + Send + 'static,
+        Executor: Handler<ActionWrapper<A>>,
+        A::Ret: 'static,
+{
+    match action_wrapper.deadline.to_owned() {
+        Some(deadline) => {
+            let request_id = action_wrapper.request_id.to_owned().unwrap_or_default();
+            let res = executor
+                .send(action_wrapper)
+                .timeout(deadline.timeout)
+                .then(move |res| match res {
+                    Err(MailboxError::Timeout) => {
+                        warn!("{} timed out after {:?} [{}]", &deadline.procedure, &deadline.timeout, &request_id);
+                        Ok(Err(deadline.error()))
+                    },
+                    res => res,
+                });
+            Box::new(res)
+        },
+        None => Box::new(executor.send(action_wrapper)),
+    }
+}
+
 impl<A: Action + Send> Message for ActionWrapper<A>
     where
         A::Ret: 'static,
@@ -127,9 +253,26 @@ impl<A: Action + Send> Handler<ActionWrapper<A>> for Executor
     type Result = ActionResult<A::Ret>;
 
     fn handle(&mut self, msg: ActionWrapper<A>, _: &mut Self::Context) -> Self::Result {
-
-        let auth_claims = msg.decode_token(self.get_token_secret());
+        // the log lines of the action have the request id until it is done
+        let request_id = msg.request_id.to_owned();
+        let _request_id_guard = request_id::enter(request_id.to_owned());
+        // the shutdown waits for it
+        let _in_flight_guard = InFlightGuard::enter();
+
+        let auth_claims = match msg.claims.to_owned() {
+            Some(claims) => Some(claims),
+            None => msg.decode_token(self.get_token_secret()),
+        };
         let domain_name = msg.get_domain_name();
+        let key_case = msg.key_case;
+        let deadline = msg.deadline.to_owned();
+        // the caller already gave up on it while it was queued
+        if let Some(ref deadline) = deadline {
+            if Instant::now() >= deadline.at {
+                warn!("skipping {}, it waited for an executor past its timeout", &deadline.procedure);
+                return Err(deadline.error());
+            }
+        }
         info!("Request for domain: {:?}", &domain_name);
 
         // Unauthorized has priority over serialization failed
@@ -145,13 +288,32 @@ impl<A: Action + Send> Handler<ActionWrapper<A>> for Executor
         };
 
         let action_req = action_req?;
-        let conn = self.get_connection();
+
+        // held until the action is done
+        let _query_limit_guard = if A::runs_domain_queries() {
+            let domain_name = domain_name.to_owned().unwrap_or_default();
+            Some(QueryLimit::acquire(&self.get_query_limit(), &domain_name))
+        } else {
+            None
+        };
+
+        // the clients get to retry instead of waiting on an exhausted pool
+        let retry_after = self.get_retry_after();
+        let conn = self.try_get_connection()
+            .map_err(|err| {
+                warn!("Could not get a metastore connection: {}", &err);
+                Error::ServiceUnavailable { retry_after }
+            })?;
 
         let domain_name_unwrapped = domain_name.to_owned().unwrap_or_default();
         let datastore_conn = self.get_datastore_conn(&domain_name_unwrapped);
         let query_conn = self.get_query_conn(&domain_name_unwrapped);
+        if datastore_conn.as_ref().err() == Some(&DomainError::Unavailable) || query_conn.as_ref().err() == Some(&DomainError::Unavailable) {
+            return Err(Error::ServiceUnavailable { retry_after });
+        }
 
-        let scripting = Scripting::new(self.get_scripts_path());
+        let scripting = Scripting::new(self.get_scripts_path())
+            .with_container(self.get_script_container());
         let secrets = self.get_secrets();
 
         //TODO: this is getting out of hand, builder pattern is the way to do this
@@ -166,7 +328,16 @@ impl<A: Action + Send> Handler<ActionWrapper<A>> for Executor
             self.jwt_issuer.to_owned(),
             self.jwt_token_duration,
             self.jwt_refresh_token_duration,
-        );
+        )
+            .with_key_case(key_case)
+            .with_request_id(request_id)
+            .with_deadline(deadline.map(|x| x.at))
+            .with_read_only(self.get_read_only())
+            .with_broadcast_metrics(self.get_broadcast_metrics())
+            .with_database_metrics(self.get_database_metrics())
+            .with_running_queries(self.get_running_queries())
+            .with_script_jobs(self.get_script_jobs())
+            .with_kafka_sink(self.get_kafka_sink());
         let result = action_req.call(&state);
         debug!("action result: {:?}", &result);
         result
@@ -202,6 +373,7 @@ mod test {
             .pass("password")
             .num_threads(1)
             .done()
+            .unwrap()
     }
 
     #[test]
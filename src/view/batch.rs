@@ -0,0 +1,160 @@
+use serde::Serialize;
+use serde_json::Value;
+
+use actix::prelude::*;
+use actix_web::FutureResponse;
+use actix_web::HttpRequest;
+use actix_web::HttpResponse;
+use actix_web::Json;
+use actix_web::Query;
+use actix_web::http::header;
+
+use futures::Future;
+use futures::future;
+
+use connection::AppStateLike;
+use connection::executor::Executor;
+use data::key_case::KeyCase;
+use data::key_case::KEY_CASE_HEADER;
+use model::actions::Action;
+use model::actions::BatchCall;
+use model::actions::RunBatch;
+use view::action_wrapper::ActionWrapper;
+use view::action_wrapper::send_action;
+use view::procedure::ProcedureBuilder;
+use view::registry::Procedure;
+use view::registry::ProcedureVisitor;
+use view::registry::visit_procedures;
+use view::request_id::request_id;
+use view::versioning::ApiVersion;
+use view::versioning::unversioned_path;
+
+fn empty_object() -> Value {
+    json!({})
+}
+
+/// A call of the batch, the same as calling `POST /<group>/<procedure>` with the params as the query and the data as the body
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchCallParams {
+    pub procedure: String,
+    #[serde(default = "empty_object")]
+    pub params: Value,
+    #[serde(default = "empty_object")]
+    pub data: Value,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchParams {
+    /// all the calls or none of them, see `RunBatch`
+    #[serde(default)]
+    pub atomic: bool,
+}
+
+/// Builds the action of a call with the procedure of the registry that has its name, if it's served over http
+struct BatchCallBuilder<'c> {
+    call: &'c BatchCallParams,
+    built: Option<Result<(Option<String>, BatchCall), serde_json::Error>>,
+}
+
+impl<'c, S> ProcedureVisitor<S> for BatchCallBuilder<'c>
+    where
+        S: AppStateLike + 'static,
+{
+    fn visit<A, PB>(&mut self, procedure: Procedure, procedure_builder: PB) -> &mut Self
+        where
+            Executor: Handler<ActionWrapper<A>>,
+            A: Action + Send + 'static,
+            PB: ProcedureBuilder<S, Value, Value, A> + Clone + 'static,
+            <A as Action>::Ret: Send + Serialize,
+    {
+        if procedure.http && procedure.name == self.call.procedure && self.built.is_none() {
+            let built = procedure_builder
+                .build(self.call.data.to_owned(), self.call.params.to_owned())
+                .map(|(domain, action)| {
+                    let call = BatchCall { procedure: procedure.name.to_owned(), step: Box::new(action) };
+                    (domain, call)
+                });
+            self.built = Some(built);
+        }
+        self
+    }
+}
+
+fn bad_request(err: &str, index: usize, request_id: &str) -> HttpResponse {
+    HttpResponse::BadRequest().json(json!({ "error": err, "details": { "index": index }, "requestId": request_id }))
+}
+
+/// Runs several procedures in one request, one after the other, and responds with their results in the same order.
+/// The calls are all built before any of them runs, so a batch with an unknown procedure or bad params doesn't run at all
+/// The calls of a batch are on one domain, since they share the connections of one action
+pub fn batch_handler<S>((req, calls, params): (HttpRequest<S>, Json<Vec<BatchCallParams>>, Query<BatchParams>)) -> FutureResponse<HttpResponse>
+    where
+        S: AppStateLike + 'static,
+{
+    let calls = calls.into_inner();
+    let atomic = params.into_inner().atomic;
+    let request_id = request_id(&req);
+    debug!("batch of {} calls, atomic: {} [{}]", calls.len(), atomic, &request_id);
+
+    // the procedures of the version the batch was sent to
+    let version = unversioned_path(req.path()).0.unwrap_or(ApiVersion::LEGACY);
+
+    let mut domain = None;
+    let mut batch_calls = vec![];
+    for (index, call) in calls.iter().enumerate() {
+        let mut builder = BatchCallBuilder { call, built: None };
+        visit_procedures::<S, _>(version, &mut builder);
+
+        let (call_domain, batch_call) = match builder.built {
+            Some(Ok(built)) => built,
+            Some(Err(err)) => return Box::new(future::ok(bad_request(&err.to_string(), index, &request_id))),
+            None => return Box::new(future::ok(bad_request(&format!("unknown procedure `{}`", &call.procedure), index, &request_id))),
+        };
+
+        if let Some(call_domain) = call_domain {
+            if domain.as_ref().map(|x| x != &call_domain).unwrap_or(false) {
+                return Box::new(future::ok(bad_request("the calls of a batch must be on the same domain", index, &request_id)));
+            }
+            domain = Some(call_domain);
+        }
+        batch_calls.push(batch_call);
+    }
+
+    let action = RunBatch::<_>::new(batch_calls, atomic);
+    let mut action_wrapper = ActionWrapper::new(Ok((domain, action)))
+        .with_request_id(&request_id);
+    if let Some(auth) = req.headers().get(header::AUTHORIZATION) {
+        action_wrapper = action_wrapper.with_auth(auth.as_bytes());
+    }
+    if let Some(key_case) = req.headers().get(KEY_CASE_HEADER).and_then(|x| KeyCase::from_header(x.as_bytes())) {
+        action_wrapper = action_wrapper.with_key_case(key_case);
+    }
+    // the batch as a whole, its calls can be of procedures with other timeouts
+    if let Some(timeout) = req.state().action_timeout("batch") {
+        action_wrapper = action_wrapper.with_timeout("batch", timeout);
+    }
+
+    let response = send_action(req.state().connect_for::<RunBatch>(), action_wrapper)
+        .from_err()
+        .and_then(move |res| match res {
+            Ok(ok_res) => Ok(HttpResponse::Ok().json(ok_res.get_data())),
+            Err(err) => {
+                debug!("Responding with error message: {:?} [{}]", &err, &request_id);
+                let mut envelope = err.envelope();
+                envelope["requestId"] = json!(request_id);
+                match err.retry_after() {
+                    Some(retry_after) => Ok(HttpResponse::ServiceUnavailable()
+                        .header(header::RETRY_AFTER, retry_after.to_string())
+                        .json(envelope)),
+                    None if err.is_timeout() => Ok(HttpResponse::GatewayTimeout()
+                        .json(envelope)),
+                    None => Ok(HttpResponse::InternalServerError()
+                        .json(envelope)),
+                }
+            },
+        });
+
+    Box::new(response)
+}
@@ -0,0 +1,102 @@
+use view::versioning::unversioned_path;
+
+/// the routes taking the rows of a table, which can be much bigger than the other requests
+const BULK_DATA_ROUTES: [&str; 5] = [
+    "/manage/insertTableData",
+    "/manage/modifyTableData",
+    "/manage/removeTableData",
+    "/manage/bulkModifyTableData",
+    "/batch",
+];
+
+/// the routes anyone can call, without being logged in
+const AUTH_ROUTES: [&str; 3] = [
+    "/users/login",
+    "/users/refresh",
+    "/users/setupUser",
+];
+
+/// actix's own limit for the json bodies
+const DEFAULT_LIMIT: usize = 256 * 1024;
+const BULK_DATA_LIMIT: usize = 16 * 1024 * 1024;
+const AUTH_LIMIT: usize = 16 * 1024;
+/// the files are written to disk, not kept in memory like the json bodies
+const UPLOAD_LIMIT: usize = 1024 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RouteClass {
+    Default,
+    BulkData,
+    Auth,
+}
+
+impl RouteClass {
+    pub fn for_path(path: &str) -> Self {
+        let (_, path) = unversioned_path(path);
+        if BULK_DATA_ROUTES.contains(&path) {
+            RouteClass::BulkData
+        } else if AUTH_ROUTES.contains(&path) {
+            RouteClass::Auth
+        } else {
+            RouteClass::Default
+        }
+    }
+}
+
+/// The maximum size of the json body of the procedures, in bytes. The bigger ones get a 413
+/// Every worker builds its routes with them, see `ProcedureExt::add_routes`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BodyLimits {
+    pub default: usize,
+    /// for inserting, modifying and removing table data
+    pub bulk_data: usize,
+    /// for the routes that don't need a login
+    pub auth: usize,
+    /// for the files uploaded to `importTableData`
+    pub upload: usize,
+}
+
+impl Default for BodyLimits {
+    fn default() -> Self {
+        Self {
+            default: DEFAULT_LIMIT,
+            bulk_data: BULK_DATA_LIMIT,
+            auth: AUTH_LIMIT,
+            upload: UPLOAD_LIMIT,
+        }
+    }
+}
+
+impl BodyLimits {
+    pub fn for_class(&self, route_class: RouteClass) -> usize {
+        match route_class {
+            RouteClass::Default => self.default,
+            RouteClass::BulkData => self.bulk_data,
+            RouteClass::Auth => self.auth,
+        }
+    }
+
+    pub fn for_path(&self, path: &str) -> usize {
+        self.for_class(RouteClass::for_path(path))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_limit_for_path() {
+        let limits = BodyLimits {
+            default: 100,
+            bulk_data: 1000,
+            auth: 10,
+            upload: 10000,
+        };
+        assert_eq!(limits.for_path("/manage/insertTableData"), 1000);
+        assert_eq!(limits.for_path("/api/v1/manage/insertTableData"), 1000);
+        assert_eq!(limits.for_path("/api/v1/batch"), 1000);
+        assert_eq!(limits.for_path("/users/login"), 10);
+        assert_eq!(limits.for_path("/manage/createTable"), 100);
+    }
+}
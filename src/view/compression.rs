@@ -0,0 +1,87 @@
+use actix_web::Body;
+use actix_web::HttpRequest;
+use actix_web::HttpResponse;
+use actix_web::Result as ActixResult;
+use actix_web::http::ContentEncoding;
+use actix_web::http::header;
+use actix_web::http::header::HeaderValue;
+use actix_web::middleware::Middleware;
+use actix_web::middleware::Response;
+
+/// The responses are compressed with the encoding the client prefers in its `Accept-Encoding`,
+/// brotli, gzip or deflate. Compressing the small ones costs more than it saves
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompressionConfig {
+    pub enabled: bool,
+    /// in bytes, the smaller responses are sent as they are. The streamed ones are always compressed
+    pub min_size: usize,
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            min_size: 1024,
+        }
+    }
+}
+
+impl CompressionConfig {
+    fn should_compress(&self, body: &Body) -> bool {
+        if !self.enabled {
+            return false;
+        }
+
+        match body {
+            Body::Binary(binary) => binary.len() >= self.min_size,
+            Body::Streaming(_) => true,
+            Body::Empty | Body::Actor(_) => false,
+        }
+    }
+}
+
+/// Middleware choosing whether the response is compressed, actix does the negotiation and the encoding
+pub struct Compression {
+    config: CompressionConfig,
+}
+
+impl Compression {
+    pub fn new(config: CompressionConfig) -> Self {
+        Self { config }
+    }
+}
+
+impl<S> Middleware<S> for Compression {
+    fn response(&self, _req: &HttpRequest<S>, mut resp: HttpResponse) -> ActixResult<Response> {
+        // the handlers setting the encoding themselves know better, e.g. for the files that are already compressed
+        if resp.content_encoding().is_some() {
+            return Ok(Response::Done(resp));
+        }
+
+        if self.config.should_compress(resp.body()) {
+            resp.set_content_encoding(ContentEncoding::Auto);
+            // the caches keep the compressed and the uncompressed responses apart
+            resp.headers_mut().append(header::VARY, HeaderValue::from_static("Accept-Encoding"));
+        } else {
+            resp.set_content_encoding(ContentEncoding::Identity);
+        }
+
+        Ok(Response::Done(resp))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_should_compress() {
+        let config = CompressionConfig::default();
+        assert!(!config.should_compress(&Body::Empty));
+        assert!(!config.should_compress(&Body::from(vec![0u8; 100])));
+        assert!(config.should_compress(&Body::from(vec![0u8; 4096])));
+
+        let disabled = CompressionConfig { enabled: false, ..CompressionConfig::default() };
+        assert!(!disabled.should_compress(&Body::from(vec![0u8; 4096])));
+    }
+}
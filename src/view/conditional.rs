@@ -0,0 +1,59 @@
+use openssl::sha::sha256;
+use serde::Serialize;
+use serde_json;
+
+/// The reads that the clients poll the most. Their responses have an `ETag`, and the requests
+/// with a matching `If-None-Match` get a 304 instead of the same data again
+const CONDITIONAL_ACTIONS: [&str; 4] = ["getTable", "getQuery", "getScript", "queryTableData"];
+
+pub fn is_conditional(action_name: &str) -> bool {
+    CONDITIONAL_ACTIONS.contains(&action_name)
+}
+
+/// A strong tag from the hash of the serialized data. The key case is already applied to the
+/// data at this point, so the camel case and the snake case responses have different tags
+pub fn etag<T>(data: &T) -> Option<String>
+    where T: Serialize,
+{
+    let serialized = serde_json::to_vec(data).ok()?;
+    let hash: String = sha256(&serialized)
+        .iter()
+        .take(16)
+        .map(|x| format!("{:02x}", x))
+        .collect();
+
+    Some(format!("\"{}\"", hash))
+}
+
+/// whether one of the `If-None-Match` headers has the tag, the weak comparison is used as in RFC 7232
+pub fn is_not_modified(if_none_match: &[String], etag: &str) -> bool {
+    if_none_match
+        .iter()
+        .flat_map(|x| x.split(','))
+        .map(|x| x.trim())
+        .any(|x| x == "*" || x.trim_start_matches("W/") == etag)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_etag() {
+        let etag = etag(&json!({ "name": "users", "columns": ["id"] })).unwrap();
+        assert_eq!(etag.len(), 34);
+        assert!(etag.starts_with('"') && etag.ends_with('"'));
+        assert_eq!(Some(etag.to_owned()), super::etag(&json!({ "name": "users", "columns": ["id"] })));
+        assert_ne!(Some(etag), super::etag(&json!({ "name": "users", "columns": ["id", "name"] })));
+    }
+
+    #[test]
+    fn test_is_not_modified() {
+        let etag = "\"0123abcd\"";
+        assert!(is_not_modified(&["\"0123abcd\"".to_string()], etag));
+        assert!(is_not_modified(&["\"ffff\", W/\"0123abcd\"".to_string()], etag));
+        assert!(is_not_modified(&["\"ffff\"".to_string(), "*".to_string()], etag));
+        assert!(!is_not_modified(&["\"ffff\"".to_string()], etag));
+        assert!(!is_not_modified(&[], etag));
+    }
+}
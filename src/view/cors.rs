@@ -0,0 +1,200 @@
+use actix_web::HttpRequest;
+use actix_web::HttpResponse;
+use actix_web::Result as ActixResult;
+use actix_web::http::header;
+use actix_web::middleware::Middleware;
+use actix_web::middleware::Started;
+use actix_web::middleware::cors::CorsBuilder;
+
+use view::rate_limit;
+use view::versioning;
+use view::request_id::REQUEST_ID_HEADER;
+
+/// The CORS policy of the routes. The default is the one for the frontend served on localhost
+#[derive(Debug, Clone, PartialEq)]
+pub struct CorsConfig {
+    /// `*` allows any origin, and `*` in an origin matches anything, e.g. `https://*.example.com`
+    pub allowed_origins: Vec<String>,
+    pub allowed_methods: Vec<String>,
+    pub allowed_headers: Vec<String>,
+    /// the response headers the browser lets the frontend read
+    pub exposed_headers: Vec<String>,
+    /// whether the browser sends the cookies and the auth headers with the requests
+    pub supports_credentials: bool,
+    /// in seconds, for how long the browser can keep the preflight response
+    pub max_age: Option<usize>,
+}
+
+impl Default for CorsConfig {
+    fn default() -> Self {
+        Self {
+            allowed_origins: vec![
+                "http://localhost:3000".to_string(),
+                "http://localhost:1845".to_string(),
+            ],
+            allowed_methods: vec!["GET", "POST", "PUT", "DELETE"]
+                .into_iter()
+                .map(|x| x.to_string())
+                .collect(),
+            allowed_headers: vec![header::AUTHORIZATION, header::ACCEPT, header::CONTENT_TYPE]
+                .into_iter()
+                .map(|x| x.as_str().to_string())
+                .collect(),
+            exposed_headers: vec![
+                header::ETAG.as_str(),
+                header::RETRY_AFTER.as_str(),
+                REQUEST_ID_HEADER,
+                rate_limit::LIMIT_HEADER,
+                rate_limit::REMAINING_HEADER,
+                rate_limit::RESET_HEADER,
+                versioning::API_VERSION_HEADER,
+                versioning::DEPRECATION_HEADER,
+                versioning::LINK_HEADER,
+            ]
+                .into_iter()
+                .map(|x| x.to_string())
+                .collect(),
+            supports_credentials: false,
+            max_age: Some(3600),
+        }
+    }
+}
+
+impl CorsConfig {
+    fn allows_any_origin(&self) -> bool {
+        self.allowed_origins.iter().any(|x| x == "*")
+    }
+
+    /// the origins with a wildcard can't be given to actix, see `OriginFilter`
+    pub fn has_origin_patterns(&self) -> bool {
+        !self.allows_any_origin() && self.allowed_origins.iter().any(|x| x.contains('*'))
+    }
+
+    pub fn is_allowed_origin(&self, origin: &str) -> bool {
+        self.allowed_origins.iter().any(|x| matches_origin(x, origin))
+    }
+
+    /// the browsers would send the credentials to any site otherwise
+    pub fn validate(&self) -> Result<(), String> {
+        if self.supports_credentials && self.allows_any_origin() {
+            return Err("the CORS policy can't allow the credentials from any origin".to_string());
+        }
+        Ok(())
+    }
+
+    pub fn apply<S: 'static>(&self, cors: &mut CorsBuilder<S>) {
+        // actix allows every origin when none is given, and answers with the origin of the request.
+        // The patterns are checked by `OriginFilter` before that
+        if !self.allows_any_origin() && !self.has_origin_patterns() {
+            for origin in self.allowed_origins.iter() {
+                cors.allowed_origin(origin);
+            }
+        }
+
+        cors
+            .allowed_methods(self.allowed_methods.iter().map(|x| x.as_str()))
+            .allowed_headers(self.allowed_headers.iter().map(|x| x.as_str()))
+            .expose_headers(self.exposed_headers.iter().map(|x| x.as_str()));
+
+        if self.supports_credentials {
+            cors.supports_credentials();
+        }
+        if let Some(max_age) = self.max_age {
+            cors.max_age(max_age);
+        }
+    }
+}
+
+/// `*` matches any part of the origin, including none of it
+fn matches_origin(pattern: &str, origin: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    if !origin.starts_with(first) {
+        return false;
+    }
+
+    let mut rest = &origin[first.len()..];
+    let parts: Vec<&str> = parts.collect();
+    let last = match parts.split_last() {
+        Some((last, middle)) => {
+            for part in middle {
+                match rest.find(part) {
+                    Some(idx) => rest = &rest[idx + part.len()..],
+                    None => return false,
+                }
+            }
+            last
+        },
+        // no wildcard, the whole origin has to match
+        None => return rest.is_empty(),
+    };
+
+    rest.ends_with(last)
+}
+
+/// Rejects the requests from the origins that don't match the patterns of the policy.
+/// Only the browsers send the origin, the requests without it are let through
+pub struct OriginFilter {
+    config: CorsConfig,
+}
+
+impl OriginFilter {
+    pub fn new(config: CorsConfig) -> Self {
+        Self { config }
+    }
+}
+
+impl<S> Middleware<S> for OriginFilter {
+    fn start(&self, req: &HttpRequest<S>) -> ActixResult<Started> {
+        let origin = req.headers()
+            .get(header::ORIGIN)
+            .and_then(|x| x.to_str().ok());
+
+        match origin {
+            Some(origin) if !self.config.is_allowed_origin(origin) => {
+                debug!("origin {} is not allowed", origin);
+                Ok(Started::Response(HttpResponse::Forbidden().json(json!({ "error": "Origin is not allowed" }))))
+            },
+            _ => Ok(Started::Done),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_matches_origin() {
+        assert!(matches_origin("https://app.example.com", "https://app.example.com"));
+        assert!(!matches_origin("https://app.example.com", "https://app.example.com.evil.io"));
+        assert!(matches_origin("https://*.example.com", "https://staging.app.example.com"));
+        assert!(!matches_origin("https://*.example.com", "https://example.com"));
+        assert!(!matches_origin("https://*.example.com", "https://example.com.evil.io"));
+        assert!(matches_origin("http://localhost:*", "http://localhost:3000"));
+        assert!(matches_origin("*", "https://anything.io"));
+    }
+
+    #[test]
+    fn test_cors_config() {
+        let config = CorsConfig::default();
+        assert!(!config.has_origin_patterns());
+        assert!(config.is_allowed_origin("http://localhost:3000"));
+        assert!(!config.is_allowed_origin("http://localhost:8080"));
+        assert!(config.validate().is_ok());
+
+        let config = CorsConfig {
+            allowed_origins: vec!["https://*.example.com".to_string()],
+            ..CorsConfig::default()
+        };
+        assert!(config.has_origin_patterns());
+
+        let config = CorsConfig {
+            allowed_origins: vec!["*".to_string()],
+            supports_credentials: true,
+            ..CorsConfig::default()
+        };
+        assert!(!config.has_origin_patterns());
+        assert!(config.validate().is_err());
+    }
+}
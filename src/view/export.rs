@@ -0,0 +1,414 @@
+
+use serde::Serialize;
+use serde_json;
+
+use actix::Arbiter;
+use actix_web::error;
+use actix_web::Error as ActixError;
+use actix_web::HttpRequest;
+use actix_web::HttpResponse;
+use actix_web::Json;
+use actix_web::Query;
+use actix_web::http::header;
+
+use bytes::Bytes;
+use futures::Future;
+use futures::Sink;
+use futures::Stream;
+use futures::future;
+use futures::stream;
+use futures::sync::mpsc;
+use linked_hash_map::LinkedHashMap;
+
+use connection::AppStateLike;
+#[cfg(feature = "parquet-export")]
+use kakapo_postgres;
+use data::key_case::KeyCase;
+use data::key_case::KEY_CASE_HEADER;
+use model::actions::ExportTableData;
+use view::action_wrapper::ActionWrapper;
+use view::request_id::request_id;
+
+/// query string parameter selecting the response format, e.g. `?format=csv`
+pub const FORMAT_PARAM: &str = "format";
+/// the procedures returning table data, the other ones are only answered with json
+pub const FORMATTED_PROCEDURES: [&str; 2] = ["queryTableData", "runQuery"];
+/// a spreadsheet reads the fields starting with these as formulas
+const FORMULA_PREFIXES: [char; 4] = ['=', '+', '-', '@'];
+
+const DEFAULT_BATCH_SIZE: usize = 1000;
+const MAX_BATCH_SIZE: usize = 10000;
+/// number of batches waiting to be written before the executor stops fetching
+const STREAM_BUFFER: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Json,
+    Csv,
+    Ndjson,
+    #[cfg(feature = "parquet-export")]
+    Parquet,
+}
+
+impl Default for ExportFormat {
+    fn default() -> Self {
+        ExportFormat::Json
+    }
+}
+
+impl ExportFormat {
+    pub fn from_param(param: Option<&str>) -> Result<Self, String> {
+        match param {
+            // the layouts of the rows are sent as json, see `ResultFormat`
+            None | Some("json") | Some("raw") | Some("rows") | Some("flat") | Some("columnar") | Some("keyed") => Ok(ExportFormat::Json),
+            Some("csv") => Ok(ExportFormat::Csv),
+            Some("ndjson") => Ok(ExportFormat::Ndjson),
+            #[cfg(feature = "parquet-export")]
+            Some("parquet") => Ok(ExportFormat::Parquet),
+            Some(other) => Err(format!("unknown format `{}`", other)),
+        }
+    }
+
+    fn from_media_type(media_type: &str) -> Option<Self> {
+        match media_type {
+            "application/json" | "application/*" | "*/*" => Some(ExportFormat::Json),
+            "text/csv" | "text/*" => Some(ExportFormat::Csv),
+            "application/x-ndjson" | "application/ndjson" => Some(ExportFormat::Ndjson),
+            #[cfg(feature = "parquet-export")]
+            "application/vnd.apache.parquet" => Some(ExportFormat::Parquet),
+            _ => None,
+        }
+    }
+
+    /// The format the client prefers in its `Accept` header, the first one with the highest quality.
+    /// None if it doesn't accept any of them
+    pub fn from_accept(accept: &str) -> Option<Self> {
+        let mut accepted: Vec<(f32, Self)> = accept
+            .split(',')
+            .filter_map(|media_range| {
+                let mut parts = media_range.split(';').map(|x| x.trim());
+                let format = Self::from_media_type(&parts.next()?.to_lowercase())?;
+                let quality = parts
+                    .filter_map(|param| {
+                        let (name, value) = param.split_at(param.find('=')?);
+                        if name.trim() == "q" {
+                            value[1..].trim().parse::<f32>().ok()
+                        } else {
+                            None
+                        }
+                    })
+                    .next()
+                    .unwrap_or(1.0);
+                Some((quality, format))
+            })
+            .filter(|(quality, _)| *quality > 0.0)
+            .collect();
+
+        // stable, so the order of the header breaks the ties
+        accepted.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
+        accepted.first().map(|(_, format)| *format)
+    }
+
+    /// Whether the procedure can be answered in this format, it's checked before the action runs
+    pub fn applies_to(&self, procedure: &str) -> bool {
+        *self == ExportFormat::Json || FORMATTED_PROCEDURES.contains(&procedure)
+    }
+
+    /// the media types that can be asked for, for the 406 responses
+    pub fn media_types() -> Vec<&'static str> {
+        let mut media_types = vec!["application/json", "text/csv", "application/x-ndjson"];
+        #[cfg(feature = "parquet-export")]
+        media_types.push("application/vnd.apache.parquet");
+        media_types
+    }
+
+    pub fn respond<T>(&self, data: &T) -> HttpResponse
+        where T: Serialize,
+    {
+        match self {
+            ExportFormat::Json => HttpResponse::Ok().json(data),
+            ExportFormat::Csv => csv_response(data),
+            ExportFormat::Ndjson => ndjson_response(data),
+            #[cfg(feature = "parquet-export")]
+            ExportFormat::Parquet => parquet_response(data),
+        }
+    }
+}
+
+/// Columns and rows of a table data result, the key columns come before the value columns
+fn table_rows(data: serde_json::Value) -> Option<(Vec<serde_json::Value>, Vec<Vec<serde_json::Value>>)> {
+    let mut data = match data {
+        serde_json::Value::Object(obj) => obj,
+        _ => return None,
+    };
+
+    let mut columns = data.remove("columns")?;
+    let mut column_names = columns["keys"].as_array().cloned().unwrap_or_default();
+    column_names.extend(columns["values"].take().as_array().cloned()?);
+
+    let rows = match data.remove("data")? {
+        serde_json::Value::Array(rows) => rows,
+        _ => return None,
+    };
+    let rows = rows
+        .into_iter()
+        .map(|mut row| {
+            let mut values = row["keys"].as_array().cloned().unwrap_or_default();
+            values.extend(row["values"].take().as_array().cloned().unwrap_or_default());
+            values
+        })
+        .collect();
+
+    Some((column_names, rows))
+}
+
+/// Renders a single value, dates and timestamps are written as their ISO strings
+/// The text that a spreadsheet would run as a formula is prefixed with a quote
+fn csv_field(value: &serde_json::Value) -> String {
+    let text = match value {
+        serde_json::Value::Null => return "".to_string(),
+        serde_json::Value::String(x) if x.starts_with(&FORMULA_PREFIXES[..]) => format!("'{}", x),
+        serde_json::Value::String(x) => x.to_owned(),
+        serde_json::Value::Object(obj) if obj.len() == 1 => {
+            let special = obj.get("$timestamp")
+                .or_else(|| obj.get("$date"))
+                .or_else(|| obj.get("$binary"))
+                .or_else(|| obj.get("$uuid"))
+                .and_then(|x| x.as_str());
+            match special {
+                Some(x) => x.to_owned(),
+                None => value.to_string(),
+            }
+        },
+        x => x.to_string(),
+    };
+
+    if text.contains(|c: char| c == ',' || c == '"' || c == '\n' || c == '\r') {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text
+    }
+}
+
+fn csv_line(values: &[serde_json::Value]) -> String {
+    let fields: Vec<String> = values.iter().map(csv_field).collect();
+    format!("{}\r\n", fields.join(","))
+}
+
+/// Streams the table data as csv, one chunk per row
+pub fn csv_response<T>(data: &T) -> HttpResponse
+    where T: Serialize,
+{
+    let table = serde_json::to_value(data)
+        .ok()
+        .and_then(table_rows);
+
+    let (column_names, rows) = match table {
+        Some(x) => x,
+        None => return HttpResponse::BadRequest()
+            .json(json!({ "error": "csv is only supported for table data" })),
+    };
+
+    let header = Bytes::from(csv_line(&column_names));
+    let lines = rows
+        .into_iter()
+        .map(|row| Bytes::from(csv_line(&row)));
+    let chunks = Some(header).into_iter().chain(lines);
+
+    HttpResponse::Ok()
+        .content_type("text/csv; charset=utf-8")
+        .streaming(stream::iter_ok::<_, ActixError>(chunks))
+}
+
+/// Writes the table data as a parquet file, the column types are taken from the values
+#[cfg(feature = "parquet-export")]
+pub fn parquet_response<T>(data: &T) -> HttpResponse
+    where T: Serialize,
+{
+    let file = serde_json::to_value(data)
+        .ok()
+        .and_then(|x| kakapo_postgres::parquet_export::to_parquet(&x).ok());
+
+    match file {
+        Some(file) => HttpResponse::Ok()
+            .content_type("application/vnd.apache.parquet")
+            .body(file),
+        None => HttpResponse::BadRequest()
+            .json(json!({ "error": "parquet is only supported for table data" })),
+    }
+}
+
+/// Renders a batch of table data as one json object per row, anything else is written as a single line
+fn ndjson_chunk(data: serde_json::Value) -> Bytes {
+    let lines = match table_rows(data.to_owned()) {
+        Some((column_names, rows)) => rows
+            .into_iter()
+            .map(|row| {
+                let object: LinkedHashMap<String, serde_json::Value> = column_names
+                    .iter()
+                    .map(|name| name.as_str().unwrap_or_default().to_owned())
+                    .zip(row)
+                    .collect();
+                format!("{}\n", serde_json::to_string(&object).unwrap_or_default())
+            })
+            .collect::<String>(),
+        None => format!("{}\n", data),
+    };
+
+    Bytes::from(lines)
+}
+
+/// The table data as one json object per row, like the streaming export. Anything else is a single line
+pub fn ndjson_response<T>(data: &T) -> HttpResponse
+    where T: Serialize,
+{
+    match serde_json::to_value(data) {
+        Ok(data) => HttpResponse::Ok()
+            .content_type("application/x-ndjson")
+            .body(ndjson_chunk(data)),
+        Err(err) => HttpResponse::InternalServerError()
+            .json(json!({ "error": err.to_string() })),
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportParams {
+    pub name: String,
+    pub domain: String,
+    /// how many rows are fetched from the database at once
+    pub batch_size: Option<usize>,
+}
+
+/// Streams the whole table as newline delimited json, one object per row
+/// The rows are read through a cursor, so only a few batches are in memory at any time
+/// An error after the rows started streaming is written as a last `{"error": ...}` line
+pub fn ndjson_handler<S>((req, params, query): (HttpRequest<S>, Query<ExportParams>, Json<serde_json::Value>)) -> HttpResponse
+    where
+        S: AppStateLike + 'static,
+{
+    let params = params.into_inner();
+    debug!("exporting table data {:?}", &params);
+
+    let batch_size = params.batch_size.unwrap_or(DEFAULT_BATCH_SIZE).min(MAX_BATCH_SIZE);
+    let (sender, receiver) = mpsc::channel(STREAM_BUFFER);
+    let error_sender = sender.clone();
+
+    let action = ExportTableData::<_>::new(params.name, query.into_inner(), batch_size, sender);
+    let mut action_wrapper = ActionWrapper::new(Ok((Some(params.domain), action)))
+        .with_request_id(&request_id(&req));
+    if let Some(auth) = req.headers().get(header::AUTHORIZATION) {
+        action_wrapper = action_wrapper.with_auth(auth.as_bytes());
+    }
+
+    let key_case = req.headers().get(KEY_CASE_HEADER).and_then(|x| KeyCase::from_header(x.as_bytes()));
+    if let Some(key_case) = key_case {
+        action_wrapper = action_wrapper.with_key_case(key_case);
+    }
+
+    let export = req
+        .state()
+        .connect_queries()
+        .send(action_wrapper)
+        .then(move |res| -> Box<Future<Item=(), Error=()>> {
+            let err = match res {
+                Ok(Ok(ok_res)) => {
+                    debug!("exported {} rows", ok_res.get_data().row_count);
+                    return Box::new(future::ok(()));
+                },
+                Ok(Err(err)) => err.to_string(),
+                Err(err) => err.to_string(),
+            };
+
+            debug!("Responding with error message: {:?}", &err);
+            let error_line = error_sender
+                .send(json!({ "error": err }))
+                .map(|_| ())
+                .map_err(|_| warn!("could not write the error, the export stream was already closed"));
+            Box::new(error_line)
+        });
+    Arbiter::spawn(export);
+
+    let chunks = receiver
+        .map(ndjson_chunk)
+        .map_err(|_| error::ErrorInternalServerError("export stream failed"));
+
+    HttpResponse::Ok()
+        .content_type("application/x-ndjson")
+        .streaming(chunks)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_csv_field() {
+        assert_eq!(csv_field(&json!(null)), "");
+        assert_eq!(csv_field(&json!(42)), "42");
+        assert_eq!(csv_field(&json!("hello")), "hello");
+        assert_eq!(csv_field(&json!("hello, world")), "\"hello, world\"");
+        assert_eq!(csv_field(&json!("say \"hi\"")), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field(&json!({"$timestamp": "2019-04-20T16:20:00"})), "2019-04-20T16:20:00");
+        assert_eq!(csv_field(&json!({"$date": "2019-04-20"})), "2019-04-20");
+        assert_eq!(csv_field(&json!({"a": 1})), "\"{\"\"a\"\":1}\"");
+        assert_eq!(csv_field(&json!("=HYPERLINK(\"http://example.com\")")), "\"'=HYPERLINK(\"\"http://example.com\"\")\"");
+        assert_eq!(csv_field(&json!("+1")), "'+1");
+        assert_eq!(csv_field(&json!("-1")), "'-1");
+        assert_eq!(csv_field(&json!("@SUM(A1)")), "'@SUM(A1)");
+        assert_eq!(csv_field(&json!(-1)), "-1");
+    }
+
+    #[test]
+    fn test_applies_to() {
+        assert!(ExportFormat::Csv.applies_to("queryTableData"));
+        assert!(ExportFormat::Ndjson.applies_to("runQuery"));
+        assert!(!ExportFormat::Csv.applies_to("insertTableData"));
+        assert!(ExportFormat::Json.applies_to("insertTableData"));
+    }
+
+    #[test]
+    fn test_table_rows() {
+        let data = json!({
+            "columns": { "keys": [], "values": ["id", "name"] },
+            "data": [
+                { "keys": [], "values": [1, "Bob"] },
+                { "keys": [], "values": [2, null] }
+            ]
+        });
+
+        let (columns, rows) = table_rows(data).unwrap();
+        assert_eq!(csv_line(&columns), "id,name\r\n");
+        assert_eq!(csv_line(&rows[0]), "1,Bob\r\n");
+        assert_eq!(csv_line(&rows[1]), "2,\r\n");
+
+        assert!(table_rows(json!([1, 2, 3])).is_none());
+    }
+
+    #[test]
+    fn test_from_accept() {
+        assert_eq!(ExportFormat::from_accept("text/csv"), Some(ExportFormat::Csv));
+        assert_eq!(ExportFormat::from_accept("application/x-ndjson, application/json;q=0.5"), Some(ExportFormat::Ndjson));
+        assert_eq!(ExportFormat::from_accept("application/json;q=0.5, text/csv"), Some(ExportFormat::Csv));
+        assert_eq!(ExportFormat::from_accept("text/html,application/xhtml+xml,*/*;q=0.8"), Some(ExportFormat::Json));
+        assert_eq!(ExportFormat::from_accept("text/csv;q=0, application/json"), Some(ExportFormat::Json));
+        assert_eq!(ExportFormat::from_accept("image/png"), None);
+    }
+
+    #[test]
+    fn test_ndjson_chunk() {
+        let data = json!({
+            "columns": { "keys": [], "values": ["id", "name"] },
+            "data": [
+                { "keys": [], "values": [1, "Bob"] },
+                { "keys": [], "values": [2, null] }
+            ]
+        });
+
+        let chunk = ndjson_chunk(data);
+        assert_eq!(&chunk[..], &b"{\"id\":1,\"name\":\"Bob\"}\n{\"id\":2,\"name\":null}\n"[..]);
+
+        let chunk = ndjson_chunk(json!({ "error": "oops" }));
+        assert_eq!(&chunk[..], &b"{\"error\":\"oops\"}\n"[..]);
+    }
+}
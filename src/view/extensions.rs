@@ -1,6 +1,7 @@
 
 use std::sync::Arc;
 use std::fmt::Debug;
+use std::marker::PhantomData;
 
 use serde::Serialize;
 
@@ -22,12 +23,31 @@ use view::procedure::ProcedureBuilder;
 use view::procedure::ProcedureHandler;
 use view::procedure::procedure_handler_function;
 use view::procedure::procedure_bad_request_handler_function;
+use view::body_limit::BodyLimits;
+use view::procedure::with_permission_required;
 
 use model::actions::Action;
-
-use view::routes::users;
-use view::routes::manage;
+use model::actions::decorator::Requirements;
+use model::actions::decorator::WithAudit;
+use model::actions::decorator::WithPermissionRequired;
+use model::actions::decorator::WithWriteAccess;
+use model::actions::ExportTableData;
+use model::actions::ImportTableData;
+use model::actions::policy::DecoratorPolicy;
+use model::actions::policy::PolicyViolation;
+use model::actions::policy::PolicyViolations;
+
+use view::registry::Procedure;
+use view::registry::ProcedureVisitor;
+use view::registry::visit_procedures;
 use view::websocket;
+use view::long_poll;
+use view::export;
+use view::versioning::ApiVersion;
+use view::versioning::Mounted;
+use view::import;
+use view::batch;
+use view::health;
 
 use connection::executor::Executor;
 use connection::AppStateLike;
@@ -43,9 +63,10 @@ pub trait ProcedureExt<S>
     ///
     /// # Arguments
     /// * `path` - A string representing the url path
+    /// * `body_limits` - The limits of the json body, the one of the path is used
     /// * `procedure_builder` - An object extending `ProcedureBuilder` for building a message
     ///
-    fn add_route<JP, QP, A, PB>(&mut self, path: &str, procedure_builder: PB) -> &mut Self
+    fn add_route<JP, QP, A, PB>(&mut self, path: &str, body_limits: &BodyLimits, procedure_builder: PB) -> &mut Self
         where
             Executor: Handler<ActionWrapper<A>>,
             A: Action + Send + 'static,
@@ -56,20 +77,104 @@ pub trait ProcedureExt<S>
             Query<QP>: FromRequest<S>,
             <A as Action>::Ret: Send + Serialize;
 
+    /// Create an RPC call which requires the given permissions
+    /// The permission check is applied here, so the action doesn't have to wire it in its constructor
+    ///
+    /// # Arguments
+    /// * `path` - A string representing the url path
+    /// * `body_limits` - The limits of the json body, the one of the path is used
+    /// * `permissions` - The permissions required for calling the procedure
+    /// * `procedure_builder` - A function building the message
+    ///
+    fn add_route_with_permission<JP, QP, A, PB>(&mut self, path: &str, body_limits: &BodyLimits, permissions: Requirements, procedure_builder: PB) -> &mut Self
+        where
+            Executor: Handler<ActionWrapper<WithPermissionRequired<A>>>,
+            A: Action + Send + 'static,
+            PB: FnOnce(JP, QP) -> Result<(Option<String>, A), serde_json::Error> + Clone + 'static,
+            JP: Debug + 'static,
+            QP: Debug + 'static,
+            Json<JP>: FromRequest<S, Config = JsonConfig<S>>,
+            Query<QP>: FromRequest<S>,
+            <A as Action>::Ret: Send + Serialize,
+    {
+        self.add_route(path, body_limits, with_permission_required(procedure_builder, permissions))
+    }
+
     /// Add the socket routes
     fn add_socket(&mut self, path: &str) -> &mut Self;
 
-    /// Add all the routes for the actix web server
-    fn add_routes(&mut self) -> &mut Self;
+    /// Add the long polling fallback for messages
+    fn add_long_poll(&mut self, path: &str) -> &mut Self;
+
+    /// Add the streaming export of table data
+    fn add_table_export(&mut self, path: &str) -> &mut Self;
+
+    /// Add the multipart upload of files with table data, up to the `upload` limit
+    fn add_table_import(&mut self, path: &str, body_limits: &BodyLimits) -> &mut Self;
+
+    /// Add the route running several procedures in one request
+    fn add_batch(&mut self, path: &str, body_limits: &BodyLimits) -> &mut Self;
+
+    /// Add the liveness and readiness checks, they don't need a token
+    fn add_health_probes(&mut self, liveness_path: &str, readiness_path: &str) -> &mut Self;
+
+    /// Add all the routes for the actix web server, every version under its prefix and the unversioned paths
+    fn add_routes(&mut self, body_limits: &BodyLimits) -> &mut Self
+        where Self: Sized,
+    {
+        for version in ApiVersion::all() {
+            Mounted::new(self, version).add_api_routes(version, body_limits);
+        }
+
+        self
+            .add_api_routes(ApiVersion::LEGACY, body_limits)
+            .add_health_probes("/healthz", "/readyz")
+    }
+
+    /// The routes of a version, the procedures are the ones of its registry, see `visit_procedures`
+    fn add_api_routes(&mut self, version: ApiVersion, body_limits: &BodyLimits) -> &mut Self
+        where Self: Sized,
+    {
+        visit_procedures(version, &mut HttpRoutes(self, body_limits));
+
+        self
+            .add_socket("/listen")
+            .add_long_poll("/messages/poll")
+            .add_table_export("/manage/exportTableData")
+            .add_table_import("/manage/importTableData", body_limits)
+            .add_batch("/batch", body_limits)
+    }
 
 }
 
 
+/// Adds the http route of every procedure served over http
+struct HttpRoutes<'a, P: 'a>(&'a mut P, &'a BodyLimits);
+
+impl<'a, S, P> ProcedureVisitor<S> for HttpRoutes<'a, P>
+    where
+        S: AppStateLike + 'static,
+        P: ProcedureExt<S>,
+{
+    fn visit<A, PB>(&mut self, procedure: Procedure, procedure_builder: PB) -> &mut Self
+        where
+            Executor: Handler<ActionWrapper<A>>,
+            A: Action + Send + 'static,
+            PB: ProcedureBuilder<S, serde_json::Value, serde_json::Value, A> + Clone + 'static,
+            <A as Action>::Ret: Send + Serialize,
+    {
+        if procedure.http {
+            self.0.add_route(&procedure.http_path(), self.1, procedure_builder);
+        }
+        self
+    }
+}
+
 impl<S> ProcedureExt<S> for CorsBuilder<S>
     where
         S: AppStateLike + 'static,
 {
-    fn add_route<JP, QP, A, PB>(&mut self, path: &str, procedure_builder: PB) -> &mut Self
+    fn add_route<JP, QP, A, PB>(&mut self, path: &str, body_limits: &BodyLimits, procedure_builder: PB) -> &mut Self
         where
             Executor: Handler<ActionWrapper<A>>,
             A: Action + Send + 'static,
@@ -80,16 +185,18 @@ impl<S> ProcedureExt<S> for CorsBuilder<S>
             Query<QP>: FromRequest<S>,
             <A as Action>::Ret: Send + Serialize,
     {
+        let limit = body_limits.for_path(path);
         self.resource(path, move |r| {
             r.method(http::Method::POST).with_config(
                 move |(req, json_params, query_params): (HttpRequest<S>, Json<JP>, Query<QP>)| {
                     let proc = ProcedureHandler::<S, JP, QP, PB, A>::setup(&procedure_builder);
                     procedure_handler_function(proc, req, json_params, query_params)
                 },
-                |((_, json_cfg, _query_cfg),)| {
+                move |((_, json_cfg, _query_cfg),)| {
                     json_cfg
-                        .error_handler(|err, _req| {
-                            procedure_bad_request_handler_function(err)
+                        .limit(limit)
+                        .error_handler(move |err, _req| {
+                            procedure_bad_request_handler_function(err, limit)
                         });
                 }
             );
@@ -100,64 +207,43 @@ impl<S> ProcedureExt<S> for CorsBuilder<S>
         self.resource(path, |r| r.f(websocket::handler))
     }
 
-    //TODO: put this in a macro, we are using this in the sockets as well
-    fn add_routes(&mut self) -> &mut Self {
-        self
-            .add_route("/manage/getAllDomains", manage::get_all_domains)
-            //TODO: manage domains?
-
-            .add_route("/manage/getAllTables", manage::get_all_tables)
-            .add_route("/manage/getAllQueries", manage::get_all_queries)
-            .add_route("/manage/getAllScripts", manage::get_all_scripts)
-
-            .add_route("/manage/getTable", manage::get_table)
-            .add_route("/manage/getQuery", manage::get_query)
-            .add_route("/manage/getScript", manage::get_script)
-
-            .add_route("/manage/createTable", manage::create_table)
-            .add_route("/manage/createQuery", manage::create_query)
-            .add_route("/manage/createScript", manage::create_script)
-
-            .add_route("/manage/updateTable", manage::update_table)
-            .add_route("/manage/updateQuery", manage::update_query)
-            .add_route("/manage/updateScript", manage::update_script)
-
-            .add_route("/manage/deleteTable", manage::delete_table)
-            .add_route("/manage/deleteQuery", manage::delete_query)
-            .add_route("/manage/deleteScript", manage::delete_script)
-
-            .add_route("/manage/queryTableData", manage::query_table_data)
-            .add_route("/manage/insertTableData", manage::insert_table_data)
-            .add_route("/manage/modifyTableData", manage::modify_table_data)
-            .add_route("/manage/removeTableData", manage::remove_table_data)
-
-            .add_route("/manage/runQuery", manage::run_query)
-            .add_route("/manage/runScript", manage::run_script)
-
-            //TODO: subscriptions maybe?
-
-            .add_route("/users/login", users::login)
-            .add_route("/users/refresh", users::refresh)
-            .add_route("/users/logout", users::logout)
-            .add_route("/users/getAllUsers", users::get_all_users)
-
-            .add_route("/users/addUser", users::add_user)
-            .add_route("/users/removeUser", users::remove_user)
-            .add_route("/users/inviteUser", users::invite_user)
-            .add_route("/users/setupUser", users::setup_user)
-            .add_route("/users/setUserPassword", users::set_user_password)
+    fn add_long_poll(&mut self, path: &str) -> &mut Self {
+        self.resource(path, |r| r.method(http::Method::GET).with(long_poll::handler))
+    }
 
-            .add_route("/users/addRole", users::add_role)
-            .add_route("/users/removeRole", users::remove_role)
-            .add_route("/users/getAllRoles", users::get_all_roles)
+    fn add_table_export(&mut self, path: &str) -> &mut Self {
+        self.resource(path, |r| r.method(http::Method::POST).with(export::ndjson_handler))
+    }
 
-            .add_route("/users/attachPermissionForRole", users::attach_permission_for_role)
-            .add_route("/users/detachPermissionForRole", users::detach_permission_for_role)
+    fn add_table_import(&mut self, path: &str, body_limits: &BodyLimits) -> &mut Self {
+        let limit = body_limits.upload;
+        self.resource(path, move |r| {
+            r.method(http::Method::POST).with(move |params: (HttpRequest<S>, Query<import::ImportParams>)| {
+                import::multipart_handler(params, limit)
+            })
+        })
+    }
 
-            .add_route("/users/attachRoleForUser", users::attach_role_for_user)
-            .add_route("/users/detachRoleForUser", users::detach_role_for_user)
+    fn add_batch(&mut self, path: &str, body_limits: &BodyLimits) -> &mut Self {
+        let limit = body_limits.for_path(path);
+        self.resource(path, move |r| {
+            r.method(http::Method::POST).with_config(
+                batch::batch_handler,
+                move |((_, json_cfg, _query_cfg),)| {
+                    json_cfg
+                        .limit(limit)
+                        .error_handler(move |err, _req| {
+                            procedure_bad_request_handler_function(err, limit)
+                        });
+                }
+            );
+        })
+    }
 
-            .add_socket("/listen")
+    fn add_health_probes(&mut self, liveness_path: &str, readiness_path: &str) -> &mut Self {
+        self
+            .resource(liveness_path, |r| r.method(http::Method::GET).f(health::healthz_handler))
+            .resource(readiness_path, |r| r.method(http::Method::GET).f(health::readyz_handler))
     }
 }
 
@@ -165,7 +251,7 @@ impl<S> ProcedureExt<S> for TestApp<S>
     where
         S: AppStateLike + 'static,
 {
-    fn add_route<JP, QP, A, PB>(&mut self, path: &str, procedure_builder: PB) -> &mut Self
+    fn add_route<JP, QP, A, PB>(&mut self, path: &str, body_limits: &BodyLimits, procedure_builder: PB) -> &mut Self
         where
             Executor: Handler<ActionWrapper<A>>,
             A: Action + Send + 'static,
@@ -176,16 +262,18 @@ impl<S> ProcedureExt<S> for TestApp<S>
             Query<QP>: FromRequest<S>,
             <A as Action>::Ret: Send + Serialize,
     {
+        let limit = body_limits.for_path(path);
         self.resource(path, move |r| {
             r.method(http::Method::POST).with_config(
                 move |(req, json_params, query_params): (HttpRequest<S>, Json<JP>, Query<QP>)| {
                     let proc = ProcedureHandler::<S, JP, QP, PB, A>::setup(&procedure_builder);
                     procedure_handler_function(proc, req, json_params, query_params)
                 },
-                |((_, json_cfg, _query_cfg),)| {
+                move |((_, json_cfg, _query_cfg),)| {
                     json_cfg
-                        .error_handler(|err, _req| {
-                            procedure_bad_request_handler_function(err)
+                        .limit(limit)
+                        .error_handler(move |err, _req| {
+                            procedure_bad_request_handler_function(err, limit)
                         });
                 }
             );
@@ -196,59 +284,124 @@ impl<S> ProcedureExt<S> for TestApp<S>
         self.resource(path, |r| r.f(websocket::handler))
     }
 
-    fn add_routes(&mut self) -> &mut Self {
-        self
-            .add_route("/manage/getAllDomains", manage::get_all_domains)
+    fn add_long_poll(&mut self, path: &str) -> &mut Self {
+        self.resource(path, |r| r.method(http::Method::GET).with(long_poll::handler))
+    }
 
-            .add_route("/manage/getAllTables", manage::get_all_tables)
-            .add_route("/manage/getAllQueries", manage::get_all_queries)
-            .add_route("/manage/getAllScripts", manage::get_all_scripts)
+    fn add_table_export(&mut self, path: &str) -> &mut Self {
+        self.resource(path, |r| r.method(http::Method::POST).with(export::ndjson_handler))
+    }
 
-            .add_route("/manage/getTable", manage::get_table)
-            .add_route("/manage/getQuery", manage::get_query)
-            .add_route("/manage/getScript", manage::get_script)
+    fn add_table_import(&mut self, path: &str, body_limits: &BodyLimits) -> &mut Self {
+        let limit = body_limits.upload;
+        self.resource(path, move |r| {
+            r.method(http::Method::POST).with(move |params: (HttpRequest<S>, Query<import::ImportParams>)| {
+                import::multipart_handler(params, limit)
+            })
+        })
+    }
 
-            .add_route("/manage/createTable", manage::create_table)
-            .add_route("/manage/createQuery", manage::create_query)
-            .add_route("/manage/createScript", manage::create_script)
+    fn add_batch(&mut self, path: &str, body_limits: &BodyLimits) -> &mut Self {
+        let limit = body_limits.for_path(path);
+        self.resource(path, move |r| {
+            r.method(http::Method::POST).with_config(
+                batch::batch_handler,
+                move |((_, json_cfg, _query_cfg),)| {
+                    json_cfg
+                        .limit(limit)
+                        .error_handler(move |err, _req| {
+                            procedure_bad_request_handler_function(err, limit)
+                        });
+                }
+            );
+        })
+    }
 
-            .add_route("/manage/updateTable", manage::update_table)
-            .add_route("/manage/updateQuery", manage::update_query)
-            .add_route("/manage/updateScript", manage::update_script)
+    fn add_health_probes(&mut self, liveness_path: &str, readiness_path: &str) -> &mut Self {
+        self
+            .resource(liveness_path, |r| r.method(http::Method::GET).f(health::healthz_handler))
+            .resource(readiness_path, |r| r.method(http::Method::GET).f(health::readyz_handler))
+    }
+}
 
-            .add_route("/manage/deleteTable", manage::delete_table)
-            .add_route("/manage/deleteQuery", manage::delete_query)
-            .add_route("/manage/deleteScript", manage::delete_script)
+/// Doesn't serve anything, only checks the decorators of every procedure against the policy
+/// so that the server can refuse to start if some action isn't properly decorated
+pub struct PolicyCheck<S> {
+    policy: DecoratorPolicy,
+    violations: Vec<PolicyViolation>,
+    phantom_data: PhantomData<S>,
+}
 
-            .add_route("/manage/queryTableData", manage::query_table_data)
-            .add_route("/manage/insertTableData", manage::insert_table_data)
-            .add_route("/manage/modifyTableData", manage::modify_table_data)
-            .add_route("/manage/removeTableData", manage::remove_table_data)
+impl<S> PolicyCheck<S> {
+    pub fn new(policy: DecoratorPolicy) -> Self {
+        Self {
+            policy,
+            violations: vec![],
+            phantom_data: PhantomData,
+        }
+    }
 
-            .add_route("/manage/runQuery", manage::run_query)
-            .add_route("/manage/runScript", manage::run_script)
+    pub fn verify(self) -> Result<(), PolicyViolations> {
+        if self.violations.is_empty() {
+            Ok(())
+        } else {
+            Err(PolicyViolations(self.violations))
+        }
+    }
+}
 
-            .add_route("/users/login", users::login)
-            .add_route("/users/refresh", users::refresh)
-            .add_route("/users/logout", users::logout)
-            .add_route("/users/getAllUsers", users::get_all_users)
+impl<S> ProcedureExt<S> for PolicyCheck<S>
+    where
+        S: AppStateLike + 'static,
+{
+    fn add_route<JP, QP, A, PB>(&mut self, path: &str, _body_limits: &BodyLimits, _procedure_builder: PB) -> &mut Self
+        where
+            Executor: Handler<ActionWrapper<A>>,
+            A: Action + Send + 'static,
+            PB: ProcedureBuilder<S, JP, QP, A> + Clone + 'static,
+            JP: Debug + 'static,
+            QP: Debug + 'static,
+            Json<JP>: FromRequest<S, Config = JsonConfig<S>>,
+            Query<QP>: FromRequest<S>,
+            <A as Action>::Ret: Send + Serialize,
+    {
+        let missing = self.policy.missing_decorators(&A::decorators());
+        if !missing.is_empty() {
+            self.violations.push(PolicyViolation { path: path.to_owned(), missing });
+        }
+        self
+    }
 
-            .add_route("/users/addUser", users::add_user)
-            .add_route("/users/removeUser", users::remove_user)
-            .add_route("/users/inviteUser", users::invite_user)
-            .add_route("/users/setupUser", users::setup_user)
-            .add_route("/users/setUserPassword", users::set_user_password)
+    fn add_socket(&mut self, _path: &str) -> &mut Self {
+        self
+    }
 
-            .add_route("/users/addRole", users::add_role)
-            .add_route("/users/removeRole", users::remove_role)
-            .add_route("/users/getAllRoles", users::get_all_roles)
+    fn add_long_poll(&mut self, _path: &str) -> &mut Self {
+        self
+    }
+
+    fn add_table_export(&mut self, path: &str) -> &mut Self {
+        let missing = self.policy.missing_decorators(&<WithPermissionRequired<ExportTableData>>::decorators());
+        if !missing.is_empty() {
+            self.violations.push(PolicyViolation { path: path.to_owned(), missing });
+        }
+        self
+    }
 
-            .add_route("/users/attachPermissionForRole", users::attach_permission_for_role)
-            .add_route("/users/detachPermissionForRole", users::detach_permission_for_role)
+    fn add_table_import(&mut self, path: &str, _body_limits: &BodyLimits) -> &mut Self {
+        let missing = self.policy.missing_decorators(&<WithAudit<WithPermissionRequired<WithWriteAccess<ImportTableData>>>>::decorators());
+        if !missing.is_empty() {
+            self.violations.push(PolicyViolation { path: path.to_owned(), missing });
+        }
+        self
+    }
 
-            .add_route("/users/attachRoleForUser", users::attach_role_for_user)
-            .add_route("/users/detachRoleForUser", users::detach_role_for_user)
+    /// the calls of a batch are the procedures, which are checked with their own routes
+    fn add_batch(&mut self, _path: &str, _body_limits: &BodyLimits) -> &mut Self {
+        self
+    }
 
-            .add_socket("/listen")
+    fn add_health_probes(&mut self, _liveness_path: &str, _readiness_path: &str) -> &mut Self {
+        self
     }
 }
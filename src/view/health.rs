@@ -0,0 +1,149 @@
+use std::time::Duration;
+
+use actix::prelude::*;
+use actix_web::AsyncResponder;
+use actix_web::Error as ActixError;
+use actix_web::HttpRequest;
+use actix_web::HttpResponse;
+
+use diesel::connection::SimpleConnection;
+
+use futures::Future;
+use futures::future;
+
+use connection::AppStateLike;
+use connection::executor::Executor;
+use connection::executor::PoolState;
+use metastore::migrations;
+use view::shutdown;
+
+type AsyncResponse = Box<Future<Item=HttpResponse, Error=ActixError>>;
+
+/// the probe fails if no executor answers in time, e.g. when all of them are stuck on slow actions
+const CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Checks out a metastore connection and runs `SELECT 1` on it, the readiness check also needs
+/// every migration to be applied
+#[derive(Debug)]
+pub struct CheckHealth {
+    pub readiness: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HealthStatus {
+    pub ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub pool: PoolState,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub migration_version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pending_migrations: Option<usize>,
+}
+
+impl Message for CheckHealth {
+    /// `Err` when the check failed, it has the status all the same
+    type Result = Result<HealthStatus, HealthStatus>;
+}
+
+impl Handler<CheckHealth> for Executor {
+    type Result = Result<HealthStatus, HealthStatus>;
+
+    fn handle(&mut self, msg: CheckHealth, _: &mut Self::Context) -> Self::Result {
+        let mut status = HealthStatus {
+            ok: false,
+            error: None,
+            pool: self.get_pool_state(),
+            migration_version: None,
+            pending_migrations: None,
+        };
+
+        let conn = match self.try_get_connection() {
+            Ok(conn) => conn,
+            Err(err) => {
+                status.error = Some(err);
+                return Err(status);
+            },
+        };
+
+        if let Err(err) = conn.batch_execute("SELECT 1;") {
+            status.error = Some(err.to_string());
+            return Err(status);
+        }
+
+        if msg.readiness {
+            let version = match migrations::current_version(&conn) {
+                Ok(version) => version,
+                Err(err) => {
+                    status.error = Some(err);
+                    return Err(status);
+                },
+            };
+            let applied = migrations::MIGRATIONS
+                .iter()
+                .position(|x| Some(x.name) == version)
+                .map(|x| x + 1)
+                .unwrap_or(0);
+            let pending = migrations::MIGRATIONS.len() - applied;
+
+            status.migration_version = version.map(|x| x.to_string());
+            status.pending_migrations = Some(pending);
+            if pending > 0 {
+                status.error = Some(format!("{} migrations are not applied", pending));
+                return Err(status);
+            }
+        }
+
+        status.ok = true;
+        Ok(status)
+    }
+}
+
+/// for the liveness probes
+pub fn healthz_handler<S>(req: &HttpRequest<S>) -> AsyncResponse
+    where
+        S: AppStateLike + 'static,
+{
+    check_health(req, false)
+}
+
+/// for the readiness probes and the load balancers
+pub fn readyz_handler<S>(req: &HttpRequest<S>) -> AsyncResponse
+    where
+        S: AppStateLike + 'static,
+{
+    // the load balancers stop sending requests while the open ones finish
+    if shutdown::is_shutting_down() {
+        let response = HttpResponse::ServiceUnavailable().json(json!({ "ok": false, "error": "shutting down" }));
+        return Box::new(future::ok(response));
+    }
+    check_health(req, true)
+}
+
+fn check_health<S>(req: &HttpRequest<S>, readiness: bool) -> AsyncResponse
+    where
+        S: AppStateLike + 'static,
+{
+    req
+        .state()
+        .connect()
+        .send(CheckHealth { readiness })
+        .timeout(CHECK_TIMEOUT)
+        .then(|res| -> Result<HttpResponse, ActixError> {
+            let response = match res {
+                Ok(Ok(status)) => HttpResponse::Ok().json(status),
+                Ok(Err(status)) => {
+                    warn!("health check failed: {:?}", &status);
+                    HttpResponse::ServiceUnavailable().json(status)
+                },
+                Err(err) => {
+                    warn!("health check failed: {:?}", &err);
+                    HttpResponse::ServiceUnavailable().json(json!({ "ok": false, "error": err.to_string() }))
+                },
+            };
+
+            Ok(response)
+        })
+        .responder()
+}
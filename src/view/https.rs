@@ -0,0 +1,99 @@
+use std::fs::File;
+use std::io::BufReader;
+
+use rustls::AllowAnyAuthenticatedClient;
+use rustls::Certificate;
+use rustls::NoClientAuth;
+use rustls::PrivateKey;
+use rustls::RootCertStore;
+use rustls::ServerConfig;
+use rustls::internal::pemfile;
+
+/// TLS for the http server and the sockets, so that a deployment doesn't need a proxy in front for https.
+/// The certificate and the key are PEM files, the key can be either PKCS#8 or RSA
+#[derive(Debug, Clone, PartialEq)]
+pub struct HttpsConfig {
+    /// the certificate chain, the certificate of the server first
+    pub cert_path: String,
+    pub key_path: String,
+    /// if set, the clients have to present a certificate signed by one of these
+    pub client_ca_path: Option<String>,
+}
+
+fn open(path: &str) -> Result<BufReader<File>, String> {
+    File::open(path)
+        .map(BufReader::new)
+        .map_err(|err| format!("could not open {}: {}", path, err))
+}
+
+impl HttpsConfig {
+    pub fn new(cert_path: &str, key_path: &str) -> Self {
+        Self {
+            cert_path: cert_path.to_string(),
+            key_path: key_path.to_string(),
+            client_ca_path: None,
+        }
+    }
+
+    pub fn client_ca_path(mut self, client_ca_path: &str) -> Self {
+        self.client_ca_path = Some(client_ca_path.to_string());
+        self
+    }
+
+    fn certificates(&self) -> Result<Vec<Certificate>, String> {
+        let certificates = pemfile::certs(&mut open(&self.cert_path)?)
+            .map_err(|_| format!("could not read the certificates in {}", &self.cert_path))?;
+        if certificates.is_empty() {
+            return Err(format!("no certificate found in {}", &self.cert_path));
+        }
+        Ok(certificates)
+    }
+
+    fn private_key(&self) -> Result<PrivateKey, String> {
+        let read_error = |_| format!("could not read the private key in {}", &self.key_path);
+        let mut keys = pemfile::pkcs8_private_keys(&mut open(&self.key_path)?).map_err(read_error)?;
+        if keys.is_empty() {
+            keys = pemfile::rsa_private_keys(&mut open(&self.key_path)?).map_err(read_error)?;
+        }
+
+        keys
+            .into_iter()
+            .next()
+            .ok_or_else(|| format!("no private key found in {}", &self.key_path))
+    }
+
+    /// the config of the server, the files are read and checked here so that a bad one stops the start
+    pub fn server_config(&self) -> Result<ServerConfig, String> {
+        let mut config = match &self.client_ca_path {
+            Some(client_ca_path) => {
+                let mut roots = RootCertStore::empty();
+                let (valid, _invalid) = roots
+                    .add_pem_file(&mut open(client_ca_path)?)
+                    .map_err(|_| format!("could not read the certificates in {}", client_ca_path))?;
+                if valid == 0 {
+                    return Err(format!("no certificate found in {}", client_ca_path));
+                }
+                ServerConfig::new(AllowAnyAuthenticatedClient::new(roots))
+            },
+            None => ServerConfig::new(NoClientAuth::new()),
+        };
+
+        config
+            .set_single_cert(self.certificates()?, self.private_key()?)
+            .map_err(|err| format!("invalid certificate or key: {}", err))?;
+
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_missing_files() {
+        let config = HttpsConfig::new("/nonexistent/cert.pem", "/nonexistent/key.pem");
+        let err = config.server_config().err().unwrap();
+        assert!(err.starts_with("could not open /nonexistent/cert.pem"));
+    }
+}
@@ -0,0 +1,160 @@
+use std::io::Write;
+
+use actix_web::dev::Payload;
+use actix_web::error;
+use actix_web::Error as ActixError;
+use actix_web::FutureResponse;
+use actix_web::HttpMessage;
+use actix_web::HttpRequest;
+use actix_web::HttpResponse;
+use actix_web::Query;
+use actix_web::http::header;
+use actix_web::multipart::Field;
+use actix_web::multipart::MultipartItem;
+
+use futures::Future;
+use futures::Stream;
+use futures::future;
+use tempfile::NamedTempFile;
+
+use connection::AppStateLike;
+use data::import::ImportFormat;
+use data::key_case::KeyCase;
+use data::key_case::KEY_CASE_HEADER;
+use model::actions::ImportTableData;
+use view::action_wrapper::ActionWrapper;
+use view::request_id::request_id;
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportParams {
+    pub name: String,
+    pub domain: String,
+    /// `csv` or `ndjson`, taken from the file name or the content type of the part otherwise
+    pub format: Option<String>,
+}
+
+/// the uploaded file, written to disk as it comes in
+#[derive(Debug)]
+struct Upload {
+    file: NamedTempFile,
+    format: Option<ImportFormat>,
+    size: usize,
+}
+
+fn payload_too_large(limit: usize) -> ActixError {
+    let resp = HttpResponse::PayloadTooLarge()
+        .json(json!({ "error": "Uploaded file is too large", "details": { "limit": limit } }));
+    error::InternalError::from_response("uploaded file is too large", resp).into()
+}
+
+fn bad_request(err: &str, request_id: &str) -> HttpResponse {
+    HttpResponse::BadRequest().json(json!({ "error": err, "requestId": request_id }))
+}
+
+/// Writes the part to a temporary file, which is removed once the import is done with it
+fn save_field(field: Field<Payload>, limit: usize) -> Box<Future<Item=Upload, Error=ActixError>> {
+    let format = field
+        .content_disposition()
+        .and_then(|x| x.get_filename().and_then(ImportFormat::from_file_name))
+        .or_else(|| ImportFormat::from_content_type(&field.content_type().to_string()));
+
+    let file = match NamedTempFile::new() {
+        Ok(file) => file,
+        Err(err) => return Box::new(future::err(error::ErrorInternalServerError(err))),
+    };
+
+    let upload = Upload { file, format, size: 0 };
+    let saved = field
+        .map_err(error::ErrorBadRequest)
+        .fold(upload, move |mut upload, bytes| {
+            upload.size += bytes.len();
+            if upload.size > limit {
+                return Err(payload_too_large(limit));
+            }
+            upload.file
+                .write_all(&bytes)
+                .map_err(error::ErrorInternalServerError)?;
+            Ok(upload)
+        });
+
+    Box::new(saved)
+}
+
+/// Takes a multipart upload of a csv or ndjson file and inserts its rows in the table, see `ImportTableData`
+/// The first part is the file, the other ones are read and dropped. The file goes to disk instead of memory,
+/// so it can be much bigger than a json body, up to `limit`, the `upload` body limit
+/// The progress is published on the channel of the user, the response is sent once all the rows are in
+pub fn multipart_handler<S>((req, params): (HttpRequest<S>, Query<ImportParams>), limit: usize) -> FutureResponse<HttpResponse>
+    where
+        S: AppStateLike + 'static,
+{
+    let params = params.into_inner();
+    let request_id = request_id(&req);
+    debug!("importing table data {:?} [{}]", &params, &request_id);
+
+    let format_param = match params.format.as_ref().map(|x| ImportFormat::from_param(x)) {
+        Some(Ok(format)) => Some(format),
+        Some(Err(err)) => return Box::new(future::ok(bad_request(&err, &request_id))),
+        None => None,
+    };
+
+    let auth = req.headers().get(header::AUTHORIZATION).map(|x| x.as_bytes().to_owned());
+    let key_case = req.headers().get(KEY_CASE_HEADER).and_then(|x| KeyCase::from_header(x.as_bytes()));
+    let state_req = req.clone();
+
+    let import = req
+        .multipart()
+        .map_err(error::ErrorBadRequest)
+        .fold(None, move |upload: Option<Upload>, item| -> Box<Future<Item=Option<Upload>, Error=ActixError>> {
+            match item {
+                MultipartItem::Field(field) => match upload {
+                    None => Box::new(save_field(field, limit).map(Some)),
+                    Some(upload) => Box::new(field
+                        .map_err(error::ErrorBadRequest)
+                        .for_each(|_| Ok(()))
+                        .map(move |_| Some(upload))),
+                },
+                MultipartItem::Nested(_) => Box::new(future::err(error::ErrorBadRequest("nested multipart bodies are not supported"))),
+            }
+        })
+        .and_then(move |upload| -> FutureResponse<HttpResponse> {
+            let upload = match upload {
+                Some(upload) => upload,
+                None => return Box::new(future::ok(bad_request("No file was uploaded", &request_id))),
+            };
+            let format = match format_param.or(upload.format) {
+                Some(format) => format,
+                None => return Box::new(future::ok(bad_request("Unknown file format, set `format` to csv or ndjson", &request_id))),
+            };
+            debug!("received {} bytes of {:?} [{}]", upload.size, &format, &request_id);
+
+            let action = ImportTableData::<_>::new(params.name, upload.file, format);
+            let mut action_wrapper = ActionWrapper::new(Ok((Some(params.domain), action)))
+                .with_request_id(&request_id);
+            if let Some(auth) = auth {
+                action_wrapper = action_wrapper.with_auth(&auth);
+            }
+            if let Some(key_case) = key_case {
+                action_wrapper = action_wrapper.with_key_case(key_case);
+            }
+
+            let response = state_req
+                .state()
+                .connect_queries()
+                .send(action_wrapper)
+                .from_err()
+                .and_then(move |res| match res {
+                    Ok(ok_res) => Ok(HttpResponse::Ok().json(ok_res.get_data())),
+                    Err(err) => {
+                        debug!("Responding with error message: {:?} [{}]", &err, &request_id);
+                        let mut envelope = err.envelope();
+                        envelope["requestId"] = json!(request_id);
+                        Ok(HttpResponse::InternalServerError().json(envelope))
+                    },
+                });
+            Box::new(response)
+        });
+
+    Box::new(import)
+}
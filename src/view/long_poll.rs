@@ -0,0 +1,96 @@
+
+use std::time::Duration;
+use std::time::Instant;
+
+use actix_web::AsyncResponder;
+use actix_web::error;
+use actix_web::Error as ActixError;
+use actix_web::HttpRequest;
+use actix_web::HttpResponse;
+use actix_web::Query;
+use actix_web::http::header;
+
+use futures::Future;
+use futures::future;
+use tokio::timer::Delay;
+
+use connection::AppStateLike;
+use model::actions::GetMessages;
+use view::action_wrapper::ActionWrapper;
+use view::request_id::request_id;
+
+type AsyncResponse = Box<Future<Item=HttpResponse, Error=ActixError>>;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(500); // same as the websocket message process
+const DEFAULT_TIMEOUT: u64 = 30; // seconds
+const MAX_TIMEOUT: u64 = 60; // seconds
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct PollParams {
+    /// cursor returned by the previous poll, defaults to now
+    pub after: Option<chrono::NaiveDateTime>,
+    /// how many seconds to wait for a message before returning an empty list
+    pub timeout: Option<u64>,
+}
+
+/// Long polling fallback for clients that can't keep a websocket open
+/// Waits until there is at least one message after the cursor, or until the timeout
+pub fn handler<S>((req, params): (HttpRequest<S>, Query<PollParams>)) -> AsyncResponse
+    where
+        S: AppStateLike + 'static,
+{
+    let params = params.into_inner();
+    debug!("long poll for messages {:?}", &params);
+
+    let after = params.after.unwrap_or_else(|| chrono::Utc::now().naive_utc());
+    let timeout = params.timeout.unwrap_or(DEFAULT_TIMEOUT).min(MAX_TIMEOUT);
+    let deadline = Instant::now() + Duration::from_secs(timeout);
+    let auth_header = req.headers().get(header::AUTHORIZATION).map(|x| x.as_bytes().to_owned());
+
+    poll(req, auth_header, after, deadline)
+}
+
+fn poll<S>(req: HttpRequest<S>, auth_header: Option<Vec<u8>>, after: chrono::NaiveDateTime, deadline: Instant) -> AsyncResponse
+    where
+        S: AppStateLike + 'static,
+{
+    let now = chrono::Utc::now().naive_utc();
+    let action = GetMessages::<_>::new(after, now);
+    let mut action_wrapper = ActionWrapper::new(Ok((None, action)))
+        .with_request_id(&request_id(&req));
+    if let Some(ref auth) = auth_header {
+        action_wrapper = action_wrapper.with_auth(auth);
+    }
+
+    let request = req
+        .state()
+        .connect()
+        .send(action_wrapper);
+
+    request
+        .from_err()
+        .and_then(move |res| -> AsyncResponse {
+            match res {
+                Ok(ok_res) => {
+                    let messages = ok_res.get_data();
+                    if !messages.is_empty() || Instant::now() >= deadline {
+                        debug!("long poll responding with {} messages", messages.len());
+                        Box::new(future::ok(HttpResponse::Ok()
+                            .json(json!({ "messages": messages, "cursor": now }))))
+                    } else {
+                        let next_poll = Delay::new(Instant::now() + POLL_INTERVAL)
+                            .map_err(|err| error::ErrorInternalServerError(err))
+                            .and_then(move |_| poll(req, auth_header, now, deadline));
+                        Box::new(next_poll)
+                    }
+                },
+                Err(err) => {
+                    debug!("Responding with error message: {:?}", &err);
+                    Box::new(future::ok(HttpResponse::InternalServerError()
+                        .json(json!({ "error": err.to_string() }))))
+                }
+            }
+        })
+        .responder()
+}
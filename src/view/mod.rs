@@ -9,6 +9,23 @@ pub mod routes;
 pub mod action_wrapper;
 pub mod extensions;
 pub mod bearer_token;
+pub mod long_poll;
+pub mod export;
+pub mod import;
+pub mod batch;
+pub mod health;
+pub mod conditional;
+pub mod rate_limit;
+pub mod request_id;
+pub mod cors;
+pub mod compression;
+pub mod body_limit;
+pub mod versioning;
+pub mod registry;
+pub mod schemas;
+pub mod https;
+pub mod shutdown;
+pub mod static_assets;
 
 use std::result::Result;
 use std::result::Result::Ok;
@@ -43,6 +60,8 @@ mod test {
         let _all_tables_action = manage::get_all_tables(data.to_owned(), query.to_owned()).unwrap();
         let _all_queries_action = manage::get_all_queries(data.to_owned(), query.to_owned()).unwrap();
         let _all_scripts_action = manage::get_all_scripts(data.to_owned(), query.to_owned()).unwrap();
+        let _all_views_action = manage::get_all_views(data.to_owned(), query.to_owned()).unwrap();
+        let _all_webhooks_action = manage::get_all_webhooks(data.to_owned(), query.to_owned()).unwrap();
     }
 
     #[test]
@@ -54,6 +73,8 @@ mod test {
         let _get_table_action = manage::get_table(data.to_owned(), query.to_owned()).unwrap();
         let _get_query_action = manage::get_query(data.to_owned(), query.to_owned()).unwrap();
         let _get_script_action = manage::get_script(data.to_owned(), query.to_owned()).unwrap();
+        let _get_view_action = manage::get_view(data.to_owned(), query.to_owned()).unwrap();
+        let _get_webhook_action = manage::get_webhook(data.to_owned(), query.to_owned()).unwrap();
     }
 
     #[test]
@@ -87,6 +108,22 @@ mod test {
             "text": "print('hello world')"
         });
         let _create_script_action = manage::create_script(data.to_owned(), query.to_owned()).unwrap();
+
+        data = json!({
+            "name": "view_name",
+            "description": "this is a really cool view",
+            "statement": "SELECT * FROM awesome_table"
+        });
+        let _create_view_action = manage::create_view(data.to_owned(), query.to_owned()).unwrap();
+
+        data = json!({
+            "name": "webhook_name",
+            "description": "this is a really cool webhook",
+            "url": "https://example.com/hooks/kakapo",
+            "secret": "hunter2",
+            "channels": [{"table": "awesome_table"}]
+        });
+        let _create_webhook_action = manage::create_webhook(data.to_owned(), query.to_owned()).unwrap();
     }
 
     #[test]
@@ -123,6 +160,23 @@ mod test {
             "text": "print('hello world')"
         });
         let _update_script_action = manage::update_script(data.to_owned(), query.to_owned()).unwrap();
+
+        data = json!({
+            "name": "view_name",
+            "description": "this is a really cool view",
+            "statement": "SELECT * FROM awesome_table"
+        });
+        let _update_view_action = manage::update_view(data.to_owned(), query.to_owned()).unwrap();
+
+        data = json!({
+            "name": "webhook_name",
+            "description": "this is a really cool webhook",
+            "url": "https://example.com/hooks/kakapo",
+            "secret": "hunter2",
+            "channels": [{"table": "awesome_table"}],
+            "retry": {"maxAttempts": 3}
+        });
+        let _update_webhook_action = manage::update_webhook(data.to_owned(), query.to_owned()).unwrap();
     }
 
     #[test]
@@ -134,6 +188,8 @@ mod test {
         let _delete_table_action = manage::delete_table(data.to_owned(), query.to_owned()).unwrap();
         let _delete_query_action = manage::delete_query(data.to_owned(), query.to_owned()).unwrap();
         let _delete_script_action = manage::delete_script(data.to_owned(), query.to_owned()).unwrap();
+        let _delete_view_action = manage::delete_view(data.to_owned(), query.to_owned()).unwrap();
+        let _delete_webhook_action = manage::delete_webhook(data.to_owned(), query.to_owned()).unwrap();
     }
 
     #[test]
@@ -15,14 +15,26 @@ use actix_web::Query;
 use actix_web::HttpRequest;
 use actix_web::HttpResponse;
 use actix_web::http::header;
+use actix_web::http::header::HeaderValue;
 
 use futures::Future;
+use futures::future;
 
 use connection::executor::Executor;
 use connection::AppStateLike;
 
 use model::actions::Action;
+use model::actions::decorator::Requirements;
+use model::actions::decorator::WithPermissionRequired;
 use view::action_wrapper::ActionWrapper;
+use view::action_wrapper::send_action;
+use data::key_case::KeyCase;
+use data::key_case::KEY_CASE_HEADER;
+use view::export::ExportFormat;
+use view::export::FORMAT_PARAM;
+use view::export::FORMATTED_PROCEDURES;
+use view::conditional;
+use view::request_id::request_id;
 
 type AsyncResponse = Box<Future<Item=HttpResponse, Error=ActixError>>;
 
@@ -66,6 +78,19 @@ impl<S, JP, QP, A, F> ProcedureBuilder<S, JP, QP, A> for F
     }
 }
 
+/// Wraps a procedure builder so that the action it builds is only run if the caller has the
+/// required permissions. Used for declaring the permissions when registering the procedure
+pub fn with_permission_required<JP, QP, A, PB>(procedure_builder: PB, permissions: Requirements)
+    -> impl FnOnce(JP, QP) -> Result<(Option<String>, WithPermissionRequired<A>), serde_json::Error> + Clone
+    where
+        PB: FnOnce(JP, QP) -> Result<(Option<String>, A), serde_json::Error> + Clone,
+        A: Action,
+{
+    move |json_param, query_params| {
+        procedure_builder(json_param, query_params)
+            .map(|(domain, action)| (domain, WithPermissionRequired::with_requirements(action, permissions)))
+    }
+}
 
 /// Container struct for implemeting the `dev::Handler<AppState>` trait
 /// This will extract the `ProcedureBuilder` and execute it asynchronously
@@ -124,39 +149,125 @@ pub fn procedure_handler_function<S, JP, QP, PB, A>(
         S: AppStateLike,
 {
 
-    debug!("Procedure called on {:?} QUERY {:?} JSON {:?}", req.path(), &json_params, &query_params);
+    let request_id = request_id(&req);
+    debug!("Procedure called on {:?} QUERY {:?} JSON {:?} [{}]", req.path(), &json_params, &query_params, &request_id);
+    // the procedure is the last part of the path, whatever the version and the group
+    let procedure = req.path().rsplit('/').next().unwrap_or_default().to_owned();
+
+    // the `format` param wins over the `Accept` header, so that a link can ask for a csv
+    let format_param = req.query().get(FORMAT_PARAM).map(|x| x.to_owned());
+    // only the procedures returning table data are negotiated, the other ones answer with json whatever is accepted
+    let accept = req.headers()
+        .get(header::ACCEPT)
+        .and_then(|x| x.to_str().ok())
+        .filter(|_| FORMATTED_PROCEDURES.contains(&procedure.as_str()))
+        .map(|x| x.to_owned());
+    let is_negotiated = format_param.is_none() && accept.is_some();
+    let format = match (format_param, accept) {
+        (Some(param), _) => match ExportFormat::from_param(Some(&param)) {
+            Ok(format) if format.applies_to(&procedure) => format,
+            // rejected before the action runs, it would be committed otherwise
+            Ok(_) => return Box::new(future::ok::<_, ActixError>(HttpResponse::BadRequest().json(json!({
+                "error": format!("the `{}` format is only supported by {}", param, FORMATTED_PROCEDURES.join(", ")),
+                "requestId": request_id,
+            })))),
+            Err(err) => return Box::new(future::ok::<_, ActixError>(HttpResponse::BadRequest().json(json!({ "error": err, "requestId": request_id })))),
+        },
+        (None, Some(accept)) => match ExportFormat::from_accept(&accept) {
+            Some(format) => format,
+            None => return Box::new(future::ok::<_, ActixError>(HttpResponse::NotAcceptable().json(json!({
+                "error": format!("cannot respond with any of `{}`", accept),
+                "details": { "supported": ExportFormat::media_types() },
+                "requestId": request_id,
+            })))),
+        },
+        (None, None) => ExportFormat::default(),
+    };
+
     let action = procedure_handler.builder.build(json_params.into_inner(), query_params.into_inner());
     let state = req.state();
 
     let auth_header = req.headers().get(header::AUTHORIZATION).map(|x| x.as_bytes());
-    let mut action_wrapper = ActionWrapper::new(action);
+    let mut action_wrapper = ActionWrapper::new(action)
+        .with_request_id(&request_id);
     if let Some(auth) = auth_header {
         action_wrapper = action_wrapper.with_auth(auth);
     }
 
-    state
-        .connect()
-        .send(action_wrapper)
+    let key_case = req.headers().get(KEY_CASE_HEADER).and_then(|x| KeyCase::from_header(x.as_bytes()));
+    if let Some(key_case) = key_case {
+        action_wrapper = action_wrapper.with_key_case(key_case);
+    }
+
+    if let Some(timeout) = state.action_timeout(&procedure) {
+        action_wrapper = action_wrapper.with_timeout(&procedure, timeout);
+    }
+
+    let if_none_match: Vec<String> = req
+        .headers()
+        .get_all(header::IF_NONE_MATCH)
+        .iter()
+        .filter_map(|x| x.to_str().ok())
+        .map(|x| x.to_owned())
+        .collect();
+
+    send_action(state.connect_for::<A>(), action_wrapper)
         .from_err()
-        .and_then(|res| match res {
+        .and_then(move |res| match res {
             Ok(ok_res) => {
+                // only the json responses are tagged, the exports are downloaded once
+                let etag = if format == ExportFormat::Json && conditional::is_conditional(&ok_res.get_name()) {
+                    conditional::etag(ok_res.get_data_ref())
+                } else {
+                    None
+                };
+
+                if let Some(ref etag) = etag {
+                    if conditional::is_not_modified(&if_none_match, etag) {
+                        debug!("Responding with not modified: {}", etag);
+                        return Ok(HttpResponse::NotModified()
+                            .header(header::ETAG, etag.to_owned())
+                            .finish());
+                    }
+                }
+
                 let serialized = ok_res.get_data();
                 debug!("Responding with message: {:?}", &serialized);
-                Ok(HttpResponse::Ok()
-                    .json(serialized))
+                let mut response = format.respond(&serialized);
+                if is_negotiated {
+                    response.headers_mut().append(header::VARY, HeaderValue::from_static("Accept"));
+                }
+                if let Some(etag) = etag.and_then(|x| HeaderValue::from_str(&x).ok()) {
+                    response.headers_mut().insert(header::ETAG, etag);
+                }
+                Ok(response)
             },
             Err(err) => {
-                debug!("Responding with error message: {:?}", &err);
-                Ok(HttpResponse::InternalServerError()
-                    .json(json!({ "error": err.to_string() })))
+                debug!("Responding with error message: {:?} [{}]", &err, &request_id);
+                let mut envelope = err.envelope();
+                envelope["requestId"] = json!(request_id);
+                match err.retry_after() {
+                    Some(retry_after) => Ok(HttpResponse::ServiceUnavailable()
+                        .header(header::RETRY_AFTER, retry_after.to_string())
+                        .json(envelope)),
+                    None if err.is_timeout() => Ok(HttpResponse::GatewayTimeout()
+                        .json(envelope)),
+                    None => Ok(HttpResponse::InternalServerError()
+                        .json(envelope)),
+                }
             }
         })
         .responder()
 }
 
-pub fn procedure_bad_request_handler_function(err: JsonPayloadError) -> actix_web::Error {
-    let resp = HttpResponse::BadRequest()
-        .json(json!({ "error": err.to_string() }));
+/// `limit` is the one of the route, see `BodyLimits`
+pub fn procedure_bad_request_handler_function(err: JsonPayloadError, limit: usize) -> actix_web::Error {
+    let resp = match err {
+        JsonPayloadError::Overflow => HttpResponse::PayloadTooLarge()
+            .json(json!({ "error": "Request body is too large", "details": { "limit": limit } })),
+        _ => HttpResponse::BadRequest()
+            .json(json!({ "error": err.to_string() })),
+    };
 
     error::InternalError::from_response(err, resp).into()
 }
@@ -0,0 +1,375 @@
+use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::MutexGuard;
+use std::time::Duration;
+use std::time::Instant;
+
+use actix_web::HttpRequest;
+use actix_web::HttpResponse;
+use actix_web::Result as ActixResult;
+use actix_web::http::Method;
+use actix_web::http::header;
+use actix_web::http::header::HeaderValue;
+use actix_web::middleware::Middleware;
+use actix_web::middleware::Response;
+use actix_web::middleware::Started;
+
+use connection::GetSecrets;
+use data::claims::AuthClaims;
+use view::bearer_token::parse_bearer_token;
+use view::versioning::unversioned_path;
+
+/// only the procedures are limited, not the health probes, the sockets or the frontend
+const PROCEDURE_PREFIXES: [&str; 2] = ["/manage/", "/users/"];
+
+/// the keys are dropped once they have no requests left in the window, checked every this many requests
+const CLEANUP_INTERVAL: usize = 1000;
+
+const FORWARDED_FOR_HEADER: &str = "X-Forwarded-For";
+
+pub const LIMIT_HEADER: &str = "X-RateLimit-Limit";
+pub const REMAINING_HEADER: &str = "X-RateLimit-Remaining";
+/// in seconds, until a request leaves the window
+pub const RESET_HEADER: &str = "X-RateLimit-Reset";
+
+/// The number of procedure calls allowed in a sliding window, by user and by client ip.
+/// The limits that aren't set don't apply, so the default doesn't limit anything
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RateLimitConfig {
+    /// the user is the one of a valid bearer token, the other requests only count against their ip
+    pub per_user: Option<usize>,
+    pub per_ip: Option<usize>,
+    pub window: Duration,
+    /// The `X-Forwarded-For` header is only read if the request comes from one of these, e.g. the load balancer,
+    /// otherwise the clients could pick their ip with it
+    pub trusted_proxies: Vec<IpAddr>,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            per_user: None,
+            per_ip: None,
+            window: Duration::from_secs(60),
+            trusted_proxies: vec![],
+        }
+    }
+}
+
+impl RateLimitConfig {
+    pub fn is_enabled(&self) -> bool {
+        self.per_user.is_some() || self.per_ip.is_some()
+    }
+
+    /// The last address of the forwarded ones that isn't a trusted proxy, since the proxies append to the header
+    /// the entries left of it could have been sent by the client
+    fn client_ip(&self, peer: IpAddr, forwarded_for: Option<&str>) -> IpAddr {
+        if !self.trusted_proxies.contains(&peer) {
+            return peer;
+        }
+
+        let forwarded: Vec<IpAddr> = forwarded_for
+            .map(|x| x.split(',').filter_map(|ip| ip.trim().parse().ok()).collect())
+            .unwrap_or_default();
+
+        forwarded
+            .iter()
+            .rev()
+            .find(|ip| !self.trusted_proxies.contains(ip))
+            .or_else(|| forwarded.first())
+            .cloned()
+            .unwrap_or(peer)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum RateLimitKey {
+    User(i64),
+    Ip(IpAddr),
+}
+
+impl RateLimitKey {
+    fn scope(&self) -> &'static str {
+        match self {
+            RateLimitKey::User(_) => "user",
+            RateLimitKey::Ip(_) => "ip",
+        }
+    }
+}
+
+/// how the request stands against the most restrictive of its limits
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RateLimitStatus {
+    pub allowed: bool,
+    pub scope: &'static str,
+    pub limit: usize,
+    pub remaining: usize,
+    pub reset: Duration,
+}
+
+#[derive(Debug, Default)]
+struct RateLimiterInner {
+    /// the times of the requests still in the window, oldest first
+    requests: HashMap<RateLimitKey, VecDeque<Instant>>,
+    since_cleanup: usize,
+}
+
+/// Shared by the workers of the server, so that the limits hold whichever worker gets the request
+#[derive(Debug)]
+pub struct RateLimiter {
+    config: RateLimitConfig,
+    inner: Mutex<RateLimiterInner>,
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitConfig) -> Self {
+        Self {
+            config,
+            inner: Mutex::new(RateLimiterInner::default()),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<RateLimiterInner> {
+        // the windows are only pruned and appended to, so they are still usable after a panic
+        self.inner.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    fn limit_for(&self, key: &RateLimitKey) -> Option<usize> {
+        match key {
+            RateLimitKey::User(_) => self.config.per_user,
+            RateLimitKey::Ip(_) => self.config.per_ip,
+        }
+    }
+
+    /// The request is counted against all of its keys if none of them is over the limit,
+    /// and against none of them otherwise. Returns None if no limit applies to the keys
+    pub fn hit(&self, keys: &[RateLimitKey], now: Instant) -> Option<RateLimitStatus> {
+        let window = self.config.window;
+        let mut inner = self.lock();
+
+        inner.since_cleanup += 1;
+        if inner.since_cleanup >= CLEANUP_INTERVAL {
+            inner.since_cleanup = 0;
+            inner.requests.retain(|_, times| times.back().map(|x| now.duration_since(*x) < window).unwrap_or(false));
+        }
+
+        let mut statuses = vec![];
+        for key in keys {
+            let limit = match self.limit_for(key) {
+                Some(limit) => limit,
+                None => continue,
+            };
+
+            let times = inner.requests.entry(key.to_owned()).or_insert_with(VecDeque::new);
+            while times.front().map(|x| now.duration_since(*x) >= window).unwrap_or(false) {
+                times.pop_front();
+            }
+
+            let reset = times.front().map(|x| window - now.duration_since(*x)).unwrap_or(window);
+            statuses.push(RateLimitStatus {
+                allowed: times.len() < limit,
+                scope: key.scope(),
+                limit,
+                remaining: limit.saturating_sub(times.len()),
+                reset,
+            });
+        }
+
+        let allowed = statuses.iter().all(|x| x.allowed);
+        if allowed {
+            for key in keys.iter().filter(|x| self.limit_for(x).is_some()) {
+                if let Some(times) = inner.requests.get_mut(key) {
+                    times.push_back(now);
+                }
+            }
+            for status in statuses.iter_mut() {
+                status.remaining = status.remaining.saturating_sub(1);
+            }
+        }
+
+        statuses
+            .into_iter()
+            .min_by_key(|x| (x.allowed, x.remaining))
+    }
+}
+
+/// Middleware limiting the procedure calls, see `RateLimitConfig`
+pub struct RateLimit {
+    limiter: Arc<RateLimiter>,
+}
+
+impl RateLimit {
+    pub fn new(limiter: Arc<RateLimiter>) -> Self {
+        Self { limiter }
+    }
+
+    /// a token that can't be verified doesn't count, otherwise any made up token would get its own limit
+    fn keys<S: GetSecrets>(&self, req: &HttpRequest<S>) -> Vec<RateLimitKey> {
+        let mut keys = vec![];
+
+        let token_secret = req.state().get_token_secret();
+        let user_id = req.headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|x| x.to_str().ok())
+            .and_then(|x| parse_bearer_token(x.to_string()))
+            .and_then(|token| {
+                jsonwebtoken::decode::<AuthClaims>(&token, token_secret.as_ref(), &jsonwebtoken::Validation::default()).ok()
+            })
+            .map(|token_data| token_data.claims.get_user_id());
+        if let Some(user_id) = user_id {
+            keys.push(RateLimitKey::User(user_id));
+        }
+
+        let forwarded_for = req.headers()
+            .get(FORWARDED_FOR_HEADER)
+            .and_then(|x| x.to_str().ok());
+        if let Some(peer) = req.peer_addr() {
+            keys.push(RateLimitKey::Ip(self.limiter.config.client_ip(peer.ip(), forwarded_for)));
+        }
+
+        keys
+    }
+}
+
+/// rounded up, so that the clients waiting for it don't come back too early
+fn reset_secs(status: &RateLimitStatus) -> u64 {
+    status.reset.as_secs() + if status.reset.subsec_nanos() > 0 { 1 } else { 0 }
+}
+
+fn set_headers(resp: &mut HttpResponse, status: &RateLimitStatus) {
+    let reset_secs = reset_secs(status);
+    let headers = resp.headers_mut();
+    for (name, value) in vec![
+        (LIMIT_HEADER, status.limit as u64),
+        (REMAINING_HEADER, status.remaining as u64),
+        (RESET_HEADER, reset_secs),
+    ] {
+        if let Ok(value) = HeaderValue::from_str(&value.to_string()) {
+            headers.insert(name, value);
+        }
+    }
+}
+
+fn too_many_requests(status: &RateLimitStatus) -> HttpResponse {
+    let retry_after = reset_secs(status).max(1);
+    let mut resp = HttpResponse::TooManyRequests()
+        .header(header::RETRY_AFTER, retry_after.to_string())
+        .json(json!({
+            "error": format!("Too many requests, try again in {} seconds", retry_after),
+            "details": {
+                "retryAfter": retry_after,
+                "limit": status.limit,
+                "scope": status.scope,
+            },
+        }));
+    set_headers(&mut resp, status);
+    resp
+}
+
+impl<S: GetSecrets + 'static> Middleware<S> for RateLimit {
+    fn start(&self, req: &HttpRequest<S>) -> ActixResult<Started> {
+        let (_, path) = unversioned_path(req.path());
+        let is_procedure = req.method() == Method::POST &&
+            PROCEDURE_PREFIXES.iter().any(|x| path.starts_with(x));
+        if !is_procedure {
+            return Ok(Started::Done);
+        }
+
+        let status = match self.limiter.hit(&self.keys(req), Instant::now()) {
+            Some(status) => status,
+            None => return Ok(Started::Done),
+        };
+
+        if !status.allowed {
+            warn!("rate limit by {} reached for {}", status.scope, req.path());
+            return Ok(Started::Response(too_many_requests(&status)));
+        }
+
+        req.extensions_mut().insert(status);
+        Ok(Started::Done)
+    }
+
+    fn response(&self, req: &HttpRequest<S>, mut resp: HttpResponse) -> ActixResult<Response> {
+        if let Some(status) = req.extensions().get::<RateLimitStatus>() {
+            set_headers(&mut resp, status);
+        }
+        Ok(Response::Done(resp))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_sliding_window() {
+        let limiter = RateLimiter::new(RateLimitConfig {
+            per_user: Some(2),
+            per_ip: Some(3),
+            window: Duration::from_secs(10),
+            ..RateLimitConfig::default()
+        });
+        let token = RateLimitKey::User(1);
+        let other_token = RateLimitKey::User(2);
+        let ip = RateLimitKey::Ip("10.0.0.1".parse().unwrap());
+        let start = Instant::now();
+
+        let status = limiter.hit(&[token.to_owned(), ip.to_owned()], start).unwrap();
+        assert!(status.allowed);
+        assert_eq!((status.scope, status.remaining), ("user", 1));
+
+        let status = limiter.hit(&[token.to_owned(), ip.to_owned()], start + Duration::from_secs(4)).unwrap();
+        assert!(status.allowed);
+        assert_eq!(status.remaining, 0);
+
+        let status = limiter.hit(&[token.to_owned(), ip.to_owned()], start + Duration::from_secs(5)).unwrap();
+        assert!(!status.allowed);
+        assert_eq!(status.scope, "user");
+        assert_eq!(status.reset, Duration::from_secs(5));
+
+        // the ip still has room, the blocked request wasn't counted against it
+        let status = limiter.hit(&[other_token.to_owned(), ip.to_owned()], start + Duration::from_secs(5)).unwrap();
+        assert!(status.allowed);
+        assert_eq!((status.scope, status.remaining), ("ip", 0));
+
+        let status = limiter.hit(&[other_token.to_owned(), ip.to_owned()], start + Duration::from_secs(6)).unwrap();
+        assert!(!status.allowed);
+        assert_eq!(status.scope, "ip");
+
+        // the first request left the window
+        let status = limiter.hit(&[token.to_owned()], start + Duration::from_secs(10)).unwrap();
+        assert!(status.allowed);
+        assert_eq!(status.remaining, 0);
+    }
+
+    #[test]
+    fn test_disabled_limits() {
+        let limiter = RateLimiter::new(RateLimitConfig {
+            per_ip: Some(1),
+            ..RateLimitConfig::default()
+        });
+        assert_eq!(limiter.hit(&[RateLimitKey::User(1)], Instant::now()), None);
+        assert!(!RateLimitConfig::default().is_enabled());
+    }
+
+    #[test]
+    fn test_client_ip() {
+        let ip = |x: &str| -> IpAddr { x.parse().unwrap() };
+        let config = RateLimitConfig {
+            trusted_proxies: vec![ip("10.0.0.1"), ip("10.0.0.2")],
+            ..RateLimitConfig::default()
+        };
+
+        // only the proxies can say who the client is
+        assert_eq!(config.client_ip(ip("192.168.1.1"), Some("1.2.3.4")), ip("192.168.1.1"));
+        assert_eq!(config.client_ip(ip("10.0.0.1"), Some("1.2.3.4")), ip("1.2.3.4"));
+        assert_eq!(config.client_ip(ip("10.0.0.1"), None), ip("10.0.0.1"));
+
+        // the client can't hide behind what it sent in the header
+        assert_eq!(config.client_ip(ip("10.0.0.1"), Some("6.6.6.6, 1.2.3.4, 10.0.0.2")), ip("1.2.3.4"));
+        assert_eq!(config.client_ip(ip("10.0.0.1"), Some("10.0.0.2, 10.0.0.1")), ip("10.0.0.2"));
+    }
+}
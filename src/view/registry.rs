@@ -0,0 +1,328 @@
+use serde;
+use serde::Serialize;
+use serde_json::Value;
+
+use actix::prelude::*;
+
+use connection::AppStateLike;
+use connection::executor::Executor;
+use data::json_schema;
+use data::json_schema::SchemaError;
+use model::actions::Action;
+use view::action_wrapper::ActionWrapper;
+use view::procedure::ProcedureBuilder;
+use view::routes::manage;
+use view::routes::pubsub;
+use view::routes::users;
+use view::schemas::data;
+use view::schemas::params;
+use view::versioning::ApiVersion;
+
+/// The group of a procedure, which is the first part of its http path
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProcedureGroup {
+    Manage,
+    Users,
+    PubSub,
+}
+
+impl ProcedureGroup {
+    pub fn path(&self) -> &'static str {
+        match self {
+            ProcedureGroup::Manage => "manage",
+            ProcedureGroup::Users => "users",
+            ProcedureGroup::PubSub => "pubsub",
+        }
+    }
+}
+
+/// What the transports need to know about a procedure, besides how its action is built
+#[derive(Debug, Clone)]
+pub struct Procedure {
+    pub name: &'static str,
+    pub group: ProcedureGroup,
+    /// served as `POST /<group>/<name>`
+    pub http: bool,
+    /// served as a `call` message on the socket
+    pub socket: bool,
+    /// the JSON Schema of the params, the query string over http, see `view::schemas`
+    pub params_schema: Option<fn() -> Value>,
+    /// the JSON Schema of the data, the body over http
+    pub data_schema: Option<fn() -> Value>,
+}
+
+impl Procedure {
+    fn new(name: &'static str, group: ProcedureGroup, http: bool, socket: bool) -> Self {
+        Self { name, group, http, socket, params_schema: None, data_schema: None }
+    }
+
+    pub fn manage(name: &'static str) -> Self {
+        Self::new(name, ProcedureGroup::Manage, true, true)
+    }
+
+    /// the sessions of the socket are authenticated with a token, the users procedures are http only
+    pub fn users(name: &'static str) -> Self {
+        Self::new(name, ProcedureGroup::Users, true, false)
+    }
+
+    /// the subscriptions are to the socket they are made on, so they are socket only
+    pub fn pubsub(name: &'static str) -> Self {
+        Self::new(name, ProcedureGroup::PubSub, false, true)
+    }
+
+    pub fn params(mut self, params_schema: fn() -> Value) -> Self {
+        self.params_schema = Some(params_schema);
+        self
+    }
+
+    pub fn data(mut self, data_schema: fn() -> Value) -> Self {
+        self.data_schema = Some(data_schema);
+        self
+    }
+
+    pub fn http_path(&self) -> String {
+        format!("/{}/{}", self.group.path(), self.name)
+    }
+
+    /// The errors of every field of the payload that doesn't conform to the schemas, e.g. `data.name: is required`
+    pub fn validate(&self, data: &Value, params: &Value) -> Vec<SchemaError> {
+        let mut errors = vec![];
+        if let Some(params_schema) = self.params_schema {
+            errors.extend(json_schema::validate(&params_schema(), params, "params"));
+        }
+        if let Some(data_schema) = self.data_schema {
+            errors.extend(json_schema::validate(&data_schema(), data, "data"));
+        }
+        errors
+    }
+}
+
+/// Checks the payload against the schemas of the procedure before its action is built. All the errors are
+/// in the message, since serde would only give the first one and without where it is
+fn validated<S, A, PB>(procedure: Procedure, procedure_builder: PB)
+    -> impl FnOnce(Value, Value) -> Result<(Option<String>, A), serde_json::Error> + Clone
+    where
+        S: AppStateLike + 'static,
+        A: Action + 'static,
+        PB: ProcedureBuilder<S, Value, Value, A> + Clone + 'static,
+{
+    move |data, params| {
+        let errors = procedure.validate(&data, &params);
+        if !errors.is_empty() {
+            let errors: Vec<String> = errors.iter().map(|x| x.to_string()).collect();
+            return Err(serde::de::Error::custom(format!("invalid payload, {}", errors.join("; "))));
+        }
+        procedure_builder.build(data, params)
+    }
+}
+
+/// A transport serving the procedures, see `visit_procedures`
+pub trait ProcedureVisitor<S>
+    where
+        S: AppStateLike + 'static,
+{
+    fn visit<A, PB>(&mut self, procedure: Procedure, procedure_builder: PB) -> &mut Self
+        where
+            Executor: Handler<ActionWrapper<A>>,
+            A: Action + Send + 'static,
+            PB: ProcedureBuilder<S, Value, Value, A> + Clone + 'static,
+            <A as Action>::Ret: Send + Serialize;
+}
+
+/// The procedures of a version of the api, for both the http routes and the socket calls.
+/// A procedure registered here is served by every transport its metadata allows
+pub fn visit_procedures<S, V>(version: ApiVersion, visitor: &mut V)
+    where
+        S: AppStateLike + 'static,
+        V: ProcedureVisitor<S>,
+{
+    let mut visitor = Validating(visitor);
+    match version {
+        ApiVersion::V1 => visit_v1_procedures::<S, _>(&mut visitor),
+    }
+}
+
+/// Hands the procedures to the visitor with their payloads validated, see `validated`
+struct Validating<'v, V: 'v>(&'v mut V);
+
+impl<'v, S, V> ProcedureVisitor<S> for Validating<'v, V>
+    where
+        S: AppStateLike + 'static,
+        V: ProcedureVisitor<S>,
+{
+    fn visit<A, PB>(&mut self, procedure: Procedure, procedure_builder: PB) -> &mut Self
+        where
+            Executor: Handler<ActionWrapper<A>>,
+            A: Action + Send + 'static,
+            PB: ProcedureBuilder<S, Value, Value, A> + Clone + 'static,
+            <A as Action>::Ret: Send + Serialize,
+    {
+        let procedure_builder = validated(procedure.to_owned(), procedure_builder);
+        self.0.visit(procedure, procedure_builder);
+        self
+    }
+}
+
+fn visit_v1_procedures<S, V>(visitor: &mut V)
+    where
+        S: AppStateLike + 'static,
+        V: ProcedureVisitor<S>,
+{
+    visitor
+        .visit(Procedure::manage("getAllDomains").params(params::paged), manage::get_all_domains)
+        //TODO: manage domains?
+
+        .visit(Procedure::manage("getAllTables").params(params::entity_list), manage::get_all_tables)
+        .visit(Procedure::manage("getAllQueries").params(params::entity_list), manage::get_all_queries)
+        .visit(Procedure::manage("getAllScripts").params(params::entity_list), manage::get_all_scripts)
+        .visit(Procedure::manage("getAllViews").params(params::entity_list), manage::get_all_views)
+        .visit(Procedure::manage("getAllWebhooks").params(params::entity_list), manage::get_all_webhooks)
+
+        .visit(Procedure::manage("getTable").params(params::entity), manage::get_table)
+        .visit(Procedure::manage("getQuery").params(params::entity), manage::get_query)
+        .visit(Procedure::manage("getScript").params(params::entity), manage::get_script)
+        .visit(Procedure::manage("getView").params(params::entity), manage::get_view)
+        .visit(Procedure::manage("getWebhook").params(params::entity), manage::get_webhook)
+
+        .visit(Procedure::manage("createTable").params(params::domain).data(data::table), manage::create_table)
+        .visit(Procedure::manage("createQuery").params(params::domain).data(data::query), manage::create_query)
+        .visit(Procedure::manage("createScript").params(params::domain).data(data::script), manage::create_script)
+        .visit(Procedure::manage("createView").params(params::domain).data(data::view), manage::create_view)
+        .visit(Procedure::manage("createWebhook").params(params::domain).data(data::webhook), manage::create_webhook)
+
+        .visit(Procedure::manage("updateTable").params(params::entity).data(data::table), manage::update_table)
+        .visit(Procedure::manage("previewSchemaChange").params(params::entity).data(data::table), manage::preview_schema_change)
+        .visit(Procedure::manage("copyTable").params(params::entity).data(data::copy_table), manage::copy_table)
+        .visit(Procedure::manage("updateQuery").params(params::entity).data(data::query), manage::update_query)
+        .visit(Procedure::manage("updateScript").params(params::entity).data(data::script), manage::update_script)
+        .visit(Procedure::manage("updateView").params(params::entity).data(data::view), manage::update_view)
+        .visit(Procedure::manage("updateWebhook").params(params::entity).data(data::webhook), manage::update_webhook)
+
+        .visit(Procedure::manage("deleteTable").params(params::entity), manage::delete_table)
+        .visit(Procedure::manage("deleteQuery").params(params::entity), manage::delete_query)
+        .visit(Procedure::manage("deleteScript").params(params::entity), manage::delete_script)
+        .visit(Procedure::manage("deleteView").params(params::entity), manage::delete_view)
+        .visit(Procedure::manage("deleteWebhook").params(params::entity), manage::delete_webhook)
+
+        .visit(Procedure::manage("queryTableData").params(params::table_data), manage::query_table_data)
+        .visit(Procedure::manage("insertTableData").params(params::entity).data(data::rows), manage::insert_table_data)
+        .visit(Procedure::manage("modifyTableData").params(params::entity).data(data::rows), manage::modify_table_data)
+        .visit(Procedure::manage("removeTableData").params(params::entity).data(data::rows), manage::remove_table_data)
+        .visit(Procedure::manage("bulkModifyTableData").params(params::bulk_modify).data(data::operations), manage::bulk_modify_table_data)
+        .visit(Procedure::manage("modifyTableDataByFilter").params(params::entity), manage::modify_table_data_by_filter)
+        .visit(Procedure::manage("removeTableDataByFilter").params(params::entity), manage::remove_table_data_by_filter)
+        .visit(Procedure::manage("getRowHistory").params(params::entity), manage::get_row_history)
+        .visit(Procedure::manage("refreshView").params(params::entity), manage::refresh_view)
+        .visit(Procedure::manage("applyRetentionPolicy").params(params::entity), manage::apply_retention_policy)
+        .visit(Procedure::manage("applyRetentionPolicies").params(params::domain), manage::apply_retention_policies)
+
+        .visit(Procedure::manage("runQuery").params(params::query_result), manage::run_query)
+        .visit(Procedure::manage("runQueryIntoTable").params(params::query_into_table), manage::run_query_into_table)
+        .visit(Procedure::manage("explainQuery").params(params::entity), manage::explain_query)
+        .visit(Procedure::manage("listScheduledRuns").params(params::entity), manage::list_scheduled_runs)
+        .visit(Procedure::manage("getQueryHistory").params(params::entity), manage::get_query_history)
+        .visit(Procedure::manage("snapshotQueryResult").params(params::query_snapshot), manage::snapshot_query_result)
+        .visit(Procedure::manage("getQuerySnapshot").params(params::query_snapshot), manage::get_query_snapshot)
+        .visit(Procedure::manage("listQuerySnapshots").params(params::entity), manage::list_query_snapshots)
+        .visit(Procedure::manage("getQueryDependencies").params(params::entity), manage::get_query_dependencies)
+        .visit(Procedure::manage("getTableDependents").params(params::entity), manage::get_table_dependents)
+        .visit(Procedure::manage("restoreQueryVersion").params(params::entity).data(data::version), manage::restore_query_version)
+        .visit(Procedure::manage("listRunningQueries"), manage::list_running_queries)
+        .visit(Procedure::manage("cancelRunningQuery").params(params::domain).data(data::run_id), manage::cancel_running_query)
+        .visit(Procedure::manage("runScript").params(params::entity), manage::run_script)
+        .visit(Procedure::manage("runScriptAsync").params(params::entity), manage::run_script_async)
+        .visit(Procedure::manage("validateScript").params(params::entity), manage::validate_script)
+        .visit(Procedure::manage("getJobStatus").data(data::job_id), manage::get_job_status)
+        .visit(Procedure::manage("getJobResult").data(data::job_id), manage::get_job_result)
+        .visit(Procedure::manage("getScriptRuns").params(params::entity), manage::get_script_runs)
+        .visit(Procedure::manage("getScriptRunLog").params(params::entity).data(data::run_id), manage::get_script_run_log)
+        .visit(Procedure::manage("setScriptSecret").params(params::entity).data(data::secret), manage::set_script_secret)
+        .visit(Procedure::manage("removeScriptSecret").params(params::entity).data(data::secret_name), manage::remove_script_secret)
+        .visit(Procedure::manage("getScriptSecrets").params(params::entity), manage::get_script_secrets)
+        .visit(Procedure::manage("getScriptHistory").params(params::entity), manage::get_script_history)
+        .visit(Procedure::manage("restoreScriptVersion").params(params::entity).data(data::version), manage::restore_script_version)
+        .visit(Procedure::manage("getWebhookDeliveries").params(params::entity), manage::get_webhook_deliveries)
+
+        .visit(Procedure::manage("setReadOnlyMode").data(data::read_only_mode), manage::set_read_only_mode)
+        .visit(Procedure::manage("getBroadcastMetrics"), manage::get_broadcast_metrics)
+        .visit(Procedure::manage("getDatabaseMetrics"), manage::get_database_metrics)
+        .visit(Procedure::manage("getAuditTrail").params(params::audit_trail), manage::get_audit_trail)
+        .visit(Procedure::manage("getEntityUsage").params(params::domain), manage::get_entity_usage)
+
+        .visit(Procedure::pubsub("subscribeTo").params(params::subscription).data(data::channel), pubsub::subscribe_to)
+        .visit(Procedure::pubsub("unsubscribeFrom").params(params::domain).data(data::channel), pubsub::unsubscribe_from)
+        .visit(Procedure::pubsub("unsubscribeAll").params(params::unsubscribe_all), pubsub::unsubscribe_all)
+        .visit(Procedure::pubsub("getSubscribers").params(params::domain).data(data::channel), pubsub::get_subscribers)
+        .visit(Procedure::pubsub("listChannels").params(params::domain), pubsub::list_channels)
+        .visit(Procedure::pubsub("listMySubscriptions"), pubsub::list_my_subscriptions)
+        .visit(Procedure::pubsub("getMessages").params(params::time_range), pubsub::get_messages)
+        .visit(Procedure::pubsub("getPendingMessages").params(params::pending_messages), pubsub::get_pending_messages)
+        .visit(Procedure::pubsub("ackMessages").data(data::message_ids), pubsub::ack_messages)
+        .visit(Procedure::pubsub("purgeMessages").data(data::message_retention), pubsub::purge_messages)
+        .visit(Procedure::pubsub("expireSubscriptions").data(data::expire_subscriptions), pubsub::expire_subscriptions)
+        .visit(Procedure::pubsub("addChannelWebhook").data(data::channel_webhook), pubsub::add_channel_webhook)
+        .visit(Procedure::pubsub("removeChannelWebhook").data(data::channel_webhook_id), pubsub::remove_channel_webhook)
+        .visit(Procedure::pubsub("getChannelWebhooks"), pubsub::get_channel_webhooks)
+        .visit(Procedure::pubsub("getDeadLetters").params(params::paged), pubsub::get_dead_letters)
+        .visit(Procedure::pubsub("redeliverDeadLetter").data(data::dead_letter_id), pubsub::redeliver_dead_letter)
+
+        .visit(Procedure::users("login").data(data::credentials), users::login)
+        .visit(Procedure::users("refresh").data(data::refresh_token), users::refresh)
+        .visit(Procedure::users("logout"), users::logout)
+        .visit(Procedure::users("getAllUsers").params(params::paged), users::get_all_users)
+
+        .visit(Procedure::users("addUser").data(data::new_user), users::add_user)
+        .visit(Procedure::users("removeUser").params(params::user), users::remove_user)
+        .visit(Procedure::users("inviteUser").data(data::invite), users::invite_user)
+        .visit(Procedure::users("setupUser").data(data::new_user), users::setup_user)
+        .visit(Procedure::users("setUserPassword").data(data::password_reset), users::set_user_password)
+
+        .visit(Procedure::users("addRole").data(data::new_role), users::add_role)
+        .visit(Procedure::users("removeRole").params(params::role), users::remove_role)
+        .visit(Procedure::users("getAllRoles").params(params::paged), users::get_all_roles)
+
+        .visit(Procedure::users("attachPermissionForRole").params(params::role).data(data::permission), users::attach_permission_for_role)
+        .visit(Procedure::users("detachPermissionForRole").params(params::role).data(data::permission), users::detach_permission_for_role)
+
+        .visit(Procedure::users("attachRoleForUser").params(params::user).data(data::role_name), users::attach_role_for_user)
+        .visit(Procedure::users("detachRoleForUser").params(params::user).data(data::role_name), users::detach_role_for_user);
+}
+
+/// Only collects the metadata, for listing the procedures
+#[derive(Debug, Default)]
+pub struct ProcedureList(pub Vec<Procedure>);
+
+impl<S> ProcedureVisitor<S> for ProcedureList
+    where
+        S: AppStateLike + 'static,
+{
+    fn visit<A, PB>(&mut self, procedure: Procedure, _procedure_builder: PB) -> &mut Self
+        where
+            Executor: Handler<ActionWrapper<A>>,
+            A: Action + Send + 'static,
+            PB: ProcedureBuilder<S, Value, Value, A> + Clone + 'static,
+            <A as Action>::Ret: Send + Serialize,
+    {
+        self.0.push(procedure);
+        self
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::HashSet;
+    use test_common::TestState;
+
+    #[test]
+    fn test_procedures_are_unique() {
+        let mut list = ProcedureList::default();
+        visit_procedures::<TestState, _>(ApiVersion::V1, &mut list);
+
+        let names: HashSet<&str> = list.0.iter().map(|x| x.name).collect();
+        assert_eq!(names.len(), list.0.len());
+
+        assert!(list.0.iter().any(|x| x.name == "getTable" && x.params_schema.is_some()));
+        assert!(list.0.iter().all(|x| x.http || x.socket));
+    }
+}
@@ -0,0 +1,135 @@
+use std::cell::RefCell;
+use std::io;
+use std::io::Write;
+
+use actix_web::HttpRequest;
+use actix_web::HttpResponse;
+use actix_web::Result as ActixResult;
+use actix_web::http::header::HeaderValue;
+use actix_web::middleware::Middleware;
+use actix_web::middleware::Response;
+use actix_web::middleware::Started;
+
+use env_logger::fmt::Formatter;
+use log::Record;
+use uuid::Uuid;
+
+/// taken from the request if the client or a proxy set it, and always set on the response
+pub const REQUEST_ID_HEADER: &str = "X-Request-Id";
+
+/// the ids taken from the requests are logged, so they are kept short and printable
+const MAX_REQUEST_ID_LENGTH: usize = 128;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestId(pub String);
+
+thread_local! {
+    /// the request the executor thread is running an action for
+    static CURRENT_REQUEST_ID: RefCell<Option<String>> = RefCell::new(None);
+}
+
+pub fn new_request_id() -> String {
+    Uuid::new_v4().to_simple().to_string()
+}
+
+fn accept_request_id(header: &str) -> Option<String> {
+    let is_valid = !header.is_empty() &&
+        header.len() <= MAX_REQUEST_ID_LENGTH &&
+        header.chars().all(|x| x.is_ascii_alphanumeric() || x == '-' || x == '_' || x == '.');
+
+    if is_valid {
+        Some(header.to_string())
+    } else {
+        None
+    }
+}
+
+/// The id of the request, the one set by the middleware or else the one in the header.
+/// A new one is generated when neither is there, e.g. when the app doesn't have the middleware
+pub fn request_id<S>(req: &HttpRequest<S>) -> String {
+    if let Some(RequestId(id)) = req.extensions().get::<RequestId>() {
+        return id.to_owned();
+    }
+
+    req.headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|x| x.to_str().ok())
+        .and_then(accept_request_id)
+        .unwrap_or_else(new_request_id)
+}
+
+/// the id of the request the current thread is working on, if any
+pub fn current() -> Option<String> {
+    CURRENT_REQUEST_ID.with(|x| x.borrow().to_owned())
+}
+
+/// Sets the current request id until the guard is dropped, so that the log lines have it
+pub fn enter(request_id: Option<String>) -> RequestIdGuard {
+    let previous = CURRENT_REQUEST_ID.with(|x| x.replace(request_id));
+    RequestIdGuard { previous }
+}
+
+pub struct RequestIdGuard {
+    previous: Option<String>,
+}
+
+impl Drop for RequestIdGuard {
+    fn drop(&mut self) {
+        let previous = self.previous.take();
+        CURRENT_REQUEST_ID.with(|x| x.replace(previous));
+    }
+}
+
+/// Log format with the request id, for `env_logger::Builder::format`
+pub fn log_format(buf: &mut Formatter, record: &Record) -> io::Result<()> {
+    match current() {
+        Some(request_id) => writeln!(buf, "{} {:<5} {} [{}] {}", buf.timestamp(), record.level(), record.target(), request_id, record.args()),
+        None => writeln!(buf, "{} {:<5} {} {}", buf.timestamp(), record.level(), record.target(), record.args()),
+    }
+}
+
+/// Middleware giving every request an id, and returning it in the `X-Request-Id` header
+pub struct RequestIdHeader;
+
+impl<S> Middleware<S> for RequestIdHeader {
+    fn start(&self, req: &HttpRequest<S>) -> ActixResult<Started> {
+        let request_id = request_id(req);
+        req.extensions_mut().insert(RequestId(request_id));
+        Ok(Started::Done)
+    }
+
+    fn response(&self, req: &HttpRequest<S>, mut resp: HttpResponse) -> ActixResult<Response> {
+        let request_id = req.extensions().get::<RequestId>().map(|RequestId(id)| id.to_owned());
+        if let Some(value) = request_id.and_then(|x| HeaderValue::from_str(&x).ok()) {
+            resp.headers_mut().insert(REQUEST_ID_HEADER, value);
+        }
+        Ok(Response::Done(resp))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_accept_request_id() {
+        assert_eq!(accept_request_id("4bf92f3577b34da6-a3ce929d0e0e4736"), Some("4bf92f3577b34da6-a3ce929d0e0e4736".to_string()));
+        assert_eq!(accept_request_id(""), None);
+        assert_eq!(accept_request_id("id\nwith a new line"), None);
+        assert_eq!(accept_request_id(&"a".repeat(MAX_REQUEST_ID_LENGTH + 1)), None);
+    }
+
+    #[test]
+    fn test_current_request_id() {
+        assert_eq!(current(), None);
+        {
+            let _guard = enter(Some("outer".to_string()));
+            {
+                let _guard = enter(Some("inner".to_string()));
+                assert_eq!(current(), Some("inner".to_string()));
+            }
+            assert_eq!(current(), Some("outer".to_string()));
+        }
+        assert_eq!(current(), None);
+    }
+}
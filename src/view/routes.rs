@@ -4,17 +4,84 @@ use model::actions;
 use view::procedure::NoQuery;
 use data;
 use model::actions::Action;
+use model::actions::paging::Paging;
 use serde_json::Value;
 use serde_json::Error;
 use serde_json::from_value;
 use connection::AppStateLike;
+use futures::sync::mpsc;
+use scripting::ScriptOutputLine;
+
+/// The values of the query string are all strings, while the websocket calls have them as json.
+/// These deserializers take either, so that the list options are the same for both
+mod query_param {
+    use serde::Deserialize;
+    use serde::Deserializer;
+    use serde::de::Error;
+    use serde_json::Value;
+
+    pub fn flag<'de, D>(deserializer: D) -> Result<bool, D::Error>
+        where D: Deserializer<'de>,
+    {
+        match Value::deserialize(deserializer)? {
+            Value::Bool(value) => Ok(value),
+            Value::String(ref value) if value == "true" => Ok(true),
+            Value::String(ref value) if value == "false" => Ok(false),
+            other => Err(D::Error::custom(format!("expected true or false, got {}", other))),
+        }
+    }
+
+    pub fn count<'de, D>(deserializer: D) -> Result<Option<usize>, D::Error>
+        where D: Deserializer<'de>,
+    {
+        match Value::deserialize(deserializer)? {
+            Value::Null => Ok(None),
+            Value::Number(ref value) if value.is_u64() => Ok(value.as_u64().map(|x| x as usize)),
+            Value::String(ref value) => value.parse().map(Some).map_err(D::Error::custom),
+            other => Err(D::Error::custom(format!("expected a positive integer, got {}", other))),
+        }
+    }
+}
+
+fn default_detailed() -> bool {
+    true
+}
 
 #[derive(Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct GetAllEntities {
     pub domain: String,
-    #[serde(default)]
+    #[serde(default, deserialize_with = "query_param::flag")]
     pub show_deleted: bool,
+    /// only the names of the entities are returned when false
+    #[serde(default = "default_detailed", deserialize_with = "query_param::flag")]
+    pub detailed: bool,
+    #[serde(default, deserialize_with = "query_param::count")]
+    pub limit: Option<usize>,
+    #[serde(default, deserialize_with = "query_param::count")]
+    pub offset: Option<usize>,
+}
+
+impl GetAllEntities {
+    pub fn paging(&self) -> Paging {
+        Paging::new(self.limit, self.offset)
+    }
+}
+
+/// for the lists that aren't in a domain, i.e. domains, users and roles
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct GetAllPaged {
+    #[serde(default, deserialize_with = "query_param::count")]
+    pub limit: Option<usize>,
+    #[serde(default, deserialize_with = "query_param::count")]
+    pub offset: Option<usize>,
+}
+
+impl GetAllPaged {
+    pub fn paging(&self) -> Paging {
+        Paging::new(self.limit, self.offset)
+    }
 }
 
 #[derive(Deserialize, Debug)]
@@ -24,12 +91,195 @@ pub struct GetEntity {
     pub domain: String,
 }
 
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct BulkModifyTable {
+    pub name: String,
+    pub domain: String,
+    /// see `BulkModifyTableData::skip_failed`
+    #[serde(default)]
+    pub skip_failed: bool,
+}
+
+/// the rows of the result that are returned, see `RunQuery`
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct GetQueryResult {
+    pub name: String,
+    pub domain: String,
+    #[serde(default)]
+    pub limit: Option<usize>,
+    #[serde(default)]
+    pub offset: usize,
+    /// see `result_format`
+    #[serde(default)]
+    pub format: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct GetTableData {
+    pub name: String,
+    pub domain: String,
+    /// see `result_format`
+    #[serde(default)]
+    pub format: Option<String>,
+}
+
+/// The `format` param selects the encoding of the http response as well, the rows are left in
+/// the raw layout for those since that's what the csv, ndjson and parquet writers read, see `ExportFormat`
+fn result_format(format: Option<String>) -> Result<data::result_format::ResultFormat, Error> {
+    match format.as_ref().map(|x| x.as_str()) {
+        None | Some("json") | Some("csv") | Some("ndjson") | Some("parquet") => Ok(data::result_format::ResultFormat::Raw),
+        Some(format) => from_value(json!(format)),
+    }
+}
+
+/// the query and the table its result is written into, see `RunQueryIntoTable`
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct QueryIntoTable {
+    pub name: String,
+    pub domain: String,
+    pub target_table: String,
+    pub mode: data::TableWriteMode,
+}
+
+/// see `SnapshotQueryResult`
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct GetQuerySnapshot {
+    pub name: String,
+    pub domain: String,
+    pub snapshot: String,
+    /// see `result_format`
+    #[serde(default)]
+    pub format: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ScriptRunId {
+    pub id: i64,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ScriptJobId {
+    pub id: String,
+}
+
+/// not `Debug`, so that the value doesn't end up in the logs
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScriptSecretValue {
+    pub name: String,
+    pub value: String,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ScriptSecretName {
+    pub name: String,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct QueryVersion {
+    pub version: i64,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ScriptVersion {
+    pub version: i64,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct RunningQueryId {
+    pub id: usize,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CopyTableData {
+    pub target: String,
+    #[serde(default)]
+    pub with_data: bool,
+}
+
 #[derive(Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct GetFromDomain {
     pub domain: String,
 }
 
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct SubscribeToParams {
+    pub domain: String,
+    /// kept after the session closes, the messages are sent until they are acknowledged
+    #[serde(default, deserialize_with = "query_param::flag")]
+    pub durable: bool,
+    /// in seconds, the subscription is kept until it's removed
+    #[serde(default, deserialize_with = "query_param::count")]
+    pub ttl: Option<usize>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct UnsubscribeAllParams {
+    #[serde(default, deserialize_with = "query_param::flag")]
+    pub keep_durable: bool,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct PendingMessagesParams {
+    #[serde(default, deserialize_with = "query_param::flag")]
+    pub redeliver: bool,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct AckMessages {
+    pub ids: Vec<i64>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct ExpireSubscriptions {
+    /// in seconds, the subscriptions past their ttl are always removed
+    #[serde(default)]
+    pub inactive_for: Option<u64>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct ChannelWebhookId {
+    pub id: i64,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct GetDeadLetters {
+    #[serde(default, deserialize_with = "query_param::count")]
+    pub limit: Option<usize>,
+    #[serde(default, deserialize_with = "query_param::count")]
+    pub offset: Option<usize>,
+}
+
+impl GetDeadLetters {
+    const DEFAULT_LIMIT: usize = 100;
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct DeadLetterId {
+    pub id: i64,
+}
+
 
 #[derive(Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
@@ -86,35 +336,89 @@ struct TimeRange {
     pub end_time: chrono::NaiveDateTime,
 }
 
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct ReadOnlyMode {
+    pub read_only: bool,
+}
+
+/// the page of the audit trail, the filters that aren't set match every entry
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct GetAuditTrail {
+    #[serde(default, deserialize_with = "query_param::count")]
+    pub limit: Option<usize>,
+    #[serde(default, deserialize_with = "query_param::count")]
+    pub offset: Option<usize>,
+    pub username: Option<String>,
+    pub procedure: Option<String>,
+    pub domain: Option<String>,
+    pub entity_type: Option<String>,
+    pub entity_name: Option<String>,
+}
+
+impl GetAuditTrail {
+    const DEFAULT_LIMIT: usize = 100;
+
+    fn filter(&self) -> data::audit::AuditFilter {
+        data::audit::AuditFilter {
+            username: self.username.to_owned(),
+            procedure: self.procedure.to_owned(),
+            domain: self.domain.to_owned(),
+            entity_type: self.entity_type.to_owned(),
+            entity_name: self.entity_name.to_owned(),
+        }
+    }
+}
+
 
 pub mod manage {
     use super::*;
 
     pub fn get_all_domains(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
         let _: NoQuery = from_value(data)?;
-        let _: NoQuery = from_value(query)?;
-        Ok((None, actions::GetAllDomains::<_>::new()))
+        let get_all_paged: GetAllPaged = from_value(query)?;
+        Ok((None, actions::GetAllDomains::<_>::new(get_all_paged.paging())))
     }
 
     pub fn get_all_tables(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
         let _: NoQuery = from_value(data)?;
         let get_all_entities: GetAllEntities = from_value(query)?;
+        let paging = get_all_entities.paging();
         let domain = get_all_entities.domain;
-        Ok((Some(domain), actions::GetAllEntities::<data::DataStoreEntity>::new(get_all_entities.show_deleted)))
+        Ok((Some(domain), actions::GetAllEntities::<data::DataStoreEntity>::new(get_all_entities.show_deleted, get_all_entities.detailed, paging)))
     }
 
     pub fn get_all_queries(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
         let _: NoQuery = from_value(data)?;
         let get_all_entities: GetAllEntities = from_value(query)?;
+        let paging = get_all_entities.paging();
         let domain = get_all_entities.domain;
-        Ok((Some(domain), actions::GetAllEntities::<data::DataQueryEntity>::new(get_all_entities.show_deleted)))
+        Ok((Some(domain), actions::GetAllEntities::<data::DataQueryEntity>::new(get_all_entities.show_deleted, get_all_entities.detailed, paging)))
     }
 
     pub fn get_all_scripts(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
         let _: NoQuery = from_value(data)?;
         let get_all_entities: GetAllEntities = from_value(query)?;
+        let paging = get_all_entities.paging();
+        let domain = get_all_entities.domain;
+        Ok((Some(domain), actions::GetAllEntities::<data::Script>::new(get_all_entities.show_deleted, get_all_entities.detailed, paging)))
+    }
+
+    pub fn get_all_views(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let _: NoQuery = from_value(data)?;
+        let get_all_entities: GetAllEntities = from_value(query)?;
+        let paging = get_all_entities.paging();
+        let domain = get_all_entities.domain;
+        Ok((Some(domain), actions::GetAllEntities::<data::View>::new(get_all_entities.show_deleted, get_all_entities.detailed, paging)))
+    }
+
+    pub fn get_all_webhooks(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let _: NoQuery = from_value(data)?;
+        let get_all_entities: GetAllEntities = from_value(query)?;
+        let paging = get_all_entities.paging();
         let domain = get_all_entities.domain;
-        Ok((Some(domain), actions::GetAllEntities::<data::Script>::new(get_all_entities.show_deleted)))
+        Ok((Some(domain), actions::GetAllEntities::<data::Webhook>::new(get_all_entities.show_deleted, get_all_entities.detailed, paging)))
     }
 
     pub fn create_table(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
@@ -138,6 +442,20 @@ pub mod manage {
         Ok((Some(domain), actions::CreateEntity::<data::Script>::new(entity)))
     }
 
+    pub fn create_view(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let entity: data::View = from_value(data)?;
+        let domain_query: GetFromDomain = from_value(query)?;
+        let domain = domain_query.domain;
+        Ok((Some(domain), actions::CreateEntity::<data::View>::new(entity)))
+    }
+
+    pub fn create_webhook(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let entity: data::Webhook = from_value(data)?;
+        let domain_query: GetFromDomain = from_value(query)?;
+        let domain = domain_query.domain;
+        Ok((Some(domain), actions::CreateEntity::<data::Webhook>::new(entity)))
+    }
+
     pub fn get_table(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
         let _: NoQuery = from_value(data)?;
         let get_entity: GetEntity = from_value(query)?;
@@ -159,6 +477,20 @@ pub mod manage {
         Ok((Some(domain), actions::GetEntity::<data::Script>::new(get_entity.name)))
     }
 
+    pub fn get_view(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let _: NoQuery = from_value(data)?;
+        let get_entity: GetEntity = from_value(query)?;
+        let domain = get_entity.domain;
+        Ok((Some(domain), actions::GetEntity::<data::View>::new(get_entity.name)))
+    }
+
+    pub fn get_webhook(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let _: NoQuery = from_value(data)?;
+        let get_entity: GetEntity = from_value(query)?;
+        let domain = get_entity.domain;
+        Ok((Some(domain), actions::GetEntity::<data::Webhook>::new(get_entity.name)))
+    }
+
     pub fn update_table(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
         let entity: data::DataStoreEntity = from_value(data)?;
         let get_entity: GetEntity = from_value(query)?;
@@ -166,6 +498,20 @@ pub mod manage {
         Ok((Some(domain), actions::UpdateEntity::<data::DataStoreEntity>::new(get_entity.name, entity)))
     }
 
+    pub fn preview_schema_change(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let entity: data::DataStoreEntity = from_value(data)?;
+        let get_entity: GetEntity = from_value(query)?;
+        let domain = get_entity.domain;
+        Ok((Some(domain), actions::PreviewSchemaChange::<_>::new(get_entity.name, entity)))
+    }
+
+    pub fn copy_table(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let copy_data: CopyTableData = from_value(data)?;
+        let get_entity: GetEntity = from_value(query)?;
+        let domain = get_entity.domain;
+        Ok((Some(domain), actions::CopyTable::<_>::new(get_entity.name, copy_data.target, copy_data.with_data)))
+    }
+
     pub fn update_query(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
         let entity: data::DataQueryEntity = from_value(data)?;
         let get_entity: GetEntity = from_value(query)?;
@@ -180,6 +526,20 @@ pub mod manage {
         Ok((Some(domain), actions::UpdateEntity::<data::Script>::new(get_entity.name, entity)))
     }
 
+    pub fn update_view(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let entity: data::View = from_value(data)?;
+        let get_entity: GetEntity = from_value(query)?;
+        let domain = get_entity.domain;
+        Ok((Some(domain), actions::UpdateEntity::<data::View>::new(get_entity.name, entity)))
+    }
+
+    pub fn update_webhook(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let entity: data::Webhook = from_value(data)?;
+        let get_entity: GetEntity = from_value(query)?;
+        let domain = get_entity.domain;
+        Ok((Some(domain), actions::UpdateEntity::<data::Webhook>::new(get_entity.name, entity)))
+    }
+
     pub fn delete_table(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
         let _: NoQuery = from_value(data)?;
         let get_entity: GetEntity = from_value(query)?;
@@ -201,11 +561,47 @@ pub mod manage {
         Ok((Some(domain), actions::DeleteEntity::<data::Script>::new(get_entity.name)))
     }
 
-    pub fn query_table_data(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
-        let table_query: Value = data;
+    pub fn delete_view(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let _: NoQuery = from_value(data)?;
+        let get_entity: GetEntity = from_value(query)?;
+        let domain = get_entity.domain;
+        Ok((Some(domain), actions::DeleteEntity::<data::View>::new(get_entity.name)))
+    }
+
+    pub fn delete_webhook(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let _: NoQuery = from_value(data)?;
+        let get_entity: GetEntity = from_value(query)?;
+        let domain = get_entity.domain;
+        Ok((Some(domain), actions::DeleteEntity::<data::Webhook>::new(get_entity.name)))
+    }
+
+    pub fn refresh_view(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let _: NoQuery = from_value(data)?;
         let get_entity: GetEntity = from_value(query)?;
         let domain = get_entity.domain;
-        Ok((Some(domain), actions::QueryTableData::<_>::new(get_entity.name, table_query)))
+        Ok((Some(domain), actions::RefreshView::<_>::new(get_entity.name)))
+    }
+
+    pub fn apply_retention_policy(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let _: NoQuery = from_value(data)?;
+        let get_entity: GetEntity = from_value(query)?;
+        let domain = get_entity.domain;
+        Ok((Some(domain), actions::ApplyRetentionPolicy::<_>::new(get_entity.name)))
+    }
+
+    pub fn apply_retention_policies(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let _: NoQuery = from_value(data)?;
+        let get_from_domain: GetFromDomain = from_value(query)?;
+        let domain = get_from_domain.domain;
+        Ok((Some(domain), actions::ApplyRetentionPolicies::<_>::new()))
+    }
+
+    pub fn query_table_data(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let table_query: Value = data;
+        let get_table_data: GetTableData = from_value(query)?;
+        let domain = get_table_data.domain;
+        let format = result_format(get_table_data.format)?;
+        Ok((Some(domain), actions::QueryTableData::<_>::new(get_table_data.name, table_query, format)))
     }
 
     pub fn insert_table_data(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
@@ -229,11 +625,125 @@ pub mod manage {
         Ok((Some(domain), actions::RemoveTableData::<_>::new(get_entity.name, keys)))
     }
 
+    pub fn bulk_modify_table_data(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let operations: Vec<data::utils::TableDataOperation> = from_value(data)?;
+        let bulk_modify: BulkModifyTable = from_value(query)?;
+        let domain = bulk_modify.domain;
+        Ok((Some(domain), actions::BulkModifyTableData::<_>::new(bulk_modify.name, operations, bulk_modify.skip_failed)))
+    }
+
+    pub fn modify_table_data_by_filter(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let filtered_values: Value = data;
+        let get_entity: GetEntity = from_value(query)?;
+        let domain = get_entity.domain;
+        Ok((Some(domain), actions::ModifyTableDataByFilter::<_>::new(get_entity.name, filtered_values)))
+    }
+
+    pub fn remove_table_data_by_filter(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let filter: Value = data;
+        let get_entity: GetEntity = from_value(query)?;
+        let domain = get_entity.domain;
+        Ok((Some(domain), actions::RemoveTableDataByFilter::<_>::new(get_entity.name, filter)))
+    }
+
+    pub fn get_row_history(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let key: Value = data;
+        let get_entity: GetEntity = from_value(query)?;
+        let domain = get_entity.domain;
+        Ok((Some(domain), actions::GetRowHistory::<_>::new(get_entity.name, key)))
+    }
+
     pub fn run_query(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
         let params: Value = data;
+        let get_query_result: GetQueryResult = from_value(query)?;
+        let domain = get_query_result.domain;
+        let format = result_format(get_query_result.format)?;
+        Ok((Some(domain), actions::RunQuery::<_>::new(get_query_result.name, params, format, get_query_result.limit, get_query_result.offset)))
+    }
+
+    pub fn run_query_into_table(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let params: Value = data;
+        let query_into_table: QueryIntoTable = from_value(query)?;
+        let domain = query_into_table.domain;
+        Ok((Some(domain), actions::RunQueryIntoTable::<_>::new(query_into_table.name, query_into_table.target_table, query_into_table.mode, params)))
+    }
+
+    pub fn explain_query(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let params: Value = data;
+        let get_entity: GetEntity = from_value(query)?;
+        let domain = get_entity.domain;
+        Ok((Some(domain), actions::ExplainQuery::<_>::new(get_entity.name, params)))
+    }
+
+    pub fn list_scheduled_runs(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let _: NoQuery = from_value(data)?;
+        let get_entity: GetEntity = from_value(query)?;
+        let domain = get_entity.domain;
+        Ok((Some(domain), actions::ListScheduledRuns::<_>::new(get_entity.name)))
+    }
+
+    pub fn get_query_history(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let _: NoQuery = from_value(data)?;
+        let get_entity: GetEntity = from_value(query)?;
+        let domain = get_entity.domain;
+        Ok((Some(domain), actions::GetQueryHistory::<_>::new(get_entity.name)))
+    }
+
+    pub fn snapshot_query_result(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let params: Value = data;
+        let get_query_snapshot: GetQuerySnapshot = from_value(query)?;
+        let domain = get_query_snapshot.domain;
+        Ok((Some(domain), actions::SnapshotQueryResult::<_>::new(get_query_snapshot.name, get_query_snapshot.snapshot, params)))
+    }
+
+    pub fn get_query_snapshot(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let _: NoQuery = from_value(data)?;
+        let get_query_snapshot: GetQuerySnapshot = from_value(query)?;
+        let domain = get_query_snapshot.domain;
+        let format = result_format(get_query_snapshot.format)?;
+        Ok((Some(domain), actions::GetQuerySnapshot::<_>::new(get_query_snapshot.name, get_query_snapshot.snapshot, format)))
+    }
+
+    pub fn list_query_snapshots(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let _: NoQuery = from_value(data)?;
         let get_entity: GetEntity = from_value(query)?;
         let domain = get_entity.domain;
-        Ok((Some(domain), actions::RunQuery::<_>::new(get_entity.name, params)))
+        Ok((Some(domain), actions::ListQuerySnapshots::<_>::new(get_entity.name)))
+    }
+
+    pub fn get_query_dependencies(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let _: NoQuery = from_value(data)?;
+        let get_entity: GetEntity = from_value(query)?;
+        let domain = get_entity.domain;
+        Ok((Some(domain), actions::GetQueryDependencies::<_>::new(get_entity.name)))
+    }
+
+    pub fn get_table_dependents(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let _: NoQuery = from_value(data)?;
+        let get_entity: GetEntity = from_value(query)?;
+        let domain = get_entity.domain;
+        Ok((Some(domain), actions::GetTableDependents::<_>::new(get_entity.name)))
+    }
+
+    pub fn restore_query_version(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let query_version: QueryVersion = from_value(data)?;
+        let get_entity: GetEntity = from_value(query)?;
+        let domain = get_entity.domain;
+        Ok((Some(domain), actions::RestoreQueryVersion::<_>::new(get_entity.name, query_version.version)))
+    }
+
+    pub fn list_running_queries(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let _: NoQuery = from_value(data)?;
+        let _: NoQuery = from_value(query)?;
+        Ok((None, actions::ListRunningQueries::<_>::new()))
+    }
+
+    /// has to be sent to the domain the query is running in
+    pub fn cancel_running_query(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let running_query: RunningQueryId = from_value(data)?;
+        let get_from_domain: GetFromDomain = from_value(query)?;
+        let domain = get_from_domain.domain;
+        Ok((Some(domain), actions::CancelRunningQuery::<_>::new(running_query.id)))
     }
 
     pub fn run_script(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
@@ -242,6 +752,131 @@ pub mod manage {
         let domain = get_entity.domain;
         Ok((Some(domain), actions::RunScript::<_>::new(get_entity.name, param)))
     }
+
+    /// the data are the params to check, `null` to only check the script
+    pub fn validate_script(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let param: Option<data::ScriptParam> = from_value(data)?;
+        let get_entity: GetEntity = from_value(query)?;
+        let domain = get_entity.domain;
+        Ok((Some(domain), actions::ValidateScript::<_>::new(get_entity.name, param)))
+    }
+
+    /// only over the websockets, the output lines are sent to the session that called it
+    pub fn stream_script(data: Value, query: Value, sender: mpsc::Sender<ScriptOutputLine>) -> Result<(Option<String>, impl Action), Error> {
+        let param: data::ScriptParam = from_value(data)?;
+        let get_entity: GetEntity = from_value(query)?;
+        let domain = get_entity.domain;
+        Ok((Some(domain), actions::StreamScript::<_>::new(get_entity.name, param, sender)))
+    }
+
+    pub fn get_script_history(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let _: NoQuery = from_value(data)?;
+        let get_entity: GetEntity = from_value(query)?;
+        let domain = get_entity.domain;
+        Ok((Some(domain), actions::GetScriptHistory::<_>::new(get_entity.name)))
+    }
+
+    pub fn restore_script_version(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let script_version: ScriptVersion = from_value(data)?;
+        let get_entity: GetEntity = from_value(query)?;
+        let domain = get_entity.domain;
+        Ok((Some(domain), actions::RestoreScriptVersion::<_>::new(get_entity.name, script_version.version)))
+    }
+
+    pub fn get_webhook_deliveries(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let _: NoQuery = from_value(data)?;
+        let get_entity: GetEntity = from_value(query)?;
+        let domain = get_entity.domain;
+        Ok((Some(domain), actions::GetWebhookDeliveries::<_>::new(get_entity.name)))
+    }
+
+    pub fn run_script_async(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let param: data::ScriptParam = from_value(data)?;
+        let get_entity: GetEntity = from_value(query)?;
+        let domain = get_entity.domain;
+        Ok((Some(domain), actions::RunScriptAsync::<_>::new(get_entity.name, param)))
+    }
+
+    pub fn get_job_status(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let script_job_id: ScriptJobId = from_value(data)?;
+        let _: NoQuery = from_value(query)?;
+        Ok((None, actions::GetJobStatus::<_>::new(script_job_id.id)))
+    }
+
+    pub fn get_job_result(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let script_job_id: ScriptJobId = from_value(data)?;
+        let _: NoQuery = from_value(query)?;
+        Ok((None, actions::GetJobResult::<_>::new(script_job_id.id)))
+    }
+
+    pub fn get_script_runs(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let _: NoQuery = from_value(data)?;
+        let get_entity: GetEntity = from_value(query)?;
+        let domain = get_entity.domain;
+        Ok((Some(domain), actions::GetScriptRuns::<_>::new(get_entity.name)))
+    }
+
+    pub fn get_script_run_log(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let script_run_id: ScriptRunId = from_value(data)?;
+        let get_entity: GetEntity = from_value(query)?;
+        let domain = get_entity.domain;
+        Ok((Some(domain), actions::GetScriptRunLog::<_>::new(get_entity.name, script_run_id.id)))
+    }
+
+    pub fn set_script_secret(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let secret: ScriptSecretValue = from_value(data)?;
+        let get_entity: GetEntity = from_value(query)?;
+        let domain = get_entity.domain;
+        Ok((Some(domain), actions::SetScriptSecret::<_>::new(get_entity.name, secret.name, secret.value)))
+    }
+
+    pub fn remove_script_secret(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let secret: ScriptSecretName = from_value(data)?;
+        let get_entity: GetEntity = from_value(query)?;
+        let domain = get_entity.domain;
+        Ok((Some(domain), actions::RemoveScriptSecret::<_>::new(get_entity.name, secret.name)))
+    }
+
+    pub fn get_script_secrets(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let _: NoQuery = from_value(data)?;
+        let get_entity: GetEntity = from_value(query)?;
+        let domain = get_entity.domain;
+        Ok((Some(domain), actions::GetScriptSecrets::<_>::new(get_entity.name)))
+    }
+
+    pub fn set_read_only_mode(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let read_only_mode: ReadOnlyMode = from_value(data)?;
+        let _: NoQuery = from_value(query)?;
+        Ok((None, actions::SetReadOnlyMode::<_>::new(read_only_mode.read_only)))
+    }
+
+    pub fn get_broadcast_metrics(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let _: NoQuery = from_value(data)?;
+        let _: NoQuery = from_value(query)?;
+        Ok((None, actions::GetBroadcastMetrics::<_>::new()))
+    }
+
+    pub fn get_database_metrics(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let _: NoQuery = from_value(data)?;
+        let _: NoQuery = from_value(query)?;
+        Ok((None, actions::GetDatabaseMetrics::<_>::new()))
+    }
+
+    pub fn get_audit_trail(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let _: NoQuery = from_value(data)?;
+        let get_audit_trail: GetAuditTrail = from_value(query)?;
+        let filter = get_audit_trail.filter();
+        let limit = get_audit_trail.limit.unwrap_or(GetAuditTrail::DEFAULT_LIMIT);
+        let offset = get_audit_trail.offset.unwrap_or(0);
+        Ok((None, actions::GetAuditTrail::<_>::new(filter, limit, offset)))
+    }
+
+    pub fn get_entity_usage(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let _: NoQuery = from_value(data)?;
+        let domain_query: GetFromDomain = from_value(query)?;
+        let domain = domain_query.domain;
+        Ok((Some(domain), actions::GetEntityUsage::<_>::new()))
+    }
 }
 
 pub mod pubsub {
@@ -249,9 +884,10 @@ pub mod pubsub {
 
     pub fn subscribe_to(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
         let channel: data::channels::Channels = from_value(data)?;
-        let domain_query: GetFromDomain = from_value(query)?;
-        let domain = domain_query.domain;
-        Ok((Some(domain), actions::SubscribeTo::<_>::new(channel)))
+        let subscribe_to_params: SubscribeToParams = from_value(query)?;
+        let domain = subscribe_to_params.domain;
+        let ttl = subscribe_to_params.ttl.map(|x| x as u64);
+        Ok((Some(domain), actions::SubscribeTo::<_>::new(channel, subscribe_to_params.durable, ttl)))
     }
 
     pub fn unsubscribe_from(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
@@ -263,9 +899,9 @@ pub mod pubsub {
 
     pub fn unsubscribe_all(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
         let _: NoQuery = from_value(data)?;
-        let _: NoQuery = from_value(query)?;
+        let unsubscribe_all_params: UnsubscribeAllParams = from_value(query)?;
 
-        Ok((None, actions::UnsubscribeAll::<_>::new()))
+        Ok((None, actions::UnsubscribeAll::<_>::new(unsubscribe_all_params.keep_durable)))
     }
 
     pub fn get_subscribers(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
@@ -275,6 +911,19 @@ pub mod pubsub {
         Ok((Some(domain), actions::GetSubscribers::<_>::new(channel)))
     }
 
+    pub fn list_channels(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let _: NoQuery = from_value(data)?;
+        let domain_query: GetFromDomain = from_value(query)?;
+        let domain = domain_query.domain;
+        Ok((Some(domain), actions::ListChannels::<_>::new()))
+    }
+
+    pub fn list_my_subscriptions(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let _: NoQuery = from_value(data)?;
+        let _: NoQuery = from_value(query)?;
+        Ok((None, actions::ListMySubscriptions::<_>::new()))
+    }
+
     pub fn get_messages(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
         let _: NoQuery = from_value(data)?;
         let range: TimeRange = from_value(query)?;
@@ -282,6 +931,62 @@ pub mod pubsub {
         Ok((None, actions::GetMessages::<_>::new(range.start_time, range.end_time)))
 
     }
+
+    pub fn get_pending_messages(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let _: NoQuery = from_value(data)?;
+        let pending_messages_params: PendingMessagesParams = from_value(query)?;
+        Ok((None, actions::GetPendingMessages::<_>::new(pending_messages_params.redeliver)))
+    }
+
+    pub fn ack_messages(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let ack_messages: AckMessages = from_value(data)?;
+        let _: NoQuery = from_value(query)?;
+        Ok((None, actions::AckMessages::<_>::new(ack_messages.ids)))
+    }
+
+    pub fn purge_messages(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let retention: data::message_retention::MessageRetention = from_value(data)?;
+        let _: NoQuery = from_value(query)?;
+        Ok((None, actions::PurgeMessages::<_>::new(retention)))
+    }
+
+    pub fn expire_subscriptions(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let expire_subscriptions: ExpireSubscriptions = from_value(data)?;
+        let _: NoQuery = from_value(query)?;
+        Ok((None, actions::ExpireSubscriptions::<_>::new(expire_subscriptions.inactive_for)))
+    }
+
+    pub fn add_channel_webhook(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let webhook: data::webhook::NewChannelWebhook = from_value(data)?;
+        let _: NoQuery = from_value(query)?;
+        Ok((None, actions::AddChannelWebhook::<_>::new(webhook)))
+    }
+
+    pub fn remove_channel_webhook(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let channel_webhook_id: ChannelWebhookId = from_value(data)?;
+        let _: NoQuery = from_value(query)?;
+        Ok((None, actions::RemoveChannelWebhook::<_>::new(channel_webhook_id.id)))
+    }
+
+    pub fn get_channel_webhooks(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let _: NoQuery = from_value(data)?;
+        let _: NoQuery = from_value(query)?;
+        Ok((None, actions::GetChannelWebhooks::<_>::new()))
+    }
+
+    pub fn get_dead_letters(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let _: NoQuery = from_value(data)?;
+        let get_dead_letters: GetDeadLetters = from_value(query)?;
+        let limit = get_dead_letters.limit.unwrap_or(GetDeadLetters::DEFAULT_LIMIT);
+        let offset = get_dead_letters.offset.unwrap_or(0);
+        Ok((None, actions::GetDeadLetters::<_>::new(limit, offset)))
+    }
+
+    pub fn redeliver_dead_letter(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let dead_letter_id: DeadLetterId = from_value(data)?;
+        let _: NoQuery = from_value(query)?;
+        Ok((None, actions::RedeliverDeadLetter::<_>::new(dead_letter_id.id)))
+    }
 }
 
 pub mod users {
@@ -307,8 +1012,8 @@ pub mod users {
 
     pub fn get_all_users(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
         let _: NoQuery = from_value(data)?;
-        let _: NoQuery = from_value(query)?;
-        Ok((None, actions::GetAllUsers::<_>::new()))
+        let get_all_paged: GetAllPaged = from_value(query)?;
+        Ok((None, actions::GetAllUsers::<_>::new(get_all_paged.paging())))
     }
 
     pub fn add_user(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
@@ -357,8 +1062,8 @@ pub mod users {
 
     pub fn get_all_roles(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
         let _: NoQuery = from_value(data)?;
-        let _: NoQuery = from_value(query)?;
-        Ok((None, actions::GetAllRoles::<_>::new()))
+        let get_all_paged: GetAllPaged = from_value(query)?;
+        Ok((None, actions::GetAllRoles::<_>::new(get_all_paged.paging())))
     }
 
     pub fn attach_permission_for_role(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
@@ -387,3 +1092,29 @@ pub mod users {
 
 }
 
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_list_options_from_query_string() {
+        let query_string = json!({ "domain": "postgres", "showDeleted": "true", "limit": "10", "offset": "20" });
+        let get_all_entities: GetAllEntities = from_value(query_string).unwrap();
+        assert!(get_all_entities.show_deleted);
+        assert!(get_all_entities.detailed);
+        assert_eq!(get_all_entities.paging(), Paging::new(Some(10), Some(20)));
+
+        let websocket = json!({ "domain": "postgres", "detailed": false, "limit": 5 });
+        let get_all_entities: GetAllEntities = from_value(websocket).unwrap();
+        assert!(!get_all_entities.show_deleted);
+        assert!(!get_all_entities.detailed);
+        assert_eq!(get_all_entities.paging(), Paging::new(Some(5), None));
+
+        let get_all_paged: GetAllPaged = from_value(json!({})).unwrap();
+        assert!(!get_all_paged.paging().is_paginated());
+
+        assert!(from_value::<GetAllPaged>(json!({ "limit": "-1" })).is_err());
+        assert!(from_value::<GetAllEntities>(json!({ "domain": "postgres", "detailed": "yes" })).is_err());
+    }
+}
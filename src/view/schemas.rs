@@ -0,0 +1,314 @@
+//! The JSON Schemas of the procedure payloads, see `Procedure::validate`.
+//! The params come from the query string over http, so the flags and the counts can be strings as well
+
+use serde_json::Value;
+
+fn name() -> Value {
+    json!({ "type": "string", "minLength": 1 })
+}
+
+fn flag() -> Value {
+    json!({ "type": ["boolean", "string"] })
+}
+
+fn count() -> Value {
+    json!({ "type": ["integer", "string"], "minimum": 0 })
+}
+
+fn object(required: &[&str], properties: Value) -> Value {
+    json!({ "type": "object", "required": required, "properties": properties })
+}
+
+pub mod params {
+    use super::*;
+
+    pub fn domain() -> Value {
+        object(&["domain"], json!({ "domain": name() }))
+    }
+
+    pub fn entity() -> Value {
+        object(&["name", "domain"], json!({ "name": name(), "domain": name() }))
+    }
+
+    pub fn entity_list() -> Value {
+        object(&["domain"], json!({
+            "domain": name(),
+            "showDeleted": flag(),
+            "detailed": flag(),
+            "limit": count(),
+            "offset": count(),
+        }))
+    }
+
+    pub fn subscription() -> Value {
+        object(&["domain"], json!({ "domain": name(), "durable": flag(), "ttl": count() }))
+    }
+
+    pub fn unsubscribe_all() -> Value {
+        object(&[], json!({ "keepDurable": flag() }))
+    }
+
+    pub fn pending_messages() -> Value {
+        object(&[], json!({ "redeliver": flag() }))
+    }
+
+    pub fn paged() -> Value {
+        object(&[], json!({ "limit": count(), "offset": count() }))
+    }
+
+    pub fn table_data() -> Value {
+        object(&["name", "domain"], json!({ "name": name(), "domain": name(), "format": { "type": "string" } }))
+    }
+
+    pub fn bulk_modify() -> Value {
+        object(&["name", "domain"], json!({ "name": name(), "domain": name(), "skipFailed": flag() }))
+    }
+
+    pub fn query_result() -> Value {
+        object(&["name", "domain"], json!({
+            "name": name(),
+            "domain": name(),
+            "limit": count(),
+            "offset": count(),
+            "format": { "type": "string" },
+        }))
+    }
+
+    pub fn query_into_table() -> Value {
+        object(&["name", "domain", "targetTable", "mode"], json!({
+            "name": name(),
+            "domain": name(),
+            "targetTable": name(),
+            "mode": { "enum": ["replace", "append"] },
+        }))
+    }
+
+    pub fn query_snapshot() -> Value {
+        object(&["name", "domain", "snapshot"], json!({
+            "name": name(),
+            "domain": name(),
+            "snapshot": name(),
+            "format": { "type": "string" },
+        }))
+    }
+
+    pub fn time_range() -> Value {
+        object(&["start", "end"], json!({ "start": { "type": "string" }, "end": { "type": "string" } }))
+    }
+
+    pub fn user() -> Value {
+        object(&["username"], json!({ "username": name() }))
+    }
+
+    pub fn role() -> Value {
+        object(&["rolename"], json!({ "rolename": name() }))
+    }
+
+    pub fn audit_trail() -> Value {
+        object(&[], json!({
+            "limit": count(),
+            "offset": count(),
+            "username": name(),
+            "procedure": name(),
+            "domain": name(),
+            "entityType": name(),
+            "entityName": name(),
+        }))
+    }
+}
+
+pub mod data {
+    use super::*;
+
+    pub fn table() -> Value {
+        object(&["name", "description", "schema"], json!({
+            "name": name(),
+            "description": { "type": "string" },
+            "schema": {
+                "type": "object",
+                "properties": {
+                    "columns": {
+                        "type": "array",
+                        "items": object(&["name", "dataType"], json!({ "name": name() })),
+                    },
+                },
+            },
+            "auditRows": { "type": "boolean" },
+            "scope": { "type": ["string", "null"] },
+        }))
+    }
+
+    pub fn query() -> Value {
+        object(&["name", "description", "statement"], json!({
+            "name": name(),
+            "description": { "type": "string" },
+            "statement": { "type": "string" },
+            "params": {
+                "type": "array",
+                "items": object(&["name", "dataType"], json!({ "name": name() })),
+            },
+            "statementTimeout": { "type": ["integer", "null"], "minimum": 0 },
+        }))
+    }
+
+    pub fn script() -> Value {
+        object(&["name", "description", "text"], json!({
+            "name": name(),
+            "description": { "type": "string" },
+            "text": { "type": "string" },
+            "requirements": { "type": "array", "items": { "type": "string" } },
+            "params": { "type": "array", "items": object(&["name"], json!({ "name": name() })) },
+        }))
+    }
+
+    pub fn view() -> Value {
+        object(&["name", "description", "statement"], json!({
+            "name": name(),
+            "description": { "type": "string" },
+            "statement": { "type": "string" },
+            "materialized": { "type": "boolean" },
+        }))
+    }
+
+    pub fn webhook() -> Value {
+        object(&["name", "description", "url", "channels"], json!({
+            "name": name(),
+            "description": { "type": "string" },
+            "url": name(),
+            "secret": { "type": "string" },
+            "channels": { "type": "array", "items": { "type": "object" } },
+            "retry": {
+                "type": "object",
+                "properties": {
+                    "maxAttempts": { "type": "integer", "minimum": 1 },
+                    "backoffSeconds": { "type": "integer", "minimum": 0 },
+                },
+            },
+        }))
+    }
+
+    pub fn channel_webhook() -> Value {
+        object(&["channel", "url", "secret"], json!({
+            "channel": { "type": "object" },
+            "url": name(),
+            "secret": name(),
+            "retry": {
+                "type": "object",
+                "properties": {
+                    "maxAttempts": { "type": "integer", "minimum": 1 },
+                    "backoffSeconds": { "type": "integer", "minimum": 0 },
+                },
+            },
+        }))
+    }
+
+    pub fn channel_webhook_id() -> Value {
+        object(&["id"], json!({ "id": { "type": "integer" } }))
+    }
+
+    pub fn dead_letter_id() -> Value {
+        object(&["id"], json!({ "id": { "type": "integer" } }))
+    }
+
+    /// one row or a list of them
+    pub fn rows() -> Value {
+        json!({ "type": ["object", "array"], "items": { "type": "object" } })
+    }
+
+    pub fn operations() -> Value {
+        json!({ "type": "array", "items": { "type": "object" } })
+    }
+
+    pub fn channel() -> Value {
+        json!({ "type": "object" })
+    }
+
+    pub fn copy_table() -> Value {
+        object(&["target"], json!({ "target": name(), "withData": { "type": "boolean" } }))
+    }
+
+    pub fn version() -> Value {
+        object(&["version"], json!({ "version": { "type": "integer" } }))
+    }
+
+    pub fn run_id() -> Value {
+        object(&["id"], json!({ "id": { "type": "integer" } }))
+    }
+
+    pub fn job_id() -> Value {
+        object(&["id"], json!({ "id": name() }))
+    }
+
+    pub fn secret() -> Value {
+        object(&["name", "value"], json!({ "name": name(), "value": { "type": "string" } }))
+    }
+
+    pub fn secret_name() -> Value {
+        object(&["name"], json!({ "name": name() }))
+    }
+
+    pub fn message_ids() -> Value {
+        object(&["ids"], json!({ "ids": { "type": "array", "items": { "type": "integer" } } }))
+    }
+
+    /// at least one of the limits, the messages past either are purged
+    pub fn message_retention() -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "maxAge": { "type": "integer", "minimum": 0 },
+                "maxPerChannel": { "type": "integer", "minimum": 0 },
+            },
+            "anyOf": [{ "required": ["maxAge"] }, { "required": ["maxPerChannel"] }],
+        })
+    }
+
+    pub fn expire_subscriptions() -> Value {
+        object(&[], json!({ "inactiveFor": { "type": ["integer", "null"], "minimum": 0 } }))
+    }
+
+    pub fn read_only_mode() -> Value {
+        object(&["readOnly"], json!({ "readOnly": { "type": "boolean" } }))
+    }
+
+    pub fn credentials() -> Value {
+        object(&["username", "password"], json!({ "username": name(), "password": { "type": "string" } }))
+    }
+
+    pub fn refresh_token() -> Value {
+        object(&["refreshToken"], json!({ "refreshToken": name() }))
+    }
+
+    pub fn new_user() -> Value {
+        object(&["username", "email", "password"], json!({
+            "username": name(),
+            "email": name(),
+            "password": name(),
+            "displayName": { "type": ["string", "null"] },
+        }))
+    }
+
+    pub fn invite() -> Value {
+        object(&["email"], json!({ "email": name() }))
+    }
+
+    pub fn password_reset() -> Value {
+        object(&["username", "oldPassword", "newPassword"], json!({
+            "username": name(),
+            "oldPassword": { "type": "string" },
+            "newPassword": name(),
+        }))
+    }
+
+    pub fn new_role() -> Value {
+        object(&["name"], json!({ "name": name(), "description": { "type": ["string", "null"] } }))
+    }
+
+    pub fn role_name() -> Value {
+        object(&["name"], json!({ "name": name() }))
+    }
+
+    pub fn permission() -> Value {
+        json!({ "type": "object" })
+    }
+}
@@ -0,0 +1,126 @@
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use std::time::Instant;
+
+use actix::prelude::*;
+use actix::actors::signal;
+use actix_web::server;
+
+/// how often the drain checks whether the actions are done
+const DRAIN_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+// set once, by the first SIGTERM or SIGINT
+static SHUTTING_DOWN: AtomicBool = AtomicBool::new(false);
+static IN_FLIGHT: AtomicUsize = AtomicUsize::new(0);
+
+/// Whether the server is draining. The jobs don't start anything new, the sockets send their last
+/// messages and close, and the readiness probe fails so that the load balancers stop sending requests
+pub fn is_shutting_down() -> bool {
+    SHUTTING_DOWN.load(Ordering::SeqCst)
+}
+
+/// the actions and the webhook deliveries that are running
+pub fn in_flight() -> usize {
+    IN_FLIGHT.load(Ordering::SeqCst)
+}
+
+/// Counted as in flight until dropped, the shutdown waits for all of them before stopping the system
+#[derive(Debug)]
+pub struct InFlightGuard(());
+
+impl InFlightGuard {
+    pub fn enter() -> Self {
+        IN_FLIGHT.fetch_add(1, Ordering::SeqCst);
+        InFlightGuard(())
+    }
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        IN_FLIGHT.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Stops the server on SIGTERM and SIGINT without cutting off the requests:
+/// 1. the listeners are closed, and the open connections get until the deadline to finish
+/// 2. the actions still running, e.g. the ones of the jobs, get what is left of it
+/// 3. the system is stopped
+/// A second signal, or SIGQUIT, stops the system right away
+pub struct GracefulShutdown {
+    server: Addr<server::Server>,
+    timeout: Duration,
+    deadline: Option<Instant>,
+}
+
+impl GracefulShutdown {
+    pub fn new(server: Addr<server::Server>, timeout: Duration) -> Self {
+        Self {
+            server,
+            timeout,
+            deadline: None,
+        }
+    }
+
+    fn begin(&mut self, ctx: &mut Context<Self>) {
+        if SHUTTING_DOWN.swap(true, Ordering::SeqCst) {
+            warn!("Stopping now, without waiting for {} actions", in_flight());
+            System::current().stop();
+            return;
+        }
+
+        info!("Shutting down, waiting up to {:?} for the requests to finish", &self.timeout);
+        self.deadline = Some(Instant::now() + self.timeout);
+
+        self.server
+            .send(server::StopServer { graceful: true })
+            .into_actor(self)
+            .then(|res, _, ctx| {
+                if let Err(err) = res {
+                    warn!("Could not stop the http server gracefully: {:?}", &err);
+                }
+                debug!("The http server is stopped, {} actions are still running", in_flight());
+                ctx.run_interval(DRAIN_CHECK_INTERVAL, |shutdown, _| shutdown.drain());
+                actix::fut::ok(())
+            })
+            .spawn(ctx);
+    }
+
+    fn drain(&mut self) {
+        let is_past_deadline = self.deadline.map(|x| Instant::now() >= x).unwrap_or(true);
+        let running = in_flight();
+        if running == 0 {
+            info!("All the actions are done, stopping");
+            System::current().stop();
+        } else if is_past_deadline {
+            warn!("Stopping with {} actions still running", running);
+            System::current().stop();
+        }
+    }
+}
+
+impl Actor for GracefulShutdown {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        signal::ProcessSignals::from_registry()
+            .do_send(signal::Subscribe(ctx.address().recipient()));
+    }
+}
+
+impl Handler<signal::Signal> for GracefulShutdown {
+    type Result = ();
+
+    fn handle(&mut self, msg: signal::Signal, ctx: &mut Self::Context) {
+        match msg.0 {
+            signal::SignalType::Term | signal::SignalType::Int => self.begin(ctx),
+            signal::SignalType::Quit => {
+                warn!("Stopping now, without waiting for {} actions", in_flight());
+                System::current().stop();
+            },
+            _ => (),
+        }
+    }
+}
+
@@ -0,0 +1,208 @@
+use std::borrow::Cow;
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+
+use actix_web::HttpRequest;
+use actix_web::HttpResponse;
+use actix_web::http::Method;
+use actix_web::http::header;
+
+const INDEX_FILE: &str = "index.html";
+/// the files other than the index are expected to have a hash in their name, so they can be kept for a while
+const ASSET_MAX_AGE: u64 = 24 * 60 * 60;
+
+/// A file compiled into the binary, e.g. `EmbeddedFile { path: "index.html", contents: include_bytes!("../admin/index.html") }`
+#[derive(Debug, Clone, Copy)]
+pub struct EmbeddedFile {
+    /// relative to the root of the bundle, without a leading `/`
+    pub path: &'static str,
+    pub contents: &'static [u8],
+}
+
+#[derive(Debug, Clone)]
+pub enum AssetSource {
+    /// read from the disk on every request, so the bundle can be replaced without a restart
+    Directory(PathBuf),
+    Embedded(&'static [EmbeddedFile]),
+}
+
+/// A bundle of static files, e.g. the admin frontend, served from the same port as the api.
+/// With the SPA fallback, the paths that aren't files get the index so that the frontend can route them
+#[derive(Debug, Clone)]
+pub struct StaticAssets {
+    pub source: AssetSource,
+    /// where the bundle is served, the routes of the api have precedence over it
+    pub mount_path: String,
+    pub spa_fallback: bool,
+}
+
+fn content_type(path: &str) -> &'static str {
+    let extension = path.rsplit('.').next().unwrap_or_default().to_lowercase();
+    match extension.as_str() {
+        "html" | "htm" => "text/html; charset=utf-8",
+        "js" | "mjs" => "application/javascript; charset=utf-8",
+        "css" => "text/css; charset=utf-8",
+        "json" | "map" => "application/json",
+        "svg" => "image/svg+xml",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "ico" => "image/x-icon",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        "wasm" => "application/wasm",
+        "txt" => "text/plain; charset=utf-8",
+        _ => "application/octet-stream",
+    }
+}
+
+/// The path of the file in the bundle, None if it tries to leave it
+fn relative_path(path: &str) -> Option<String> {
+    let segments: Vec<&str> = path
+        .split('/')
+        .filter(|x| !x.is_empty() && *x != ".")
+        .collect();
+    if segments.iter().any(|x| *x == ".." || x.contains('\\')) {
+        return None;
+    }
+
+    let relative = segments.join("/");
+    if relative.is_empty() || path.ends_with('/') {
+        Some(if relative.is_empty() { INDEX_FILE.to_string() } else { format!("{}/{}", relative, INDEX_FILE) })
+    } else {
+        Some(relative)
+    }
+}
+
+impl StaticAssets {
+    pub fn directory(path: &Path) -> Self {
+        Self {
+            source: AssetSource::Directory(path.to_path_buf()),
+            mount_path: "/".to_string(),
+            spa_fallback: true,
+        }
+    }
+
+    pub fn embedded(files: &'static [EmbeddedFile]) -> Self {
+        Self {
+            source: AssetSource::Embedded(files),
+            mount_path: "/".to_string(),
+            spa_fallback: true,
+        }
+    }
+
+    pub fn mount_path(mut self, mount_path: &str) -> Self {
+        self.mount_path = mount_path.to_string();
+        self
+    }
+
+    pub fn spa_fallback(mut self, spa_fallback: bool) -> Self {
+        self.spa_fallback = spa_fallback;
+        self
+    }
+
+    /// a missing bundle is reported when the server starts, not on the first request
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.mount_path.starts_with('/') {
+            return Err(format!("the static assets must be mounted on an absolute path, not {:?}", &self.mount_path));
+        }
+        if self.read(INDEX_FILE).is_none() {
+            return Err(match &self.source {
+                AssetSource::Directory(path) => format!("no {} in the static assets directory {:?}", INDEX_FILE, path),
+                AssetSource::Embedded(_) => format!("no {} in the embedded static assets", INDEX_FILE),
+            });
+        }
+        Ok(())
+    }
+
+    fn read(&self, relative_path: &str) -> Option<Cow<'static, [u8]>> {
+        match &self.source {
+            AssetSource::Directory(root) => {
+                let path = root.join(relative_path);
+                if !path.is_file() {
+                    return None;
+                }
+                fs::read(&path).ok().map(Cow::Owned)
+            },
+            AssetSource::Embedded(files) => files
+                .iter()
+                .find(|x| x.path == relative_path)
+                .map(|x| Cow::Borrowed(x.contents)),
+        }
+    }
+
+    /// The file to respond with, the index when the path isn't a file and the fallback applies
+    fn resolve(&self, path: &str, accepts_html: bool) -> Option<(String, Cow<'static, [u8]>)> {
+        let path = if path.starts_with(&self.mount_path) { &path[self.mount_path.len()..] } else { path };
+        let relative_path = relative_path(path)?;
+
+        if let Some(contents) = self.read(&relative_path) {
+            return Some((relative_path, contents));
+        }
+
+        // only the navigations of the browser, a missing script or image is still a 404
+        if self.spa_fallback && accepts_html {
+            return self.read(INDEX_FILE).map(|contents| (INDEX_FILE.to_string(), contents));
+        }
+
+        None
+    }
+
+    pub fn respond<S>(&self, req: &HttpRequest<S>) -> HttpResponse {
+        if req.method() != Method::GET && req.method() != Method::HEAD {
+            return HttpResponse::MethodNotAllowed()
+                .header(header::ALLOW, "GET, HEAD")
+                .finish();
+        }
+
+        let accepts_html = req
+            .headers()
+            .get(header::ACCEPT)
+            .and_then(|x| x.to_str().ok())
+            .map(|x| x.contains("text/html"))
+            .unwrap_or(false);
+
+        match self.resolve(req.path(), accepts_html) {
+            Some((file, contents)) => {
+                // the index links to the current files, so it's always checked again
+                let cache_control = if file.ends_with(INDEX_FILE) {
+                    "no-cache".to_string()
+                } else {
+                    format!("public, max-age={}", ASSET_MAX_AGE)
+                };
+                HttpResponse::Ok()
+                    .content_type(content_type(&file))
+                    .header(header::CACHE_CONTROL, cache_control)
+                    .body(contents.into_owned())
+            },
+            None => HttpResponse::NotFound().finish(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    static FILES: [EmbeddedFile; 2] = [
+        EmbeddedFile { path: "index.html", contents: b"<html></html>" },
+        EmbeddedFile { path: "static/app.js", contents: b"run()" },
+    ];
+
+    #[test]
+    fn test_resolve() {
+        let assets = StaticAssets::embedded(&FILES).mount_path("/admin");
+        assert!(assets.validate().is_ok());
+
+        let file = |path, accepts_html| assets.resolve(path, accepts_html).map(|x| x.0);
+        assert_eq!(file("/admin", true), Some("index.html".to_string()));
+        assert_eq!(file("/admin/static/app.js", false), Some("static/app.js".to_string()));
+        assert_eq!(file("/admin/tables/users", true), Some("index.html".to_string()));
+        assert_eq!(file("/admin/static/missing.js", false), None);
+        assert_eq!(file("/admin/../Cargo.toml", true), None);
+
+        assert!(StaticAssets::embedded(&FILES[1..]).validate().is_err());
+        assert_eq!(content_type("static/app.js"), "application/javascript; charset=utf-8");
+    }
+}
@@ -0,0 +1,227 @@
+use std::fmt::Debug;
+
+use serde::Serialize;
+
+use actix::prelude::*;
+use actix_web::FromRequest;
+use actix_web::HttpRequest;
+use actix_web::HttpResponse;
+use actix_web::Json;
+use actix_web::Query;
+use actix_web::Result as ActixResult;
+use actix_web::dev::JsonConfig;
+use actix_web::http::header::HeaderValue;
+use actix_web::middleware::Middleware;
+use actix_web::middleware::Response;
+
+use connection::AppStateLike;
+use connection::executor::Executor;
+use model::actions::Action;
+use view::action_wrapper::ActionWrapper;
+use view::extensions::ProcedureExt;
+use view::body_limit::BodyLimits;
+use view::procedure::ProcedureBuilder;
+
+/// the version that served the response
+pub const API_VERSION_HEADER: &str = "Api-Version";
+/// set on the paths the clients should move away from
+pub const DEPRECATION_HEADER: &str = "Deprecation";
+/// the path replacing a deprecated one
+pub const LINK_HEADER: &str = "Link";
+
+/// the paths of the api that were there before the versioning, they are the ones of `ApiVersion::LEGACY`
+const LEGACY_PREFIXES: [&str; 4] = ["/manage/", "/users/", "/listen", "/messages/"];
+
+/// The versions of the api, each mounted under `/api/<version>/`. A version keeps its payloads as they are,
+/// the breaking changes go in a new version with its own route table, see `ProcedureExt::add_api_routes`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ApiVersion {
+    V1,
+}
+
+impl ApiVersion {
+    /// the version the unversioned paths serve, for the clients written before the versioning
+    pub const LEGACY: ApiVersion = ApiVersion::V1;
+    pub const LATEST: ApiVersion = ApiVersion::V1;
+
+    pub fn all() -> Vec<ApiVersion> {
+        vec![ApiVersion::V1]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ApiVersion::V1 => "v1",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::all().into_iter().find(|x| x.name() == name)
+    }
+
+    pub fn prefix(&self) -> String {
+        format!("/api/{}", self.name())
+    }
+
+    /// still served, but answered with a `Deprecation` header
+    pub fn is_deprecated(&self) -> bool {
+        match self {
+            ApiVersion::V1 => false,
+        }
+    }
+}
+
+/// The version in the path and the path without it, e.g. `/manage/getTable` for `/api/v1/manage/getTable`.
+/// The version is None for the unversioned paths
+pub fn unversioned_path(path: &str) -> (Option<ApiVersion>, &str) {
+    if path.starts_with("/api/") {
+        let rest = &path["/api/".len()..];
+        let (name, rest) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+        if let Some(version) = ApiVersion::from_name(name) {
+            return (Some(version), rest);
+        }
+    }
+
+    (None, path)
+}
+
+fn is_legacy_path(path: &str) -> bool {
+    LEGACY_PREFIXES.iter().any(|x| path.starts_with(x))
+}
+
+/// Mounts the routes of a version under its prefix, e.g. `/manage/getTable` goes to `/api/v1/manage/getTable`
+pub struct Mounted<'a, P: 'a> {
+    inner: &'a mut P,
+    prefix: String,
+}
+
+impl<'a, P> Mounted<'a, P> {
+    pub fn new(inner: &'a mut P, version: ApiVersion) -> Self {
+        Self {
+            inner,
+            prefix: version.prefix(),
+        }
+    }
+
+    fn path(&self, path: &str) -> String {
+        format!("{}{}", self.prefix, path)
+    }
+}
+
+impl<'a, S, P> ProcedureExt<S> for Mounted<'a, P>
+    where
+        S: AppStateLike + 'static,
+        P: ProcedureExt<S>,
+{
+    fn add_route<JP, QP, A, PB>(&mut self, path: &str, body_limits: &BodyLimits, procedure_builder: PB) -> &mut Self
+        where
+            Executor: Handler<ActionWrapper<A>>,
+            A: Action + Send + 'static,
+            PB: ProcedureBuilder<S, JP, QP, A> + Clone + 'static,
+            JP: Debug + 'static,
+            QP: Debug + 'static,
+            Json<JP>: FromRequest<S, Config = JsonConfig<S>>,
+            Query<QP>: FromRequest<S>,
+            <A as Action>::Ret: Send + Serialize,
+    {
+        let path = self.path(path);
+        self.inner.add_route(&path, body_limits, procedure_builder);
+        self
+    }
+
+    fn add_socket(&mut self, path: &str) -> &mut Self {
+        let path = self.path(path);
+        self.inner.add_socket(&path);
+        self
+    }
+
+    fn add_long_poll(&mut self, path: &str) -> &mut Self {
+        let path = self.path(path);
+        self.inner.add_long_poll(&path);
+        self
+    }
+
+    fn add_table_export(&mut self, path: &str) -> &mut Self {
+        let path = self.path(path);
+        self.inner.add_table_export(&path);
+        self
+    }
+
+    fn add_table_import(&mut self, path: &str, body_limits: &BodyLimits) -> &mut Self {
+        let path = self.path(path);
+        self.inner.add_table_import(&path, body_limits);
+        self
+    }
+
+    fn add_batch(&mut self, path: &str, body_limits: &BodyLimits) -> &mut Self {
+        let path = self.path(path);
+        self.inner.add_batch(&path, body_limits);
+        self
+    }
+
+    fn add_health_probes(&mut self, liveness_path: &str, readiness_path: &str) -> &mut Self {
+        let liveness_path = self.path(liveness_path);
+        let readiness_path = self.path(readiness_path);
+        self.inner.add_health_probes(&liveness_path, &readiness_path);
+        self
+    }
+}
+
+/// The headers telling the client which version answered, and where to go when its path is deprecated.
+/// Returns the headers to set, so that they can be checked without a response
+fn version_headers(path: &str) -> Vec<(&'static str, String)> {
+    match unversioned_path(path) {
+        (Some(version), _) => {
+            let mut headers = vec![(API_VERSION_HEADER, version.name().to_string())];
+            if version.is_deprecated() {
+                headers.push((DEPRECATION_HEADER, "true".to_string()));
+                headers.push((LINK_HEADER, format!("<{}>; rel=\"latest-version\"", ApiVersion::LATEST.prefix())));
+            }
+            headers
+        },
+        (None, path) if is_legacy_path(path) => vec![
+            (API_VERSION_HEADER, ApiVersion::LEGACY.name().to_string()),
+            (DEPRECATION_HEADER, "true".to_string()),
+            (LINK_HEADER, format!("<{}{}>; rel=\"successor-version\"", ApiVersion::LEGACY.prefix(), path)),
+        ],
+        (None, _) => vec![],
+    }
+}
+
+/// Middleware setting the version headers, see `version_headers`
+pub struct ApiVersionHeaders;
+
+impl<S> Middleware<S> for ApiVersionHeaders {
+    fn response(&self, req: &HttpRequest<S>, mut resp: HttpResponse) -> ActixResult<Response> {
+        for (name, value) in version_headers(req.path()) {
+            if let Ok(value) = HeaderValue::from_str(&value) {
+                resp.headers_mut().insert(name, value);
+            }
+        }
+        Ok(Response::Done(resp))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_unversioned_path() {
+        assert_eq!(unversioned_path("/api/v1/manage/getTable"), (Some(ApiVersion::V1), "/manage/getTable"));
+        assert_eq!(unversioned_path("/manage/getTable"), (None, "/manage/getTable"));
+        assert_eq!(unversioned_path("/api/v9/manage/getTable"), (None, "/api/v9/manage/getTable"));
+        assert_eq!(unversioned_path("/api/v1"), (Some(ApiVersion::V1), ""));
+    }
+
+    #[test]
+    fn test_version_headers() {
+        assert_eq!(version_headers("/api/v1/manage/getTable"), vec![(API_VERSION_HEADER, "v1".to_string())]);
+        assert_eq!(version_headers("/manage/getTable"), vec![
+            (API_VERSION_HEADER, "v1".to_string()),
+            (DEPRECATION_HEADER, "true".to_string()),
+            (LINK_HEADER, "</api/v1/manage/getTable>; rel=\"successor-version\"".to_string()),
+        ]);
+        assert!(version_headers("/healthz").is_empty());
+        assert!(version_headers("/index.html").is_empty());
+    }
+}